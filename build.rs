@@ -0,0 +1,10 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The gRPC server and client of the `grpc` feature, generated with `protoc`.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/dumpsys.proto");
+        tonic_build::compile_protos("proto/dumpsys.proto").expect("compile proto/dumpsys.proto");
+    }
+}
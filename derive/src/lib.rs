@@ -0,0 +1,150 @@
+//! `#[derive(DumpParse)]` for `dumpsys-rs`, enabled through its `derive` feature
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, punctuated::Punctuated, Data, DeriveInput, Error, Fields, LitStr, Result,
+    Token, Type,
+};
+
+/// Where a field is found in the dump text
+enum Source {
+    Key(LitStr),
+    Regex(LitStr),
+}
+
+/// Implement `dumpsys_rs::DumpParse` for a struct with named fields.
+///
+/// The struct takes `#[dump(service = "...", args = ["..."])]`, each field `#[dump(key = "...")]` or
+/// `#[dump(regex = "...")]` and defaults to its own name as key.
+#[proc_macro_derive(DumpParse, attributes(dump))]
+pub fn derive_dump_parse(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let mut service = None;
+    let mut args = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("dump"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("service") {
+                service = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("args") {
+                let value = meta.value()?;
+                let content;
+                syn::bracketed!(content in value);
+                args = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+                    .into_iter()
+                    .collect();
+                Ok(())
+            } else {
+                Err(meta.error("expected `service` or `args`"))
+            }
+        })?;
+    }
+    let service = service
+        .ok_or_else(|| Error::new_spanned(&input.ident, "missing #[dump(service = \"...\")]"))?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "DumpParse can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &data.fields,
+            "DumpParse needs named fields",
+        ));
+    };
+
+    let mut inits = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let mut source = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("dump"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("key") {
+                    source = Some(Source::Key(meta.value()?.parse()?));
+                    Ok(())
+                } else if meta.path.is_ident("regex") {
+                    source = Some(Source::Regex(meta.value()?.parse()?));
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `key` or `regex`"))
+                }
+            })?;
+        }
+
+        let name = LitStr::new(&ident.to_string(), ident.span());
+        let optional = is_option(&field.ty);
+        let init = match source.unwrap_or_else(|| Source::Key(name.clone())) {
+            Source::Key(key) => {
+                let get = if optional {
+                    quote!(key_opt)
+                } else {
+                    quote!(key)
+                };
+                quote!(::dumpsys_rs::__private::#get(&fields, #key)?)
+            }
+            Source::Regex(regex) => {
+                let get = if optional {
+                    quote!(regex_opt)
+                } else {
+                    quote!(regex)
+                };
+                quote!({
+                    static REGEX: ::dumpsys_rs::__private::LazyRegex =
+                        ::dumpsys_rs::__private::LazyRegex::new(#regex);
+                    ::dumpsys_rs::__private::#get(text, &REGEX, #name)?
+                })
+            }
+        };
+        inits.push(quote!(#ident: #init));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::dumpsys_rs::DumpParse for #ident #ty_generics #where_clause {
+            const SERVICE: &'static str = #service;
+            const ARGS: &'static [&'static str] = &[#(#args),*];
+
+            fn parse(
+                text: &str,
+            ) -> ::core::result::Result<Self, ::dumpsys_rs::error::ParseError> {
+                #[allow(unused_variables)]
+                let fields = ::dumpsys_rs::parse::kv::KeyValues::parse(text);
+                ::core::result::Result::Ok(Self {
+                    #(#inits),*
+                })
+            }
+        }
+    })
+}
+
+/// Whether `ty` is spelled `Option<...>`, whose field may be missing from the output.
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option")
+}
@@ -0,0 +1,76 @@
+/*
+ * C ABI of dumpsys-rs, built with `cargo rustc --release --features ffi --crate-type cdylib`.
+ *
+ * Functions returning int return DUMPSYS_OK or a negative DUMPSYS_ERR_* code, and
+ * dumpsys_last_error() describes the last failure on the calling thread. Strings passed in must be
+ * NUL-terminated UTF-8. Output is allocated with malloc, NUL-terminated, and released with
+ * dumpsys_string_free() or free(). A handle may be used from several threads at once.
+ */
+#ifndef DUMPSYS_H
+#define DUMPSYS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DUMPSYS_OK 0
+/* A null pointer, or a string that isn't UTF-8 */
+#define DUMPSYS_ERR_INVALID_ARGUMENT (-1)
+#define DUMPSYS_ERR_NOT_FOUND (-2)
+#define DUMPSYS_ERR_PERMISSION_DENIED (-3)
+#define DUMPSYS_ERR_DEAD_OBJECT (-4)
+/* The dump transaction failed with another binder status */
+#define DUMPSYS_ERR_STATUS (-5)
+#define DUMPSYS_ERR_IO (-6)
+/* The output read until the timeout is still returned */
+#define DUMPSYS_ERR_TIMEOUT (-7)
+/* The output read until the limit is still returned */
+#define DUMPSYS_ERR_TRUNCATED (-8)
+#define DUMPSYS_ERR_CANCELLED (-9)
+#define DUMPSYS_ERR_PARSE (-10)
+/* A bug in dumpsys-rs, caught before unwinding into C */
+#define DUMPSYS_ERR_PANIC (-11)
+/* malloc failed */
+#define DUMPSYS_ERR_NO_MEMORY (-12)
+/* The service stopped mid-dump, the output written until then is still returned */
+#define DUMPSYS_ERR_INCOMPLETE (-13)
+
+typedef struct DumpsysHandle dumpsys_t;
+
+/* Look service up, waiting up to connect_timeout_ms for it to appear. Returns NULL if it doesn't. */
+dumpsys_t *dumpsys_new(const char *service, uint32_t connect_timeout_ms);
+
+/*
+ * Dump the service of handle with nargs arguments, giving up after timeout_ms unless it's 0.
+ *
+ * On success, and on DUMPSYS_ERR_IO, DUMPSYS_ERR_TIMEOUT, DUMPSYS_ERR_TRUNCATED,
+ * DUMPSYS_ERR_INCOMPLETE and DUMPSYS_ERR_CANCELLED with the output read until then, *output
+ * points to the output and *output_len holds its length, which excludes the final NUL. Otherwise
+ * *output is NULL and *output_len 0.
+ */
+int dumpsys_dump(const dumpsys_t *handle, const char *const *args, size_t nargs,
+                 uint32_t timeout_ms, char **output, size_t *output_len);
+
+/*
+ * The registered services, one per line, in *output as with dumpsys_dump(). On failure *output is
+ * NULL and *output_len 0.
+ */
+int dumpsys_list_services(char **output, size_t *output_len);
+
+/* Release a handle. NULL is ignored. */
+void dumpsys_free(dumpsys_t *handle);
+
+/* Release output returned by dumpsys_dump() or dumpsys_list_services(). NULL is ignored. */
+void dumpsys_string_free(char *output);
+
+/* The last failure on the calling thread, or NULL. Valid until the next call on the thread. */
+const char *dumpsys_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DUMPSYS_H */
@@ -0,0 +1,430 @@
+//! Transactions and parcels
+//!
+//! Parcels only travel within the process, so they use an encoding of their own rather than the one of
+//! libbinder: little-endian 32-bit lengths, UTF-8 strings padded to 4 bytes, and binders kept aside
+//! and referred to by index.
+
+use std::{
+    cell::{Cell, RefCell},
+    ffi::{CStr, CString},
+    fs::File,
+    io::Write,
+    mem::ManuallyDrop,
+    os::fd::{AsRawFd, FromRawFd},
+};
+
+use crate::{manager, ExceptionCode, IBinder, Node, Object, Result, SpIBinder, Status, StatusCode};
+
+pub type TransactionCode = u32;
+pub type TransactionFlags = u32;
+
+pub const FIRST_CALL_TRANSACTION: TransactionCode = 0x0000_0001;
+pub const LAST_CALL_TRANSACTION: TransactionCode = 0x00ff_ffff;
+pub const FLAG_ONEWAY: TransactionFlags = 0x01;
+
+/// The lower level calls of a binder
+pub trait IBinderInternal: IBinder {
+    fn is_binder_alive(&self) -> bool;
+
+    /// Write the dump of the service into `fp`, returning once it's complete.
+    fn dump<F: AsRawFd>(&mut self, fp: &F, args: &[&str]) -> Result<()>;
+
+    fn get_extension(&mut self) -> Result<Option<SpIBinder>>;
+
+    fn prepare_transact(&self) -> Result<Parcel>;
+
+    fn submit_transact(
+        &self,
+        code: TransactionCode,
+        data: Parcel,
+        flags: TransactionFlags,
+    ) -> Result<Parcel>;
+
+    fn get_class(&mut self) -> Option<InterfaceClass>;
+}
+
+impl IBinderInternal for SpIBinder {
+    fn is_binder_alive(&self) -> bool {
+        self.node().is_alive()
+    }
+
+    fn dump<F: AsRawFd>(&mut self, fp: &F, args: &[&str]) -> Result<()> {
+        if !self.is_binder_alive() {
+            return Err(StatusCode::DEAD_OBJECT);
+        }
+        // SAFETY: `fp` stays open for the call, and `ManuallyDrop` leaves closing it to its owner.
+        let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fp.as_raw_fd()) });
+        match &self.node().object {
+            Object::Service(service) => {
+                let output = service.respond(args)?;
+                // Like real services, ignore a reader that went away.
+                let _ = file.write_all(&output);
+                Ok(())
+            }
+            Object::Manager => Ok(()),
+            Object::Native { native, .. } => {
+                let args = args
+                    .iter()
+                    .map(|&arg| CString::new(arg).map_err(|_| StatusCode::BAD_VALUE))
+                    .collect::<Result<Vec<_>>>()?;
+                let args: Vec<&CStr> = args.iter().map(CString::as_c_str).collect();
+                native.on_dump(&mut *file, &args)
+            }
+        }
+    }
+
+    fn get_extension(&mut self) -> Result<Option<SpIBinder>> {
+        Ok(None)
+    }
+
+    fn prepare_transact(&self) -> Result<Parcel> {
+        Ok(Parcel::new())
+    }
+
+    fn submit_transact(
+        &self,
+        code: TransactionCode,
+        data: Parcel,
+        _flags: TransactionFlags,
+    ) -> Result<Parcel> {
+        if !self.is_binder_alive() {
+            return Err(StatusCode::DEAD_OBJECT);
+        }
+        data.pos.set(0);
+        let mut reply = Parcel::new();
+        match &self.node().object {
+            Object::Service(_) => return Err(StatusCode::UNKNOWN_TRANSACTION),
+            Object::Manager => manager::transact(code, &data, &mut reply)?,
+            Object::Native { native, .. } => {
+                native.on_transact(code, &BorrowedParcel(&data), &mut reply.borrowed())?
+            }
+        }
+        reply.pos.set(0);
+        Ok(reply)
+    }
+
+    fn get_class(&mut self) -> Option<InterfaceClass> {
+        self.node()
+            .class()
+            .map(|descriptor| InterfaceClass(descriptor.to_owned()))
+    }
+}
+
+/// The interface a binder was checked against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceClass(String);
+
+impl InterfaceClass {
+    pub fn get_descriptor(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// The native side of an interface, implemented by [`declare_binder_interface`](crate::declare_binder_interface)
+pub trait Remotable: Send + Sync + 'static {
+    fn get_descriptor() -> &'static str
+    where
+        Self: Sized;
+
+    fn on_transact(
+        &self,
+        code: TransactionCode,
+        data: &BorrowedParcel<'_>,
+        reply: &mut BorrowedParcel<'_>,
+    ) -> Result<()>;
+
+    fn on_dump(&self, writer: &mut dyn Write, args: &[&CStr]) -> Result<()>;
+}
+
+#[doc(hidden)]
+pub fn __new_native<R: Remotable>(native: R) -> SpIBinder {
+    SpIBinder::new(Object::Native {
+        descriptor: R::get_descriptor(),
+        native: Box::new(native),
+    })
+}
+
+#[doc(hidden)]
+pub fn __associate(binder: &SpIBinder, descriptor: &str) -> Result<()> {
+    Node::associate(binder, descriptor)
+}
+
+/// Data of a transaction or its reply
+///
+/// Reads and writes start at the data position, which a sent parcel resets to the start.
+#[derive(Default)]
+pub struct Parcel {
+    data: RefCell<Vec<u8>>,
+    pos: Cell<usize>,
+    binders: RefCell<Vec<SpIBinder>>,
+}
+
+/// A parcel as passed to [`Serialize`], [`Deserialize`] and `on_transact`
+pub struct BorrowedParcel<'a>(&'a Parcel);
+
+impl Parcel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn borrowed(&mut self) -> BorrowedParcel<'_> {
+        BorrowedParcel(self)
+    }
+
+    pub fn write<S: Serialize + ?Sized>(&mut self, parcelable: &S) -> Result<()> {
+        parcelable.serialize(&mut self.borrowed())
+    }
+
+    pub fn read<D: Deserialize>(&self) -> Result<D> {
+        D::deserialize(&BorrowedParcel(self))
+    }
+
+    pub fn get_data_size(&self) -> i32 {
+        self.data.borrow().len() as i32
+    }
+
+    pub fn get_data_position(&self) -> i32 {
+        self.pos.get() as i32
+    }
+
+    /// Move the data position to `pos`.
+    ///
+    /// # Safety
+    ///
+    /// Safe in the mock, which only checks that `pos` lies within the data; unsafe in libbinder.
+    pub unsafe fn set_data_position(&self, pos: i32) -> Result<()> {
+        match usize::try_from(pos) {
+            Ok(pos) if pos <= self.data.borrow().len() => {
+                self.pos.set(pos);
+                Ok(())
+            }
+            _ => Err(StatusCode::BAD_VALUE),
+        }
+    }
+
+    fn write_bytes(&self, bytes: &[u8]) {
+        let mut data = self.data.borrow_mut();
+        let pos = self.pos.get();
+        let end = pos + bytes.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[pos..end].copy_from_slice(bytes);
+        self.pos.set(end);
+    }
+
+    fn read_bytes<const N: usize>(&self) -> Result<[u8; N]> {
+        let data = self.data.borrow();
+        let pos = self.pos.get();
+        let bytes = data.get(pos..pos + N).ok_or(StatusCode::NOT_ENOUGH_DATA)?;
+        self.pos.set(pos + N);
+        Ok(bytes.try_into().expect("N bytes"))
+    }
+
+    /// Write a parcelable of `write`, prefixed by its size as in AIDL.
+    pub(crate) fn write_sized(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        let start = self.pos.get();
+        self.write(&0i32)?;
+        write(self)?;
+        let end = self.pos.get();
+        self.pos.set(start);
+        self.write(&((end - start) as i32))?;
+        self.pos.set(end);
+        Ok(())
+    }
+}
+
+impl<'a> BorrowedParcel<'a> {
+    pub fn write<S: Serialize + ?Sized>(&mut self, parcelable: &S) -> Result<()> {
+        parcelable.serialize(self)
+    }
+
+    pub fn read<D: Deserialize>(&self) -> Result<D> {
+        D::deserialize(self)
+    }
+
+    pub fn get_data_size(&self) -> i32 {
+        self.0.get_data_size()
+    }
+
+    pub fn get_data_position(&self) -> i32 {
+        self.0.get_data_position()
+    }
+}
+
+pub trait Serialize {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()>;
+}
+
+pub trait Deserialize: Sized {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self>;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+                    parcel.0.write_bytes(&self.to_le_bytes());
+                    Ok(())
+                }
+            }
+
+            impl Deserialize for $ty {
+                fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+                    Ok(<$ty>::from_le_bytes(parcel.0.read_bytes()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive!(i32, u32, i64, u64, f32, f64);
+
+impl Serialize for bool {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        parcel.write(&i32::from(*self))
+    }
+}
+
+impl Deserialize for bool {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        Ok(parcel.read::<i32>()? != 0)
+    }
+}
+
+impl Serialize for str {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let len = i32::try_from(self.len()).map_err(|_| StatusCode::BAD_VALUE)?;
+        parcel.write(&len)?;
+        parcel.0.write_bytes(self.as_bytes());
+        parcel
+            .0
+            .write_bytes(&[0; 3][..self.len().wrapping_neg() % 4]);
+        Ok(())
+    }
+}
+
+impl Serialize for String {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        self.as_str().serialize(parcel)
+    }
+}
+
+impl Deserialize for String {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        let len =
+            usize::try_from(parcel.read::<i32>()?).map_err(|_| StatusCode::UNEXPECTED_NULL)?;
+        let data = parcel.0.data.borrow();
+        let start = parcel.0.pos.get();
+        let bytes = data
+            .get(start..start + len)
+            .ok_or(StatusCode::NOT_ENOUGH_DATA)?;
+        let string = String::from_utf8(bytes.to_vec()).map_err(|_| StatusCode::BAD_VALUE)?;
+        parcel
+            .0
+            .pos
+            .set((start + len).next_multiple_of(4).min(data.len()));
+        Ok(string)
+    }
+}
+
+impl<T: Serialize> Serialize for [T] {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let len = i32::try_from(self.len()).map_err(|_| StatusCode::BAD_VALUE)?;
+        parcel.write(&len)?;
+        self.iter().try_for_each(|item| parcel.write(item))
+    }
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        self.as_slice().serialize(parcel)
+    }
+}
+
+impl<T: Deserialize> Deserialize for Vec<T> {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        let len =
+            usize::try_from(parcel.read::<i32>()?).map_err(|_| StatusCode::UNEXPECTED_NULL)?;
+        (0..len).map(|_| parcel.read()).collect()
+    }
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        parcel.write(&self.is_some())?;
+        self.as_ref().map_or(Ok(()), |value| parcel.write(value))
+    }
+}
+
+impl<T: Deserialize> Deserialize for Option<T> {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        match parcel.read::<bool>()? {
+            true => parcel.read().map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for &T {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        (**self).serialize(parcel)
+    }
+}
+
+impl Serialize for SpIBinder {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        let index = {
+            let mut binders = parcel.0.binders.borrow_mut();
+            binders.push(self.clone());
+            binders.len() - 1
+        };
+        parcel.write(&(index as i32))
+    }
+}
+
+impl Deserialize for SpIBinder {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        let index = usize::try_from(parcel.read::<i32>()?).map_err(|_| StatusCode::BAD_VALUE)?;
+        parcel
+            .0
+            .binders
+            .borrow()
+            .get(index)
+            .cloned()
+            .ok_or(StatusCode::BAD_VALUE)
+    }
+}
+
+impl Serialize for Status {
+    fn serialize(&self, parcel: &mut BorrowedParcel<'_>) -> Result<()> {
+        parcel.write(&(self.exception as i32))?;
+        match self.exception {
+            ExceptionCode::NONE => Ok(()),
+            ExceptionCode::TRANSACTION_FAILED => Err(self.transaction_error),
+            _ => {
+                parcel.write(&self.description)?;
+                parcel.write(&self.service_specific_error)
+            }
+        }
+    }
+}
+
+impl Deserialize for Status {
+    fn deserialize(parcel: &BorrowedParcel<'_>) -> Result<Self> {
+        let exception = ExceptionCode::from_i32(parcel.read()?);
+        if exception == ExceptionCode::NONE {
+            return Ok(Status::ok());
+        }
+        let description: String = parcel.read()?;
+        Ok(Status {
+            service_specific_error: parcel.read()?,
+            description,
+            ..exception.into()
+        })
+    }
+}
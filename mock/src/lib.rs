@@ -0,0 +1,505 @@
+//! Off-device stand-in for `binder_ndk`, the binder crate of `dumpsys-rs`
+//!
+//! `dumpsys-rs` uses this crate instead of `binder_ndk` on every target but Android, so crates built on
+//! it compile and run their tests on Linux and macOS machines without libbinder. It covers the parts of
+//! the `binder` API that `dumpsys-rs` uses, backed by an in-process servicemanager whose services answer
+//! dumps with the canned output registered through the [`mock`] module.
+
+pub mod binder_impl;
+mod manager;
+pub mod mock;
+
+use std::{
+    ffi::CStr,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+
+use binder_impl::Remotable;
+use mock::MockService;
+
+pub type Result<T> = std::result::Result<T, StatusCode>;
+
+/// `status_t` of libbinder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum StatusCode {
+    OK = 0,
+    NO_MEMORY = -12,
+    INVALID_OPERATION = -38,
+    BAD_VALUE = -22,
+    BAD_TYPE = i32::MIN + 1,
+    NAME_NOT_FOUND = -2,
+    PERMISSION_DENIED = -1,
+    NO_INIT = -19,
+    ALREADY_EXISTS = -17,
+    DEAD_OBJECT = -32,
+    FAILED_TRANSACTION = i32::MIN + 2,
+    BAD_INDEX = -75,
+    NOT_ENOUGH_DATA = -61,
+    WOULD_BLOCK = -11,
+    TIMED_OUT = -110,
+    UNKNOWN_TRANSACTION = -74,
+    FDS_NOT_ALLOWED = i32::MIN + 7,
+    UNEXPECTED_NULL = i32::MIN + 8,
+    UNKNOWN_ERROR = i32::MIN,
+}
+
+/// Exception codes of AIDL replies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum ExceptionCode {
+    NONE = 0,
+    SECURITY = -1,
+    BAD_PARCELABLE = -2,
+    ILLEGAL_ARGUMENT = -3,
+    NULL_POINTER = -4,
+    ILLEGAL_STATE = -5,
+    NETWORK_MAIN_THREAD = -6,
+    UNSUPPORTED_OPERATION = -7,
+    SERVICE_SPECIFIC = -8,
+    PARCELABLE = -9,
+    TRANSACTION_FAILED = -129,
+    JUST_PLAIN_BROKEN = -256,
+}
+
+impl ExceptionCode {
+    fn from_i32(code: i32) -> Self {
+        [
+            Self::NONE,
+            Self::SECURITY,
+            Self::BAD_PARCELABLE,
+            Self::ILLEGAL_ARGUMENT,
+            Self::NULL_POINTER,
+            Self::ILLEGAL_STATE,
+            Self::NETWORK_MAIN_THREAD,
+            Self::UNSUPPORTED_OPERATION,
+            Self::SERVICE_SPECIFIC,
+            Self::PARCELABLE,
+            Self::TRANSACTION_FAILED,
+        ]
+        .into_iter()
+        .find(|exception| *exception as i32 == code)
+        .unwrap_or(Self::JUST_PLAIN_BROKEN)
+    }
+}
+
+/// The status header of an AIDL reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    exception: ExceptionCode,
+    transaction_error: StatusCode,
+    service_specific_error: i32,
+    description: String,
+}
+
+impl Status {
+    pub fn ok() -> Self {
+        ExceptionCode::NONE.into()
+    }
+
+    pub fn new_exception(exception: ExceptionCode, message: Option<&CStr>) -> Self {
+        Self {
+            description: message.map_or_else(String::new, |message| {
+                message.to_string_lossy().into_owned()
+            }),
+            ..exception.into()
+        }
+    }
+
+    pub fn new_service_specific_error(err: i32, message: Option<&CStr>) -> Self {
+        Self {
+            service_specific_error: err,
+            ..Self::new_exception(ExceptionCode::SERVICE_SPECIFIC, message)
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.exception == ExceptionCode::NONE
+    }
+
+    pub fn exception_code(&self) -> ExceptionCode {
+        self.exception
+    }
+
+    pub fn transaction_error(&self) -> StatusCode {
+        self.transaction_error
+    }
+
+    pub fn service_specific_error(&self) -> i32 {
+        self.service_specific_error
+    }
+
+    pub fn get_description(&self) -> String {
+        format!(
+            "Status({:?}, {:?}): '{}'",
+            self.exception, self.transaction_error, self.description
+        )
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.get_description())
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl From<ExceptionCode> for Status {
+    fn from(exception: ExceptionCode) -> Self {
+        Self {
+            exception,
+            transaction_error: StatusCode::OK,
+            service_specific_error: 0,
+            description: String::new(),
+        }
+    }
+}
+
+impl From<StatusCode> for Status {
+    fn from(status: StatusCode) -> Self {
+        match status {
+            StatusCode::OK => Self::ok(),
+            status => Self {
+                transaction_error: status,
+                ..ExceptionCode::TRANSACTION_FAILED.into()
+            },
+        }
+    }
+}
+
+/// A strong reference to a binder object
+#[derive(Clone)]
+pub struct SpIBinder(Arc<Node>);
+
+/// What a [`SpIBinder`] points to
+pub(crate) enum Object {
+    /// A service registered through [`mock::add_service`]
+    Service(MockService),
+    /// servicemanager itself
+    Manager,
+    /// An object of this process created with `new_binder`, see [`declare_binder_interface`]
+    Native {
+        descriptor: &'static str,
+        native: Box<dyn Remotable>,
+    },
+}
+
+pub(crate) struct Node {
+    pub(crate) object: Object,
+    alive: AtomicBool,
+    /// Descriptor of the interface the binder was checked against
+    class: OnceLock<String>,
+    recipients: Mutex<Vec<Arc<dyn Fn() + Send + Sync>>>,
+}
+
+impl SpIBinder {
+    pub(crate) fn new(object: Object) -> Self {
+        Self(Arc::new(Node {
+            object,
+            alive: AtomicBool::new(true),
+            class: OnceLock::new(),
+            recipients: Mutex::new(Vec::new()),
+        }))
+    }
+
+    pub(crate) fn node(&self) -> &Node {
+        &self.0
+    }
+
+    /// Mark the object dead and call its death recipients on a thread of their own, like a binder thread.
+    pub(crate) fn kill(&self) {
+        if !self.0.alive.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let recipients = std::mem::take(&mut *self.0.recipients.lock().unwrap());
+        thread::spawn(move || recipients.iter().for_each(|recipient| recipient()));
+    }
+
+    /// Descriptor of the interface the object implements, if it declares one.
+    fn descriptor(&self) -> Option<&str> {
+        match &self.0.object {
+            Object::Service(service) => service.descriptor(),
+            Object::Manager => Some(manager::DESCRIPTOR),
+            Object::Native { descriptor, .. } => Some(descriptor),
+        }
+    }
+
+    pub fn into_interface<I: FromIBinder + Interface + ?Sized>(self) -> Result<Strong<I>> {
+        I::try_from(self)
+    }
+}
+
+impl fmt::Debug for SpIBinder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let object = match &self.0.object {
+            Object::Service(service) => format!("{service:?}"),
+            Object::Manager => "servicemanager".to_owned(),
+            Object::Native { descriptor, .. } => (*descriptor).to_owned(),
+        };
+        f.debug_tuple("SpIBinder").field(&object).finish()
+    }
+}
+
+impl PartialEq for SpIBinder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SpIBinder {}
+
+/// Callback for [`IBinder::link_to_death`]
+#[derive(Clone)]
+pub struct DeathRecipient(Arc<dyn Fn() + Send + Sync>);
+
+impl DeathRecipient {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+}
+
+pub trait IBinder {
+    fn link_to_death(&mut self, recipient: &mut DeathRecipient) -> Result<()>;
+
+    fn unlink_to_death(&mut self, recipient: &mut DeathRecipient) -> Result<()>;
+
+    fn ping_binder(&mut self) -> Result<()>;
+}
+
+impl IBinder for SpIBinder {
+    fn link_to_death(&mut self, recipient: &mut DeathRecipient) -> Result<()> {
+        let mut recipients = self.0.recipients.lock().unwrap();
+        // Checked under the lock, so `kill` either sees the recipient or fails the link.
+        if !self.0.alive.load(Ordering::SeqCst) {
+            return Err(StatusCode::DEAD_OBJECT);
+        }
+        recipients.push(recipient.0.clone());
+        Ok(())
+    }
+
+    fn unlink_to_death(&mut self, recipient: &mut DeathRecipient) -> Result<()> {
+        let mut recipients = self.0.recipients.lock().unwrap();
+        let len = recipients.len();
+        recipients.retain(|linked| !Arc::ptr_eq(linked, &recipient.0));
+        if recipients.len() == len {
+            return Err(StatusCode::NAME_NOT_FOUND);
+        }
+        Ok(())
+    }
+
+    fn ping_binder(&mut self) -> Result<()> {
+        match self.0.alive.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => Err(StatusCode::DEAD_OBJECT),
+        }
+    }
+}
+
+/// Look a service up without waiting for it, `None` unless it was registered with [`mock::add_service`]
+/// or [`add_service`].
+///
+/// `manager` is servicemanager, which lists the registered services and notifies about new ones.
+pub fn check_service(name: &str) -> Option<SpIBinder> {
+    match name {
+        manager::NAME => Some(manager::binder()),
+        name => mock::service(name),
+    }
+}
+
+/// Register a binder object of this process as `identifier`, like a service written in Rust does, so
+/// dumps reach its [`Interface::dump`].
+pub fn add_service(identifier: &str, binder: SpIBinder) -> Result<()> {
+    if matches!(binder.node().object, Object::Manager) {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    mock::register(identifier.to_owned(), binder);
+    Ok(())
+}
+
+/// Whether `name` was declared with [`mock::declare`].
+pub fn is_declared(name: &str) -> Result<bool> {
+    Ok(mock::is_declared(name))
+}
+
+/// Instances of `interface` declared with [`mock::declare`].
+pub fn get_declared_instances(interface: &str) -> Result<Vec<String>> {
+    Ok(mock::declared_instances(interface))
+}
+
+/// The binder thread pool, which the mock doesn't need
+pub struct ProcessState;
+
+impl ProcessState {
+    pub fn start_thread_pool() {}
+
+    pub fn set_thread_pool_max_thread_count(_num_threads: u32) {}
+
+    pub fn join_thread_pool() {}
+}
+
+/// Interface of a binder object, implemented by the types of [`declare_binder_interface`]
+pub trait Interface: Send + Sync {
+    fn as_binder(&self) -> SpIBinder {
+        panic!("this object wasn't created with `new_binder`")
+    }
+
+    /// Write the dump of the object, for `dumpsys` and [`IBinderInternal::dump`](binder_impl::IBinderInternal::dump).
+    fn dump(&self, _writer: &mut dyn std::io::Write, _args: &[&CStr]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Interfaces that can be looked up on a [`SpIBinder`]
+pub trait FromIBinder: Interface {
+    fn try_from(binder: SpIBinder) -> Result<Strong<Self>>;
+}
+
+/// A strong reference to an object implementing `I`
+pub struct Strong<I: FromIBinder + ?Sized> {
+    object: Arc<I>,
+    /// The native binder of an object created with `new_binder`
+    binder: Option<SpIBinder>,
+}
+
+impl<I: FromIBinder + ?Sized> Strong<I> {
+    pub fn new(object: Box<I>) -> Self {
+        Self {
+            object: object.into(),
+            binder: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn __native(object: Arc<I>, binder: SpIBinder) -> Self {
+        Self {
+            object,
+            binder: Some(binder),
+        }
+    }
+
+    /// The binder of the object, passed along in transactions.
+    pub fn as_binder(&self) -> SpIBinder {
+        self.binder
+            .clone()
+            .unwrap_or_else(|| self.object.as_binder())
+    }
+}
+
+impl<I: FromIBinder + ?Sized> Clone for Strong<I> {
+    fn clone(&self) -> Self {
+        Self {
+            object: self.object.clone(),
+            binder: self.binder.clone(),
+        }
+    }
+}
+
+impl<I: FromIBinder + ?Sized> std::ops::Deref for Strong<I> {
+    type Target = I;
+
+    fn deref(&self) -> &I {
+        &self.object
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BinderFeatures {
+    pub set_requesting_sid: bool,
+}
+
+/// Declare the native and proxy types of a binder interface.
+///
+/// `native::new_binder` serves an implementation of the interface through `on_transact`, and the proxy
+/// implements it for objects found with [`SpIBinder::into_interface`], holding their binder in `binder`.
+#[macro_export]
+macro_rules! declare_binder_interface {
+    {
+        $interface:path[$descriptor:expr] {
+            native: $native:ident($on_transact:path),
+            proxy: $proxy:ident,
+        }
+    } => {
+        pub struct $proxy {
+            binder: $crate::SpIBinder,
+        }
+
+        pub struct $native(::std::sync::Arc<dyn $interface + Sync + Send + 'static>);
+
+        impl $native {
+            pub fn new_binder<T: $interface + Sync + Send + 'static>(
+                inner: T,
+                _features: $crate::BinderFeatures,
+            ) -> $crate::Strong<dyn $interface> {
+                let object: ::std::sync::Arc<dyn $interface> = ::std::sync::Arc::new(inner);
+                let binder = $crate::binder_impl::__new_native($native(object.clone()));
+                $crate::Strong::__native(object, binder)
+            }
+        }
+
+        impl $crate::binder_impl::Remotable for $native {
+            fn get_descriptor() -> &'static str {
+                $descriptor
+            }
+
+            fn on_transact(
+                &self,
+                code: $crate::binder_impl::TransactionCode,
+                data: &$crate::binder_impl::BorrowedParcel<'_>,
+                reply: &mut $crate::binder_impl::BorrowedParcel<'_>,
+            ) -> $crate::Result<()> {
+                $on_transact(&*self.0, code, data, reply)
+            }
+
+            fn on_dump(
+                &self,
+                writer: &mut dyn ::std::io::Write,
+                args: &[&::std::ffi::CStr],
+            ) -> $crate::Result<()> {
+                self.0.dump(writer, args)
+            }
+        }
+
+        impl $crate::Interface for $proxy {
+            fn as_binder(&self) -> $crate::SpIBinder {
+                self.binder.clone()
+            }
+        }
+
+        impl $crate::FromIBinder for dyn $interface {
+            fn try_from(binder: $crate::SpIBinder) -> $crate::Result<$crate::Strong<dyn $interface>> {
+                $crate::binder_impl::__associate(&binder, <$native as $crate::binder_impl::Remotable>::get_descriptor())?;
+                Ok($crate::Strong::new(Box::new($proxy { binder })))
+            }
+        }
+    };
+}
+
+impl Node {
+    pub(crate) fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn class(&self) -> Option<&str> {
+        self.class.get().map(String::as_str)
+    }
+
+    /// Check the object against `descriptor`, remembering it as the class of the binder.
+    pub(crate) fn associate(binder: &SpIBinder, descriptor: &str) -> Result<()> {
+        if binder.descriptor() != Some(descriptor) {
+            return Err(StatusCode::BAD_TYPE);
+        }
+        let _ = binder.0.class.set(descriptor.to_owned());
+        Ok(())
+    }
+}
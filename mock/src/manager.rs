@@ -0,0 +1,74 @@
+//! servicemanager, answering the `IServiceManager` transactions that `dumpsys-rs` sends
+
+use std::{sync::OnceLock, thread};
+
+use crate::{
+    binder_impl::{IBinderInternal, Parcel, TransactionCode, FIRST_CALL_TRANSACTION, FLAG_ONEWAY},
+    mock, Object, Result, SpIBinder, Status, StatusCode,
+};
+
+/// Name servicemanager registers itself under
+pub(crate) const NAME: &str = "manager";
+pub(crate) const DESCRIPTOR: &str = "android.os.IServiceManager";
+
+// Declaration order in `IServiceManager.aidl` and `IServiceCallback.aidl` of Android 14 and 15.
+const LIST_SERVICES: TransactionCode = FIRST_CALL_TRANSACTION + 3;
+const REGISTER_FOR_NOTIFICATIONS: TransactionCode = FIRST_CALL_TRANSACTION + 4;
+const UNREGISTER_FOR_NOTIFICATIONS: TransactionCode = FIRST_CALL_TRANSACTION + 5;
+const GET_SERVICE_DEBUG_INFO: TransactionCode = FIRST_CALL_TRANSACTION + 13;
+const ON_REGISTRATION: TransactionCode = FIRST_CALL_TRANSACTION;
+
+pub(crate) fn binder() -> SpIBinder {
+    static MANAGER: OnceLock<SpIBinder> = OnceLock::new();
+    MANAGER
+        .get_or_init(|| SpIBinder::new(Object::Manager))
+        .clone()
+}
+
+pub(crate) fn transact(code: TransactionCode, data: &Parcel, reply: &mut Parcel) -> Result<()> {
+    match code {
+        LIST_SERVICES => {
+            let dump_priority: i32 = data.read()?;
+            reply.write(&Status::ok())?;
+            reply.write(&mock::service_names(dump_priority))
+        }
+        REGISTER_FOR_NOTIFICATIONS => {
+            mock::watch(data.read()?, data.read()?);
+            reply.write(&Status::ok())
+        }
+        UNREGISTER_FOR_NOTIFICATIONS => {
+            let name: String = data.read()?;
+            mock::unwatch(&name, &data.read()?);
+            reply.write(&Status::ok())
+        }
+        GET_SERVICE_DEBUG_INFO => {
+            let infos = mock::debug_infos();
+            reply.write(&Status::ok())?;
+            reply.write(&(infos.len() as i32))?;
+            for (name, pid) in infos {
+                // Non-null parcelable.
+                reply.write(&1i32)?;
+                reply.write_sized(|reply| {
+                    reply.write(&name)?;
+                    reply.write(&pid)
+                })?;
+            }
+            Ok(())
+        }
+        _ => Err(StatusCode::UNKNOWN_TRANSACTION),
+    }
+}
+
+/// Tell `callback` that `service` registered as `name`, on a thread of its own like a binder thread.
+pub(crate) fn notify(callback: SpIBinder, name: String, service: SpIBinder) {
+    thread::spawn(move || {
+        let mut data = Parcel::new();
+        if data
+            .write(&name)
+            .and_then(|()| data.write(&service))
+            .is_ok()
+        {
+            let _ = callback.submit_transact(ON_REGISTRATION, data, FLAG_ONEWAY);
+        }
+    });
+}
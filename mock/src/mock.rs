@@ -0,0 +1,265 @@
+//! Services of the in-process servicemanager and their canned dumps
+//!
+//! The registry is global to the process, so tests running in parallel should register services under
+//! names of their own.
+//!
+//! # Example
+//!
+//! ```
+//! use binder::{
+//!     mock::{self, MockService},
+//!     StatusCode,
+//! };
+//!
+//! mock::add_service(
+//!     "SurfaceFlinger",
+//!     MockService::new("Display 0 HWC layers:\n").with_args(&["--latency"], "16666666\n"),
+//! );
+//! mock::add_service("battery", MockService::failing(StatusCode::PERMISSION_DENIED));
+//! assert!(binder::check_service("SurfaceFlinger").is_some());
+//!
+//! mock::kill_service("SurfaceFlinger");
+//! assert!(binder::check_service("SurfaceFlinger").is_none());
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{manager, Object, SpIBinder, StatusCode};
+
+/// `IServiceManager.DUMP_FLAG_PRIORITY_CRITICAL`
+pub const DUMP_FLAG_PRIORITY_CRITICAL: i32 = 1 << 0;
+/// `IServiceManager.DUMP_FLAG_PRIORITY_HIGH`
+pub const DUMP_FLAG_PRIORITY_HIGH: i32 = 1 << 1;
+/// `IServiceManager.DUMP_FLAG_PRIORITY_NORMAL`
+pub const DUMP_FLAG_PRIORITY_NORMAL: i32 = 1 << 2;
+/// `IServiceManager.DUMP_FLAG_PRIORITY_DEFAULT`, the priority of services added without one
+pub const DUMP_FLAG_PRIORITY_DEFAULT: i32 = 1 << 3;
+/// `IServiceManager.DUMP_FLAG_PROTO`, for services that dump as protobuf with `--proto`
+pub const DUMP_FLAG_PROTO: i32 = 1 << 4;
+
+static SERVICES: Mutex<BTreeMap<String, SpIBinder>> = Mutex::new(BTreeMap::new());
+/// Callbacks registered for notifications, with the name they watch
+static WATCHERS: Mutex<Vec<(String, SpIBinder)>> = Mutex::new(Vec::new());
+/// Instances declared with [`declare`]
+static DECLARED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+type Respond = dyn Fn(&[&str]) -> Result<Vec<u8>, StatusCode> + Send + Sync;
+
+/// How a service answers dumps
+#[derive(Clone)]
+pub struct MockService {
+    respond: Arc<Respond>,
+    /// Output for exact argument lists, ahead of `respond`
+    outputs: Vec<(Vec<String>, Arc<[u8]>)>,
+    delay: Duration,
+    dump_priority: i32,
+    descriptor: Option<String>,
+    pid: i32,
+}
+
+impl MockService {
+    /// Dump `output` whatever the arguments.
+    pub fn new(output: impl Into<Vec<u8>>) -> Self {
+        let output: Arc<[u8]> = output.into().into();
+        Self::from_fn(move |_| Ok(output.to_vec()))
+    }
+
+    /// Fail every dump with `status`, e.g. [`StatusCode::PERMISSION_DENIED`] like a service that
+    /// checks the `DUMP` permission.
+    pub fn failing(status: StatusCode) -> Self {
+        Self::from_fn(move |_| Err(status))
+    }
+
+    /// Dump what `respond` returns for the arguments, or fail with its status.
+    pub fn from_fn<F>(respond: F) -> Self
+    where
+        F: Fn(&[&str]) -> Result<Vec<u8>, StatusCode> + Send + Sync + 'static,
+    {
+        Self {
+            respond: Arc::new(respond),
+            outputs: Vec::new(),
+            delay: Duration::ZERO,
+            dump_priority: DUMP_FLAG_PRIORITY_DEFAULT,
+            descriptor: None,
+            pid: std::process::id() as i32,
+        }
+    }
+
+    /// Dump `output` instead when the arguments are exactly `args`.
+    pub fn with_args<S: AsRef<str>>(mut self, args: &[S], output: impl Into<Vec<u8>>) -> Self {
+        let args = args.iter().map(|arg| arg.as_ref().to_owned()).collect();
+        self.outputs.push((args, output.into().into()));
+        self
+    }
+
+    /// Wait for `delay` before every dump, to exercise timeouts.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// List the service for the `DUMP_FLAG_*` in `flags`, [`DUMP_FLAG_PRIORITY_DEFAULT`] by default.
+    pub fn dump_priority(mut self, flags: i32) -> Self {
+        self.dump_priority = flags;
+        self
+    }
+
+    /// Implement the AIDL interface `descriptor`, e.g. `android.ui.ISurfaceComposer`, for interface
+    /// checks. Services implement none by default.
+    pub fn interface(mut self, descriptor: impl Into<String>) -> Self {
+        self.descriptor = Some(descriptor.into());
+        self
+    }
+
+    /// Report `pid` as the process hosting the service, the id of this process by default.
+    pub fn pid(mut self, pid: i32) -> Self {
+        self.pid = pid;
+        self
+    }
+
+    pub(crate) fn descriptor(&self) -> Option<&str> {
+        self.descriptor.as_deref()
+    }
+
+    /// The output of a dump with `args`.
+    pub(crate) fn respond(&self, args: &[&str]) -> Result<Vec<u8>, StatusCode> {
+        thread::sleep(self.delay);
+        match self.outputs.iter().find(|(expected, _)| expected == args) {
+            Some((_, output)) => Ok(output.to_vec()),
+            None => (self.respond)(args),
+        }
+    }
+}
+
+impl fmt::Debug for MockService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockService")
+            .field("outputs", &self.outputs.len())
+            .field("delay", &self.delay)
+            .field("dump_priority", &self.dump_priority)
+            .field("descriptor", &self.descriptor)
+            .field("pid", &self.pid)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Register `service` as `name`, replacing a service of the same name, and notify callbacks watching
+/// for it.
+pub fn add_service(name: impl Into<String>, service: MockService) {
+    register(name.into(), SpIBinder::new(Object::Service(service)));
+}
+
+pub(crate) fn register(name: String, binder: SpIBinder) {
+    let mut services = SERVICES.lock().unwrap();
+    services.insert(name.clone(), binder.clone());
+
+    // Under the lock of the services, so `watch` can't miss the service or see it twice.
+    for (watched, callback) in WATCHERS.lock().unwrap().iter() {
+        if *watched == name {
+            manager::notify(callback.clone(), name.clone(), binder.clone());
+        }
+    }
+}
+
+/// Unregister `name`, leaving handles already looked up working. Returns whether it was registered.
+pub fn remove_service(name: &str) -> bool {
+    SERVICES.lock().unwrap().remove(name).is_some()
+}
+
+/// Unregister `name` and let its process die, failing dumps on its handles with
+/// [`StatusCode::DEAD_OBJECT`] and calling its death recipients. Returns whether it was registered.
+///
+/// Add the service again to simulate a restart.
+pub fn kill_service(name: &str) -> bool {
+    let service = SERVICES.lock().unwrap().remove(name);
+    let registered = service.is_some();
+    if let Some(service) = service {
+        service.kill();
+    }
+    registered
+}
+
+/// Declare `name`, e.g. `android.hardware.thermal.IThermal/default`, as if it was in the VINTF manifest.
+pub fn declare(name: impl Into<String>) {
+    DECLARED.lock().unwrap().insert(name.into());
+}
+
+/// Kill every service, as with [`kill_service`], and forget every callback and declaration.
+pub fn reset() {
+    let services = std::mem::take(&mut *SERVICES.lock().unwrap());
+    services.values().for_each(SpIBinder::kill);
+    WATCHERS.lock().unwrap().clear();
+    DECLARED.lock().unwrap().clear();
+}
+
+pub(crate) fn service(name: &str) -> Option<SpIBinder> {
+    SERVICES.lock().unwrap().get(name).cloned()
+}
+
+/// Names of the services listed for `dump_priority`, sorted. Objects registered with
+/// [`add_service`](crate::add_service) have the default priority.
+pub(crate) fn service_names(dump_priority: i32) -> Vec<String> {
+    SERVICES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, binder)| {
+            let flags = match &binder.node().object {
+                Object::Service(service) => service.dump_priority,
+                _ => DUMP_FLAG_PRIORITY_DEFAULT,
+            };
+            flags & dump_priority != 0
+        })
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Name and pid of every service.
+pub(crate) fn debug_infos() -> Vec<(String, i32)> {
+    SERVICES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, binder)| match &binder.node().object {
+            Object::Service(service) => (name.clone(), service.pid),
+            _ => (name.clone(), std::process::id() as i32),
+        })
+        .collect()
+}
+
+/// Call `callback` whenever `name` registers, starting now if it already is, like servicemanager.
+pub(crate) fn watch(name: String, callback: SpIBinder) {
+    let services = SERVICES.lock().unwrap();
+    if let Some(service) = services.get(&name) {
+        manager::notify(callback.clone(), name.clone(), service.clone());
+    }
+    WATCHERS.lock().unwrap().push((name, callback));
+}
+
+pub(crate) fn unwatch(name: &str, callback: &SpIBinder) {
+    WATCHERS
+        .lock()
+        .unwrap()
+        .retain(|(watched, watcher)| !(watched == name && watcher == callback));
+}
+
+pub(crate) fn is_declared(name: &str) -> bool {
+    DECLARED.lock().unwrap().contains(name)
+}
+
+/// Instance names declared for `interface`.
+pub(crate) fn declared_instances(interface: &str) -> Vec<String> {
+    DECLARED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|name| name.strip_prefix(interface)?.strip_prefix('/'))
+        .map(str::to_owned)
+        .collect()
+}
@@ -0,0 +1,507 @@
+//! Typed output of `dumpsys activity activities` and `dumpsys activity processes`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::activity::{Activities, Processes};
+//!
+//! let activities = Activities::parse(
+//!     "ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+//! Display #0 (activities from top to bottom):
+//!   * Task{3b2a1 #123 type=standard A=10123:com.example.app U=0 visible=true mode=fullscreen translucent=false sz=2}
+//!     mLastPausedActivity: ActivityRecord{aa11 u0 com.example.app/.SplashActivity t123}
+//!     * Hist  #1: ActivityRecord{d4e5f6 u0 com.example.app/.MainActivity t123}
+//!     * Hist  #0: ActivityRecord{aa11 u0 com.example.app/.SplashActivity t123}
+//!   * Task{c0ff #1 type=home U=0 visible=false mode=fullscreen translucent=false sz=1}
+//!     * Hist  #0: ActivityRecord{bb22 u0 com.android.launcher/.Launcher t1}
+//!
+//!   ResumedActivity: ActivityRecord{d4e5f6 u0 com.example.app/.MainActivity t123}",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(activities.resumed.as_ref().unwrap().activity, ".MainActivity");
+//! assert_eq!(activities.tasks.len(), 2);
+//! assert_eq!(activities.tasks[0].id, 123);
+//! assert_eq!(activities.tasks[0].affinity.as_deref(), Some("com.example.app"));
+//! assert_eq!(activities.tasks[0].activities.len(), 2);
+//! assert_eq!(activities.tasks[1].activity_type.as_deref(), Some("home"));
+//!
+//! let processes = Processes::parse(
+//!     "ACTIVITY MANAGER RUNNING PROCESSES (dumpsys activity processes)
+//!   Process LRU list (sorted by oom_adj, 3 total, non-act at 1, non-svc at 1):
+//!     Proc # 2: fg     T/A/TOP  LCM  t: 0 1234:com.example.app/u0a123 (top-activity)
+//!     PERS # 1: sys    F/ /PER  LCM  t: 0 1000:system/1000 (fixed)
+//!     Proc # 0: cch+75 B/ /CEM  ---  t: 0 5678:com.example.cached/u0a99 (cch-empty)",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(processes.lru[0].pid, 1234);
+//! assert_eq!(processes.lru[0].proc_state, "TOP");
+//! assert_eq!(processes.lru[0].uid, Some(10123));
+//! assert!(processes.lru[1].persistent);
+//! assert_eq!(processes.lru[1].sched_group, "F");
+//! assert!(processes.lru[2].is_cached());
+//! assert_eq!(processes.process("com.example.cached").unwrap().reason.as_deref(), Some("cch-empty"));
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::{
+        kv::{invalid, KeyValues},
+        parse_uid,
+    },
+    typed,
+    window::ActivityRecord,
+    DumpParse,
+};
+
+const ACTIVITY_RECORD: &str = "ActivityRecord{";
+const TASKS: [&str; 2] = ["* Task{", "* TaskRecord{"];
+const HISTORY: &str = "* Hist";
+/// Prefixes of the resumed activity, Android 12 and later first
+const RESUMED: [&str; 3] = [
+    "topResumedActivity=",
+    "ResumedActivity: ",
+    "mResumedActivity: ",
+];
+const LRU_LIST: &str = "Process LRU list";
+const LRU_ENTRIES: [&str; 2] = ["Proc #", "PERS #"];
+const CACHED_ADJ: &str = "cch";
+
+/// A task with its activities, a summary of `Task.toString`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Task {
+    pub id: u32,
+    /// Package the task belongs to, from its affinity
+    pub affinity: Option<String>,
+    pub user: Option<u32>,
+    /// Activity type such as `standard` or `home`, on Android 12 and later
+    pub activity_type: Option<String>,
+    /// Windowing mode such as `fullscreen` or `pinned`, on Android 12 and later
+    pub windowing_mode: Option<String>,
+    pub visible: Option<bool>,
+    /// Activities from top to bottom
+    pub activities: Vec<ActivityRecord>,
+}
+
+/// Output of `dumpsys activity activities`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Activities {
+    /// Activity resumed on the focused display
+    pub resumed: Option<ActivityRecord>,
+    /// Tasks of all displays from top to bottom
+    pub tasks: Vec<Task>,
+}
+
+impl Activities {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut activities = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(task) = TASKS.iter().find_map(|prefix| line.strip_prefix(prefix)) {
+                activities.tasks.push(task_of(task)?);
+                continue;
+            }
+            if line.starts_with(HISTORY) {
+                let activity = activity_record(line).ok_or_else(|| invalid("Hist", line))?;
+                if let Some(task) = activities.tasks.last_mut() {
+                    task.activities.push(activity);
+                }
+                continue;
+            }
+            if activities.resumed.is_none() && RESUMED.iter().any(|prefix| line.starts_with(prefix))
+            {
+                activities.resumed = activity_record(line);
+            }
+        }
+
+        Ok(activities)
+    }
+
+    /// The resumed activity, or the top activity of the top task while none is resumed.
+    pub fn top_activity(&self) -> Option<&ActivityRecord> {
+        self.resumed
+            .as_ref()
+            .or_else(|| self.tasks.iter().find_map(|task| task.activities.first()))
+    }
+
+    pub fn task(&self, id: u32) -> Option<&Task> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+}
+
+impl DumpParse for Activities {
+    const SERVICE: &'static str = "activity";
+    const ARGS: &'static [&'static str] = &["activities"];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// An entry of the LRU process list, which is sorted by oom adjustment
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LruProcess {
+    /// Position in the LRU list, 0 being the least recently used
+    pub index: u32,
+    /// Whether the process is persistent, listed as `PERS`
+    pub persistent: bool,
+    /// Oom adjustment label such as `fg`, `vis`, `prcp` or `cch+75`
+    pub adj: String,
+    /// Scheduling group such as `T` for top app, `F` for foreground or `B` for background
+    pub sched_group: String,
+    /// Process state such as `TOP`, `FGS` or `CEM`
+    pub proc_state: String,
+    pub pid: u32,
+    pub name: String,
+    pub uid: Option<u32>,
+    /// Why the process has its adjustment, such as `top-activity` or `fg-service`
+    pub reason: Option<String>,
+}
+
+impl LruProcess {
+    /// Whether the process is cached, the first to be killed under memory pressure.
+    pub fn is_cached(&self) -> bool {
+        self.adj.starts_with(CACHED_ADJ)
+    }
+}
+
+/// Output of `dumpsys activity processes`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Processes {
+    /// Processes in order of the dump, most important first
+    pub lru: Vec<LruProcess>,
+}
+
+impl Processes {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut processes = Self::default();
+        let mut in_lru = false;
+
+        for line in text.lines().map(str::trim) {
+            if line.starts_with(LRU_LIST) {
+                in_lru = true;
+                continue;
+            }
+            if !in_lru {
+                continue;
+            }
+            match LRU_ENTRIES
+                .iter()
+                .position(|prefix| line.starts_with(prefix))
+            {
+                Some(kind) => processes.lru.push(lru_process(line, kind == 1)?),
+                None if processes.lru.is_empty() => {}
+                // The list ends at the first line that isn't an entry.
+                None => break,
+            }
+        }
+
+        Ok(processes)
+    }
+
+    /// The process called `name`.
+    pub fn process(&self, name: &str) -> Option<&LruProcess> {
+        self.lru.iter().find(|process| process.name == name)
+    }
+
+    pub fn pid(&self, pid: u32) -> Option<&LruProcess> {
+        self.lru.iter().find(|process| process.pid == pid)
+    }
+}
+
+impl DumpParse for Processes {
+    const SERVICE: &'static str = "activity";
+    const ARGS: &'static [&'static str] = &["processes"];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `activity activities` and return the resumed activity.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// if let Some(activity) = dumpsys_rs::activity::resumed_activity().unwrap() {
+///     println!("{}", activity.class_name());
+/// }
+/// # }
+/// ```
+pub fn resumed_activity() -> Result<Option<ActivityRecord>, DumpError> {
+    let activities = typed::dump_service::<Activities>(Activities::ARGS)?;
+    Ok(activities.resumed)
+}
+
+/// The `ActivityRecord{...}` in `line`.
+fn activity_record(line: &str) -> Option<ActivityRecord> {
+    let start = line.find(ACTIVITY_RECORD)? + ACTIVITY_RECORD.len();
+    ActivityRecord::parse(line[start..].split('}').next()?)
+}
+
+/// `3b2a1 #123 type=standard A=10123:com.example.app U=0 visible=true mode=fullscreen sz=1}`
+fn task_of(task: &str) -> Result<Task, ParseError> {
+    let task = task.strip_suffix('}').unwrap_or(task);
+    let id = task
+        .split_whitespace()
+        .find_map(|word| word.strip_prefix('#'))
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| invalid("Task", task))?;
+    let fields = KeyValues::parse(task);
+    Ok(Task {
+        id,
+        // Android 12 and later prefix the affinity with the uid.
+        affinity: fields
+            .get("A")
+            .map(|affinity| {
+                affinity
+                    .split_once(':')
+                    .map_or(affinity, |(_, package)| package)
+            })
+            .map(str::to_owned),
+        user: fields.get_parsed("U").ok(),
+        activity_type: fields.get("type").map(str::to_owned),
+        windowing_mode: fields.get("mode").map(str::to_owned),
+        visible: fields.get_bool("visible").ok(),
+        activities: Vec::new(),
+    })
+}
+
+/// `Proc # 2: fg     T/A/TOP  LCM  t: 0 1234:com.example.app/u0a123 (top-activity)`
+fn lru_process(line: &str, persistent: bool) -> Result<LruProcess, ParseError> {
+    let (index, rest) = line
+        .split_once('#')
+        .and_then(|(_, rest)| rest.split_once(':'))
+        .ok_or_else(|| invalid("Proc", line))?;
+    let rest = rest.trim_start();
+    let (adj, rest) = rest.split_once(' ').ok_or_else(|| invalid("Proc", line))?;
+
+    // The flags before the `<pid>:<name>/<user>` word may contain spaces, as in `F/ /PER`.
+    let process = rest
+        .split_whitespace()
+        .find(|word| {
+            word.split_once(':').is_some_and(|(pid, name)| {
+                !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit()) && name.contains('/')
+            })
+        })
+        .ok_or_else(|| invalid("Proc", line))?;
+    let process_start = rest.find(process).unwrap_or(0);
+    let flags = &rest[..process_start];
+    let mut states = flags.split('/');
+    let sched_group = states.next().unwrap_or("").trim();
+    let proc_state = states
+        .nth(1)
+        .and_then(|state| state.split_whitespace().next())
+        .unwrap_or("");
+
+    let (pid, name) = process
+        .split_once(':')
+        .ok_or_else(|| invalid("Proc", line))?;
+    let (name, user) = name.rsplit_once('/').ok_or_else(|| invalid("Proc", line))?;
+    let reason = rest[process_start + process.len()..]
+        .trim()
+        .strip_prefix('(')
+        .and_then(|reason| reason.strip_suffix(')'))
+        .map(str::to_owned);
+
+    Ok(LruProcess {
+        index: index.trim().parse().map_err(|_| invalid("Proc", line))?,
+        persistent,
+        adj: adj.to_owned(),
+        sched_group: sched_group.to_owned(),
+        proc_state: proc_state.to_owned(),
+        pid: pid.parse().map_err(|_| invalid("Proc", line))?,
+        name: name.to_owned(),
+        uid: parse_uid(user),
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys activity activities` of Android 13, with the launcher below the app
+    const ACTIVITIES: &str = "ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+  * Task{6a2c4e1 #87 type=standard A=10216:com.android.chrome U=0 visible=true visibleRequested=true mode=fullscreen translucent=false sz=1}
+    mLastPausedActivity: ActivityRecord{c11e5b5 u0 com.google.android.apps.nexuslauncher/.NexusLauncherActivity t75}
+    isSleeping=false
+    topResumedActivity=ActivityRecord{3f8d2a2 u0 com.android.chrome/org.chromium.chrome.browser.ChromeTabbedActivity t87}
+    * Hist  #0: ActivityRecord{3f8d2a2 u0 com.android.chrome/org.chromium.chrome.browser.ChromeTabbedActivity t87}
+  * Task{1b0c9f7 #1 type=home U=0 visible=false visibleRequested=false mode=fullscreen translucent=false sz=1}
+    * Task{8e7d3a0 #75 type=home A=10160:com.google.android.apps.nexuslauncher U=0 visible=false visibleRequested=false mode=fullscreen translucent=false sz=1}
+      * Hist  #0: ActivityRecord{c11e5b5 u0 com.google.android.apps.nexuslauncher/.NexusLauncherActivity t75}
+
+  Resumed activities in task display areas (from top to bottom):
+    Resumed: ActivityRecord{3f8d2a2 u0 com.android.chrome/org.chromium.chrome.browser.ChromeTabbedActivity t87}
+
+  ResumedActivity: ActivityRecord{3f8d2a2 u0 com.android.chrome/org.chromium.chrome.browser.ChromeTabbedActivity t87}
+";
+
+    /// `dumpsys activity processes` of Android 13, cut down to a few processes
+    const PROCESSES: &str = "ACTIVITY MANAGER RUNNING PROCESSES (dumpsys activity processes)
+  All known processes:
+  *PERS* UID 1000 ProcessRecord{5c1d2e3 1520:system/1000}
+    user #0 uid=1000 gids={1000, 1065, 3002}
+  Process LRU list (sorted by oom_adj, 62 total, non-act at 4, non-svc at 4):
+    Proc #61: fg     T/A/TOP  LCMNFUA  t: 0 21865:com.android.chrome/u0a216 (top-activity)
+    Proc #60: vis    F/ /BFGS ---N---  t: 0 3012:com.google.android.gms.persistent/u0a146 (service)
+    PERS #59: sys    F/ /PER  LCMNFU-  t: 0 1520:system/1000 (fixed)
+    Proc #58: prcp   F/ /IMPF  LCMN---  t: 0 4321:com.android.systemui:screenshot/u0a212 (service)
+    Proc # 3: cch+75 B/ /CEM  ---N---  t: 0 9876:com.example.cached/u0a99 (cch-empty)
+
+  PID mappings:
+    PID #1520: ProcessRecord{5c1d2e3 1520:system/1000}
+";
+
+    #[test]
+    fn parses_activities() {
+        let activities = Activities::parse(ACTIVITIES).unwrap();
+        let resumed = activities.resumed.as_ref().unwrap();
+        assert_eq!(resumed.package, "com.android.chrome");
+        assert_eq!(
+            resumed.activity,
+            "org.chromium.chrome.browser.ChromeTabbedActivity"
+        );
+        assert_eq!(resumed.task_id, Some(87));
+
+        assert_eq!(activities.tasks.len(), 3);
+        let chrome = &activities.tasks[0];
+        assert_eq!(chrome.id, 87);
+        assert_eq!(chrome.affinity.as_deref(), Some("com.android.chrome"));
+        assert_eq!(chrome.user, Some(0));
+        assert_eq!(chrome.visible, Some(true));
+        assert_eq!(chrome.windowing_mode.as_deref(), Some("fullscreen"));
+        assert_eq!(chrome.activities.len(), 1);
+
+        // The root home task only holds the launcher task
+        let home = activities.task(1).unwrap();
+        assert_eq!(home.affinity, None);
+        assert!(home.activities.is_empty());
+        let launcher = activities.task(75).unwrap();
+        assert_eq!(launcher.activities[0].activity, ".NexusLauncherActivity");
+        assert_eq!(activities.top_activity(), Some(resumed));
+    }
+
+    #[test]
+    fn parses_task_records_before_android_10() {
+        let text = "ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+
+  Stack #1: type=standard mode=fullscreen
+    * TaskRecord{4f2a3c1 #42 A=com.example.app U=0 StackId=1 sz=1}
+      * Hist #0: ActivityRecord{9d8e7f6 u0 com.example.app/.MainActivity t42}
+    mResumedActivity: ActivityRecord{9d8e7f6 u0 com.example.app/.MainActivity t42}
+";
+        let activities = Activities::parse(text).unwrap();
+        let task = &activities.tasks[0];
+        assert_eq!(task.id, 42);
+        assert_eq!(task.affinity.as_deref(), Some("com.example.app"));
+        assert_eq!(task.activity_type, None);
+        assert_eq!(task.visible, None);
+        assert_eq!(
+            task.activities[0].class_name(),
+            "com.example.app.MainActivity"
+        );
+        assert_eq!(activities.resumed.unwrap().task_id, Some(42));
+    }
+
+    #[test]
+    fn top_activity_without_a_resumed_one() {
+        let text = ACTIVITIES
+            .lines()
+            .filter(|line| !line.contains("Resumed"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let activities = Activities::parse(&text).unwrap();
+        assert_eq!(activities.resumed, None);
+        assert_eq!(activities.top_activity().unwrap().task_id, Some(87));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            Activities::parse(&ACTIVITIES.replace('\n', "\r\n")).unwrap(),
+            Activities::parse(ACTIVITIES).unwrap()
+        );
+        assert_eq!(
+            Processes::parse(&PROCESSES.replace('\n', "\r\n")).unwrap(),
+            Processes::parse(PROCESSES).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(Activities::parse("").unwrap(), Activities::default());
+        assert_eq!(Processes::parse("").unwrap(), Processes::default());
+    }
+
+    #[test]
+    fn truncated_activities_are_invalid() {
+        assert_eq!(
+            Activities::parse("  * Task{6a2c4e1 #").unwrap_err(),
+            invalid("Task", "6a2c4e1 #")
+        );
+        let line = "* Hist  #0: ActivityRecord{3f8d2a2 u0";
+        assert_eq!(
+            Activities::parse(&format!("  * Task{{6a2c4e1 #87 sz=1}}\n    {line}")).unwrap_err(),
+            invalid("Hist", line)
+        );
+    }
+
+    #[test]
+    fn parses_the_lru_list() {
+        let processes = Processes::parse(PROCESSES).unwrap();
+        assert_eq!(processes.lru.len(), 5);
+        assert_eq!(
+            processes.lru[0],
+            LruProcess {
+                index: 61,
+                persistent: false,
+                adj: "fg".to_owned(),
+                sched_group: "T".to_owned(),
+                proc_state: "TOP".to_owned(),
+                pid: 21865,
+                name: "com.android.chrome".to_owned(),
+                uid: Some(10216),
+                reason: Some("top-activity".to_owned()),
+            }
+        );
+        let system = processes.pid(1520).unwrap();
+        assert!(system.persistent);
+        assert_eq!(system.proc_state, "PER");
+        assert_eq!(system.uid, Some(1000));
+        let screenshot = processes
+            .process("com.android.systemui:screenshot")
+            .unwrap();
+        assert_eq!(screenshot.adj, "prcp");
+        assert_eq!(screenshot.proc_state, "IMPF");
+        let cached = processes.process("com.example.cached").unwrap();
+        assert_eq!(cached.index, 3);
+        assert!(cached.is_cached());
+        assert_eq!(processes.pid(1), None);
+    }
+
+    #[test]
+    fn lru_process_without_a_reason() {
+        let text = "  Process LRU list (sorted by oom_adj, 1 total, non-act at 0, non-svc at 0):
+    Proc # 0: cch+95 B/ /CEM  ---N---  t: 0 9876:com.example.cached/u0a99";
+        let processes = Processes::parse(text).unwrap();
+        assert_eq!(processes.lru[0].reason, None);
+        assert_eq!(processes.lru[0].adj, "cch+95");
+    }
+
+    #[test]
+    fn truncated_lru_entry_is_invalid() {
+        let line = "Proc #61: fg     T/A/TOP  LCMNFUA  t: 0";
+        let text = format!("  Process LRU list (sorted by oom_adj, 1 total):\n    {line}\n");
+        assert_eq!(Processes::parse(&text).unwrap_err(), invalid("Proc", line));
+    }
+}
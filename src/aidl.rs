@@ -0,0 +1,242 @@
+//! Hand-written AIDL bindings for the parts of servicemanager this crate talks to
+//!
+//! Transaction codes follow the declaration order in `IServiceManager.aidl` and `IServiceCallback.aidl`.
+
+use binder::{
+    binder_impl::{
+        BorrowedParcel, IBinderInternal, Parcel, TransactionCode, FIRST_CALL_TRANSACTION,
+    },
+    declare_binder_interface, ExceptionCode, Interface, SpIBinder, Status, StatusCode,
+};
+
+use crate::compat::Sdk;
+
+const LIST_SERVICES: TransactionCode = FIRST_CALL_TRANSACTION + 3;
+const REGISTER_FOR_NOTIFICATIONS: TransactionCode = FIRST_CALL_TRANSACTION + 4;
+const UNREGISTER_FOR_NOTIFICATIONS: TransactionCode = FIRST_CALL_TRANSACTION + 5;
+
+const ON_REGISTRATION: TransactionCode = FIRST_CALL_TRANSACTION;
+
+pub(crate) const DUMP_FLAG_PRIORITY_CRITICAL: i32 = 1 << 0;
+pub(crate) const DUMP_FLAG_PRIORITY_HIGH: i32 = 1 << 1;
+pub(crate) const DUMP_FLAG_PRIORITY_NORMAL: i32 = 1 << 2;
+pub(crate) const DUMP_FLAG_PRIORITY_DEFAULT: i32 = 1 << 3;
+pub(crate) const DUMP_FLAG_PRIORITY_ALL: i32 = DUMP_FLAG_PRIORITY_CRITICAL
+    | DUMP_FLAG_PRIORITY_HIGH
+    | DUMP_FLAG_PRIORITY_NORMAL
+    | DUMP_FLAG_PRIORITY_DEFAULT;
+pub(crate) const DUMP_FLAG_PROTO: i32 = 1 << 4;
+
+/// Code of `getServiceDebugInfo` on release `sdk`.
+///
+/// It's declared last, so it moves down as releases declare methods before it: `getConnectionInfo`
+/// in Android 13 and `getUpdatableNames` in Android 14. `None` before Android 12, which lacks it, and
+/// past Android 15, whose declarations haven't been checked.
+pub(crate) fn get_service_debug_info_code(sdk: Sdk) -> Option<TransactionCode> {
+    let index = match sdk {
+        sdk if sdk < Sdk::S => return None,
+        sdk if sdk < Sdk::TIRAMISU => 11,
+        sdk if sdk < Sdk::UPSIDE_DOWN_CAKE => 12,
+        sdk if sdk <= Sdk::VANILLA_ICE_CREAM => 13,
+        _ => return None,
+    };
+    Some(FIRST_CALL_TRANSACTION + index)
+}
+
+/// `android.os.IServiceManager`, as implemented by servicemanager
+pub(crate) trait IServiceManager: Interface {
+    fn list_services(&self, dump_priority: i32) -> binder::Result<Vec<String>>;
+
+    fn register_for_notifications(
+        &self,
+        name: &str,
+        callback: &binder::Strong<dyn IServiceCallback>,
+    ) -> binder::Result<()>;
+
+    fn unregister_for_notifications(
+        &self,
+        name: &str,
+        callback: &binder::Strong<dyn IServiceCallback>,
+    ) -> binder::Result<()>;
+
+    /// `code` is the one of the release, see [`get_service_debug_info_code`].
+    fn get_service_debug_info(
+        &self,
+        code: TransactionCode,
+    ) -> binder::Result<Vec<ServiceDebugInfo>>;
+}
+
+/// `android.os.ServiceDebugInfo`, available since Android 12
+pub(crate) struct ServiceDebugInfo {
+    pub(crate) name: String,
+    pub(crate) debug_pid: i32,
+}
+
+declare_binder_interface! {
+    IServiceManager["android.os.IServiceManager"] {
+        native: BnServiceManager(serve_service_manager),
+        proxy: BpServiceManager,
+    }
+}
+
+/// We only ever call servicemanager, never serve its interface.
+fn serve_service_manager(
+    _service: &dyn IServiceManager,
+    _code: TransactionCode,
+    _data: &BorrowedParcel<'_>,
+    _reply: &mut BorrowedParcel<'_>,
+) -> Result<(), StatusCode> {
+    Err(StatusCode::UNKNOWN_TRANSACTION)
+}
+
+impl IServiceManager for BpServiceManager {
+    fn list_services(&self, dump_priority: i32) -> binder::Result<Vec<String>> {
+        let mut data = self.binder.prepare_transact()?;
+        data.write(&dump_priority)?;
+        let reply = self.binder.submit_transact(LIST_SERVICES, data, 0)?;
+        read_status(&reply)?;
+        reply.read()
+    }
+
+    fn register_for_notifications(
+        &self,
+        name: &str,
+        callback: &binder::Strong<dyn IServiceCallback>,
+    ) -> binder::Result<()> {
+        let mut data = self.binder.prepare_transact()?;
+        data.write(name)?;
+        data.write(&callback.as_binder())?;
+        let reply = self
+            .binder
+            .submit_transact(REGISTER_FOR_NOTIFICATIONS, data, 0)?;
+        read_status(&reply)
+    }
+
+    fn unregister_for_notifications(
+        &self,
+        name: &str,
+        callback: &binder::Strong<dyn IServiceCallback>,
+    ) -> binder::Result<()> {
+        let mut data = self.binder.prepare_transact()?;
+        data.write(name)?;
+        data.write(&callback.as_binder())?;
+        let reply = self
+            .binder
+            .submit_transact(UNREGISTER_FOR_NOTIFICATIONS, data, 0)?;
+        read_status(&reply)
+    }
+
+    fn get_service_debug_info(
+        &self,
+        code: TransactionCode,
+    ) -> binder::Result<Vec<ServiceDebugInfo>> {
+        let data = self.binder.prepare_transact()?;
+        let reply = self.binder.submit_transact(code, data, 0)?;
+        read_status(&reply)?;
+
+        let len: i32 = reply.read()?;
+        (0..len.max(0))
+            .map(|_| {
+                read_parcelable(&reply, |reply| {
+                    Ok(ServiceDebugInfo {
+                        name: reply.read()?,
+                        debug_pid: reply.read()?,
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// `android.os.IServiceCallback`, called by servicemanager when a watched service registers
+pub(crate) trait IServiceCallback: Interface {
+    fn on_registration(&self, name: String, binder: SpIBinder) -> binder::Result<()>;
+}
+
+declare_binder_interface! {
+    IServiceCallback["android.os.IServiceCallback"] {
+        native: BnServiceCallback(serve_service_callback),
+        proxy: BpServiceCallback,
+    }
+}
+
+fn serve_service_callback(
+    service: &dyn IServiceCallback,
+    code: TransactionCode,
+    data: &BorrowedParcel<'_>,
+    _reply: &mut BorrowedParcel<'_>,
+) -> Result<(), StatusCode> {
+    match code {
+        // oneway, so there's no reply to write.
+        ON_REGISTRATION => {
+            let name = data.read()?;
+            let binder = data.read()?;
+            service
+                .on_registration(name, binder)
+                .map_err(|_| StatusCode::UNKNOWN_ERROR)
+        }
+        _ => Err(StatusCode::UNKNOWN_TRANSACTION),
+    }
+}
+
+impl IServiceCallback for BpServiceCallback {
+    fn on_registration(&self, _name: String, _binder: SpIBinder) -> binder::Result<()> {
+        Err(StatusCode::INVALID_OPERATION)
+    }
+}
+
+/// Read an element of a parcelable array: a non-null marker, then the parcelable prefixed by its size.
+///
+/// Skips fields added by newer Android versions after `read` is done with the known ones.
+fn read_parcelable<T>(
+    reply: &Parcel,
+    read: impl FnOnce(&Parcel) -> binder::Result<T>,
+) -> binder::Result<T> {
+    let non_null: i32 = reply.read()?;
+    if non_null == 0 {
+        return Err(StatusCode::UNEXPECTED_NULL);
+    }
+
+    let start = reply.get_data_position();
+    let size: i32 = reply.read()?;
+    let parcelable = read(reply)?;
+    let end = start.checked_add(size).ok_or(StatusCode::BAD_VALUE)?;
+    if size < 4 || end > reply.get_data_size() {
+        return Err(StatusCode::BAD_VALUE);
+    }
+    // SAFETY: `end` lies within the reply, checked above.
+    unsafe { reply.set_data_position(end) }?;
+    Ok(parcelable)
+}
+
+/// Check the AIDL status header of a reply, turning exceptions into status codes.
+fn read_status(reply: &Parcel) -> binder::Result<()> {
+    let status: Status = reply.read()?;
+    if status.is_ok() {
+        return Ok(());
+    }
+    Err(match status.exception_code() {
+        ExceptionCode::TRANSACTION_FAILED => status.transaction_error(),
+        ExceptionCode::SECURITY => StatusCode::PERMISSION_DENIED,
+        ExceptionCode::ILLEGAL_ARGUMENT | ExceptionCode::NULL_POINTER => StatusCode::BAD_VALUE,
+        _ => StatusCode::FAILED_TRANSACTION,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_debug_info_code_follows_the_release() {
+        let index =
+            |sdk| get_service_debug_info_code(Sdk(sdk)).map(|code| code - FIRST_CALL_TRANSACTION);
+        assert_eq!(index(30), None);
+        assert_eq!(index(31), Some(11));
+        assert_eq!(index(32), Some(11));
+        assert_eq!(index(33), Some(12));
+        assert_eq!(index(34), Some(13));
+        assert_eq!(index(35), Some(13));
+        assert_eq!(index(36), None);
+    }
+}
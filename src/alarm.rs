@@ -0,0 +1,543 @@
+//! Typed output of `dumpsys alarm`
+//!
+//! Both the batches of Android 11 and earlier and the flat list of later versions are read.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::alarm::{AlarmManager, AlarmType};
+//!
+//! let alarms = AlarmManager::parse(
+//!     "Current Alarm Manager state:
+//!   Pending alarms: 2
+//!     RTC_WAKEUP #0: Alarm{5a7c8b0 type 0 origWhen 1704110400000 whenElapsed 123456 com.example.app}
+//!       tag=*walarm*:com.example.SYNC
+//!       type=RTC_WAKEUP origWhen=2024-01-01 12:00:00.000 window=+1m0s repeatInterval=900000 count=0 flags=0x8
+//!       whenElapsed=+1h2m maxWhenElapsed=+1h3m
+//!     ELAPSED #1: Alarm{1f2e3d4 type 3 origWhen 456789 whenElapsed 456789 android}
+//!       tag=*alarm*:android.intent.action.TIME_TICK
+//!       type=ELAPSED origWhen=+1m window=0 repeatInterval=0 count=0 flags=0x1
+//!
+//!   Pending user blocked background alarms:
+//!
+//!   Alarm Stats:
+//!   u0a123:com.example.app +1m2s345ms running, 12 wakeups:
+//!     +1m1s 10 wakes 20 alarms, last -5m2s:
+//!       *walarm*:com.example.SYNC
+//!     +1s345ms 2 wakes 2 alarms, last -1h:
+//!       *walarm*:com.example.REFRESH
+//!   1000:android +3s running, 0 wakeups:
+//!     +3s 0 wakes 60 alarms, last -10s:
+//!       *alarm*:android.intent.action.TIME_TICK",
+//! )
+//! .unwrap();
+//!
+//! let alarm = &alarms.pending[0];
+//! assert_eq!(alarm.kind, AlarmType::RtcWakeup);
+//! assert_eq!(alarm.package, "com.example.app");
+//! assert_eq!(alarm.tag.as_deref(), Some("*walarm*:com.example.SYNC"));
+//! assert_eq!(alarm.when.as_deref(), Some("2024-01-01 12:00:00.000"));
+//! assert_eq!(alarm.repeat_interval, Some(Duration::from_secs(900)));
+//! assert_eq!(alarm.time_until, Some(Duration::from_secs(3720)));
+//! assert_eq!(alarms.pending_of("android").count(), 1);
+//!
+//! let stats = alarms.stats_of(10123).unwrap();
+//! assert_eq!(stats.running_time, Duration::from_millis(62345));
+//! assert_eq!(stats.wakeups, 12);
+//! assert_eq!(stats.count(), 22);
+//! assert_eq!(stats.tags[0].last, Some(Duration::from_secs(302)));
+//! assert_eq!(alarms.top_wakeups()[0].package, "com.example.app");
+//! ```
+
+use std::{cmp::Reverse, time::Duration};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::{
+        kv::{invalid, parse_duration, KeyValues},
+        parse_uid,
+    },
+    typed, DumpParse,
+};
+
+const PENDING_ALARMS: &str = "Pending alarms:";
+const PENDING_BATCHES: &str = "Pending alarm batches:";
+const BATCH: &str = "Batch{";
+const ALARM_STATS: &str = "Alarm Stats:";
+const ALARM: &str = "Alarm{";
+const RUNNING: &str = " running, ";
+const WAKEUPS: &str = " wakeups:";
+
+/// `AlarmManager.RTC_WAKEUP` and friends
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlarmType {
+    RtcWakeup,
+    Rtc,
+    ElapsedRealtimeWakeup,
+    ElapsedRealtime,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl AlarmType {
+    /// Whether the alarm wakes the device up.
+    pub fn is_wakeup(&self) -> bool {
+        matches!(self, Self::RtcWakeup | Self::ElapsedRealtimeWakeup)
+    }
+}
+
+impl From<&str> for AlarmType {
+    fn from(name: &str) -> Self {
+        match name {
+            "RTC_WAKEUP" => Self::RtcWakeup,
+            "RTC" => Self::Rtc,
+            "ELAPSED_WAKEUP" => Self::ElapsedRealtimeWakeup,
+            "ELAPSED" => Self::ElapsedRealtime,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A scheduled alarm
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alarm {
+    pub kind: AlarmType,
+    /// Package that set the alarm
+    pub package: String,
+    /// As in `*walarm*:com.example.SYNC`, the intent action for most alarms
+    pub tag: Option<String>,
+    /// Requested time as printed, a date for RTC alarms and a time since boot for the others
+    pub when: Option<String>,
+    /// Time left until the alarm is due, on Android 12 and later
+    pub time_until: Option<Duration>,
+    /// How late the alarm may be delivered
+    pub window: Option<Duration>,
+    pub repeat_interval: Option<Duration>,
+}
+
+/// Statistics of the alarms of one tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagStats {
+    pub tag: String,
+    /// Time spent holding the alarm wakelock
+    pub running_time: Duration,
+    pub wakeups: u64,
+    pub count: u64,
+    /// Time since the last alarm
+    pub last: Option<Duration>,
+}
+
+/// Statistics of the alarms of one package since boot
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmStats {
+    pub uid: u32,
+    pub package: String,
+    pub running_time: Duration,
+    pub wakeups: u64,
+    pub tags: Vec<TagStats>,
+}
+
+impl AlarmStats {
+    /// Alarms delivered, wakeup or not.
+    pub fn count(&self) -> u64 {
+        self.tags.iter().map(|tag| tag.count).sum()
+    }
+}
+
+/// Output of `dumpsys alarm`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmManager {
+    /// Alarms not delivered yet, soonest first
+    pub pending: Vec<Alarm>,
+    pub stats: Vec<AlarmStats>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    /// Indent of the header
+    Pending(usize),
+    Stats,
+}
+
+impl AlarmManager {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut alarms = Self::default();
+        let mut section = Section::Other;
+        // Tag statistics print the tag on the line after the counts.
+        let mut expect_tag = false;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+
+            if trimmed.starts_with(PENDING_ALARMS) || trimmed.starts_with(PENDING_BATCHES) {
+                section = Section::Pending(indent);
+                continue;
+            }
+            if trimmed == ALARM_STATS {
+                section = Section::Stats;
+                continue;
+            }
+
+            match section {
+                Section::Other => {}
+                Section::Pending(header) => {
+                    if indent <= header && !trimmed.starts_with(BATCH) {
+                        section = Section::Other;
+                    } else if trimmed.contains(ALARM) {
+                        alarms.pending.push(alarm(trimmed)?);
+                    } else if let Some(alarm) = alarms.pending.last_mut() {
+                        alarm.details(trimmed);
+                    }
+                }
+                Section::Stats => {
+                    if expect_tag {
+                        expect_tag = false;
+                        if let Some(tag) = alarms
+                            .stats
+                            .last_mut()
+                            .and_then(|stats| stats.tags.last_mut())
+                        {
+                            tag.tag = trimmed.to_owned();
+                            continue;
+                        }
+                    }
+                    if let Some(stats) = package_stats(trimmed)? {
+                        alarms.stats.push(stats);
+                    } else if let Some(tag) = tag_stats(trimmed)? {
+                        expect_tag = tag.tag.is_empty();
+                        let stats = alarms
+                            .stats
+                            .last_mut()
+                            .ok_or_else(|| invalid("Alarm Stats", trimmed))?;
+                        stats.tags.push(tag);
+                    } else {
+                        section = Section::Other;
+                    }
+                }
+            }
+        }
+
+        Ok(alarms)
+    }
+
+    /// Pending alarms set by `package`.
+    pub fn pending_of<'a>(&'a self, package: &'a str) -> impl Iterator<Item = &'a Alarm> + 'a {
+        self.pending
+            .iter()
+            .filter(move |alarm| alarm.package == package)
+    }
+
+    /// Statistics of the first package of `uid`.
+    pub fn stats_of(&self, uid: u32) -> Option<&AlarmStats> {
+        self.stats.iter().find(|stats| stats.uid == uid)
+    }
+
+    /// Packages by wakeups, most first.
+    pub fn top_wakeups(&self) -> Vec<&AlarmStats> {
+        let mut stats: Vec<_> = self.stats.iter().collect();
+        stats.sort_by_key(|stats| Reverse(stats.wakeups));
+        stats
+    }
+}
+
+impl DumpParse for AlarmManager {
+    const SERVICE: &'static str = "alarm";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `alarm` and return the alarm statistics of every package.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for stats in dumpsys_rs::alarm::alarm_stats().unwrap() {
+///     println!("{}: {} wakeups", stats.package, stats.wakeups);
+/// }
+/// # }
+/// ```
+pub fn alarm_stats() -> Result<Vec<AlarmStats>, DumpError> {
+    typed::dump_service::<AlarmManager>(Vec::<&str>::new()).map(|alarms| alarms.stats)
+}
+
+impl Alarm {
+    /// A line after the `Alarm{...}` header.
+    fn details(&mut self, line: &str) {
+        let fields = KeyValues::parse(line);
+        if let Some(tag) = line.strip_prefix("tag=") {
+            self.tag = Some(tag.to_owned());
+        } else if line.starts_with("type=") {
+            self.when = fields.get("origWhen").map(str::to_owned);
+            self.window = fields.get("window").and_then(parse_duration);
+            self.repeat_interval = fields
+                .get("repeatInterval")
+                .and_then(parse_duration)
+                .filter(|interval| !interval.is_zero());
+        } else if line.starts_with("whenElapsed=") {
+            self.time_until = fields.get("whenElapsed").and_then(parse_duration);
+        }
+    }
+}
+
+/// `RTC_WAKEUP #0: Alarm{5a7c8b0 type 0 origWhen 1704110400000 whenElapsed 123456 com.example.app}`
+fn alarm(line: &str) -> Result<Alarm, ParseError> {
+    let kind = line.split_whitespace().next().unwrap_or_default();
+    let package = line
+        .split(ALARM)
+        .nth(1)
+        .and_then(|record| record.trim_end_matches('}').split_whitespace().last())
+        .ok_or_else(|| invalid("Alarm", line))?;
+    Ok(Alarm {
+        kind: kind.into(),
+        package: package.to_owned(),
+        tag: None,
+        when: None,
+        time_until: None,
+        window: None,
+        repeat_interval: None,
+    })
+}
+
+/// `u0a123:com.example.app +1m2s345ms running, 12 wakeups:`
+fn package_stats(line: &str) -> Result<Option<AlarmStats>, ParseError> {
+    let Some((owner, running_time, wakeups)) = line
+        .strip_suffix(WAKEUPS)
+        .and_then(|line| line.split_once(RUNNING))
+        .and_then(|(head, wakeups)| {
+            let (owner, running_time) = head.split_once(' ')?;
+            Some((owner, running_time, wakeups))
+        })
+    else {
+        return Ok(None);
+    };
+    let (uid, package) = owner.split_once(':').ok_or_else(|| invalid("uid", owner))?;
+    Ok(Some(AlarmStats {
+        uid: parse_uid(uid).ok_or_else(|| invalid("uid", uid))?,
+        package: package.to_owned(),
+        running_time: parse_duration(running_time)
+            .ok_or_else(|| invalid("running", running_time))?,
+        wakeups: wakeups.parse().map_err(|_| invalid("wakeups", wakeups))?,
+        tags: Vec::new(),
+    }))
+}
+
+/// `+1m1s 10 wakes 20 alarms, last -5m2s:`, with the tag after the colon on older versions.
+fn tag_stats(line: &str) -> Result<Option<TagStats>, ParseError> {
+    let words: Vec<_> = line.split_whitespace().take(5).collect();
+    let [running_time, wakeups, "wakes", count, alarms] = words[..] else {
+        return Ok(None);
+    };
+    let Some(running_time) = running_time.strip_prefix('+') else {
+        return Ok(None);
+    };
+    let (last, tag) = match line.split_once(':') {
+        Some((head, tag)) => (head, tag.trim()),
+        None => (line, ""),
+    };
+    if !alarms.starts_with("alarms") {
+        return Ok(None);
+    }
+    Ok(Some(TagStats {
+        tag: tag.to_owned(),
+        running_time: parse_duration(running_time)
+            .ok_or_else(|| invalid("running", running_time))?,
+        wakeups: wakeups.parse().map_err(|_| invalid("wakes", wakeups))?,
+        count: count.parse().map_err(|_| invalid("alarms", count))?,
+        last: last.split("last -").nth(1).and_then(parse_duration),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys alarm` of Android 14, cut down to a few alarms and packages
+    const DUMP: &str = "Current Alarm Manager state:
+  Settings:
+    min_futurity=+5s0ms
+    min_interval=+1m0s0ms
+  nowRTC=1710234764000 = 2024-03-12 09:12:44.000 nowELAPSED=+10d2h3m4s567ms
+
+  Pending alarms: 3
+    RTC_WAKEUP #0: Alarm{a31c8d1 type 0 origWhen 1710235200000 whenElapsed 872112345 com.example.app}
+      tag=*walarm*:com.example.app.SYNC
+      type=RTC_WAKEUP origWhen=2024-03-12 09:20:00.000 window=+2m15s repeatInterval=900000 count=0 flags=0x8
+      policyWhenElapsed: requester=+7m15s app_standby=+7m15s device_idle=-- battery_saver=-- tare=--
+      whenElapsed=+7m15s maxWhenElapsed=+9m30s
+      operation=PendingIntent{60b8c9f: PendingIntentRecord{3e4a2b5 com.example.app broadcastIntent}}
+    ELAPSED_WAKEUP #1: Alarm{5d0e7a2 type 2 origWhen 873004012 whenElapsed 873004012 android}
+      tag=*walarm*:android.appwidget.action.APPWIDGET_UPDATE
+      type=ELAPSED_WAKEUP origWhen=+10d2h18m0s0ms window=0 repeatInterval=0 count=0 flags=0x0
+      policyWhenElapsed: requester=+15m app_standby=+15m device_idle=-- battery_saver=-- tare=--
+      whenElapsed=+15m maxWhenElapsed=+15m
+    ELAPSED #2: Alarm{1f2e3d4 type 3 origWhen 872642100 whenElapsed 872642100 android}
+      tag=*alarm*:android.intent.action.TIME_TICK
+      type=ELAPSED origWhen=+10d2h3m40s0ms window=0 repeatInterval=0 count=0 flags=0x1
+      whenElapsed=+55s433ms maxWhenElapsed=+55s433ms
+      listener=android.app.AlarmManager$ListenerWrapper@3b1d8f
+
+  Pending user blocked background alarms:
+
+  Top Alarms:
+    +1m1s running, 10 wakeups, 20 alarms: u0a231:com.example.app
+      *walarm*:com.example.app.SYNC
+
+  Alarm Stats:
+  u0a231:com.example.app +1m2s345ms running, 12 wakeups:
+    +1m1s 10 wakes 20 alarms, last -5m2s:
+      *walarm*:com.example.app.SYNC
+    +1s345ms 2 wakes 2 alarms, last -1h0m0s:
+      *walarm*:com.example.app.REFRESH
+  1000:android +4s12ms running, 31 wakeups:
+    +3s 0 wakes 1440 alarms, last -4s:
+      *alarm*:android.intent.action.TIME_TICK
+    +1s12ms 31 wakes 31 alarms, last -12m:
+      *walarm*:android.appwidget.action.APPWIDGET_UPDATE
+
+  Allow while idle history:
+";
+
+    #[test]
+    fn parses_the_pending_alarms() {
+        let alarms = AlarmManager::parse(DUMP).unwrap();
+        assert_eq!(alarms.pending.len(), 3);
+        assert_eq!(
+            alarms.pending[0],
+            Alarm {
+                kind: AlarmType::RtcWakeup,
+                package: "com.example.app".to_owned(),
+                tag: Some("*walarm*:com.example.app.SYNC".to_owned()),
+                when: Some("2024-03-12 09:20:00.000".to_owned()),
+                time_until: Some(Duration::from_secs(435)),
+                window: Some(Duration::from_secs(135)),
+                repeat_interval: Some(Duration::from_secs(900)),
+            }
+        );
+        let widget = &alarms.pending[1];
+        assert_eq!(widget.kind, AlarmType::ElapsedRealtimeWakeup);
+        assert_eq!(widget.when.as_deref(), Some("+10d2h18m0s0ms"));
+        assert_eq!(widget.repeat_interval, None);
+        assert_eq!(
+            alarms
+                .pending
+                .iter()
+                .filter(|alarm| alarm.kind.is_wakeup())
+                .count(),
+            2
+        );
+        assert_eq!(alarms.pending_of("android").count(), 2);
+    }
+
+    #[test]
+    fn parses_the_alarm_stats() {
+        let alarms = AlarmManager::parse(DUMP).unwrap();
+        // Top alarms repeat what the stats have
+        assert_eq!(alarms.stats.len(), 2);
+        let android = alarms.stats_of(1000).unwrap();
+        assert_eq!(android.package, "android");
+        assert_eq!(android.running_time, Duration::from_millis(4012));
+        assert_eq!(android.count(), 1471);
+        assert_eq!(
+            android.tags[1],
+            TagStats {
+                tag: "*walarm*:android.appwidget.action.APPWIDGET_UPDATE".to_owned(),
+                running_time: Duration::from_millis(1012),
+                wakeups: 31,
+                count: 31,
+                last: Some(Duration::from_secs(720)),
+            }
+        );
+        let packages: Vec<_> = alarms
+            .top_wakeups()
+            .iter()
+            .map(|stats| &stats.package)
+            .collect();
+        assert_eq!(packages, ["android", "com.example.app"]);
+    }
+
+    #[test]
+    fn batches_of_older_releases() {
+        // Android 11 groups the alarms in batches and prints the tag of a stat on the same line
+        let text = "  Pending alarm batches: 2
+  Batch{9e1c0f4 num=1 start=872112345 end=873112345 flags=0x8}:
+    RTC_WAKEUP #0: Alarm{a31c8d1 type 0 when 1710235200000 com.example.app}
+      tag=*walarm*:com.example.app.SYNC
+      type=0 whenElapsed=+7m15s when=2024-03-12 09:20:00 window=+2m15s repeatInterval=900000 count=0 flags=0x8
+  Batch{2b7d9a1 num=1 start=873004012 end=873004012}:
+    --unknown-- #0: Alarm{5d0e7a2 type 5 when 873004012 android}
+
+  Alarm Stats:
+  u0a231:com.example.app +1m2s345ms running, 12 wakeups:
+    +1m1s 10 wakes 20 alarms, last -5m2s: *walarm*:com.example.app.SYNC
+";
+        let alarms = AlarmManager::parse(text).unwrap();
+        assert_eq!(alarms.pending.len(), 2);
+        assert_eq!(alarms.pending[0].when, None);
+        assert_eq!(alarms.pending[0].time_until, None);
+        assert_eq!(alarms.pending[0].window, Some(Duration::from_secs(135)));
+        assert_eq!(
+            alarms.pending[1].kind,
+            AlarmType::Other("--unknown--".to_owned())
+        );
+        assert_eq!(alarms.stats[0].tags[0].tag, "*walarm*:com.example.app.SYNC");
+        assert_eq!(alarms.stats[0].tags[0].count, 20);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let line = "RTC_WAKEUP #0: Alarm{}";
+        let text = format!("  {PENDING_ALARMS} 1\n    {line}\n");
+        assert_eq!(
+            AlarmManager::parse(&text).unwrap_err(),
+            invalid("Alarm", line)
+        );
+
+        let stats = |line: &str| AlarmManager::parse(&format!("  {ALARM_STATS}\n  {line}\n"));
+        assert_eq!(
+            stats("u0a231 +1s running, 1 wakeups:").unwrap_err(),
+            invalid("uid", "u0a231")
+        );
+        assert_eq!(
+            stats("u0a231:com.example.app 1 minute running, 1 wakeups:").unwrap_err(),
+            invalid("running", "1 minute")
+        );
+        assert_eq!(
+            stats("u0a231:com.example.app +1s running, some wakeups:").unwrap_err(),
+            invalid("wakeups", "some")
+        );
+        let line = "+1m1s 10 wakes 20 alarms, last -5m2s:";
+        assert_eq!(stats(line).unwrap_err(), invalid("Alarm Stats", line));
+        let text =
+            "u0a231:com.example.app +1s running, 1 wakeups:\n    +1s x wakes 2 alarms, last -1s:";
+        assert_eq!(stats(text).unwrap_err(), invalid("wakes", "x"));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            AlarmManager::parse(&crlf).unwrap(),
+            AlarmManager::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(AlarmManager::parse("").unwrap(), AlarmManager::default());
+    }
+}
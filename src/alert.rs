@@ -0,0 +1,359 @@
+//! Alerts raised when periodic dumps match a pattern
+//!
+//! A [`Rule`] dumps a service at its own interval and looks for a substring, a regex with the `regex`
+//! feature, or whatever a predicate finds. [`Watchdog`] runs any number of rules on one background
+//! thread and reports an [`Alert`] with the lines around the match.
+//!
+//! A dump usually keeps matching for a while, as `dumpsys activity` does after an ANR, so an alert is
+//! only sent when a rule starts matching or the excerpt changes, and [`AlertEvent::Cleared`] once it
+//! stops.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::alert::Rule;
+//!
+//! let rule = Rule::contains("anr", "activity", ["lastanr"], "ANR in ").context(1);
+//! let alert = rule
+//!     .find(
+//!         "ACTIVITY MANAGER LAST ANR (dumpsys activity lastanr)
+//!   ANR time: Jan 1, 2024 12:05:00 PM
+//!   ANR in com.example.app (com.example.app/.MainActivity)
+//!   Reason: Input dispatching timed out",
+//!     )
+//!     .unwrap();
+//!
+//! assert_eq!(alert.rule, "anr");
+//! assert_eq!(alert.matched, "ANR in ");
+//! assert_eq!(
+//!     alert.excerpt,
+//!     "  ANR time: Jan 1, 2024 12:05:00 PM
+//!   ANR in com.example.app (com.example.app/.MainActivity)
+//!   Reason: Input dispatching timed out",
+//! );
+//! assert!(rule.find("  <no ANR has occurred since boot>").is_none());
+//!
+//! // Predicates return the part of the output they matched.
+//! let hot = Rule::new("hot", "thermalservice", [] as [&str; 0], |output| {
+//!     output.lines().find(|line| line.contains("mStatus=") && !line.ends_with("mStatus=0"))
+//! });
+//! assert_eq!(hot.find("Current temperatures:\n  mStatus=3").unwrap().excerpt, "  mStatus=3");
+//! ```
+
+use std::{
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::{builder::Config, cancel::CancelToken, error::DumpError, owned_args, typed, Dumpsys};
+
+type Matcher = Box<dyn for<'o> Fn(&'o str) -> Option<&'o str> + Send>;
+
+/// A service to dump at an interval and what to look for in its output
+pub struct Rule {
+    name: String,
+    service: String,
+    args: Vec<String>,
+    interval: Duration,
+    context: usize,
+    matcher: Matcher,
+}
+
+impl Rule {
+    /// Alert when `matcher` returns part of the output of `service` dumped with `args`.
+    ///
+    /// The returned part must borrow from the output for the excerpt to hold its lines; anything
+    /// else is reported as is.
+    pub fn new<F>(
+        name: impl Into<String>,
+        service: impl Into<String>,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        matcher: F,
+    ) -> Self
+    where
+        F: for<'o> Fn(&'o str) -> Option<&'o str> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            service: service.into(),
+            args: owned_args(args),
+            interval: Duration::from_secs(10),
+            context: 0,
+            matcher: Box::new(matcher),
+        }
+    }
+
+    /// Alert when the output contains `needle`.
+    pub fn contains(
+        name: impl Into<String>,
+        service: impl Into<String>,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        needle: impl Into<String>,
+    ) -> Self {
+        let needle = needle.into();
+        Self::new(name, service, args, move |output| {
+            output
+                .find(&needle)
+                .map(|start| &output[start..start + needle.len()])
+        })
+    }
+
+    /// Alert when `regex` matches the output.
+    #[cfg(feature = "regex")]
+    pub fn regex(
+        name: impl Into<String>,
+        service: impl Into<String>,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        regex: Regex,
+    ) -> Self {
+        Self::new(name, service, args, move |output| {
+            regex.find(output).map(|found| found.as_str())
+        })
+    }
+
+    /// How often the service is dumped, 10 seconds by default.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Lines of output before and after the match to add to the excerpt, 0 by default.
+    pub fn context(mut self, lines: usize) -> Self {
+        self.context = lines;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The alert `output` raises, without a dump.
+    pub fn find(&self, output: &str) -> Option<Alert> {
+        let matched = (self.matcher)(output)?;
+        Some(Alert {
+            rule: self.name.clone(),
+            service: self.service.clone(),
+            matched: matched.to_owned(),
+            excerpt: excerpt(output, matched, self.context),
+        })
+    }
+}
+
+/// A dump matched a [`Rule`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alert {
+    /// Name of the rule
+    pub rule: String,
+    pub service: String,
+    /// The part of the output the rule matched
+    pub matched: String,
+    /// The lines holding the match, with the context of the rule
+    pub excerpt: String,
+}
+
+/// What a [`Watchdog`] reports
+#[derive(Debug)]
+pub enum AlertEvent {
+    /// A rule started matching, or matched with another excerpt than its last alert
+    Alert(Alert),
+    /// The rule named here stopped matching after an [`AlertEvent::Alert`]
+    Cleared(String),
+    /// Dumping the service of a rule failed, the rule is tried again at its next interval
+    Error { rule: String, error: DumpError },
+}
+
+/// Configure a [`Watchdog`], see the [module docs](self)
+#[derive(Default)]
+pub struct WatchdogBuilder {
+    rules: Vec<Rule>,
+}
+
+impl WatchdogBuilder {
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Start checking the rules, passing events to `callback` until it breaks or the watchdog stops.
+    ///
+    /// Fails if the service of a rule isn't registered.
+    pub fn spawn<F>(self, mut callback: F) -> Result<Watchdog, DumpError>
+    where
+        F: FnMut(AlertEvent) -> ControlFlow<()> + Send + 'static,
+    {
+        let cancel = CancelToken::new();
+        let mut checks = self
+            .rules
+            .into_iter()
+            .map(|rule| Check::connect(rule, &cancel))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let token = cancel.clone();
+        let thread = thread::spawn(move || loop {
+            let Some(check) = checks.iter_mut().min_by_key(|check| check.due) else {
+                let _ = stopped.recv();
+                return;
+            };
+            match stopped.recv_timeout(check.due.saturating_duration_since(Instant::now())) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+            check.due = (check.due + check.rule.interval).max(Instant::now());
+
+            if let Some(event) = check.run() {
+                if token.is_cancelled() || callback(event).is_break() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Watchdog {
+            stop: Some(stop),
+            cancel,
+            thread: Some(thread),
+        })
+    }
+
+    /// Start checking the rules, sending events to the returned receiver until it's dropped.
+    pub fn channel(self) -> Result<(Watchdog, Receiver<AlertEvent>), DumpError> {
+        let (tx, rx) = mpsc::channel();
+        let watchdog = self.spawn(move |event| {
+            tx.send(event)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+        })?;
+        Ok((watchdog, rx))
+    }
+}
+
+/// Checks [`Rule`]s on a background thread, see the [module docs](self)
+///
+/// Every rule is first checked right away. Stopping, or dropping the watchdog, aborts a dump in
+/// flight and joins the thread.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::alert::{AlertEvent, Rule, Watchdog};
+///
+/// # fn foo() {
+/// let (watchdog, events) = Watchdog::builder()
+///     .rule(Rule::contains("anr", "activity", ["lastanr"], "ANR in ").context(3))
+///     .rule(
+///         Rule::contains("doze", "deviceidle", [] as [&str; 0], "mState=IDLE")
+///             .interval(Duration::from_secs(60)),
+///     )
+///     .channel()
+///     .unwrap();
+/// for event in events.iter().take(5) {
+///     if let AlertEvent::Alert(alert) = event {
+///         println!("{} in {}:\n{}", alert.rule, alert.service, alert.excerpt);
+///     }
+/// }
+/// watchdog.stop();
+/// # }
+/// ```
+pub struct Watchdog {
+    stop: Option<Sender<()>>,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn builder() -> WatchdogBuilder {
+        WatchdogBuilder::default()
+    }
+
+    /// Stop checking and wait for the thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.cancel.cancel();
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// A rule being checked
+struct Check {
+    rule: Rule,
+    dumpsys: Dumpsys,
+    due: Instant,
+    /// Excerpt of the last alert, while the rule keeps matching
+    last: Option<String>,
+}
+
+impl Check {
+    fn connect(rule: Rule, cancel: &CancelToken) -> Result<Self, DumpError> {
+        let dumpsys = typed::connect(&rule.service, &rule.args)?;
+        Ok(Self {
+            dumpsys: dumpsys.with_config(Config {
+                cancel: Some(cancel.clone()),
+                ..(*dumpsys.config).clone()
+            }),
+            rule,
+            due: Instant::now(),
+            last: None,
+        })
+    }
+
+    /// Dump the service and return the event the output raises, if any.
+    fn run(&mut self) -> Option<AlertEvent> {
+        let output = match self.dumpsys.dump(&self.rule.args) {
+            Ok(output) => output,
+            Err(error) => {
+                return Some(AlertEvent::Error {
+                    rule: self.rule.name.clone(),
+                    error,
+                })
+            }
+        };
+        match self.rule.find(&output) {
+            Some(alert) if self.last.as_ref() == Some(&alert.excerpt) => None,
+            Some(alert) => {
+                self.last = Some(alert.excerpt.clone());
+                Some(AlertEvent::Alert(alert))
+            }
+            None => self
+                .last
+                .take()
+                .map(|_| AlertEvent::Cleared(self.rule.name.clone())),
+        }
+    }
+}
+
+/// The lines of `output` holding `matched`, with `context` lines before and after.
+fn excerpt(output: &str, matched: &str, context: usize) -> String {
+    let start = (matched.as_ptr() as usize).wrapping_sub(output.as_ptr() as usize);
+    if start > output.len() || matched.len() > output.len() - start {
+        // Not borrowed from `output`.
+        return matched.to_owned();
+    }
+    let first = output[..start].matches('\n').count();
+    let last = first + matched.trim_end_matches('\n').matches('\n').count();
+    let first = first.saturating_sub(context);
+    output
+        .lines()
+        .skip(first)
+        .take(last + context + 1 - first)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,525 @@
+//! Typed output of `dumpsys appops`
+//!
+//! Accesses are read from the per-attribution history of Android 11 and later, the `Access:` lines of
+//! Android 10 and the `time=` fields of older versions.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::appops::{AccessKind, AppOps, Mode};
+//!
+//! let ops = AppOps::parse(
+//!     "Current AppOps Service state:
+//!   Uid u0a123:
+//!     state=cch
+//!     Package com.example.app:
+//!       CAMERA (allow):
+//!         null=[
+//!           Access: [fg-s] 2024-01-01 12:00:00.000 (-5m) duration=+1s500ms
+//!           Reject: [bg-s] 2024-01-01 11:00:00.000 (-1h5m)
+//!         ]
+//!         recorder=[
+//!           Access: [top-s] 2024-01-01 11:59:00.000 (-6m)
+//!         ]
+//!       COARSE_LOCATION (ignore / switch FINE_LOCATION=ignore):
+//!   Uid 1000:
+//!     Package android:
+//!       WAKE_LOCK (allow): time=+1h2m ago; duration=+3s",
+//! )
+//! .unwrap();
+//!
+//! let camera = ops.package("com.example.app").unwrap().op("CAMERA").unwrap();
+//! assert_eq!(camera.mode, Mode::Allow);
+//! assert_eq!(camera.accesses.len(), 3);
+//! assert_eq!(camera.accesses[1].kind, AccessKind::Reject);
+//! assert_eq!(camera.accesses[2].attribution_tag.as_deref(), Some("recorder"));
+//!
+//! let last = camera.last_access().unwrap();
+//! assert_eq!(last.state.as_deref(), Some("fg-s"));
+//! assert_eq!(last.time.as_deref(), Some("2024-01-01 12:00:00.000"));
+//! assert_eq!(last.ago, Some(Duration::from_secs(300)));
+//! assert_eq!(last.duration, Some(Duration::from_millis(1500)));
+//!
+//! let location = ops.package("com.example.app").unwrap().op("COARSE_LOCATION").unwrap();
+//! assert_eq!(location.mode, Mode::Ignore);
+//!
+//! let users: Vec<_> = ops.users_of("CAMERA", Duration::from_secs(600)).map(|ops| ops.package.as_str()).collect();
+//! assert_eq!(users, ["com.example.app"]);
+//! assert_eq!(ops.package("android").unwrap().uid, 1000);
+//! let wake_lock = ops.package("android").unwrap().op("WAKE_LOCK").unwrap();
+//! assert_eq!(wake_lock.last_access().unwrap().ago, Some(Duration::from_secs(3720)));
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::{
+        kv::{invalid, parse_duration},
+        parse_uid,
+    },
+    typed, DumpParse,
+};
+
+const UID: &str = "Uid ";
+const PACKAGE: &str = "Package ";
+const ACCESS: &str = "Access: ";
+const REJECT: &str = "Reject: ";
+const DURATION: &str = "duration=";
+
+/// `AppOpsManager.MODE_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mode {
+    Allow,
+    /// Denied silently
+    Ignore,
+    /// Denied with an exception
+    Deny,
+    /// Decided by the permission of the op
+    Default,
+    /// Allowed while the app is in the foreground
+    Foreground,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for Mode {
+    fn from(name: &str) -> Self {
+        match name {
+            "allow" => Self::Allow,
+            "ignore" => Self::Ignore,
+            "deny" => Self::Deny,
+            "default" => Self::Default,
+            "foreground" => Self::Foreground,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// Whether an [`OpAccess`] was let through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccessKind {
+    Access,
+    Reject,
+}
+
+/// The last access to an op in one uid state
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpAccess {
+    pub kind: AccessKind,
+    /// Uid state and flags, as in `top-s` or `fg-s`
+    pub state: Option<String>,
+    /// As printed, in the local time of the device
+    pub time: Option<String>,
+    /// Time since the access
+    pub ago: Option<Duration>,
+    /// How long the op was held, for ops such as `CAMERA` that are started and finished
+    pub duration: Option<Duration>,
+    /// Attribution tag the app accessed the op with, on Android 11 and later
+    pub attribution_tag: Option<String>,
+}
+
+/// An op of a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Op {
+    /// As in `CAMERA`, `RECORD_AUDIO` or `FINE_LOCATION`
+    pub name: String,
+    pub mode: Mode,
+    pub accesses: Vec<OpAccess>,
+}
+
+impl Op {
+    /// The most recent access let through.
+    pub fn last_access(&self) -> Option<&OpAccess> {
+        self.accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Access)
+            .min_by_key(|access| access.ago.unwrap_or(Duration::MAX))
+    }
+}
+
+/// Ops of a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageOps {
+    pub uid: u32,
+    pub package: String,
+    pub ops: Vec<Op>,
+}
+
+impl PackageOps {
+    pub fn op(&self, name: &str) -> Option<&Op> {
+        self.ops.iter().find(|op| op.name == name)
+    }
+}
+
+/// Output of `dumpsys appops`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AppOps {
+    pub packages: Vec<PackageOps>,
+}
+
+impl AppOps {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut ops = Self::default();
+        let mut uid = None;
+        let mut attribution_tag = None;
+
+        for line in text.lines().map(str::trim) {
+            if let Some(owner) = line.strip_prefix(UID).and_then(|uid| uid.strip_suffix(':')) {
+                uid = Some(parse_uid(owner).ok_or_else(|| invalid("Uid", owner))?);
+                continue;
+            }
+            if let Some(package) = line
+                .strip_prefix(PACKAGE)
+                .and_then(|package| package.strip_suffix(':'))
+            {
+                ops.packages.push(PackageOps {
+                    uid: uid.ok_or_else(|| invalid("Package", package))?,
+                    package: package.to_owned(),
+                    ops: Vec::new(),
+                });
+                continue;
+            }
+            // Uid modes come before the packages of the uid.
+            let Some(package) = ops
+                .packages
+                .last_mut()
+                .filter(|package| Some(package.uid) == uid)
+            else {
+                continue;
+            };
+
+            if let Some(op) = op_header(line) {
+                attribution_tag = None;
+                package.ops.push(op);
+            } else if let Some(tag) = line.strip_suffix("=[") {
+                attribution_tag = (tag != "null").then(|| tag.to_owned());
+            } else if line == "]" {
+                attribution_tag = None;
+            } else if let Some(access) = access(line) {
+                let op = package
+                    .ops
+                    .last_mut()
+                    .ok_or_else(|| invalid("Access", line))?;
+                op.accesses.push(OpAccess {
+                    attribution_tag: attribution_tag.clone(),
+                    ..access
+                });
+            }
+        }
+
+        Ok(ops)
+    }
+
+    /// Ops of `package`, for the first uid it runs as.
+    pub fn package(&self, package: &str) -> Option<&PackageOps> {
+        self.packages.iter().find(|ops| ops.package == package)
+    }
+
+    /// Packages that accessed `op` in the last `within`.
+    pub fn users_of<'a>(
+        &'a self,
+        op: &'a str,
+        within: Duration,
+    ) -> impl Iterator<Item = &'a PackageOps> + 'a {
+        self.packages.iter().filter(move |package| {
+            package
+                .op(op)
+                .and_then(Op::last_access)
+                .and_then(|access| access.ago)
+                .is_some_and(|ago| ago <= within)
+        })
+    }
+
+    /// Dump the ops of `package` only.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::appops::AppOps;
+    ///
+    /// # fn foo() {
+    /// let ops = AppOps::for_package("com.example.app").unwrap();
+    /// for op in ops.packages.iter().flat_map(|package| &package.ops) {
+    ///     println!("{}: {:?}", op.name, op.mode);
+    /// }
+    /// # }
+    /// ```
+    pub fn for_package(package: &str) -> Result<Self, DumpError> {
+        typed::dump_service::<Self>(["--package", package])
+    }
+}
+
+impl DumpParse for AppOps {
+    const SERVICE: &'static str = "appops";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// `CAMERA (allow):`, `COARSE_LOCATION (ignore / switch FINE_LOCATION=ignore):` or, before Android 10,
+/// `WAKE_LOCK (allow): time=+1h2m ago; duration=+3s`.
+fn op_header(line: &str) -> Option<Op> {
+    let (name, rest) = line.split_once(" (")?;
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+    {
+        return None;
+    }
+    let (mode, fields) = rest.split_once("):")?;
+    let mode = mode.split([' ', '/']).next()?;
+
+    let mut accesses = Vec::new();
+    let field = |key: &str| {
+        fields
+            .split(';')
+            .find_map(|field| field.trim().strip_prefix(key))
+    };
+    if let Some(time) = field("time=") {
+        accesses.push(OpAccess {
+            kind: AccessKind::Access,
+            state: None,
+            time: None,
+            ago: parse_duration(time.trim_end_matches(" ago")),
+            duration: field(DURATION).and_then(parse_duration),
+            attribution_tag: None,
+        });
+    }
+    Some(Op {
+        name: name.to_owned(),
+        mode: mode.into(),
+        accesses,
+    })
+}
+
+/// `Access: [fg-s] 2024-01-01 12:00:00.000 (-5m) duration=+1s`
+fn access(line: &str) -> Option<OpAccess> {
+    let (kind, rest) = if let Some(rest) = line.strip_prefix(ACCESS) {
+        (AccessKind::Access, rest)
+    } else {
+        (AccessKind::Reject, line.strip_prefix(REJECT)?)
+    };
+    let (state, rest) = match rest
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+    {
+        Some((state, rest)) => (Some(state.to_owned()), rest),
+        None => (None, rest),
+    };
+    let (time, ago) = match rest.split_once(" (-") {
+        Some((time, rest)) => (time, rest.split(')').next().and_then(parse_duration)),
+        None => (rest.split(" duration=").next().unwrap_or(rest), None),
+    };
+    let duration = rest
+        .split(DURATION)
+        .nth(1)
+        .and_then(|duration| parse_duration(duration.split_whitespace().next()?));
+    Some(OpAccess {
+        kind,
+        state,
+        time: Some(time.trim().to_owned()).filter(|time| !time.is_empty()),
+        ago,
+        duration,
+        attribution_tag: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys appops` of Android 14, cut down to a few uids and ops
+    const DUMP: &str = "Current AppOps Service state:
+  Settings:
+    top_state_settle_time=+5s0ms
+    fg_service_state_settle_time=+5s0ms
+  Uid 1000:
+    state=pers
+    capability=LCMN
+    appWidgetVisible=false
+    Package android:
+      WAKE_LOCK (allow):
+        null=[
+          Access: [pers-s] 2024-03-12 09:12:40.012 (-4s106ms) duration=+15ms
+        ]
+    Package com.android.settings:
+      GET_USAGE_STATS (allow):
+  Uid u0a231:
+    state=top
+    capability=LCMN
+    appWidgetVisible=false
+      LEGACY_STORAGE: mode=allow
+    Package com.example.app:
+      CAMERA (allow):
+        null=[
+          Access: [top-s] 2024-03-12 09:05:31.124 (-7m13s120ms) duration=+12s401ms
+          Reject: [bg-s] 2024-03-12 08:00:00.000 (-1h12m44s)
+        ]
+      RECORD_AUDIO (allow):
+        voice=[
+          Access: [fg-s] 2024-03-12 08:30:00.001 (-42m44s) duration=+1m2s proxy[uid=10148, pkg=com.google.android.gms, attributionTag=null]
+        ]
+      COARSE_LOCATION (foreground):
+      READ_CLIPBOARD (ignore):
+        null=[
+          Reject: [top-s] 2024-03-12 09:10:00.000 (-2m44s)
+        ]
+      RUN_IN_BACKGROUND (allow / switch RUN_ANY_IN_BACKGROUND=allow):
+";
+
+    #[test]
+    fn parses_the_packages() {
+        let ops = AppOps::parse(DUMP).unwrap();
+        let packages: Vec<_> = ops
+            .packages
+            .iter()
+            .map(|package| (package.uid, package.package.as_str()))
+            .collect();
+        assert_eq!(
+            packages,
+            [
+                (1000, "android"),
+                (1000, "com.android.settings"),
+                (10231, "com.example.app"),
+            ]
+        );
+        let app = ops.package("com.example.app").unwrap();
+        let names: Vec<_> = app.ops.iter().map(|op| op.name.as_str()).collect();
+        // The uid modes aren't ops of the package
+        assert_eq!(
+            names,
+            [
+                "CAMERA",
+                "RECORD_AUDIO",
+                "COARSE_LOCATION",
+                "READ_CLIPBOARD",
+                "RUN_IN_BACKGROUND"
+            ]
+        );
+        assert_eq!(app.op("COARSE_LOCATION").unwrap().mode, Mode::Foreground);
+        assert_eq!(app.op("READ_CLIPBOARD").unwrap().mode, Mode::Ignore);
+        assert_eq!(app.op("RUN_IN_BACKGROUND").unwrap().mode, Mode::Allow);
+        assert!(ops.package("com.android.settings").unwrap().ops[0]
+            .accesses
+            .is_empty());
+    }
+
+    #[test]
+    fn parses_the_accesses() {
+        let ops = AppOps::parse(DUMP).unwrap();
+        let app = ops.package("com.example.app").unwrap();
+        let camera = app.op("CAMERA").unwrap();
+        assert_eq!(
+            camera.accesses,
+            [
+                OpAccess {
+                    kind: AccessKind::Access,
+                    state: Some("top-s".to_owned()),
+                    time: Some("2024-03-12 09:05:31.124".to_owned()),
+                    ago: Some(Duration::from_millis(433_120)),
+                    duration: Some(Duration::from_millis(12_401)),
+                    attribution_tag: None,
+                },
+                OpAccess {
+                    kind: AccessKind::Reject,
+                    state: Some("bg-s".to_owned()),
+                    time: Some("2024-03-12 08:00:00.000".to_owned()),
+                    ago: Some(Duration::from_secs(4364)),
+                    duration: None,
+                    attribution_tag: None,
+                },
+            ]
+        );
+        let audio = app.op("RECORD_AUDIO").unwrap().last_access().unwrap();
+        assert_eq!(audio.attribution_tag.as_deref(), Some("voice"));
+        assert_eq!(audio.duration, Some(Duration::from_secs(62)));
+        // Rejected accesses don't count as the last one
+        assert_eq!(app.op("READ_CLIPBOARD").unwrap().last_access(), None);
+    }
+
+    #[test]
+    fn users_of_an_op() {
+        let ops = AppOps::parse(DUMP).unwrap();
+        let minute = Duration::from_secs(60);
+        let users = |op, within| -> Vec<_> {
+            ops.users_of(op, within)
+                .map(|package| package.package.as_str())
+                .collect()
+        };
+        assert_eq!(users("CAMERA", 10 * minute), ["com.example.app"]);
+        assert!(users("CAMERA", 5 * minute).is_empty());
+        assert_eq!(users("WAKE_LOCK", minute), ["android"]);
+        assert!(users("READ_CLIPBOARD", 60 * minute).is_empty());
+    }
+
+    #[test]
+    fn unknown_modes_are_kept() {
+        let text = "  Uid u0a231:\n    Package com.example.app:\n      CAMERA (mode=5):\n";
+        let ops = AppOps::parse(text).unwrap();
+        assert_eq!(
+            ops.packages[0].ops[0].mode,
+            Mode::Other("mode=5".to_owned())
+        );
+    }
+
+    #[test]
+    fn accesses_of_older_releases() {
+        // Android 10 has no attribution tags, Android 9 prints the last access next to the mode
+        let text = "  Uid u0a231:
+    Package com.example.app:
+      CAMERA (allow):
+          Access: [fg-s] 2024-03-12 09:05:31.124 (-7m13s120ms) duration=+12s401ms
+      WAKE_LOCK (allow): time=+1h2m ago; duration=+3s
+      VIBRATE (default): time=+5m ago
+";
+        let ops = AppOps::parse(text).unwrap();
+        let app = &ops.packages[0];
+        let camera = app.op("CAMERA").unwrap().last_access().unwrap();
+        assert_eq!(camera.attribution_tag, None);
+        assert_eq!(camera.ago, Some(Duration::from_millis(433_120)));
+        let wake_lock = app.op("WAKE_LOCK").unwrap().last_access().unwrap();
+        assert_eq!(wake_lock.time, None);
+        assert_eq!(wake_lock.ago, Some(Duration::from_secs(3720)));
+        assert_eq!(wake_lock.duration, Some(Duration::from_secs(3)));
+        let vibrate = app.op("VIBRATE").unwrap();
+        assert_eq!(vibrate.mode, Mode::Default);
+        assert_eq!(vibrate.accesses[0].duration, None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(
+            AppOps::parse("  Uid u0x1:").unwrap_err(),
+            invalid("Uid", "u0x1")
+        );
+        assert_eq!(
+            AppOps::parse("    Package com.example.app:").unwrap_err(),
+            invalid("Package", "com.example.app")
+        );
+        let line = "Access: [top-s] 2024-03-12 09:05:31.124 (-7m13s120ms)";
+        let text = format!("  Uid u0a231:\n    Package com.example.app:\n      {line}\n");
+        assert_eq!(AppOps::parse(&text).unwrap_err(), invalid("Access", line));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(AppOps::parse(&crlf).unwrap(), AppOps::parse(DUMP).unwrap());
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(AppOps::parse("").unwrap(), AppOps::default());
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    future::Future,
+    io,
+    os::fd::{AsFd, OwnedFd},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll, Waker},
+    time::Instant,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, ReadBuf},
+    net::unix::pipe::Receiver,
+    task,
+    time::{self, Sleep},
+};
+
+use crate::{
+    cancel::CancelToken,
+    error::DumpError,
+    instrument,
+    observer::{Attempt, Observed},
+    owned_args,
+    pipe::{self, CANCEL_POLL_INTERVAL},
+    stats::Tally,
+    watchdog, Dumpsys, Execution, CHUNK_SIZE,
+};
+
+/// Output of an in-flight dump as a tokio [`AsyncRead`], returned by [`Dumpsys::dump_async_reader`]
+///
+/// Reading to the end waits for the dump transaction and fails if it didn't succeed. Reads fail with
+/// [`DumpError::Truncated`] past `max_output_bytes` and with [`DumpError::Cancelled`] once the cancel
+/// token fires, wrapped in an [`io::Error`] like the other failures.
+/// Dropping the reader early closes the pipe so the service stops writing.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::Dumpsys;
+/// use tokio::io::{AsyncBufReadExt, BufReader};
+///
+/// # async fn foo() -> Option<()> {
+/// let reader = Dumpsys::new_async("window")
+///     .await?
+///     .dump_async_reader(&["windows"])
+///     .unwrap();
+/// let mut lines = BufReader::new(reader).lines();
+/// while let Some(line) = lines.next_line().await.unwrap() {
+///     println!("{line}");
+/// }
+/// # Some(())
+/// # }
+/// ```
+pub struct AsyncDumpReader {
+    read: Receiver,
+    handle: Option<Completion>,
+    attempt: Attempt,
+    tally: Tally,
+    observed: Option<Observed>,
+    bytes_read: u64,
+    limit: Option<u64>,
+    cancel: Option<CancelToken>,
+    /// Wakes a pending read now and then to look at `cancel`, which has no waker of its own
+    cancel_timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl AsyncDumpReader {
+    /// Stop reading and return the final status of the dump transaction.
+    ///
+    /// Returns `Ok` if a read already reported how the dump ended.
+    pub async fn finish(mut self) -> Result<(), DumpError> {
+        drop(self.read);
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+        let bytes_read = self.bytes_read;
+        let status = handle.await.map_err(|err| err.after_output(bytes_read));
+        self.tally.record();
+        self.attempt.finish(Some(bytes_read), status)
+    }
+
+    fn poll_pipe(&mut self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.read).poll_read(cx, buf);
+        if poll.is_pending() && self.cancel.is_some() {
+            let timer = self
+                .cancel_timer
+                .get_or_insert_with(|| Box::pin(time::sleep(CANCEL_POLL_INTERVAL)));
+            if timer.as_mut().poll(cx).is_ready() {
+                timer
+                    .as_mut()
+                    .reset(time::Instant::now() + CANCEL_POLL_INTERVAL);
+                cx.waker().wake_by_ref();
+            }
+        }
+        poll
+    }
+
+    /// At the end of the output, wait for the transaction and report how the dump ended.
+    fn poll_finish(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(handle) = self.handle.as_mut() {
+            let status = ready!(Pin::new(handle).poll(cx));
+            self.handle = None;
+            let status = status.map_err(|err| err.after_output(self.bytes_read));
+            self.tally.record();
+            self.attempt
+                .finish(Some(self.bytes_read), status)
+                .map_err(io::Error::other)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// End the dump with `err`, leaving the transaction to return on its own once the pipe is closed.
+    fn fail(&mut self, err: DumpError) -> io::Error {
+        if self.handle.take().is_none() {
+            return io::Error::other(err);
+        }
+        self.tally.record();
+        io::Error::other(self.attempt.fail(Some(self.bytes_read), err))
+    }
+}
+
+impl AsyncRead for AsyncDumpReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Poll::Ready(Err(this.fail(DumpError::cancelled())));
+        }
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let left = this
+            .limit
+            .map_or(u64::MAX, |limit| limit.saturating_sub(this.bytes_read));
+        if left == 0 {
+            // Output of exactly the limit isn't truncated, so look for one more byte.
+            let mut probe = [0u8; 1];
+            let mut probe = ReadBuf::new(&mut probe);
+            ready!(this.poll_pipe(cx, &mut probe))?;
+            this.tally.read(0, 1);
+            if !probe.filled().is_empty() {
+                return Poll::Ready(Err(this.fail(DumpError::truncated(this.bytes_read))));
+            }
+            return this.poll_finish(cx);
+        }
+
+        let len = usize::try_from(left).map_or(buf.remaining(), |left| left.min(buf.remaining()));
+        let mut limited = ReadBuf::new(buf.initialize_unfilled_to(len));
+        ready!(this.poll_pipe(cx, &mut limited))?;
+        let n = limited.filled().len();
+        buf.advance(n);
+
+        let chunk = &buf.filled()[buf.filled().len() - n..];
+        this.bytes_read += n as u64;
+        this.tally.read(n as u64, 1);
+        if let (Some(observed), false) = (&this.observed, chunk.is_empty()) {
+            observed.chunk(chunk);
+        }
+        if n == 0 {
+            return this.poll_finish(cx);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Dumpsys {
+    /// Like [`Dumpsys::new`], without blocking the async runtime while the service is looked up.
+    pub async fn new_async<S>(service_name: S) -> Option<Self>
+    where
+        S: AsRef<str>,
+    {
+        let service_name = service_name.as_ref().to_owned();
+        task::spawn_blocking(move || Self::new(service_name))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Like [`Dumpsys::dump`], reading the pipe through tokio instead of a dedicated thread.
+    ///
+    /// The binder transaction itself runs as the [`Execution`] of the builder says, except that
+    /// [`Execution::CurrentThread`] runs it on tokio's blocking pool rather than blocking the runtime
+    /// that reads its output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # async fn foo() -> Option<()> {
+    /// let result = Dumpsys::new_async("SurfaceFlinger")
+    ///     .await?
+    ///     .dump_async(&["--latency"])
+    ///     .await
+    ///     .unwrap();
+    /// println!("{result}");
+    /// # Some(())
+    /// # }
+    /// ```
+    pub async fn dump_async(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, DumpError> {
+        let args = owned_args(args);
+        let mut attempts = 1;
+        let mut reconnected = false;
+        let mut tally = self.tally();
+
+        let result = instrument::dump_async(&self.service_name, &args, async {
+            loop {
+                match self.dump_async_once(args.clone(), &mut tally).await {
+                    Err(err) => match self.recover(&err, &mut attempts, &mut reconnected) {
+                        Some(delay) => {
+                            tally.retried();
+                            time::sleep(delay).await;
+                        }
+                        None => return Err(err.with_context(&self.service_name, &args)),
+                    },
+                    result => return result,
+                }
+            }
+        })
+        .await;
+        tally.record();
+        result
+    }
+
+    async fn dump_async_once(
+        &self,
+        args: Vec<String>,
+        tally: &mut Tally,
+    ) -> Result<String, DumpError> {
+        let attempt = self.attempt(&args);
+        let mut bytes_read = None;
+        let result = self
+            .read_async_once(args, attempt.observed(), &mut bytes_read, tally)
+            .await;
+        attempt.finish(bytes_read, result)
+    }
+
+    /// An attempt of [`Dumpsys::dump_async`], setting `bytes` once the output was read.
+    async fn read_async_once(
+        &self,
+        args: Vec<String>,
+        observed: Option<Observed>,
+        bytes: &mut Option<u64>,
+        tally: &mut Tally,
+    ) -> Result<String, DumpError> {
+        let (mut read, handle) = self.spawn_async(args)?;
+        let (limit, cancel) = (self.config.max_output_bytes, self.config.cancel.as_ref());
+        let mut buf = Vec::with_capacity(self.config.buffer_capacity);
+        let mut reads = 0;
+        let mut truncated = false;
+
+        let read_all = async {
+            let mut chunk = vec![0u8; CHUNK_SIZE];
+            loop {
+                let left = limit.map_or(u64::MAX, |limit| limit.saturating_sub(buf.len() as u64));
+                // Output of exactly the limit isn't truncated, so look for one more byte.
+                let len = usize::try_from(left)
+                    .unwrap_or(usize::MAX)
+                    .clamp(1, CHUNK_SIZE);
+                let n = read_chunk(&mut read, &mut chunk[..len], cancel).await?;
+                reads += 1;
+                if n == 0 {
+                    return io::Result::Ok(());
+                }
+                if left == 0 {
+                    truncated = true;
+                    return Ok(());
+                }
+                if let Some(observed) = &observed {
+                    observed.chunk(&chunk[..n]);
+                }
+                buf.extend_from_slice(&chunk[..n]);
+            }
+        };
+        let result = match self.config.dump_timeout {
+            Some(timeout) => time::timeout(timeout, read_all).await.ok(),
+            None => Some(read_all.await),
+        };
+        drop(read);
+        *bytes = Some(buf.len() as u64);
+        tally.read(buf.len() as u64, reads);
+
+        let Some(result) = result else {
+            return Err(DumpError::timeout().with_partial(|| buf));
+        };
+        if let Err(err) = result {
+            return Err(DumpError::from(err).with_partial(|| buf));
+        }
+        if truncated {
+            return Err(DumpError::truncated(buf.len() as u64).with_partial(|| buf));
+        }
+        if let Err(err) = handle.await {
+            return Err(err.after_output(buf.len() as u64).with_partial(|| buf));
+        }
+        String::from_utf8(buf).map_err(DumpError::invalid_utf8)
+    }
+
+    /// Start a dump and read its output through tokio, see [`AsyncDumpReader`].
+    pub fn dump_async_reader(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<AsyncDumpReader, DumpError> {
+        let args = owned_args(args);
+        let (attempt, tally) = (self.attempt(&args), self.tally());
+        let (read, handle) = self.spawn_async(args).map_err(|err| {
+            tally.record();
+            attempt.fail(None, err)
+        })?;
+        Ok(AsyncDumpReader {
+            read,
+            handle: Some(handle),
+            observed: attempt.observed(),
+            attempt,
+            tally,
+            bytes_read: 0,
+            limit: self.config.max_output_bytes,
+            cancel: self.config.cancel.clone(),
+            cancel_timer: None,
+        })
+    }
+
+    /// Start dumping per [`Execution`], returning a non-blocking read end registered with tokio.
+    pub(crate) fn spawn_async(
+        &self,
+        args: Vec<String>,
+    ) -> Result<(Receiver, Completion), DumpError> {
+        if self.cancelled() {
+            return Err(DumpError::cancelled());
+        }
+        if self.is_suspect() {
+            return Err(DumpError::timeout());
+        }
+        let mut service = self.service();
+        let (read, write) = pipe::pipe(self.config.pipe_size)?;
+        let read = Receiver::from_owned_fd(read.into())?;
+        let (backend, service_name) = (self.config.backend.clone(), self.service_name.clone());
+        let timeout = self.config.dump_timeout;
+        let write = Arc::new(OwnedFd::from(write));
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let watch = watchdog::watch(&self.health, &self.service_name, deadline, &write);
+        let mut dump =
+            move || backend.dump(&mut service, &service_name, &args, timeout, write.as_fd());
+        let (complete, completion) = completion();
+
+        match &self.config.execution {
+            Execution::Spawn => {
+                let threads = self.config.threads.clone();
+                threads.builder().spawn(move || {
+                    let _watch = watch;
+                    complete.send(
+                        threads
+                            .apply()
+                            .map_err(DumpError::from)
+                            .and_then(|()| dump()),
+                    );
+                })?;
+            }
+            Execution::Pool(pool) => {
+                // The result goes to `complete` rather than to a transaction nothing waits on.
+                pool.submit(
+                    move || {
+                        complete.send(dump());
+                        Ok(())
+                    },
+                    watch,
+                );
+            }
+            Execution::CurrentThread => {
+                task::spawn_blocking(move || {
+                    let _watch = watch;
+                    complete.send(dump());
+                });
+            }
+        }
+
+        Ok((read, completion))
+    }
+}
+
+/// Read into `chunk`, failing like a blocking read once `cancel` fires.
+async fn read_chunk(
+    read: &mut Receiver,
+    chunk: &mut [u8],
+    cancel: Option<&CancelToken>,
+) -> io::Result<usize> {
+    let Some(cancel) = cancel else {
+        return read.read(chunk).await;
+    };
+    loop {
+        if cancel.is_cancelled() {
+            return Err(pipe::cancelled());
+        }
+        // Reading is cancel safe, so nothing is lost when the interval runs out first.
+        if let Ok(result) = time::timeout(CANCEL_POLL_INTERVAL, read.read(chunk)).await {
+            return result;
+        }
+    }
+}
+
+/// Where a transaction leaves its result for the [`Completion`] awaiting it
+#[derive(Default)]
+struct Slot {
+    result: Option<Result<(), DumpError>>,
+    waker: Option<Waker>,
+}
+
+/// The result of a dump transaction running off the runtime, failing with [`DumpError::Io`] if its
+/// thread panicked
+pub(crate) struct Completion(Arc<Mutex<Slot>>);
+
+/// Sending end of a [`Completion`], moved to the thread running the transaction
+struct Complete(Arc<Mutex<Slot>>);
+
+fn completion() -> (Complete, Completion) {
+    let slot = Arc::new(Mutex::new(Slot::default()));
+    (Complete(slot.clone()), Completion(slot))
+}
+
+impl Complete {
+    fn send(self, result: Result<(), DumpError>) {
+        self.set(result);
+    }
+
+    fn set(&self, result: Result<(), DumpError>) {
+        let mut slot = self.0.lock().unwrap();
+        slot.result.get_or_insert(result);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Complete {
+    fn drop(&mut self) {
+        // A no-op after `send`, only a panic drops it without a result.
+        self.set(Err(io::Error::other("dump transaction panicked").into()));
+    }
+}
+
+impl Future for Completion {
+    type Output = Result<(), DumpError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,625 @@
+//! Typed output of `dumpsys audio`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::audio::{AudioState, PlayerState, RingerMode, Stream};
+//!
+//! let audio = AudioState::parse(
+//!     "
+//! Stream volumes (device: index)
+//! - STREAM_RING:
+//!    Muted: false
+//!    Min: 0
+//!    Max: 7
+//!    Current: 2 (speaker): 5, 40000000 (default): 5
+//!    Devices: speaker
+//! - STREAM_MUSIC:
+//!    Muted: false
+//!    Min: 0
+//!    Max: 25
+//!    streamVolume:12
+//!    Current: 2 (speaker): 10, 80 (bt_a2dp): 12, 40000000 (default): 10
+//!    Devices: bt_a2dp
+//!
+//! Ringer mode:
+//! - mode (internal) = VIBRATE
+//! - mode (external) = VIBRATE
+//!
+//!   Connected devices:
+//!   [DeviceInfo: type:0x80 (bt_a2dp) name:Pixel Buds addr:00:11:22:33:44:55 codec: 0]
+//!
+//! Playback activity manager:
+//!   players:
+//!   AudioPlaybackConfiguration piid:15 deviceId:3 type:android.media.AudioTrack u/pid:10123/4567 state:started attr:AudioAttributes: usage=USAGE_MEDIA content=CONTENT_TYPE_MUSIC flags=0x800 tags= bundle=null sessionId:97
+//!   AudioPlaybackConfiguration piid:23 deviceId:0 type:android.media.SoundPool u/pid:1000/1234 state:idle attr:AudioAttributes: usage=USAGE_ASSISTANCE_SONIFICATION content=CONTENT_TYPE_SONIFICATION flags=0x0 tags= bundle=null sessionId:0",
+//! )
+//! .unwrap();
+//!
+//! let music = audio.stream(&Stream::Music).unwrap();
+//! assert_eq!(music.max, Some(25));
+//! assert_eq!(music.current.len(), 3);
+//! assert_eq!(music.current[1].device, 0x80);
+//! assert_eq!(music.volume("speaker"), Some(10));
+//! assert_eq!(music.active_volume(), Some(12));
+//! assert_eq!(audio.ringer_mode, RingerMode::Vibrate);
+//!
+//! assert_eq!(audio.devices[0].type_name.as_deref(), Some("bt_a2dp"));
+//! assert_eq!(audio.devices[0].name.as_deref(), Some("Pixel Buds"));
+//! assert_eq!(audio.devices[0].address.as_deref(), Some("00:11:22:33:44:55"));
+//!
+//! let playing: Vec<_> = audio.active_playbacks().collect();
+//! assert_eq!(playing.len(), 1);
+//! assert_eq!(playing[0].uid, Some(10123));
+//! assert_eq!(playing[0].state, PlayerState::Started);
+//! assert_eq!(playing[0].usage.as_deref(), Some("USAGE_MEDIA"));
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, KeyValues},
+    typed, DumpParse,
+};
+
+const STREAM_VOLUMES: &str = "Stream volumes";
+const RINGER_MODE: &str = "- mode (internal) = ";
+const DEVICE_INFO: &str = "[DeviceInfo: ";
+const PLAYBACK: &str = "AudioPlaybackConfiguration ";
+const DEFAULT_DEVICE: &str = "default";
+
+/// `AudioManager.STREAM_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stream {
+    VoiceCall,
+    System,
+    Ring,
+    Music,
+    Alarm,
+    Notification,
+    BluetoothSco,
+    SystemEnforced,
+    Dtmf,
+    Tts,
+    Accessibility,
+    Assistant,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for Stream {
+    fn from(name: &str) -> Self {
+        match name {
+            "STREAM_VOICE_CALL" => Self::VoiceCall,
+            "STREAM_SYSTEM" => Self::System,
+            "STREAM_RING" => Self::Ring,
+            "STREAM_MUSIC" => Self::Music,
+            "STREAM_ALARM" => Self::Alarm,
+            "STREAM_NOTIFICATION" => Self::Notification,
+            "STREAM_BLUETOOTH_SCO" => Self::BluetoothSco,
+            "STREAM_SYSTEM_ENFORCED" => Self::SystemEnforced,
+            "STREAM_DTMF" => Self::Dtmf,
+            "STREAM_TTS" => Self::Tts,
+            "STREAM_ACCESSIBILITY" => Self::Accessibility,
+            "STREAM_ASSISTANT" => Self::Assistant,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// Volume index of a stream on one output device
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceVolume {
+    /// `AudioSystem.DEVICE_OUT_*` bit
+    pub device: u32,
+    /// As in `speaker` or `bt_a2dp`, on Android 7 and later
+    pub name: Option<String>,
+    pub index: u32,
+}
+
+/// Volume of a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamVolume {
+    pub stream: Stream,
+    pub muted: Option<bool>,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    /// Index on each device the stream was set for, including `default`
+    pub current: Vec<DeviceVolume>,
+    /// Devices the stream currently plays on
+    pub devices: Vec<String>,
+}
+
+impl StreamVolume {
+    /// Index on the device named `device`, falling back to the default index.
+    pub fn volume(&self, device: &str) -> Option<u32> {
+        let index = |name: &str| {
+            self.current
+                .iter()
+                .find(|volume| volume.name.as_deref() == Some(name))
+                .map(|volume| volume.index)
+        };
+        index(device).or_else(|| index(DEFAULT_DEVICE))
+    }
+
+    /// Index on the device the stream currently plays on.
+    pub fn active_volume(&self) -> Option<u32> {
+        self.volume(self.devices.first().map_or(DEFAULT_DEVICE, String::as_str))
+    }
+}
+
+/// `AudioManager.RINGER_MODE_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RingerMode {
+    Silent,
+    Vibrate,
+    #[default]
+    Normal,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for RingerMode {
+    fn from(name: &str) -> Self {
+        match name {
+            "SILENT" => Self::Silent,
+            "VIBRATE" => Self::Vibrate,
+            "NORMAL" => Self::Normal,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// `AudioPlaybackConfiguration.PLAYER_STATE_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayerState {
+    Idle,
+    Started,
+    Paused,
+    Stopped,
+    Released,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for PlayerState {
+    fn from(name: &str) -> Self {
+        match name {
+            "idle" => Self::Idle,
+            "started" => Self::Started,
+            "paused" => Self::Paused,
+            "stopped" => Self::Stopped,
+            "released" => Self::Released,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A player known to the playback activity manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Playback {
+    /// Player interface id
+    pub piid: i64,
+    /// As in `android.media.AudioTrack`
+    pub player_type: Option<String>,
+    pub uid: Option<u32>,
+    pub pid: Option<u32>,
+    pub state: PlayerState,
+    /// As in `USAGE_MEDIA`
+    pub usage: Option<String>,
+    /// As in `CONTENT_TYPE_MUSIC`
+    pub content_type: Option<String>,
+    pub session_id: Option<i64>,
+}
+
+/// A connected audio device, such as headphones
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioDevice {
+    /// `AudioSystem.DEVICE_*` bit
+    pub device_type: u32,
+    /// As in `bt_a2dp`
+    pub type_name: Option<String>,
+    pub name: Option<String>,
+    pub address: Option<String>,
+}
+
+/// Output of `dumpsys audio`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioState {
+    pub streams: Vec<StreamVolume>,
+    pub ringer_mode: RingerMode,
+    pub playbacks: Vec<Playback>,
+    pub devices: Vec<AudioDevice>,
+}
+
+impl AudioState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut audio = Self::default();
+        let mut in_streams = false;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if trimmed.starts_with(STREAM_VOLUMES) {
+                in_streams = true;
+                continue;
+            }
+            if let Some(mode) = trimmed.strip_prefix(RINGER_MODE) {
+                audio.ringer_mode = mode.trim().into();
+            } else if let Some(device) = trimmed.strip_prefix(DEVICE_INFO) {
+                audio.devices.push(audio_device(device)?);
+            } else if let Some(config) = trimmed.strip_prefix(PLAYBACK) {
+                audio.playbacks.push(playback(config)?);
+            } else if !line.starts_with(char::is_whitespace) && !line.starts_with("- ") {
+                in_streams = false;
+            } else if in_streams {
+                if let Some(stream) = trimmed
+                    .strip_prefix("- ")
+                    .and_then(|stream| stream.strip_suffix(':'))
+                {
+                    audio.streams.push(StreamVolume {
+                        stream: stream.into(),
+                        muted: None,
+                        min: None,
+                        max: None,
+                        current: Vec::new(),
+                        devices: Vec::new(),
+                    });
+                } else if let Some(stream) = audio.streams.last_mut() {
+                    stream.line(trimmed)?;
+                }
+            }
+        }
+
+        Ok(audio)
+    }
+
+    pub fn stream(&self, stream: &Stream) -> Option<&StreamVolume> {
+        self.streams.iter().find(|volume| volume.stream == *stream)
+    }
+
+    /// Players currently started.
+    pub fn active_playbacks(&self) -> impl Iterator<Item = &Playback> + '_ {
+        self.playbacks
+            .iter()
+            .filter(|playback| playback.state == PlayerState::Started)
+    }
+}
+
+impl DumpParse for AudioState {
+    const SERVICE: &'static str = "audio";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `audio` and return the music volume on the device it currently plays on.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// println!("{:?}", dumpsys_rs::audio::media_volume().unwrap());
+/// # }
+/// ```
+pub fn media_volume() -> Result<Option<u32>, DumpError> {
+    let audio = typed::dump_service::<AudioState>(Vec::<&str>::new())?;
+    Ok(audio
+        .stream(&Stream::Music)
+        .and_then(StreamVolume::active_volume))
+}
+
+impl StreamVolume {
+    /// A `Key: value` line under `- STREAM_MUSIC:`.
+    fn line(&mut self, line: &str) -> Result<(), ParseError> {
+        let Some((key, value)) = line.split_once(':') else {
+            return Ok(());
+        };
+        let value = value.trim();
+        let number = || value.parse().map_err(|_| invalid(key, value));
+        match key {
+            "Muted" => self.muted = Some(value == "true"),
+            "Min" => self.min = Some(number()?),
+            "Max" => self.max = Some(number()?),
+            "Current" => {
+                self.current = value
+                    .split(", ")
+                    .map(|volume| device_volume(volume).ok_or_else(|| invalid(key, volume)))
+                    .collect::<Result<_, _>>()?;
+            }
+            "Devices" => {
+                self.devices = value
+                    .split([',', ' '])
+                    .filter(|device| !device.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// `80 (bt_a2dp): 12`, or `80: 12` before Android 7
+fn device_volume(text: &str) -> Option<DeviceVolume> {
+    let (device, index) = text.rsplit_once(": ")?;
+    let (device, name) = match device.split_once(" (") {
+        Some((device, name)) => (device, Some(name.trim_end_matches(')').to_owned())),
+        None => (device, None),
+    };
+    Some(DeviceVolume {
+        device: u32::from_str_radix(device, 16).ok()?,
+        name,
+        index: index.trim().parse().ok()?,
+    })
+}
+
+/// `type:0x80 (bt_a2dp) name:Pixel Buds addr:00:11:22:33:44:55 codec: 0]`
+fn audio_device(text: &str) -> Result<AudioDevice, ParseError> {
+    let between = |start: &str, end: &str| {
+        let (_, rest) = text.split_once(start)?;
+        let value = rest.split(end).next().unwrap_or(rest).trim();
+        Some(value.to_owned()).filter(|value| !value.is_empty() && value != "null")
+    };
+    let device_type = text
+        .strip_prefix("type:0x")
+        .and_then(|rest| rest.split(' ').next())
+        .and_then(|device| u32::from_str_radix(device, 16).ok())
+        .ok_or_else(|| invalid("DeviceInfo", text))?;
+    Ok(AudioDevice {
+        device_type,
+        type_name: between(" (", ")"),
+        name: between(" name:", " addr:"),
+        address: between(" addr:", " codec:"),
+    })
+}
+
+/// `piid:15 deviceId:3 type:android.media.AudioTrack u/pid:10123/4567 state:started attr:AudioAttributes: usage=USAGE_MEDIA ... sessionId:97`
+fn playback(text: &str) -> Result<Playback, ParseError> {
+    let word = |key: &str| {
+        text.split_whitespace()
+            .find_map(|word| word.strip_prefix(key))
+    };
+    let attributes = KeyValues::parse(text);
+    let (uid, pid) = word("u/pid:")
+        .and_then(|ids| ids.split_once('/'))
+        .map_or((None, None), |(uid, pid)| {
+            (uid.parse().ok(), pid.parse().ok())
+        });
+    Ok(Playback {
+        piid: word("piid:")
+            .and_then(|piid| piid.parse().ok())
+            .ok_or_else(|| invalid("piid", text))?,
+        player_type: word("type:").map(str::to_owned),
+        uid,
+        pid,
+        state: word("state:").unwrap_or_default().into(),
+        usage: attributes.get("usage").map(str::to_owned),
+        content_type: attributes.get("content").map(str::to_owned),
+        session_id: word("sessionId:").and_then(|session| session.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys audio` of Android 14, cut down to a few streams and players
+    const DUMP: &str = "MESSAGES QUEUE:
+  MSG_SET_DEVICE_VOLUME
+
+Stream volumes (device: index)
+- STREAM_VOICE_CALL:
+   Muted: false
+   Muted Internally: false
+   Min: 1
+   Max: 7
+   streamVolume:4
+   Current: 2 (speaker): 4, 40000000 (default): 4
+   Devices: speaker
+- STREAM_RING:
+   Muted: true
+   Muted Internally: false
+   Min: 0
+   Max: 7
+   streamVolume:0
+   Current: 2 (speaker): 5, 40000000 (default): 5
+   Devices: speaker
+- STREAM_MUSIC:
+   Muted: false
+   Muted Internally: false
+   Min: 0
+   Max: 25
+   streamVolume:12
+   Current: 2 (speaker): 10, 80 (bt_a2dp): 12, 40000000 (default): 10
+   Devices: bt_a2dp
+
+Ringer mode:
+- mode (internal) = SILENT
+- mode (external) = SILENT
+- zen mode:3
+- ringer mode affected streams = 0x00000026 (STREAM_SYSTEM STREAM_RING STREAM_NOTIFICATION)
+- ringer mode muted streams = 0x00000026 (STREAM_SYSTEM STREAM_RING STREAM_NOTIFICATION)
+
+Audio routes:
+  mMainType=0x0
+  mBluetoothName=Pixel Buds
+
+  Connected devices:
+  [DeviceInfo: type:0x80 (bt_a2dp) name:Pixel Buds addr:00:11:22:33:44:55 codec: 0 peer addr:null]
+  [DeviceInfo: type:0x4 (headset) name: addr: codec: 0 peer addr:null]
+
+Playback activity manager:
+  players:
+  AudioPlaybackConfiguration piid:15 deviceId:3 type:android.media.AudioTrack u/pid:10123/4567 state:started attr:AudioAttributes: usage=USAGE_MEDIA content=CONTENT_TYPE_MUSIC flags=0x800 tags= bundle=null sessionId:97
+  AudioPlaybackConfiguration piid:23 deviceId:0 type:android.media.SoundPool u/pid:1000/1234 state:released attr:AudioAttributes: usage=USAGE_ASSISTANCE_SONIFICATION content=CONTENT_TYPE_SONIFICATION flags=0x0 tags= bundle=null sessionId:0
+";
+
+    #[test]
+    fn parses_the_stream_volumes() {
+        let audio = AudioState::parse(DUMP).unwrap();
+        assert_eq!(audio.streams.len(), 3);
+        assert_eq!(
+            audio.stream(&Stream::Ring).unwrap(),
+            &StreamVolume {
+                stream: Stream::Ring,
+                muted: Some(true),
+                min: Some(0),
+                max: Some(7),
+                current: vec![
+                    DeviceVolume {
+                        device: 0x2,
+                        name: Some("speaker".to_owned()),
+                        index: 5,
+                    },
+                    DeviceVolume {
+                        device: 0x4000_0000,
+                        name: Some("default".to_owned()),
+                        index: 5,
+                    },
+                ],
+                devices: vec!["speaker".to_owned()],
+            }
+        );
+        let music = audio.stream(&Stream::Music).unwrap();
+        assert_eq!(music.active_volume(), Some(12));
+        assert_eq!(music.volume("wired_headset"), Some(10));
+        assert_eq!(audio.stream(&Stream::Alarm), None);
+        assert_eq!(audio.ringer_mode, RingerMode::Silent);
+    }
+
+    #[test]
+    fn parses_the_devices_and_players() {
+        let audio = AudioState::parse(DUMP).unwrap();
+        assert_eq!(
+            audio.devices,
+            [
+                AudioDevice {
+                    device_type: 0x80,
+                    type_name: Some("bt_a2dp".to_owned()),
+                    name: Some("Pixel Buds".to_owned()),
+                    address: Some("00:11:22:33:44:55".to_owned()),
+                },
+                // Wired headsets have neither a name nor an address
+                AudioDevice {
+                    device_type: 0x4,
+                    type_name: Some("headset".to_owned()),
+                    name: None,
+                    address: None,
+                },
+            ]
+        );
+        assert_eq!(
+            audio.playbacks[0],
+            Playback {
+                piid: 15,
+                player_type: Some("android.media.AudioTrack".to_owned()),
+                uid: Some(10123),
+                pid: Some(4567),
+                state: PlayerState::Started,
+                usage: Some("USAGE_MEDIA".to_owned()),
+                content_type: Some("CONTENT_TYPE_MUSIC".to_owned()),
+                session_id: Some(97),
+            }
+        );
+        assert_eq!(audio.playbacks[1].state, PlayerState::Released);
+        assert_eq!(audio.active_playbacks().count(), 1);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            AudioState::parse(&DUMP.replace('\n', "\r\n")),
+            AudioState::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(AudioState::parse("").unwrap(), AudioState::default());
+    }
+
+    #[test]
+    fn volumes_of_older_releases() {
+        // Android 6 prints the device bits without their names
+        let text = "Stream volumes (device: index)
+- STREAM_MUSIC:
+   Muted: false
+   Min: 0
+   Max: 15
+   Current: 2: 8, 40000000: 6
+   Devices: speaker
+";
+        let audio = AudioState::parse(text).unwrap();
+        let music = audio.stream(&Stream::Music).unwrap();
+        assert_eq!(
+            music.current[0],
+            DeviceVolume {
+                device: 0x2,
+                name: None,
+                index: 8,
+            }
+        );
+        // Without names there is no device to look up
+        assert_eq!(music.active_volume(), None);
+    }
+
+    #[test]
+    fn unknown_values_are_kept() {
+        let text = "Stream volumes (device: index)
+- STREAM_CALL_ASSISTANT:
+   Max: 15
+
+Ringer mode:
+- mode (internal) = LOUD
+
+  AudioPlaybackConfiguration piid:7 type:android.media.MediaPlayer state:buffering
+";
+        let audio = AudioState::parse(text).unwrap();
+        assert_eq!(
+            audio.streams[0].stream,
+            Stream::Other("STREAM_CALL_ASSISTANT".to_owned())
+        );
+        assert_eq!(audio.ringer_mode, RingerMode::Other("LOUD".to_owned()));
+        let player = &audio.playbacks[0];
+        assert_eq!(player.state, PlayerState::Other("buffering".to_owned()));
+        assert_eq!(player.uid, None);
+        assert_eq!(player.usage, None);
+        assert_eq!(player.session_id, None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let stream = |line: &str| {
+            AudioState::parse(&format!(
+                "{STREAM_VOLUMES} (device: index)\n- STREAM_MUSIC:\n   {line}\n"
+            ))
+        };
+        assert_eq!(stream("Max: many").unwrap_err(), invalid("Max", "many"));
+        // A line cut short in the middle of the volumes
+        assert_eq!(
+            stream("Current: 2 (speaker): 10, 80 (bt_a2").unwrap_err(),
+            invalid("Current", "80 (bt_a2")
+        );
+
+        assert_eq!(
+            AudioState::parse("  [DeviceInfo: type:headset]").unwrap_err(),
+            invalid("DeviceInfo", "type:headset]")
+        );
+        let line = "deviceId:3 type:android.media.AudioTrack state:started";
+        assert_eq!(
+            AudioState::parse(&format!("  {PLAYBACK}{line}")).unwrap_err(),
+            invalid("piid", line)
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    os::{fd::BorrowedFd, unix::process::ExitStatusExt},
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+    time::Duration,
+};
+
+use binder::{binder_impl::IBinderInternal, SpIBinder, StatusCode};
+
+use crate::{
+    borrowed_args,
+    error::DumpError,
+    recording::{Recorder, Replay},
+};
+
+/// The `dumpsys` binary of the device
+const DUMPSYS: &str = "/system/bin/dumpsys";
+/// `-t` of `dumpsys` when there is no dump timeout, rather than its default of 10 seconds
+const NO_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// How dumps reach the service, chosen with [`DumpsysBuilder::backend`](crate::DumpsysBuilder::backend)
+///
+/// The service is looked up through servicemanager either way, so
+/// [`Dumpsys::as_binder`](crate::Dumpsys::as_binder) and the other binder calls keep working; only the
+/// dump moves. That holds for [`Backend::Replay`]
+/// too, which off-device can register the recorded services with [`mock`](crate::mock) itself.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{Backend, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// // SELinux may deny the dump transaction to the domain of this process.
+/// let dumpsys = Dumpsys::builder("SurfaceFlinger")
+///     .backend(Backend::fallback())
+///     .build()?;
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The `dump` binder transaction, with the service writing straight into a pipe of this process
+    #[default]
+    Binder,
+    /// Run the `dumpsys` binary at this path and read its stdout, see [`Backend::subprocess`]
+    ///
+    /// Slower, as it forks and output is copied once more, but it works where the dump transaction
+    /// doesn't. A failure is read from the exit status and stderr of `dumpsys`, and its `-t` option is
+    /// set from the dump timeout.
+    Subprocess(PathBuf),
+    /// The binder transaction, running the `dumpsys` binary at this path instead when the service
+    /// refuses it with [`DumpError::PermissionDenied`]
+    Fallback(PathBuf),
+    /// Dump through the backend of the [`Recorder`], saving every dump to its directory
+    Record(Recorder),
+    /// Serve the dumps recorded in a directory instead of asking the service, see [`Replay`]
+    Replay(Replay),
+}
+
+impl Backend {
+    /// Run `/system/bin/dumpsys`.
+    pub fn subprocess() -> Self {
+        Self::Subprocess(DUMPSYS.into())
+    }
+
+    /// The binder transaction, with `/system/bin/dumpsys` when it's denied.
+    pub fn fallback() -> Self {
+        Self::Fallback(DUMPSYS.into())
+    }
+
+    /// Dump `service_name` into `fd`, returning once the dump is complete.
+    pub(crate) fn dump(
+        &self,
+        service: &mut SpIBinder,
+        service_name: &str,
+        args: &[String],
+        timeout: Option<Duration>,
+        fd: BorrowedFd<'_>,
+    ) -> Result<(), DumpError> {
+        let program = match self {
+            Self::Binder => return Ok(service.dump(&fd, &borrowed_args(args))?),
+            Self::Subprocess(program) => program,
+            Self::Fallback(program) => match service.dump(&fd, &borrowed_args(args)) {
+                Err(StatusCode::PERMISSION_DENIED) => program,
+                result => return Ok(result?),
+            },
+            Self::Record(recorder) => {
+                return recorder.dump(service, service_name, args, timeout, fd)
+            }
+            Self::Replay(replay) => return replay.dump(service_name, args, fd),
+        };
+
+        let secs = timeout.map_or(NO_TIMEOUT_SECS, |timeout| {
+            timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0)
+        });
+        let output = Command::new(program)
+            .arg("-t")
+            .arg(secs.max(1).to_string())
+            .arg(service_name)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(fd.try_clone_to_owned()?)
+            .stderr(Stdio::piped())
+            .output()?;
+        Ok(status(
+            output.status,
+            &String::from_utf8_lossy(&output.stderr),
+        )?)
+    }
+}
+
+/// The status of the dump according to how `dumpsys` exited and what it printed to stderr.
+///
+/// `dumpsys` returns the `status_t` of a failure, truncated to a byte, but only for some of them,
+/// and reports the others on stderr. Output cut off because the reader closed the pipe isn't a
+/// failure, the reader reports why it stopped.
+fn status(exit: ExitStatus, stderr: &str) -> binder::Result<()> {
+    let stderr = stderr.to_lowercase();
+    let sigpipe = exit.signal() == Some(libc::SIGPIPE) || exit.code() == Some(128 + libc::SIGPIPE);
+    if sigpipe || stderr.contains("broken pipe") {
+        return Ok(());
+    }
+    let reported = [
+        ("can't find service", StatusCode::NAME_NOT_FOUND),
+        ("permission denied", StatusCode::PERMISSION_DENIED),
+        ("permission_denied", StatusCode::PERMISSION_DENIED),
+        ("dead_object", StatusCode::DEAD_OBJECT),
+        ("dead object", StatusCode::DEAD_OBJECT),
+        ("timed out", StatusCode::TIMED_OUT),
+        ("error dumping service", StatusCode::UNKNOWN_ERROR),
+    ];
+    if let Some((_, status)) = reported
+        .iter()
+        .find(|(message, _)| stderr.contains(message))
+    {
+        return Err(*status);
+    }
+
+    match exit.code() {
+        Some(0) => Ok(()),
+        Some(code) => Err(match code as u8 as i8 {
+            -2 => StatusCode::NAME_NOT_FOUND,
+            -1 => StatusCode::PERMISSION_DENIED,
+            -32 => StatusCode::DEAD_OBJECT,
+            _ => StatusCode::UNKNOWN_ERROR,
+        }),
+        // Killed by a signal.
+        None => Err(StatusCode::UNKNOWN_ERROR),
+    }
+}
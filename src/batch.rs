@@ -0,0 +1,224 @@
+use std::{
+    io,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    builder::Config,
+    error::{DumpContext, DumpError},
+    owned_args, DumpSink, Dumpsys, Snapshot,
+};
+
+/// Dumps of several services run concurrently, like the service section of a bugreport
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::DumpBatch;
+///
+/// let results = DumpBatch::new()
+///     .add("SurfaceFlinger", &["--latency"])
+///     .add("meminfo", &["-a"])
+///     .parallelism(2)
+///     .timeout(Duration::from_secs(10))
+///     .run();
+/// for (service, result) in results {
+///     println!("{service}: {:?}", result.map(|output| output.len()));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DumpBatch {
+    jobs: Vec<Job>,
+    parallelism: usize,
+    timeout: Option<Duration>,
+}
+
+impl Default for DumpBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DumpBatch {
+    /// An empty batch running as many dumps at once as there are CPUs.
+    pub fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+            parallelism: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            timeout: None,
+        }
+    }
+
+    /// Dump `service` with `args`, as often as it's added, e.g. with different arguments.
+    pub fn add(
+        mut self,
+        service: impl AsRef<str>,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Self {
+        self.jobs.push(Job {
+            service: service.as_ref().to_owned(),
+            args: owned_args(args),
+            timeout: None,
+        });
+        self
+    }
+
+    /// Run at most `parallelism` dumps at once, at least one.
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Give up on each dump still producing output after `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Dump every service, blocking until all are done, and return the results in the order the
+    /// services were added.
+    pub fn run(self) -> Vec<(String, Result<String, DumpError>)> {
+        let jobs = self.jobs();
+        run_parallel(&jobs, self.parallelism)
+            .into_iter()
+            .zip(jobs)
+            .map(|(result, job)| (job.service, result.map(|snapshot| snapshot.output)))
+            .collect()
+    }
+
+    /// Dump every service and write the snapshots to `sink` in the order they were added, then flush it.
+    ///
+    /// Returns the dumps that failed in the order they were added, or the first error of the sink.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::{DumpBatch, RotatingFileSink};
+    ///
+    /// let mut sink = RotatingFileSink::new("/data/local/tmp/dumps", "batch");
+    /// let failed = DumpBatch::new()
+    ///     .add("SurfaceFlinger", &["--latency"])
+    ///     .add("meminfo", &["-a"])
+    ///     .run_into(&mut sink)
+    ///     .unwrap();
+    /// for (service, err) in failed {
+    ///     eprintln!("{service}: {err}");
+    /// }
+    /// ```
+    pub fn run_into(self, mut sink: impl DumpSink) -> io::Result<Vec<(String, DumpError)>> {
+        let mut failed = Vec::new();
+        let jobs = self.jobs();
+        let results = run_parallel(&jobs, self.parallelism);
+        for (result, job) in results.into_iter().zip(jobs) {
+            match result {
+                Ok(snapshot) => sink.write_snapshot(&snapshot)?,
+                Err(err) => failed.push((job.service, err)),
+            }
+        }
+        sink.flush()?;
+        Ok(failed)
+    }
+
+    fn jobs(&self) -> Vec<Job> {
+        self.jobs
+            .iter()
+            .map(|job| Job {
+                timeout: self.timeout,
+                ..job.clone()
+            })
+            .collect()
+    }
+}
+
+/// Dump each `(service, args)` job with at most `max_parallel` running at once, giving each up to `timeout`.
+///
+/// Results come back in the order of `jobs`. Bounding the parallelism keeps a big collection from
+/// spawning a reader per service and exhausting the binder thread pools of busy services.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let results = dumpsys_rs::dump_many(
+///     [("SurfaceFlinger", vec!["--latency"]), ("gfxinfo", vec![])],
+///     4,
+///     Duration::from_secs(5),
+/// );
+/// ```
+pub fn dump_many(
+    jobs: impl IntoIterator<Item = (impl AsRef<str>, impl IntoIterator<Item = impl AsRef<str>>)>,
+    max_parallel: usize,
+    timeout: Duration,
+) -> Vec<Result<String, DumpError>> {
+    let jobs: Vec<_> = jobs
+        .into_iter()
+        .map(|(service, args)| Job {
+            service: service.as_ref().to_owned(),
+            args: owned_args(args),
+            timeout: Some(timeout),
+        })
+        .collect();
+    run_parallel(&jobs, max_parallel.max(1))
+        .into_iter()
+        .map(|result| result.map(|snapshot| snapshot.output))
+        .collect()
+}
+
+/// One dump of a batch
+#[derive(Debug, Clone)]
+pub(crate) struct Job {
+    pub(crate) service: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) timeout: Option<Duration>,
+}
+
+/// Dump each job on up to `parallelism` scoped threads, keeping the results in job order.
+pub(crate) fn run_parallel(jobs: &[Job], parallelism: usize) -> Vec<Result<Snapshot, DumpError>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..parallelism.min(jobs.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+                let result = dump_one(job);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every job ran"))
+        .collect()
+}
+
+fn dump_one(job: &Job) -> Result<Snapshot, DumpError> {
+    let Some(dumpsys) = Dumpsys::try_new(&job.service) else {
+        return Err(DumpError::ServiceNotFound {
+            context: DumpContext {
+                service: job.service.clone(),
+                args: job.args.clone(),
+            },
+        });
+    };
+    let dumpsys = dumpsys.with_config(Config {
+        dump_timeout: job.timeout,
+        ..(*dumpsys.config).clone()
+    });
+    dumpsys.dump_snapshot(&job.args)
+}
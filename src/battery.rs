@@ -0,0 +1,309 @@
+//! Typed output of `dumpsys battery`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::battery::{BatteryStatus, ChargeStatus, Health, PlugType};
+//!
+//! let battery = BatteryStatus::parse(
+//!     "Current Battery Service state:
+//!   AC powered: false
+//!   USB powered: true
+//!   Wireless powered: false
+//!   Dock powered: false
+//!   Charge counter: 3000000
+//!   status: 2
+//!   health: 2
+//!   present: true
+//!   level: 85
+//!   scale: 100
+//!   voltage: 4200
+//!   temperature: 251
+//!   technology: Li-ion",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(battery.level, 85);
+//! assert_eq!(battery.status, ChargeStatus::Charging);
+//! assert_eq!(battery.health, Health::Good);
+//! assert_eq!(battery.plug, PlugType::Usb);
+//! assert_eq!(battery.temperature, 25.1);
+//! assert_eq!(battery.current_now_ua, None);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_int},
+    typed, DumpParse,
+};
+
+/// `BatteryManager.BATTERY_STATUS_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChargeStatus {
+    Unknown,
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for ChargeStatus {
+    fn from(status: i64) -> Self {
+        match status {
+            1 => Self::Unknown,
+            2 => Self::Charging,
+            3 => Self::Discharging,
+            4 => Self::NotCharging,
+            5 => Self::Full,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// `BatteryManager.BATTERY_HEALTH_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Health {
+    Unknown,
+    Good,
+    Overheat,
+    Dead,
+    OverVoltage,
+    UnspecifiedFailure,
+    Cold,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for Health {
+    fn from(health: i64) -> Self {
+        match health {
+            1 => Self::Unknown,
+            2 => Self::Good,
+            3 => Self::Overheat,
+            4 => Self::Dead,
+            5 => Self::OverVoltage,
+            6 => Self::UnspecifiedFailure,
+            7 => Self::Cold,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Power source the device is plugged into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlugType {
+    /// On battery
+    #[default]
+    None,
+    Ac,
+    Usb,
+    Wireless,
+    Dock,
+}
+
+/// Output of `dumpsys battery`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatteryStatus {
+    /// Charge level out of [`BatteryStatus::scale`]
+    pub level: u32,
+    pub scale: u32,
+    pub status: ChargeStatus,
+    pub health: Health,
+    pub plug: PlugType,
+    pub present: bool,
+    pub voltage_mv: u32,
+    /// Degrees Celsius
+    pub temperature: f64,
+    /// Current flowing out of the battery in µA, negative while charging, on devices that report it
+    pub current_now_ua: Option<i64>,
+    pub charge_counter_uah: Option<i64>,
+    pub technology: Option<String>,
+}
+
+impl BatteryStatus {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let fields: HashMap<&str, &str> = text
+            .lines()
+            .filter_map(|line| line.trim().split_once(": "))
+            .map(|(key, value)| (key, value.trim()))
+            .collect();
+        let get = |key: &str| fields.get(key).copied();
+        let int = |key: &str| match get(key) {
+            Some(value) => parse_int(value)
+                .map(Some)
+                .ok_or_else(|| invalid(key, value)),
+            None => Ok(None),
+        };
+        let require = |key: &str| int(key)?.ok_or_else(|| ParseError::Missing(key.to_owned()));
+        let powered = |key: &str| get(key) == Some("true");
+
+        let plug = if powered("AC powered") {
+            PlugType::Ac
+        } else if powered("USB powered") {
+            PlugType::Usb
+        } else if powered("Wireless powered") {
+            PlugType::Wireless
+        } else if powered("Dock powered") {
+            PlugType::Dock
+        } else {
+            PlugType::None
+        };
+
+        Ok(Self {
+            level: unsigned("level", require("level")?)?,
+            scale: unsigned("scale", int("scale")?.unwrap_or(100))?,
+            status: require("status")?.into(),
+            health: int("health")?.unwrap_or(1).into(),
+            plug,
+            present: get("present") != Some("false"),
+            voltage_mv: unsigned("voltage", int("voltage")?.unwrap_or(0))?,
+            temperature: int("temperature")?.unwrap_or(0) as f64 / 10.0,
+            current_now_ua: int("current now")?,
+            charge_counter_uah: int("Charge counter")?,
+            technology: get("technology").map(str::to_owned),
+        })
+    }
+
+    /// Charge level in percent.
+    pub fn percent(&self) -> f64 {
+        if self.scale == 0 {
+            return 0.0;
+        }
+        f64::from(self.level) * 100.0 / f64::from(self.scale)
+    }
+
+    /// Whether the battery is charging, as opposed to plugged in but full or not charging.
+    pub fn is_charging(&self) -> bool {
+        self.status == ChargeStatus::Charging
+    }
+
+    pub fn is_plugged(&self) -> bool {
+        self.plug != PlugType::None
+    }
+}
+
+impl DumpParse for BatteryStatus {
+    const SERVICE: &'static str = "battery";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump and parse the state of the battery.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::battery;
+///
+/// # fn foo() {
+/// let battery = battery::status().unwrap();
+/// println!("{}% at {} °C", battery.percent(), battery.temperature);
+/// # }
+/// ```
+pub fn status() -> Result<BatteryStatus, DumpError> {
+    typed::dump_service::<BatteryStatus>(Vec::<&str>::new())
+}
+
+/// Charge level in percent.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// println!("{}%", dumpsys_rs::battery::battery_level().unwrap());
+/// # }
+/// ```
+pub fn battery_level() -> Result<f64, DumpError> {
+    status().map(|status| status.percent())
+}
+
+fn unsigned(key: &str, value: i64) -> Result<u32, ParseError> {
+    u32::try_from(value).map_err(|_| invalid(key, &value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "Current Battery Service state:
+  AC powered: false
+  USB powered: true
+  Wireless powered: false
+  Max charging current: 500000
+  status: 2
+  health: 2
+  present: true
+  level: 85
+  scale: 100
+  voltage: 4200
+  temperature: 285
+  technology: Li-ion
+";
+
+    #[test]
+    fn parses_the_dump() {
+        let battery = BatteryStatus::parse(DUMP).unwrap();
+        assert_eq!(battery.level, 85);
+        assert_eq!(battery.status, ChargeStatus::Charging);
+        assert_eq!(battery.health, Health::Good);
+        assert_eq!(battery.plug, PlugType::Usb);
+        assert_eq!(battery.temperature, 28.5);
+        assert_eq!(battery.current_now_ua, None);
+        assert_eq!(battery.technology.as_deref(), Some("Li-ion"));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            BatteryStatus::parse(&crlf).unwrap(),
+            BatteryStatus::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_level_fails() {
+        let err = BatteryStatus::parse(&DUMP.replace("  level: 85\n", "")).unwrap_err();
+        assert_eq!(err, ParseError::Missing("level".to_owned()));
+    }
+
+    #[test]
+    fn empty_input_fails() {
+        assert_eq!(
+            BatteryStatus::parse("").unwrap_err(),
+            ParseError::Missing("level".to_owned())
+        );
+    }
+
+    #[test]
+    fn unknown_enum_values_are_kept() {
+        let text = DUMP
+            .replace("status: 2", "status: 9")
+            .replace("health: 2", "health: 42");
+        let battery = BatteryStatus::parse(&text).unwrap();
+        assert_eq!(battery.status, ChargeStatus::Other(9));
+        assert_eq!(battery.health, Health::Other(42));
+    }
+
+    #[test]
+    fn truncated_value_is_invalid() {
+        let text = "  status: 2\n  level: 8x";
+        assert_eq!(
+            BatteryStatus::parse(text).unwrap_err(),
+            ParseError::Invalid {
+                key: "level".to_owned(),
+                value: "8x".to_owned(),
+            }
+        );
+    }
+}
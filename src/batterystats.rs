@@ -0,0 +1,494 @@
+//! Typed output of `dumpsys batterystats`
+//!
+//! Only the first `Statistics since last charge` block is read. For data that must survive Android
+//! releases, prefer the `--checkin` format, see [`checkin`](crate::checkin).
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::batterystats::BatteryStats;
+//!
+//! let stats = BatteryStats::parse(
+//!     "Battery History (2% used, 12KB used of 512KB, 100 strings using 6KB):
+//!                     0 (9) RESET:TIME: 2023-01-01-00-00-00
+//!                     0 (2) 100 status=discharging health=good plug=none +running +wake_lock=u0a123:\"*alarm*\"
+//!            +1m2s345ms (2) 099 -wake_lock
+//!
+//! Statistics since last charge:
+//!   Time on battery: 2h 3m 4s 567ms (95.0%) realtime, 1h 0m 0s 0ms (50.0%) uptime
+//!   Estimated power use (mAh):
+//!     Capacity: 4000, Computed drain: 612, actual drain: 600-640
+//!     UID u0a123: 150 ( cpu=100 wake=3.5 mobileRadio=46.5 )
+//!     UID 1000: 125 fg: 100 bg: 25
+//!
+//!   All partial wake locks:
+//!   Wake lock u0a123 *alarm*: 1m 2s 345ms (12 times) max=3000 actual=3500 realtime
+//!   Wake lock 1000 NetworkStats: 10s 5ms (3 times) realtime",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(stats.time_on_battery, Some(Duration::from_millis(7_384_567)));
+//! assert_eq!(stats.capacity_mah, Some(4000.0));
+//! assert_eq!(stats.history[2].time, Duration::from_millis(62_345));
+//! assert_eq!(stats.history[2].level, Some(99));
+//! assert_eq!(stats.power[0].uid, 10123);
+//! assert_eq!(stats.power[0].components["wake"], 3.5);
+//! assert_eq!(stats.wakelocks[0].name, "*alarm*");
+//! assert_eq!(stats.wakelocks[0].count, 12);
+//! assert_eq!(stats.wakelocks[1].duration, Duration::from_millis(10_005));
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    error::ParseError,
+    parse::{
+        kv::{invalid, parse_duration},
+        parse_uid,
+    },
+    DumpParse,
+};
+
+const HISTORY: &str = "Battery History";
+const SINCE_CHARGED: &str = "Statistics since last charge:";
+const TIME_ON_BATTERY: &str = "Time on battery: ";
+const POWER_USE: &str = "Estimated power use (mAh):";
+const PARTIAL_WAKE_LOCKS: &str = "All partial wake locks:";
+const WAKE_LOCK: &str = "Wake lock ";
+
+/// One entry of the battery history
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistoryItem {
+    /// Time since the history started
+    pub time: Duration,
+    /// Battery level in percent, missing on entries like `RESET`
+    pub level: Option<u8>,
+    /// State changes such as `+wake_lock` or `status=discharging`
+    pub events: Vec<String>,
+}
+
+/// Total time a partial wake lock was held since the last charge
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartialWakelock {
+    pub uid: u32,
+    pub name: String,
+    pub duration: Duration,
+    /// Times it was acquired
+    pub count: u64,
+}
+
+/// Power an app is estimated to have used since the last charge
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UidPower {
+    pub uid: u32,
+    pub mah: f64,
+    /// Breakdown by component such as `cpu` or `wake`, on releases that print it on the same line
+    pub components: BTreeMap<String, f64>,
+}
+
+/// Output of `dumpsys batterystats`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatteryStats {
+    /// Realtime on battery since the last charge
+    pub time_on_battery: Option<Duration>,
+    pub capacity_mah: Option<f64>,
+    /// Drain according to the power model
+    pub computed_drain_mah: Option<f64>,
+    pub history: Vec<HistoryItem>,
+    /// Partial wake locks in order of the dump, longest first
+    pub wakelocks: Vec<PartialWakelock>,
+    /// Apps by estimated power use
+    pub power: Vec<UidPower>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    History,
+    Power,
+    Wakelocks,
+}
+
+impl BatteryStats {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut stats = Self::default();
+        let mut section = Section::Other;
+        let mut since_charged = 0;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                section = Section::Other;
+                continue;
+            }
+            if trimmed == SINCE_CHARGED {
+                since_charged += 1;
+                section = Section::Other;
+                continue;
+            }
+
+            if trimmed.starts_with(HISTORY) && stats.history.is_empty() {
+                section = Section::History;
+                continue;
+            }
+            if section == Section::History {
+                stats.history.push(history_item(trimmed)?);
+                continue;
+            }
+            // Later blocks repeat the same sections for other periods.
+            if since_charged != 1 {
+                continue;
+            }
+
+            match trimmed {
+                POWER_USE => section = Section::Power,
+                PARTIAL_WAKE_LOCKS => section = Section::Wakelocks,
+                _ if section == Section::Power => stats.parse_power(trimmed)?,
+                _ if section == Section::Wakelocks => {
+                    if let Some(wakelock) = trimmed.strip_prefix(WAKE_LOCK) {
+                        stats.wakelocks.extend(partial_wakelock(wakelock)?);
+                    }
+                }
+                _ => {
+                    if let Some(time) = trimmed.strip_prefix(TIME_ON_BATTERY) {
+                        let time = time.split('(').next().unwrap_or(time);
+                        stats.time_on_battery = spaced_duration(time);
+                    }
+                }
+            }
+        }
+
+        stats.power.sort_by(|a, b| b.mah.total_cmp(&a.mah));
+        Ok(stats)
+    }
+
+    /// The battery level over time, one entry per change.
+    pub fn discharge(&self) -> impl Iterator<Item = (Duration, u8)> + '_ {
+        let mut last = None;
+        self.history.iter().filter_map(move |item| {
+            let level = item.level?;
+            (last.replace(level) != Some(level)).then_some((item.time, level))
+        })
+    }
+
+    /// Estimated power used by `uid`.
+    pub fn uid_power(&self, uid: u32) -> Option<&UidPower> {
+        self.power.iter().find(|power| power.uid == uid)
+    }
+
+    /// Parse a line of `Estimated power use`, ignoring the global breakdown.
+    fn parse_power(&mut self, line: &str) -> Result<(), ParseError> {
+        if let Some(totals) = line.strip_prefix("Capacity: ") {
+            // Capacity: 4000, Computed drain: 612, actual drain: 600-640
+            let mut values = totals.split(", ");
+            self.capacity_mah = values.next().and_then(|capacity| capacity.parse().ok());
+            self.computed_drain_mah = values
+                .find_map(|value| value.strip_prefix("Computed drain: "))
+                .and_then(|drain| drain.parse().ok());
+            return Ok(());
+        }
+
+        // UID u0a123: 150 ( cpu=100 wake=3 ) or UID 1000: 125 fg: 100 bg: 25
+        let Some(rest) = line
+            .strip_prefix("UID ")
+            .or_else(|| line.strip_prefix("Uid "))
+        else {
+            return Ok(());
+        };
+        let Some((uid, rest)) = rest.split_once(": ") else {
+            return Err(invalid("uid", line));
+        };
+        let Some(uid) = parse_uid(uid) else {
+            return Ok(());
+        };
+        let mut words = rest.split_whitespace();
+        let mah = words
+            .next()
+            .and_then(|mah| mah.parse().ok())
+            .ok_or_else(|| invalid("power", line))?;
+        let components = words
+            .take_while(|word| *word != ")")
+            .filter_map(|word| {
+                let (component, mah) = word.split_once('=')?;
+                Some((component.to_owned(), mah.parse().ok()?))
+            })
+            .collect();
+
+        self.power.push(UidPower {
+            uid,
+            mah,
+            components,
+        });
+        Ok(())
+    }
+}
+
+impl DumpParse for BatteryStats {
+    const SERVICE: &'static str = "batterystats";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// `+1m2s345ms (2) 099 -wake_lock`
+fn history_item(line: &str) -> Result<HistoryItem, ParseError> {
+    let mut words = line.split_whitespace().peekable();
+    let time = words
+        .next()
+        .and_then(parse_duration)
+        .ok_or_else(|| invalid(HISTORY, line))?;
+    words.next_if(|word| word.starts_with('(') && word.ends_with(')'));
+    let level = words
+        .next_if(|word| word.len() == 3 && word.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|level| level.parse().ok());
+
+    Ok(HistoryItem {
+        time,
+        level,
+        events: words.map(str::to_owned).collect(),
+    })
+}
+
+/// `u0a123 *alarm*: 1m 2s 345ms (12 times) max=3000 realtime`, `None` for uids that can't be parsed.
+fn partial_wakelock(line: &str) -> Result<Option<PartialWakelock>, ParseError> {
+    let (owner, stats) = line
+        .rsplit_once(": ")
+        .ok_or_else(|| invalid(WAKE_LOCK.trim(), line))?;
+    let (uid, name) = owner.split_once(' ').unwrap_or((owner, ""));
+    let Some(uid) = parse_uid(uid) else {
+        return Ok(None);
+    };
+    let (duration, count) = stats.split_once(" (").unwrap_or((stats, ""));
+    let count = count
+        .split_whitespace()
+        .next()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+
+    Ok(Some(PartialWakelock {
+        uid,
+        name: name.to_owned(),
+        duration: spaced_duration(duration).ok_or_else(|| invalid(WAKE_LOCK.trim(), line))?,
+        count,
+    }))
+}
+
+/// `1h 2m 3s 456ms`, as printed by `TimeUtils.formatDuration` with spaces.
+fn spaced_duration(text: &str) -> Option<Duration> {
+    let compact: String = text.split_whitespace().collect();
+    parse_duration(&compact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys batterystats` of Android 14, cut down to a few entries of each section
+    const DUMP: &str = r#"Battery History (1% used, 10KB used of 1024KB, 98 strings using 7142):
+                    0 (15) RESET:TIME: 2024-03-11-08-12-44
+                    0 (2) 100 status=discharging health=good plug=none temp=254 volt=4371 +running +wake_lock +screen
+              +1s012ms (2) 100 -wake_lock
+           +12m3s456ms (2) 099 +wake_lock=1001:"telephony-radio"
+        +1h2m3s4ms (2) 097 -running -screen
+
+Per-PID Stats:
+  PID 1520 wake time: +3m12s81ms
+
+Statistics since last charge:
+  System starts: 0, currently on battery: true
+  Estimated battery capacity: 4410 mAh
+  Time on battery: 5h 12m 30s 104ms (99.8%) realtime, 1h 40m 2s 518ms (32.1%) uptime
+  Screen on: 1h 2m 3s 4ms (19.8%) 14x, Interactive: 1h 1m 58s 311ms (19.8%)
+
+  Estimated power use (mAh):
+    Capacity: 4410, Computed drain: 1235, actual drain: 1103-1147
+    Global
+      screen: 412 apps: 412 duration: 1h 2m 3s 4ms
+      cpu: 311 apps: 311 duration: 1h 40m 2s 518ms
+    UID u0a231: 214 fg: 180 bg: 34 fgs: 0 cached: 0 ( screen=150 cpu=41.2 wakelock=22.8 )
+    UID 1000: 356 fg: 12 bg: 344 fgs: 0 cached: 0 ( cpu=301 wakelock=55 )
+    UID u0a97: 0.512 fg: 0.512 bg: 0 fgs: 0 cached: 0
+
+  All partial wake locks:
+  Wake lock u0a231 *job*/com.example.app/.SyncJob: 4m 12s 30ms (14 times) max=61234 actual=72000 realtime
+  Wake lock 1000 *alarm*: 1m 2s 3ms (40 times) max=3200 realtime
+  Wake lock 1027 NfcService:mNfcPollingWakeLock: 3s 100ms (5 times) max=1200 realtime
+
+  u0a231:
+    Wake lock *job*/com.example.app/.SyncJob: 4m 12s 30ms partial (14 times) realtime
+"#;
+
+    #[test]
+    fn parses_the_dump() {
+        let stats = BatteryStats::parse(DUMP).unwrap();
+        assert_eq!(
+            stats.time_on_battery,
+            Some(Duration::from_millis(18_750_104))
+        );
+        assert_eq!(stats.capacity_mah, Some(4410.0));
+        assert_eq!(stats.computed_drain_mah, Some(1235.0));
+        assert_eq!(stats.history.len(), 5);
+        assert_eq!(
+            stats.history[3],
+            HistoryItem {
+                time: Duration::from_millis(723_456),
+                level: Some(99),
+                events: vec![r#"+wake_lock=1001:"telephony-radio""#.to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn history_entries_without_level() {
+        let stats = BatteryStats::parse(DUMP).unwrap();
+        assert_eq!(stats.history[0].level, None);
+        assert_eq!(
+            stats.history[0].events,
+            ["RESET:TIME:", "2024-03-11-08-12-44"]
+        );
+        assert_eq!(
+            stats.discharge().collect::<Vec<_>>(),
+            [
+                (Duration::ZERO, 100),
+                (Duration::from_millis(723_456), 99),
+                (Duration::from_millis(3_723_004), 97),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_power_use_by_uid() {
+        let stats = BatteryStats::parse(DUMP).unwrap();
+        assert_eq!(stats.power.len(), 3);
+        assert_eq!(stats.power[0].uid, 1000);
+        assert_eq!(stats.power[2].mah, 0.512);
+        assert!(stats.power[2].components.is_empty());
+        let app = stats.uid_power(10231).unwrap();
+        assert_eq!(app.mah, 214.0);
+        assert_eq!(
+            app.components,
+            BTreeMap::from([
+                ("screen".to_owned(), 150.0),
+                ("cpu".to_owned(), 41.2),
+                ("wakelock".to_owned(), 22.8),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_partial_wake_locks_only() {
+        let stats = BatteryStats::parse(DUMP).unwrap();
+        assert_eq!(
+            stats.wakelocks,
+            [
+                PartialWakelock {
+                    uid: 10231,
+                    name: "*job*/com.example.app/.SyncJob".to_owned(),
+                    duration: Duration::from_millis(252_030),
+                    count: 14,
+                },
+                PartialWakelock {
+                    uid: 1000,
+                    name: "*alarm*".to_owned(),
+                    duration: Duration::from_millis(62_003),
+                    count: 40,
+                },
+                PartialWakelock {
+                    uid: 1027,
+                    name: "NfcService:mNfcPollingWakeLock".to_owned(),
+                    duration: Duration::from_millis(3_100),
+                    count: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn later_blocks_are_ignored() {
+        let text = format!("{DUMP}\n{SINCE_CHARGED}\n  Estimated power use (mAh):\n    Capacity: 3000, Computed drain: 1\n    UID 2000: 99\n");
+        assert_eq!(
+            BatteryStats::parse(&text).unwrap(),
+            BatteryStats::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_sections() {
+        let text = "Statistics since last charge:
+  Time on battery: 12s 5ms (100.0%) realtime, 12s 5ms (100.0%) uptime
+";
+        let stats = BatteryStats::parse(text).unwrap();
+        assert_eq!(stats.time_on_battery, Some(Duration::from_millis(12_005)));
+        assert_eq!(stats.capacity_mah, None);
+        assert!(stats.history.is_empty() && stats.power.is_empty() && stats.wakelocks.is_empty());
+    }
+
+    #[test]
+    fn unknown_uids_are_skipped() {
+        let text = "Statistics since last charge:
+  Estimated power use (mAh):
+    UID ???: 12 fg: 12
+    UID u0a12: 3
+  All partial wake locks:
+  Wake lock ??? *alarm*: 1s 2ms (1 times) realtime
+";
+        let stats = BatteryStats::parse(text).unwrap();
+        assert_eq!(stats.power.len(), 1);
+        assert_eq!(stats.power[0].uid, 10012);
+        assert!(stats.wakelocks.is_empty());
+    }
+
+    #[test]
+    fn truncated_lines_are_invalid() {
+        let power = "Statistics since last charge:\n  Estimated power use (mAh):\n";
+        let line = "UID u0a231 214";
+        assert_eq!(
+            BatteryStats::parse(&format!("{power}    {line}")).unwrap_err(),
+            invalid("uid", line)
+        );
+        let line = "UID u0a231: fg";
+        assert_eq!(
+            BatteryStats::parse(&format!("{power}    {line}")).unwrap_err(),
+            invalid("power", line)
+        );
+
+        let wakelocks = "Statistics since last charge:\n  All partial wake locks:\n";
+        let text = format!("{wakelocks}  Wake lock u0a231 *alarm*");
+        assert_eq!(
+            BatteryStats::parse(&text).unwrap_err(),
+            invalid("Wake lock", "u0a231 *alarm*")
+        );
+        let text = format!("{wakelocks}  Wake lock u0a231 *alarm*: 1m 2 (3 times)");
+        assert_eq!(
+            BatteryStats::parse(&text).unwrap_err(),
+            invalid("Wake lock", "u0a231 *alarm*: 1m 2 (3 times)")
+        );
+
+        let line = "+1m2 (2) 099 -wake_lock";
+        assert_eq!(
+            BatteryStats::parse(&format!("Battery History:\n{line}")).unwrap_err(),
+            invalid(HISTORY, line)
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            BatteryStats::parse(&crlf).unwrap(),
+            BatteryStats::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(BatteryStats::parse("").unwrap(), BatteryStats::default());
+    }
+}
@@ -0,0 +1,319 @@
+//! `dumpsys-rs`, a scriptable `dumpsys` built on the library
+//!
+//! Dumps go through binder directly, so this works on devices whose toolbox `dumpsys` is missing or
+//! broken, and `batch` dumps services in parallel.
+
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+    time::Duration,
+};
+
+use dumpsys_rs::{
+    activity, alarm, appops, audio, battery, batterystats, checkin, connectivity, cpuinfo,
+    deviceidle, diff, diskstats, display, dropbox, error::ParseError, gfxinfo, gpu, input,
+    jobscheduler, json, location, media_session, meminfo, netstats, notification, package, power,
+    procstats, sensorservice, service_manager, surfaceflinger, telephony, thermalservice,
+    usagestats, wifi, window, DumpBatch, DumpParse, Dumpsys,
+};
+use serde::Serialize;
+
+const USAGE: &str = "Usage: dumpsys-rs <command> [options]
+
+Commands:
+  list                           List the registered services
+  dump [-t SECS] SERVICE [ARGS]  Dump a service, giving up after SECS seconds
+  watch [-n SECS] SERVICE [ARGS] Dump every SECS seconds, 2 by default, printing changed dumps
+  batch [-t SECS] [-j JOBS] [--json] SERVICE...
+                                 Dump several services in parallel, as JSON with --json
+  diff OLD NEW                   Diff two dump files line by line
+  parse PARSER FILE              Parse a dump file as JSON, `-` reading stdin
+  parsers                        List the parsers of `parse`";
+
+type Parser = fn(&str) -> Result<String, ParseError>;
+
+/// Parsers by name, the service for the main dump of a service and `service:dump` for the others
+const PARSERS: &[(&str, Parser)] = &[
+    ("activity", to_json::<activity::Activities>),
+    ("activity:processes", to_json::<activity::Processes>),
+    ("alarm", to_json::<alarm::AlarmManager>),
+    ("appops", to_json::<appops::AppOps>),
+    ("audio", to_json::<audio::AudioState>),
+    ("battery", to_json::<battery::BatteryStatus>),
+    ("batterystats", to_json::<batterystats::BatteryStats>),
+    ("batterystats:checkin", to_json::<checkin::Checkin>),
+    ("connectivity", to_json::<connectivity::Connectivity>),
+    ("cpuinfo", to_json::<cpuinfo::CpuInfo>),
+    ("deviceidle", to_json::<deviceidle::DeviceIdle>),
+    ("diskstats", to_json::<diskstats::DiskStats>),
+    ("display", to_json::<display::DisplayManager>),
+    ("dropbox", to_json::<dropbox::DropBox>),
+    ("gfxinfo", to_json::<gfxinfo::Gfxinfo>),
+    ("gpu", to_json::<gpu::GpuState>),
+    ("input", to_json::<input::InputState>),
+    ("jobscheduler", to_json::<jobscheduler::JobScheduler>),
+    ("location", to_json::<location::LocationState>),
+    ("media_session", to_json::<media_session::MediaSessions>),
+    ("meminfo", to_json::<meminfo::MemInfo>),
+    ("meminfo:package", to_json::<meminfo::PackageMemInfo>),
+    ("netstats", to_json::<netstats::NetStats>),
+    ("notification", to_json::<notification::NotificationState>),
+    ("package", to_json::<package::PackageInfo>),
+    ("power", to_json::<power::PowerState>),
+    ("procstats", to_json::<procstats::ProcStats>),
+    ("sensorservice", to_json::<sensorservice::SensorService>),
+    ("SurfaceFlinger:buffers", to_json::<gpu::GraphicBuffers>),
+    ("SurfaceFlinger:latency", to_json::<surfaceflinger::Latency>),
+    (
+        "SurfaceFlinger:timestats",
+        to_json::<surfaceflinger::TimeStats>,
+    ),
+    (
+        "telephony.registry",
+        to_json::<telephony::TelephonyRegistry>,
+    ),
+    ("thermalservice", to_json::<thermalservice::ThermalService>),
+    ("usagestats", to_json::<usagestats::UsageStats>),
+    ("wifi", to_json::<wifi::WifiState>),
+    ("window", to_json::<window::WindowManager>),
+];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+
+    let result = match command.as_str() {
+        "list" => list(),
+        "dump" => dump(args),
+        "watch" => watch(args),
+        "batch" => batch(args),
+        "diff" => diff(args),
+        "parse" => parse(args),
+        "parsers" => {
+            for (name, _) in PARSERS {
+                println!("{name}");
+            }
+            Ok(())
+        }
+        "-h" | "--help" | "help" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(Error::Usage(format!("unknown command `{command}`"))),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Error::Usage(message)) => {
+            eprintln!("dumpsys-rs: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(Error::Failed(message)) => {
+            eprintln!("dumpsys-rs: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+enum Error {
+    /// Bad command line, printed with the usage
+    Usage(String),
+    Failed(String),
+}
+
+impl<E: std::error::Error> From<E> for Error {
+    fn from(err: E) -> Self {
+        Self::Failed(err.to_string())
+    }
+}
+
+fn list() -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    for service in service_manager::list_services()? {
+        writeln!(stdout, "{service}")?;
+    }
+    Ok(())
+}
+
+fn dump(args: &[String]) -> Result<(), Error> {
+    let (timeout, args) = match args {
+        [name, value, rest @ ..] if name == "-t" => (Some(value_of(name, value)?), rest),
+        _ => (None, args),
+    };
+    let (service, args) = service(args)?;
+    let mut builder = Dumpsys::builder(service);
+    if let Some(timeout) = timeout {
+        builder = builder.dump_timeout(timeout);
+    }
+    let dumpsys = builder.build().ok_or_else(|| not_found(service))?;
+    dumpsys.dump_to_writer(args, &mut io::stdout().lock())?;
+    Ok(())
+}
+
+fn watch(args: &[String]) -> Result<(), Error> {
+    let (interval, args) = match args {
+        [name, value, rest @ ..] if name == "-n" => (value_of(name, value)?, rest),
+        _ => (Duration::from_secs(2), args),
+    };
+    let (service, args) = service(args)?;
+    let dumpsys = Dumpsys::new(service).ok_or_else(|| not_found(service))?;
+    for output in dumpsys.watch(args, interval) {
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", output?)?;
+        writeln!(stdout, "{}", "-".repeat(80))?;
+    }
+    Ok(())
+}
+
+fn batch(mut args: &[String]) -> Result<(), Error> {
+    let mut batch = DumpBatch::new();
+    let mut as_json = false;
+    loop {
+        match args {
+            [name, value, rest @ ..] if name == "-t" => {
+                batch = batch.timeout(value_of(name, value)?);
+                args = rest;
+            }
+            [name, value, rest @ ..] if name == "-j" => {
+                batch = batch.parallelism(value_of(name, value)?);
+                args = rest;
+            }
+            [name, rest @ ..] if name == "--json" => {
+                as_json = true;
+                args = rest;
+            }
+            _ => break,
+        }
+    }
+    if args.is_empty() {
+        return Err(Error::Usage("no services to dump".to_owned()));
+    }
+
+    let results = args
+        .iter()
+        .fold(batch, |batch, service| batch.add(service, [] as [&str; 0]))
+        .run();
+
+    let mut stdout = io::stdout().lock();
+    if as_json {
+        writeln!(stdout, "{}", json::dump_all(results))?;
+        return Ok(());
+    }
+    let mut failed = false;
+    for (service, result) in results {
+        // The layout of `dumpsys` without a service.
+        writeln!(stdout, "{}", "-".repeat(80))?;
+        writeln!(stdout, "DUMP OF SERVICE {service}:")?;
+        match result {
+            Ok(output) => write!(stdout, "{output}")?,
+            Err(err) => {
+                failed = true;
+                // What was read before the failure, like `dumpsys` does on a timeout.
+                stdout.write_all(err.partial())?;
+                eprintln!("dumpsys-rs: {service}: {err}");
+            }
+        }
+    }
+    if failed {
+        return Err(Error::Failed("some dumps failed".to_owned()));
+    }
+    Ok(())
+}
+
+fn diff(args: &[String]) -> Result<(), Error> {
+    let [old, new] = args else {
+        return Err(Error::Usage("diff takes two files".to_owned()));
+    };
+    let (old, new) = (read(old)?, read(new)?);
+
+    let mut stdout = io::stdout().lock();
+    for change in diff::diff(&old, &new).changes {
+        match change {
+            diff::LineChange::Added { new_line, text } => writeln!(stdout, "{new_line}\t+{text}")?,
+            diff::LineChange::Removed { old_line, text } => {
+                writeln!(stdout, "{old_line}\t-{text}")?
+            }
+            diff::LineChange::Changed {
+                old_line,
+                new_line,
+                old,
+                new,
+            } => {
+                writeln!(stdout, "{old_line}\t-{old}")?;
+                writeln!(stdout, "{new_line}\t+{new}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse(args: &[String]) -> Result<(), Error> {
+    let [name, file] = args else {
+        return Err(Error::Usage("parse takes a parser and a file".to_owned()));
+    };
+    let Some((_, parser)) = PARSERS.iter().find(|(parser, _)| parser == name) else {
+        return Err(Error::Usage(format!(
+            "unknown parser `{name}`, see `dumpsys-rs parsers`"
+        )));
+    };
+    println!("{}", parser(&read(file)?)?);
+    Ok(())
+}
+
+fn to_json<T: DumpParse + Serialize>(text: &str) -> Result<String, ParseError> {
+    let parsed = T::parse(text)?;
+    Ok(serde_json::to_string_pretty(&parsed).expect("parsed dumps serialize"))
+}
+
+fn not_found(service: &str) -> Error {
+    let suggestions = service_manager::suggestions(service).unwrap_or_default();
+    match suggestions.first() {
+        Some(suggestion) => Error::Failed(format!(
+            "service `{service}` not found, did you mean `{suggestion}`?"
+        )),
+        None => Error::Failed(format!("service `{service}` not found")),
+    }
+}
+
+/// The service and its arguments.
+fn service(args: &[String]) -> Result<(&str, &[String]), Error> {
+    args.split_first()
+        .map(|(service, args)| (service.as_str(), args))
+        .ok_or_else(|| Error::Usage("no service given".to_owned()))
+}
+
+/// The value of the option `name`.
+fn value_of<T: OptionValue>(name: &str, value: &str) -> Result<T, Error> {
+    T::parse(value).ok_or_else(|| Error::Usage(format!("invalid value `{value}` of {name}")))
+}
+
+trait OptionValue: Sized {
+    fn parse(value: &str) -> Option<Self>;
+}
+
+impl OptionValue for Duration {
+    /// Seconds, fractions allowed.
+    fn parse(value: &str) -> Option<Self> {
+        Duration::try_from_secs_f64(value.parse().ok()?).ok()
+    }
+}
+
+impl OptionValue for usize {
+    fn parse(value: &str) -> Option<Self> {
+        value.parse().ok()
+    }
+}
+
+/// The contents of `path`, or stdin for `-`.
+fn read(path: &str) -> Result<String, Error> {
+    if path == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    Ok(fs::read_to_string(path)?)
+}
@@ -0,0 +1,341 @@
+//! `dumpsys-top`, a live view of frame rate, temperatures and the busiest processes
+//!
+//! Every source is a [`Sampler`] of its own, or a [`JankMonitor`] for the frame rate of `-l LAYER`,
+//! so one slow dump doesn't hold back the others. Run it from `adb shell`; `q` quits, `c` and `m`
+//! sort the processes by CPU or by PSS.
+
+use std::{
+    cmp::Reverse,
+    env,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    ops::ControlFlow,
+    process::ExitCode,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
+
+use dumpsys_rs::{
+    activity::Processes,
+    cpuinfo::CpuInfo,
+    error::DumpError,
+    jank::{JankEvent, JankMonitor, JankStats},
+    meminfo::MemInfo,
+    monitor::{ProcessRecord, ProcessTable},
+    thermalservice::ThermalService,
+    DumpParse, Dumpsys, Sampler,
+};
+
+const USAGE: &str = "Usage: dumpsys-top [-n SECS] [-l LAYER]
+
+  -n SECS   Refresh every SECS seconds, 2 by default
+  -l LAYER  Show the frame rate of the SurfaceFlinger layer LAYER
+
+Keys: q quits, c sorts by CPU, m sorts by PSS";
+
+/// Rows before the process list
+const HEADER_ROWS: usize = 6;
+
+enum Update {
+    CpuInfo(CpuInfo),
+    MemInfo(MemInfo),
+    Processes(Processes),
+    Thermal(ThermalService),
+    Frames(JankStats),
+    Error(String),
+    Key(u8),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortBy {
+    Cpu,
+    Pss,
+}
+
+#[derive(Default)]
+struct Screen {
+    cpuinfo: CpuInfo,
+    meminfo: MemInfo,
+    processes: Processes,
+    thermal: Option<ThermalService>,
+    frames: Option<JankStats>,
+    error: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut interval = Duration::from_secs(2);
+    let mut layer = None;
+    let mut rest = args.as_slice();
+    loop {
+        match rest {
+            [name, value, tail @ ..] if name == "-n" => {
+                let Some(secs) = value
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                else {
+                    eprintln!("dumpsys-top: invalid value `{value}` of -n\n\n{USAGE}");
+                    return ExitCode::from(2);
+                };
+                interval = secs;
+                rest = tail;
+            }
+            [name, value, tail @ ..] if name == "-l" => {
+                layer = Some(value.clone());
+                rest = tail;
+            }
+            [] => break,
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let (tx, updates) = mpsc::channel();
+    let samplers = [
+        sample(interval, &tx, Update::CpuInfo),
+        sample(interval, &tx, Update::MemInfo),
+        sample(interval, &tx, Update::Processes),
+        sample(interval, &tx, Update::Thermal),
+    ];
+    let jank = layer.and_then(|layer| frames(interval, &tx, layer));
+    let keys = tx.clone();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buf = [0; 16];
+        while let Ok(len @ 1..) = stdin.read(&mut buf) {
+            for &key in &buf[..len] {
+                if keys.send(Update::Key(key)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    drop(tx);
+
+    let terminal = Terminal::enter();
+    let mut screen = Screen::default();
+    let mut sort_by = SortBy::Cpu;
+    for update in updates {
+        match update {
+            Update::CpuInfo(cpuinfo) => screen.cpuinfo = cpuinfo,
+            Update::MemInfo(meminfo) => screen.meminfo = meminfo,
+            Update::Processes(processes) => screen.processes = processes,
+            Update::Thermal(thermal) => screen.thermal = Some(thermal),
+            Update::Frames(stats) => screen.frames = Some(stats),
+            Update::Error(error) => screen.error = Some(error),
+            Update::Key(b'q') => break,
+            Update::Key(b'c') => sort_by = SortBy::Cpu,
+            Update::Key(b'm') => sort_by = SortBy::Pss,
+            Update::Key(_) => continue,
+        }
+        let (rows, columns) = terminal.size();
+        let frame = screen.render(sort_by, rows, columns);
+        let mut stdout = io::stdout().lock();
+        if write!(stdout, "\x1b[H\x1b[2J{frame}")
+            .and_then(|()| stdout.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    drop(terminal);
+    drop(jank);
+    for sampler in samplers.into_iter().flatten() {
+        sampler.stop();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Dump `T::SERVICE` every `interval` and send each parsed dump, `None` if the service is missing.
+fn sample<T: DumpParse + 'static>(
+    interval: Duration,
+    tx: &Sender<Update>,
+    update: fn(T) -> Update,
+) -> Option<Sampler> {
+    let tx = tx.clone();
+    let dumpsys = Dumpsys::try_new(T::SERVICE)?;
+    Some(Sampler::new(dumpsys, T::ARGS, interval, move |sample| {
+        let parsed =
+            sample.and_then(|snapshot| T::parse(&snapshot.output).map_err(DumpError::from));
+        let message = match parsed {
+            Ok(parsed) => update(parsed),
+            Err(err) => Update::Error(format!("{}: {err}", T::SERVICE)),
+        };
+        tx.send(message)
+            .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+    }))
+}
+
+/// Follow the frames of `layer`, sending the stats of every interval.
+fn frames(interval: Duration, tx: &Sender<Update>, layer: String) -> Option<JankMonitor> {
+    let tx = tx.clone();
+    let dumpsys = Dumpsys::try_new("SurfaceFlinger")?;
+    Some(
+        JankMonitor::builder(dumpsys, layer)
+            .interval(interval)
+            .spawn(move |event| {
+                let message = match event {
+                    JankEvent::Stats(stats) => Update::Frames(stats),
+                    JankEvent::Error(err) => Update::Error(format!("SurfaceFlinger: {err}")),
+                    _ => return ControlFlow::Continue(()),
+                };
+                tx.send(message)
+                    .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+            }),
+    )
+}
+
+impl Screen {
+    fn render(&self, sort_by: SortBy, rows: usize, columns: usize) -> String {
+        let table = ProcessTable::join(&self.cpuinfo, &self.meminfo, &self.processes);
+        let mut lines = Vec::new();
+
+        let sort = match sort_by {
+            SortBy::Cpu => "CPU",
+            SortBy::Pss => "PSS",
+        };
+        lines.push(format!(
+            "dumpsys-top, sorted by {sort}; q quits, c/m sort by CPU/PSS"
+        ));
+
+        let mut system = String::new();
+        if let Some(load) = &table.load {
+            let _ = write!(
+                system,
+                "Load {:.2} {:.2} {:.2}   ",
+                load.one, load.five, load.fifteen
+            );
+        }
+        if let Some(cpu) = &table.cpu {
+            let _ = write!(
+                system,
+                "CPU {:.0}% ({:.0}% user, {:.0}% kernel)   ",
+                cpu.total, cpu.user, cpu.kernel
+            );
+        }
+        if table.total_ram_kb > 0 {
+            let _ = write!(
+                system,
+                "RAM {} free of {}",
+                size(table.free_ram_kb),
+                size(table.total_ram_kb)
+            );
+        }
+        lines.push(system);
+
+        lines.push(match &self.frames {
+            Some(stats) => format!(
+                "FPS {:.1}   p99 {:.1} ms   {} janky, {} dropped",
+                stats.fps,
+                stats.p99.as_secs_f64() * 1000.0,
+                stats.janky_frames,
+                stats.dropped_frames
+            ),
+            None => "FPS -".to_owned(),
+        });
+
+        lines.push(match &self.thermal {
+            Some(thermal) => {
+                let mut line = format!("Thermal {:?}  ", thermal.status);
+                for sensor in thermal.sensors() {
+                    let _ = write!(line, " {} {:.1}°C", sensor.name, sensor.value);
+                }
+                line
+            }
+            None => "Thermal -".to_owned(),
+        });
+        lines.push(self.error.clone().unwrap_or_default());
+        lines.push(format!(
+            "{:>7} {:>6} {:>9} {:<8} {:<5} NAME",
+            "PID", "CPU%", "PSS", "ADJ", "STATE"
+        ));
+
+        let mut processes: Vec<&ProcessRecord> = table.processes.iter().collect();
+        if sort_by == SortBy::Pss {
+            processes.sort_by_key(|process| Reverse(process.pss_kb));
+        }
+        for process in processes.iter().take(rows.saturating_sub(HEADER_ROWS)) {
+            lines.push(format!(
+                "{:>7} {:>6} {:>9} {:<8} {:<5} {}",
+                process.pid,
+                process
+                    .cpu_percent
+                    .map_or_else(|| "-".to_owned(), |cpu| format!("{cpu:.1}")),
+                process.pss_kb.map_or_else(|| "-".to_owned(), size),
+                process.adj.as_deref().unwrap_or("-"),
+                process.proc_state.as_deref().unwrap_or("-"),
+                process.name
+            ));
+        }
+
+        lines
+            .iter()
+            .map(|line| line.chars().take(columns).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// `kb` in the largest unit that keeps it above 1.
+fn size(kb: u64) -> String {
+    if kb < 1024 {
+        format!("{kb}K")
+    } else if kb < 1024 * 1024 {
+        format!("{:.1}M", kb as f64 / 1024.0)
+    } else {
+        format!("{:.2}G", kb as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// The terminal in the alternate screen without echo or line buffering, restored on drop
+struct Terminal {
+    saved: Option<libc::termios>,
+}
+
+impl Terminal {
+    fn enter() -> Self {
+        // SAFETY: tcgetattr and tcsetattr only access the termios passed in.
+        let saved = unsafe {
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            (libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0).then(|| {
+                let saved = termios;
+                termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+                saved
+            })
+        };
+        print!("\x1b[?1049h\x1b[?25l");
+        Self { saved }
+    }
+
+    /// Rows and columns, 24 by 80 when stdout isn't a terminal.
+    fn size(&self) -> (usize, usize) {
+        // SAFETY: TIOCGWINSZ writes a winsize to the pointer.
+        unsafe {
+            let mut size = std::mem::zeroed::<libc::winsize>();
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_row > 0
+            {
+                return (size.ws_row.into(), size.ws_col.into());
+            }
+        }
+        (24, 80)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        if let Some(saved) = &self.saved {
+            // SAFETY: restores the attributes read in `enter`.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+use crate::{error::DumpError, parse, Dumpsys};
+
+/// Per-process binder state, exposed by binderfs or by debugfs on older kernels
+const BINDER_PROC_LOGS: [&str; 2] = ["/dev/binderfs/binder_logs/proc", "/d/binder/proc"];
+/// Binder context of the framework services, as opposed to `hwbinder` and `vndbinder`
+const BINDER_CONTEXT: &str = "binder";
+
+/// Binder thread pool usage of the process hosting a service, returned by [`Dumpsys::thread_usage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadUsage {
+    /// Threads currently handling a transaction
+    pub in_use: u32,
+    /// Binder threads the process has
+    pub total: u32,
+}
+
+impl ThreadUsage {
+    /// Whether every binder thread is busy, so new transactions queue up.
+    pub fn exhausted(&self) -> bool {
+        self.total > 0 && self.in_use >= self.total
+    }
+
+    /// Count the threads of the `binder` context in the text of `/dev/binderfs/binder_logs/proc/<pid>`,
+    /// in the same way as libbinderdebug.
+    pub fn parse(logs: &str) -> Self {
+        let mut usage = Self::default();
+        let mut in_context = false;
+
+        for line in logs.lines() {
+            if let Some(context) = line.strip_prefix("context ") {
+                in_context = context.trim() == BINDER_CONTEXT;
+                continue;
+            }
+            if !in_context {
+                continue;
+            }
+
+            // e.g. "  thread 1234: l 12 need_return 0 tr 0"
+            let Some(thread) = line.trim_start().strip_prefix("thread ") else {
+                continue;
+            };
+            let Some((_, looper)) = thread.split_once(": l ") else {
+                continue;
+            };
+            let mut state = looper.bytes();
+            let (Some(wait), Some(kind)) = (state.next(), state.next()) else {
+                continue;
+            };
+
+            // A first digit of 1 waits in the driver, a second one of 0 only called into binder.
+            if !wait.is_ascii_digit() || !kind.is_ascii_digit() || kind == b'0' {
+                continue;
+            }
+            if wait != b'1' {
+                usage.in_use += 1;
+            }
+            usage.total += 1;
+        }
+
+        usage
+    }
+
+    /// Like [`ThreadUsage::parse`], reading the logs from `reader`, e.g. a file saved earlier.
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        Ok(Self::parse(&parse::read_lossy(reader)?))
+    }
+}
+
+impl Dumpsys {
+    /// Binder threads in use by the process hosting the service, like `dumpsys --thread`.
+    ///
+    /// A pool that stays exhausted means dumps and other calls are about to time out.
+    /// Reading the binder logs usually takes root.
+    pub fn thread_usage(&self) -> Result<ThreadUsage, DumpError> {
+        let pid = self.pid()?;
+        let mut logs = Err(io::ErrorKind::NotFound.into());
+        for dir in BINDER_PROC_LOGS {
+            logs = fs::read_to_string(format!("{dir}/{pid}"));
+            if logs.is_ok() {
+                break;
+            }
+        }
+
+        Ok(ThreadUsage::parse(&logs.map_err(|err| self.error(err))?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGS: &str = "binder proc state:
+proc 1234
+context binder
+  thread 1234: l 12 need_return 0 tr 0
+  thread 1240: l 11 need_return 0 tr 0
+  thread 1241: l 01 need_return 0 tr 0
+  thread 1250: l 00 need_return 0 tr 0
+context hwbinder
+  thread 1260: l 01 need_return 0 tr 0
+";
+
+    #[test]
+    fn counts_threads_of_the_binder_context() {
+        assert_eq!(
+            ThreadUsage::parse(LOGS),
+            ThreadUsage {
+                in_use: 1,
+                total: 3
+            }
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = LOGS.replace('\n', "\r\n");
+        assert_eq!(ThreadUsage::parse(&crlf), ThreadUsage::parse(LOGS));
+    }
+
+    #[test]
+    fn empty_input_has_no_threads() {
+        assert_eq!(ThreadUsage::parse(""), ThreadUsage::default());
+        assert!(!ThreadUsage::default().exhausted());
+    }
+
+    #[test]
+    fn truncated_thread_lines_are_skipped() {
+        let logs =
+            "context binder\n  thread 1234: l 12 need_return 0\n  thread 1240: l 1\n  thread 12";
+        assert_eq!(
+            ThreadUsage::parse(logs),
+            ThreadUsage {
+                in_use: 0,
+                total: 1
+            }
+        );
+    }
+
+    #[test]
+    fn threads_outside_a_context_are_skipped() {
+        let logs = "  thread 1234: l 01 need_return 0 tr 0\ncontext vndbinder\n  thread 1240: l 01";
+        assert_eq!(ThreadUsage::parse(logs), ThreadUsage::default());
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use binder::{check_service, FromIBinder, SpIBinder};
+
+use crate::{
+    backend::Backend,
+    cancel::CancelToken,
+    execution::Execution,
+    instrument,
+    observer::{DumpObserver, Observer},
+    retry::RetryPolicy,
+    sched::ThreadOptions,
+    Dumpsys,
+};
+
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Per-instance settings applied to every dump
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Config {
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) dump_timeout: Option<Duration>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) buffer_capacity: usize,
+    pub(crate) pipe_size: Option<usize>,
+    pub(crate) max_output_bytes: Option<u64>,
+    pub(crate) execution: Execution,
+    pub(crate) backend: Backend,
+    pub(crate) cancel: Option<CancelToken>,
+    pub(crate) reconnect: bool,
+    pub(crate) verify: Option<fn(SpIBinder) -> bool>,
+    pub(crate) observer: Option<Observer>,
+    pub(crate) threads: ThreadOptions,
+    #[cfg(feature = "io-uring")]
+    pub(crate) io_uring: bool,
+    #[cfg(feature = "futures")]
+    pub(crate) stream_high_water_mark: Option<usize>,
+}
+
+impl Config {
+    /// Whether `service` passes the interface check, if there is one.
+    pub(crate) fn accepts(&self, service: &SpIBinder) -> bool {
+        self.verify.is_none_or(|verify| verify(service.clone()))
+    }
+}
+
+/// Configure a [`Dumpsys`] before resolving its service
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{Dumpsys, RetryPolicy};
+///
+/// # fn foo() -> Option<()> {
+/// let dumpsys = Dumpsys::builder("SurfaceFlinger")
+///     .connect_timeout(Duration::from_secs(10))
+///     .dump_timeout(Duration::from_secs(1))
+///     .retry(RetryPolicy::new(3, Duration::from_millis(50)))
+///     .buffer_capacity(64 * 1024)
+///     .pipe_size(1024 * 1024)
+///     .build()?;
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DumpsysBuilder {
+    service_name: String,
+    config: Config,
+}
+
+impl DumpsysBuilder {
+    pub(crate) fn new(service_name: String) -> Self {
+        Self {
+            service_name,
+            config: Config::default(),
+        }
+    }
+
+    /// Keep looking the service up until it appears or `timeout` expires.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on a dump that is still producing output after `timeout`.
+    ///
+    /// A service that doesn't return from the dump shortly after that marks the handle
+    /// [suspect](Dumpsys::is_suspect) until it does.
+    pub fn dump_timeout(mut self, timeout: Duration) -> Self {
+        self.config.dump_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry dumps whose binder transaction failed.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+
+    /// Bytes reserved up front for the output buffer of each dump.
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.config.buffer_capacity = capacity;
+        self
+    }
+
+    /// Kernel buffer size of the dump pipe, see `F_SETPIPE_SZ` in `fcntl(2)`.
+    ///
+    /// A larger pipe (e.g. 1 MiB) lets the service write big dumps without waiting for the reader.
+    /// Unprivileged processes get at most `/proc/sys/fs/pipe-max-size`.
+    pub fn pipe_size(mut self, size: usize) -> Self {
+        self.config.pipe_size = Some(size);
+        self
+    }
+
+    /// Stop reading after `limit` bytes and fail with [`DumpError::Truncated`](crate::error::DumpError::Truncated),
+    /// so a runaway service can't exhaust memory.
+    pub fn max_output_bytes(mut self, limit: u64) -> Self {
+        self.config.max_output_bytes = Some(limit);
+        self
+    }
+
+    /// Choose where the blocking dump transaction runs, see [`Execution`].
+    pub fn execution(mut self, execution: Execution) -> Self {
+        self.config.execution = execution;
+        self
+    }
+
+    /// Choose how dumps reach the service, e.g. through the `dumpsys` binary, see [`Backend`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    /// Look the service up again and retry when a dump finds it dead, e.g. after SurfaceFlinger restarted.
+    ///
+    /// The lookup waits up to the connect timeout, see also [`Dumpsys::refresh`].
+    pub fn reconnect(mut self, enable: bool) -> Self {
+        self.config.reconnect = enable;
+        self
+    }
+
+    /// Only accept a service implementing the AIDL interface `I`, in case another binder is registered
+    /// under the same name on some Android version.
+    ///
+    /// The descriptor declared for `I` with [`binder::declare_binder_interface`] is checked against the
+    /// service on connect and on [`Dumpsys::refresh`], and becomes its [`Dumpsys::interface_descriptor`].
+    pub fn expect_interface<I>(mut self) -> Self
+    where
+        I: FromIBinder + ?Sized,
+    {
+        self.config.verify = Some(|service| service.into_interface::<I>().is_ok());
+        self
+    }
+
+    /// Abort dumps in flight once `token` is cancelled, see [`CancelToken`].
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.config.cancel = Some(token);
+        self
+    }
+
+    /// Read the dump pipe with io_uring instead of blocking reads, falling back when the kernel refuses.
+    #[cfg(feature = "io-uring")]
+    pub fn io_uring(mut self, enable: bool) -> Self {
+        self.config.io_uring = enable;
+        self
+    }
+
+    /// Let [`Dumpsys::dump_stream`] queue up to `bytes` of output ahead of a slow consumer, 256 KiB by
+    /// default.
+    ///
+    /// Once the queue holds that much, reading pauses and the pipe fills up until the service blocks
+    /// writing. Reading resumes when the consumer has taken the queue down to half of it.
+    #[cfg(feature = "futures")]
+    pub fn stream_high_water_mark(mut self, bytes: usize) -> Self {
+        self.config.stream_high_water_mark = Some(bytes);
+        self
+    }
+
+    /// Name and schedule the threads dumps spawn as `options` says, see [`ThreadOptions`].
+    ///
+    /// With the default [`Execution::Spawn`], that's the thread running the transaction: the pipe of
+    /// [`Dumpsys::dump`] is read on the calling thread, which is left as it is, and
+    /// `Dumpsys::dump_stream` reads it on a spawned thread the options apply to as well.
+    pub fn threads(mut self, options: ThreadOptions) -> Self {
+        self.config.threads = options;
+        self
+    }
+
+    /// Report every dump attempt to `observer`, which can be shared by the handles of several services.
+    pub fn observer(mut self, observer: Arc<dyn DumpObserver>) -> Self {
+        self.config.observer = Some(Observer(observer));
+        self
+    }
+
+    /// Resolve the service, `None` if it didn't show up in time.
+    pub fn build(self) -> Option<Dumpsys> {
+        let service = connect(&self.service_name, self.config.connect_timeout)
+            .filter(|service| self.config.accepts(service))?;
+        Some(Dumpsys::from_parts(self.service_name, service, self.config))
+    }
+}
+
+pub(crate) fn connect(service_name: &str, timeout: Option<Duration>) -> Option<SpIBinder> {
+    instrument::resolve(service_name, || match timeout {
+        Some(timeout) => wait_for(
+            service_name,
+            Instant::now() + timeout,
+            CONNECT_POLL_INTERVAL,
+        ),
+        None => check_service(service_name),
+    })
+}
+
+/// Poll servicemanager every `poll_interval` until the service shows up or `deadline` passes.
+pub(crate) fn wait_for(
+    service_name: &str,
+    deadline: Instant,
+    poll_interval: Duration,
+) -> Option<SpIBinder> {
+    loop {
+        if let Some(service) = check_service(service_name) {
+            return Some(service);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+        thread::sleep(remaining.min(poll_interval));
+    }
+}
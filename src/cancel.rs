@@ -0,0 +1,49 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Aborts in-flight dumps from another thread
+///
+/// A dump sharing a cancelled token stops reading within a few tens of milliseconds, closes the pipe so
+/// the service stops writing, and fails with [`DumpError::Cancelled`](crate::error::DumpError::Cancelled).
+/// Dumps started after cancellation fail right away until the token is [reset](CancelToken::reset).
+///
+/// # Example
+///
+/// ```
+/// use std::thread;
+///
+/// use dumpsys_rs::{CancelToken, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// let token = CancelToken::new();
+/// let stop = token.clone();
+/// thread::spawn(move || stop.cancel());
+///
+/// let result = Dumpsys::new("meminfo")?.dump_cancellable(&["-a"], &token);
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort every dump using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Allow dumps with this token again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,492 @@
+//! The `--checkin` format of `batterystats`
+//!
+//! Every line is a comma separated record `version,uid,category,section,values...`, except the battery
+//! history, whose lines are `version,h,...` and whose strings are pooled in `version,hsp,...` lines.
+//! Unlike the human readable dump, the field order of a section only grows at its end between Android
+//! releases.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::checkin::Checkin;
+//!
+//! let checkin = Checkin::parse(
+//!     "9,0,i,vers,36,214,PPR1.180610.011,PPR1.180610.011
+//! 9,hsp,0,1000,\"*alarm*\"
+//! 9,h,0:RESET:TIME:1540000000000
+//! 9,h,1200,+w=0
+//! 9,10013,l,wl,*alarm*,0,f,0,1500,p,3,0,w,0
+//! 9,10013,l,nt,100,200,3000,4000,1,2,30,40",
+//! )
+//! .unwrap();
+//!
+//! let wakelocks: Vec<_> = checkin.wakelocks().collect();
+//! assert_eq!(wakelocks[0].name, "*alarm*");
+//! assert_eq!(wakelocks[0].partial, Duration::from_millis(1500));
+//! assert_eq!(checkin.network().next().unwrap().wifi_rx_bytes, 3000);
+//! assert_eq!(checkin.history[1].events, ["+w=0"]);
+//! assert_eq!(checkin.history_string(0), Some("*alarm*"));
+//! ```
+
+use std::{collections::HashMap, mem, time::Duration};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_int},
+    DumpParse, Dumpsys,
+};
+
+const CHECKIN_ARG: &str = "--checkin";
+const HISTORY: &str = "h";
+const HISTORY_STRING_POOL: &str = "hsp";
+const WAKELOCK: &str = "wl";
+const NETWORK: &str = "nt";
+
+/// Period a record covers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Category {
+    /// `i`, static information such as the version
+    Info,
+    /// `l`, since the device was last fully charged
+    SinceCharged,
+    /// `c`, since the stats were last reset
+    Current,
+    /// `u`, since the device was last unplugged
+    Unplugged,
+    /// A category this crate doesn't know
+    Other(String),
+}
+
+impl Category {
+    fn parse(text: &str) -> Self {
+        match text {
+            "i" => Self::Info,
+            "l" => Self::SinceCharged,
+            "c" => Self::Current,
+            "u" => Self::Unplugged,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// One `version,uid,category,section,values...` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    pub version: u32,
+    /// 0 for device wide records
+    pub uid: u32,
+    pub category: Category,
+    /// Section name such as `wl` for wake locks or `nt` for network
+    pub section: String,
+    pub values: Vec<String>,
+}
+
+/// One battery history line, `version,h,time,events...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistoryEntry {
+    /// Time since the previous entry
+    pub delta: Duration,
+    /// State changes such as `+w=0`, where the number refers to [`Checkin::history_string`], or
+    /// `RESET:TIME:<ms>` for the start of the history
+    pub events: Vec<String>,
+}
+
+/// Wake lock of an app, from the `wl` section
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Wakelock {
+    pub uid: u32,
+    pub category: Category,
+    pub name: String,
+    /// Time a full wake lock was held
+    pub full: Duration,
+    pub full_count: u64,
+    /// Time a partial wake lock was held, the kind keeping the CPU awake with the screen off
+    pub partial: Duration,
+    pub partial_count: u64,
+    pub window: Duration,
+    pub window_count: u64,
+}
+
+/// Network traffic of an app, or of the device for uid 0, from the `nt` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NetworkUsage {
+    pub uid: u32,
+    pub mobile_rx_bytes: u64,
+    pub mobile_tx_bytes: u64,
+    pub wifi_rx_bytes: u64,
+    pub wifi_tx_bytes: u64,
+    pub mobile_rx_packets: u64,
+    pub mobile_tx_packets: u64,
+    pub wifi_rx_packets: u64,
+    pub wifi_tx_packets: u64,
+}
+
+/// Parsed output of `dumpsys batterystats --checkin`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkin {
+    /// Every record other than the history, in order
+    pub records: Vec<Record>,
+    pub history: Vec<HistoryEntry>,
+    /// History strings by index, with the uid they belong to
+    pub history_strings: HashMap<u32, (u32, String)>,
+}
+
+impl Checkin {
+    /// Parse checkin output, skipping lines that aren't records.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut checkin = Self::default();
+
+        for line in text.lines() {
+            let fields = split(line.trim());
+            let [version, kind, rest @ ..] = fields.as_slice() else {
+                continue;
+            };
+            let Ok(version) = version.parse() else {
+                continue;
+            };
+
+            match kind.as_str() {
+                HISTORY => checkin.history.push(history_entry(rest)?),
+                HISTORY_STRING_POOL => {
+                    let [index, uid, string, ..] = rest else {
+                        return Err(invalid(HISTORY_STRING_POOL, line));
+                    };
+                    checkin.history_strings.insert(
+                        number(HISTORY_STRING_POOL, index)?,
+                        (number(HISTORY_STRING_POOL, uid)?, string.clone()),
+                    );
+                }
+                uid => {
+                    let [category, section, values @ ..] = rest else {
+                        continue;
+                    };
+                    checkin.records.push(Record {
+                        version,
+                        uid: number("uid", uid)?,
+                        category: Category::parse(category),
+                        section: section.clone(),
+                        values: values.to_vec(),
+                    });
+                }
+            }
+        }
+
+        Ok(checkin)
+    }
+
+    /// Records of `section`, e.g. `bt` for battery totals.
+    pub fn section<'s>(&'s self, section: &'s str) -> impl Iterator<Item = &'s Record> + 's {
+        self.records
+            .iter()
+            .filter(move |record| record.section == section)
+    }
+
+    /// String `index` of the history string pool.
+    pub fn history_string(&self, index: u32) -> Option<&str> {
+        self.history_strings
+            .get(&index)
+            .map(|(_, string)| string.as_str())
+    }
+
+    /// Wake locks listed in the `wl` section, skipping malformed records.
+    pub fn wakelocks(&self) -> impl Iterator<Item = Wakelock> + '_ {
+        self.section(WAKELOCK).filter_map(|record| {
+            // name, full time, "f", full count, partial time, "p", partial count, window time, "w", window count
+            let values = &record.values;
+            let int = |i: usize| values.get(i)?.parse::<u64>().ok();
+            Some(Wakelock {
+                uid: record.uid,
+                category: record.category.clone(),
+                name: values.first()?.clone(),
+                full: Duration::from_millis(int(1)?),
+                full_count: int(3)?,
+                partial: Duration::from_millis(int(4)?),
+                partial_count: int(6)?,
+                window: Duration::from_millis(int(7)?),
+                window_count: int(9)?,
+            })
+        })
+    }
+
+    /// Network traffic listed in the `nt` section, skipping malformed records.
+    pub fn network(&self) -> impl Iterator<Item = NetworkUsage> + '_ {
+        self.section(NETWORK).filter_map(|record| {
+            let mut values = record.values.iter().map(|value| value.parse::<u64>().ok());
+            let mut next = || values.next().flatten();
+            Some(NetworkUsage {
+                uid: record.uid,
+                mobile_rx_bytes: next()?,
+                mobile_tx_bytes: next()?,
+                wifi_rx_bytes: next()?,
+                wifi_tx_bytes: next()?,
+                mobile_rx_packets: next()?,
+                mobile_tx_packets: next()?,
+                wifi_rx_packets: next()?,
+                wifi_tx_packets: next()?,
+            })
+        })
+    }
+}
+
+impl DumpParse for Checkin {
+    const SERVICE: &'static str = "batterystats";
+    const ARGS: &'static [&'static str] = &[CHECKIN_ARG];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+impl Dumpsys {
+    /// Dump in the machine readable `--checkin` format, e.g. of `batterystats`, `procstats` or
+    /// `meminfo`. See [`Checkin`] for parsing `batterystats`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::{checkin::Checkin, Dumpsys};
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let text = Dumpsys::new("batterystats")?
+    ///     .dump_checkin(Vec::<&str>::new())
+    ///     .unwrap();
+    /// for wakelock in Checkin::parse(&text).unwrap().wakelocks() {
+    ///     println!("{} {:?}", wakelock.name, wakelock.partial);
+    /// }
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_checkin(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, DumpError> {
+        let args: Vec<String> = [CHECKIN_ARG.to_owned()]
+            .into_iter()
+            .chain(args.into_iter().map(|arg| arg.as_ref().to_owned()))
+            .collect();
+        self.dump(&args)
+    }
+}
+
+/// `time,events...` of a history line, where the time may carry the first event as in `0:RESET:TIME:...`.
+fn history_entry(fields: &[String]) -> Result<HistoryEntry, ParseError> {
+    let Some((time, events)) = fields.split_first() else {
+        return Err(ParseError::Missing(HISTORY.to_owned()));
+    };
+    let (time, first) = match time.split_once(':') {
+        Some((time, first)) => (time, Some(first.to_owned())),
+        None => (time.as_str(), None),
+    };
+    let delta = parse_int(time)
+        .and_then(|ms| u64::try_from(ms).ok())
+        .ok_or_else(|| invalid(HISTORY, time))?;
+
+    Ok(HistoryEntry {
+        delta: Duration::from_millis(delta),
+        events: first.into_iter().chain(events.iter().cloned()).collect(),
+    })
+}
+
+fn number(key: &str, value: &str) -> Result<u32, ParseError> {
+    value.parse().map_err(|_| invalid(key, value))
+}
+
+/// Split a record on commas, except inside `"quoted"` strings, dropping the quotes.
+fn split(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if !line.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys batterystats --checkin` of Android 14, cut down to a few records of each section
+    const DUMP: &str = r#"9,0,i,vers,36,214,UP1A.231005.007,UP1A.231005.007
+9,0,i,uid,1000,android
+9,0,i,uid,10231,com.example.app
+9,hsp,0,1000,"*alarm*"
+9,hsp,1,10231,"*job*/com.example.app/.SyncJob"
+9,hsp,2,1001,"telephony-radio,qmi"
+9,h,0:RESET:TIME:1710144764000
+9,h,0,Bl=100,Bs=d,Bh=g,Bp=n,Bt=254,Bv=4371,+r,+w=0,+S
+9,h,1012,-w
+9,h,723456,Bl=99,+w=1
+9,0,l,bt,0,18750104,6002518,18763420,6015834,1710144764000,18740011,5990021,4410,1103,1147,4480
+9,0,l,nt,12345678,2345678,98765432,8765432,12000,9000,80000,60000,0,0,0,0,0,0,0,0
+9,10231,l,nt,1234567,234567,9876543,876543,1200,900,8000,6000,0,0,0,0,0,0,0,0
+9,10231,l,wl,*job*/com.example.app/.SyncJob,0,f,0,252030,p,14,0,w,0,0,bp,0,0,bc,0,0,bt
+9,1000,l,wl,*alarm*,0,f,0,62003,p,40,0,w,0
+9,1000,c,wl,*alarm*,0,f,0,1200,p,2,0,w,0
+9,10231,l,pwi,uid,214,0,150,34
+"#;
+
+    #[test]
+    fn parses_the_records() {
+        let checkin = Checkin::parse(DUMP).unwrap();
+        assert_eq!(checkin.records.len(), 10);
+        assert_eq!(
+            checkin.records[0],
+            Record {
+                version: 9,
+                uid: 0,
+                category: Category::Info,
+                section: "vers".to_owned(),
+                values: ["36", "214", "UP1A.231005.007", "UP1A.231005.007"]
+                    .map(str::to_owned)
+                    .to_vec(),
+            }
+        );
+        let battery = checkin.section("bt").next().unwrap();
+        assert_eq!(battery.category, Category::SinceCharged);
+        assert_eq!(battery.values[1], "18750104");
+        assert_eq!(checkin.section("pwi").next().unwrap().uid, 10231);
+        assert_eq!(checkin.section("dc").count(), 0);
+    }
+
+    #[test]
+    fn parses_the_history() {
+        let checkin = Checkin::parse(DUMP).unwrap();
+        assert_eq!(checkin.history.len(), 4);
+        assert_eq!(checkin.history[0].events, ["RESET:TIME:1710144764000"]);
+        assert_eq!(checkin.history[1].delta, Duration::ZERO);
+        assert_eq!(checkin.history[1].events.len(), 9);
+        assert_eq!(
+            checkin.history[3],
+            HistoryEntry {
+                delta: Duration::from_millis(723_456),
+                events: vec!["Bl=99".to_owned(), "+w=1".to_owned()],
+            }
+        );
+        assert_eq!(
+            checkin.history_strings[&1],
+            (10231, "*job*/com.example.app/.SyncJob".to_owned())
+        );
+        // Quoted strings keep their commas
+        assert_eq!(checkin.history_string(2), Some("telephony-radio,qmi"));
+        assert_eq!(checkin.history_string(3), None);
+    }
+
+    #[test]
+    fn parses_wakelocks() {
+        let checkin = Checkin::parse(DUMP).unwrap();
+        let wakelocks: Vec<_> = checkin.wakelocks().collect();
+        assert_eq!(wakelocks.len(), 3);
+        assert_eq!(
+            wakelocks[0],
+            Wakelock {
+                uid: 10231,
+                category: Category::SinceCharged,
+                name: "*job*/com.example.app/.SyncJob".to_owned(),
+                full: Duration::ZERO,
+                full_count: 0,
+                partial: Duration::from_millis(252_030),
+                partial_count: 14,
+                window: Duration::ZERO,
+                window_count: 0,
+            }
+        );
+        assert_eq!(wakelocks[2].category, Category::Current);
+        assert_eq!(wakelocks[2].partial_count, 2);
+    }
+
+    #[test]
+    fn parses_network_usage() {
+        let checkin = Checkin::parse(DUMP).unwrap();
+        let network: Vec<_> = checkin.network().collect();
+        assert_eq!(network.len(), 2);
+        assert_eq!(network[0].uid, 0);
+        assert_eq!(
+            network[1],
+            NetworkUsage {
+                uid: 10231,
+                mobile_rx_bytes: 1_234_567,
+                mobile_tx_bytes: 234_567,
+                wifi_rx_bytes: 9_876_543,
+                wifi_tx_bytes: 876_543,
+                mobile_rx_packets: 1200,
+                mobile_tx_packets: 900,
+                wifi_rx_packets: 8000,
+                wifi_tx_packets: 6000,
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_records_are_skipped() {
+        let text = "Checkin of batterystats:
+9,10231,l
+9,10231
+9,1000,l,wl,*alarm*,0,f
+9,1000,l,nt,100,200,3000";
+        let checkin = Checkin::parse(text).unwrap();
+        assert_eq!(checkin.records.len(), 2);
+        assert_eq!(checkin.wakelocks().count(), 0);
+        assert_eq!(checkin.network().count(), 0);
+    }
+
+    #[test]
+    fn unknown_categories_are_kept() {
+        let checkin = Checkin::parse("9,0,x,dc,1,2").unwrap();
+        assert_eq!(checkin.records[0].category, Category::Other("x".to_owned()));
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(
+            Checkin::parse("9,u0a231,l,wl,*alarm*").unwrap_err(),
+            invalid("uid", "u0a231")
+        );
+        assert_eq!(
+            Checkin::parse("9,hsp,3").unwrap_err(),
+            invalid(HISTORY_STRING_POOL, "9,hsp,3")
+        );
+        assert_eq!(
+            Checkin::parse("9,hsp,x,1000,wake").unwrap_err(),
+            invalid(HISTORY_STRING_POOL, "x")
+        );
+        assert_eq!(
+            Checkin::parse("9,h,-12,+w").unwrap_err(),
+            invalid(HISTORY, "-12")
+        );
+        assert_eq!(
+            Checkin::parse("9,h").unwrap_err(),
+            ParseError::Missing(HISTORY.to_owned())
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            Checkin::parse(&crlf).unwrap(),
+            Checkin::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(Checkin::parse("").unwrap(), Checkin::default());
+    }
+}
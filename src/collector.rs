@@ -0,0 +1,357 @@
+//! Dump a set of services into one tar archive, a quick programmatic bugreport
+//!
+//! Each service becomes a `<service>.txt` entry next to a `manifest.json` describing every dump,
+//! including the ones that failed. Wrap the writer in a compressor such as
+//! [`GzEncoder`](https://docs.rs/flate2) for a `.tar.gz`.
+//!
+//! # Example
+//!
+//! ```
+//! use std::{fs::File, time::Duration};
+//!
+//! use dumpsys_rs::{
+//!     collector::{Collector, Section},
+//!     DumpPriority,
+//! };
+//!
+//! # fn foo() -> Option<()> {
+//! let archive = File::create("/data/local/tmp/dumps.tar").ok()?;
+//! let manifest = Collector::new()
+//!     .section(Section::new("SurfaceFlinger").args(&["--latency"]))
+//!     .section(Section::new("meminfo").priority(DumpPriority::Critical))
+//!     .section(Section::new("batterystats").timeout(Duration::from_secs(30)))
+//!     .timeout(Duration::from_secs(10))
+//!     .write_tar(archive)
+//!     .ok()?;
+//! for entry in manifest.entries.iter().filter(|entry| entry.error.is_some()) {
+//!     eprintln!("{} failed", entry.service);
+//! }
+//! # Some(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashSet,
+    fmt::Write as _,
+    io::{self, Write},
+    num::NonZeroUsize,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    batch::{self, Job},
+    owned_args, DumpPriority,
+};
+
+const BLOCK: usize = 512;
+const MANIFEST: &str = "manifest.json";
+
+/// One service to collect
+#[derive(Debug, Clone)]
+pub struct Section {
+    service: String,
+    args: Vec<String>,
+    priority: Option<DumpPriority>,
+    timeout: Option<Duration>,
+}
+
+impl Section {
+    /// Dump `service` without arguments.
+    pub fn new(service: impl AsRef<str>) -> Self {
+        Self {
+            service: service.as_ref().to_owned(),
+            args: Vec::new(),
+            priority: None,
+            timeout: None,
+        }
+    }
+
+    /// Pass `args` to the dump.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.args = owned_args(args);
+        self
+    }
+
+    /// Dump only the `priority` section, see [`Dumpsys::dump_with_priority`](crate::Dumpsys::dump_with_priority).
+    pub fn priority(mut self, priority: DumpPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Give up on this dump after `timeout`, overriding [`Collector::timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// Dumps a set of services concurrently into a tar archive, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Collector {
+    sections: Vec<Section>,
+    parallelism: usize,
+    timeout: Option<Duration>,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector {
+    /// An empty collection running as many dumps at once as there are CPUs.
+    pub fn new() -> Self {
+        Self {
+            sections: Vec::new(),
+            parallelism: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            timeout: None,
+        }
+    }
+
+    /// Add a service to the archive.
+    pub fn section(mut self, section: Section) -> Self {
+        self.sections.push(section);
+        self
+    }
+
+    /// Run at most `parallelism` dumps at once, at least one.
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Give up on each dump without a timeout of its own after `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Dump every section and write the archive to `out`, returning the manifest stored in it.
+    ///
+    /// Failed dumps still get an entry with whatever output was read before they failed.
+    pub fn write_tar(self, mut out: impl Write) -> io::Result<Manifest> {
+        let captured_at = SystemTime::now();
+        let start = Instant::now();
+
+        let jobs: Vec<_> = self
+            .sections
+            .iter()
+            .map(|section| Job {
+                service: section.service.clone(),
+                args: section
+                    .priority
+                    .map_or(&[][..], DumpPriority::args)
+                    .iter()
+                    .map(|arg| (*arg).to_owned())
+                    .chain(section.args.iter().cloned())
+                    .collect(),
+                timeout: section.timeout.or(self.timeout),
+            })
+            .collect();
+        let results = batch::run_parallel(&jobs, self.parallelism);
+
+        let mut names = HashSet::new();
+        let mut files = Vec::with_capacity(jobs.len());
+        let mut entries = Vec::with_capacity(jobs.len());
+        for (job, result) in jobs.into_iter().zip(results) {
+            let file = file_name(&job.service, &mut names);
+            let (output, entry) = match result {
+                Ok(snapshot) => {
+                    let entry = ManifestEntry {
+                        file: file.clone(),
+                        service: job.service,
+                        args: job.args,
+                        bytes: snapshot.output.len() as u64,
+                        captured_at: Some(snapshot.captured_at),
+                        duration: Some(snapshot.duration),
+                        error: None,
+                    };
+                    (snapshot.output.into_bytes(), entry)
+                }
+                Err(err) => {
+                    let output = err.partial().to_vec();
+                    let entry = ManifestEntry {
+                        file: file.clone(),
+                        service: job.service,
+                        args: job.args,
+                        bytes: output.len() as u64,
+                        captured_at: None,
+                        duration: None,
+                        error: Some(err.to_string()),
+                    };
+                    (output, entry)
+                }
+            };
+            files.push((file, output));
+            entries.push(entry);
+        }
+
+        let manifest = Manifest {
+            captured_at,
+            duration: start.elapsed(),
+            entries,
+        };
+        let mtime = unix_secs(captured_at);
+        write_entry(&mut out, MANIFEST, manifest.to_json().as_bytes(), mtime)?;
+        for (file, output) in &files {
+            write_entry(&mut out, file, output, mtime)?;
+        }
+        out.write_all(&[0; 2 * BLOCK])?;
+        out.flush()?;
+        Ok(manifest)
+    }
+}
+
+/// Description of a collected archive, stored in it as `manifest.json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    /// Wall clock time the collection started
+    pub captured_at: SystemTime,
+    /// How long collecting all sections took
+    pub duration: Duration,
+    /// One entry per section, in the order they were added
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// One dump of a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    /// Path of the output in the archive
+    pub file: String,
+    pub service: String,
+    /// Arguments passed to the service, including those selecting the priority
+    pub args: Vec<String>,
+    /// Size of the stored output
+    pub bytes: u64,
+    /// Wall clock time the dump started, `None` if it failed
+    pub captured_at: Option<SystemTime>,
+    /// How long the dump took, `None` if it failed
+    pub duration: Option<Duration>,
+    /// Why the dump failed
+    pub error: Option<String>,
+}
+
+impl Manifest {
+    fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            "{{\n  \"captured_at_ms\": {},\n  \"duration_ms\": {},\n  \"entries\": [",
+            unix_millis(self.captured_at),
+            self.duration.as_millis()
+        );
+        for (i, entry) in self.entries.iter().enumerate() {
+            json.push_str(if i == 0 { "\n    {" } else { ",\n    {" });
+            let _ = write!(json, "\"file\": {}", quote(&entry.file));
+            let _ = write!(json, ", \"service\": {}", quote(&entry.service));
+            json.push_str(", \"args\": [");
+            for (i, arg) in entry.args.iter().enumerate() {
+                if i > 0 {
+                    json.push_str(", ");
+                }
+                json.push_str(&quote(arg));
+            }
+            let _ = write!(json, "], \"bytes\": {}", entry.bytes);
+            if let Some(captured_at) = entry.captured_at {
+                let _ = write!(json, ", \"captured_at_ms\": {}", unix_millis(captured_at));
+            }
+            if let Some(duration) = entry.duration {
+                let _ = write!(json, ", \"duration_ms\": {}", duration.as_millis());
+            }
+            if let Some(error) = &entry.error {
+                let _ = write!(json, ", \"error\": {}", quote(error));
+            }
+            json.push('}');
+        }
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+}
+
+/// `value` as a JSON string literal.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A unique `<service>.txt` archive path, numbering repeated services.
+fn file_name(service: &str, taken: &mut HashSet<String>) -> String {
+    let stem: String = service
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .take(80)
+        .collect();
+
+    let mut name = format!("{stem}.txt");
+    let mut n = 2;
+    while !taken.insert(name.clone()) {
+        name = format!("{stem}-{n}.txt");
+        n += 1;
+    }
+    name
+}
+
+/// Write a regular file to a ustar archive.
+fn write_entry(out: &mut impl Write, name: &str, data: &[u8], mtime: u64) -> io::Result<()> {
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], data.len() as u64);
+    octal(&mut header[136..148], mtime);
+    header[148..156].fill(b' ');
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    octal(&mut header[148..155], u64::from(checksum));
+
+    out.write_all(&header)?;
+    out.write_all(data)?;
+    let padding = (BLOCK - data.len() % BLOCK) % BLOCK;
+    out.write_all(&[0; BLOCK][..padding])
+}
+
+/// Fill `field` with `value` as zero padded octal followed by a NUL.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let text = format!("{value:0digits$o}");
+    field[..digits].copy_from_slice(&text.as_bytes()[text.len() - digits..]);
+    field[digits] = 0;
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
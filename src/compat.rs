@@ -0,0 +1,206 @@
+//! The Android release of the device, and what requests take on it
+//!
+//! Services gain flags and change their output from one release to the next: `dumpsys --proto` and
+//! `--priority` need Android 9, `meminfo --unreachable` Android 7, and SurfaceFlinger names the layers of
+//! an app differently on nearly every release. [`Sdk::current`] reads the API level of the device,
+//! and [`Compat`] turns requests like "the frame latency of this activity" into the dumps that answer
+//! them there, failing with [`io::ErrorKind::Unsupported`] where the release can't.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::compat::{Compat, Feature, Sdk};
+//!
+//! let compat = Compat::new(Sdk::P);
+//! assert!(compat.supports(Feature::Proto));
+//! assert!(!Compat::new(Sdk::O).supports(Feature::DumpPriority));
+//!
+//! # fn foo() -> Option<()> {
+//! let compat = Compat::detect()?;
+//! let latency = compat
+//!     .frame_latency("com.example.game/.MainActivity")
+//!     .unwrap();
+//! println!("{:?} fps on {}", latency.fps(), compat.sdk());
+//! # Some(())
+//! # }
+//! ```
+
+use std::{fmt, io, sync::OnceLock};
+
+use crate::{
+    error::DumpError,
+    gfxinfo::Gfxinfo,
+    surfaceflinger::{self, Latency},
+    typed, DumpParse,
+};
+
+const FRAMESTATS_ARG: &str = "framestats";
+const UNREACHABLE_ARG: &str = "--unreachable";
+
+/// An Android API level, as in `Build.VERSION.SDK_INT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sdk(pub u32);
+
+impl Sdk {
+    /// Android 6
+    pub const M: Self = Self(23);
+    /// Android 7
+    pub const N: Self = Self(24);
+    /// Android 8
+    pub const O: Self = Self(26);
+    /// Android 9
+    pub const P: Self = Self(28);
+    /// Android 10
+    pub const Q: Self = Self(29);
+    /// Android 11
+    pub const R: Self = Self(30);
+    /// Android 12
+    pub const S: Self = Self(31);
+    /// Android 13
+    pub const TIRAMISU: Self = Self(33);
+    /// Android 14
+    pub const UPSIDE_DOWN_CAKE: Self = Self(34);
+    /// Android 15
+    pub const VANILLA_ICE_CREAM: Self = Self(35);
+
+    /// The API level of the device from `ro.build.version.sdk`, read once. `None` off-device, where
+    /// [`Compat::new`] takes the release to act as.
+    pub fn current() -> Option<Self> {
+        static CURRENT: OnceLock<Option<Sdk>> = OnceLock::new();
+        *CURRENT.get_or_init(|| {
+            let sdk = system_property(c"ro.build.version.sdk")?;
+            sdk.trim().parse().ok().map(Self)
+        })
+    }
+}
+
+impl fmt::Display for Sdk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API {}", self.0)
+    }
+}
+
+#[cfg(target_os = "android")]
+fn system_property(name: &std::ffi::CStr) -> Option<String> {
+    let mut value = [0; libc::PROP_VALUE_MAX as usize];
+    // SAFETY: `value` has room for the longest property value and its nul terminator.
+    let len = unsafe { libc::__system_property_get(name.as_ptr(), value.as_mut_ptr()) };
+    if len <= 0 {
+        return None;
+    }
+    // SAFETY: the value was written nul terminated.
+    let value = unsafe { std::ffi::CStr::from_ptr(value.as_ptr()) };
+    value.to_str().ok().map(str::to_owned)
+}
+
+/// There are no system properties off-device.
+#[cfg(not(target_os = "android"))]
+fn system_property(_name: &std::ffi::CStr) -> Option<String> {
+    None
+}
+
+/// Flags and dumps that not every release has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    /// `gfxinfo <package> framestats`, the timestamps of every frame
+    Framestats,
+    /// `meminfo --unreachable <process>`, native memory nothing points to anymore
+    UnreachableMemory,
+    /// `dumpsys --proto`, see [`Dumpsys::dump_proto`](crate::Dumpsys::dump_proto)
+    Proto,
+    /// `dumpsys --priority`, see [`Dumpsys::dump_with_priority`](crate::Dumpsys::dump_with_priority)
+    DumpPriority,
+    /// `SurfaceFlinger --timestats`, see [`surfaceflinger::timestats`]
+    TimeStats,
+}
+
+impl Feature {
+    /// The first release with the feature.
+    pub const fn since(self) -> Sdk {
+        match self {
+            Self::Framestats => Sdk::M,
+            Self::UnreachableMemory => Sdk::N,
+            Self::Proto | Self::DumpPriority | Self::TimeStats => Sdk::P,
+        }
+    }
+}
+
+/// Requests mapped to the arguments and parsers of one release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compat {
+    sdk: Sdk,
+}
+
+impl Compat {
+    /// Act as on release `sdk`, e.g. to handle the output of another device.
+    pub fn new(sdk: Sdk) -> Self {
+        Self { sdk }
+    }
+
+    /// Act as on the release of the device, see [`Sdk::current`].
+    pub fn detect() -> Option<Self> {
+        Sdk::current().map(Self::new)
+    }
+
+    /// The release acted as.
+    pub fn sdk(&self) -> Sdk {
+        self.sdk
+    }
+
+    /// Whether the release has `feature`.
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.sdk >= feature.since()
+    }
+
+    /// Arguments of `gfxinfo` for the rendering stats of `package`: with the timestamps of every frame
+    /// where the release has them, and the summary alone before.
+    pub fn frame_stats_args<'a>(&self, package: &'a str) -> Vec<&'a str> {
+        if self.supports(Feature::Framestats) {
+            vec![package, FRAMESTATS_ARG]
+        } else {
+            vec![package]
+        }
+    }
+
+    /// Rendering stats of `package`, without [`Gfxinfo::all_frames`] before Android 6.
+    pub fn frame_stats(&self, package: &str) -> Result<Gfxinfo, DumpError> {
+        typed::dump_service::<Gfxinfo>(self.frame_stats_args(package))
+    }
+
+    /// Frame timings of activity `component`, e.g. `com.example.game/.MainActivity`, from the layer
+    /// its frames go to on this release, see [`surfaceflinger::activity_layer`].
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if SurfaceFlinger has no layer of the activity, e.g.
+    /// because it isn't visible.
+    pub fn frame_latency(&self, component: &str) -> Result<Latency, DumpError> {
+        let dumpsys = typed::connect(Latency::SERVICE, &[])?;
+        let layers = surfaceflinger::layers(&dumpsys)?;
+        let Some(layer) = surfaceflinger::activity_layer(&layers, component) else {
+            let err = io::Error::new(io::ErrorKind::NotFound, format!("no layer of {component}"));
+            return Err(DumpError::from(err).with_context(Latency::SERVICE, &[]));
+        };
+        surfaceflinger::latency(&dumpsys, layer)
+    }
+
+    /// Native memory of `process` that nothing points to anymore, as `meminfo --unreachable` prints it.
+    pub fn unreachable_memory(&self, process: &str) -> Result<String, DumpError> {
+        const SERVICE: &str = "meminfo";
+        let args = [UNREACHABLE_ARG.to_owned(), process.to_owned()];
+        self.require(Feature::UnreachableMemory, SERVICE, &args)?;
+        typed::connect(SERVICE, &args)?.dump(&args)
+    }
+
+    /// Fail a dump of `service` with `args` that needs `feature` if the release lacks it.
+    fn require(&self, feature: Feature, service: &str, args: &[String]) -> Result<(), DumpError> {
+        if self.supports(feature) {
+            return Ok(());
+        }
+        let err = io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{feature:?} needs {}, not {}", feature.since(), self.sdk),
+        );
+        Err(DumpError::from(err).with_context(service, args))
+    }
+}
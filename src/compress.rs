@@ -0,0 +1,143 @@
+use std::io::{self, Write};
+
+use crate::{sink::header, DumpSink, Snapshot};
+
+/// Writes snapshots gzip compressed to `W`, in the format of [`RotatingFileSink`](crate::RotatingFileSink)
+///
+/// Rotating ends the current gzip member and starts a new one; concatenated members decompress as
+/// one stream with `zcat`. Call [`GzipSink::finish`] to write the trailer, dropping the sink without it
+/// leaves a truncated stream.
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+///
+/// use dumpsys_rs::{DumpSink, Dumpsys, GzipSink};
+///
+/// # fn foo() -> Option<()> {
+/// let file = File::create("/data/local/tmp/batterystats.log.gz").unwrap();
+/// let mut sink = GzipSink::new(file, 6);
+/// let snapshot = Dumpsys::new("batterystats")?.dump_snapshot(&["--checkin"]).unwrap();
+/// sink.write_snapshot(&snapshot).unwrap();
+/// sink.finish().unwrap();
+/// # Some(())
+/// # }
+/// ```
+#[cfg(feature = "gzip")]
+#[derive(Debug)]
+pub struct GzipSink<W: Write> {
+    encoder: Option<flate2::write::GzEncoder<W>>,
+    level: flate2::Compression,
+}
+
+#[cfg(feature = "gzip")]
+impl<W: Write> GzipSink<W> {
+    /// Compress into `out` at `level`, from 0 (none) to 9 (best).
+    pub fn new(out: W, level: u32) -> Self {
+        let level = flate2::Compression::new(level.min(9));
+        Self {
+            encoder: Some(flate2::write::GzEncoder::new(out, level)),
+            level,
+        }
+    }
+
+    /// End the stream and return the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encoder.take().expect("encoder present").finish()
+    }
+
+    fn encoder(&mut self) -> &mut flate2::write::GzEncoder<W> {
+        self.encoder.as_mut().expect("encoder present")
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<W: Write> DumpSink for GzipSink<W> {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        write_record(self.encoder(), snapshot)
+    }
+
+    /// Flush the compressor so everything written so far can be decompressed.
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder().flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let out = self.encoder.take().expect("encoder present").finish()?;
+        self.encoder = Some(flate2::write::GzEncoder::new(out, self.level));
+        Ok(())
+    }
+}
+
+/// Writes snapshots zstd compressed to `W`, in the format of [`RotatingFileSink`](crate::RotatingFileSink)
+///
+/// Rotating ends the current zstd frame and starts a new one; concatenated frames decompress as one
+/// stream with `zstdcat`. Call [`ZstdSink::finish`] to end the frame, dropping the sink without it
+/// leaves a truncated stream.
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+///
+/// use dumpsys_rs::{DumpSink, Dumpsys, ZstdSink};
+///
+/// # fn foo() -> Option<()> {
+/// let file = File::create("/data/local/tmp/meminfo.log.zst").unwrap();
+/// let mut sink = ZstdSink::new(file, 3).unwrap();
+/// let snapshot = Dumpsys::new("meminfo")?.dump_snapshot(&["-a"]).unwrap();
+/// sink.write_snapshot(&snapshot).unwrap();
+/// sink.finish().unwrap();
+/// # Some(())
+/// # }
+/// ```
+#[cfg(feature = "zstd")]
+pub struct ZstdSink<W: Write> {
+    encoder: Option<zstd::stream::write::Encoder<'static, W>>,
+    level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> ZstdSink<W> {
+    /// Compress into `out` at `level`, 0 picks zstd's default.
+    pub fn new(out: W, level: i32) -> io::Result<Self> {
+        Ok(Self {
+            encoder: Some(zstd::stream::write::Encoder::new(out, level)?),
+            level,
+        })
+    }
+
+    /// End the frame and return the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encoder.take().expect("encoder present").finish()
+    }
+
+    fn encoder(&mut self) -> &mut zstd::stream::write::Encoder<'static, W> {
+        self.encoder.as_mut().expect("encoder present")
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> DumpSink for ZstdSink<W> {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        write_record(self.encoder(), snapshot)
+    }
+
+    /// Flush the compressor so everything written so far can be decompressed.
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder().flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let out = self.encoder.take().expect("encoder present").finish()?;
+        self.encoder = Some(zstd::stream::write::Encoder::new(out, self.level)?);
+        Ok(())
+    }
+}
+
+fn write_record(out: &mut impl Write, snapshot: &Snapshot) -> io::Result<()> {
+    out.write_all(header(snapshot).as_bytes())?;
+    out.write_all(snapshot.output.as_bytes())?;
+    out.write_all(b"\n")
+}
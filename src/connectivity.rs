@@ -0,0 +1,370 @@
+//! Typed output of `dumpsys connectivity`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::connectivity::{Connectivity, Transport};
+//!
+//! let connectivity = Connectivity::parse(
+//!     "NetworkProviders for:
+//!   WifiNetworkProvider
+//!
+//! Active default network: 101
+//!
+//! Current Networks:
+//!   NetworkAgentInfo{network{100}  handle{429513165} ni{WIFI CONNECTED extra: } Score(60) created everValidated lp{{InterfaceName: wlan0 LinkAddresses: [ 192.168.1.23/24 ]}} nc{[ Transports: WIFI Capabilities: NOT_METERED&INTERNET&NOT_RESTRICTED&TRUSTED&NOT_VPN&VALIDATED&NOT_ROAMING&FOREGROUND LinkUpBandwidth>=1048576Kbps]}}
+//!     Requests: REQUEST:3 LISTEN:30 BACKGROUND_REQUEST:0 total:33
+//!   NetworkAgentInfo{network{101}  handle{433807438} ni{VPN CONNECTED extra: } Score(101) created lp{{InterfaceName: tun0 }} nc{[ Transports: WIFI|VPN Capabilities: NOT_METERED&INTERNET&NOT_RESTRICTED&TRUSTED&FOREGROUND]}}
+//!   NetworkAgentInfo{network{102}  handle{437} ni{MOBILE[LTE] CONNECTED extra: } Score(50) created lp{{InterfaceName: rmnet0 }} nc{[ Transports: CELLULAR Capabilities: INTERNET&NOT_RESTRICTED&TRUSTED&NOT_VPN]}}
+//!
+//! Network Requests:
+//!   uid/pid:1000/1234 activeRequest: null",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(connectivity.default_network, Some(101));
+//! assert_eq!(connectivity.networks.len(), 3);
+//! assert!(connectivity.has_vpn());
+//! assert_eq!(connectivity.active_transport(), Some(Transport::Wifi));
+//!
+//! let wifi = connectivity.network(100).unwrap();
+//! assert_eq!(wifi.interface.as_deref(), Some("wlan0"));
+//! assert!(wifi.is_validated());
+//! assert!(wifi.ever_validated);
+//! assert!(wifi.has_capability("NOT_METERED"));
+//!
+//! let vpn = connectivity.vpn().unwrap();
+//! assert_eq!(vpn.transports, [Transport::Wifi, Transport::Vpn]);
+//! assert!(!vpn.is_validated());
+//!
+//! let cellular = connectivity.network(102).unwrap();
+//! assert_eq!(cellular.transport(), Some(Transport::Cellular));
+//! assert!(!cellular.ever_validated);
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{first_word, invalid},
+    typed, DumpParse,
+};
+
+const DEFAULT_NETWORK: &str = "Active default network: ";
+const CURRENT_NETWORKS: &str = "Current Networks:";
+const NETWORK_AGENT: &str = "NetworkAgentInfo";
+const NETWORK_ID: &str = "network{";
+const INTERFACE: &str = "InterfaceName: ";
+const TRANSPORTS: &str = "Transports: ";
+const CAPABILITIES: &str = "Capabilities: ";
+const VALIDATED: &str = "VALIDATED";
+
+/// `NetworkCapabilities.TRANSPORT_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transport {
+    Cellular,
+    Wifi,
+    Bluetooth,
+    Ethernet,
+    Vpn,
+    WifiAware,
+    Lowpan,
+    Usb,
+    Thread,
+    Satellite,
+    Test,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for Transport {
+    fn from(name: &str) -> Self {
+        match name {
+            "CELLULAR" => Self::Cellular,
+            "WIFI" => Self::Wifi,
+            "BLUETOOTH" => Self::Bluetooth,
+            "ETHERNET" => Self::Ethernet,
+            "VPN" => Self::Vpn,
+            "WIFI_AWARE" => Self::WifiAware,
+            "LOWPAN" => Self::Lowpan,
+            "USB" => Self::Usb,
+            "THREAD" => Self::Thread,
+            "SATELLITE" => Self::Satellite,
+            "TEST" => Self::Test,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A network known to `ConnectivityService`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Network {
+    /// Network id, as in `Network.toString()`
+    pub id: u32,
+    pub interface: Option<String>,
+    /// A VPN lists the transports of its underlying networks next to `Vpn`
+    pub transports: Vec<Transport>,
+    /// As in `INTERNET` or `NOT_METERED`
+    pub capabilities: Vec<String>,
+    /// Whether the network passed validation at least once
+    pub ever_validated: bool,
+}
+
+impl Network {
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|name| name == capability)
+    }
+
+    /// Whether the last validation reached the internet.
+    pub fn is_validated(&self) -> bool {
+        self.has_capability(VALIDATED)
+    }
+
+    pub fn is_vpn(&self) -> bool {
+        self.transports.contains(&Transport::Vpn)
+    }
+
+    /// The physical transport, or `Vpn` when a VPN doesn't declare its underlying networks.
+    pub fn transport(&self) -> Option<Transport> {
+        self.transports
+            .iter()
+            .find(|transport| **transport != Transport::Vpn)
+            .or_else(|| self.transports.first())
+            .cloned()
+    }
+}
+
+/// Output of `dumpsys connectivity`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Connectivity {
+    /// Id of the network apps use by default, `None` when offline
+    pub default_network: Option<u32>,
+    pub networks: Vec<Network>,
+}
+
+impl Connectivity {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut connectivity = Self::default();
+        let mut in_networks = false;
+
+        for line in text.lines() {
+            if !line.starts_with(' ') {
+                in_networks = line == CURRENT_NETWORKS;
+            }
+            if let Some(id) = line.strip_prefix(DEFAULT_NETWORK) {
+                connectivity.default_network = id.trim().parse().ok();
+            } else if in_networks && line.trim_start().starts_with(NETWORK_AGENT) {
+                connectivity.networks.push(network(line.trim())?);
+            }
+        }
+
+        Ok(connectivity)
+    }
+
+    pub fn network(&self, id: u32) -> Option<&Network> {
+        self.networks.iter().find(|network| network.id == id)
+    }
+
+    /// The network apps use by default.
+    pub fn active(&self) -> Option<&Network> {
+        self.network(self.default_network?)
+    }
+
+    /// The physical transport of the default network.
+    pub fn active_transport(&self) -> Option<Transport> {
+        self.active()?.transport()
+    }
+
+    pub fn vpn(&self) -> Option<&Network> {
+        self.networks.iter().find(|network| network.is_vpn())
+    }
+
+    pub fn has_vpn(&self) -> bool {
+        self.vpn().is_some()
+    }
+}
+
+impl DumpParse for Connectivity {
+    const SERVICE: &'static str = "connectivity";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `connectivity` and return the physical transport of the default network, `None` when
+/// offline.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// use dumpsys_rs::connectivity::{self, Transport};
+///
+/// if connectivity::active_transport().unwrap() == Some(Transport::Cellular) {
+///     println!("on mobile data");
+/// }
+/// # }
+/// ```
+pub fn active_transport() -> Result<Option<Transport>, DumpError> {
+    typed::dump_service::<Connectivity>(Vec::<&str>::new())
+        .map(|connectivity| connectivity.active_transport())
+}
+
+/// `NetworkAgentInfo{network{100} ... everValidated lp{{InterfaceName: wlan0 ...}} nc{[ Transports:
+/// WIFI Capabilities: INTERNET&VALIDATED ...]}}`, with `everValidated{true}` before Android 12.
+fn network(line: &str) -> Result<Network, ParseError> {
+    let after = |key: &str| {
+        line.split_once(key)
+            .map(|(_, rest)| first_word(rest).trim_end_matches([']', '}']))
+    };
+    let id = after(NETWORK_ID)
+        .and_then(|id| id.split('}').next())
+        .ok_or_else(|| invalid(NETWORK_ID, line))?;
+    Ok(Network {
+        id: id.parse().map_err(|_| invalid(NETWORK_ID, id))?,
+        interface: after(INTERFACE).map(str::to_owned),
+        transports: after(TRANSPORTS)
+            .map(|names| names.split('|').map(Transport::from).collect())
+            .unwrap_or_default(),
+        capabilities: after(CAPABILITIES)
+            .map(|names| names.split('&').map(str::to_owned).collect())
+            .unwrap_or_default(),
+        ever_validated: line
+            .split_whitespace()
+            .any(|word| word == "everValidated" || word == "everValidated{true}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys connectivity` of Android 14, cut down to the Wi-Fi and mobile networks
+    const DUMP: &str = "NetworkProviders for:
+  WifiNetworkProvider
+  TelephonyNetworkProvider
+
+Active default network: 100
+
+Current state timestamp: 2024-03-12T09:12:44.123
+
+Current Networks:
+  NetworkAgentInfo{network{100}  handle{429513165} ni{WIFI CONNECTED extra: } Score(Policies : IS_VALIDATED&EVER_VALIDATED&EVER_EVALUATED&IS_UNMETERED KeepConnected : 0) created everValidated lastValidated lp{{InterfaceName: wlan0 LinkAddresses: [ 192.168.1.23/24 ] DnsAddresses: [ /192.168.1.1 ] Domains: lan MTU: 1500}} nc{[ Transports: WIFI Capabilities: NOT_METERED&INTERNET&NOT_RESTRICTED&TRUSTED&NOT_VPN&VALIDATED&NOT_ROAMING&FOREGROUND&NOT_CONGESTED&NOT_SUSPENDED LinkUpBandwidth>=1048576Kbps LinkDnBandwidth>=1048576Kbps SignalStrength: -55]}  factorySerialNumber=1}
+    Requests: REQUEST:3 LISTEN:30 BACKGROUND_REQUEST:0 total:33
+    Lingered:
+  NetworkAgentInfo{network{102}  handle{437} ni{MOBILE[LTE] CONNECTED extra: internet} Score(Policies : EVER_EVALUATED KeepConnected : 0) created lp{{InterfaceName: rmnet_data1 LinkAddresses: [ 10.0.0.7/30 ]}} nc{[ Transports: CELLULAR Capabilities: SUPL&INTERNET&NOT_RESTRICTED&TRUSTED&NOT_VPN&NOT_ROAMING&FOREGROUND]}  factorySerialNumber=3}
+    Requests: REQUEST:0 LISTEN:12 BACKGROUND_REQUEST:1 total:13
+
+Status for known Networks:
+  NetworkAgentInfo{network{99}  handle{1} ni{WIFI DISCONNECTED extra: }}
+
+Network Requests:
+  uid/pid:1000/1234 activeRequest: null
+";
+
+    #[test]
+    fn parses_the_networks() {
+        let connectivity = Connectivity::parse(DUMP).unwrap();
+        // Only current networks, not the known ones
+        assert_eq!(connectivity.networks.len(), 2);
+        let wifi = connectivity.active().unwrap();
+        assert_eq!(wifi.id, 100);
+        assert_eq!(wifi.interface.as_deref(), Some("wlan0"));
+        assert_eq!(wifi.transports, [Transport::Wifi]);
+        assert!(wifi.is_validated());
+        assert!(wifi.ever_validated);
+        assert!(wifi.has_capability("NOT_CONGESTED"));
+        assert_eq!(
+            connectivity.network(102).unwrap(),
+            &Network {
+                id: 102,
+                interface: Some("rmnet_data1".to_owned()),
+                transports: vec![Transport::Cellular],
+                capabilities: [
+                    "SUPL",
+                    "INTERNET",
+                    "NOT_RESTRICTED",
+                    "TRUSTED",
+                    "NOT_VPN",
+                    "NOT_ROAMING",
+                    "FOREGROUND",
+                ]
+                .map(str::to_owned)
+                .to_vec(),
+                ever_validated: false,
+            }
+        );
+        assert_eq!(connectivity.active_transport(), Some(Transport::Wifi));
+        assert!(!connectivity.has_vpn());
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            Connectivity::parse(&DUMP.replace('\n', "\r\n")),
+            Connectivity::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(Connectivity::parse("").unwrap(), Connectivity::default());
+    }
+
+    #[test]
+    fn offline() {
+        let text = "Active default network: none
+
+Current Networks:
+";
+        let connectivity = Connectivity::parse(text).unwrap();
+        assert_eq!(connectivity.default_network, None);
+        assert_eq!(connectivity.active(), None);
+        assert_eq!(connectivity.active_transport(), None);
+    }
+
+    #[test]
+    fn networks_of_older_releases() {
+        // Android 11 prints the validation as a field
+        let text = "Current Networks:
+  NetworkAgentInfo{ ni{[type: WIFI[], state: CONNECTED/CONNECTED]}  network{100}  nethandle{429513165} lp{{InterfaceName: wlan0 }}  nc{[ Transports: WIFI Capabilities: INTERNET&NOT_RESTRICTED&TRUSTED&NOT_VPN&VALIDATED]}  Score{60}  everValidated{true}  lastValidated{true}  created{true} lingering{false}}
+  NetworkAgentInfo{ ni{[type: MOBILE[LTE], state: CONNECTED/CONNECTED]}  network{101}  nethandle{433807438} lp{{InterfaceName: rmnet0 }}  nc{[ Transports: CELLULAR Capabilities: INTERNET]}  Score{50}  everValidated{false}  lastValidated{false}  created{true} lingering{false}}
+";
+        let connectivity = Connectivity::parse(text).unwrap();
+        assert!(connectivity.network(100).unwrap().ever_validated);
+        assert!(!connectivity.network(101).unwrap().ever_validated);
+    }
+
+    #[test]
+    fn unknown_and_missing_fields() {
+        let text = "Current Networks:
+  NetworkAgentInfo{network{103}  handle{1} ni{VPN CONNECTED extra: } nc{[ Transports: VPN|WIFI_NAN Capabilities: INTERNET]}}
+  NetworkAgentInfo{network{104}  handle{2} ni{ CONNECTING extra: }}
+";
+        let connectivity = Connectivity::parse(text).unwrap();
+        let vpn = connectivity.vpn().unwrap();
+        assert_eq!(
+            vpn.transports,
+            [Transport::Vpn, Transport::Other("WIFI_NAN".to_owned())]
+        );
+        assert_eq!(vpn.interface, None);
+        let connecting = connectivity.network(104).unwrap();
+        assert!(connecting.transports.is_empty());
+        assert_eq!(connecting.transport(), None);
+        assert!(!connecting.is_validated());
+    }
+
+    #[test]
+    fn invalid_networks() {
+        let networks = |line: &str| Connectivity::parse(&format!("{CURRENT_NETWORKS}\n  {line}\n"));
+        // Cut short before the network id
+        let line = "NetworkAgentInfo{ ni{WIFI CONNECTED";
+        assert_eq!(networks(line).unwrap_err(), invalid(NETWORK_ID, line));
+        assert_eq!(
+            networks("NetworkAgentInfo{network{wifi}  handle{1}}").unwrap_err(),
+            invalid(NETWORK_ID, "wifi")
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! Typed output of `dumpsys cpuinfo`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::cpuinfo::CpuInfo;
+//!
+//! let cpuinfo = CpuInfo::parse(
+//!     "Load: 12.5 / 11.8 / 10.2
+//! CPU usage from 34567ms to 4567ms ago (2023-01-01 00:00:00.000 to 2023-01-01 00:00:30.000):
+//!   10% 567/surfaceflinger: 6% user + 3.5% kernel / faults: 10 minor
+//!   25% 1234/system_server: 15% user + 10% kernel / faults: 1234 minor 2 major
+//!   0.5% 89/kworker/u16:2: 0% user + 0.5% kernel
+//! 23% TOTAL: 12% user + 9% kernel + 0.8% iowait + 0.6% irq + 0.4% softirq",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(cpuinfo.load.unwrap().one, 12.5);
+//! assert_eq!(cpuinfo.window, Some(Duration::from_secs(30)));
+//! assert_eq!(cpuinfo.processes[0].name, "system_server");
+//! assert_eq!(cpuinfo.processes[0].major_faults, Some(2));
+//! assert_eq!(cpuinfo.processes[2].name, "kworker/u16:2");
+//! assert_eq!(cpuinfo.total.unwrap().iowait, 0.8);
+//! ```
+
+use std::time::Duration;
+
+use crate::{error::ParseError, parse::kv::invalid, DumpParse};
+
+const LOAD: &str = "Load: ";
+const USAGE: &str = "CPU usage from ";
+const TOTAL: &str = "TOTAL";
+
+/// Load averages over 1, 5 and 15 minutes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+/// CPU usage of one process in percent of one core
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessCpu {
+    pub pid: u32,
+    pub name: String,
+    pub total: f64,
+    pub user: f64,
+    pub kernel: f64,
+    pub minor_faults: Option<u64>,
+    pub major_faults: Option<u64>,
+}
+
+/// CPU usage of the whole device in percent of all cores
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuTotal {
+    pub total: f64,
+    pub user: f64,
+    pub kernel: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+}
+
+/// Output of `dumpsys cpuinfo`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuInfo {
+    pub load: Option<LoadAverage>,
+    /// Length of the sampling window the usage was measured over
+    pub window: Option<Duration>,
+    /// Processes by total usage, busiest first
+    pub processes: Vec<ProcessCpu>,
+    pub total: Option<CpuTotal>,
+}
+
+impl CpuInfo {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut cpuinfo = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(load) = line.strip_prefix(LOAD) {
+                let mut averages = load.split('/').map(|average| average.trim().parse());
+                let (Some(Ok(one)), Some(Ok(five)), Some(Ok(fifteen))) =
+                    (averages.next(), averages.next(), averages.next())
+                else {
+                    return Err(invalid(LOAD.trim(), load));
+                };
+                cpuinfo.load = Some(LoadAverage { one, five, fifteen });
+                continue;
+            }
+            if let Some(usage) = line.strip_prefix(USAGE) {
+                cpuinfo.window = window(usage);
+                continue;
+            }
+
+            // 25% 1234/system_server: 15% user + 10% kernel / faults: 1234 minor 2 major
+            let Some((total, rest)) = line.split_once("% ") else {
+                continue;
+            };
+            let Ok(total) = total.trim_start_matches(['+', '-']).parse::<f64>() else {
+                continue;
+            };
+            let Some((name, breakdown)) = rest.split_once(": ") else {
+                continue;
+            };
+            let (usage, faults) = breakdown.split_once(" / ").unwrap_or((breakdown, ""));
+
+            if name == TOTAL {
+                let mut cpu = CpuTotal {
+                    total,
+                    ..CpuTotal::default()
+                };
+                for (percent, kind) in percents(usage, line)? {
+                    match kind {
+                        "user" => cpu.user = percent,
+                        "kernel" => cpu.kernel = percent,
+                        "iowait" => cpu.iowait = percent,
+                        "irq" => cpu.irq = percent,
+                        "softirq" => cpu.softirq = percent,
+                        _ => {}
+                    }
+                }
+                cpuinfo.total = Some(cpu);
+                continue;
+            }
+
+            let (pid, name) = name.split_once('/').ok_or_else(|| invalid("pid", line))?;
+            let mut process = ProcessCpu {
+                pid: pid.parse().map_err(|_| invalid("pid", line))?,
+                name: name.to_owned(),
+                total,
+                ..ProcessCpu::default()
+            };
+            for (percent, kind) in percents(usage, line)? {
+                match kind {
+                    "user" => process.user = percent,
+                    "kernel" => process.kernel = percent,
+                    _ => {}
+                }
+            }
+            // faults: 1234 minor 2 major
+            let words: Vec<&str> = faults.split_whitespace().collect();
+            for pair in words.windows(2) {
+                match pair[1] {
+                    "minor" => process.minor_faults = pair[0].parse().ok(),
+                    "major" => process.major_faults = pair[0].parse().ok(),
+                    _ => {}
+                }
+            }
+            cpuinfo.processes.push(process);
+        }
+
+        cpuinfo
+            .processes
+            .sort_by(|a, b| b.total.total_cmp(&a.total));
+        Ok(cpuinfo)
+    }
+
+    /// The process called `name`.
+    pub fn process(&self, name: &str) -> Option<&ProcessCpu> {
+        self.processes.iter().find(|process| process.name == name)
+    }
+}
+
+impl DumpParse for CpuInfo {
+    const SERVICE: &'static str = "cpuinfo";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// `15% user + 10% kernel` as `(15.0, "user"), (10.0, "kernel")`.
+fn percents<'a>(usage: &'a str, line: &str) -> Result<Vec<(f64, &'a str)>, ParseError> {
+    usage
+        .split(" + ")
+        .map(|part| {
+            let (percent, kind) = part
+                .trim()
+                .split_once("% ")
+                .ok_or_else(|| invalid("usage", line))?;
+            let percent = percent.parse().map_err(|_| invalid("usage", line))?;
+            Ok((percent, kind.trim()))
+        })
+        .collect()
+}
+
+/// Length of `34567ms to 4567ms ago (...)` or `1234ms to 5678ms later (...)`.
+fn window(usage: &str) -> Option<Duration> {
+    let mut times = usage
+        .split_whitespace()
+        .filter_map(|word| word.strip_suffix("ms")?.parse::<u64>().ok());
+    let (from, to) = (times.next()?, times.next()?);
+    Some(Duration::from_millis(from.abs_diff(to)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys cpuinfo` of Android 14, cut down to a few processes
+    const DUMP: &str = "Load: 5.62 / 5.88 / 6.1
+CPU usage from 94387ms to 34366ms ago (2024-03-11 10:31:56.855 to 2024-03-11 10:32:56.876):
+  3.1% 777/surfaceflinger: 1.8% user + 1.2% kernel / faults: 312 minor
+  8.7% 1520/system_server: 5.3% user + 3.3% kernel / faults: 10458 minor 9 major
+  2.4% 21865/com.google.android.gms.persistent: 1.6% user + 0.7% kernel / faults: 2045 minor
+  0% 13/rcu_preempt: 0% user + 0% kernel
+  +0% 24588/kworker/u16:7: 0% user + 0% kernel
+13% TOTAL: 7.4% user + 4.8% kernel + 0.2% iowait + 0.5% irq + 0.1% softirq
+";
+
+    #[test]
+    fn parses_the_dump() {
+        let cpuinfo = CpuInfo::parse(DUMP).unwrap();
+        assert_eq!(
+            cpuinfo.load,
+            Some(LoadAverage {
+                one: 5.62,
+                five: 5.88,
+                fifteen: 6.1,
+            })
+        );
+        assert_eq!(cpuinfo.window, Some(Duration::from_millis(60_021)));
+        assert_eq!(cpuinfo.processes.len(), 5);
+        assert_eq!(
+            cpuinfo.processes[0],
+            ProcessCpu {
+                pid: 1520,
+                name: "system_server".to_owned(),
+                total: 8.7,
+                user: 5.3,
+                kernel: 3.3,
+                minor_faults: Some(10458),
+                major_faults: Some(9),
+            }
+        );
+        let surfaceflinger = cpuinfo.process("surfaceflinger").unwrap();
+        assert_eq!(surfaceflinger.minor_faults, Some(312));
+        assert_eq!(surfaceflinger.major_faults, None);
+        assert_eq!(cpuinfo.process("kworker/u16:7").unwrap().pid, 24588);
+        assert_eq!(
+            cpuinfo.total,
+            Some(CpuTotal {
+                total: 13.0,
+                user: 7.4,
+                kernel: 4.8,
+                iowait: 0.2,
+                irq: 0.5,
+                softirq: 0.1,
+            })
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            CpuInfo::parse(&crlf).unwrap(),
+            CpuInfo::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(CpuInfo::parse("").unwrap(), CpuInfo::default());
+    }
+
+    #[test]
+    fn missing_load_and_total() {
+        let text = "CPU usage from 1234ms to 5678ms later (2024-03-11 10:31:56.855 to 2024-03-11 10:32:01.299):
+  25% 1234/system_server: 15% user + 10% kernel
+";
+        let cpuinfo = CpuInfo::parse(text).unwrap();
+        assert_eq!(cpuinfo.load, None);
+        assert_eq!(cpuinfo.total, None);
+        assert_eq!(cpuinfo.window, Some(Duration::from_millis(4444)));
+        assert_eq!(cpuinfo.processes[0].minor_faults, None);
+    }
+
+    #[test]
+    fn unknown_usage_kinds_are_skipped() {
+        let text = "  4% 567/surfaceflinger: 2% user + 1% kernel + 1% nice
+9% TOTAL: 5% user + 3% kernel + 1% steal";
+        let cpuinfo = CpuInfo::parse(text).unwrap();
+        assert_eq!(cpuinfo.processes[0].user, 2.0);
+        assert_eq!(cpuinfo.processes[0].kernel, 1.0);
+        assert_eq!(cpuinfo.total.unwrap().kernel, 3.0);
+        assert_eq!(cpuinfo.total.unwrap().iowait, 0.0);
+    }
+
+    #[test]
+    fn truncated_usage_is_invalid() {
+        let line = "  8.7% 1520/system_server: 5.3% user + 3.3";
+        assert_eq!(
+            CpuInfo::parse(line).unwrap_err(),
+            invalid("usage", line.trim())
+        );
+    }
+
+    #[test]
+    fn invalid_load_and_pid() {
+        assert_eq!(
+            CpuInfo::parse("Load: 5.62 / 5.88").unwrap_err(),
+            invalid("Load:", "5.62 / 5.88")
+        );
+        let line = "25% system_server: 15% user + 10% kernel";
+        assert_eq!(CpuInfo::parse(line).unwrap_err(), invalid("pid", line));
+    }
+}
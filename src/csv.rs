@@ -0,0 +1,123 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    time::UNIX_EPOCH,
+};
+
+use crate::{DumpSink, Snapshot};
+
+type Metric = Box<dyn Fn(&str) -> Option<f64> + Send>;
+
+/// Writes numeric metrics extracted from each snapshot as CSV rows, e.g. for spreadsheets or pandas
+///
+/// Each row holds the capture time in milliseconds since the epoch, the service and a column per
+/// metric, left empty where the metric wasn't found. The header row is written before the first one.
+///
+/// # Example
+///
+/// ```
+/// use std::{fs::File, time::Duration};
+///
+/// use dumpsys_rs::{CsvSink, Dumpsys, Sampler};
+///
+/// /// A `  level: 85` line of `dumpsys battery`.
+/// fn field(output: &str, name: &str) -> Option<f64> {
+///     output.lines().find_map(|line| {
+///         let (key, value) = line.trim().split_once(": ")?;
+///         (key == name).then(|| value.parse().ok())?
+///     })
+/// }
+///
+/// # fn foo() -> Option<()> {
+/// let sink = CsvSink::new(File::create("/data/local/tmp/battery.csv").unwrap())
+///     .metric("level", |output| field(output, "level"))
+///     .metric("temperature", |output| Some(field(output, "temperature")? / 10.0));
+/// let battery = Dumpsys::new("battery")?;
+/// let sampler = Sampler::with_sink(battery, Vec::<&str>::new(), Duration::from_secs(60), sink);
+/// # Some(())
+/// # }
+/// ```
+pub struct CsvSink<W: Write> {
+    out: W,
+    metrics: Vec<(String, Metric)>,
+    header_written: bool,
+}
+
+impl<W: Write> fmt::Debug for CsvSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metrics: Vec<_> = self.metrics.iter().map(|(name, _)| name).collect();
+        f.debug_struct("CsvSink")
+            .field("metrics", &metrics)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Write rows to `out`, with no metric columns yet.
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            metrics: Vec::new(),
+            header_written: false,
+        }
+    }
+
+    /// Add a column `name`, filled by `extract` from the output of each snapshot.
+    pub fn metric<F>(mut self, name: impl AsRef<str>, extract: F) -> Self
+    where
+        F: Fn(&str) -> Option<f64> + Send + 'static,
+    {
+        self.metrics
+            .push((name.as_ref().to_owned(), Box::new(extract)));
+        self
+    }
+
+    /// The writer, e.g. to read back an in-memory buffer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> DumpSink for CsvSink<W> {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        if !self.header_written {
+            let mut header = String::from("timestamp_ms,service");
+            for (name, _) in &self.metrics {
+                header.push(',');
+                push_field(&mut header, name);
+            }
+            writeln!(self.out, "{header}")?;
+            self.header_written = true;
+        }
+
+        let timestamp = snapshot
+            .captured_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut row = format!("{timestamp},");
+        push_field(&mut row, &snapshot.service);
+        for (_, extract) in &self.metrics {
+            row.push(',');
+            if let Some(value) = extract(&snapshot.output).filter(|value| value.is_finite()) {
+                row.push_str(&value.to_string());
+            }
+        }
+        writeln!(self.out, "{row}")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Append `field`, quoted if it contains a separator, quote or line break.
+fn push_field(row: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        row.push('"');
+        row.push_str(&field.replace('"', "\"\""));
+        row.push('"');
+    } else {
+        row.push_str(field);
+    }
+}
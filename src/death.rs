@@ -0,0 +1,50 @@
+use binder::{DeathRecipient, IBinder, ProcessState, SpIBinder};
+
+use crate::{error::DumpError, Dumpsys};
+
+/// A death callback registered with [`Dumpsys::on_death`], unlinked again on drop
+pub struct DeathWatch {
+    service: SpIBinder,
+    recipient: DeathRecipient,
+}
+
+impl Drop for DeathWatch {
+    fn drop(&mut self) {
+        let _ = self.service.unlink_to_death(&mut self.recipient);
+    }
+}
+
+impl Dumpsys {
+    /// Call `callback` as soon as the process hosting the service dies, instead of finding out on the next dump.
+    ///
+    /// The callback runs on a binder thread, which is started for the process if needed. It fires at most
+    /// once per handle, use [`Dumpsys::refresh`] and register again to keep watching a restarted service.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let surfaceflinger = Dumpsys::new("SurfaceFlinger")?;
+    /// let watch = surfaceflinger
+    ///     .on_death(|| eprintln!("SurfaceFlinger died"))
+    ///     .unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn on_death<F>(&self, callback: F) -> Result<DeathWatch, DumpError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let mut service = self.service();
+        let mut recipient = DeathRecipient::new(callback);
+
+        ProcessState::start_thread_pool();
+        service
+            .link_to_death(&mut recipient)
+            .map_err(|status| self.error(status))?;
+
+        Ok(DeathWatch { service, recipient })
+    }
+}
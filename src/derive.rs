@@ -0,0 +1,74 @@
+//! Runtime support of `#[derive(DumpParse)]`, not a public API
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{
+    error::ParseError,
+    parse::{kv::KeyValues, FromDumpValue},
+};
+
+/// A field pattern compiled on first use
+pub struct LazyRegex {
+    pattern: &'static str,
+    regex: OnceLock<Regex>,
+}
+
+impl LazyRegex {
+    pub const fn new(pattern: &'static str) -> Self {
+        Self {
+            pattern,
+            regex: OnceLock::new(),
+        }
+    }
+
+    fn get(&self) -> &Regex {
+        self.regex.get_or_init(|| {
+            Regex::new(self.pattern)
+                .unwrap_or_else(|err| panic!("invalid #[dump(regex)] `{}`: {err}", self.pattern))
+        })
+    }
+}
+
+pub fn key<T: FromDumpValue>(fields: &KeyValues<'_>, key: &str) -> Result<T, ParseError> {
+    key_opt(fields, key)?.ok_or_else(|| ParseError::Missing(key.to_owned()))
+}
+
+pub fn key_opt<T: FromDumpValue>(
+    fields: &KeyValues<'_>,
+    key: &str,
+) -> Result<Option<T>, ParseError> {
+    fields.get(key).map(|value| convert(key, value)).transpose()
+}
+
+/// The first capture group of `regex` in `text`, or the whole match without groups.
+pub fn regex<T: FromDumpValue>(
+    text: &str,
+    regex: &LazyRegex,
+    field: &str,
+) -> Result<T, ParseError> {
+    regex_opt(text, regex, field)?.ok_or_else(|| ParseError::Missing(field.to_owned()))
+}
+
+pub fn regex_opt<T: FromDumpValue>(
+    text: &str,
+    regex: &LazyRegex,
+    field: &str,
+) -> Result<Option<T>, ParseError> {
+    let Some(captures) = regex.get().captures(text) else {
+        return Ok(None);
+    };
+    let value = captures
+        .get(1)
+        .or_else(|| captures.get(0))
+        .map_or("", |value| value.as_str());
+    convert(field, value).map(Some)
+}
+
+fn convert<T: FromDumpValue>(key: &str, value: &str) -> Result<T, ParseError> {
+    T::from_dump_value(value).ok_or_else(|| ParseError::Invalid {
+        key: key.to_owned(),
+        value: value.to_owned(),
+    })
+}
@@ -0,0 +1,515 @@
+//! Typed output of `dumpsys deviceidle`, the Doze state of the device
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::deviceidle::{DeepState, DeviceIdle, IdleEventKind, LightState};
+//!
+//! let idle = DeviceIdle::parse(
+//!     "  Settings:
+//!     light_after_inactive_to=+3m0s
+//!   Whitelist (except idle) system apps:
+//!     com.android.providers.downloads
+//!   Whitelist system apps:
+//!     com.android.phone
+//!   Whitelist user apps:
+//!     com.example.app
+//!   Whitelist all app ids:
+//!     1001
+//!   mLightEnabled=true  mDeepEnabled=true
+//!   mForceIdle=false
+//!   mScreenOn=false
+//!   mCharging=false
+//!   mMotionActive=false
+//!   mState=IDLE mLightState=OVERRIDE
+//!   Idling history:
+//!          normal: -1h2m3s (unplugged)
+//!      light-idle: -1h (light-idle)
+//!       deep-idle: -30m0s",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(idle.state.deep, DeepState::Idle);
+//! assert_eq!(idle.state.light, LightState::Override);
+//! assert!(idle.state.is_dozing());
+//! assert_eq!(idle.screen_on, Some(false));
+//! assert!(idle.is_whitelisted("com.example.app"));
+//! assert_eq!(idle.whitelist.except_idle, ["com.android.providers.downloads"]);
+//! assert_eq!(idle.history.len(), 3);
+//! assert_eq!(idle.history[0].kind, IdleEventKind::Normal);
+//! assert_eq!(idle.history[0].ago, Duration::from_secs(3723));
+//! assert_eq!(idle.history[0].reason.as_deref(), Some("unplugged"));
+//! assert_eq!(idle.history[2].kind, IdleEventKind::DeepIdle);
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_duration, KeyValues},
+    typed, DumpParse,
+};
+
+const SERVICE: &str = "deviceidle";
+const EXCEPT_IDLE: &str = "Whitelist (except idle) system apps:";
+const SYSTEM: &str = "Whitelist system apps:";
+const USER: &str = "Whitelist user apps:";
+const HISTORY: &str = "Idling history:";
+
+/// `DeviceIdleController.STATE_*`, the deep Doze state entered while the device is still
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeepState {
+    #[default]
+    Active,
+    Inactive,
+    IdlePending,
+    Sensing,
+    Locating,
+    Idle,
+    IdleMaintenance,
+    QuickDozeDelay,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for DeepState {
+    fn from(name: &str) -> Self {
+        match name {
+            "ACTIVE" => Self::Active,
+            "INACTIVE" => Self::Inactive,
+            "IDLE_PENDING" => Self::IdlePending,
+            "SENSING" => Self::Sensing,
+            "LOCATING" => Self::Locating,
+            "IDLE" => Self::Idle,
+            "IDLE_MAINTENANCE" => Self::IdleMaintenance,
+            "QUICK_DOZE_DELAY" => Self::QuickDozeDelay,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// `DeviceIdleController.LIGHT_STATE_*`, the light Doze state entered while the screen is off
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LightState {
+    #[default]
+    Active,
+    Inactive,
+    PreIdle,
+    Idle,
+    WaitingForNetwork,
+    IdleMaintenance,
+    /// Deep Doze took over
+    Override,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for LightState {
+    fn from(name: &str) -> Self {
+        match name {
+            "ACTIVE" => Self::Active,
+            "INACTIVE" => Self::Inactive,
+            "PRE_IDLE" => Self::PreIdle,
+            "IDLE" => Self::Idle,
+            "WAITING_FOR_NETWORK" => Self::WaitingForNetwork,
+            "IDLE_MAINTENANCE" => Self::IdleMaintenance,
+            "OVERRIDE" => Self::Override,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// Deep and light Doze states
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DozeState {
+    pub deep: DeepState,
+    pub light: LightState,
+}
+
+impl DozeState {
+    /// Whether either Doze mode restricts apps, outside of maintenance windows.
+    pub fn is_dozing(&self) -> bool {
+        self.deep == DeepState::Idle
+            || matches!(self.light, LightState::Idle | LightState::WaitingForNetwork)
+    }
+}
+
+/// Packages exempt from Doze and App Standby
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Whitelist {
+    /// System apps exempt from App Standby but not from Doze
+    pub except_idle: Vec<String>,
+    pub system: Vec<String>,
+    /// Apps the user exempted from battery optimizations
+    pub user: Vec<String>,
+}
+
+/// What an [`IdleEvent`] entered
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IdleEventKind {
+    /// Doze left
+    Normal,
+    LightIdle,
+    LightMaintenance,
+    DeepIdle,
+    DeepMaintenance,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for IdleEventKind {
+    fn from(label: &str) -> Self {
+        match label {
+            "normal" => Self::Normal,
+            "light-idle" => Self::LightIdle,
+            "light-maint" => Self::LightMaintenance,
+            "deep-idle" => Self::DeepIdle,
+            "deep-maint" => Self::DeepMaintenance,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A step in the Doze history
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdleEvent {
+    pub kind: IdleEventKind,
+    /// Time since the step
+    pub ago: Duration,
+    /// Why the step was taken, as in `unplugged` or `screen`
+    pub reason: Option<String>,
+}
+
+/// Output of `dumpsys deviceidle`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceIdle {
+    pub state: DozeState,
+    pub light_enabled: Option<bool>,
+    pub deep_enabled: Option<bool>,
+    /// Forced with `dumpsys deviceidle force-idle`
+    pub force_idle: Option<bool>,
+    pub screen_on: Option<bool>,
+    pub charging: Option<bool>,
+    pub motion_active: Option<bool>,
+    pub whitelist: Whitelist,
+    /// Oldest first
+    pub history: Vec<IdleEvent>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    ExceptIdle,
+    System,
+    User,
+    History,
+}
+
+impl DeviceIdle {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut idle = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let header = match trimmed {
+                EXCEPT_IDLE => Some(Section::ExceptIdle),
+                SYSTEM => Some(Section::System),
+                USER => Some(Section::User),
+                HISTORY => Some(Section::History),
+                _ if trimmed.ends_with(':') => Some(Section::Other),
+                _ => None,
+            };
+            if let Some(header) = header {
+                section = header;
+                continue;
+            }
+
+            let list = match section {
+                Section::ExceptIdle => Some(&mut idle.whitelist.except_idle),
+                Section::System => Some(&mut idle.whitelist.system),
+                Section::User => Some(&mut idle.whitelist.user),
+                Section::History | Section::Other => None,
+            };
+            if let Some(list) = list {
+                if !trimmed.contains('=') {
+                    list.push(trimmed.to_owned());
+                    continue;
+                }
+                section = Section::Other;
+            }
+            if section == Section::History {
+                if let Some(event) = idle_event(trimmed) {
+                    idle.history.push(event?);
+                    continue;
+                }
+                section = Section::Other;
+            }
+
+            for (key, value) in KeyValues::parse(trimmed).iter() {
+                let value = value.trim();
+                let flag = || Some(value == "true");
+                match key {
+                    "mState" => idle.state.deep = value.into(),
+                    "mLightState" => idle.state.light = value.into(),
+                    "mLightEnabled" => idle.light_enabled = flag(),
+                    "mDeepEnabled" => idle.deep_enabled = flag(),
+                    "mForceIdle" => idle.force_idle = flag(),
+                    "mScreenOn" => idle.screen_on = flag(),
+                    "mCharging" => idle.charging = flag(),
+                    "mMotionActive" => idle.motion_active = flag(),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(idle)
+    }
+
+    /// Whether `package` is exempt from Doze, by the system or the user.
+    pub fn is_whitelisted(&self, package: &str) -> bool {
+        let Whitelist { system, user, .. } = &self.whitelist;
+        system
+            .iter()
+            .chain(user)
+            .any(|whitelisted| whitelisted == package)
+    }
+}
+
+impl DumpParse for DeviceIdle {
+    const SERVICE: &'static str = SERVICE;
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// The current Doze state, read with `dumpsys deviceidle get deep` and `get light` rather than the
+/// full dump.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// if dumpsys_rs::deviceidle::doze_state().unwrap().is_dozing() {
+///     println!("dozing");
+/// }
+/// # }
+/// ```
+pub fn doze_state() -> Result<DozeState, DumpError> {
+    let deviceidle = typed::connect(SERVICE, &["get".to_owned(), "deep".to_owned()])?;
+    let deep = deviceidle.dump(["get", "deep"])?;
+    let light = deviceidle.dump(["get", "light"])?;
+    Ok(DozeState {
+        deep: deep.trim().into(),
+        light: light.trim().into(),
+    })
+}
+
+/// `normal: -1h2m3s (unplugged)`
+fn idle_event(line: &str) -> Option<Result<IdleEvent, ParseError>> {
+    let (label, rest) = line.split_once(": ")?;
+    let ago = rest.strip_prefix('-')?;
+    let (ago, reason) = match ago.split_once(" (") {
+        Some((ago, reason)) => (ago, Some(reason.trim_end_matches(')').to_owned())),
+        None => (ago, None),
+    };
+    Some(
+        parse_duration(ago)
+            .map(|ago| IdleEvent {
+                kind: label.into(),
+                ago,
+                reason,
+            })
+            .ok_or_else(|| invalid(label, rest)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys deviceidle` of Android 14, cut down to a few settings and apps
+    const DUMP: &str = "  Settings:
+    flex_time_short=+1m0s0ms
+    light_after_inactive_to=+4m0s0ms
+    light_idle_to=+5m0s0ms
+    inactive_to=+30m0s0ms
+  Idling history:
+         normal: -5h2m3s4ms (screen)
+     light-idle: -4h57m1s (unknown)
+    light-maint: -4h52m1s (unknown)
+      deep-idle: -4h31m50s12ms (unknown)
+     deep-maint: -3h31m50s (unknown)
+      deep-idle: -3h30m40s (unknown)
+         normal: -12m (motion)
+  Whitelist (except idle) system apps:
+    com.android.vending
+    com.google.android.gms
+  Whitelist system apps:
+    com.android.phone
+    com.google.android.gms
+  Whitelist user apps:
+    com.example.app
+  Whitelist (except idle) all app ids:
+    1001
+    10148
+  Whitelist all app ids:
+    1001
+    10148
+    10231
+  mLightEnabled=true  mDeepEnabled=true
+  mQuickDozeActivated=false
+  mForceIdle=false
+  mMotionSensor={Significant motion Non-wakeup, vendor=Google, version=1, type=17, maxRange=1.0, resolution=1.0, power=0.0, minDelay=-1}
+  mScreenOn=false
+  mScreenLocked=true
+  mNetworkConnected=true
+  mCharging=false
+  mMotionActive=false
+  mNotMoving=true
+  mHasGps=true mHasNetwork=true mLocated=false
+  mState=IDLE_MAINTENANCE mLightState=OVERRIDE
+  mInactiveTimeout=+30m0s0ms
+  mNextAlarmTime=+58m12s
+";
+
+    #[test]
+    fn parses_the_state() {
+        let idle = DeviceIdle::parse(DUMP).unwrap();
+        assert_eq!(
+            idle.state,
+            DozeState {
+                deep: DeepState::IdleMaintenance,
+                light: LightState::Override,
+            }
+        );
+        // Maintenance windows let apps run
+        assert!(!idle.state.is_dozing());
+        assert_eq!(idle.light_enabled, Some(true));
+        assert_eq!(idle.deep_enabled, Some(true));
+        assert_eq!(idle.force_idle, Some(false));
+        assert_eq!(idle.screen_on, Some(false));
+        assert_eq!(idle.charging, Some(false));
+        assert_eq!(idle.motion_active, Some(false));
+    }
+
+    #[test]
+    fn parses_the_whitelists() {
+        let idle = DeviceIdle::parse(DUMP).unwrap();
+        assert_eq!(
+            idle.whitelist,
+            Whitelist {
+                except_idle: vec![
+                    "com.android.vending".to_owned(),
+                    "com.google.android.gms".to_owned()
+                ],
+                system: vec![
+                    "com.android.phone".to_owned(),
+                    "com.google.android.gms".to_owned()
+                ],
+                user: vec!["com.example.app".to_owned()],
+            }
+        );
+        assert!(idle.is_whitelisted("com.example.app"));
+        assert!(idle.is_whitelisted("com.google.android.gms"));
+        // Only exempt from App Standby
+        assert!(!idle.is_whitelisted("com.android.vending"));
+    }
+
+    #[test]
+    fn parses_the_history() {
+        let idle = DeviceIdle::parse(DUMP).unwrap();
+        let kinds: Vec<_> = idle.history.iter().map(|event| &event.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &IdleEventKind::Normal,
+                &IdleEventKind::LightIdle,
+                &IdleEventKind::LightMaintenance,
+                &IdleEventKind::DeepIdle,
+                &IdleEventKind::DeepMaintenance,
+                &IdleEventKind::DeepIdle,
+                &IdleEventKind::Normal,
+            ]
+        );
+        assert_eq!(
+            idle.history[3],
+            IdleEvent {
+                kind: IdleEventKind::DeepIdle,
+                ago: Duration::from_millis(16_310_012),
+                reason: Some("unknown".to_owned()),
+            }
+        );
+        assert_eq!(idle.history[6].ago, Duration::from_secs(720));
+        assert_eq!(idle.history[6].reason.as_deref(), Some("motion"));
+    }
+
+    #[test]
+    fn sections_end_at_the_next_field() {
+        let text = "  Whitelist user apps:
+    com.example.app
+  mScreenOn=true
+  Idling history:
+      deep-idle: -3m
+  mState=ACTIVE mLightState=ACTIVE
+";
+        let idle = DeviceIdle::parse(text).unwrap();
+        assert_eq!(idle.whitelist.user, ["com.example.app"]);
+        assert_eq!(idle.screen_on, Some(true));
+        assert_eq!(idle.history.len(), 1);
+        assert_eq!(idle.history[0].reason, None);
+        assert_eq!(idle.state, DozeState::default());
+        assert_eq!(idle.charging, None);
+    }
+
+    #[test]
+    fn unknown_values_are_kept() {
+        let text = "  Idling history:
+   deep-pending: -3m (unknown)
+  mState=HIBERNATING mLightState=NAPPING
+";
+        let idle = DeviceIdle::parse(text).unwrap();
+        assert_eq!(
+            idle.history[0].kind,
+            IdleEventKind::Other("deep-pending".to_owned())
+        );
+        assert_eq!(idle.state.deep, DeepState::Other("HIBERNATING".to_owned()));
+        assert_eq!(idle.state.light, LightState::Other("NAPPING".to_owned()));
+    }
+
+    #[test]
+    fn truncated_history_is_invalid() {
+        let text = "  Idling history:\n         normal: -5h2 (screen)\n";
+        assert_eq!(
+            DeviceIdle::parse(text).unwrap_err(),
+            invalid("normal", "-5h2 (screen)")
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            DeviceIdle::parse(&crlf).unwrap(),
+            DeviceIdle::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(DeviceIdle::parse("").unwrap(), DeviceIdle::default());
+    }
+}
@@ -0,0 +1,264 @@
+//! Line-oriented diffs between two dumps, e.g. before and after a change on the device
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::diff::{self, LineChange};
+//!
+//! let before = "layers: 3\nfps: 60\n";
+//! let after = "layers: 4\nfps: 60\nhdr: on\n";
+//! let diff = diff::diff(before, after);
+//! assert_eq!(diff.changes.len(), 2);
+//! assert!(matches!(diff.changes[0], LineChange::Changed { old: "layers: 3", new: "layers: 4", .. }));
+//! ```
+
+/// A difference between the old and the new dump, with 1-based line numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LineChange<'a> {
+    /// A line only in the new dump
+    Added { new_line: usize, text: &'a str },
+    /// A line only in the old dump
+    Removed { old_line: usize, text: &'a str },
+    /// A line replaced in place
+    Changed {
+        old_line: usize,
+        new_line: usize,
+        old: &'a str,
+        new: &'a str,
+    },
+}
+
+/// Every difference between two dumps in order, returned by [`diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diff<'a> {
+    pub changes: Vec<LineChange<'a>>,
+}
+
+impl Diff<'_> {
+    /// Whether both dumps have the same lines.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Lines added, counting changed lines too.
+    pub fn added(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| !matches!(change, LineChange::Removed { .. }))
+            .count()
+    }
+
+    /// Lines removed, counting changed lines too.
+    pub fn removed(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| !matches!(change, LineChange::Added { .. }))
+            .count()
+    }
+}
+
+/// The differences of one section, returned by [`diff_sections`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SectionDiff<'a> {
+    /// The header line of the section, empty for lines before the first header
+    pub section: &'a str,
+    pub diff: Diff<'a>,
+}
+
+/// Diff `old` against `new` line by line, pairing up replaced lines as [`LineChange::Changed`].
+pub fn diff<'a>(old: &'a str, new: &'a str) -> Diff<'a> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    diff_lines(&old, 0, &new, 0)
+}
+
+/// Diff `old` against `new` section by section, where `is_header` tells which lines start a section.
+///
+/// Sections are matched up by their header line, so reordered sections don't show up as changes.
+/// Sections in only one of the dumps are entirely added or removed. Sections without changes are left out.
+pub fn diff_sections<'a>(
+    old: &'a str,
+    new: &'a str,
+    is_header: impl Fn(&str) -> bool,
+) -> Vec<SectionDiff<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let old_sections = sections(&old, &is_header);
+    let new_sections = sections(&new, &is_header);
+
+    let mut diffs = Vec::new();
+    for &(section, new_start, new_end) in &new_sections {
+        let (old_start, old_end) = old_sections
+            .iter()
+            .find(|(old_section, ..)| *old_section == section)
+            .map_or((0, 0), |&(_, start, end)| (start, end));
+        let diff = diff_lines(
+            &old[old_start..old_end],
+            old_start,
+            &new[new_start..new_end],
+            new_start,
+        );
+        diffs.push(SectionDiff { section, diff });
+    }
+    for &(section, old_start, old_end) in &old_sections {
+        if !new_sections
+            .iter()
+            .any(|(new_section, ..)| *new_section == section)
+        {
+            let diff = diff_lines(&old[old_start..old_end], old_start, &[], 0);
+            diffs.push(SectionDiff { section, diff });
+        }
+    }
+
+    diffs.retain(|section| !section.diff.is_empty());
+    diffs
+}
+
+/// `(header, start, end)` line ranges of each section, including the header line.
+fn sections<'a>(
+    lines: &[&'a str],
+    is_header: impl Fn(&str) -> bool,
+) -> Vec<(&'a str, usize, usize)> {
+    let mut sections = vec![("", 0, 0)];
+    for (i, line) in lines.iter().enumerate() {
+        if is_header(line) {
+            sections.push((line, i, i));
+        }
+        sections.last_mut().unwrap().2 = i + 1;
+    }
+    sections
+}
+
+#[derive(Clone, Copy)]
+enum Edit {
+    Keep,
+    Remove(usize),
+    Add(usize),
+}
+
+/// Diff two runs of lines starting at `old_offset` and `new_offset` of their dumps.
+fn diff_lines<'a>(
+    old: &[&'a str],
+    old_offset: usize,
+    new: &[&'a str],
+    new_offset: usize,
+) -> Diff<'a> {
+    // Only the middle between common prefix and suffix needs the actual diff.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut flush = |removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        let paired = removed.len().min(added.len());
+        for (&old_index, &new_index) in removed.iter().zip(added.iter()) {
+            changes.push(LineChange::Changed {
+                old_line: old_offset + prefix + old_index + 1,
+                new_line: new_offset + prefix + new_index + 1,
+                old: old_mid[old_index],
+                new: new_mid[new_index],
+            });
+        }
+        for &old_index in &removed[paired..] {
+            changes.push(LineChange::Removed {
+                old_line: old_offset + prefix + old_index + 1,
+                text: old_mid[old_index],
+            });
+        }
+        for &new_index in &added[paired..] {
+            changes.push(LineChange::Added {
+                new_line: new_offset + prefix + new_index + 1,
+                text: new_mid[new_index],
+            });
+        }
+        removed.clear();
+        added.clear();
+    };
+
+    for edit in myers(old_mid, new_mid) {
+        match edit {
+            Edit::Keep => flush(&mut removed, &mut added),
+            Edit::Remove(i) => removed.push(i),
+            Edit::Add(j) => added.push(j),
+        }
+    }
+    flush(&mut removed, &mut added);
+
+    Diff { changes }
+}
+
+/// The shortest edit script turning `old` into `new`, after Myers' "An O(ND) Difference Algorithm".
+fn myers(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = n + m;
+    let offset = max;
+    // Furthest x reached on each diagonal k = x - y.
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    // v as it was before each round d, on the diagonals -d..=d only.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let at = |k: isize| (offset + k) as usize;
+    'search: for d in 0..=max {
+        trace.push(v[at(-d)..=at(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().skip(1).rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if x == prev_x {
+            edits.push(Edit::Add((y - 1) as usize));
+        } else {
+            edits.push(Edit::Remove((x - 1) as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    // What's left is the snake of round 0, lines both start with.
+    edits.extend((0..x).map(|_| Edit::Keep));
+
+    edits.reverse();
+    edits
+}
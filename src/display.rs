@@ -0,0 +1,413 @@
+//! Typed output of `dumpsys display`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::display::DisplayManager;
+//!
+//! let display = DisplayManager::parse(
+//!     r#"DISPLAY MANAGER (dumpsys display)
+//!   mSafeMode=false
+//! Logical Displays: size=1
+//!   Display 0:
+//!     mDisplayId=0
+//!     mBaseDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, FLAG_SECURE, real 1080 x 2400, largest app 2400 x 2400, mode 2, defaultMode 1, modes [{id=1, width=1080, height=2400, fps=60.000004, alternativeRefreshRates=[120.0]}, {id=2, width=1080, height=2400, fps=120.0, alternativeRefreshRates=[60.000004]}], renderFrameRate 60.0, state ON}
+//!
+//! Display Power Controller:
+//!   mUseAutoBrightness=true
+//!
+//! Display Power State:
+//!   mScreenState=ON
+//!   mScreenBrightness=0.39763778"#,
+//! )
+//! .unwrap();
+//!
+//! let screen = display.default_display().unwrap();
+//! assert_eq!(screen.name, "Built-in Screen");
+//! assert_eq!((screen.width, screen.height), (1080, 2400));
+//! assert_eq!(screen.modes.len(), 2);
+//! assert_eq!(screen.modes[0].alternative_refresh_rates, [120.0]);
+//! assert_eq!(screen.refresh_rate(), Some(120.0));
+//! assert_eq!(screen.render_frame_rate, Some(60.0));
+//! assert_eq!(display.brightness, Some(0.39763778));
+//! assert_eq!(display.auto_brightness, Some(true));
+//! assert_eq!(display.screen_state.as_deref(), Some("ON"));
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, KeyValues},
+    typed, DumpParse,
+};
+
+const BASE_INFO: &str = "mBaseDisplayInfo=DisplayInfo{";
+const OVERRIDE_INFO: &str = "mOverrideDisplayInfo=DisplayInfo{";
+
+/// A mode a display supports
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayMode {
+    pub id: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Refresh rate in Hz
+    pub refresh_rate: f64,
+    /// Refresh rates of modes with the same resolution that can be switched to seamlessly
+    pub alternative_refresh_rates: Vec<f64>,
+}
+
+/// A logical display, as seen by apps
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Display {
+    pub display_id: u32,
+    pub name: String,
+    /// Resolution of the display in pixels
+    pub width: u32,
+    pub height: u32,
+    /// Id of the active mode
+    pub mode_id: u32,
+    pub default_mode_id: u32,
+    pub modes: Vec<DisplayMode>,
+    /// Frame rate apps render at, lower than the refresh rate when SurfaceFlinger divides it
+    pub render_frame_rate: Option<f64>,
+    /// Power state such as `ON` or `DOZE`
+    pub state: Option<String>,
+}
+
+impl Display {
+    pub fn active_mode(&self) -> Option<&DisplayMode> {
+        self.mode(self.mode_id)
+    }
+
+    pub fn mode(&self, id: u32) -> Option<&DisplayMode> {
+        self.modes.iter().find(|mode| mode.id == id)
+    }
+
+    /// Refresh rate of the active mode in Hz.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.active_mode().map(|mode| mode.refresh_rate)
+    }
+}
+
+/// Output of `dumpsys display`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayManager {
+    /// Logical displays by id
+    pub displays: Vec<Display>,
+    /// State of the default display such as `ON` or `OFF`
+    pub screen_state: Option<String>,
+    /// Brightness of the default display, between 0 and 1 on Android 11 and later, 0 to 255 before
+    pub brightness: Option<f64>,
+    pub auto_brightness: Option<bool>,
+}
+
+impl DisplayManager {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut manager = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            let info = line
+                .strip_prefix(OVERRIDE_INFO)
+                .map(|info| (info, true))
+                .or_else(|| line.strip_prefix(BASE_INFO).map(|info| (info, false)));
+            let Some((info, is_override)) = info else {
+                continue;
+            };
+            let display = display(info.strip_suffix('}').unwrap_or(info))?;
+            match manager
+                .displays
+                .iter_mut()
+                .find(|other| other.display_id == display.display_id)
+            {
+                // The override carries what apps see, including mode changes they requested.
+                Some(other) if is_override => *other = display,
+                Some(_) => {}
+                None => manager.displays.push(display),
+            }
+        }
+        manager.displays.sort_by_key(|display| display.display_id);
+
+        let fields = KeyValues::parse(text);
+        manager.screen_state = fields.get("mScreenState").map(str::to_owned);
+        manager.brightness = fields.get_float("mScreenBrightness").ok();
+        manager.auto_brightness = fields.get_bool("mUseAutoBrightness").ok();
+        Ok(manager)
+    }
+
+    /// Display 0, the built-in screen on phones.
+    pub fn default_display(&self) -> Option<&Display> {
+        self.display(0)
+    }
+
+    pub fn display(&self, id: u32) -> Option<&Display> {
+        self.displays
+            .iter()
+            .find(|display| display.display_id == id)
+    }
+}
+
+impl DumpParse for DisplayManager {
+    const SERVICE: &'static str = "display";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `display` and return the refresh rate of the default display in Hz.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// let refresh_rate = dumpsys_rs::display::current_refresh_rate().unwrap();
+/// println!("frame budget: {:.2} ms", 1000.0 / refresh_rate);
+/// # }
+/// ```
+pub fn current_refresh_rate() -> Result<f64, DumpError> {
+    let manager = typed::dump_service::<DisplayManager>(Vec::<&str>::new())?;
+    manager
+        .default_display()
+        .and_then(Display::refresh_rate)
+        .ok_or_else(|| DumpError::from(ParseError::Missing("refresh rate".to_owned())))
+}
+
+/// Fields of `DisplayInfo.toString`, as in `"Built-in Screen", displayId 0, real 1080 x 2400, mode 2`.
+fn display(info: &str) -> Result<Display, ParseError> {
+    let mut display = Display::default();
+
+    for (i, field) in split_fields(info).into_iter().enumerate() {
+        if i == 0 {
+            display.name = field.trim_matches('"').to_owned();
+            continue;
+        }
+        let Some((key, value)) = field.split_once(' ') else {
+            continue;
+        };
+        let number = |value: &str| value.parse().map_err(|_| invalid(key, value));
+        match key {
+            "displayId" => display.display_id = number(value)?,
+            "mode" => display.mode_id = number(value)?,
+            "defaultMode" => display.default_mode_id = number(value)?,
+            "real" => {
+                let (width, height) = value.split_once(" x ").ok_or_else(|| invalid(key, value))?;
+                display.width = number(width)?;
+                display.height = number(height)?;
+            }
+            "modes" => {
+                let modes = value.trim_start_matches('[').trim_end_matches(']');
+                display.modes = split_fields(modes)
+                    .into_iter()
+                    .map(mode)
+                    .collect::<Result<_, _>>()?;
+            }
+            "renderFrameRate" => {
+                display.render_frame_rate = Some(value.parse().map_err(|_| invalid(key, value))?);
+            }
+            "state" => display.state = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+
+    Ok(display)
+}
+
+/// `{id=1, width=1080, height=2400, fps=60.000004, alternativeRefreshRates=[120.0]}`
+fn mode(mode: &str) -> Result<DisplayMode, ParseError> {
+    let fields = KeyValues::parse(mode);
+    let alternative_refresh_rates = match fields.get("alternativeRefreshRates") {
+        Some(rates) => rates
+            .trim_matches(['[', ']'])
+            .split(", ")
+            .filter(|rate| !rate.is_empty())
+            .map(|rate| {
+                rate.parse()
+                    .map_err(|_| invalid("alternativeRefreshRates", rates))
+            })
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    Ok(DisplayMode {
+        id: fields.get_parsed("id")?,
+        width: fields.get_parsed("width")?,
+        height: fields.get_parsed("height")?,
+        refresh_rate: fields.get_float("fps")?,
+        alternative_refresh_rates,
+    })
+}
+
+/// Split at `, ` outside of brackets, braces and quotes.
+fn split_fields(text: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut start = 0;
+    let bytes = text.as_bytes();
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'[' | b'{' if !quoted => depth += 1,
+            b']' | b'}' if !quoted => depth = depth.saturating_sub(1),
+            b',' if !quoted && depth == 0 && bytes.get(i + 1) == Some(&b' ') => {
+                fields.push(text[start..i].trim());
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    if start < text.len() {
+        fields.push(text[start..].trim());
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys display` of Android 14, cut down to the display infos and power state
+    const DUMP: &str = r#"DISPLAY MANAGER (dumpsys display)
+  mOnlyCode=false
+  mSafeMode=false
+  mPendingTraversal=false
+Logical Displays: size=2
+  Display 0:
+    mDisplayId=0
+    mIsEnabled=true
+    mLayerStack=0
+    mPrimaryDisplayDevice=Built-in Screen
+    mBaseDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, displayGroupId 0, FLAG_SECURE, FLAG_SUPPORTS_PROTECTED_BUFFERS, FLAG_TRUSTED, real 1080 x 2400, largest app 2400 x 2400, smallest app 1080 x 1080, appVsyncOff 7500000, presDeadline 11666666, mode 1, defaultMode 1, userPreferredModeId -1, modes [{id=1, width=1080, height=2400, fps=60.000004, alternativeRefreshRates=[90.0, 120.0], supportedHdrTypes=[2, 3, 4]}, {id=2, width=1080, height=2400, fps=90.0, alternativeRefreshRates=[60.000004, 120.0], supportedHdrTypes=[2, 3, 4]}, {id=3, width=1080, height=2400, fps=120.0, alternativeRefreshRates=[60.000004, 90.0], supportedHdrTypes=[2, 3, 4]}], hdrCapabilities HdrCapabilities{mSupportedHdrTypes=[2, 3, 4], mMaxLuminance=1000.0, mMaxAverageLuminance=120.0, mMinLuminance=5.0E-4}, userDisabledHdrTypes [], minimalPostProcessingSupported false, rotation 0, state ON, committedState ON, type INTERNAL, uniqueId "local:4619827259835644672", app 1080 x 2400, density 420 (411.0 x 409.0) dpi, layerStack 0, colorMode 0, supportedColorModes [0, 7, 9], address {port=128, model=0x401cec6a7a2b7b}, deviceProductInfo DeviceProductInfo{name=, manufacturerPnpId=GGL, productId=1, modelYear=null, manufactureDate=ManufactureDate{week=27, year=2006}, connectionToSinkType=0}, removeMode 0, refreshRateOverride 0.0, brightnessMinimum 0.0, brightnessMaximum 1.0, brightnessDefault 0.39763778, installOrientation ORIENTATION_0, renderFrameRate 120.0}
+    mOverrideDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, displayGroupId 0, FLAG_SECURE, FLAG_SUPPORTS_PROTECTED_BUFFERS, FLAG_TRUSTED, real 1080 x 2400, largest app 2400 x 2400, smallest app 1080 x 1080, appVsyncOff 7500000, presDeadline 11666666, mode 3, defaultMode 1, userPreferredModeId -1, modes [{id=1, width=1080, height=2400, fps=60.000004, alternativeRefreshRates=[90.0, 120.0], supportedHdrTypes=[2, 3, 4]}, {id=2, width=1080, height=2400, fps=90.0, alternativeRefreshRates=[60.000004, 120.0], supportedHdrTypes=[2, 3, 4]}, {id=3, width=1080, height=2400, fps=120.0, alternativeRefreshRates=[60.000004, 90.0], supportedHdrTypes=[2, 3, 4]}], rotation 0, state ON, committedState ON, type INTERNAL, renderFrameRate 60.0}
+  Display 2:
+    mDisplayId=2
+    mIsEnabled=true
+    mLayerStack=2
+    mPrimaryDisplayDevice=Overlay #1
+    mBaseDisplayInfo=DisplayInfo{"Overlay #1", displayId 2, displayGroupId 0, FLAG_PRESENTATION, real 720 x 480, largest app 720 x 480, smallest app 720 x 480, appVsyncOff 0, presDeadline 16666667, mode 4, defaultMode 4, modes [{id=4, width=720, height=480, fps=60.0, alternativeRefreshRates=[], supportedHdrTypes=[]}], rotation 0, state ON, committedState UNKNOWN, type OVERLAY, renderFrameRate 60.0}
+    mOverrideDisplayInfo=null
+
+Display Power Controller:
+  mDisplayId=0
+  mUseAutoBrightness=false
+
+Display Power State:
+  mStopped=false
+  mScreenState=ON
+  mScreenBrightness=0.2519685
+  mSdrScreenBrightness=0.2519685
+"#;
+
+    #[test]
+    fn parses_the_dump() {
+        let manager = DisplayManager::parse(DUMP).unwrap();
+        assert_eq!(manager.displays.len(), 2);
+        assert_eq!(manager.screen_state.as_deref(), Some("ON"));
+        assert_eq!(manager.brightness, Some(0.2519685));
+        assert_eq!(manager.auto_brightness, Some(false));
+
+        let overlay = manager.display(2).unwrap();
+        assert_eq!(overlay.name, "Overlay #1");
+        assert_eq!((overlay.width, overlay.height), (720, 480));
+        assert_eq!(
+            overlay.modes,
+            [DisplayMode {
+                id: 4,
+                width: 720,
+                height: 480,
+                refresh_rate: 60.0,
+                alternative_refresh_rates: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn override_info_replaces_the_base() {
+        let manager = DisplayManager::parse(DUMP).unwrap();
+        let screen = manager.default_display().unwrap();
+        assert_eq!(screen.name, "Built-in Screen");
+        assert_eq!((screen.mode_id, screen.default_mode_id), (3, 1));
+        assert_eq!(screen.refresh_rate(), Some(120.0));
+        assert_eq!(screen.render_frame_rate, Some(60.0));
+        assert_eq!(screen.state.as_deref(), Some("ON"));
+        assert_eq!(screen.modes.len(), 3);
+        assert_eq!(
+            screen.mode(2).unwrap().alternative_refresh_rates,
+            [60.000004, 120.0]
+        );
+        assert_eq!(screen.mode(1).unwrap().refresh_rate, 60.000004);
+    }
+
+    #[test]
+    fn fields_of_older_releases() {
+        // Android 10 has neither alternative refresh rates nor a render frame rate
+        let text = r#"    mBaseDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, uniqueId "local:0", app 1080 x 2340, real 1080 x 2340, largest app 2340 x 2204, smallest app 1080 x 964, mode 2, defaultMode 1, modes [{id=1, width=1080, height=2340, fps=60.000004}, {id=2, width=1080, height=2340, fps=90.0}], colorMode 0, rotation 0, state ON}
+  mScreenBrightness=102
+"#;
+        let manager = DisplayManager::parse(text).unwrap();
+        let screen = manager.default_display().unwrap();
+        assert_eq!(screen.name, "Built-in Screen");
+        assert_eq!(screen.refresh_rate(), Some(90.0));
+        assert!(screen.modes[0].alternative_refresh_rates.is_empty());
+        assert_eq!(screen.render_frame_rate, None);
+        assert_eq!(manager.brightness, Some(102.0));
+        assert_eq!(manager.auto_brightness, None);
+    }
+
+    #[test]
+    fn unknown_active_mode_has_no_refresh_rate() {
+        let text = r#"mBaseDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, mode 7, modes [{id=1, width=1080, height=2400, fps=60.0}]}"#;
+        let manager = DisplayManager::parse(text).unwrap();
+        assert_eq!(manager.default_display().unwrap().active_mode(), None);
+        assert_eq!(manager.default_display().unwrap().refresh_rate(), None);
+    }
+
+    #[test]
+    fn invalid_display_infos() {
+        let info = |fields: &str| {
+            format!(r#"mBaseDisplayInfo=DisplayInfo{{"Built-in Screen", {fields}}}"#)
+        };
+        assert_eq!(
+            DisplayManager::parse(&info("displayId zero")).unwrap_err(),
+            invalid("displayId", "zero")
+        );
+        assert_eq!(
+            DisplayManager::parse(&info("real 1080x2400")).unwrap_err(),
+            invalid("real", "1080x2400")
+        );
+        assert_eq!(
+            DisplayManager::parse(&info("renderFrameRate fast")).unwrap_err(),
+            invalid("renderFrameRate", "fast")
+        );
+        assert_eq!(
+            DisplayManager::parse(&info("modes [{id=1, width=1080, height=2400}]")).unwrap_err(),
+            ParseError::Missing("fps".to_owned())
+        );
+        assert_eq!(
+            DisplayManager::parse(&info(
+                "modes [{id=1, width=1080, height=2400, fps=60.0, alternativeRefreshRates=[90.0, fast]}]"
+            ))
+            .unwrap_err(),
+            invalid("alternativeRefreshRates", "[90.0, fast")
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            DisplayManager::parse(&crlf).unwrap(),
+            DisplayManager::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(
+            DisplayManager::parse("").unwrap(),
+            DisplayManager::default()
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Typed output of `dumpsys dropbox` and `dumpsys dropbox --print`
+//!
+//! Without `--print` the dump lists every entry with a one-line preview of its text; with it, the
+//! full text follows each entry. Timestamps are kept as printed, in the local time of the device.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::dropbox::DropBox;
+//!
+//! let summary = DropBox::parse(
+//!     "Drop box contents: 3 entries
+//! Max entries: 1000
+//!
+//! 2024-01-01 12:00:00 data_app_crash (text, 1234 bytes)
+//!     Process: com.example.app/PID: 1234/Flags: 0x38c83e44/Package: com.example.app v7 (1.0)/...
+//! 2024-01-01 12:01:00 SYSTEM_BOOT (compressed text, 345 bytes)
+//!     isPrevious: true/Build: example/generic:14/...
+//! 2024-01-01 12:02:00 SYSTEM_TOMBSTONE_PROTO (data, 80000 bytes)",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(summary.entries.len(), 3);
+//! let crash = &summary.entries[0];
+//! assert_eq!(crash.timestamp, "2024-01-01 12:00:00");
+//! assert_eq!(crash.tag, "data_app_crash");
+//! assert_eq!(crash.size, Some(1234));
+//! assert!(crash.preview.as_deref().unwrap().starts_with("Process: com.example.app/"));
+//! assert_eq!(crash.text, None);
+//! assert!(summary.entries[1].compressed);
+//! assert!(!summary.entries[2].is_text);
+//! assert_eq!(summary.tagged("SYSTEM_BOOT").count(), 1);
+//!
+//! let printed = DropBox::parse(
+//!     "Drop box contents: 1 entries
+//! Max entries: 1000
+//!
+//! ========================================
+//! 2024-01-01 12:00:00 data_app_crash (text, 1234 bytes)
+//! Process: com.example.app
+//! PID: 1234
+//!
+//! java.lang.NullPointerException
+//! \tat com.example.app.MainActivity.onCreate(MainActivity.java:12)
+//!
+//! ",
+//! )
+//! .unwrap();
+//!
+//! let crash = &printed.entries[0];
+//! assert_eq!(
+//!     crash.text.as_deref(),
+//!     Some("Process: com.example.app\nPID: 1234\n\njava.lang.NullPointerException\n\tat com.example.app.MainActivity.onCreate(MainActivity.java:12)"),
+//! );
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    typed, DumpParse,
+};
+
+const PRINT_ARG: &str = "--print";
+const SEPARATOR: &str = "========================================";
+const PREVIEW_INDENT: &str = "    ";
+
+/// An entry of the drop box
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entry {
+    /// As in `data_app_crash` or `SYSTEM_BOOT`
+    pub tag: String,
+    /// As printed by the device, to the second
+    pub timestamp: String,
+    /// Whether the entry is text rather than binary data
+    pub is_text: bool,
+    pub compressed: bool,
+    /// Size of the file, `None` when the file is missing or its contents were lost
+    pub size: Option<u64>,
+    /// Start of the text, with newlines as `/`, without `--print`
+    pub preview: Option<String>,
+    /// Full text, with `--print`
+    pub text: Option<String>,
+}
+
+/// Output of `dumpsys dropbox`, with or without `--print`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DropBox {
+    /// Oldest first
+    pub entries: Vec<Entry>,
+}
+
+impl DropBox {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            entries: entries(text),
+        })
+    }
+
+    /// The entries with `tag`, oldest first.
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.entries.iter().filter(move |entry| entry.tag == tag)
+    }
+
+    /// The newest entry with `tag`.
+    pub fn latest(&self, tag: &str) -> Option<&Entry> {
+        self.entries.iter().rev().find(|entry| entry.tag == tag)
+    }
+}
+
+impl DumpParse for DropBox {
+    const SERVICE: &'static str = "dropbox";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `dropbox` and return every entry, with previews of their text.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for entry in dumpsys_rs::dropbox::list().unwrap() {
+///     println!("{} {} ({:?} bytes)", entry.timestamp, entry.tag, entry.size);
+/// }
+/// # }
+/// ```
+pub fn list() -> Result<Vec<Entry>, DumpError> {
+    typed::dump_service::<DropBox>(Vec::<&str>::new()).map(|dropbox| dropbox.entries)
+}
+
+/// Dump `dropbox --print <tag>` and return the entries with `tag`, with their full text.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for entry in dumpsys_rs::dropbox::print("data_app_anr").unwrap() {
+///     println!("{}\n{}", entry.timestamp, entry.text.unwrap_or_default());
+/// }
+/// # }
+/// ```
+pub fn print(tag: &str) -> Result<Vec<Entry>, DumpError> {
+    typed::dump_service::<DropBox>([PRINT_ARG, tag]).map(|dropbox| dropbox.entries)
+}
+
+/// Entries of the output of `dumpsys dropbox`, with or without `--print`.
+pub(crate) fn entries(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut printed = false;
+    let mut after_separator = false;
+    let mut body: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        if line == SEPARATOR {
+            finish(&mut entries, body.take());
+            printed = true;
+            after_separator = true;
+            continue;
+        }
+        // Text printed with `--print` may hold lines that look like headers.
+        let header = if !printed || after_separator {
+            header(line)
+        } else {
+            None
+        };
+        after_separator = false;
+
+        if let Some(entry) = header {
+            finish(&mut entries, body.take());
+            entries.push(entry);
+            if printed {
+                body = Some(Vec::new());
+            }
+        } else if let Some(body) = &mut body {
+            body.push(line);
+        } else if let Some(preview) = line.strip_prefix(PREVIEW_INDENT) {
+            if let Some(entry) = entries.last_mut() {
+                entry.preview.get_or_insert_with(|| preview.to_owned());
+            }
+        }
+    }
+    finish(&mut entries, body);
+    entries
+}
+
+/// Set the text of the last entry to the `--print`ed `body`.
+fn finish(entries: &mut [Entry], body: Option<Vec<&str>>) {
+    let (Some(entry), Some(body)) = (entries.last_mut(), body) else {
+        return;
+    };
+    let text = body.join("\n");
+    let text = text.trim_end_matches('\n');
+    if entry.is_text && !text.is_empty() {
+        entry.text = Some(text.to_owned());
+    }
+}
+
+/// `2024-01-01 12:00:00 data_app_crash (compressed text, 1234 bytes)`, or `(no file)` and
+/// `(contents lost)` in place of the size.
+fn header(line: &str) -> Option<Entry> {
+    let bytes = line.as_bytes();
+    let is_date = bytes.len() > 20
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[10] == b' '
+        && bytes[13] == b':';
+    if !is_date {
+        return None;
+    }
+    let (timestamp, rest) = line.split_at(line[11..].find(' ')? + 11);
+    let (tag, details) = rest
+        .trim_start()
+        .split_once(' ')
+        .unwrap_or((rest.trim(), ""));
+    let details = details.trim_start_matches('(').trim_end_matches(')');
+    let (kind, size) = details.split_once(", ").unwrap_or((details, ""));
+    Some(Entry {
+        tag: tag.to_owned(),
+        timestamp: timestamp.to_owned(),
+        is_text: kind.ends_with("text"),
+        compressed: kind.starts_with("compressed "),
+        size: size
+            .strip_suffix(" bytes")
+            .and_then(|size| size.parse().ok()),
+        preview: None,
+        text: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys dropbox` of Android 14, cut down to a few entries
+    const DUMP: &str = "Drop box contents: 5 entries
+Max entries: 1000
+Low priority rate limit period: 2000 ms
+Low priority tags: {data_app_wtf, keymaster, system_server_wtf, system_app_strictmode, system_app_wtf, system_server_strictmode, data_app_strictmode, netstats}
+
+2024-03-12 09:12:44 SYSTEM_BOOT (compressed text, 412 bytes)
+    isPrevious: true/Build: example/generic:14/UP1A.231005.007/10754064:user/release-keys/...
+2024-03-12 09:15:02 data_app_crash (compressed text, 2345 bytes)
+    Process: com.example.app/PID: 4567/UID: 10123/Flags: 0x38c83e44/Package: com.example.app v7 (1.0)/...
+2024-03-12 09:20:13 SYSTEM_TOMBSTONE_PROTO_WITH_HEADERS (data, 80000 bytes)
+2024-03-12 09:31:47 data_app_anr (no file)
+2024-03-12 09:40:00 data_app_crash (contents lost)
+";
+
+    /// `dumpsys dropbox --print data_app_crash` of Android 14
+    const PRINTED: &str = "Drop box contents: 5 entries
+Max entries: 1000
+
+Searching for: data_app_crash
+
+========================================
+2024-03-12 09:15:02 data_app_crash (compressed text, 2345 bytes)
+Process: com.example.app
+PID: 4567
+UID: 10123
+Package: com.example.app v7 (1.0)
+
+java.lang.IllegalStateException: 2024-03-12 09:15:02 crashed at startup
+\tat com.example.app.MainActivity.onCreate(MainActivity.java:12)
+
+========================================
+2024-03-12 09:40:00 data_app_crash (contents lost)
+";
+
+    #[test]
+    fn parses_the_entries() {
+        let dropbox = DropBox::parse(DUMP).unwrap();
+        assert_eq!(dropbox.entries.len(), 5);
+        assert_eq!(
+            dropbox.entries[1],
+            Entry {
+                tag: "data_app_crash".to_owned(),
+                timestamp: "2024-03-12 09:15:02".to_owned(),
+                is_text: true,
+                compressed: true,
+                size: Some(2345),
+                preview: Some(
+                    "Process: com.example.app/PID: 4567/UID: 10123/Flags: 0x38c83e44/Package: \
+                     com.example.app v7 (1.0)/..."
+                        .to_owned()
+                ),
+                text: None,
+            }
+        );
+        let tombstone = &dropbox.entries[2];
+        assert!(!tombstone.is_text);
+        assert!(!tombstone.compressed);
+        assert_eq!(tombstone.size, Some(80000));
+        assert_eq!(tombstone.preview, None);
+        // Without a file there is no size
+        assert_eq!(dropbox.entries[3].size, None);
+        assert_eq!(dropbox.tagged("data_app_crash").count(), 2);
+        assert_eq!(dropbox.latest("data_app_crash").unwrap().size, None);
+        assert_eq!(dropbox.latest("SYSTEM_RECOVERY_LOG"), None);
+    }
+
+    #[test]
+    fn parses_the_printed_entries() {
+        let dropbox = DropBox::parse(PRINTED).unwrap();
+        assert_eq!(dropbox.entries.len(), 2);
+        // The timestamp in the stack trace doesn't start an entry
+        let crash = &dropbox.entries[0];
+        assert_eq!(
+            crash.text.as_deref(),
+            Some(
+                "Process: com.example.app\nPID: 4567\nUID: 10123\nPackage: com.example.app v7 \
+                 (1.0)\n\njava.lang.IllegalStateException: 2024-03-12 09:15:02 crashed at \
+                 startup\n\tat com.example.app.MainActivity.onCreate(MainActivity.java:12)"
+            )
+        );
+        assert_eq!(crash.preview, None);
+        assert_eq!(dropbox.entries[1].text, None);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        for text in [DUMP, PRINTED] {
+            assert_eq!(
+                DropBox::parse(&text.replace('\n', "\r\n")),
+                DropBox::parse(text)
+            );
+        }
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(DropBox::parse("").unwrap(), DropBox::default());
+        assert_eq!(
+            DropBox::parse("Drop box contents: 0 entries\nMax entries: 1000\n").unwrap(),
+            DropBox::default()
+        );
+    }
+
+    #[test]
+    fn truncated_headers() {
+        let text = "2024-03-12 09:15:02 data_app_crash (compressed te
+2024-03-12 09:1
+";
+        let dropbox = DropBox::parse(text).unwrap();
+        // The header cut short before the time is no entry
+        assert_eq!(dropbox.entries.len(), 1);
+        let crash = &dropbox.entries[0];
+        assert_eq!(crash.tag, "data_app_crash");
+        assert!(crash.compressed);
+        assert!(!crash.is_text);
+        assert_eq!(crash.size, None);
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::Read;
+
+use crate::{error::DumpError, Dumpsys};
+
+/// A [`Dumpsys`] that keeps its output buffers between dumps, for polling loops
+///
+/// Dumps return views into the buffers instead of owned output, so once the buffers have grown to the
+/// size of the largest dump, polling no longer allocates for the output. What remains per dump is
+/// the bookkeeping of the dump itself, e.g. the thread of [`Execution::Spawn`](crate::Execution::Spawn),
+/// which [`Execution::Pool`](crate::Execution::Pool) avoids.
+///
+/// # Example
+///
+/// ```
+/// use std::{thread, time::Duration};
+///
+/// use dumpsys_rs::{Dumper, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// let mut surfaceflinger = Dumper::new(Dumpsys::new("SurfaceFlinger")?);
+/// loop {
+///     let latency = surfaceflinger.dump(&["--latency"]).unwrap();
+///     println!("{}", latency.lines().count());
+///     thread::sleep(Duration::from_millis(100));
+/// }
+/// # }
+/// ```
+pub struct Dumper {
+    dumpsys: Dumpsys,
+    text: String,
+    bytes: Vec<u8>,
+}
+
+impl Dumper {
+    /// Dump with `dumpsys`, starting with empty buffers.
+    pub fn new(dumpsys: Dumpsys) -> Self {
+        Self {
+            dumpsys,
+            text: String::new(),
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Like [`Dumpsys::dump`], returning the text buffer the output was read into.
+    pub fn dump(
+        &mut self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<&str, DumpError> {
+        self.dumpsys.dump_into(args, &mut self.text)?;
+        Ok(&self.text)
+    }
+
+    /// Like [`Dumpsys::dump_to_vec`], returning the byte buffer the output was read into.
+    pub fn dump_bytes(
+        &mut self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<&[u8], DumpError> {
+        let bytes = &mut self.bytes;
+        self.dumpsys.run(args, |read| {
+            bytes.clear();
+            read.read_to_end(bytes)
+        })?;
+        Ok(&self.bytes)
+    }
+
+    /// Give back the memory of buffers beyond `capacity` bytes, e.g. after an unusually large dump.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.text.shrink_to(capacity);
+        self.bytes.shrink_to(capacity);
+    }
+
+    /// The handle dumps go through.
+    pub fn dumpsys(&self) -> &Dumpsys {
+        &self.dumpsys
+    }
+
+    /// Take the handle back, dropping the buffers.
+    pub fn into_inner(self) -> Dumpsys {
+        self.dumpsys
+    }
+}
+
+impl From<Dumpsys> for Dumper {
+    fn from(dumpsys: Dumpsys) -> Self {
+        Self::new(dumpsys)
+    }
+}
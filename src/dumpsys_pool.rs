@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::{death::DeathWatch, Dumpsys};
+
+type Cache = Mutex<HashMap<String, Entry>>;
+
+struct Entry {
+    dumpsys: Dumpsys,
+    // Dropped with the entry, unlinking the death callback.
+    _death: Option<DeathWatch>,
+}
+
+/// A cache of resolved services shared by long-running monitors
+///
+/// Services are looked up on first use and handed out as clones afterwards, which are cheap and share
+/// the handle, its stats and whether it's [suspect](Dumpsys::is_suspect). A service is forgotten as
+/// soon as its process dies, so the next [`DumpsysPool::get`] looks up the restarted one. Clones of
+/// the pool share the cache.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::DumpsysPool;
+///
+/// let pool = DumpsysPool::new();
+/// loop {
+///     if let Some(surfaceflinger) = pool.get("SurfaceFlinger") {
+///         let _ = surfaceflinger.dump(&["--latency"]);
+///     }
+/// #   break;
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct DumpsysPool {
+    cache: Arc<Cache>,
+}
+
+impl DumpsysPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached handle of `service_name`, looking it up if there is none, `None` if it isn't registered.
+    pub fn get(&self, service_name: impl AsRef<str>) -> Option<Dumpsys> {
+        let service_name = service_name.as_ref();
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(entry) = cache.get(service_name) {
+            return Some(entry.dumpsys.clone());
+        }
+
+        let dumpsys = Dumpsys::try_new(service_name)?;
+        let death = watch(&self.cache, service_name, &dumpsys);
+        cache.insert(
+            service_name.to_owned(),
+            Entry {
+                dumpsys: dumpsys.clone(),
+                _death: death,
+            },
+        );
+        Some(dumpsys)
+    }
+
+    /// Forget `service_name`, so the next [`DumpsysPool::get`] looks it up again.
+    pub fn invalidate(&self, service_name: &str) {
+        self.cache.lock().unwrap().remove(service_name);
+    }
+
+    /// Forget every service.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+/// Evict `dumpsys` from the cache once its service dies, unless it was replaced meanwhile.
+fn watch(cache: &Arc<Cache>, service_name: &str, dumpsys: &Dumpsys) -> Option<DeathWatch> {
+    let cache: Weak<Cache> = Arc::downgrade(cache);
+    // Clones share the handle, so its lock tells the cached clone apart from a replacement.
+    let handle = Arc::downgrade(&dumpsys.service);
+    let service_name = service_name.to_owned();
+
+    dumpsys
+        .on_death(move || {
+            let Some(cache) = cache.upgrade() else {
+                return;
+            };
+            let mut cache = cache.lock().unwrap();
+            let current = cache.get(&service_name).is_some_and(|entry| {
+                Weak::ptr_eq(&Arc::downgrade(&entry.dumpsys.service), &handle)
+            });
+            if current {
+                // Dropping the entry outside the lock lets its death watch unlink freely.
+                let entry = cache.remove(&service_name);
+                drop(cache);
+                drop(entry);
+            }
+        })
+        .ok()
+}
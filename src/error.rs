@@ -1,12 +1,285 @@
-use std::io;
+use std::{fmt, io, string::FromUtf8Error};
 
 use binder::StatusCode;
 use thiserror::Error;
 
+/// The service and arguments of the dump that failed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DumpContext {
+    pub service: String,
+    pub args: Vec<String>,
+}
+
+impl fmt::Display for DumpContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`dumpsys {}", self.service)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        write!(f, "`")
+    }
+}
+
+/// Why a dump failed
 #[derive(Error, Debug)]
 pub enum DumpError {
-    #[error("IO Error")]
-    IO(#[from] io::Error),
-    #[error("Dump error")]
-    DumpStatus(#[from] StatusCode),
+    /// The service isn't registered with servicemanager
+    #[error("{context}: service not found")]
+    ServiceNotFound { context: DumpContext },
+    /// The caller isn't allowed to dump the service
+    #[error("{context}: permission denied")]
+    PermissionDenied { context: DumpContext },
+    /// The service died, e.g. because it restarted, see [`Dumpsys::refresh`](crate::Dumpsys::refresh)
+    #[error("{context}: service died")]
+    DeadObject { context: DumpContext },
+    /// The dump transaction failed with any other status
+    #[error("{context}: dump failed with {status:?}")]
+    Status {
+        context: DumpContext,
+        status: StatusCode,
+    },
+    /// Reading the output failed, e.g. on a broken pipe or output that isn't UTF-8, or the thread
+    /// running the transaction panicked, `partial` holds what was read before that
+    #[error("{context}: IO error")]
+    Io {
+        context: DumpContext,
+        #[source]
+        source: io::Error,
+        partial: Vec<u8>,
+    },
+    /// The dump didn't finish within the dump timeout, `partial` holds what was read until then
+    #[error("{context}: dump timed out")]
+    Timeout {
+        context: DumpContext,
+        partial: Vec<u8>,
+    },
+    /// Output went past `max_output_bytes`, `partial` holds what was read before that
+    #[error("{context}: dump output exceeded the limit after {bytes_read} bytes")]
+    Truncated {
+        context: DumpContext,
+        bytes_read: u64,
+        partial: Vec<u8>,
+    },
+    /// The service stopped mid-dump: the transaction failed with `status` after `bytes_read` bytes of
+    /// output, e.g. because the service died, and `partial` holds them
+    #[error("{context}: dump stopped after {bytes_read} bytes with {status:?}")]
+    Incomplete {
+        context: DumpContext,
+        status: StatusCode,
+        bytes_read: u64,
+        partial: Vec<u8>,
+    },
+    /// The dump was aborted through its [`CancelToken`](crate::CancelToken), `partial` holds what was read until then
+    #[error("{context}: dump cancelled")]
+    Cancelled {
+        context: DumpContext,
+        partial: Vec<u8>,
+    },
+    /// The dump succeeded but its output couldn't be parsed, see [`DumpParse`](crate::DumpParse)
+    #[error("{context}: unexpected output")]
+    Parse {
+        context: DumpContext,
+        #[source]
+        source: ParseError,
+    },
+}
+
+impl DumpError {
+    pub(crate) fn timeout() -> Self {
+        Self::Timeout {
+            context: DumpContext::default(),
+            partial: Vec::new(),
+        }
+    }
+
+    pub(crate) fn truncated(bytes_read: u64) -> Self {
+        Self::Truncated {
+            context: DumpContext::default(),
+            bytes_read,
+            partial: Vec::new(),
+        }
+    }
+
+    pub(crate) fn cancelled() -> Self {
+        Self::Cancelled {
+            context: DumpContext::default(),
+            partial: Vec::new(),
+        }
+    }
+
+    /// Output that isn't UTF-8, as an [`io::ErrorKind::InvalidData`] error carrying the raw output.
+    pub(crate) fn invalid_utf8(err: FromUtf8Error) -> Self {
+        Self::Io {
+            context: DumpContext::default(),
+            source: io::Error::new(io::ErrorKind::InvalidData, err.utf8_error()),
+            partial: err.into_bytes(),
+        }
+    }
+
+    /// The service and arguments of the failed dump.
+    pub fn context(&self) -> &DumpContext {
+        match self {
+            Self::ServiceNotFound { context }
+            | Self::PermissionDenied { context }
+            | Self::DeadObject { context }
+            | Self::Status { context, .. }
+            | Self::Io { context, .. }
+            | Self::Timeout { context, .. }
+            | Self::Truncated { context, .. }
+            | Self::Incomplete { context, .. }
+            | Self::Cancelled { context, .. }
+            | Self::Parse { context, .. } => context,
+        }
+    }
+
+    /// Output read before the failure, empty for errors that happen before any is read.
+    ///
+    /// A dump that was retried only keeps the output of its last attempt.
+    ///
+    /// A dump cut short is often still enough to diagnose the problem, such as the activities of
+    /// `dumpsys activity` listed until it hung.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let activity = Dumpsys::builder("activity")
+    ///     .dump_timeout(Duration::from_secs(10))
+    ///     .build()?;
+    /// let output = match activity.dump(&["activities"]) {
+    ///     Ok(output) => output,
+    ///     Err(err) => String::from_utf8_lossy(err.partial()).into_owned(),
+    /// };
+    /// println!("{output}");
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn partial(&self) -> &[u8] {
+        match self {
+            Self::Io { partial, .. }
+            | Self::Timeout { partial, .. }
+            | Self::Truncated { partial, .. }
+            | Self::Incomplete { partial, .. }
+            | Self::Cancelled { partial, .. } => partial,
+            _ => &[],
+        }
+    }
+
+    /// The binder status of a failed dump transaction.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::ServiceNotFound { .. } => Some(StatusCode::NAME_NOT_FOUND),
+            Self::PermissionDenied { .. } => Some(StatusCode::PERMISSION_DENIED),
+            Self::DeadObject { .. } => Some(StatusCode::DEAD_OBJECT),
+            Self::Status { status, .. } | Self::Incomplete { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Tell a transaction that failed after `bytes_read` bytes of output from one that failed before any.
+    pub(crate) fn after_output(self, bytes_read: u64) -> Self {
+        match self {
+            Self::DeadObject { context } if bytes_read > 0 => Self::Incomplete {
+                context,
+                status: StatusCode::DEAD_OBJECT,
+                bytes_read,
+                partial: Vec::new(),
+            },
+            Self::Status { context, status } if bytes_read > 0 => Self::Incomplete {
+                context,
+                status,
+                bytes_read,
+                partial: Vec::new(),
+            },
+            err => err,
+        }
+    }
+
+    /// Record which dump failed.
+    pub(crate) fn with_context(mut self, service: &str, args: &[String]) -> Self {
+        let context = match &mut self {
+            Self::ServiceNotFound { context }
+            | Self::PermissionDenied { context }
+            | Self::DeadObject { context }
+            | Self::Status { context, .. }
+            | Self::Io { context, .. }
+            | Self::Timeout { context, .. }
+            | Self::Truncated { context, .. }
+            | Self::Incomplete { context, .. }
+            | Self::Cancelled { context, .. }
+            | Self::Parse { context, .. } => context,
+        };
+        *context = DumpContext {
+            service: service.to_owned(),
+            args: args.to_vec(),
+        };
+        self
+    }
+
+    /// Attach the output received before the failure, for errors that carry it and don't have it yet.
+    pub(crate) fn with_partial(mut self, output: impl FnOnce() -> Vec<u8>) -> Self {
+        if let Self::Io { partial, .. }
+        | Self::Timeout { partial, .. }
+        | Self::Truncated { partial, .. }
+        | Self::Incomplete { partial, .. }
+        | Self::Cancelled { partial, .. } = &mut self
+        {
+            if partial.is_empty() {
+                *partial = output();
+            }
+        }
+        self
+    }
+}
+
+/// Why dump text couldn't be parsed
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParseError {
+    /// A required field isn't in the output
+    #[error("`{0}` not found")]
+    Missing(String),
+    /// A field has a value of the wrong form
+    #[error("`{key}` has invalid value `{value}`")]
+    Invalid { key: String, value: String },
+}
+
+impl From<ParseError> for DumpError {
+    fn from(source: ParseError) -> Self {
+        Self::Parse {
+            context: DumpContext::default(),
+            source,
+        }
+    }
+}
+
+impl From<StatusCode> for DumpError {
+    fn from(status: StatusCode) -> Self {
+        let context = DumpContext::default();
+        match status {
+            StatusCode::NAME_NOT_FOUND => Self::ServiceNotFound { context },
+            StatusCode::PERMISSION_DENIED => Self::PermissionDenied { context },
+            StatusCode::DEAD_OBJECT => Self::DeadObject { context },
+            status => Self::Status { context, status },
+        }
+    }
+}
+
+impl From<io::Error> for DumpError {
+    /// Unwraps dump errors that were passed through [`io::Read`], e.g. by [`DumpReader`](crate::DumpReader).
+    fn from(err: io::Error) -> Self {
+        match err.downcast::<Self>() {
+            Ok(err) => err,
+            Err(source) => Self::Io {
+                context: DumpContext::default(),
+                source,
+                partial: Vec::new(),
+            },
+        }
+    }
 }
@@ -0,0 +1,255 @@
+use std::{
+    fmt, io,
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{error::DumpError, sched::ThreadOptions, watchdog::Watch};
+
+/// How often [`Transaction::wait_until`] checks whether a transaction thread has exited
+const JOIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Breaks when the worker that ran it should exit
+type Job = Box<dyn FnOnce() -> ControlFlow<()> + Send>;
+
+/// Where the blocking dump transaction runs
+///
+/// The transaction doesn't return until the service has written all its output, so something else has to
+/// drain the pipe meanwhile or the service blocks once the pipe is full.
+#[derive(Debug, Clone, Default)]
+pub enum Execution {
+    /// Spawn a thread for the transaction per dump while the calling thread reads the pipe
+    #[default]
+    Spawn,
+    /// Run the transaction on the calling thread, for processes that can't or shouldn't spawn threads
+    ///
+    /// The service dumps into an anonymous memfd instead of a pipe, so it never blocks on a reader,
+    /// and the output is read back once the transaction returns. The output is held in memory by the
+    /// kernel until it's consumed, at most `max_output_bytes` of it rounded up to whole pages: writes
+    /// past that fail, and the dump with [`DumpError::Truncated`](crate::error::DumpError::Truncated).
+    ///
+    /// The dump timeout doesn't apply: the transaction blocks the only thread that could give up on
+    /// it, and returns only once the service does.
+    CurrentThread,
+    /// Run the transaction on one of the persistent threads of a [`WorkerPool`]
+    Pool(WorkerPool),
+}
+
+/// A fixed set of threads running dump transactions, shareable between [`Dumpsys`](crate::Dumpsys) handles
+///
+/// Avoids creating a thread per dump in high-frequency polling and keeps the thread count deterministic.
+/// Dumps queue up while every worker is busy. A worker stuck on a service that hasn't returned past
+/// the dump timeout is replaced if a new one can be spawned, and then exits once the service returns,
+/// so hung services don't starve the pool. The threads exit once every clone of the pool is dropped.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{Dumpsys, Execution, WorkerPool};
+///
+/// # fn foo() -> Option<()> {
+/// let pool = WorkerPool::new(2);
+/// let surfaceflinger = Dumpsys::builder("SurfaceFlinger")
+///     .execution(Execution::Pool(pool.clone()))
+///     .build()?;
+/// let gfxinfo = Dumpsys::builder("gfxinfo")
+///     .execution(Execution::Pool(pool))
+///     .build()?;
+/// # Some(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WorkerPool {
+    jobs: mpsc::Sender<Job>,
+    workers: Arc<Workers>,
+    threads: usize,
+}
+
+/// What the workers of a pool share, to spawn replacements from
+struct Workers {
+    queue: Mutex<mpsc::Receiver<Job>>,
+    options: ThreadOptions,
+    /// Workers spawned so far, numbering the next one
+    spawned: AtomicUsize,
+}
+
+impl Workers {
+    /// Spawn a worker, sending whether it could be scheduled to `started`.
+    fn spawn(self: &Arc<Self>, started: mpsc::Sender<io::Result<()>>) -> io::Result<()> {
+        let i = self.spawned.fetch_add(1, Ordering::Relaxed);
+        let builder = match self.options.thread_name() {
+            Some(name) => thread::Builder::new().name(format!("{name}-{i}")),
+            None => thread::Builder::new(),
+        };
+        let workers = self.clone();
+        builder.spawn(move || {
+            let result = workers.options.apply();
+            let failed = result.is_err();
+            let _ = started.send(result);
+            drop(started);
+            if failed {
+                return;
+            }
+            loop {
+                let job = workers.queue.lock().unwrap().recv();
+                match job {
+                    Ok(job) => {
+                        if job().is_break() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        })?;
+        Ok(())
+    }
+}
+
+impl WorkerPool {
+    /// Start `threads` workers, at least one.
+    pub fn new(threads: usize) -> Self {
+        Self::with_options(threads, ThreadOptions::new().name("dumpsys-worker"))
+            .expect("failed to spawn dumpsys worker")
+    }
+
+    /// Start `threads` workers, at least one, named after `options` with their index appended and
+    /// scheduled as it says.
+    ///
+    /// Fails if a worker couldn't be spawned or scheduled.
+    pub fn with_options(threads: usize, options: ThreadOptions) -> io::Result<Self> {
+        let threads = threads.max(1);
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let workers = Arc::new(Workers {
+            queue: Mutex::new(queue),
+            options,
+            spawned: AtomicUsize::new(0),
+        });
+        let (started, scheduled) = mpsc::channel();
+
+        for _ in 0..threads {
+            workers.spawn(started.clone())?;
+        }
+        drop(started);
+        // A failed worker exits, and the rest once `jobs` is dropped.
+        for result in scheduled {
+            result?;
+        }
+
+        Ok(Self {
+            jobs,
+            workers,
+            threads,
+        })
+    }
+
+    /// Number of workers taking dumps, not counting those left stuck on a hung service.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Run `f` on a worker, which `watch` replaces with a new one if `f` hangs.
+    ///
+    /// The stuck worker exits once `f` returns only if its replacement was scheduled, so the pool
+    /// neither shrinks when a replacement fails nor grows when `f` returns while one is spawned.
+    pub(crate) fn submit(
+        &self,
+        f: impl FnOnce() -> Result<(), DumpError> + Send + 'static,
+        watch: Watch,
+    ) -> Transaction {
+        let replacement = Arc::new(Mutex::new(Replacement::default()));
+        let workers = Arc::downgrade(&self.workers);
+        let replacing = replacement.clone();
+        watch.on_hung(move || {
+            let mut replacement = replacing.lock().unwrap();
+            if let (false, Some(workers)) = (replacement.returned, workers.upgrade()) {
+                let (started, scheduled) = mpsc::channel();
+                replacement.spawned =
+                    workers.spawn(started).is_ok() && matches!(scheduled.recv(), Ok(Ok(())));
+            }
+        });
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move || {
+            let _ = tx.send(f());
+            watch.finish();
+            let mut replacement = replacement.lock().unwrap();
+            replacement.returned = true;
+            if replacement.spawned {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        self.jobs
+            .send(job)
+            .expect("dumpsys workers exited while the pool is alive");
+        Transaction::Worker(rx)
+    }
+}
+
+/// Whether a worker stuck on a hung dump was replaced, or returned before it could be
+#[derive(Default)]
+struct Replacement {
+    returned: bool,
+    spawned: bool,
+}
+
+impl fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("threads", &self.threads)
+            .finish()
+    }
+}
+
+/// A dump transaction still running off the reading thread
+pub(crate) enum Transaction {
+    Thread(JoinHandle<Result<(), DumpError>>),
+    Worker(mpsc::Receiver<Result<(), DumpError>>),
+}
+
+impl Transaction {
+    /// Block until the transaction returns, failing with [`DumpError::Io`] if its thread panicked.
+    pub(crate) fn wait(self) -> Result<(), DumpError> {
+        let result = match self {
+            Self::Thread(handle) => handle.join().ok(),
+            Self::Worker(result) => result.recv().ok(),
+        };
+        result.unwrap_or_else(|| Err(panicked()))
+    }
+
+    /// Like [`wait`](Self::wait), but giving up at `deadline`: `None` if the transaction is still
+    /// running then, which leaves it to the watchdog.
+    pub(crate) fn wait_until(self, deadline: Option<Instant>) -> Option<Result<(), DumpError>> {
+        let Some(deadline) = deadline else {
+            return Some(self.wait());
+        };
+        match self {
+            Self::Thread(handle) => {
+                while !handle.is_finished() {
+                    let remaining = deadline.checked_duration_since(Instant::now())?;
+                    thread::sleep(remaining.min(JOIN_POLL_INTERVAL));
+                }
+                Some(Self::Thread(handle).wait())
+            }
+            Self::Worker(result) => {
+                match result.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(result) => Some(result),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => Some(Err(panicked())),
+                }
+            }
+        }
+    }
+}
+
+fn panicked() -> DumpError {
+    io::Error::other("dump transaction panicked").into()
+}
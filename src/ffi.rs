@@ -0,0 +1,327 @@
+//! A C ABI, for C/C++ daemons and JNI shims that would otherwise fork `dumpsys`
+//!
+//! `include/dumpsys.h` declares the functions below. Build the library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`, or `staticlib`.
+//!
+//! Functions returning `int` return [`DUMPSYS_OK`] or one of the negative `DUMPSYS_ERR_*` codes, and
+//! [`dumpsys_last_error`] describes the last failure on the calling thread. Strings passed in must be
+//! NUL-terminated UTF-8. Output is allocated with `malloc`, NUL-terminated, and released with
+//! [`dumpsys_string_free`] or `free`. A handle may be used from several threads at once.
+//!
+//! ```c
+//! dumpsys_t *sf = dumpsys_new("SurfaceFlinger", 1000);
+//! const char *args[] = {"--latency"};
+//! char *output;
+//! size_t len;
+//! if (sf && dumpsys_dump(sf, args, 1, 500, &output, &len) == DUMPSYS_OK) {
+//!     fwrite(output, 1, len, stdout);
+//!     dumpsys_string_free(output);
+//! } else {
+//!     fprintf(stderr, "%s\n", dumpsys_last_error());
+//! }
+//! dumpsys_free(sf);
+//! ```
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    time::Duration,
+};
+
+use crate::{builder::Config, error::DumpError, service_manager, Dumpsys};
+
+pub const DUMPSYS_OK: c_int = 0;
+/// A null pointer, or a string that isn't UTF-8
+pub const DUMPSYS_ERR_INVALID_ARGUMENT: c_int = -1;
+pub const DUMPSYS_ERR_NOT_FOUND: c_int = -2;
+pub const DUMPSYS_ERR_PERMISSION_DENIED: c_int = -3;
+pub const DUMPSYS_ERR_DEAD_OBJECT: c_int = -4;
+/// The dump transaction failed with another binder status
+pub const DUMPSYS_ERR_STATUS: c_int = -5;
+pub const DUMPSYS_ERR_IO: c_int = -6;
+/// The output read until the timeout is still returned
+pub const DUMPSYS_ERR_TIMEOUT: c_int = -7;
+/// The output read until the limit is still returned
+pub const DUMPSYS_ERR_TRUNCATED: c_int = -8;
+pub const DUMPSYS_ERR_CANCELLED: c_int = -9;
+pub const DUMPSYS_ERR_PARSE: c_int = -10;
+/// A bug in this crate, caught before unwinding into C
+pub const DUMPSYS_ERR_PANIC: c_int = -11;
+/// `malloc` failed
+pub const DUMPSYS_ERR_NO_MEMORY: c_int = -12;
+/// The service stopped mid-dump, the output written until then is still returned
+pub const DUMPSYS_ERR_INCOMPLETE: c_int = -13;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A connected service, `dumpsys_t` in C
+pub struct DumpsysHandle {
+    dumpsys: Dumpsys,
+}
+
+/// Look `service` up, waiting up to `connect_timeout_ms` for it to appear. Returns null if it
+/// doesn't.
+///
+/// # Safety
+///
+/// `service` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dumpsys_new(
+    service: *const c_char,
+    connect_timeout_ms: u32,
+) -> *mut DumpsysHandle {
+    let mut handle = ptr::null_mut();
+    guard(|| {
+        // SAFETY: guaranteed by the caller.
+        let service = unsafe { str_arg(service, "service") }?;
+        let dumpsys = Dumpsys::builder(service)
+            .connect_timeout(Duration::from_millis(connect_timeout_ms.into()))
+            .build()
+            .ok_or_else(|| {
+                (
+                    DUMPSYS_ERR_NOT_FOUND,
+                    format!("service `{service}` not found"),
+                )
+            })?;
+        handle = Box::into_raw(Box::new(DumpsysHandle { dumpsys }));
+        Ok(())
+    });
+    handle
+}
+
+/// Dump the service of `handle` with `nargs` arguments, giving up after `timeout_ms` unless it's 0.
+///
+/// On success, and on [`DUMPSYS_ERR_IO`], [`DUMPSYS_ERR_TIMEOUT`], [`DUMPSYS_ERR_TRUNCATED`],
+/// [`DUMPSYS_ERR_INCOMPLETE`] and [`DUMPSYS_ERR_CANCELLED`] with the output read until then,
+/// `*output` points to the output and `*output_len` holds its length, which excludes the final NUL.
+/// Otherwise `*output` is null and `*output_len` 0.
+///
+/// # Safety
+///
+/// `handle` must come from [`dumpsys_new`], `args` must point to `nargs` NUL-terminated strings
+/// unless `nargs` is 0, and `output` and `output_len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn dumpsys_dump(
+    handle: *const DumpsysHandle,
+    args: *const *const c_char,
+    nargs: usize,
+    timeout_ms: u32,
+    output: *mut *mut c_char,
+    output_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        // SAFETY: the caller guarantees both are valid for writes unless null.
+        unsafe { clear_output(output, output_len) };
+        if output.is_null() || output_len.is_null() {
+            return Err(invalid("output is null"));
+        }
+        // SAFETY: the caller guarantees a handle from `dumpsys_new` that wasn't freed yet.
+        let handle = unsafe { handle.as_ref() }.ok_or_else(|| invalid("handle is null"))?;
+        if nargs > 0 && args.is_null() {
+            return Err(invalid("args is null"));
+        }
+        let args = if nargs == 0 {
+            Vec::new()
+        } else {
+            // SAFETY: the caller guarantees `nargs` string pointers at `args`.
+            unsafe { slice::from_raw_parts(args, nargs) }
+                .iter()
+                // SAFETY: as above.
+                .map(|&arg| unsafe { str_arg(arg, "argument") })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let dumpsys = match timeout_ms {
+            0 => None,
+            ms => Some(handle.dumpsys.with_config(Config {
+                dump_timeout: Some(Duration::from_millis(ms.into())),
+                ..(*handle.dumpsys.config).clone()
+            })),
+        };
+        let dumpsys = dumpsys.as_ref().unwrap_or(&handle.dumpsys);
+        let (result, bytes) = match dumpsys.dump_to_vec(&args) {
+            Ok(bytes) => (Ok(()), bytes),
+            Err(err) => {
+                let failure = error(&err);
+                match err {
+                    DumpError::Io { partial, .. }
+                    | DumpError::Timeout { partial, .. }
+                    | DumpError::Truncated { partial, .. }
+                    | DumpError::Incomplete { partial, .. }
+                    | DumpError::Cancelled { partial, .. } => (Err(failure), partial),
+                    _ => return Err(failure),
+                }
+            }
+        };
+        let len = bytes.len();
+        // SAFETY: checked for null above.
+        unsafe {
+            *output = c_string(&bytes)?;
+            *output_len = len;
+        }
+        result
+    })
+}
+
+/// The registered services, one per line, in `*output` as with [`dumpsys_dump`]. On failure `*output`
+/// is null and `*output_len` 0.
+///
+/// # Safety
+///
+/// `output` and `output_len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn dumpsys_list_services(
+    output: *mut *mut c_char,
+    output_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        // SAFETY: the caller guarantees both are valid for writes unless null.
+        unsafe { clear_output(output, output_len) };
+        if output.is_null() || output_len.is_null() {
+            return Err(invalid("output is null"));
+        }
+        let services = service_manager::list_services().map_err(|err| error(&err))?;
+        let bytes: String = services
+            .iter()
+            .map(|service| format!("{service}\n"))
+            .collect();
+        let len = bytes.len();
+        // SAFETY: checked for null above, the caller guarantees they are valid for writes.
+        unsafe {
+            *output = c_string(bytes.as_bytes())?;
+            *output_len = len;
+        }
+        Ok(())
+    })
+}
+
+/// Release a handle. Null is ignored.
+///
+/// # Safety
+///
+/// `handle` must come from [`dumpsys_new`] and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn dumpsys_free(handle: *mut DumpsysHandle) {
+    if !handle.is_null() {
+        // SAFETY: the caller hands back a pointer from `Box::into_raw` in `dumpsys_new`.
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Release output returned by [`dumpsys_dump`] or [`dumpsys_list_services`]. Null is ignored.
+///
+/// # Safety
+///
+/// `output` must be returned by this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn dumpsys_string_free(output: *mut c_char) {
+    // SAFETY: output is allocated with malloc.
+    unsafe { libc::free(output.cast()) }
+}
+
+/// The last failure on the calling thread, or null. Valid until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn dumpsys_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Leave no output behind for the caller to free on failure, writing what isn't null.
+///
+/// # Safety
+///
+/// `output` and `output_len` must each be null or valid for writes.
+unsafe fn clear_output(output: *mut *mut c_char, output_len: *mut usize) {
+    // SAFETY: the caller guarantees both are valid for writes unless null.
+    unsafe {
+        if let Some(output) = output.as_mut() {
+            *output = ptr::null_mut();
+        }
+        if let Some(output_len) = output_len.as_mut() {
+            *output_len = 0;
+        }
+    }
+}
+
+type Failure = (c_int, String);
+
+/// Run `f`, recording its failure, and turn its result or panic into an error code.
+fn guard(f: impl FnOnce() -> Result<(), Failure>) -> c_int {
+    let (code, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return DUMPSYS_OK,
+        Ok(Err(failure)) => failure,
+        Err(_) => (DUMPSYS_ERR_PANIC, "dumpsys-rs panicked".to_owned()),
+    };
+    let message = CString::new(message.replace('\0', " ")).expect("NUL bytes replaced");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+fn invalid(message: &str) -> Failure {
+    (DUMPSYS_ERR_INVALID_ARGUMENT, message.to_owned())
+}
+
+fn error(err: &DumpError) -> Failure {
+    let code = match err {
+        DumpError::ServiceNotFound { .. } => DUMPSYS_ERR_NOT_FOUND,
+        DumpError::PermissionDenied { .. } => DUMPSYS_ERR_PERMISSION_DENIED,
+        DumpError::DeadObject { .. } => DUMPSYS_ERR_DEAD_OBJECT,
+        DumpError::Status { .. } => DUMPSYS_ERR_STATUS,
+        DumpError::Io { .. } => DUMPSYS_ERR_IO,
+        DumpError::Timeout { .. } => DUMPSYS_ERR_TIMEOUT,
+        DumpError::Truncated { .. } => DUMPSYS_ERR_TRUNCATED,
+        DumpError::Incomplete { .. } => DUMPSYS_ERR_INCOMPLETE,
+        DumpError::Cancelled { .. } => DUMPSYS_ERR_CANCELLED,
+        DumpError::Parse { .. } => DUMPSYS_ERR_PARSE,
+    };
+    (code, err.to_string())
+}
+
+/// # Safety
+///
+/// `arg` must be null or a NUL-terminated string.
+unsafe fn str_arg<'a>(arg: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if arg.is_null() {
+        return Err(invalid(&format!("{name} is null")));
+    }
+    // SAFETY: guaranteed by the caller.
+    unsafe { CStr::from_ptr(arg) }
+        .to_str()
+        .map_err(|_| invalid(&format!("{name} isn't UTF-8")))
+}
+
+/// Copy `bytes` to a NUL-terminated `malloc` allocation.
+fn c_string(bytes: &[u8]) -> Result<*mut c_char, Failure> {
+    // SAFETY: malloc may be called with any size.
+    let output = unsafe { libc::malloc(bytes.len() + 1) }.cast::<u8>();
+    if output.is_null() {
+        return Err((DUMPSYS_ERR_NO_MEMORY, "out of memory".to_owned()));
+    }
+    // SAFETY: `output` holds `bytes.len() + 1` bytes and doesn't overlap `bytes`.
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), output, bytes.len());
+        *output.add(bytes.len()) = 0;
+    }
+    Ok(output.cast())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_services_leaves_no_output_on_failure() {
+        let mut output = ptr::NonNull::dangling().as_ptr();
+        // SAFETY: `output` is valid for writes and the length is null.
+        let code = unsafe { dumpsys_list_services(&mut output, ptr::null_mut()) };
+        assert_eq!(code, DUMPSYS_ERR_INVALID_ARGUMENT);
+        assert!(output.is_null());
+    }
+}
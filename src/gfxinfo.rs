@@ -0,0 +1,380 @@
+//! Typed output of `dumpsys gfxinfo <package> framestats`
+//!
+//! Besides the summary of the app's rendering, `framestats` prints the timestamps of its last 120 frames
+//! as CSV between `---PROFILEDATA---` lines, which [`Gfxinfo`] turns into the duration of each stage of
+//! the frame.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::gfxinfo::Gfxinfo;
+//!
+//! let gfxinfo = Gfxinfo::parse(
+//!     "Total frames rendered: 120
+//! Janky frames: 6 (5.00%)
+//! 90th percentile: 12ms
+//! ---PROFILEDATA---
+//! Flags,IntendedVsync,Vsync,HandleInputStart,AnimationStart,PerformTraversalsStart,DrawStart,SyncQueued,SyncStart,IssueDrawCommandsStart,SwapBuffers,FrameCompleted,GpuCompleted,
+//! 0,1000000,1000000,1100000,1200000,1500000,2000000,4000000,4100000,4500000,8000000,9000000,10000000,
+//! 1,1000000,1000000,0,0,0,0,0,0,0,0,0,0,
+//! ---PROFILEDATA---",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(gfxinfo.janky_frames, 6);
+//! assert_eq!(gfxinfo.p90, Some(Duration::from_millis(12)));
+//! let frame = gfxinfo.frames().next().unwrap();
+//! assert_eq!(frame.draw, Duration::from_millis(2));
+//! assert_eq!(frame.gpu, Some(Duration::from_micros(5500)));
+//! assert_eq!(frame.total, Duration::from_millis(8));
+//! ```
+
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{first_word, invalid},
+    typed, DumpParse,
+};
+
+const PROFILE_DATA: &str = "---PROFILEDATA---";
+const FRAMESTATS_ARG: &str = "framestats";
+const RESET_ARG: &str = "reset";
+
+/// Time spent in each stage of one frame, from its `framestats` timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameStats {
+    /// Non-zero for frames that aren't representative, e.g. the first one of a window
+    pub flags: u64,
+    /// Vsync the frame was scheduled for, in nanoseconds of `CLOCK_MONOTONIC`
+    pub intended_vsync: u64,
+    /// Handling input events
+    pub input: Duration,
+    /// Running animators and callbacks
+    pub animation: Duration,
+    /// Measure and layout
+    pub traversal: Duration,
+    /// Recording the display lists
+    pub draw: Duration,
+    /// Uploading bitmaps to the GPU
+    pub sync: Duration,
+    /// Issuing the draw commands on the render thread
+    pub command_issue: Duration,
+    /// Until the GPU finished, on releases that report it
+    pub gpu: Option<Duration>,
+    /// From the intended vsync until the frame was completed
+    pub total: Duration,
+}
+
+impl FrameStats {
+    /// Whether the frame counts towards frame time statistics.
+    pub fn is_valid(&self) -> bool {
+        self.flags == 0
+    }
+}
+
+/// Output of `dumpsys gfxinfo <package> framestats`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gfxinfo {
+    pub total_frames: u64,
+    pub janky_frames: u64,
+    pub p50: Option<Duration>,
+    pub p90: Option<Duration>,
+    pub p95: Option<Duration>,
+    pub p99: Option<Duration>,
+    /// Every frame of every window of the app, including invalid ones
+    pub all_frames: Vec<FrameStats>,
+}
+
+impl Gfxinfo {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut gfxinfo = Self::default();
+        let mut lines = text.lines().map(str::trim);
+
+        while let Some(line) = lines.next() {
+            if line == PROFILE_DATA {
+                let block = lines.by_ref().take_while(|line| *line != PROFILE_DATA);
+                profile_data(block, &mut gfxinfo.all_frames)?;
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(": ") else {
+                continue;
+            };
+            let value = first_word(value);
+            match key {
+                "Total frames rendered" => gfxinfo.total_frames = count(key, value)?,
+                "Janky frames" => gfxinfo.janky_frames = count(key, value)?,
+                "50th percentile" => gfxinfo.p50 = Some(millis(key, value)?),
+                "90th percentile" => gfxinfo.p90 = Some(millis(key, value)?),
+                "95th percentile" => gfxinfo.p95 = Some(millis(key, value)?),
+                "99th percentile" => gfxinfo.p99 = Some(millis(key, value)?),
+                _ => {}
+            }
+        }
+
+        Ok(gfxinfo)
+    }
+
+    /// Dump and parse the frame stats of `package`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::gfxinfo::Gfxinfo;
+    ///
+    /// # fn foo() {
+    /// let gfxinfo = Gfxinfo::for_package("com.example.app").unwrap();
+    /// for frame in gfxinfo.frames() {
+    ///     println!("draw {:?} gpu {:?}", frame.draw, frame.gpu);
+    /// }
+    /// # }
+    /// ```
+    pub fn for_package(package: &str) -> Result<Self, DumpError> {
+        typed::dump_service::<Self>([package, FRAMESTATS_ARG])
+    }
+
+    /// Reset the stats of `package`, so the next dump only covers frames rendered after this.
+    pub fn reset(package: &str) -> Result<(), DumpError> {
+        let args = [package.to_owned(), RESET_ARG.to_owned()];
+        typed::connect(Self::SERVICE, &args)?.dump(&args).map(drop)
+    }
+
+    /// Frames that count towards frame time statistics.
+    pub fn frames(&self) -> impl Iterator<Item = &FrameStats> {
+        self.all_frames.iter().filter(|frame| frame.is_valid())
+    }
+}
+
+impl DumpParse for Gfxinfo {
+    const SERVICE: &'static str = "gfxinfo";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Parse the CSV lines of one `---PROFILEDATA---` block, a header followed by a row per frame.
+fn profile_data<'a>(
+    mut lines: impl Iterator<Item = &'a str>,
+    frames: &mut Vec<FrameStats>,
+) -> Result<(), ParseError> {
+    let Some(header) = lines.next() else {
+        return Ok(());
+    };
+    let columns: HashMap<&str, usize> = header
+        .split(',')
+        .enumerate()
+        .map(|(i, column)| (column, i))
+        .collect();
+
+    for line in lines.filter(|line| !line.is_empty()) {
+        let values = line
+            .trim_end_matches(',')
+            .split(',')
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| invalid(PROFILE_DATA, line))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let at = |column: &str| {
+            columns
+                .get(column)
+                .and_then(|&i| values.get(i).copied())
+                .unwrap_or(0)
+        };
+        let stage = |from: &str, to: &str| Duration::from_nanos(at(to).saturating_sub(at(from)));
+
+        let gpu_completed = at("GpuCompleted");
+        frames.push(FrameStats {
+            flags: at("Flags"),
+            intended_vsync: at("IntendedVsync"),
+            input: stage("HandleInputStart", "AnimationStart"),
+            animation: stage("AnimationStart", "PerformTraversalsStart"),
+            traversal: stage("PerformTraversalsStart", "DrawStart"),
+            draw: stage("DrawStart", "SyncQueued"),
+            sync: stage("SyncStart", "IssueDrawCommandsStart"),
+            command_issue: stage("IssueDrawCommandsStart", "SwapBuffers"),
+            gpu: (gpu_completed > 0).then(|| stage("IssueDrawCommandsStart", "GpuCompleted")),
+            total: stage("IntendedVsync", "FrameCompleted"),
+        });
+    }
+    Ok(())
+}
+
+fn count(key: &str, value: &str) -> Result<u64, ParseError> {
+    value.parse().map_err(|_| invalid(key, value))
+}
+
+/// `12ms` of the percentile lines.
+fn millis(key: &str, value: &str) -> Result<Duration, ParseError> {
+    value
+        .strip_suffix("ms")
+        .and_then(|ms| ms.parse().ok())
+        .map(Duration::from_millis)
+        .ok_or_else(|| invalid(key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "Flags,FrameTimelineVsyncId,IntendedVsync,Vsync,InputEventId,HandleInputStart,AnimationStart,PerformTraversalsStart,DrawStart,FrameDeadline,FrameInterval,FrameStartTime,SyncQueued,SyncStart,IssueDrawCommandsStart,SwapBuffers,FrameCompleted,DequeueBufferDuration,QueueBufferDuration,GpuCompleted,SwapBuffersCompleted,DisplayPresentTime,CommandSubmissionCompleted,";
+
+    /// `dumpsys gfxinfo com.android.chrome framestats` of Android 13, cut down to a few frames
+    fn dump() -> String {
+        format!(
+            "Applications Graphics Acceleration Info:
+Uptime: 1133480123 Realtime: 1133480123
+
+** Graphics info for pid 21865 [com.android.chrome] **
+
+Stats since: 1133064963419ns
+Total frames rendered: 2048
+Janky frames: 97 (4.74%)
+Janky frames (legacy): 312 (15.23%)
+50th percentile: 7ms
+90th percentile: 13ms
+95th percentile: 19ms
+99th percentile: 42ms
+Number Missed Vsync: 21
+Number High input latency: 4
+Number Slow UI thread: 38
+HISTOGRAM: 5ms=1008 6ms=241 7ms=187 8ms=102
+50th gpu percentile: 4ms
+90th gpu percentile: 7ms
+Pipeline=Skia (Vulkan)
+Profile data in ms:
+
+\tcom.android.chrome/org.chromium.chrome.browser.ChromeTabbedActivity/android.view.ViewRootImpl@5e2a1f3 (visibility=0)
+---PROFILEDATA---
+{HEADER}
+0,1738275,1133300000000,1133300000000,0,1133301000000,1133301200000,1133301500000,1133302500000,1133316666666,16666666,1133300100000,1133305000000,1133305100000,1133305400000,1133309000000,1133310500000,120000,80000,1133312000000,1133310400000,0,1133309100000,
+1,1738280,1133316666666,1133316666666,0,0,0,0,0,1133333333332,16666666,0,0,0,0,0,0,0,0,0,0,0,0,
+---PROFILEDATA---
+
+\tcom.android.chrome/android.widget.PopupWindow$PopupDecorView@1c2b3a4 (visibility=0)
+---PROFILEDATA---
+{HEADER}
+0,1738301,1133333333332,1133333333332,0,1133334000000,1133334000000,1133334000000,1133334500000,1133349999998,16666666,1133333400000,1133336000000,1133336000000,1133336000000,1133338000000,1133340000000,100000,60000,0,1133339900000,0,1133338500000,
+---PROFILEDATA---
+
+View hierarchy:
+
+  com.android.chrome/org.chromium.chrome.browser.ChromeTabbedActivity/android.view.ViewRootImpl@5e2a1f3
+  412 views, 389.61 kB of render nodes
+
+Total ViewRootImpl   : 2
+Total attached Views : 431
+"
+        )
+    }
+
+    #[test]
+    fn parses_the_summary() {
+        let gfxinfo = Gfxinfo::parse(&dump()).unwrap();
+        assert_eq!(gfxinfo.total_frames, 2048);
+        assert_eq!(gfxinfo.janky_frames, 97);
+        assert_eq!(gfxinfo.p50, Some(Duration::from_millis(7)));
+        assert_eq!(gfxinfo.p90, Some(Duration::from_millis(13)));
+        assert_eq!(gfxinfo.p95, Some(Duration::from_millis(19)));
+        assert_eq!(gfxinfo.p99, Some(Duration::from_millis(42)));
+    }
+
+    #[test]
+    fn parses_the_frames_of_every_window() {
+        let gfxinfo = Gfxinfo::parse(&dump()).unwrap();
+        assert_eq!(gfxinfo.all_frames.len(), 3);
+        assert_eq!(gfxinfo.frames().count(), 2);
+        assert_eq!(gfxinfo.all_frames[1].flags, 1);
+        assert_eq!(gfxinfo.all_frames[1].total, Duration::ZERO);
+
+        let from_micros = Duration::from_micros;
+        assert_eq!(
+            gfxinfo.all_frames[0],
+            FrameStats {
+                flags: 0,
+                intended_vsync: 1_133_300_000_000,
+                input: from_micros(200),
+                animation: from_micros(300),
+                traversal: from_micros(1000),
+                draw: from_micros(2500),
+                sync: from_micros(300),
+                command_issue: from_micros(3600),
+                gpu: Some(from_micros(6600)),
+                total: from_micros(10_500),
+            }
+        );
+        // The popup's frame didn't report GPU completion
+        let popup = gfxinfo.frames().nth(1).unwrap();
+        assert_eq!(popup.gpu, None);
+        assert_eq!(popup.input, Duration::ZERO);
+        assert_eq!(popup.total, Duration::from_nanos(6_666_668));
+    }
+
+    #[test]
+    fn frames_before_gpu_completion_was_recorded() {
+        let text = "---PROFILEDATA---
+Flags,IntendedVsync,Vsync,OldestInputEvent,NewestInputEvent,HandleInputStart,AnimationStart,PerformTraversalsStart,DrawStart,SyncQueued,SyncStart,IssueDrawCommandsStart,SwapBuffers,FrameCompleted,
+0,5000000,5000000,0,0,5100000,5200000,5300000,5400000,6000000,6100000,6200000,7000000,8000000,
+---PROFILEDATA---";
+        let frame = Gfxinfo::parse(text).unwrap().all_frames[0];
+        assert_eq!(frame.gpu, None);
+        assert_eq!(frame.draw, Duration::from_micros(600));
+        assert_eq!(frame.total, Duration::from_millis(3));
+    }
+
+    #[test]
+    fn truncated_profile_data() {
+        // Columns cut off are zero, a dump cut off before the closing line keeps its frames
+        let text = format!(
+            "---PROFILEDATA---\n{HEADER}\n0,1738275,1133300000000,1133300000000,0,1133301000000"
+        );
+        let gfxinfo = Gfxinfo::parse(&text).unwrap();
+        assert_eq!(gfxinfo.all_frames.len(), 1);
+        assert_eq!(gfxinfo.all_frames[0].input, Duration::ZERO);
+        assert_eq!(gfxinfo.all_frames[0].gpu, None);
+
+        let line = "0,1738275,11333000000-";
+        let text = format!("---PROFILEDATA---\n{HEADER}\n{line}\n---PROFILEDATA---");
+        assert_eq!(
+            Gfxinfo::parse(&text).unwrap_err(),
+            invalid(PROFILE_DATA, line)
+        );
+    }
+
+    #[test]
+    fn invalid_summary_values() {
+        assert_eq!(
+            Gfxinfo::parse("90th percentile: 13").unwrap_err(),
+            invalid("90th percentile", "13")
+        );
+        assert_eq!(
+            Gfxinfo::parse("Janky frames: many (4.74%)").unwrap_err(),
+            invalid("Janky frames", "many")
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let dump = dump();
+        assert_eq!(
+            Gfxinfo::parse(&dump.replace('\n', "\r\n")).unwrap(),
+            Gfxinfo::parse(&dump).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(Gfxinfo::parse("").unwrap(), Gfxinfo::default());
+        assert_eq!(
+            Gfxinfo::parse("---PROFILEDATA---\n---PROFILEDATA---").unwrap(),
+            Gfxinfo::default()
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! Typed output of `dumpsys gpu --gpumem` and of the graphic buffer list of `dumpsys SurfaceFlinger`
+//!
+//! The GPU service attributes memory to processes on devices whose kernel exports the
+//! `gpu_mem_total` tracepoint. SurfaceFlinger lists every graphic buffer it allocated with the name
+//! of the layer that asked for it, which covers the buffers games render into on other devices.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::gpu::{GpuState, GraphicBuffers};
+//!
+//! let gpu = GpuState::parse(
+//!     "Memory snapshot for GPU 0:
+//! Global total: 463716352
+//! Proc 561 total: 25882624
+//! Proc 4321 total: 300421632",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(gpu.memory[0].global_total, 463716352);
+//! assert_eq!(gpu.memory_of(4321), Some(300421632));
+//! assert_eq!(gpu.top(1)[0].pid, 4321);
+//!
+//! let buffers = GraphicBuffers::parse(
+//!     "GraphicBufferAllocator buffers:
+//! 0x7b0c8e2b40: 8100.00 KiB | 1080 (1088) x 1920 |    1 |        1 | 0x1b00 | FramebufferSurface
+//! 0x7b0c8e2c80: 8160.00 KiB | 1080 (1088) x 2400 |    1 |        1 | 0x933 | SurfaceView[com.example.game/com.example.game.MainActivity]#0(BLAST Consumer)0
+//! 0x7b0c8e2d20: 8160.00 KiB | 1080 (1088) x 2400 |    1 |        1 | 0x933 | SurfaceView[com.example.game/com.example.game.MainActivity]#0(BLAST Consumer)0
+//! Total allocated by GraphicBufferAllocator (estimate): 24420.00 KB
+//! Imported gralloc buffers:",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(buffers.buffers.len(), 3);
+//! let game = &buffers.buffers[1];
+//! assert_eq!((game.width, game.stride, game.height), (1080, 1088, 2400));
+//! assert_eq!(game.usage, 0x933);
+//! assert_eq!(buffers.total_bytes, Some(24420 * 1024));
+//! assert_eq!(buffers.bytes_of("com.example.game"), 2 * 8160 * 1024);
+//! ```
+
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::invalid,
+    typed, DumpParse,
+};
+
+const GPUMEM_ARG: &str = "--gpumem";
+const SNAPSHOT: &str = "Memory snapshot for GPU ";
+const GLOBAL_TOTAL: &str = "Global total: ";
+const PROC: &str = "Proc ";
+const ALLOCATOR: &str = "GraphicBufferAllocator buffers:";
+const ALLOCATOR_TOTAL: &str = "Total allocated by GraphicBufferAllocator (estimate): ";
+
+/// GPU memory a process holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessGpuMemory {
+    pub pid: u32,
+    pub bytes: u64,
+}
+
+/// Memory of one GPU
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GpuMemory {
+    pub gpu_id: u32,
+    /// Including memory the kernel driver holds for no process
+    pub global_total: u64,
+    pub processes: Vec<ProcessGpuMemory>,
+}
+
+/// Output of `dumpsys gpu --gpumem`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GpuState {
+    /// Empty when the kernel doesn't export GPU memory
+    pub memory: Vec<GpuMemory>,
+}
+
+impl GpuState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut state = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(id) = line.strip_prefix(SNAPSHOT) {
+                let id = id.trim_end_matches(':');
+                state.memory.push(GpuMemory {
+                    gpu_id: id.parse().map_err(|_| invalid("GPU", id))?,
+                    ..GpuMemory::default()
+                });
+                continue;
+            }
+            let Some(gpu) = state.memory.last_mut() else {
+                continue;
+            };
+            if let Some(total) = line.strip_prefix(GLOBAL_TOTAL) {
+                gpu.global_total = total.parse().map_err(|_| invalid("Global total", total))?;
+            } else if let Some((pid, bytes)) = line
+                .strip_prefix(PROC)
+                .and_then(|line| line.split_once(" total: "))
+            {
+                gpu.processes.push(ProcessGpuMemory {
+                    pid: pid.parse().map_err(|_| invalid("Proc", pid))?,
+                    bytes: bytes.parse().map_err(|_| invalid("total", bytes))?,
+                });
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// GPU memory of `pid` over every GPU, `None` when the process holds none.
+    pub fn memory_of(&self, pid: u32) -> Option<u64> {
+        self.memory
+            .iter()
+            .flat_map(|gpu| &gpu.processes)
+            .filter(|process| process.pid == pid)
+            .map(|process| process.bytes)
+            .reduce(|total, bytes| total + bytes)
+    }
+
+    /// The `n` processes holding the most GPU memory over every GPU, largest first.
+    pub fn top(&self, n: usize) -> Vec<ProcessGpuMemory> {
+        let mut totals = BTreeMap::<u32, u64>::new();
+        for process in self.memory.iter().flat_map(|gpu| &gpu.processes) {
+            *totals.entry(process.pid).or_default() += process.bytes;
+        }
+        let mut processes = totals
+            .into_iter()
+            .map(|(pid, bytes)| ProcessGpuMemory { pid, bytes })
+            .collect::<Vec<_>>();
+        processes.sort_by_key(|process| Reverse(process.bytes));
+        processes.truncate(n);
+        processes
+    }
+}
+
+impl DumpParse for GpuState {
+    const SERVICE: &'static str = "gpu";
+    const ARGS: &'static [&'static str] = &[GPUMEM_ARG];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// A buffer SurfaceFlinger allocated
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphicBuffer {
+    pub handle: String,
+    /// `None` when the allocator doesn't know
+    pub size_bytes: Option<u64>,
+    pub width: u32,
+    /// Row length in pixels
+    pub stride: u32,
+    pub height: u32,
+    pub layers: u32,
+    /// `PixelFormat`
+    pub format: u32,
+    /// `GRALLOC_USAGE_*` bits
+    pub usage: u64,
+    /// Name of the layer or consumer that asked for the buffer
+    pub requestor: String,
+}
+
+/// The graphic buffer list of `dumpsys SurfaceFlinger`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphicBuffers {
+    pub buffers: Vec<GraphicBuffer>,
+    /// Estimate SurfaceFlinger prints, rounded to the kilobyte
+    pub total_bytes: Option<u64>,
+}
+
+impl GraphicBuffers {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut buffers = Self::default();
+        let mut in_buffers = false;
+
+        for line in text.lines().map(str::trim) {
+            if line == ALLOCATOR {
+                in_buffers = true;
+            } else if let Some(total) = line.strip_prefix(ALLOCATOR_TOTAL) {
+                buffers.total_bytes = kilobytes(total.trim_end_matches(" KB"));
+                in_buffers = false;
+            } else if in_buffers && !line.is_empty() {
+                if let Some(buffer) = graphic_buffer(line)? {
+                    buffers.buffers.push(buffer);
+                }
+            }
+        }
+
+        Ok(buffers)
+    }
+
+    /// Bytes of the buffers each requestor asked for.
+    pub fn by_requestor(&self) -> BTreeMap<&str, u64> {
+        let mut sizes = BTreeMap::new();
+        for buffer in &self.buffers {
+            *sizes.entry(buffer.requestor.as_str()).or_default() +=
+                buffer.size_bytes.unwrap_or_default();
+        }
+        sizes
+    }
+
+    /// Bytes of the buffers of layers whose name contains `package`.
+    pub fn bytes_of(&self, package: &str) -> u64 {
+        self.buffers
+            .iter()
+            .filter(|buffer| buffer.requestor.contains(package))
+            .filter_map(|buffer| buffer.size_bytes)
+            .sum()
+    }
+}
+
+impl DumpParse for GraphicBuffers {
+    const SERVICE: &'static str = "SurfaceFlinger";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `gpu --gpumem` and return the GPU memory of `pid`, `None` when the process holds none or the
+/// kernel doesn't export GPU memory.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// let bytes = dumpsys_rs::gpu::memory_of(4321).unwrap().unwrap_or_default();
+/// println!("{} MiB of GPU memory", bytes >> 20);
+/// # }
+/// ```
+pub fn memory_of(pid: u32) -> Result<Option<u64>, DumpError> {
+    typed::dump_service::<GpuState>([GPUMEM_ARG]).map(|state| state.memory_of(pid))
+}
+
+/// `8100.00`, in KiB
+fn kilobytes(text: &str) -> Option<u64> {
+    let kilobytes = text.trim().parse::<f64>().ok()?;
+    Some((kilobytes * 1024.0).round() as u64)
+}
+
+/// `0x7b0c8e2b40: 8100.00 KiB | 1080 (1088) x 1920 |    1 |        1 | 0x1b00 | FramebufferSurface`
+fn graphic_buffer(line: &str) -> Result<Option<GraphicBuffer>, ParseError> {
+    let mut columns = line.splitn(6, " | ").map(str::trim);
+    let (Some(head), Some(size), Some(layers), Some(format), Some(usage), Some(requestor)) = (
+        columns.next(),
+        columns.next(),
+        columns.next(),
+        columns.next(),
+        columns.next(),
+        columns.next(),
+    ) else {
+        return Ok(None);
+    };
+    let Some((handle, bytes)) = head.split_once(": ") else {
+        return Ok(None);
+    };
+
+    // `1080 (1088) x 1920`
+    let dimensions = size.replace(['(', ')'], "");
+    let mut dimensions = dimensions.split_whitespace().filter(|word| *word != "x");
+    let mut dimension = || {
+        dimensions
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| invalid("size", size))
+    };
+    let width = dimension()?;
+    let stride = dimension()?;
+    let height = dimension()?;
+    let usage = usage.trim_start_matches("0x").trim();
+    Ok(Some(GraphicBuffer {
+        handle: handle.to_owned(),
+        size_bytes: bytes.strip_suffix(" KiB").and_then(kilobytes),
+        width,
+        stride,
+        height,
+        layers: layers.parse().map_err(|_| invalid("layers", layers))?,
+        format: u32::from_str_radix(format, 16).map_err(|_| invalid("format", format))?,
+        usage: u64::from_str_radix(usage, 16).map_err(|_| invalid("usage", usage))?,
+        requestor: requestor.to_owned(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys gpu --gpumem` of Android 14, with a second GPU added
+    const GPUMEM: &str = "Memory snapshot for GPU 0:
+Global total: 463716352
+Proc 561 total: 25882624
+Proc 1203 total: 41943040
+Proc 4321 total: 300421632
+Memory snapshot for GPU 1:
+Global total: 67108864
+Proc 4321 total: 16777216
+";
+
+    /// `dumpsys SurfaceFlinger` of Android 14, cut down to the graphic buffer list
+    const BUFFERS: &str = "Display 4619827259835644672 (active) HWC layers:
+GraphicBufferAllocator buffers:
+    Handle |        Size |     W (Stride) x H | Layers |   Format |      Usage | Requestor
+0xb400007a8e2b40: 8100.00 KiB | 1080 (1088) x 1920 |    1 |        1 | 0x1b00 | FramebufferSurface
+0xb400007a8e2c80: 8160.00 KiB | 1080 (1088) x 2400 |    1 |        1 | 0x933 | SurfaceView[com.example.game/com.example.game.MainActivity]#0(BLAST Consumer)0
+0xb400007a8e2d20: 8160.00 KiB | 1080 (1088) x 2400 |    1 |        1 | 0x933 | SurfaceView[com.example.game/com.example.game.MainActivity]#0(BLAST Consumer)0
+0xb400007a8e2e60:  126.56 KiB |   90 (  96) x  360 |    1 |       22 | 0x20000900 | com.android.systemui.ScreenDecorHwcLayer#0(BLAST Consumer)0
+0xb400007a8e2f00: unknown     |    0 (   0) x    0 |    1 |        1 | 0x300 | placeholder
+Total allocated by GraphicBufferAllocator (estimate): 24546.56 KB
+Imported gralloc buffers:
++ name:FramebufferSurface, id:2413771620352, size:8294400, w/h:438x780
+";
+
+    #[test]
+    fn parses_the_gpu_memory() {
+        let gpu = GpuState::parse(GPUMEM).unwrap();
+        assert_eq!(gpu.memory.len(), 2);
+        assert_eq!(
+            gpu.memory[1],
+            GpuMemory {
+                gpu_id: 1,
+                global_total: 67_108_864,
+                processes: vec![ProcessGpuMemory {
+                    pid: 4321,
+                    bytes: 16_777_216,
+                }],
+            }
+        );
+        // Summed over both GPUs
+        assert_eq!(gpu.memory_of(4321), Some(317_198_848));
+        assert_eq!(gpu.memory_of(1), None);
+        let top: Vec<_> = gpu.top(2).iter().map(|process| process.pid).collect();
+        assert_eq!(top, [4321, 1203]);
+        assert_eq!(gpu.top(10).len(), 3);
+    }
+
+    #[test]
+    fn parses_the_graphic_buffers() {
+        let buffers = GraphicBuffers::parse(BUFFERS).unwrap();
+        // Neither the header nor the imported buffers are allocated ones
+        assert_eq!(buffers.buffers.len(), 5);
+        assert_eq!(
+            buffers.buffers[3],
+            GraphicBuffer {
+                handle: "0xb400007a8e2e60".to_owned(),
+                size_bytes: Some(129_597),
+                width: 90,
+                stride: 96,
+                height: 360,
+                layers: 1,
+                format: 0x22,
+                usage: 0x2000_0900,
+                requestor: "com.android.systemui.ScreenDecorHwcLayer#0(BLAST Consumer)0".to_owned(),
+            }
+        );
+        assert_eq!(buffers.buffers[4].size_bytes, None);
+        assert_eq!(buffers.total_bytes, Some(25_135_677));
+        assert_eq!(buffers.bytes_of("com.example.game"), 2 * 8160 * 1024);
+        let by_requestor = buffers.by_requestor();
+        assert_eq!(by_requestor["FramebufferSurface"], 8100 * 1024);
+        assert_eq!(by_requestor["placeholder"], 0);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            GpuState::parse(&GPUMEM.replace('\n', "\r\n")),
+            GpuState::parse(GPUMEM)
+        );
+        assert_eq!(
+            GraphicBuffers::parse(&BUFFERS.replace('\n', "\r\n")),
+            GraphicBuffers::parse(BUFFERS)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(GpuState::parse("").unwrap(), GpuState::default());
+        assert_eq!(
+            GraphicBuffers::parse("").unwrap(),
+            GraphicBuffers::default()
+        );
+    }
+
+    #[test]
+    fn without_gpu_memory() {
+        // Kernels without the tracepoint print no snapshot
+        let gpu = GpuState::parse("GPU memory total usage is not supported\n").unwrap();
+        assert!(gpu.memory.is_empty());
+        assert_eq!(gpu.memory_of(4321), None);
+        assert!(gpu.top(1).is_empty());
+    }
+
+    #[test]
+    fn buffers_of_older_releases() {
+        // Android 11 has no header, and a buffer cut short is skipped
+        let text = "GraphicBufferAllocator buffers:
+0x7b0c8e2b40: 8100.00 KiB | 1080 (1088) x 1920 |    1 |        1 | 0x1b00 | FramebufferSurface
+0x7b0c8e2c80: 8160.00 KiB | 1080 (1088) x 2400
+Total allocated by GraphicBufferAllocator (estimate): 16260.00 KB
+";
+        let buffers = GraphicBuffers::parse(text).unwrap();
+        assert_eq!(buffers.buffers.len(), 1);
+        assert_eq!(buffers.total_bytes, Some(16260 * 1024));
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(
+            GpuState::parse("Memory snapshot for GPU zero:").unwrap_err(),
+            invalid("GPU", "zero")
+        );
+        let gpu = |line: &str| GpuState::parse(&format!("{SNAPSHOT}0:\n{line}\n"));
+        assert_eq!(
+            gpu("Global total: 442 MB").unwrap_err(),
+            invalid("Global total", "442 MB")
+        );
+        assert_eq!(
+            gpu("Proc 561 total: -1").unwrap_err(),
+            invalid("total", "-1")
+        );
+
+        let buffer = |line: &str| GraphicBuffers::parse(&format!("{ALLOCATOR}\n{line}\n"));
+        assert_eq!(
+            buffer("0x7b0c8e2b40: 8100.00 KiB | 1080 x 1920 | 1 | 1 | 0x1b00 | FramebufferSurface")
+                .unwrap_err(),
+            invalid("size", "1080 x 1920")
+        );
+        assert_eq!(
+            buffer("0x7b0c8e2b40: 8100.00 KiB | 1080 (1088) x 1920 | 1 | RGBA | 0x1b00 | FramebufferSurface")
+                .unwrap_err(),
+            invalid("format", "RGBA")
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! A gRPC service wrapping the crate, for lab infrastructure driving collection on a device
+//!
+//! [`DumpsysService`] implements the `DumpService` of `proto/dumpsys.proto`, shipped with the crate
+//! for clients in other languages:
+//!
+//! - `ListServices` lists the registered services.
+//! - `Dump` dumps a service once. A client cancelling the call, or its deadline passing, aborts the
+//!   dump.
+//! - `StreamSamples` runs a [`Sampler`] for the call, streaming every snapshot and the error of every
+//!   failed dump until `count` samples were sent or the client goes away.
+//!
+//! Failed calls map [`DumpError`]s to gRPC codes: `NOT_FOUND` for missing services,
+//! `PERMISSION_DENIED`, `DEADLINE_EXCEEDED` on timeouts, `CANCELLED`, `UNAVAILABLE` for dead services
+//! and `INTERNAL` otherwise. A Rust client is generated too, see [`DumpServiceClient`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::grpc::DumpsysService;
+//!
+//! # async fn foo() {
+//! DumpsysService::new()
+//!     .dump_timeout(Duration::from_secs(10))
+//!     .min_interval(Duration::from_millis(500))
+//!     .serve("0.0.0.0:50051".parse().unwrap())
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::{
+    net::SocketAddr,
+    ops::ControlFlow,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use tokio::{sync::mpsc, task};
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::{
+    cancel::CancelToken,
+    error::{DumpContext, DumpError},
+    service_manager, Dumpsys, Sampler, Snapshot,
+};
+
+/// Messages, server and client generated from `proto/dumpsys.proto`
+#[allow(clippy::all, missing_docs)]
+pub mod proto {
+    tonic::include_proto!("dumpsys.v1");
+}
+
+pub use proto::{dump_service_client::DumpServiceClient, dump_service_server::DumpServiceServer};
+
+use proto::{
+    dump_service_server::DumpService, sample, DumpRequest, DumpResponse, ListServicesRequest,
+    ListServicesResponse, Sample, StreamSamplesRequest,
+};
+
+/// Samples buffered for a slow `StreamSamples` client before the sampler waits for it
+const STREAM_BUFFER: usize = 16;
+
+/// The `DumpService` of `proto/dumpsys.proto`, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct DumpsysService {
+    dump_timeout: Duration,
+    min_interval: Duration,
+}
+
+impl Default for DumpsysService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DumpsysService {
+    pub fn new() -> Self {
+        Self {
+            dump_timeout: Duration::from_secs(30),
+            min_interval: Duration::from_millis(100),
+        }
+    }
+
+    /// Timeout of dumps whose request leaves it at 0, 30 seconds by default.
+    pub fn dump_timeout(mut self, timeout: Duration) -> Self {
+        self.dump_timeout = timeout;
+        self
+    }
+
+    /// Sample no more often than every `interval`, whatever clients ask for, 100 ms by default.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// The service ready to be added to a [`tonic::transport::Server`].
+    pub fn into_server(self) -> DumpServiceServer<Self> {
+        DumpServiceServer::new(self)
+    }
+
+    /// Serve on `addr` until the server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_server())
+            .serve(addr)
+            .await
+    }
+
+    fn timeout(&self, timeout_ms: u64) -> Duration {
+        match timeout_ms {
+            0 => self.dump_timeout,
+            ms => Duration::from_millis(ms),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl DumpService for DumpsysService {
+    async fn list_services(
+        &self,
+        _request: Request<ListServicesRequest>,
+    ) -> Result<Response<ListServicesResponse>, Status> {
+        let services = blocking(service_manager::list_services).await?;
+        Ok(Response::new(ListServicesResponse { services }))
+    }
+
+    async fn dump(&self, request: Request<DumpRequest>) -> Result<Response<DumpResponse>, Status> {
+        let request = request.into_inner();
+        let timeout = self.timeout(request.timeout_ms);
+        // Dropped with the call when the client cancels it.
+        let cancel = CancelOnDrop(CancelToken::new());
+        let token = cancel.0.clone();
+        let snapshot = blocking(move || {
+            connect(&request.service, &request.args, timeout, Some(token))?
+                .dump_snapshot(&request.args)
+        })
+        .await?;
+        Ok(Response::new(DumpResponse {
+            snapshot: Some(snapshot.into()),
+        }))
+    }
+
+    type StreamSamplesStream = SampleStream;
+
+    async fn stream_samples(
+        &self,
+        request: Request<StreamSamplesRequest>,
+    ) -> Result<Response<SampleStream>, Status> {
+        let StreamSamplesRequest {
+            service,
+            args,
+            interval_ms,
+            timeout_ms,
+            count,
+        } = request.into_inner();
+        let timeout = self.timeout(timeout_ms);
+        let interval = Duration::from_millis(interval_ms).max(self.min_interval);
+        // A missing service fails the call rather than every sample.
+        let dumpsys = {
+            let args = args.clone();
+            blocking(move || connect(&service, &args, timeout, None)).await?
+        };
+
+        let (tx, samples) = mpsc::channel(STREAM_BUFFER);
+        let mut remaining = count;
+        let sampler = Sampler::new(dumpsys, args, interval, move |sample| {
+            let result = match sample {
+                Ok(snapshot) => sample::Result::Snapshot(snapshot.into()),
+                Err(err) => sample::Result::Error(err.to_string()),
+            };
+            let sample = Sample {
+                result: Some(result),
+            };
+            if tx.blocking_send(Ok(sample)).is_err() {
+                return ControlFlow::Break(());
+            }
+            match remaining {
+                0 => ControlFlow::Continue(()),
+                1 => ControlFlow::Break(()),
+                _ => {
+                    remaining -= 1;
+                    ControlFlow::Continue(())
+                }
+            }
+        });
+
+        Ok(Response::new(SampleStream {
+            samples,
+            _sampler: sampler,
+        }))
+    }
+}
+
+/// Samples of a `StreamSamples` call, stopping the sampler when dropped
+pub struct SampleStream {
+    samples: mpsc::Receiver<Result<Sample, Status>>,
+    // Dropped after the receiver, so a sampler blocked on a full channel wakes up.
+    _sampler: Sampler,
+}
+
+impl Stream for SampleStream {
+    type Item = Result<Sample, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.samples.poll_recv(cx)
+    }
+}
+
+impl From<Snapshot> for proto::Snapshot {
+    fn from(snapshot: Snapshot) -> Self {
+        Self {
+            service: snapshot.service,
+            args: snapshot.args,
+            output: snapshot.output,
+            captured_at_ms: snapshot
+                .captured_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            duration_us: snapshot.duration.as_micros() as u64,
+        }
+    }
+}
+
+impl From<DumpError> for Status {
+    fn from(err: DumpError) -> Self {
+        let message = err.to_string();
+        match err {
+            DumpError::ServiceNotFound { .. } => Status::not_found(message),
+            DumpError::PermissionDenied { .. } => Status::permission_denied(message),
+            DumpError::Timeout { .. } => Status::deadline_exceeded(message),
+            DumpError::Cancelled { .. } => Status::cancelled(message),
+            DumpError::DeadObject { .. } => Status::unavailable(message),
+            DumpError::Incomplete { .. } => Status::data_loss(message),
+            _ => Status::internal(message),
+        }
+    }
+}
+
+/// Cancels a dump once the call owning it is done or dropped.
+struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+fn connect(
+    service: &str,
+    args: &[String],
+    timeout: Duration,
+    cancel: Option<CancelToken>,
+) -> Result<Dumpsys, DumpError> {
+    let mut builder = Dumpsys::builder(service).dump_timeout(timeout);
+    if let Some(cancel) = cancel {
+        builder = builder.cancel_token(cancel);
+    }
+    builder.build().ok_or_else(|| {
+        DumpError::ServiceNotFound {
+            context: DumpContext::default(),
+        }
+        .with_context(service, args)
+    })
+}
+
+/// Run `f` on the blocking pool of tokio.
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, DumpError> + Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?
+        .map_err(Status::from)
+}
@@ -0,0 +1,328 @@
+//! The service side: answering `dumpsys <service>` from a binder service written in Rust
+//!
+//! Implement [`DumpHandler`] for the service, then either forward
+//! [`Interface::dump`] of its AIDL interface to [`dump`], or register a dump-only service with
+//! [`add_service`]. Arguments arrive parsed into [`DumpArgs`], text goes through a [`DumpWriter`] that
+//! indents nested sections like the Java services do, and `--proto` dumps go to
+//! [`DumpHandler::dump_proto`].
+//!
+//! # Example
+//!
+//! ```
+//! use std::io::{self, Write};
+//!
+//! use binder::ProcessState;
+//! use dumpsys_rs::{
+//!     handler::{self, DumpArgs, DumpHandler, DumpWriter},
+//!     Dumpsys,
+//! };
+//!
+//! struct Cache {
+//!     entries: Vec<(String, usize)>,
+//! }
+//!
+//! impl DumpHandler for Cache {
+//!     fn dump(&self, out: &mut DumpWriter<'_>, args: &DumpArgs) -> io::Result<()> {
+//!         writeln!(out, "Cache of {} entries", self.entries.len())?;
+//!         if args.wants("entries") {
+//!             out.section("Entries", |out| {
+//!                 for (key, size) in &self.entries {
+//!                     out.field(key, size)?;
+//!                 }
+//!                 Ok(())
+//!             })?;
+//!         }
+//!         Ok(())
+//!     }
+//!
+//!     fn usage(&self) -> Option<&str> {
+//!         Some("dumpsys cache [entries]\n")
+//!     }
+//! }
+//!
+//! # fn foo() -> Option<()> {
+//! ProcessState::start_thread_pool();
+//! let cache = Cache {
+//!     entries: vec![("icons".to_owned(), 4096)],
+//! };
+//! handler::add_service("cache", cache).ok()?;
+//!
+//! let dump = Dumpsys::new("cache")?.dump(&["entries"]).unwrap();
+//! assert_eq!(dump, "Cache of 1 entries\nEntries:\n  icons: 4096\n");
+//! # Some(())
+//! # }
+//! ```
+
+use std::{
+    ffi::CStr,
+    fmt,
+    io::{self, ErrorKind, Write},
+};
+
+use binder::{
+    binder_impl::{BorrowedParcel, TransactionCode},
+    declare_binder_interface, BinderFeatures, Interface, StatusCode,
+};
+
+/// `--proto` of `dumpsys`, see [`Dumpsys::dump_proto`](crate::Dumpsys::dump_proto)
+const PROTO_ARG: &str = "--proto";
+/// Spaces per level of [`DumpWriter::section`], like `IndentingPrintWriter`
+const INDENT: usize = 2;
+
+/// How a service answers dumps
+pub trait DumpHandler: Send + Sync {
+    /// Write the dump as text.
+    fn dump(&self, out: &mut DumpWriter<'_>, args: &DumpArgs) -> io::Result<()>;
+
+    /// Write the dump as a serialized protobuf, for `--proto`. Fails with [`ErrorKind::Unsupported`]
+    /// by default, which the reader gets as `INVALID_OPERATION`.
+    fn dump_proto(&self, out: &mut dyn Write, args: &DumpArgs) -> io::Result<()> {
+        let _ = (out, args);
+        Err(ErrorKind::Unsupported.into())
+    }
+
+    /// Usage written for `-h` and `--help` instead of the dump, none by default.
+    fn usage(&self) -> Option<&str> {
+        None
+    }
+
+    /// Options taking the next argument as their value, e.g. `--user` for `--user 10`. Others only
+    /// take one as `--user=10`.
+    fn options(&self) -> &[&str] {
+        &[]
+    }
+}
+
+/// Arguments of a dump, sorted into flags, options with a value and positional arguments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpArgs {
+    args: Vec<String>,
+    proto: bool,
+    flags: Vec<String>,
+    values: Vec<(String, String)>,
+    positional: Vec<String>,
+}
+
+impl DumpArgs {
+    /// Parse `args`, where the names in `options` take the next argument as their value.
+    ///
+    /// Arguments after `--` are positional whatever they start with.
+    pub fn parse<S: AsRef<str>>(args: &[S], options: &[&str]) -> Self {
+        let mut parsed = Self::default();
+        let mut args = args.iter().map(AsRef::as_ref);
+        while let Some(arg) = args.next() {
+            if arg == PROTO_ARG {
+                parsed.proto = true;
+                continue;
+            }
+            parsed.args.push(arg.to_owned());
+            if arg == "--" {
+                for arg in args.by_ref() {
+                    parsed.args.push(arg.to_owned());
+                    parsed.positional.push(arg.to_owned());
+                }
+            } else if arg.len() < 2 || !arg.starts_with('-') {
+                parsed.positional.push(arg.to_owned());
+            } else if let Some((name, value)) = arg.split_once('=') {
+                parsed.values.push((name.to_owned(), value.to_owned()));
+            } else if options.contains(&arg) {
+                let value = args.next().unwrap_or_default();
+                parsed.args.push(value.to_owned());
+                parsed.values.push((arg.to_owned(), value.to_owned()));
+            } else {
+                parsed.flags.push(arg.to_owned());
+            }
+        }
+        parsed
+    }
+
+    /// The arguments as passed, without `--proto`.
+    pub fn as_slice(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Whether the dump is for `--proto`, see [`DumpHandler::dump_proto`].
+    pub fn proto(&self) -> bool {
+        self.proto
+    }
+
+    /// Whether `flag`, e.g. `-a`, was passed.
+    pub fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|passed| passed == flag)
+    }
+
+    /// Whether `-h` or `--help` was passed.
+    pub fn help(&self) -> bool {
+        self.flag("-h") || self.flag("--help")
+    }
+
+    /// The value of the last `option`, e.g. `--user`.
+    pub fn value(&self, option: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(name, _)| name == option)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Arguments that are neither flags nor options, e.g. the sections to dump.
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    /// Whether to dump `section`: when it's among the positional arguments, ignoring case, or there
+    /// are none, like `dumpsys activity activities` does.
+    pub fn wants(&self, section: &str) -> bool {
+        self.positional.is_empty()
+            || self
+                .positional
+                .iter()
+                .any(|arg| arg.eq_ignore_ascii_case(section))
+    }
+}
+
+/// Text output of a dump, indenting the lines of nested [sections](DumpWriter::section)
+pub struct DumpWriter<'a> {
+    out: &'a mut dyn Write,
+    indent: usize,
+    line_start: bool,
+}
+
+impl<'a> DumpWriter<'a> {
+    /// Write to `out`, e.g. a `Vec<u8>` to check the dump of a handler.
+    pub fn new(out: &'a mut dyn Write) -> Self {
+        Self {
+            out,
+            indent: 0,
+            line_start: true,
+        }
+    }
+
+    /// Write `title:` and then whatever `write` does, indented by one more level.
+    pub fn section(
+        &mut self,
+        title: impl fmt::Display,
+        write: impl FnOnce(&mut Self) -> io::Result<()>,
+    ) -> io::Result<()> {
+        writeln!(self, "{title}:")?;
+        self.indent += INDENT;
+        let result = write(self);
+        self.indent -= INDENT;
+        result
+    }
+
+    /// Write `name: value` on a line of its own.
+    pub fn field(&mut self, name: &str, value: impl fmt::Display) -> io::Result<()> {
+        writeln!(self, "{name}: {value}")
+    }
+}
+
+impl Write for DumpWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in buf.split_inclusive(|&byte| byte == b'\n') {
+            if self.line_start && line != b"\n" {
+                write!(self.out, "{:1$}", "", self.indent)?;
+            }
+            self.out.write_all(line)?;
+            self.line_start = line.ends_with(b"\n");
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Answer a dump with `handler`, to implement [`Interface::dump`] of a service with:
+///
+/// ```
+/// # use std::io::{self, Write};
+/// # use dumpsys_rs::handler::{self, DumpArgs, DumpHandler, DumpWriter};
+/// # struct MyService;
+/// # impl DumpHandler for MyService {
+/// #     fn dump(&self, _out: &mut DumpWriter<'_>, _args: &DumpArgs) -> io::Result<()> { Ok(()) }
+/// # }
+/// impl binder::Interface for MyService {
+///     fn dump(&self, writer: &mut dyn Write, args: &[&std::ffi::CStr]) -> binder::Result<()> {
+///         handler::dump(self, writer, args)
+///     }
+/// }
+/// ```
+///
+/// A reader that goes away before the dump is complete isn't a failure. Other errors of the handler
+/// reach the reader as a binder status: `PERMISSION_DENIED` for [`ErrorKind::PermissionDenied`],
+/// `INVALID_OPERATION` for [`ErrorKind::Unsupported`] and `UNKNOWN_ERROR` for the rest.
+pub fn dump<H: DumpHandler + ?Sized>(
+    handler: &H,
+    writer: &mut dyn Write,
+    args: &[&CStr],
+) -> binder::Result<()> {
+    let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+    let args = DumpArgs::parse(&args, handler.options());
+    let result = match handler.usage() {
+        _ if args.proto() => handler.dump_proto(writer, &args),
+        Some(usage) if args.help() => writer.write_all(usage.as_bytes()),
+        _ => {
+            let mut out = DumpWriter::new(writer);
+            handler.dump(&mut out, &args).and_then(|()| out.flush())
+        }
+    };
+    match result {
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => Err(StatusCode::PERMISSION_DENIED),
+        Err(err) if err.kind() == ErrorKind::Unsupported => Err(StatusCode::INVALID_OPERATION),
+        result => result.map_err(|_| StatusCode::UNKNOWN_ERROR),
+    }
+}
+
+/// Register `handler` with servicemanager as `name`, as a service that does nothing but dump.
+///
+/// servicemanager only lets processes add the services their SELinux domain is allowed to, and
+/// dumps arrive on binder threads, so the process needs
+/// [`ProcessState::start_thread_pool`](binder::ProcessState::start_thread_pool).
+pub fn add_service(name: &str, handler: impl DumpHandler + 'static) -> binder::Result<()> {
+    add_dump_service(name, move |writer, args| dump(&handler, writer, args))
+}
+
+/// Register a service as `name` whose dumps `dump` answers with the raw arguments and binder status.
+pub(crate) fn add_dump_service<F>(name: &str, dump: F) -> binder::Result<()>
+where
+    F: Fn(&mut dyn Write, &[&CStr]) -> binder::Result<()> + Send + Sync + 'static,
+{
+    let service = BnDumpOnly::new_binder(DumpOnly(Box::new(dump)), BinderFeatures::default());
+    binder::add_service(name, service.as_binder())
+}
+
+/// Interface of the services of [`add_service`], without transactions of its own
+pub(crate) trait IDumpOnly: Interface {}
+
+declare_binder_interface! {
+    IDumpOnly["dumpsys_rs.IDumpOnly"] {
+        native: BnDumpOnly(serve_dump_only),
+        proxy: BpDumpOnly,
+    }
+}
+
+fn serve_dump_only(
+    _service: &dyn IDumpOnly,
+    _code: TransactionCode,
+    _data: &BorrowedParcel<'_>,
+    _reply: &mut BorrowedParcel<'_>,
+) -> Result<(), StatusCode> {
+    Err(StatusCode::UNKNOWN_TRANSACTION)
+}
+
+impl IDumpOnly for BpDumpOnly {}
+
+type Dump = dyn Fn(&mut dyn Write, &[&CStr]) -> binder::Result<()> + Send + Sync;
+
+struct DumpOnly(Box<Dump>);
+
+impl Interface for DumpOnly {
+    fn dump(&self, writer: &mut dyn Write, args: &[&CStr]) -> binder::Result<()> {
+        (self.0)(writer, args)
+    }
+}
+
+impl IDumpOnly for DumpOnly {}
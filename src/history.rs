@@ -0,0 +1,120 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+use crate::Snapshot;
+
+/// The last `N` snapshots, oldest first, for flight-recorder style debugging
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     sync::{Arc, Mutex},
+///     time::Duration,
+/// };
+///
+/// use dumpsys_rs::{Dumpsys, History, Sampler};
+///
+/// # fn foo() -> Option<()> {
+/// let history = Arc::new(Mutex::new(History::<120>::new()));
+/// let recorder = history.clone();
+/// let sampler = Sampler::new(
+///     Dumpsys::new("SurfaceFlinger")?,
+///     &["--latency"],
+///     Duration::from_millis(500),
+///     move |sample| {
+///         if let Ok(snapshot) = sample {
+///             recorder.lock().unwrap().push(snapshot);
+///         }
+///         std::ops::ControlFlow::Continue(())
+///     },
+/// );
+///
+/// // After something went wrong
+/// for snapshot in history.lock().unwrap().within(Duration::from_secs(30)) {
+///     println!("{:?}: {}", snapshot.captured_at, snapshot.output);
+/// }
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct History<const N: usize> {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl<const N: usize> Default for History<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> History<N> {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(N),
+        }
+    }
+
+    /// Record `snapshot`, dropping the oldest one once `N` are kept.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if N == 0 {
+            return;
+        }
+        if self.snapshots.len() == N {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// The most recent snapshot.
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    /// Every kept snapshot, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Snapshot> + ExactSizeIterator {
+        self.snapshots.iter()
+    }
+
+    /// Snapshots captured between `from` and `to`, oldest first.
+    pub fn between(
+        &self,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> impl DoubleEndedIterator<Item = &Snapshot> {
+        self.snapshots
+            .iter()
+            .filter(move |snapshot| (from..=to).contains(&snapshot.captured_at))
+    }
+
+    /// Snapshots captured in the last `window`, e.g. all samples of the last 30 seconds.
+    pub fn within(&self, window: Duration) -> impl DoubleEndedIterator<Item = &Snapshot> {
+        let now = SystemTime::now();
+        let from = now.checked_sub(window).unwrap_or(SystemTime::UNIX_EPOCH);
+        self.between(from, now)
+    }
+
+    /// Forget every snapshot.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a History<N> {
+    type Item = &'a Snapshot;
+    type IntoIter = std::collections::vec_deque::Iter<'a, Snapshot>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.snapshots.iter()
+    }
+}
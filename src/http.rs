@@ -0,0 +1,230 @@
+//! A small HTTP server exposing dumps, for agents queried from a host without adb
+//!
+//! [`HttpServer`] answers, one thread per connection:
+//!
+//! ```text
+//! GET /services                        registered services, one per line
+//! GET /dump/<service>?args=-a+--proto  the dump of a service, arguments split at spaces
+//! GET /metrics                         the metrics of the builder, in the Prometheus text format
+//! ```
+//!
+//! Failed dumps answer 404 for missing services, 403 when permission is denied, 504 on timeouts and
+//! 500 otherwise, with the error as the body. Nothing is authenticated, so bind to `127.0.0.1` and
+//! `adb forward` the port unless the network is trusted.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::{http::HttpServer, prometheus::Metrics};
+//!
+//! # fn foo() {
+//! let server = HttpServer::builder()
+//!     .metrics(Metrics::new())
+//!     .dump_timeout(Duration::from_secs(10))
+//!     .serve("0.0.0.0:8080")
+//!     .unwrap();
+//! println!("curl http://{}/dump/SurfaceFlinger?args=--latency", server.local_addr());
+//! # }
+//! ```
+
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    cancel::CancelToken,
+    error::{DumpContext, DumpError},
+    prometheus::{self, Metrics},
+    service_manager, Dumpsys,
+};
+
+const TEXT: &str = "text/plain; charset=utf-8";
+const DUMP_PREFIX: &str = "/dump/";
+const ARGS_PARAM: &str = "args";
+
+/// Configure an [`HttpServer`], see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct HttpServerBuilder {
+    metrics: Option<Metrics>,
+    dump_timeout: Duration,
+}
+
+impl HttpServerBuilder {
+    /// Serve `metrics` on `/metrics`, which answers 404 otherwise.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Give up on dumps still producing output after `timeout`, 30 seconds by default.
+    pub fn dump_timeout(mut self, timeout: Duration) -> Self {
+        self.dump_timeout = timeout;
+        self
+    }
+
+    /// Listen on `addr` and answer requests on background threads.
+    pub fn serve(self, addr: impl ToSocketAddrs) -> io::Result<HttpServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let cancel = CancelToken::new();
+
+        let stopped = stop.clone();
+        let token = cancel.clone();
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let (builder, token) = (self.clone(), token.clone());
+                thread::spawn(move || {
+                    let _ = builder.respond(stream, &token);
+                });
+            }
+        });
+
+        Ok(HttpServer {
+            addr,
+            stop,
+            cancel,
+            thread: Some(thread),
+        })
+    }
+
+    fn respond(&self, mut stream: TcpStream, cancel: &CancelToken) -> io::Result<()> {
+        let request = prometheus::read_request(&mut stream)?;
+        if request.method != "GET" {
+            return prometheus::write_response(&mut stream, "405 Method Not Allowed", TEXT, "");
+        }
+
+        let (status, content_type, body) = match request.path.as_str() {
+            "/services" => match service_manager::list_services() {
+                Ok(services) => ("200 OK", TEXT, services.join("\n") + "\n"),
+                Err(err) => (status(&err), TEXT, format!("{err}\n")),
+            },
+            "/metrics" => match &self.metrics {
+                Some(metrics) => ("200 OK", prometheus::CONTENT_TYPE, metrics.render()),
+                None => ("404 Not Found", TEXT, String::from("no metrics\n")),
+            },
+            path => match path.strip_prefix(DUMP_PREFIX) {
+                Some(service) => match self.dump(&decode(service), &request.query, cancel) {
+                    Ok(output) => ("200 OK", TEXT, output),
+                    Err(err) => (status(&err), TEXT, format!("{err}\n")),
+                },
+                None => (
+                    "404 Not Found",
+                    TEXT,
+                    String::from("try /services, /dump/<service> or /metrics\n"),
+                ),
+            },
+        };
+        prometheus::write_response(&mut stream, status, content_type, &body)
+    }
+
+    fn dump(&self, service: &str, query: &str, cancel: &CancelToken) -> Result<String, DumpError> {
+        let args: Vec<String> = query
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .filter(|(key, _)| *key == ARGS_PARAM)
+            .flat_map(|(_, value)| {
+                decode(value)
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let dumpsys = Dumpsys::builder(service)
+            .dump_timeout(self.dump_timeout)
+            .cancel_token(cancel.clone())
+            .build()
+            .ok_or_else(|| {
+                DumpError::ServiceNotFound {
+                    context: DumpContext::default(),
+                }
+                .with_context(service, &args)
+            })?;
+        dumpsys.dump(&args)
+    }
+}
+
+/// Dumps served over HTTP on background threads, see the [module docs](self)
+///
+/// Dropping it stops the server and cancels the dumps in flight.
+#[derive(Debug)]
+pub struct HttpServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    pub fn builder() -> HttpServerBuilder {
+        HttpServerBuilder {
+            metrics: None,
+            dump_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Address the server listens on, e.g. to find the port picked for `127.0.0.1:0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.cancel.cancel();
+        // Wake the blocking accept.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn status(err: &DumpError) -> &'static str {
+    match err {
+        DumpError::ServiceNotFound { .. } => "404 Not Found",
+        DumpError::PermissionDenied { .. } => "403 Forbidden",
+        DumpError::Timeout { .. } => "504 Gateway Timeout",
+        _ => "500 Internal Server Error",
+    }
+}
+
+/// Percent-decode a path segment or query value, with `+` for a space.
+fn decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = tail.get(..2).and_then(|hex| std::str::from_utf8(hex).ok());
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(decoded) => {
+                        bytes.push(decoded);
+                        rest = &tail[2..];
+                    }
+                    None => bytes.push(b'%'),
+                }
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
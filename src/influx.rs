@@ -0,0 +1,140 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    time::UNIX_EPOCH,
+};
+
+use crate::{DumpSink, Snapshot};
+
+type Field = Box<dyn Fn(&str) -> Option<f64> + Send>;
+
+/// Writes metrics extracted from each snapshot in InfluxDB line protocol, one line per snapshot
+///
+/// Lines carry a `service` tag plus the tags added with [`InfluxSink::tag`], e.g. the device serial,
+/// and a nanosecond timestamp of the capture. Snapshots where no field was found are skipped, as the
+/// protocol needs at least one.
+///
+/// # Example
+///
+/// ```
+/// use std::{net::TcpStream, time::Duration};
+///
+/// use dumpsys_rs::{parse::kv::KeyValues, Dumpsys, InfluxSink, Sampler};
+///
+/// # fn foo() -> Option<()> {
+/// let telegraf = TcpStream::connect("10.0.0.1:8094").unwrap();
+/// let sink = InfluxSink::new(telegraf, "power")
+///     .tag("device", "R58M123ABC")
+///     .field("brightness", |output| {
+///         KeyValues::parse(output).get_float("mScreenBrightnessSetting").ok()
+///     });
+/// let sampler = Sampler::with_sink(Dumpsys::new("power")?, Vec::<&str>::new(), Duration::from_secs(10), sink);
+/// # Some(())
+/// # }
+/// ```
+pub struct InfluxSink<W: Write> {
+    out: W,
+    measurement: String,
+    tags: Vec<(String, String)>,
+    fields: Vec<(String, Field)>,
+}
+
+impl<W: Write> fmt::Debug for InfluxSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<_> = self.fields.iter().map(|(name, _)| name).collect();
+        f.debug_struct("InfluxSink")
+            .field("measurement", &self.measurement)
+            .field("tags", &self.tags)
+            .field("fields", &fields)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> InfluxSink<W> {
+    /// Write lines of `measurement` to `out`, with no fields yet.
+    pub fn new(out: W, measurement: impl AsRef<str>) -> Self {
+        Self {
+            out,
+            measurement: measurement.as_ref().to_owned(),
+            tags: Vec::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Tag every line with `key=value`.
+    pub fn tag(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.tags
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// Add a field `name`, filled by `extract` from the output of each snapshot.
+    pub fn field<F>(mut self, name: impl AsRef<str>, extract: F) -> Self
+    where
+        F: Fn(&str) -> Option<f64> + Send + 'static,
+    {
+        self.fields
+            .push((name.as_ref().to_owned(), Box::new(extract)));
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> DumpSink for InfluxSink<W> {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let mut fields = String::new();
+        for (name, extract) in &self.fields {
+            let Some(value) = extract(&snapshot.output).filter(|value| value.is_finite()) else {
+                continue;
+            };
+            if !fields.is_empty() {
+                fields.push(',');
+            }
+            push_escaped(&mut fields, name, &[',', '=', ' ']);
+            fields.push('=');
+            fields.push_str(&value.to_string());
+        }
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let mut line = String::new();
+        push_escaped(&mut line, &self.measurement, &[',', ' ']);
+        let service = ("service", snapshot.service.as_str());
+        let tags = self
+            .tags
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()));
+        for (key, value) in [service].into_iter().chain(tags) {
+            line.push(',');
+            push_escaped(&mut line, key, &[',', '=', ' ']);
+            line.push('=');
+            push_escaped(&mut line, value, &[',', '=', ' ']);
+        }
+
+        let timestamp = snapshot
+            .captured_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        writeln!(self.out, "{line} {fields} {timestamp}")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Append `text` with a backslash before each of `special`, turning line breaks the protocol can't carry into spaces.
+fn push_escaped(line: &mut String, text: &str, special: &[char]) {
+    for c in text.chars() {
+        let c = if matches!(c, '\n' | '\r') { ' ' } else { c };
+        if special.contains(&c) {
+            line.push('\\');
+        }
+        line.push(c);
+    }
+}
@@ -0,0 +1,686 @@
+//! Typed output of `dumpsys input`
+//!
+//! Devices are read from the input reader, with the classes the event hub found for them. Sources are
+//! printed as a mask before Android 13 and as names after, both read into the mask.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::input::InputState;
+//!
+//! let input = InputState::parse(
+//!     "INPUT MANAGER (dumpsys input)
+//!
+//! Event Hub State:
+//!   BuiltInKeyboardId: -2
+//!   Devices:
+//!     2: gpio-keys
+//!       Classes: KEYBOARD
+//!       Path: /dev/input/event2
+//!     4: sec_touchscreen
+//!       Classes: TOUCH | TOUCH_MT
+//!       Path: /dev/input/event4
+//!
+//! Input Reader State (Nums of device: 2):
+//!   Device 3: gpio-keys
+//!     EventHub Devices: [ 2 ]
+//!     IsExternal: false
+//!     Sources: KEYBOARD
+//!     KeyboardType: non-alphabetic
+//!   Device 5: sec_touchscreen
+//!     EventHub Devices: [ 4 ]
+//!     IsExternal: false
+//!     Sources: 0x00001103
+//!     KeyboardType: 1
+//!     Motion Ranges:
+//!       X: source=0x00001002, min=0.000, max=1079.000, flat=0.000, fuzz=0.000, resolution=0.000
+//!       Y: source=0x00001002, min=0.000, max=2399.000, flat=0.000, fuzz=0.000, resolution=0.000
+//!     Touch Input Mapper (mode - DIRECT):
+//!
+//! Input Dispatcher State:
+//!   DispatchEnabled: true
+//!   DispatchFrozen: false
+//!   FocusedDisplayId: 0
+//!   FocusedApplications:
+//!     displayId=0, name='ActivityRecord{abc u0 com.example.app/.MainActivity t123}', dispatchingTimeout=5000ms
+//!   FocusedWindows:
+//!     displayId=0, name='1a2b3c com.example.app/com.example.app.MainActivity'
+//!   Connections:",
+//! )
+//! .unwrap();
+//!
+//! let touchscreen = input.device_by_name("sec_touchscreen").unwrap();
+//! assert_eq!(touchscreen.id, 5);
+//! assert!(touchscreen.is_touchscreen());
+//! assert_eq!(touchscreen.classes, ["TOUCH", "TOUCH_MT"]);
+//! assert_eq!(touchscreen.path.as_deref(), Some("/dev/input/event4"));
+//! assert_eq!(touchscreen.axes.len(), 2);
+//! assert_eq!(touchscreen.axes[1].name, "Y");
+//! assert_eq!(touchscreen.axes[1].max, 2399.0);
+//!
+//! let keys = input.device(3).unwrap();
+//! assert!(keys.has_keys());
+//! assert!(!keys.is_touchscreen());
+//! assert_eq!(keys.keyboard_type.as_deref(), Some("non-alphabetic"));
+//!
+//! let dispatcher = &input.dispatcher;
+//! assert_eq!(dispatcher.enabled, Some(true));
+//! assert_eq!(dispatcher.focused_applications[0].dispatching_timeout, Some(Duration::from_secs(5)));
+//! assert_eq!(
+//!     input.focused_window().unwrap().name,
+//!     "1a2b3c com.example.app/com.example.app.MainActivity"
+//! );
+//! ```
+
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_duration, KeyValues},
+    typed, DumpParse,
+};
+
+const EVENT_HUB: &str = "Event Hub State:";
+const INPUT_READER: &str = "Input Reader State";
+const DISPATCHER: &str = "Input Dispatcher State:";
+const DEVICE: &str = "Device ";
+const MOTION_RANGES: &str = "Motion Ranges";
+const FOCUSED_APPLICATIONS: &str = "FocusedApplications:";
+const FOCUSED_WINDOWS: &str = "FocusedWindows:";
+const NAME: &str = "name='";
+
+/// `InputDevice.SOURCE_KEYBOARD`
+const SOURCE_KEYBOARD: u32 = 0x101;
+/// `InputDevice.SOURCE_TOUCHSCREEN`
+const SOURCE_TOUCHSCREEN: u32 = 0x1002;
+
+/// A motion axis of a device
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Axis {
+    /// As in `X`, `PRESSURE` or `TOUCH_MAJOR`
+    pub name: String,
+    /// `InputDevice.SOURCE_*` the range applies to
+    pub source: Option<u32>,
+    pub min: f64,
+    pub max: f64,
+    pub flat: f64,
+    pub fuzz: f64,
+    pub resolution: f64,
+}
+
+/// An input device known to the input reader
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputDevice {
+    pub id: i32,
+    pub name: String,
+    /// Ids of the event hub devices the device is made of, on Android 11 and later
+    pub event_hub_ids: Vec<i32>,
+    pub external: Option<bool>,
+    /// `InputDevice.SOURCE_*` mask
+    pub sources: u32,
+    /// As in `alphabetic` on Android 13 and later, or `2` before
+    pub keyboard_type: Option<String>,
+    /// Event hub classes, such as `KEYBOARD` or `TOUCH_MT`
+    pub classes: Vec<String>,
+    /// Path of the event node, as in `/dev/input/event2`
+    pub path: Option<String>,
+    pub axes: Vec<Axis>,
+}
+
+impl InputDevice {
+    pub fn is_touchscreen(&self) -> bool {
+        self.sources & SOURCE_TOUCHSCREEN == SOURCE_TOUCHSCREEN
+    }
+
+    /// Whether the device has keys, such as a keyboard or the power and volume buttons.
+    pub fn has_keys(&self) -> bool {
+        self.sources & SOURCE_KEYBOARD == SOURCE_KEYBOARD
+    }
+
+    pub fn axis(&self, name: &str) -> Option<&Axis> {
+        self.axes.iter().find(|axis| axis.name == name)
+    }
+}
+
+/// The application input is dispatched to on a display
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FocusedApplication {
+    /// `None` before Android 10, with a single display
+    pub display_id: Option<i32>,
+    pub name: String,
+    /// Time the application has to handle an event before it's not responding
+    pub dispatching_timeout: Option<Duration>,
+}
+
+/// The window input is dispatched to on a display
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FocusedWindow {
+    pub display_id: Option<i32>,
+    pub name: String,
+}
+
+/// State of the input dispatcher
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dispatcher {
+    pub enabled: Option<bool>,
+    /// Frozen while the window manager reconfigures windows
+    pub frozen: Option<bool>,
+    pub focused_display: Option<i32>,
+    pub focused_applications: Vec<FocusedApplication>,
+    pub focused_windows: Vec<FocusedWindow>,
+}
+
+/// Output of `dumpsys input`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputState {
+    pub devices: Vec<InputDevice>,
+    pub dispatcher: Dispatcher,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    EventHub,
+    Reader,
+    Dispatcher,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focused {
+    None,
+    Applications,
+    Windows,
+}
+
+/// Classes and path of an event hub device
+#[derive(Default)]
+struct HubDevice {
+    classes: Vec<String>,
+    path: Option<String>,
+}
+
+impl InputState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut input = Self::default();
+        let mut section = Section::Other;
+        let mut hub: HashMap<i32, HubDevice> = HashMap::new();
+        let mut hub_device = None;
+        let mut focused = Focused::None;
+        // Indent of `Motion Ranges:` while in the axes of a device, which mappers print again
+        let mut ranges = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                section = match trimmed {
+                    EVENT_HUB => Section::EventHub,
+                    DISPATCHER => Section::Dispatcher,
+                    _ if trimmed.starts_with(INPUT_READER) => Section::Reader,
+                    _ => Section::Other,
+                };
+                continue;
+            }
+            let (key, value) = trimmed
+                .split_once(": ")
+                .map_or((trimmed.trim_end_matches(':'), ""), |(key, value)| {
+                    (key, value.trim())
+                });
+
+            match section {
+                Section::Other => {}
+                Section::EventHub => {
+                    if let Ok(id) = key.parse() {
+                        hub_device = Some(id);
+                        continue;
+                    }
+                    let Some(device) = hub_device.map(|id| hub.entry(id).or_default()) else {
+                        continue;
+                    };
+                    match key {
+                        "Classes" => device.classes = names(value),
+                        "Path" => device.path = Some(value.to_owned()),
+                        _ => {}
+                    }
+                }
+                Section::Reader => {
+                    if let Some((id, name)) = trimmed
+                        .strip_prefix(DEVICE)
+                        .and_then(|device| device.split_once(": "))
+                    {
+                        input.devices.push(InputDevice {
+                            id: id.parse().map_err(|_| invalid("Device", id))?,
+                            name: name.to_owned(),
+                            ..InputDevice::default()
+                        });
+                        ranges = None;
+                    } else if let Some(device) = input.devices.last_mut() {
+                        let indent = line.len() - line.trim_start().len();
+                        if ranges.is_some_and(|ranges| indent > ranges) {
+                            device.axes.extend(axis(key, value));
+                        } else {
+                            ranges = (key == MOTION_RANGES).then_some(indent);
+                            device.line(key, value)?;
+                        }
+                    }
+                }
+                Section::Dispatcher => {
+                    if trimmed.contains(NAME) && focused != Focused::None {
+                        input.dispatcher.focused(focused, trimmed);
+                        continue;
+                    }
+                    focused = Focused::None;
+                    // Printed as `1` and `0` before Android 10.
+                    let flag = || Some(matches!(value, "true" | "1"));
+                    match key {
+                        "DispatchEnabled" => input.dispatcher.enabled = flag(),
+                        "DispatchFrozen" => input.dispatcher.frozen = flag(),
+                        "FocusedDisplayId" => input.dispatcher.focused_display = value.parse().ok(),
+                        _ if trimmed == FOCUSED_APPLICATIONS => focused = Focused::Applications,
+                        _ if trimmed == FOCUSED_WINDOWS => focused = Focused::Windows,
+                        // Before Android 10, with a single display.
+                        "FocusedApplication" => {
+                            input.dispatcher.focused(Focused::Applications, value);
+                        }
+                        "FocusedWindow" => input.dispatcher.focused(Focused::Windows, value),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for device in &mut input.devices {
+            // Before Android 11, reader and event hub ids are the same.
+            let ids = if device.event_hub_ids.is_empty() {
+                vec![device.id]
+            } else {
+                device.event_hub_ids.clone()
+            };
+            for id in ids {
+                if let Some(HubDevice { classes, path }) = hub.remove(&id) {
+                    device.classes.extend(classes);
+                    device.path = device.path.take().or(path);
+                }
+            }
+        }
+
+        Ok(input)
+    }
+
+    pub fn device(&self, id: i32) -> Option<&InputDevice> {
+        self.devices.iter().find(|device| device.id == id)
+    }
+
+    pub fn device_by_name(&self, name: &str) -> Option<&InputDevice> {
+        self.devices.iter().find(|device| device.name == name)
+    }
+
+    /// The window focused on the focused display.
+    pub fn focused_window(&self) -> Option<&FocusedWindow> {
+        let Dispatcher {
+            focused_display,
+            focused_windows,
+            ..
+        } = &self.dispatcher;
+        focused_windows
+            .iter()
+            .find(|window| window.display_id.is_none() || window.display_id == *focused_display)
+            .or_else(|| focused_windows.first())
+    }
+}
+
+impl DumpParse for InputState {
+    const SERVICE: &'static str = "input";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `input` and return the input devices.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for device in dumpsys_rs::input::devices().unwrap() {
+///     println!("{}: {} (touchscreen: {})", device.id, device.name, device.is_touchscreen());
+/// }
+/// # }
+/// ```
+pub fn devices() -> Result<Vec<InputDevice>, DumpError> {
+    typed::dump_service::<InputState>(Vec::<&str>::new()).map(|input| input.devices)
+}
+
+impl InputDevice {
+    /// A `Key: value` line under `Device 3: gpio-keys`.
+    fn line(&mut self, key: &str, value: &str) -> Result<(), ParseError> {
+        match key {
+            "EventHub Devices" => {
+                // [ 2 4 ]
+                self.event_hub_ids = value
+                    .trim_matches(['[', ']', ' '])
+                    .split_whitespace()
+                    .filter_map(|id| id.parse().ok())
+                    .collect();
+            }
+            "IsExternal" => self.external = Some(value == "true"),
+            "Sources" => {
+                self.sources = sources(value).ok_or_else(|| invalid("Sources", value))?;
+            }
+            "KeyboardType" => self.keyboard_type = Some(value.to_owned()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Dispatcher {
+    /// `displayId=0, name='...', dispatchingTimeout=5000ms`
+    fn focused(&mut self, focused: Focused, line: &str) {
+        let Some(name) = line
+            .split_once(NAME)
+            .and_then(|(_, name)| name.rsplit_once('\''))
+            .map(|(name, _)| name.to_owned())
+        else {
+            return;
+        };
+        let fields = KeyValues::parse(line);
+        let display_id = fields.get_parsed("displayId").ok();
+        match focused {
+            Focused::Applications => self.focused_applications.push(FocusedApplication {
+                display_id,
+                name,
+                dispatching_timeout: fields.get("dispatchingTimeout").and_then(timeout),
+            }),
+            Focused::Windows => self
+                .focused_windows
+                .push(FocusedWindow { display_id, name }),
+            Focused::None => {}
+        }
+    }
+}
+
+/// `5000ms`, or `5000.000ms` before Android 11.
+fn timeout(value: &str) -> Option<Duration> {
+    parse_duration(value).or_else(|| {
+        let millis: f64 = value.strip_suffix("ms")?.parse().ok()?;
+        Duration::try_from_secs_f64(millis / 1000.0).ok()
+    })
+}
+
+/// `X: source=0x00001002, min=0.000, max=1079.000, flat=0.000, fuzz=0.000, resolution=0.000`
+fn axis(name: &str, value: &str) -> Option<Axis> {
+    let fields = KeyValues::parse(value);
+    let number = |key| fields.get_float(key).ok();
+    Some(Axis {
+        name: name.to_owned(),
+        source: fields.get("source").and_then(sources),
+        min: number("min")?,
+        max: number("max")?,
+        flat: number("flat").unwrap_or_default(),
+        fuzz: number("fuzz").unwrap_or_default(),
+        resolution: number("resolution").unwrap_or_default(),
+    })
+}
+
+/// `0x00001103`, or `KEYBOARD | TOUCHSCREEN` on Android 13 and later.
+fn sources(value: &str) -> Option<u32> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    names(value)
+        .iter()
+        .map(|name| {
+            Some(match name.as_str() {
+                "KEYBOARD" => 0x101,
+                "DPAD" => 0x201,
+                "GAMEPAD" => 0x401,
+                "TOUCHSCREEN" => SOURCE_TOUCHSCREEN,
+                "MOUSE" => 0x2002,
+                "STYLUS" => 0x4002,
+                "BLUETOOTH_STYLUS" => 0xc002,
+                "TRACKBALL" => 0x10004,
+                "MOUSE_RELATIVE" => 0x20004,
+                "TOUCHPAD" => 0x100008,
+                "TOUCH_NAVIGATION" => 0x200000,
+                "ROTARY_ENCODER" => 0x400000,
+                "JOYSTICK" => 0x1000010,
+                "HDMI" => 0x2000001,
+                "SENSOR" => 0x4000000,
+                // Unknown bits are printed as hex.
+                other => u32::from_str_radix(other.strip_prefix("0x")?, 16).ok()?,
+            })
+        })
+        .try_fold(0, |mask, source| Some(mask | source?))
+}
+
+/// `TOUCH | TOUCH_MT`
+fn names(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys input` of Android 14, cut down to three devices
+    const DUMP: &str = "INPUT MANAGER (dumpsys input)
+
+Input Manager Service (Java) State:
+  Gesture Monitors (implementation of InputManager):
+
+Event Hub State:
+  BuiltInKeyboardId: -2
+  Devices:
+    -1: Virtual
+      Classes: KEYBOARD | ALPHAKEY | VIRTUAL
+      Path: <virtual>
+      Enabled: true
+    2: gpio-keys
+      Classes: KEYBOARD
+      Path: /dev/input/event2
+      Enabled: true
+    4: fts_ts
+      Classes: TOUCH | TOUCH_MT
+      Path: /dev/input/event4
+      Enabled: true
+
+Input Reader State (Nums of device: 3):
+  Device -1: Virtual
+    EventHub Devices: [ -1 ] 
+    Generation: 2
+    IsExternal: false
+    Sources: KEYBOARD | DPAD
+    KeyboardType: alphabetic
+  Device 3: gpio-keys
+    EventHub Devices: [ 2 ] 
+    Generation: 4
+    IsExternal: false
+    Sources: KEYBOARD
+    KeyboardType: non-alphabetic
+  Device 5: fts_ts
+    EventHub Devices: [ 4 ] 
+    Generation: 6
+    IsExternal: false
+    Sources: TOUCHSCREEN
+    KeyboardType: none
+    Motion Ranges:
+      X: source=TOUCHSCREEN, min=0.000, max=1079.000, flat=0.000, fuzz=0.000, resolution=0.000
+      Y: source=TOUCHSCREEN, min=0.000, max=2399.000, flat=0.000, fuzz=0.000, resolution=0.000
+      PRESSURE: source=TOUCHSCREEN, min=0.000, max=1.000, flat=0.000, fuzz=0.000, resolution=0.000
+    Touch Input Mapper (mode - DIRECT):
+      Parameters:
+        GestureMode: MULTI_TOUCH
+      Raw Touch Axes:
+        X: min=0, max=1079, flat=0, fuzz=0, resolution=0
+
+Input Dispatcher State:
+  DispatchEnabled: true
+  DispatchFrozen: false
+  InputFilterEnabled: false
+  FocusedDisplayId: 0
+  FocusedApplications:
+    displayId=0, name='ActivityRecord{abc u0 com.example.app/.MainActivity t123}', dispatchingTimeout=5000ms
+  FocusedWindows:
+    displayId=0, name='1a2b3c com.example.app/com.example.app.MainActivity'
+  FocusRequests:
+    displayId=0, name='1a2b3c com.example.app/com.example.app.MainActivity' result='OK'
+  Connections:
+";
+
+    #[test]
+    fn parses_the_devices() {
+        let input = InputState::parse(DUMP).unwrap();
+        assert_eq!(input.devices.len(), 3);
+        let virtual_keyboard = input.device(-1).unwrap();
+        assert_eq!(virtual_keyboard.sources, 0x301);
+        assert_eq!(
+            virtual_keyboard.classes,
+            ["KEYBOARD", "ALPHAKEY", "VIRTUAL"]
+        );
+        assert_eq!(virtual_keyboard.path.as_deref(), Some("<virtual>"));
+        let touchscreen = input.device_by_name("fts_ts").unwrap();
+        assert_eq!(touchscreen.event_hub_ids, [4]);
+        assert_eq!(touchscreen.external, Some(false));
+        assert!(touchscreen.is_touchscreen());
+        assert!(!touchscreen.has_keys());
+        // The raw axes of the touch mapper are not motion ranges
+        assert_eq!(touchscreen.axes.len(), 3);
+        assert_eq!(
+            touchscreen.axis("PRESSURE").unwrap(),
+            &Axis {
+                name: "PRESSURE".to_owned(),
+                source: Some(SOURCE_TOUCHSCREEN),
+                min: 0.0,
+                max: 1.0,
+                flat: 0.0,
+                fuzz: 0.0,
+                resolution: 0.0,
+            }
+        );
+        assert_eq!(touchscreen.axis("TOUCH_MAJOR"), None);
+    }
+
+    #[test]
+    fn parses_the_dispatcher() {
+        let input = InputState::parse(DUMP).unwrap();
+        assert_eq!(
+            input.dispatcher,
+            Dispatcher {
+                enabled: Some(true),
+                frozen: Some(false),
+                focused_display: Some(0),
+                focused_applications: vec![FocusedApplication {
+                    display_id: Some(0),
+                    name: "ActivityRecord{abc u0 com.example.app/.MainActivity t123}".to_owned(),
+                    dispatching_timeout: Some(Duration::from_secs(5)),
+                }],
+                // Focus requests are not focused windows
+                focused_windows: vec![FocusedWindow {
+                    display_id: Some(0),
+                    name: "1a2b3c com.example.app/com.example.app.MainActivity".to_owned(),
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            InputState::parse(&DUMP.replace('\n', "\r\n")),
+            InputState::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(InputState::parse("").unwrap(), InputState::default());
+    }
+
+    #[test]
+    fn state_of_older_releases() {
+        // Android 9 has a single display, hex sources and the same ids in the reader and event hub
+        let text = "Event Hub State:
+  Devices:
+    3: gpio-keys
+      Classes: 0x00000001
+      Path: /dev/input/event3
+
+Input Reader State:
+  Device 3: gpio-keys
+    IsExternal: false
+    Sources: 0x00000101
+    KeyboardType: 1
+
+Input Dispatcher State:
+  DispatchEnabled: 1
+  DispatchFrozen: 0
+  FocusedApplication: name='AppWindowToken{5d1e token=Token{9f2 ActivityRecord{abc u0 com.example.app/.MainActivity t123}}}', dispatchingTimeout=5000.000ms
+  FocusedWindow: name='Window{1a2b3c u0 com.example.app/com.example.app.MainActivity}'
+";
+        let input = InputState::parse(text).unwrap();
+        let keys = input.device(3).unwrap();
+        assert!(keys.has_keys());
+        assert_eq!(keys.keyboard_type.as_deref(), Some("1"));
+        assert_eq!(keys.path.as_deref(), Some("/dev/input/event3"));
+        let dispatcher = &input.dispatcher;
+        assert_eq!(dispatcher.enabled, Some(true));
+        assert_eq!(dispatcher.frozen, Some(false));
+        assert_eq!(dispatcher.focused_applications[0].display_id, None);
+        assert_eq!(
+            dispatcher.focused_applications[0].dispatching_timeout,
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            input.focused_window().unwrap().name,
+            "Window{1a2b3c u0 com.example.app/com.example.app.MainActivity}"
+        );
+    }
+
+    #[test]
+    fn unknown_sources_and_truncated_axes() {
+        let text = "Input Reader State (Nums of device: 1):
+  Device 7: stylus
+    Sources: STYLUS | 0x10000000
+    Motion Ranges:
+      X: source=STYLUS, min=0.000, max=1079.000
+      Y: source=STYLUS, min=0.000
+";
+        let input = InputState::parse(text).unwrap();
+        let stylus = &input.devices[0];
+        assert_eq!(stylus.sources, 0x1000_4002);
+        // No event hub state, so no classes
+        assert!(stylus.classes.is_empty());
+        assert_eq!(stylus.axes.len(), 1);
+        assert_eq!(stylus.axes[0].flat, 0.0);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let reader = |line: &str| InputState::parse(&format!("{INPUT_READER}:\n  {line}\n"));
+        assert_eq!(
+            reader("Device three: gpio-keys").unwrap_err(),
+            invalid("Device", "three")
+        );
+        assert_eq!(
+            reader("Device 3: gpio-keys\n    Sources: KEYBOARD | FOOTPEDAL").unwrap_err(),
+            invalid("Sources", "KEYBOARD | FOOTPEDAL")
+        );
+    }
+}
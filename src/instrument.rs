@@ -0,0 +1,105 @@
+//! Spans and events for the `tracing` feature, compiled out without it
+//!
+//! Lookups run in a `resolve` span and dumps in a `dump` span covering every attempt, both at debug
+//! level with the service name. Within them, debug events report the lookup, the start and end of
+//! each attempt with its duration and the bytes read, and warnings report retries and reconnects.
+
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "tokio", feature = "tracing"))]
+use tracing::Instrument;
+
+use crate::error::DumpError;
+
+/// Look `service` up with `resolve` in a span of its own.
+pub(crate) fn resolve<T>(service: &str, resolve: impl FnOnce() -> Option<T>) -> Option<T> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("resolve", service).entered();
+    let start = Instant::now();
+    let resolved = resolve();
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        found = resolved.is_some(),
+        elapsed = ?start.elapsed(),
+        "resolved service"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (service, start);
+    resolved
+}
+
+/// Run every attempt of a dump of `service` with `args` in a span of its own.
+pub(crate) fn dump<T>(service: &str, args: &[String], dump: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("dump", service, ?args).entered();
+    #[cfg(not(feature = "tracing"))]
+    let _ = (service, args);
+    dump()
+}
+
+/// Like [`dump`], for the attempts of an asynchronous dump.
+#[cfg(feature = "tokio")]
+pub(crate) async fn dump_async<T>(
+    service: &str,
+    args: &[String],
+    dump: impl Future<Output = T>,
+) -> T {
+    #[cfg(feature = "tracing")]
+    let dump = dump.instrument(tracing::debug_span!("dump", service, ?args));
+    #[cfg(not(feature = "tracing"))]
+    let _ = (service, args);
+    dump.await
+}
+
+/// An attempt starts, returning its start for [`finished`].
+pub(crate) fn started() -> Instant {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("dump started");
+    Instant::now()
+}
+
+/// The attempt since `start` ended, failing with `err` if any, after reading `bytes` from the service
+/// if known.
+pub(crate) fn finished(start: Instant, bytes: Option<u64>, err: Option<&DumpError>) {
+    #[cfg(feature = "tracing")]
+    {
+        let elapsed = start.elapsed();
+        match (bytes, err) {
+            (Some(bytes), None) => tracing::debug!(bytes, ?elapsed, "dump finished"),
+            (None, None) => tracing::debug!(?elapsed, "dump finished"),
+            (Some(bytes), Some(err)) => tracing::debug!(bytes, ?elapsed, %err, "dump failed"),
+            (None, Some(err)) => tracing::debug!(?elapsed, %err, "dump failed"),
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (start, bytes, err);
+}
+
+/// A dump failed with `err` for the `attempts`th time and runs again after `delay`.
+pub(crate) fn retrying(err: &DumpError, attempts: u32, delay: Duration) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(attempts, ?delay, %err, "retrying dump");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (err, attempts, delay);
+}
+
+/// A dump found the service dead with `err`, and it was looked up again.
+pub(crate) fn reconnected(err: &DumpError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(%err, "service died, reconnected");
+    #[cfg(not(feature = "tracing"))]
+    let _ = err;
+}
+
+/// A dump transaction of `service` outlived its deadline and was given up on.
+pub(crate) fn hung(service: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        service,
+        "dump transaction hung past its timeout, giving up on it"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = service;
+}
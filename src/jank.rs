@@ -0,0 +1,352 @@
+//! FPS and jank of a layer, sampled from SurfaceFlinger in the background
+//!
+//! [`JankMonitor`] follows the frames of a layer with a
+//! [`FrameLatencyTracker`](crate::surfaceflinger::FrameLatencyTracker) and, every interval, turns the
+//! frames presented since the last one into [`JankStats`]. Thresholds set on the builder raise a
+//! [`JankEvent::Jank`] when they are crossed and a [`JankEvent::Recovered`] once all of them hold again.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::{jank::{JankEvent, JankMonitor}, Dumpsys};
+//!
+//! # fn foo() -> Option<()> {
+//! let dumpsys = Dumpsys::new("SurfaceFlinger")?;
+//! let (monitor, events) = JankMonitor::builder(dumpsys, "SurfaceView[com.example.game/.MainActivity]#0")
+//!     .interval(Duration::from_secs(1))
+//!     .min_fps(55.0)
+//!     .max_frame_time(Duration::from_millis(33))
+//!     .channel();
+//! for event in events.iter().take(60) {
+//!     match event {
+//!         JankEvent::Stats(stats) => println!("{:.1} fps, p99 {:?}", stats.fps, stats.p99),
+//!         JankEvent::Jank { threshold, .. } => println!("jank: {threshold:?}"),
+//!         JankEvent::Recovered(_) => println!("smooth again"),
+//!         JankEvent::Error(err) => println!("{err}"),
+//!     }
+//! }
+//! monitor.stop();
+//! # Some(())
+//! # }
+//! ```
+
+use std::{
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    builder::Config,
+    cancel::CancelToken,
+    error::DumpError,
+    surfaceflinger::{FrameLatencyTracker, FrameTiming},
+    Dumpsys,
+};
+
+/// Frames presented later than this many refresh periods after the previous one count as janky
+pub(crate) const JANK_FACTOR: f64 = 1.5;
+
+/// Frame statistics of one sampling interval
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JankStats {
+    /// Frames presented in the interval
+    pub frames: usize,
+    pub fps: f64,
+    /// Frames shown for more than 1.5 refresh periods
+    pub janky_frames: usize,
+    /// Vsyncs that passed without a new frame while one was shown for longer than a refresh period
+    pub dropped_frames: u64,
+    /// Median time between presents
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl JankStats {
+    /// Stats of `frames`, presented in order on a display refreshing every `refresh_period`.
+    ///
+    /// `previous` is the present time of the frame before them, so the first frame gets a frame time too.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::{jank::JankStats, surfaceflinger::FrameTiming};
+    ///
+    /// let frames: Vec<_> = [16, 33, 66, 83]
+    ///     .map(|ms| FrameTiming {
+    ///         desired_present: ms * 1_000_000,
+    ///         actual_present: ms * 1_000_000,
+    ///         frame_ready: ms * 1_000_000,
+    ///     })
+    ///     .into();
+    /// let stats = JankStats::from_frames(&frames, Duration::from_nanos(16_666_667), Some(0));
+    /// assert_eq!(stats.frames, 4);
+    /// assert_eq!(stats.janky_frames, 1);
+    /// assert_eq!(stats.dropped_frames, 1);
+    /// assert_eq!(stats.max, Duration::from_millis(33));
+    /// assert!((stats.fps - 48.2).abs() < 0.1);
+    /// ```
+    pub fn from_frames(
+        frames: &[FrameTiming],
+        refresh_period: Duration,
+        previous: Option<u64>,
+    ) -> Self {
+        let presents = previous
+            .into_iter()
+            .chain(frames.iter().map(|frame| frame.actual_present));
+        let mut intervals: Vec<u64> = presents
+            .clone()
+            .zip(presents.skip(1))
+            .map(|(previous, present)| present.saturating_sub(previous))
+            .collect();
+        intervals.sort_unstable();
+
+        let mut stats = Self {
+            frames: frames.len(),
+            ..Self::default()
+        };
+        let total: u64 = intervals.iter().sum();
+        if total == 0 {
+            return stats;
+        }
+        stats.fps = intervals.len() as f64 / Duration::from_nanos(total).as_secs_f64();
+
+        let period = refresh_period.as_nanos() as f64;
+        if period > 0.0 {
+            stats.janky_frames = intervals
+                .iter()
+                .filter(|&&interval| interval as f64 > period * JANK_FACTOR)
+                .count();
+            stats.dropped_frames = intervals
+                .iter()
+                .map(|&interval| ((interval as f64 / period).round() as u64).saturating_sub(1))
+                .sum();
+        }
+
+        let percentile = |percentile: usize| {
+            let index = (intervals.len() * percentile).div_ceil(100).max(1) - 1;
+            Duration::from_nanos(intervals[index])
+        };
+        stats.p50 = percentile(50);
+        stats.p90 = percentile(90);
+        stats.p99 = percentile(99);
+        stats.max = Duration::from_nanos(intervals[intervals.len() - 1]);
+        stats
+    }
+}
+
+/// A limit set on [`JankMonitorBuilder`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Threshold {
+    MinFps(f64),
+    MaxDroppedFrames(u64),
+    /// Limit on the 99th percentile frame time
+    MaxFrameTime(Duration),
+}
+
+impl Threshold {
+    fn crossed(&self, stats: &JankStats) -> bool {
+        match *self {
+            Self::MinFps(fps) => stats.fps < fps,
+            Self::MaxDroppedFrames(dropped) => stats.dropped_frames > dropped,
+            Self::MaxFrameTime(frame_time) => stats.p99 > frame_time,
+        }
+    }
+}
+
+/// What a [`JankMonitor`] reports
+#[derive(Debug)]
+pub enum JankEvent {
+    /// Stats of an interval in which frames were presented
+    Stats(JankStats),
+    /// `threshold` was crossed, sent once until the monitor recovers
+    Jank {
+        stats: JankStats,
+        threshold: Threshold,
+    },
+    /// All thresholds hold again after a [`JankEvent::Jank`]
+    Recovered(JankStats),
+    /// Dumping SurfaceFlinger failed, the monitor keeps trying every interval
+    Error(DumpError),
+}
+
+/// Configure a [`JankMonitor`], see the [module docs](self)
+pub struct JankMonitorBuilder {
+    dumpsys: Dumpsys,
+    layer: String,
+    interval: Duration,
+    thresholds: Vec<Threshold>,
+}
+
+impl JankMonitorBuilder {
+    /// How often stats are computed, 1 second by default.
+    ///
+    /// SurfaceFlinger only keeps the last 128 frames, so intervals much longer than 2 seconds lose frames
+    /// at 60 Hz and above.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn min_fps(self, fps: f64) -> Self {
+        self.threshold(Threshold::MinFps(fps))
+    }
+
+    pub fn max_dropped_frames(self, dropped: u64) -> Self {
+        self.threshold(Threshold::MaxDroppedFrames(dropped))
+    }
+
+    /// Limit the 99th percentile frame time of an interval.
+    pub fn max_frame_time(self, frame_time: Duration) -> Self {
+        self.threshold(Threshold::MaxFrameTime(frame_time))
+    }
+
+    fn threshold(mut self, threshold: Threshold) -> Self {
+        self.thresholds.push(threshold);
+        self
+    }
+
+    /// Start monitoring, passing events to `callback` until it breaks or the monitor stops.
+    pub fn spawn<F>(self, mut callback: F) -> JankMonitor
+    where
+        F: FnMut(JankEvent) -> ControlFlow<()> + Send + 'static,
+    {
+        let cancel = CancelToken::new();
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let token = cancel.clone();
+        let thread = thread::spawn(move || {
+            let dumpsys = self.dumpsys.with_config(Config {
+                cancel: Some(token.clone()),
+                ..(*self.dumpsys.config).clone()
+            });
+            let mut tracker = FrameLatencyTracker::new(dumpsys, self.layer);
+            let mut refresh_period = Duration::ZERO;
+            let mut previous = None;
+            let mut crossed: Vec<Threshold> = Vec::new();
+
+            if let Err(err) = tracker.clear() {
+                if callback(JankEvent::Error(err)).is_break() {
+                    return;
+                }
+            }
+
+            loop {
+                match stopped.recv_timeout(self.interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+
+                let events = match tracker.poll_latency() {
+                    Ok((period, frames)) if !frames.is_empty() => {
+                        if !period.is_zero() {
+                            refresh_period = period;
+                        }
+                        let stats = JankStats::from_frames(&frames, refresh_period, previous);
+                        previous = frames.last().map(|frame| frame.actual_present);
+                        evaluate(stats, &self.thresholds, &mut crossed)
+                    }
+                    Ok(_) => Vec::new(),
+                    Err(err) => vec![JankEvent::Error(err)],
+                };
+
+                for event in events {
+                    if token.is_cancelled() || callback(event).is_break() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        JankMonitor {
+            stop: Some(stop),
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    /// Start monitoring, sending events to the returned receiver until it's dropped.
+    pub fn channel(self) -> (JankMonitor, Receiver<JankEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let monitor = self.spawn(move |event| {
+            tx.send(event)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+        });
+        (monitor, rx)
+    }
+}
+
+/// Computes jank stats of a layer on a background thread, see the [module docs](self)
+///
+/// Stopping, or dropping the monitor, aborts a dump in flight and joins the thread.
+pub struct JankMonitor {
+    stop: Option<Sender<()>>,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl JankMonitor {
+    /// Monitor `layer` of `dumpsys`, a handle to `SurfaceFlinger`.
+    pub fn builder(dumpsys: Dumpsys, layer: impl Into<String>) -> JankMonitorBuilder {
+        JankMonitorBuilder {
+            dumpsys,
+            layer: layer.into(),
+            interval: Duration::from_secs(1),
+            thresholds: Vec::new(),
+        }
+    }
+
+    /// Stop monitoring and wait for the thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.cancel.cancel();
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for JankMonitor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Events for `stats`, updating the thresholds `crossed` so far.
+fn evaluate(
+    stats: JankStats,
+    thresholds: &[Threshold],
+    crossed: &mut Vec<Threshold>,
+) -> Vec<JankEvent> {
+    let mut events = vec![JankEvent::Stats(stats)];
+    let was_janky = !crossed.is_empty();
+
+    for &threshold in thresholds {
+        let is_crossed = threshold.crossed(&stats);
+        let was_crossed = crossed.contains(&threshold);
+        if is_crossed && !was_crossed {
+            crossed.push(threshold);
+            events.push(JankEvent::Jank { stats, threshold });
+        } else if !is_crossed && was_crossed {
+            crossed.retain(|other| *other != threshold);
+        }
+    }
+
+    if was_janky && crossed.is_empty() {
+        events.push(JankEvent::Recovered(stats));
+    }
+    events
+}
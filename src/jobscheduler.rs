@@ -0,0 +1,487 @@
+//! Typed output of `dumpsys jobscheduler`
+//!
+//! Timestamps are kept as printed, in the local time of the device.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::{jobscheduler::JobScheduler, usagestats::StandbyBucket};
+//!
+//! let scheduler = JobScheduler::parse(
+//!     "Started users: [0]
+//! Registered 2 jobs:
+//!   JOB #u0a123/42: 1a2b3c com.example.app/.SyncJobService
+//!     u0a123 tag=*job*/com.example.app/.SyncJobService#42
+//!     Source: uid=u0a123 user=0 pkg=com.example.app
+//!     Required constraints: CHARGING CONNECTIVITY [0x10000001]
+//!     Satisfied constraints: CONNECTIVITY DEVICE_NOT_DOZING [0x12000000]
+//!     Unsatisfied constraints: CHARGING [0x1]
+//!     Standby bucket: WORKING_SET
+//!     Last successful run: 2024-01-01 12:00:00
+//!     Ready: false (job=false user=true !restricted=true pending=false active=false)
+//!   JOB #1000/7: 4d5e6f android/com.android.server.pm.BackgroundDexOptService
+//!     Source: uid=1000 user=0 pkg=android
+//!
+//! Pending queue:
+//!   Pending #0: 4d5e6f #1000/7 android/com.android.server.pm.BackgroundDexOptService
+//!
+//! Active jobs:
+//!   Slot #0: inactive since -1m2s, stopped because: timeout
+//!   Slot #1: 7a8b9c #u0a123/43 com.example.app/.UploadJobService
+//!     Running for: +5s123ms, timeout at: +9m54s",
+//! )
+//! .unwrap();
+//!
+//! let job = scheduler.job(10123, 42).unwrap();
+//! assert_eq!(job.component, "com.example.app/.SyncJobService");
+//! assert_eq!(job.package.as_deref(), Some("com.example.app"));
+//! assert_eq!(job.required_constraints, ["CHARGING", "CONNECTIVITY"]);
+//! assert_eq!(job.unsatisfied_constraints, ["CHARGING"]);
+//! assert_eq!(job.standby_bucket, Some(StandbyBucket::WorkingSet));
+//! assert_eq!(job.last_successful_run.as_deref(), Some("2024-01-01 12:00:00"));
+//! assert_eq!(job.ready, Some(false));
+//!
+//! assert!(scheduler.is_pending(1000, 7));
+//! assert_eq!(scheduler.active.len(), 1);
+//! assert_eq!(scheduler.active[0].slot, 1);
+//! assert_eq!(scheduler.active[0].job.job_id, 43);
+//! assert_eq!(scheduler.active[0].running_for, Some(Duration::from_millis(5123)));
+//! assert_eq!(scheduler.jobs_for_package("com.example.app").count(), 1);
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::{
+        kv::{invalid, parse_duration, KeyValues},
+        parse_uid,
+    },
+    typed,
+    usagestats::StandbyBucket,
+    DumpParse,
+};
+
+const REGISTERED: &str = "Registered ";
+const PENDING_QUEUE: &str = "Pending queue:";
+const ACTIVE_JOBS: &str = "Active jobs:";
+const JOB: &str = "JOB #";
+const PENDING: &str = "Pending #";
+const SLOT: &str = "Slot #";
+const RUNNING_FOR: &str = "Running for: ";
+
+/// A job as in `#u0a123/42 com.example.app/.SyncJobService`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JobRef {
+    /// Uid the job runs as
+    pub uid: u32,
+    pub job_id: i64,
+    /// Service running the job, as in `com.example.app/.SyncJobService`
+    pub component: String,
+}
+
+/// A scheduled job
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Job {
+    pub uid: u32,
+    pub job_id: i64,
+    pub component: String,
+    /// Package the job is scheduled for, which differs from the uid for jobs scheduled by the system
+    pub package: Option<String>,
+    /// Constraints such as `CHARGING`, `IDLE` or `CONNECTIVITY`
+    pub required_constraints: Vec<String>,
+    pub satisfied_constraints: Vec<String>,
+    /// Constraints keeping the job from running
+    pub unsatisfied_constraints: Vec<String>,
+    pub standby_bucket: Option<StandbyBucket>,
+    pub last_successful_run: Option<String>,
+    pub last_failed_run: Option<String>,
+    /// Whether the job could run now
+    pub ready: Option<bool>,
+}
+
+/// A job running in a slot of the scheduler
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActiveJob {
+    pub slot: u32,
+    pub job: JobRef,
+    pub running_for: Option<Duration>,
+}
+
+/// Output of `dumpsys jobscheduler`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JobScheduler {
+    /// Every scheduled job, whether pending, running or waiting for constraints
+    pub jobs: Vec<Job>,
+    /// Jobs ready to run, waiting for a slot
+    pub pending: Vec<JobRef>,
+    pub active: Vec<ActiveJob>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    Jobs,
+    Pending,
+    Active,
+}
+
+impl JobScheduler {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut scheduler = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                section = match trimmed {
+                    PENDING_QUEUE => Section::Pending,
+                    ACTIVE_JOBS => Section::Active,
+                    _ if trimmed.starts_with(REGISTERED) && trimmed.ends_with(" jobs:") => {
+                        Section::Jobs
+                    }
+                    _ => Section::Other,
+                };
+                continue;
+            }
+
+            match section {
+                Section::Other => {}
+                Section::Jobs => scheduler.job_line(trimmed)?,
+                Section::Pending => {
+                    if trimmed.starts_with(PENDING) {
+                        scheduler
+                            .pending
+                            .push(job_ref(trimmed).ok_or_else(|| invalid("Pending", trimmed))?);
+                    }
+                }
+                Section::Active => {
+                    if let Some(slot) = trimmed.strip_prefix(SLOT) {
+                        // Idle slots have no job.
+                        let Some(job) = job_ref(slot) else {
+                            continue;
+                        };
+                        let slot = slot
+                            .split(|c: char| !c.is_ascii_digit())
+                            .next()
+                            .and_then(|slot| slot.parse().ok())
+                            .ok_or_else(|| invalid("Slot", trimmed))?;
+                        scheduler.active.push(ActiveJob {
+                            slot,
+                            job,
+                            running_for: None,
+                        });
+                    } else if let Some(running) = trimmed.strip_prefix(RUNNING_FOR) {
+                        if let Some(active) = scheduler.active.last_mut() {
+                            let running = running.split(',').next().unwrap_or(running);
+                            active.running_for = parse_duration(running);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(scheduler)
+    }
+
+    pub fn job(&self, uid: u32, job_id: i64) -> Option<&Job> {
+        self.jobs
+            .iter()
+            .find(|job| job.uid == uid && job.job_id == job_id)
+    }
+
+    /// Jobs scheduled for `package`.
+    pub fn jobs_for_package<'a>(&'a self, package: &'a str) -> impl Iterator<Item = &'a Job> + 'a {
+        self.jobs
+            .iter()
+            .filter(move |job| job.package.as_deref() == Some(package))
+    }
+
+    pub fn is_pending(&self, uid: u32, job_id: i64) -> bool {
+        self.pending
+            .iter()
+            .any(|job| job.uid == uid && job.job_id == job_id)
+    }
+
+    pub fn is_active(&self, uid: u32, job_id: i64) -> bool {
+        self.active
+            .iter()
+            .any(|active| active.job.uid == uid && active.job.job_id == job_id)
+    }
+
+    /// A line of the `Registered N jobs:` section.
+    fn job_line(&mut self, line: &str) -> Result<(), ParseError> {
+        if line.starts_with(JOB) {
+            let JobRef {
+                uid,
+                job_id,
+                component,
+            } = job_ref(line).ok_or_else(|| invalid("JOB", line))?;
+            self.jobs.push(Job {
+                uid,
+                job_id,
+                component,
+                ..Job::default()
+            });
+            return Ok(());
+        }
+        let Some(job) = self.jobs.last_mut() else {
+            return Ok(());
+        };
+        let Some((key, value)) = line.split_once(':') else {
+            return Ok(());
+        };
+        let value = value.trim();
+        match key {
+            "Source" => job.package = KeyValues::parse(value).get("pkg").map(str::to_owned),
+            "Required constraints" => job.required_constraints = constraints(value),
+            "Satisfied constraints" => job.satisfied_constraints = constraints(value),
+            "Unsatisfied constraints" => job.unsatisfied_constraints = constraints(value),
+            "Standby bucket" => job.standby_bucket = StandbyBucket::from_name(value),
+            "Last successful run" => job.last_successful_run = Some(value.to_owned()),
+            "Last failed run" => job.last_failed_run = Some(value.to_owned()),
+            "Ready" => job.ready = value.split_whitespace().next().map(|ready| ready == "true"),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl DumpParse for JobScheduler {
+    const SERVICE: &'static str = "jobscheduler";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `jobscheduler` and return the jobs scheduled for `package`.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for job in dumpsys_rs::jobscheduler::jobs_for_package("com.example.app").unwrap() {
+///     println!("#{} waits for {:?}", job.job_id, job.unsatisfied_constraints);
+/// }
+/// # }
+/// ```
+pub fn jobs_for_package(package: &str) -> Result<Vec<Job>, DumpError> {
+    let scheduler = typed::dump_service::<JobScheduler>(Vec::<&str>::new())?;
+    Ok(scheduler
+        .jobs
+        .into_iter()
+        .filter(|job| job.package.as_deref() == Some(package))
+        .collect())
+}
+
+/// The `#<uid>/<id>` word and the component after it, as in `#u0a123/42: 1a2b3c com.example/.Job`.
+fn job_ref(line: &str) -> Option<JobRef> {
+    let mut words = line.split_whitespace();
+    let (uid, job_id) = words.find_map(|word| {
+        let (_, job) = word.split_once('#')?;
+        job.trim_end_matches(':').split_once('/')
+    })?;
+    let component = words.find(|word| word.contains('/'))?;
+    Some(JobRef {
+        uid: parse_uid(uid)?,
+        job_id: job_id.parse().ok()?,
+        component: component.to_owned(),
+    })
+}
+
+/// `CHARGING CONNECTIVITY [0x10000001]`
+fn constraints(value: &str) -> Vec<String> {
+    value
+        .split_whitespace()
+        .filter(|constraint| !constraint.starts_with('['))
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys jobscheduler` of Android 14, cut down to a few jobs
+    const DUMP: &str = "Settings:
+  min_ready_non_active_jobs_count=5
+  max_non_active_job_batch_delay_ms=1860000
+
+Started users: [0]
+
+Registered 3 jobs:
+  JOB #u0a231/1001: 5b8c2d1 com.example.app/androidx.work.impl.background.systemjob.SystemJobService
+    u0a231 tag=*job*/com.example.app/androidx.work.impl.background.systemjob.SystemJobService
+    Source: uid=u0a231 user=0 pkg=com.example.app
+    JobInfo:
+      Service: com.example.app/androidx.work.impl.background.systemjob.SystemJobService
+      Requires: charging=false batteryNotLow=false deviceIdle=false
+      Network type: NetworkRequest [ NONE id=0, [ Capabilities: INTERNET&NOT_RESTRICTED&TRUSTED&VALIDATED] ]
+    Required constraints: TIMING_DELAY CONNECTIVITY [0x90000000]
+    Satisfied constraints: TIMING_DELAY DEVICE_NOT_DOZING BACKGROUND_NOT_RESTRICTED WITHIN_QUOTA [0x53400000]
+    Unsatisfied constraints: CONNECTIVITY [0x10000000]
+    Standby bucket: RARE
+    Enqueue time: -1h2m3s
+    Last successful run: 2024-03-12 08:54:31
+    Ready: false (job=false user=true !restricted=true !pending=true !active=true !backingup=true comp=true)
+  JOB #1000/-300: 8d3a0f2 android/com.android.server.pm.BackgroundDexOptService
+    SYSTEM tag=*job*/android/com.android.server.pm.BackgroundDexOptService
+    Source: uid=1000 user=0 pkg=android
+    Required constraints: IDLE CHARGING [0x6]
+    Satisfied constraints: DEVICE_NOT_DOZING [0x2000000]
+    Unsatisfied constraints: IDLE CHARGING [0x6]
+    Standby bucket: EXEMPTED
+    Last failed run: 2024-03-11 03:12:09
+    Ready: false (job=false user=true !restricted=true !pending=true !active=true !backingup=true comp=true)
+  JOB #1000/2113: c02e911 com.google.android.gms/.chimera.GmsInternalBoundBrokerService
+    SYSTEM tag=*job*/com.google.android.gms/.chimera.GmsInternalBoundBrokerService
+    Source: uid=u0a148 user=0 pkg=com.google.android.gms
+    Standby bucket: ACTIVE
+    Ready: true (job=true user=true !restricted=true !pending=false !active=true !backingup=true comp=true)
+
+Pending queue:
+  Pending #0: c02e911 #1000/2113 com.google.android.gms/.chimera.GmsInternalBoundBrokerService
+    Enq: -1s204ms
+
+Active jobs:
+  Slot #0: inactive since -12m5s, stopped because: timeout
+  Slot #1: 7a8b9c0 #u0a231/1002 com.example.app/androidx.work.impl.background.systemjob.SystemJobService
+    Running for: +5s123ms, timeout at: +9m54s
+  Slot #2: inactive since -1h, stopped because: app called jobFinished
+";
+
+    #[test]
+    fn parses_the_jobs() {
+        let scheduler = JobScheduler::parse(DUMP).unwrap();
+        assert_eq!(scheduler.jobs.len(), 3);
+        assert_eq!(
+            scheduler.job(10231, 1001),
+            Some(&Job {
+                uid: 10231,
+                job_id: 1001,
+                component:
+                    "com.example.app/androidx.work.impl.background.systemjob.SystemJobService"
+                        .to_owned(),
+                package: Some("com.example.app".to_owned()),
+                required_constraints: vec!["TIMING_DELAY".to_owned(), "CONNECTIVITY".to_owned()],
+                satisfied_constraints: [
+                    "TIMING_DELAY",
+                    "DEVICE_NOT_DOZING",
+                    "BACKGROUND_NOT_RESTRICTED",
+                    "WITHIN_QUOTA",
+                ]
+                .map(str::to_owned)
+                .to_vec(),
+                unsatisfied_constraints: vec!["CONNECTIVITY".to_owned()],
+                standby_bucket: Some(StandbyBucket::Rare),
+                last_successful_run: Some("2024-03-12 08:54:31".to_owned()),
+                last_failed_run: None,
+                ready: Some(false),
+            })
+        );
+        let dexopt = scheduler.job(1000, -300).unwrap();
+        assert_eq!(
+            dexopt.last_failed_run.as_deref(),
+            Some("2024-03-11 03:12:09")
+        );
+        assert_eq!(dexopt.standby_bucket, Some(StandbyBucket::Exempted));
+    }
+
+    #[test]
+    fn jobs_scheduled_by_the_system_for_a_package() {
+        let scheduler = JobScheduler::parse(DUMP).unwrap();
+        let gms: Vec<_> = scheduler
+            .jobs_for_package("com.google.android.gms")
+            .collect();
+        assert_eq!(gms.len(), 1);
+        assert_eq!(gms[0].uid, 1000);
+        assert!(gms[0].required_constraints.is_empty());
+        assert_eq!(gms[0].ready, Some(true));
+        assert_eq!(scheduler.jobs_for_package("com.example").count(), 0);
+    }
+
+    #[test]
+    fn parses_the_pending_and_active_jobs() {
+        let scheduler = JobScheduler::parse(DUMP).unwrap();
+        assert_eq!(scheduler.pending.len(), 1);
+        assert!(scheduler.is_pending(1000, 2113));
+        assert!(!scheduler.is_pending(10231, 1001));
+        // Inactive slots have no job
+        assert_eq!(
+            scheduler.active,
+            [ActiveJob {
+                slot: 1,
+                job: JobRef {
+                    uid: 10231,
+                    job_id: 1002,
+                    component:
+                        "com.example.app/androidx.work.impl.background.systemjob.SystemJobService"
+                            .to_owned(),
+                },
+                running_for: Some(Duration::from_millis(5123)),
+            }]
+        );
+        assert!(scheduler.is_active(10231, 1002));
+        assert!(!scheduler.is_active(1000, 2113));
+    }
+
+    #[test]
+    fn unknown_standby_buckets_are_dropped() {
+        let text = "Registered 1 jobs:
+  JOB #u0a231/1: 5b8c2d1 com.example.app/.SyncJob
+    Standby bucket: SOMETIMES
+";
+        let scheduler = JobScheduler::parse(text).unwrap();
+        assert_eq!(scheduler.jobs[0].standby_bucket, None);
+        assert_eq!(scheduler.jobs[0].package, None);
+        assert_eq!(scheduler.jobs[0].ready, None);
+    }
+
+    #[test]
+    fn truncated_lines_are_invalid() {
+        let line = "JOB #u0a231: 5b8c2d1 com.example.app/.SyncJob";
+        let text = format!("Registered 1 jobs:\n  {line}\n");
+        assert_eq!(
+            JobScheduler::parse(&text).unwrap_err(),
+            invalid("JOB", line)
+        );
+
+        let line = "Pending #0: c02e911";
+        let text = format!("{PENDING_QUEUE}\n  {line}\n");
+        assert_eq!(
+            JobScheduler::parse(&text).unwrap_err(),
+            invalid("Pending", line)
+        );
+
+        let line = "Slot #x: 7a8b9c0 #u0a231/1002 com.example.app/.SyncJob";
+        let text = format!("{ACTIVE_JOBS}\n  {line}\n");
+        assert_eq!(
+            JobScheduler::parse(&text).unwrap_err(),
+            invalid("Slot", line)
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            JobScheduler::parse(&crlf).unwrap(),
+            JobScheduler::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(JobScheduler::parse("").unwrap(), JobScheduler::default());
+    }
+}
@@ -0,0 +1,162 @@
+//! JSON in a stable schema, for dashboards and log pipelines
+//!
+//! Every document carries `"schema": 1`, bumped on incompatible changes. Times are milliseconds since
+//! the epoch and durations milliseconds, instead of the structs serde derives for them.
+//!
+//! ```text
+//! snapshot: {"schema": 1, "service": "meminfo", "args": ["-a"], "captured_at_ms": 1700000000000,
+//!            "duration_ms": 120, "output": "..."}
+//! parsed:   {"schema": 1, "service": "power", "args": [], "captured_at_ms": ..., "duration_ms": ...,
+//!            "data": {...}}
+//! dump_all: {"schema": 1, "services": [{"service": "power", "output": "..."},
+//!            {"service": "meminfo", "error": {"kind": "timeout", "message": "..."}}]}
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::{json, service_manager, Dumpsys};
+//!
+//! # fn foo() -> Option<()> {
+//! let snapshot = Dumpsys::new("SurfaceFlinger")?.dump_snapshot(&["--latency"]).unwrap();
+//! println!("{}", snapshot.to_json());
+//!
+//! let all = service_manager::dump_all(Duration::from_secs(10), ["meminfo"]).unwrap();
+//! println!("{}", json::dump_all(all));
+//! # Some(())
+//! # }
+//! ```
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::{error::DumpError, Snapshot};
+
+/// Version of the schema, the `schema` field of every document
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SnapshotJson<'a, T> {
+    schema: u32,
+    service: &'a str,
+    args: &'a [String],
+    captured_at_ms: u64,
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<&'a T>,
+}
+
+#[derive(Serialize)]
+struct DumpAllJson {
+    schema: u32,
+    services: Vec<ServiceJson>,
+}
+
+#[derive(Serialize)]
+struct ServiceJson {
+    service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorJson>,
+}
+
+#[derive(Serialize)]
+struct ErrorJson {
+    kind: &'static str,
+    message: String,
+}
+
+impl Snapshot {
+    /// The snapshot as a JSON document, see the [`json`](crate::json) module for the schema.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&envelope::<()>(self, Some(&self.output), None))
+            .expect("snapshots serialize")
+    }
+}
+
+/// `data`, parsed from `snapshot`, as a JSON document together with the capture metadata.
+///
+/// Fails only if `T`'s `Serialize` implementation does.
+pub fn parsed<T: Serialize>(snapshot: &Snapshot, data: &T) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&envelope(snapshot, None, Some(data)))
+}
+
+/// Results of [`dump_all`](crate::service_manager::dump_all) or [`DumpBatch`](crate::DumpBatch) as a JSON
+/// document, in the order given.
+pub fn dump_all(
+    results: impl IntoIterator<Item = (impl AsRef<str>, Result<String, DumpError>)>,
+) -> String {
+    let services = results
+        .into_iter()
+        .map(|(service, result)| {
+            let service = service.as_ref().to_owned();
+            match result {
+                Ok(output) => ServiceJson {
+                    service,
+                    output: Some(output),
+                    error: None,
+                },
+                Err(err) => ServiceJson {
+                    service,
+                    output: None,
+                    error: Some(ErrorJson {
+                        kind: kind(&err),
+                        message: err.to_string(),
+                    }),
+                },
+            }
+        })
+        .collect();
+    serde_json::to_string(&DumpAllJson {
+        schema: SCHEMA_VERSION,
+        services,
+    })
+    .expect("dump results serialize")
+}
+
+fn envelope<'a, T>(
+    snapshot: &'a Snapshot,
+    output: Option<&'a str>,
+    data: Option<&'a T>,
+) -> SnapshotJson<'a, T> {
+    SnapshotJson {
+        schema: SCHEMA_VERSION,
+        service: &snapshot.service,
+        args: &snapshot.args,
+        captured_at_ms: millis(
+            snapshot
+                .captured_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        ),
+        duration_ms: millis(snapshot.duration),
+        output,
+        data,
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Stable name of the error variant, the `kind` of an error.
+fn kind(err: &DumpError) -> &'static str {
+    match err {
+        DumpError::ServiceNotFound { .. } => "service_not_found",
+        DumpError::PermissionDenied { .. } => "permission_denied",
+        DumpError::DeadObject { .. } => "dead_object",
+        DumpError::Status { .. } => "status",
+        DumpError::Io { .. } => "io",
+        DumpError::Timeout { .. } => "timeout",
+        DumpError::Truncated { .. } => "truncated",
+        DumpError::Incomplete { .. } => "incomplete",
+        DumpError::Cancelled { .. } => "cancelled",
+        DumpError::Parse { .. } => "parse",
+    }
+}
@@ -1,17 +1,217 @@
-mod error;
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+#[path = "derive.rs"]
+pub mod __private;
+pub mod activity;
+mod aidl;
+pub mod alarm;
+pub mod alert;
+pub mod appops;
+#[cfg(feature = "tokio")]
+mod asynchronous;
+pub mod audio;
+mod backend;
+mod batch;
+pub mod battery;
+pub mod batterystats;
+mod binder_debug;
+mod builder;
+mod cancel;
+pub mod checkin;
+pub mod collector;
+pub mod compat;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compress;
+pub mod connectivity;
+pub mod cpuinfo;
+mod csv;
+mod death;
+pub mod deviceidle;
+pub mod diff;
+pub mod diskstats;
+pub mod display;
+pub mod dropbox;
+mod dumper;
+mod dumpsys_pool;
+pub mod error;
+mod execution;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod gfxinfo;
+pub mod gpu;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod handler;
+mod history;
+#[cfg(feature = "http")]
+pub mod http;
+mod influx;
+pub mod input;
+mod instrument;
+pub mod jank;
+pub mod jobscheduler;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod location;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod mapped;
+pub mod media_session;
+pub mod meminfo;
+pub mod memtrend;
+#[cfg(not(target_os = "android"))]
+pub mod mock;
+pub mod monitor;
+pub mod netstats;
+pub mod notification;
+mod observer;
+pub mod package;
+pub mod parse;
+mod pipe;
+pub mod power;
+pub mod presets;
+mod priority;
+pub mod procstats;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+mod proto;
+mod reader;
+pub mod recording;
+pub mod remote;
+mod retry;
+mod sampler;
+mod sched;
+pub mod sensorservice;
+pub mod service_manager;
+mod services;
+mod shell;
+mod sink;
+mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stability;
+mod stats;
+#[cfg(feature = "futures")]
+mod stream;
+pub mod surfaceflinger;
+pub mod telephony;
+pub mod testing;
+#[cfg(all(test, not(target_os = "android")))]
+mod tests;
+pub mod thermalservice;
+pub mod trace;
+mod typed;
+#[cfg(feature = "io-uring")]
+mod uring;
+pub mod usagestats;
+pub mod user;
+mod watch;
+mod watchdog;
+pub mod wifi;
+pub mod window;
 
-use std::{io::Read, thread};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, Write},
+    mem,
+    ops::ControlFlow,
+    os::fd::{AsFd, OwnedFd},
+    path::Path,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
 
-use binder::{binder_impl::IBinderInternal, check_service, SpIBinder};
+use binder::{
+    binder_impl::{IBinderInternal, Parcel, TransactionCode},
+    IBinder, SpIBinder, StatusCode,
+};
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncDumpReader;
+pub use backend::Backend;
+pub use batch::{dump_many, DumpBatch};
+pub use binder_debug::ThreadUsage;
+use builder::Config;
+pub use builder::DumpsysBuilder;
+pub use cancel::CancelToken;
+#[cfg(feature = "gzip")]
+pub use compress::GzipSink;
+#[cfg(feature = "zstd")]
+pub use compress::ZstdSink;
+pub use csv::CsvSink;
+pub use death::DeathWatch;
+pub use dumper::Dumper;
+pub use dumpsys_pool::DumpsysPool;
+#[cfg(feature = "derive")]
+pub use dumpsys_rs_derive::DumpParse;
+use execution::Transaction;
+pub use execution::{Execution, WorkerPool};
+pub use history::History;
+pub use influx::InfluxSink;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use mapped::MappedDump;
+pub use observer::DumpObserver;
+use observer::{Attempt, Observed};
+pub use parse::DumpParse;
+pub use priority::DumpPriority;
+pub use reader::DumpReader;
+pub use retry::RetryPolicy;
+pub use sampler::Sampler;
+pub use sched::{SchedPolicy, ThreadOptions};
+pub use services::ServiceName;
+pub use shell::ShellOutput;
+pub use sink::{DumpSink, MemorySink, RotatingFileSink};
+pub use snapshot::Snapshot;
+pub use stats::DumpStats;
+use stats::{LastStats, Tally};
+#[cfg(feature = "futures")]
+pub use stream::DumpStream;
+pub use typed::TypedDumpsys;
+pub use watch::Watch;
+use watchdog::Health;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// How long [`Dumpsys::new`] waits for a service to be registered
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// The main entry of this crate
+///
+/// Clones are cheap and share the binder handle, so a service [refreshed](Dumpsys::refresh) through
+/// one clone is refreshed for all of them, as well as the settings and [`Dumpsys::last_stats`].
+///
+/// Handles are `Send` and `Sync`, so one resolved handle can serve a whole thread pool, by reference or
+/// cloned. Dumps running at once each get a pipe and a transaction of their own, so their outputs never
+/// mix; whether the service answers them in parallel or one after the other is up to the service.
+#[derive(Clone)]
 pub struct Dumpsys {
-    service: SpIBinder,
+    service_name: Arc<str>,
+    service: Arc<RwLock<SpIBinder>>,
+    config: Arc<Config>,
+    last_stats: LastStats,
+    health: Arc<Health>,
+}
+
+const _: () = {
+    const fn shared<T: Clone + Send + Sync>() {}
+    shared::<Dumpsys>();
+};
+
+impl fmt::Debug for Dumpsys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dumpsys")
+            .field("service_name", &self.service_name)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Dumpsys {
     /// Retrieve an existing service and save it for dump, blocking for a few seconds if it doesn't yet exist.
     ///
+    /// See [`Dumpsys::try_new`] and [`Dumpsys::new_with_timeout`] to choose how long to wait, and
+    /// [`service_manager::suggestions`] for the services that were likely meant when there is none.
+    ///
     /// For example
     ///
     /// ```sh
@@ -29,8 +229,188 @@ impl Dumpsys {
     where
         S: AsRef<str>,
     {
-        let service = check_service(service_name.as_ref())?;
-        Some(Self { service })
+        Self::new_with_timeout(service_name, DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    /// Retrieve a service only if it's registered right now, returning `None` immediately otherwise.
+    pub fn try_new<S>(service_name: S) -> Option<Self>
+    where
+        S: AsRef<str>,
+    {
+        Self::builder(service_name).build()
+    }
+
+    /// Retrieve a service, waiting up to `timeout` for it to be registered, e.g. during boot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let dumpsys = Dumpsys::new_with_timeout("SurfaceFlinger", Duration::from_secs(30))?;
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn new_with_timeout<S>(service_name: S, timeout: Duration) -> Option<Self>
+    where
+        S: AsRef<str>,
+    {
+        Self::builder(service_name).connect_timeout(timeout).build()
+    }
+
+    /// Block until the service is registered, asking servicemanager every `poll_interval`.
+    ///
+    /// Fails with [`DumpError::ServiceNotFound`](error::DumpError::ServiceNotFound) if it's still missing at `deadline`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let deadline = Instant::now() + Duration::from_secs(60);
+    /// let dumpsys = Dumpsys::wait_for("SurfaceFlinger", deadline, Duration::from_millis(250)).ok()?;
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn wait_for<S>(
+        service_name: S,
+        deadline: Instant,
+        poll_interval: Duration,
+    ) -> Result<Self, error::DumpError>
+    where
+        S: AsRef<str>,
+    {
+        let service_name = service_name.as_ref();
+        let Some(service) = builder::wait_for(service_name, deadline, poll_interval) else {
+            return Err(error::DumpError::ServiceNotFound {
+                context: error::DumpContext {
+                    service: service_name.to_owned(),
+                    args: Vec::new(),
+                },
+            });
+        };
+
+        Ok(Self::from_parts(
+            service_name.to_owned(),
+            service,
+            Config::default(),
+        ))
+    }
+
+    /// Look the service up again, e.g. after it restarted and the saved handle died.
+    ///
+    /// Waits up to the connect timeout of the builder, keeping the old handle and returning `false` if
+    /// the service doesn't come back.
+    pub fn refresh(&self) -> bool {
+        let Some(service) = builder::connect(&self.service_name, self.config.connect_timeout)
+            .filter(|service| self.config.accepts(service))
+        else {
+            return false;
+        };
+        *self.service.write().unwrap() = service;
+        true
+    }
+
+    /// The binder handle of the service, for transactions of your own without looking it up again.
+    ///
+    /// Returns a new strong reference to the handle rather than `&SpIBinder`: the handle sits behind a
+    /// lock as [`Dumpsys::refresh`] may replace it from another thread, and a borrow of it, or a read
+    /// guard standing in for one, would keep the refresh waiting as long as it's held. Cloning only
+    /// bumps the reference count.
+    pub fn as_binder(&self) -> SpIBinder {
+        self.service()
+    }
+
+    /// Give up dumping and keep only the binder handle of the service.
+    pub fn into_binder(self) -> SpIBinder {
+        self.service()
+    }
+
+    /// The binder extension the service attached, where several HALs publish their debug interfaces.
+    ///
+    /// The extension isn't registered with servicemanager, so it's named `<service>/extension` and can't be
+    /// [refreshed](Dumpsys::refresh).
+    pub fn extension(&self) -> Result<Option<Self>, error::DumpError> {
+        let extension = self
+            .service()
+            .get_extension()
+            .map_err(|status| self.error(status))?;
+        Ok(extension.map(|extension| {
+            Self::from_parts(
+                format!("{}/extension", self.service_name),
+                extension,
+                Config::default(),
+            )
+        }))
+    }
+
+    /// Send a service-specific transaction on the same handle, filling the request in `write` and
+    /// returning the reply.
+    ///
+    /// The NDK starts every request with the interface token of the handle's class, so the handle has to be
+    /// checked against an interface first, see [`DumpsysBuilder::expect_interface`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use binder::binder_impl::FIRST_CALL_TRANSACTION;
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo(dumpsys: Dumpsys) {
+    /// let reply = dumpsys
+    ///     .transact(FIRST_CALL_TRANSACTION + 1, |data| data.write(&42i32))
+    ///     .unwrap();
+    /// let answer: i32 = reply.read().unwrap();
+    /// # }
+    /// ```
+    pub fn transact(
+        &self,
+        code: TransactionCode,
+        write: impl FnOnce(&mut Parcel) -> binder::Result<()>,
+    ) -> Result<Parcel, error::DumpError> {
+        let service = self.service();
+        let mut data = service
+            .prepare_transact()
+            .map_err(|status| self.error(status))?;
+        write(&mut data).map_err(|status| self.error(status))?;
+        service
+            .submit_transact(code, data, 0)
+            .map_err(|status| self.error(status))
+    }
+
+    /// Send the binder ping transaction, a cheap check that the service still answers before an expensive dump.
+    pub fn ping(&self) -> Result<(), error::DumpError> {
+        self.service()
+            .ping_binder()
+            .map_err(|status| self.error(status))
+    }
+
+    /// The AIDL interface descriptor of the service, e.g. `android.ui.ISurfaceComposer`.
+    ///
+    /// Known only once the handle was checked against an interface, see [`DumpsysBuilder::expect_interface`].
+    pub fn interface_descriptor(&self) -> Option<String> {
+        self.service()
+            .get_class()
+            .map(|class| class.get_descriptor())
+    }
+
+    /// Whether the process hosting the service is still running, without a transaction.
+    pub fn is_alive(&self) -> bool {
+        self.service().is_binder_alive()
+    }
+
+    /// Configure timeouts, retries and buffering before resolving the service, see [`DumpsysBuilder`].
+    pub fn builder<S>(service_name: S) -> DumpsysBuilder
+    where
+        S: AsRef<str>,
+    {
+        DumpsysBuilder::new(service_name.as_ref().to_owned())
     }
 
     /// # Example
@@ -46,54 +426,769 @@ impl Dumpsys {
     /// # Some(())
     /// # }
     /// ```
-    pub fn dump(&self, args: &'static [&str]) -> Result<String, error::DumpError> {
-        let mut buf = String::new();
+    ///
+    /// Arguments don't need to be `'static`, so they can be built at runtime:
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo(layer: String) -> Option<()> {
+    /// let result = Dumpsys::new("SurfaceFlinger")?
+    ///     .dump(["--latency".to_string(), layer])
+    ///     .unwrap();
+    /// println!("{result}");
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, error::DumpError> {
+        let args = owned_args(args);
+        let buf = self.dump_to_vec(&args)?;
+        String::from_utf8(buf).map_err(|err| {
+            error::DumpError::invalid_utf8(err).with_context(&self.service_name, &args)
+        })
+    }
 
-        {
-            let mut service = self.service.clone();
-            let (mut read, write) = os_pipe::pipe()?;
-            let handle = thread::spawn(move || service.dump(&write, args));
-            let _ = read.read_to_string(&mut buf);
-            handle.join().unwrap()?;
+    /// Like [`Dumpsys::dump`], but gives up on a service still writing after `timeout`, like `dumpsys -t`.
+    ///
+    /// Overrides the dump timeout of the builder for this call. On [`DumpError::Timeout`](error::DumpError::Timeout)
+    /// the error carries the output received before the deadline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::{error::DumpError, Dumpsys};
+    ///
+    /// # fn foo() -> Option<()> {
+    /// match Dumpsys::new("activity")?.dump_with_timeout(&["activities"], Duration::from_secs(10)) {
+    ///     Ok(output) => println!("{output}"),
+    ///     Err(DumpError::Timeout { partial, .. }) => println!("{}", String::from_utf8_lossy(&partial)),
+    ///     Err(err) => panic!("{err}"),
+    /// }
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_with_timeout(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        timeout: Duration,
+    ) -> Result<String, error::DumpError> {
+        self.with_config(Config {
+            dump_timeout: Some(timeout),
+            ..(*self.config).clone()
+        })
+        .dump(args)
+    }
+
+    /// Like [`Dumpsys::dump`], aborting with [`DumpError::Cancelled`](error::DumpError::Cancelled) once `token`
+    /// is cancelled from another thread.
+    ///
+    /// Overrides the cancel token of the builder for this call. The error carries the output received
+    /// before cancellation.
+    pub fn dump_cancellable(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        token: &CancelToken,
+    ) -> Result<String, error::DumpError> {
+        self.with_config(Config {
+            cancel: Some(token.clone()),
+            ..(*self.config).clone()
+        })
+        .dump(args)
+    }
+
+    /// Like [`Dumpsys::dump`], but clears and reuses `buf` so polling loops don't allocate every call.
+    ///
+    /// Output read before a failure stays in `buf` rather than in the error, lossily converted if it
+    /// isn't UTF-8. A dump that was retried only keeps the output of its last attempt.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let dumpsys = Dumpsys::new("SurfaceFlinger")?;
+    /// let mut buf = String::new();
+    /// loop {
+    ///     dumpsys.dump_into(&["--latency"], &mut buf).unwrap();
+    ///     println!("{buf}");
+    /// }
+    /// # }
+    /// ```
+    pub fn dump_into(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        buf: &mut String,
+    ) -> Result<(), error::DumpError> {
+        let args = owned_args(args);
+        let mut bytes = mem::take(buf).into_bytes();
+        let result = self.run(&args, |read| {
+            bytes.clear();
+            read.read_to_end(&mut bytes)
+        });
+        match String::from_utf8(bytes) {
+            Ok(output) => *buf = output,
+            Err(err) => {
+                *buf = String::from_utf8_lossy(err.as_bytes()).into_owned();
+                result?;
+                return Err(
+                    error::DumpError::invalid_utf8(err).with_context(&self.service_name, &args)
+                );
+            }
         }
+        result.map(drop)
+    }
+
+    /// Read the whole dump as raw bytes, for binary output such as `--proto`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let proto = Dumpsys::new("window")?.dump_to_vec(&["--proto"]).unwrap();
+    /// println!("{} bytes", proto.len());
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_to_vec(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Vec<u8>, error::DumpError> {
+        let mut buf = Vec::with_capacity(self.config.buffer_capacity);
+
+        self.run(args, |read| {
+            buf.clear();
+            read.read_to_end(&mut buf)
+        })
+        .map_err(|err| err.with_partial(|| mem::take(&mut buf)))?;
 
         Ok(buf)
     }
 
+    /// Like [`Dumpsys::dump_to_vec`], into [`Bytes`](bytes::Bytes) that async pipelines and network code
+    /// can share and slice without copying.
+    ///
     /// # Example
     ///
     /// ```
     /// use dumpsys_rs::Dumpsys;
     ///
     /// # fn foo() -> Option<()> {
-    /// let result = Dumpsys::new("SurfaceFlinger")?
+    /// let proto = Dumpsys::new("window")?.dump_bytes(&["--proto"]).unwrap();
+    /// let header = proto.slice(..16.min(proto.len()));
+    /// println!("{} bytes, starting {header:?}", proto.len());
+    /// # Some(())
+    /// # }
+    /// ```
+    #[cfg(feature = "bytes")]
+    pub fn dump_bytes(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<bytes::Bytes, error::DumpError> {
+        Ok(self.dump_bytes_mut(args)?.freeze())
+    }
+
+    /// Like [`Dumpsys::dump_bytes`], into [`BytesMut`](bytes::BytesMut) that can still be written to,
+    /// e.g. to frame the dump for a socket.
+    #[cfg(feature = "bytes")]
+    pub fn dump_bytes_mut(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<bytes::BytesMut, error::DumpError> {
+        let mut buf = bytes::BytesMut::with_capacity(self.config.buffer_capacity);
+
+        self.run(args, |read| {
+            buf.clear();
+            // Reads straight into the buffer, where `read_to_end` would need a `Vec` to copy from.
+            loop {
+                let len = buf.len();
+                buf.resize(len + CHUNK_SIZE, 0);
+                match read.read(&mut buf[len..]) {
+                    Ok(0) => {
+                        buf.truncate(len);
+                        return Ok(());
+                    }
+                    Ok(n) => buf.truncate(len + n),
+                    Err(err) => {
+                        buf.truncate(len);
+                        if err.kind() != io::ErrorKind::Interrupted {
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        })
+        .map_err(|err| err.with_partial(|| buf.to_vec()))?;
+
+        Ok(buf)
+    }
+
+    /// Like [`Dumpsys::dump`], but replaces invalid UTF-8 with `U+FFFD` instead of discarding the output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let result = Dumpsys::new("activity")?.dump_lossy(&["processes"]).unwrap();
+    /// println!("{result}");
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_lossy(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, error::DumpError> {
+        let buf = self.dump_to_vec(args)?;
+        Ok(match String::from_utf8(buf) {
+            Ok(buf) => buf,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        })
+    }
+
+    /// Like [`Dumpsys::dump`], without checking that the output is UTF-8, for services polled so often
+    /// that the check shows up in profiles, e.g. `SurfaceFlinger --latency` at 60 Hz.
+    ///
+    /// # Safety
+    ///
+    /// The service has to be trusted to write UTF-8: any other output makes a `String` that isn't,
+    /// which is undefined behavior.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let surfaceflinger = Dumpsys::new("SurfaceFlinger")?;
+    /// // SAFETY: SurfaceFlinger prints `--latency` as ASCII numbers.
+    /// let latency = unsafe { surfaceflinger.dump_utf8_unchecked(&["--latency"]) }.unwrap();
+    /// println!("{latency}");
+    /// # Some(())
+    /// # }
+    /// ```
+    pub unsafe fn dump_utf8_unchecked(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, error::DumpError> {
+        let buf = self.dump_to_vec(args)?;
+        // SAFETY: the caller trusts the service to have written UTF-8.
+        Ok(unsafe { String::from_utf8_unchecked(buf) })
+    }
+
+    /// Read up to `N` bytes of the dump, returning the zero-padded buffer and how many bytes of it are output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let (result, len) = Dumpsys::new("SurfaceFlinger")?
     ///     .dump_to_byte::<1024>(&["--latency"])
     ///     .unwrap();
-    /// println!("{result}");
+    /// println!("{}", String::from_utf8_lossy(&result[..len]));
     /// # Some(())
     /// # }
     /// ```
     pub fn dump_to_byte<const N: usize>(
         &self,
-        args: &'static [&str],
-    ) -> Result<[u8; N], error::DumpError> {
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<([u8; N], usize), error::DumpError> {
         let mut buf = [0u8; N];
-        let mut total_read = 0;
+        let len = self.dump_into_slice(args, &mut buf)?;
+        buf[len..].fill(0);
+        Ok((buf, len))
+    }
 
-        {
-            let mut service = self.service.clone();
-            let (mut read, write) = os_pipe::pipe()?;
-            let handle = thread::spawn(move || service.dump(&write, args));
-            while total_read < N {
+    /// Read the dump into `buf`, returning how many bytes were written; output beyond `buf.len()` is discarded.
+    ///
+    /// Output read before a failure stays in `buf` rather than in the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let mut buf = [0u8; 4096];
+    /// let len = Dumpsys::new("SurfaceFlinger")?
+    ///     .dump_into_slice(&["--latency"], &mut buf)
+    ///     .unwrap();
+    /// println!("{}", String::from_utf8_lossy(&buf[..len]));
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_into_slice(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        buf: &mut [u8],
+    ) -> Result<usize, error::DumpError> {
+        self.run(args, |read| {
+            let mut total_read = 0;
+            while total_read < buf.len() {
                 let n = read.read(&mut buf[total_read..])?;
                 if n == 0 {
                     break;
                 }
                 total_read += n;
             }
+            Ok(total_read)
+        })
+    }
+
+    /// Stream the dump into `writer` without buffering it in memory, returning the number of bytes written.
+    ///
+    /// Output of a failed attempt is already in `writer` when the [`RetryPolicy`] runs the dump again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let mut file = File::create("/data/local/tmp/meminfo.txt").ok()?;
+    /// Dumpsys::new("meminfo")?
+    ///     .dump_to_writer(&["-a"], &mut file)
+    ///     .unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_to_writer(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        writer: &mut impl Write,
+    ) -> Result<u64, error::DumpError> {
+        self.run(args, |read| io::copy(read, writer))
+    }
+
+    /// Dump into the file at `path`, moving data from the pipe with `splice(2)` instead of through userspace.
+    ///
+    /// The file is created or truncated, returns the number of bytes written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// Dumpsys::new("meminfo")?
+    ///     .dump_to_file(&["-a"], "/data/local/tmp/meminfo.txt")
+    ///     .unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_to_file(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        path: impl AsRef<Path>,
+    ) -> Result<u64, error::DumpError> {
+        let mut file = File::create(path)?;
+
+        self.run(args, |read| {
+            file.set_len(0)?;
+            file.rewind()?;
+            read.splice_to(&mut file)
+        })
+    }
+
+    /// Let the service write straight into `fd` (a `File`, socket or memfd), skipping the internal pipe and copy.
+    ///
+    /// The dump runs on the calling thread and isn't bounded by the dump timeout.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let file = File::create("/data/local/tmp/meminfo.txt").ok()?;
+    /// Dumpsys::new("meminfo")?.dump_to_fd(&["-a"], &file).unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_to_fd(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        fd: impl AsFd,
+    ) -> Result<(), error::DumpError> {
+        let args = owned_args(args);
+        let fd = fd.as_fd();
+
+        self.retry(&args, |_| {
+            let attempt = self.attempt(&args);
+            let result = self.config.backend.dump(
+                &mut self.service(),
+                &self.service_name,
+                &args,
+                self.config.dump_timeout,
+                fd,
+            );
+            attempt.finish(None, result)
+        })
+    }
+
+    /// Start a dump and read its output incrementally, see [`DumpReader`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let mut reader = Dumpsys::new("meminfo")?.dump_reader(&["-a"]).unwrap();
+    /// let mut head = [0u8; 4096];
+    /// let n = reader.read(&mut head).unwrap();
+    /// println!("{}", String::from_utf8_lossy(&head[..n]));
+    /// reader.finish().unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_reader(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<DumpReader, error::DumpError> {
+        let args = owned_args(args);
+        instrument::dump(&self.service_name, &args, || {
+            let (attempt, tally) = (self.attempt(&args), self.tally());
+            match self.start(args.clone(), attempt.observed()) {
+                Ok((read, handle)) => Ok(DumpReader::new(read, handle, attempt, tally)),
+                Err(err) => {
+                    tally.record();
+                    attempt.finish(None, Err(err))
+                }
+            }
+        })
+    }
+
+    /// Iterate over the dump line by line as it arrives; dropping the iterator stops the dump early.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let focus = Dumpsys::new("window")?
+    ///     .dump_lines(&["windows"])
+    ///     .unwrap()
+    ///     .map_while(Result::ok)
+    ///     .find(|line| line.contains("mCurrentFocus"));
+    /// println!("{focus:?}");
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_lines(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<io::Lines<BufReader<DumpReader>>, error::DumpError> {
+        Ok(BufReader::new(self.dump_reader(args)?).lines())
+    }
+
+    /// Feed the dump to `on_chunk` as it arrives; returning [`ControlFlow::Break`] aborts the dump early.
+    ///
+    /// Returns the break value, or [`ControlFlow::Continue`] if the whole dump was consumed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let mut total = 0;
+    /// Dumpsys::new("meminfo")?
+    ///     .dump_with(&["-a"], |chunk| {
+    ///         total += chunk.len();
+    ///         if total > 1024 * 1024 {
+    ///             ControlFlow::Break(())
+    ///         } else {
+    ///             ControlFlow::Continue(())
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_with<B>(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        mut on_chunk: impl FnMut(&[u8]) -> ControlFlow<B>,
+    ) -> Result<ControlFlow<B>, error::DumpError> {
+        let mut buf = vec![0u8; CHUNK_SIZE];
+
+        self.run(args, |read| loop {
+            let n = read.read(&mut buf)?;
+            if n == 0 {
+                return Ok(ControlFlow::Continue(()));
+            }
+            if let ControlFlow::Break(value) = on_chunk(&buf[..n]) {
+                return Ok(ControlFlow::Break(value));
+            }
+        })
+    }
+
+    /// Dump the service and hand the read end of the pipe to `consume`, retrying per [`RetryPolicy`].
+    fn run<T>(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        mut consume: impl FnMut(&mut pipe::Reader) -> io::Result<T>,
+    ) -> Result<T, error::DumpError> {
+        let args = owned_args(args);
+        self.retry(&args, |tally| self.run_once(&args, &mut consume, tally))
+    }
+
+    fn retry<T>(
+        &self,
+        args: &[String],
+        mut attempt: impl FnMut(&mut Tally) -> Result<T, error::DumpError>,
+    ) -> Result<T, error::DumpError> {
+        let mut attempts = 1;
+        let mut reconnected = false;
+        let mut tally = self.tally();
+
+        let result = instrument::dump(&self.service_name, args, || loop {
+            match attempt(&mut tally) {
+                Err(err) => match self.recover(&err, &mut attempts, &mut reconnected) {
+                    Some(delay) => {
+                        tally.retried();
+                        thread::sleep(delay);
+                    }
+                    None => return Err(err.with_context(&self.service_name, args)),
+                },
+                result => return result,
+            }
+        });
+        tally.record();
+        result
+    }
 
-            handle.join().unwrap()?;
+    /// How long to wait before running a dump that failed `attempts` times with `err` again, `None` to give up.
+    ///
+    /// With reconnection enabled a dead service is looked up again, and the first dump after that runs
+    /// right away without counting as an attempt.
+    pub(crate) fn recover(
+        &self,
+        err: &error::DumpError,
+        attempts: &mut u32,
+        reconnected: &mut bool,
+    ) -> Option<Duration> {
+        let dead = self.config.reconnect && err.status() == Some(StatusCode::DEAD_OBJECT);
+        if dead && !*reconnected && self.refresh() {
+            *reconnected = true;
+            instrument::reconnected(err);
+            return Some(Duration::ZERO);
         }
-        Ok(buf)
+
+        let retry = &self.config.retry;
+        if !retry.should_retry(err, *attempts) {
+            return None;
+        }
+        if dead {
+            self.refresh();
+        }
+        let delay = retry.delay(*attempts);
+        instrument::retrying(err, *attempts, delay);
+        *attempts += 1;
+        Some(delay)
+    }
+
+    pub(crate) fn from_parts(service_name: String, service: SpIBinder, config: Config) -> Self {
+        Self {
+            service_name: service_name.into(),
+            service: Arc::new(RwLock::new(service)),
+            config: Arc::new(config),
+            last_stats: LastStats::default(),
+            health: Arc::default(),
+        }
+    }
+
+    /// A failure of a request to the service that isn't a dump.
+    pub(crate) fn error(&self, err: impl Into<error::DumpError>) -> error::DumpError {
+        err.into().with_context(&self.service_name, &[])
     }
+
+    /// The current handle of the service.
+    pub(crate) fn service(&self) -> SpIBinder {
+        self.service.read().unwrap().clone()
+    }
+
+    /// A handle to the same service dumping with other settings.
+    fn with_config(&self, config: Config) -> Self {
+        Self {
+            config: Arc::new(config),
+            ..self.clone()
+        }
+    }
+
+    fn run_once<T>(
+        &self,
+        args: &[String],
+        consume: &mut impl FnMut(&mut pipe::Reader) -> io::Result<T>,
+        tally: &mut Tally,
+    ) -> Result<T, error::DumpError> {
+        let attempt = self.attempt(args);
+        let mut bytes_read = None;
+        let result = self.read_once(args, consume, attempt.observed(), &mut bytes_read, tally);
+        attempt.finish(bytes_read, result)
+    }
+
+    /// An attempt of [`Dumpsys::run`], setting `bytes` once the output was read.
+    fn read_once<T>(
+        &self,
+        args: &[String],
+        consume: &mut impl FnMut(&mut pipe::Reader) -> io::Result<T>,
+        observed: Option<Observed>,
+        bytes: &mut Option<u64>,
+        tally: &mut Tally,
+    ) -> Result<T, error::DumpError> {
+        let (mut read, handle) = self.start(args.to_vec(), observed)?;
+        let output = consume(&mut read);
+        let (bytes_read, truncated) = (read.bytes_read(), read.truncated());
+        *bytes = Some(bytes_read);
+        tally.read(bytes_read, read.reads());
+        // Closing our end unblocks a service still writing output nobody reads.
+        drop(read);
+
+        let output = output.map_err(|err| match err.kind() {
+            io::ErrorKind::TimedOut => error::DumpError::timeout(),
+            _ => err.into(),
+        })?;
+        if truncated {
+            return Err(error::DumpError::truncated(bytes_read));
+        }
+        if let Some(handle) = handle {
+            handle.wait().map_err(|err| err.after_output(bytes_read))?;
+        }
+        Ok(output)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.config
+            .cancel
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Start reporting an attempt to dump with `args`.
+    pub(crate) fn attempt(&self, args: &[String]) -> Attempt {
+        Attempt::start(self.config.observer.as_ref(), &self.service_name, args)
+    }
+
+    fn reader(
+        &self,
+        pipe: impl Into<OwnedFd>,
+        deadline: Option<Instant>,
+        observed: Option<Observed>,
+    ) -> pipe::Reader {
+        let reader = pipe::Reader::new(pipe, deadline)
+            .with_limit(self.config.max_output_bytes)
+            .with_cancel(self.config.cancel.clone())
+            .with_observer(observed);
+        #[cfg(feature = "io-uring")]
+        let reader = if self.config.io_uring {
+            reader.with_io_uring()
+        } else {
+            reader
+        };
+        reader
+    }
+
+    /// Start a dump per [`Execution`], returning its output and the thread still running the transaction.
+    fn start(
+        &self,
+        args: Vec<String>,
+        observed: Option<Observed>,
+    ) -> Result<(pipe::Reader, Option<Transaction>), error::DumpError> {
+        if self.cancelled() {
+            return Err(error::DumpError::cancelled());
+        }
+        if self.is_suspect() {
+            return Err(error::DumpError::timeout());
+        }
+        let deadline = self
+            .config
+            .dump_timeout
+            .map(|timeout| Instant::now() + timeout);
+        let mut service = self.service();
+        let (backend, service_name) = (self.config.backend.clone(), self.service_name.clone());
+        let timeout = self.config.dump_timeout;
+
+        match &self.config.execution {
+            Execution::Spawn => {
+                let (read, write) = pipe::pipe(self.config.pipe_size)?;
+                let write = Arc::new(OwnedFd::from(write));
+                let watch = watchdog::watch(&self.health, &self.service_name, deadline, &write);
+                let threads = self.config.threads.clone();
+                let handle = threads.builder().spawn(move || {
+                    let _watch = watch;
+                    threads.apply()?;
+                    backend.dump(&mut service, &service_name, &args, timeout, write.as_fd())
+                })?;
+
+                Ok((
+                    self.reader(read, deadline, observed),
+                    Some(Transaction::Thread(handle)),
+                ))
+            }
+            Execution::Pool(pool) => {
+                let (read, write) = pipe::pipe(self.config.pipe_size)?;
+                let write = Arc::new(OwnedFd::from(write));
+                let watch = watchdog::watch(&self.health, &self.service_name, deadline, &write);
+                let handle = pool.submit(
+                    move || {
+                        backend.dump(&mut service, &service_name, &args, timeout, write.as_fd())
+                    },
+                    watch,
+                );
+
+                Ok((self.reader(read, deadline, observed), Some(handle)))
+            }
+            Execution::CurrentThread => {
+                let mut file = pipe::memfd(self.config.max_output_bytes)?;
+                let result =
+                    backend.dump(&mut service, &service_name, &args, timeout, file.as_fd());
+                // The service writes through a duplicate sharing the file position, which ends up
+                // past its output while the file may be sized for the limit.
+                let written = file.stream_position()?;
+                file.set_len(written)?;
+                file.rewind()?;
+                if let Err(err) = result {
+                    return Err(err.after_output(written).with_partial(|| {
+                        let mut partial = Vec::new();
+                        let _ = file.read_to_end(&mut partial);
+                        partial
+                    }));
+                }
+
+                Ok((self.reader(file, None, observed), None))
+            }
+        }
+    }
+}
+
+/// Copy the arguments so they can be moved into the dump thread.
+fn owned_args(args: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<String> {
+    args.into_iter()
+        .map(|arg| arg.as_ref().to_owned())
+        .collect()
+}
+
+fn borrowed_args(args: &[String]) -> Vec<&str> {
+    args.iter().map(String::as_str).collect()
 }
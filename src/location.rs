@@ -0,0 +1,562 @@
+//! Typed output of `dumpsys location`
+//!
+//! Providers, their fixes and registrations are read from the per-provider dump of Android 12 and
+//! later, or the `Active Records by Provider` and `Last Known Locations` sections of earlier
+//! versions.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::location::LocationState;
+//!
+//! let location = LocationState::parse(
+//!     "Location Manager State:
+//!   Location Providers:
+//!     gps provider:
+//!       user 0:
+//!         last location=Location[gps 37.421998,-122.084000 hAcc=5.0 et=+1h0m0s0ms alt=10.0 vAcc=3.0]
+//!         enabled=true
+//!       registrations:
+//!         10123/com.example.maps Request[@+1s0ms HIGH_ACCURACY]
+//!         10124/com.example.fitness {bg} Request[@+5m0s0ms BALANCED]
+//!     network provider:
+//!       user 0:
+//!         last location=Location[network 37.4219,-122.0840 hAcc=20.0 et=+59m0s0ms]
+//!         enabled=false
+//!     passive provider:
+//!       user 0:
+//!         enabled=true",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(location.providers.len(), 3);
+//! assert_eq!(location.enabled_providers(), ["gps", "passive"]);
+//!
+//! let gps = location.provider("gps").unwrap();
+//! let fix = gps.last_location.as_ref().unwrap();
+//! assert_eq!(fix.latitude, Some(37.421998));
+//! assert_eq!(fix.accuracy_m, Some(5.0));
+//! assert_eq!(
+//!     fix.age_at(Duration::from_secs(3630)),
+//!     Some(Duration::from_secs(30)),
+//! );
+//! assert_eq!(location.latest_fix().unwrap().provider, "gps");
+//!
+//! assert_eq!(gps.requests.len(), 2);
+//! let maps = &location.requests_of("com.example.maps")[0];
+//! assert_eq!(maps.uid, Some(10123));
+//! assert_eq!(maps.provider, "gps");
+//! assert!(maps.foreground);
+//! assert_eq!(maps.quality.as_deref(), Some("HIGH_ACCURACY"));
+//! assert_eq!(maps.interval, Some(Duration::from_secs(1)));
+//! assert!(!location.requests_of("com.example.fitness")[0].foreground);
+//!
+//! let legacy = LocationState::parse(
+//!     "Location Manager State:
+//!   Active Records by Provider:
+//!     gps:
+//!       UpdateRecord[gps com.example.maps(10123 foreground) Request[ACCURACY_FINE gps requested=+1s0ms fastest=+1s0ms] null]
+//!   Last Known Locations:
+//!     gps: Location[gps 37.421998,-122.084000 hAcc=20 et=+1h2m3s alt=5.0]",
+//! )
+//! .unwrap();
+//! let gps = legacy.provider("gps").unwrap();
+//! assert_eq!(gps.requests[0].quality.as_deref(), Some("ACCURACY_FINE"));
+//! assert_eq!(gps.requests[0].interval, Some(Duration::from_secs(1)));
+//! assert_eq!(gps.last_location.as_ref().unwrap().accuracy_m, Some(20.0));
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{parse_duration, KeyValues},
+    typed, DumpParse,
+};
+
+const PROVIDER: &str = " provider:";
+const LAST_LOCATION: &str = "last location=";
+const LAST_COARSE_LOCATION: &str = "last coarse location=";
+const REGISTRATIONS: &str = "registrations:";
+const LOCATION: &str = "Location[";
+const REQUEST: &str = "Request[";
+const UPDATE_RECORD: &str = "UpdateRecord[";
+const ACTIVE_RECORDS: &str = "Active Records by Provider:";
+const LAST_KNOWN: &str = "Last Known Locations:";
+
+/// A location a provider reported
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fix {
+    pub provider: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Horizontal accuracy in meters
+    pub accuracy_m: Option<f64>,
+    /// Time since boot of the fix
+    pub elapsed_realtime: Option<Duration>,
+}
+
+impl Fix {
+    /// Age of the fix at `now`, the time since boot.
+    pub fn age_at(&self, now: Duration) -> Option<Duration> {
+        now.checked_sub(self.elapsed_realtime?)
+    }
+}
+
+/// A client receiving updates from a provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocationRequest {
+    pub provider: String,
+    pub uid: Option<u32>,
+    pub package: String,
+    pub foreground: bool,
+    /// As in `HIGH_ACCURACY`, or `ACCURACY_FINE` before Android 12
+    pub quality: Option<String>,
+    /// Requested interval between updates
+    pub interval: Option<Duration>,
+}
+
+/// A location provider
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provider {
+    /// As in `gps`, `network` or `fused`
+    pub name: String,
+    /// For the first user listed, `None` before Android 12
+    pub enabled: Option<bool>,
+    pub last_location: Option<Fix>,
+    /// Fix handed to apps holding only coarse location permission
+    pub last_coarse_location: Option<Fix>,
+    pub requests: Vec<LocationRequest>,
+}
+
+/// Output of `dumpsys location`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocationState {
+    pub providers: Vec<Provider>,
+}
+
+/// Each with the indent of its header
+#[derive(Clone, Copy)]
+enum Section {
+    /// Android 12 and later, with the indent of its registrations when inside them
+    Provider(usize, Option<usize>),
+    ActiveRecords(usize),
+    LastKnown(usize),
+    Other,
+}
+
+impl Section {
+    fn indent(self) -> Option<usize> {
+        match self {
+            Self::Provider(indent, _) | Self::ActiveRecords(indent) | Self::LastKnown(indent) => {
+                Some(indent)
+            }
+            Self::Other => None,
+        }
+    }
+}
+
+impl LocationState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut location = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            let indent = line.len() - line.trim_start().len();
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if section.indent().is_some_and(|header| indent <= header) {
+                section = Section::Other;
+            }
+
+            if let Some(name) = line
+                .strip_suffix(PROVIDER)
+                .filter(|name| !name.contains(' '))
+            {
+                location.provider_mut(name);
+                section = Section::Provider(indent, None);
+                continue;
+            }
+            match line {
+                ACTIVE_RECORDS => {
+                    section = Section::ActiveRecords(indent);
+                    continue;
+                }
+                LAST_KNOWN => {
+                    section = Section::LastKnown(indent);
+                    continue;
+                }
+                _ => {}
+            }
+
+            match section {
+                Section::Provider(header, registrations) => {
+                    let Some(provider) = location.providers.last_mut() else {
+                        continue;
+                    };
+                    if registrations.is_some_and(|registrations| indent > registrations) {
+                        if let Some(request) = registration(&provider.name, line) {
+                            provider.requests.push(request);
+                        }
+                        continue;
+                    }
+                    section = Section::Provider(header, None);
+                    if line == REGISTRATIONS {
+                        section = Section::Provider(header, Some(indent));
+                    } else if let Some(fix) = line.strip_prefix(LAST_LOCATION) {
+                        if provider.last_location.is_none() {
+                            provider.last_location = self::fix(fix);
+                        }
+                    } else if let Some(fix) = line.strip_prefix(LAST_COARSE_LOCATION) {
+                        if provider.last_coarse_location.is_none() {
+                            provider.last_coarse_location = self::fix(fix);
+                        }
+                    } else if let Some(enabled) = line.strip_prefix("enabled=") {
+                        provider.enabled.get_or_insert(enabled == "true");
+                    }
+                }
+                Section::ActiveRecords(_) => {
+                    if let Some(request) = update_record(line) {
+                        location
+                            .provider_mut(&request.provider)
+                            .requests
+                            .push(request);
+                    }
+                }
+                Section::LastKnown(_) => {
+                    let Some((name, fix)) = line.split_once(": ") else {
+                        continue;
+                    };
+                    if let Some(fix) = self::fix(fix) {
+                        location.provider_mut(name).last_location = Some(fix);
+                    }
+                }
+                Section::Other => {}
+            }
+        }
+
+        Ok(location)
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&Provider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    /// Names of the providers enabled for the first user.
+    pub fn enabled_providers(&self) -> Vec<&str> {
+        self.providers
+            .iter()
+            .filter(|provider| provider.enabled == Some(true))
+            .map(|provider| provider.name.as_str())
+            .collect()
+    }
+
+    /// The most recent fix of any provider.
+    pub fn latest_fix(&self) -> Option<&Fix> {
+        self.providers
+            .iter()
+            .filter_map(|provider| provider.last_location.as_ref())
+            .max_by_key(|fix| fix.elapsed_realtime)
+    }
+
+    /// Requests of `package` to every provider.
+    pub fn requests_of(&self, package: &str) -> Vec<&LocationRequest> {
+        self.providers
+            .iter()
+            .flat_map(|provider| &provider.requests)
+            .filter(|request| request.package == package)
+            .collect()
+    }
+
+    fn provider_mut(&mut self, name: &str) -> &mut Provider {
+        let index = match self
+            .providers
+            .iter()
+            .position(|provider| provider.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.providers.push(Provider {
+                    name: name.to_owned(),
+                    ..Provider::default()
+                });
+                self.providers.len() - 1
+            }
+        };
+        &mut self.providers[index]
+    }
+}
+
+impl DumpParse for LocationState {
+    const SERVICE: &'static str = "location";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `location` and return the requests of every provider.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for request in dumpsys_rs::location::active_requests().unwrap() {
+///     println!("{} uses {} every {:?}", request.package, request.provider, request.interval);
+/// }
+/// # }
+/// ```
+pub fn active_requests() -> Result<Vec<LocationRequest>, DumpError> {
+    let location = typed::dump_service::<LocationState>(Vec::<&str>::new())?;
+    Ok(location
+        .providers
+        .into_iter()
+        .flat_map(|provider| provider.requests)
+        .collect())
+}
+
+/// `Location[gps 37.421998,-122.084000 hAcc=5.0 et=+1h0m0s0ms alt=10.0 ...]`
+fn fix(text: &str) -> Option<Fix> {
+    let body = text.strip_prefix(LOCATION)?;
+    let (provider, rest) = body.split_once(' ')?;
+    let (coordinates, fields) = rest.split_once(' ').unwrap_or((rest, ""));
+    let (latitude, longitude) = coordinates.split_once(',').unzip();
+    let fields = KeyValues::parse(fields);
+    Some(Fix {
+        provider: provider.to_owned(),
+        latitude: latitude.and_then(|latitude| latitude.parse().ok()),
+        longitude: longitude.and_then(|longitude| longitude.parse().ok()),
+        // `acc` before Android 8.
+        accuracy_m: fields
+            .get_float("hAcc")
+            .or_else(|_| fields.get_float("acc"))
+            .ok(),
+        elapsed_realtime: fields.get_duration("et").ok(),
+    })
+}
+
+/// `10123/com.example.maps {bg} Request[@+1s0ms HIGH_ACCURACY]`
+fn registration(provider: &str, line: &str) -> Option<LocationRequest> {
+    let (identity, rest) = line.split_once(' ')?;
+    let (uid, package) = identity.split_once('/')?;
+    let package = package.split(['/', '[']).next().unwrap_or(package);
+    let flags = rest.split(REQUEST).next().unwrap_or_default();
+    let mut request = request(rest);
+    request.provider = provider.to_owned();
+    request.uid = uid.parse().ok();
+    request.package = package.to_owned();
+    request.foreground = !flags.contains("bg");
+    Some(request)
+}
+
+/// `UpdateRecord[gps com.example.maps(10123 foreground) Request[ACCURACY_FINE gps requested=+1s0ms
+/// fastest=+1s0ms] null]`
+fn update_record(line: &str) -> Option<LocationRequest> {
+    let body = line.strip_prefix(UPDATE_RECORD)?;
+    let (provider, rest) = body.split_once(' ')?;
+    let (package, rest) = rest.split_once('(')?;
+    let (owner, rest) = rest.split_once(')')?;
+    let (uid, state) = owner.split_once(' ').unwrap_or((owner, ""));
+    let mut request = request(rest);
+    request.provider = provider.to_owned();
+    request.uid = uid.parse().ok();
+    request.package = package.to_owned();
+    request.foreground = state == "foreground";
+    Some(request)
+}
+
+/// `Request[@+1s0ms HIGH_ACCURACY, ...]`, or `Request[ACCURACY_FINE gps requested=+1s0ms ...]`
+/// before Android 12.
+fn request(text: &str) -> LocationRequest {
+    let body = text
+        .split_once(REQUEST)
+        .map_or("", |(_, body)| body.split(']').next().unwrap_or(body));
+    let words = body.split([' ', ',']).filter(|word| !word.is_empty());
+    let mut quality = None;
+    let mut interval = None;
+    for word in words {
+        if let Some(duration) = word
+            .strip_prefix('@')
+            .or_else(|| word.strip_prefix("requested="))
+        {
+            interval = interval.or_else(|| parse_duration(duration));
+        } else if quality.is_none() && word.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+            quality = Some(word.to_owned());
+        }
+    }
+    LocationRequest {
+        provider: String::new(),
+        uid: None,
+        package: String::new(),
+        foreground: true,
+        quality,
+        interval,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys location` of Android 14, cut down to three providers and two users
+    const DUMP: &str = "Location Manager State:
+  User Info:
+    current user: [0]
+    visible users: [0, 10]
+  Location Settings:
+    Location Enabled: true
+  Historical Aggregate Location Provider Data:
+    gps:
+      com.example.maps: min/max interval = +1s0ms/+1s0ms, total/active/foreground duration = +2h0m0s0ms/+1h0m0s0ms/+1h0m0s0ms
+  Location Providers:
+    passive provider:
+      user 0:
+        last location=Location[gps 37.421998,-122.084000 hAcc=5.0 et=+1h0m0s0ms alt=10.0 vAcc=3.0]
+        enabled=true
+    network provider:
+      service: ProviderRequest[OFF]
+      user 0:
+        last location=null
+        last coarse location=null
+        enabled=false
+    gps provider:
+      service: ProviderRequest[@+1s0ms HIGH_ACCURACY, WorkSource{10123 com.example.maps}]
+      user 0:
+        last location=Location[gps 37.421998,-122.084000 hAcc=5.0 et=+1h0m0s0ms alt=10.0 vAcc=3.0 vel=0.0 sAcc=0.5 bear=0.0 bAcc=30.0]
+        last coarse location=Location[gps 37.42,-122.08 hAcc=2000.0 et=+59m0s0ms]
+        enabled=true
+      user 10:
+        last location=Location[gps 48.858370,2.294481 hAcc=8.0 et=+30m0s0ms]
+        enabled=false
+      registrations:
+        10123/com.example.maps/listener@1a2b3c Request[@+1s0ms HIGH_ACCURACY]
+        10124/com.example.fitness/pendingintent@4d5e6f {bg} Request[@+5m0s0ms BALANCED, minUpdateInterval=+1m0s0ms]
+      Event Log:
+        10123/com.example.maps added Request[@+1s0ms HIGH_ACCURACY]
+  Geofence Manager:
+    10123/com.example.maps Geofence[37.42,-122.08 100.0m]
+";
+
+    #[test]
+    fn parses_the_providers() {
+        let location = LocationState::parse(DUMP).unwrap();
+        assert_eq!(location.providers.len(), 3);
+        assert_eq!(location.enabled_providers(), ["passive", "gps"]);
+        let gps = location.provider("gps").unwrap();
+        // The fix of the first user listed
+        assert_eq!(
+            gps.last_location.as_ref().unwrap(),
+            &Fix {
+                provider: "gps".to_owned(),
+                latitude: Some(37.421998),
+                longitude: Some(-122.084),
+                accuracy_m: Some(5.0),
+                elapsed_realtime: Some(Duration::from_secs(3600)),
+            }
+        );
+        assert_eq!(
+            gps.last_coarse_location.as_ref().unwrap().accuracy_m,
+            Some(2000.0)
+        );
+        let network = location.provider("network").unwrap();
+        assert_eq!(network.enabled, Some(false));
+        assert_eq!(network.last_location, None);
+        assert_eq!(network.last_coarse_location, None);
+        assert_eq!(location.provider("fused"), None);
+    }
+
+    #[test]
+    fn parses_the_registrations() {
+        let location = LocationState::parse(DUMP).unwrap();
+        let gps = location.provider("gps").unwrap();
+        // Neither the event log nor the geofences are registrations
+        assert_eq!(gps.requests.len(), 2);
+        assert_eq!(
+            gps.requests[1],
+            LocationRequest {
+                provider: "gps".to_owned(),
+                uid: Some(10124),
+                package: "com.example.fitness".to_owned(),
+                foreground: false,
+                quality: Some("BALANCED".to_owned()),
+                interval: Some(Duration::from_secs(300)),
+            }
+        );
+        assert!(location.requests_of("com.example.maps")[0].foreground);
+        assert!(location.requests_of("com.example.other").is_empty());
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            LocationState::parse(&DUMP.replace('\n', "\r\n")),
+            LocationState::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(LocationState::parse("").unwrap(), LocationState::default());
+    }
+
+    #[test]
+    fn state_of_older_releases() {
+        // Android 7 prints the accuracy as `acc` and requests with their provider
+        let text = "Location Manager State:
+  Location Listeners:
+    Receiver[1a2b3c listener UpdateRecord[gps com.example.maps(10123 foreground) Request[ACCURACY_FINE gps requested=+1s0ms fastest=+1s0ms] null]]
+  Active Records by Provider:
+    gps:
+      UpdateRecord[gps com.example.maps(10123 foreground) Request[ACCURACY_FINE gps requested=+1s0ms fastest=+1s0ms] null]
+      UpdateRecord[gps com.example.fitness(10124 background) Request[POWER_LOW gps requested=+5m0s0ms fastest=+1m0s0ms] null]
+    network:
+      UpdateRecord[network com.example.weather(10125) Request[ACCURACY_BLOCK network requested=+30m0s0ms fastest=+10m0s0ms] null]
+  Last Known Locations:
+    gps: Location[gps 37.421998,-122.084000 acc=20 et=+1h2m3s alt=5.0]
+    network: Location[network 37.4219,-122.0840 acc=1500 et=+1h0m0s]
+  Last Known Locations Coarse Intervals:
+    gps: Location[gps 37.42,-122.08 acc=2000 et=+1h2m3s]
+";
+        let location = LocationState::parse(text).unwrap();
+        assert_eq!(location.providers.len(), 2);
+        let gps = location.provider("gps").unwrap();
+        assert_eq!(gps.enabled, None);
+        assert_eq!(gps.requests.len(), 2);
+        assert!(!gps.requests[1].foreground);
+        assert_eq!(gps.requests[1].quality.as_deref(), Some("POWER_LOW"));
+        assert_eq!(gps.last_location.as_ref().unwrap().accuracy_m, Some(20.0));
+        let network = location.provider("network").unwrap();
+        assert!(!network.requests[0].foreground);
+        assert_eq!(location.latest_fix().unwrap().provider, "gps");
+    }
+
+    #[test]
+    fn truncated_and_missing_fields() {
+        let text = "  Location Providers:
+    gps provider:
+      user 0:
+        last location=Location[gps
+        enabled=true
+      registrations:
+        10123/com.example.maps Request[
+        com.example.broken
+";
+        let location = LocationState::parse(text).unwrap();
+        let gps = location.provider("gps").unwrap();
+        assert_eq!(gps.last_location, None);
+        // A request cut short keeps the client
+        assert_eq!(gps.requests.len(), 1);
+        assert_eq!(gps.requests[0].uid, Some(10123));
+        assert_eq!(gps.requests[0].quality, None);
+        assert_eq!(gps.requests[0].interval, None);
+        assert_eq!(location.latest_fix(), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Seek},
+    ops::Deref,
+    os::fd::{AsFd, AsRawFd, BorrowedFd},
+    ptr::NonNull,
+    slice,
+};
+
+use crate::{error::DumpError, pipe, Dumpsys};
+
+/// Seals that make the memfd immutable for everyone, including whoever it's passed on to
+const SEALS: libc::c_int =
+    libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE;
+
+/// Output of [`Dumpsys::dump_to_memfd`], a read-only mapping of a sealed memfd
+///
+/// Dereferences to the output bytes, which the kernel pages in as they're read.
+pub struct MappedDump {
+    file: File,
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: the mapping is read-only and the seals keep anyone from changing the memfd behind it.
+unsafe impl Send for MappedDump {}
+// SAFETY: as above.
+unsafe impl Sync for MappedDump {}
+
+impl MappedDump {
+    /// Seal `file` and map it.
+    fn seal(file: File) -> io::Result<Self> {
+        // SAFETY: `file` is an open memfd created with `MFD_ALLOW_SEALING`.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, SEALS) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len =
+            usize::try_from(file.metadata()?.len()).map_err(|_| io::ErrorKind::OutOfMemory)?;
+        if len == 0 {
+            // Empty mappings don't exist, and an empty slice needs no memory behind it.
+            return Ok(Self {
+                file,
+                ptr: NonNull::dangling(),
+                len,
+            });
+        }
+
+        // SAFETY: maps `len` bytes of an open file read-only at an address of the kernel's choice.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            file,
+            ptr: NonNull::new(ptr.cast()).expect("mmap succeeded"),
+            len,
+        })
+    }
+
+    /// The sealed memfd, e.g. to pass the dump on to another process, or to read it with `pread`.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Deref for MappedDump {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` is a live mapping of `len` bytes, or dangling for no bytes, and the seals keep
+        // it from changing.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for MappedDump {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsFd for MappedDump {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl fmt::Debug for MappedDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedDump")
+            .field("fd", &self.file.as_raw_fd())
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Drop for MappedDump {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: `ptr` and `len` are the mapping made in `seal`, unmapped nowhere else.
+            unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+    }
+}
+
+impl Dumpsys {
+    /// Dump into a sealed memfd mapped read-only, for dumps too large to comfortably hold in a buffer,
+    /// e.g. `meminfo -a --unreachable`.
+    ///
+    /// The output moves from the pipe into the memfd with `splice(2)`, so it's held once, by the
+    /// kernel, and only paged in as it's read. Like [`Dumpsys::dump_to_file`], a retried dump starts
+    /// the memfd over.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let dump = Dumpsys::new("meminfo")?
+    ///     .dump_to_memfd(&["-a", "--unreachable"])
+    ///     .unwrap();
+    /// let leaks = dump
+    ///     .split(|&byte| byte == b'\n')
+    ///     .filter(|line| line.starts_with(b"Unreachable"))
+    ///     .count();
+    /// println!("{leaks} of {} bytes", dump.len());
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_to_memfd(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<MappedDump, DumpError> {
+        let mut file = pipe::memfd(None)?;
+
+        self.run(args, |read| {
+            file.set_len(0)?;
+            file.rewind()?;
+            read.splice_to(&mut file)
+        })?;
+
+        Ok(MappedDump::seal(file)?)
+    }
+}
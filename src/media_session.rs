@@ -0,0 +1,481 @@
+//! Typed output of `dumpsys media_session`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::media_session::{MediaSessions, PlayState};
+//!
+//! let sessions = MediaSessions::parse(
+//!     "MEDIA SESSION SERVICE (dumpsys media_session)
+//!
+//!   Media button session is com.example.music/MusicService (userId=0)
+//!   Sessions Stack - have 2 sessions:
+//!     MusicService com.example.music/MusicService (userId=0)
+//!       ownerPid=4567, ownerUid=10123, userId=0
+//!       package=com.example.music
+//!       active=true
+//!       flags=3
+//!       state=PlaybackState {state=3, position=83500, buffered position=0, speed=1.0, updated=123456789, actions=3669711, custom actions=[], active item id=-1, error=null}
+//!       metadata: size=8, description=Hello, World, Example Artist, null
+//!     VideoSession com.example.video/VideoSession (userId=0)
+//!       ownerPid=5678, ownerUid=10124, userId=0
+//!       package=com.example.video
+//!       active=false
+//!       state=PlaybackState {state=PAUSED(2), position=-1, buffered position=0, speed=0.0, updated=0, actions=0, custom actions=[], active item id=-1, error=null}
+//!       metadata: null",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(sessions.sessions.len(), 2);
+//! let music = sessions.now_playing().unwrap();
+//! assert_eq!(music.package, "com.example.music");
+//! assert_eq!(music.tag, "MusicService");
+//! assert_eq!(music.owner_uid, Some(10123));
+//! let playback = music.playback.as_ref().unwrap();
+//! assert_eq!(playback.state, PlayState::Playing);
+//! assert_eq!(playback.position, Some(Duration::from_millis(83500)));
+//! assert_eq!(playback.speed, 1.0);
+//! let metadata = music.metadata.as_ref().unwrap();
+//! assert_eq!(metadata.title.as_deref(), Some("Hello, World"));
+//! assert_eq!(metadata.artist.as_deref(), Some("Example Artist"));
+//! assert_eq!(metadata.description, None);
+//!
+//! let video = sessions.session("com.example.video").unwrap();
+//! assert_eq!(video.playback.as_ref().unwrap().state, PlayState::Paused);
+//! assert_eq!(video.playback.as_ref().unwrap().position, None);
+//! assert_eq!(video.metadata, None);
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_int, KeyValues},
+    typed, DumpParse,
+};
+
+const USER_ID: &str = " (userId=";
+const PLAYBACK_STATE: &str = "PlaybackState {";
+const DESCRIPTION: &str = "description=";
+
+/// `PlaybackState.STATE_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayState {
+    None,
+    Stopped,
+    Paused,
+    Playing,
+    FastForwarding,
+    Rewinding,
+    Buffering,
+    Error,
+    Connecting,
+    SkippingToPrevious,
+    SkippingToNext,
+    SkippingToQueueItem,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for PlayState {
+    fn from(state: i64) -> Self {
+        match state {
+            0 => Self::None,
+            1 => Self::Stopped,
+            2 => Self::Paused,
+            3 => Self::Playing,
+            4 => Self::FastForwarding,
+            5 => Self::Rewinding,
+            6 => Self::Buffering,
+            7 => Self::Error,
+            8 => Self::Connecting,
+            9 => Self::SkippingToPrevious,
+            10 => Self::SkippingToNext,
+            11 => Self::SkippingToQueueItem,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Playback state reported by a session
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Playback {
+    pub state: PlayState,
+    /// Position at `updated`, `None` when unknown
+    pub position: Option<Duration>,
+    pub buffered_position: Option<Duration>,
+    /// Playback speed, negative when rewinding
+    pub speed: f64,
+    /// When the state was reported, in milliseconds since boot
+    pub updated: i64,
+    pub error: Option<String>,
+}
+
+impl Playback {
+    /// Position at `now`, in milliseconds since boot, extrapolated from the reported speed.
+    pub fn position_at(&self, now: i64) -> Option<Duration> {
+        let position = self.position?;
+        if self.state != PlayState::Playing || now <= self.updated {
+            return Some(position);
+        }
+        let elapsed = (now - self.updated) as f64 * self.speed;
+        Some(Duration::from_millis(
+            (position.as_millis() as f64 + elapsed).max(0.0) as u64,
+        ))
+    }
+}
+
+/// Description of the current item
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    pub title: Option<String>,
+    /// Subtitle of the description, the artist for music
+    pub artist: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A media session
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaSession {
+    /// Tag the app created the session with
+    pub tag: String,
+    pub package: String,
+    pub user: Option<u32>,
+    pub owner_pid: Option<u32>,
+    pub owner_uid: Option<u32>,
+    /// Whether the session receives media buttons and transport controls
+    pub active: bool,
+    pub playback: Option<Playback>,
+    pub metadata: Option<Metadata>,
+}
+
+impl MediaSession {
+    pub fn is_playing(&self) -> bool {
+        self.playback
+            .as_ref()
+            .is_some_and(|playback| playback.state == PlayState::Playing)
+    }
+}
+
+/// Output of `dumpsys media_session`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaSessions {
+    /// In priority order, for each user
+    pub sessions: Vec<MediaSession>,
+}
+
+impl MediaSessions {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut sessions = Self::default();
+        // `MusicService com.example.music/MusicService (userId=0)`, also printed for the media button
+        // session, so only a header followed by the owner starts a session.
+        let mut header = None;
+
+        for line in text.lines().map(str::trim) {
+            if line.ends_with(')') && line.contains(USER_ID) {
+                header = Some(line);
+                continue;
+            }
+            if line.starts_with("ownerPid=") {
+                let header = header.take().ok_or_else(|| invalid("ownerPid", line))?;
+                sessions
+                    .sessions
+                    .push(session(header, &KeyValues::parse(line))?);
+                continue;
+            }
+            let Some(session) = sessions.sessions.last_mut() else {
+                continue;
+            };
+            if let Some(active) = line.strip_prefix("active=") {
+                session.active = active == "true";
+            } else if let Some(state) = line.strip_prefix("state=") {
+                session.playback = playback(state)?;
+            } else if let Some(description) = line.strip_prefix("metadata: ") {
+                session.metadata = metadata(description);
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    /// The first session of `package`.
+    pub fn session(&self, package: &str) -> Option<&MediaSession> {
+        self.sessions
+            .iter()
+            .find(|session| session.package == package)
+    }
+
+    /// The session with the highest priority that is playing.
+    pub fn now_playing(&self) -> Option<&MediaSession> {
+        self.sessions.iter().find(|session| session.is_playing())
+    }
+}
+
+impl DumpParse for MediaSessions {
+    const SERVICE: &'static str = "media_session";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `media_session` and return the session playing, if any.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// if let Some(session) = dumpsys_rs::media_session::now_playing().unwrap() {
+///     let title = session.metadata.and_then(|metadata| metadata.title);
+///     println!("{} plays {title:?}", session.package);
+/// }
+/// # }
+/// ```
+pub fn now_playing() -> Result<Option<MediaSession>, DumpError> {
+    let sessions = typed::dump_service::<MediaSessions>(Vec::<&str>::new())?;
+    Ok(sessions.sessions.into_iter().find(MediaSession::is_playing))
+}
+
+/// `MusicService com.example.music/MusicService (userId=0)` and `ownerPid=4567, ownerUid=10123, userId=0`
+fn session(header: &str, owner: &KeyValues<'_>) -> Result<MediaSession, ParseError> {
+    let (head, user) = header.rsplit_once(USER_ID).unwrap_or((header, ""));
+    let (tag, record) = head
+        .split_once(' ')
+        .ok_or_else(|| invalid("session", header))?;
+    let package = record.split('/').next().unwrap_or(record);
+    Ok(MediaSession {
+        tag: tag.to_owned(),
+        package: package.to_owned(),
+        user: user.trim_end_matches(')').parse().ok(),
+        owner_pid: owner.get_parsed("ownerPid").ok(),
+        owner_uid: owner.get_parsed("ownerUid").ok(),
+        active: false,
+        playback: None,
+        metadata: None,
+    })
+}
+
+/// `PlaybackState {state=3, position=83500, ...}`, with the state as `PLAYING(3)` on Android 13 and
+/// later.
+fn playback(text: &str) -> Result<Option<Playback>, ParseError> {
+    let Some(state) = text.strip_prefix(PLAYBACK_STATE) else {
+        return Ok(None);
+    };
+    // `buffered position` has a space, so the fields are split by hand.
+    let field = |key: &str| {
+        state
+            .trim_end_matches('}')
+            .split(", ")
+            .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+    };
+    let code = field("state").ok_or_else(|| invalid("state", text))?;
+    let code = code
+        .split_once('(')
+        .map_or(code, |(_, code)| code.trim_end_matches(')'));
+    let position = |key| {
+        field(key)
+            .and_then(parse_int)
+            .and_then(|millis| u64::try_from(millis).ok())
+            .map(Duration::from_millis)
+    };
+    Ok(Some(Playback {
+        state: parse_int(code)
+            .ok_or_else(|| invalid("state", code))?
+            .into(),
+        position: position("position"),
+        buffered_position: position("buffered position"),
+        speed: field("speed")
+            .and_then(|speed| speed.parse().ok())
+            .unwrap_or_default(),
+        updated: field("updated").and_then(parse_int).unwrap_or_default(),
+        error: field("error")
+            .filter(|error| *error != "null")
+            .map(str::to_owned),
+    }))
+}
+
+/// `size=8, description=Hello, World, Example Artist, null`, the title, subtitle and description.
+fn metadata(text: &str) -> Option<Metadata> {
+    let (_, description) = text.split_once(DESCRIPTION)?;
+    let mut parts = description.rsplitn(3, ", ");
+    let field = |part: Option<&str>| part.filter(|part| *part != "null").map(str::to_owned);
+    let description = field(parts.next());
+    let artist = field(parts.next());
+    Some(Metadata {
+        title: field(parts.next()),
+        artist,
+        description,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys media_session` of Android 14, cut down to two sessions
+    const DUMP: &str = "MEDIA SESSION SERVICE (dumpsys media_session)
+
+  Media button session is com.example.music/MusicService/1 (userId=0)
+  Sessions Stack - have 2 sessions:
+    MusicService com.example.music/MusicService/1 (userId=0)
+      ownerPid=4567, ownerUid=10123, userId=0
+      package=com.example.music
+      launchIntent=null
+      active=true
+      flags=3
+      rating type=0
+      controllers: 2
+      state=PlaybackState {state=PLAYING(3), position=83500, buffered position=120000, speed=1.0, updated=123456789, actions=3669711, custom actions=[], active item id=-1, error=null}
+      audioAttrs=AudioAttributes: usage=USAGE_MEDIA content=CONTENT_TYPE_MUSIC flags=0x800 tags= bundle=null
+      volumeType=1, controlType=2, max=25, current=12
+      metadata: size=8, description=Hello, World, Example Artist, null
+      queueTitle=null, size=0
+    VideoSession com.example.video/VideoSession/2 (userId=10)
+      ownerPid=5678, ownerUid=1010124, userId=10
+      package=com.example.video
+      active=false
+      flags=1
+      state=PlaybackState {state=ERROR(7), position=-1, buffered position=0, speed=0.0, updated=0, actions=0, custom actions=[], active item id=-1, error=Network unavailable}
+      metadata: null
+  Audio playback (lastly played comes first)
+    uid=10123 packages=com.example.music
+";
+
+    #[test]
+    fn parses_the_sessions() {
+        let sessions = MediaSessions::parse(DUMP).unwrap();
+        assert_eq!(sessions.sessions.len(), 2);
+        assert_eq!(
+            sessions.sessions[0],
+            MediaSession {
+                tag: "MusicService".to_owned(),
+                package: "com.example.music".to_owned(),
+                user: Some(0),
+                owner_pid: Some(4567),
+                owner_uid: Some(10123),
+                active: true,
+                playback: Some(Playback {
+                    state: PlayState::Playing,
+                    position: Some(Duration::from_millis(83500)),
+                    buffered_position: Some(Duration::from_secs(120)),
+                    speed: 1.0,
+                    updated: 123_456_789,
+                    error: None,
+                }),
+                metadata: Some(Metadata {
+                    title: Some("Hello, World".to_owned()),
+                    artist: Some("Example Artist".to_owned()),
+                    description: None,
+                }),
+            }
+        );
+        let video = sessions.session("com.example.video").unwrap();
+        assert_eq!(video.user, Some(10));
+        assert!(!video.active);
+        let playback = video.playback.as_ref().unwrap();
+        assert_eq!(playback.state, PlayState::Error);
+        assert_eq!(playback.position, None);
+        assert_eq!(playback.error.as_deref(), Some("Network unavailable"));
+        assert_eq!(video.metadata, None);
+        assert_eq!(sessions.now_playing().unwrap().tag, "MusicService");
+    }
+
+    #[test]
+    fn extrapolates_the_position() {
+        let sessions = MediaSessions::parse(DUMP).unwrap();
+        let playback = sessions.sessions[0].playback.as_ref().unwrap();
+        assert_eq!(
+            playback.position_at(123_466_789),
+            Some(Duration::from_millis(93500))
+        );
+        // Before the update the position is the reported one
+        assert_eq!(playback.position_at(0), Some(Duration::from_millis(83500)));
+        let rewinding = Playback {
+            state: PlayState::Playing,
+            speed: -2.0,
+            ..playback.clone()
+        };
+        assert_eq!(rewinding.position_at(123_556_789), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            MediaSessions::parse(&DUMP.replace('\n', "\r\n")),
+            MediaSessions::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(MediaSessions::parse("").unwrap(), MediaSessions::default());
+    }
+
+    #[test]
+    fn states_of_older_releases() {
+        // Android 12 prints the state as a bare code
+        let text = "    Session com.example.music/MusicService (userId=0)
+      ownerPid=4567, ownerUid=10123, userId=0
+      active=true
+      state=PlaybackState {state=6, position=0, buffered position=0, speed=0.0, updated=42, actions=0, custom actions=[], active item id=-1, error=null}
+";
+        let sessions = MediaSessions::parse(text).unwrap();
+        let playback = sessions.sessions[0].playback.as_ref().unwrap();
+        assert_eq!(playback.state, PlayState::Buffering);
+        assert_eq!(playback.updated, 42);
+        assert!(!sessions.sessions[0].is_playing());
+        assert_eq!(sessions.now_playing(), None);
+    }
+
+    #[test]
+    fn missing_and_unknown_fields() {
+        let text = "    MusicService com.example.music/MusicService (userId=0)
+      ownerPid=4567
+      state=PlaybackState {state=UNKNOWN(42)}
+      metadata: size=0
+";
+        let sessions = MediaSessions::parse(text).unwrap();
+        let session = &sessions.sessions[0];
+        assert_eq!(session.owner_uid, None);
+        let playback = session.playback.as_ref().unwrap();
+        assert_eq!(playback.state, PlayState::Other(42));
+        assert_eq!(playback.position, None);
+        assert_eq!(playback.speed, 0.0);
+        // Metadata without a description
+        assert_eq!(session.metadata, None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(
+            MediaSessions::parse("      ownerPid=4567, ownerUid=10123").unwrap_err(),
+            invalid("ownerPid", "ownerPid=4567, ownerUid=10123")
+        );
+        assert_eq!(
+            MediaSessions::parse("    MusicService (userId=0)\n      ownerPid=4567").unwrap_err(),
+            invalid("session", "MusicService (userId=0)")
+        );
+
+        let state = |state: &str| {
+            MediaSessions::parse(&format!(
+                "    Session com.example.music/Session (userId=0)\n      ownerPid=1\n      state={state}\n"
+            ))
+        };
+        // Cut short before the state
+        assert_eq!(
+            state("PlaybackState {position=0").unwrap_err(),
+            invalid("state", "PlaybackState {position=0")
+        );
+        assert_eq!(
+            state("PlaybackState {state=PLAYING}").unwrap_err(),
+            invalid("state", "PLAYING")
+        );
+    }
+}
@@ -0,0 +1,722 @@
+//! Typed output of `dumpsys meminfo`
+//!
+//! Sizes are in KiB, as printed by the service.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::meminfo::MemInfo;
+//!
+//! let meminfo = MemInfo::parse(
+//!     "Applications Memory Usage (in Kilobytes):
+//! Uptime: 123456 Realtime: 123456
+//!
+//! Total PSS by process:
+//!     250,123K: system (pid 1234)
+//!     150,000K: com.android.systemui (pid 2345 / activities)
+//!
+//! Total PSS by OOM adjustment:
+//!     250,123K: System
+//!         250,123K: system (pid 1234)
+//!     150,000K: Persistent
+//!         150,000K: com.android.systemui (pid 2345 / activities)
+//!
+//! Total RAM: 7,823,456K (status normal)
+//!  Free RAM: 3,456,789K (  123,456K cached pss + 2,000,000K cached kernel +   333,333K free)
+//!  Used RAM: 4,000,000K (3,500,000K used pss +   500,000K kernel)
+//!  Lost RAM:   300,000K
+//!      ZRAM:   100,000K physical used for   400,000K in swap (2,097,148K total swap)",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(meminfo.total_ram_kb, 7_823_456);
+//! assert_eq!(meminfo.lost_ram_kb, 300_000);
+//! assert_eq!(meminfo.zram.unwrap().swap_total_kb, 2_097_148);
+//! assert_eq!(meminfo.processes[1].name, "com.android.systemui");
+//! assert!(meminfo.processes[1].activities);
+//! assert_eq!(meminfo.oom_adjustments[0].name, "System");
+//! assert_eq!(meminfo.oom_adjustments[1].processes[0].pid, 2345);
+//! ```
+//!
+//! [`PackageMemInfo`] parses the detailed breakdown of `dumpsys meminfo <package>`.
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::invalid,
+    typed, DumpParse,
+};
+
+const PSS_BY_PROCESS: &str = "Total PSS by process:";
+const PSS_BY_OOM_ADJUSTMENT: &str = "Total PSS by OOM adjustment:";
+const PACKAGE_HEADER: &str = "** MEMINFO in pid ";
+const APP_SUMMARY: &str = "App Summary";
+const TOTAL: &str = "TOTAL";
+
+/// PSS of one process
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessPss {
+    pub name: String,
+    pub pid: u32,
+    pub pss_kb: u64,
+    /// Swapped out memory, on releases that report it
+    pub swap_kb: Option<u64>,
+    /// Whether the process has activities
+    pub activities: bool,
+}
+
+/// PSS of the processes in one OOM adjustment category, such as `Foreground` or `Cached`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OomAdjustment {
+    pub name: String,
+    pub pss_kb: u64,
+    pub processes: Vec<ProcessPss>,
+}
+
+/// Compressed swap in RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Zram {
+    /// RAM taken by the compressed pages
+    pub physical_kb: u64,
+    /// Pages swapped out, before compression
+    pub swap_used_kb: u64,
+    pub swap_total_kb: u64,
+}
+
+/// Output of `dumpsys meminfo`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemInfo {
+    pub total_ram_kb: u64,
+    /// Free memory, counting cached processes and kernel caches as free
+    pub free_ram_kb: u64,
+    pub used_ram_kb: u64,
+    /// Memory unaccounted for by PSS and the kernel, e.g. leaked by drivers
+    pub lost_ram_kb: u64,
+    pub zram: Option<Zram>,
+    /// Processes by PSS, largest first
+    pub processes: Vec<ProcessPss>,
+    pub oom_adjustments: Vec<OomAdjustment>,
+}
+
+impl MemInfo {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut meminfo = Self::default();
+        let mut section = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.ends_with(':') {
+                section = Some(trimmed);
+                continue;
+            }
+
+            match section {
+                Some(PSS_BY_PROCESS) => meminfo.processes.push(process(trimmed)?),
+                Some(PSS_BY_OOM_ADJUSTMENT) if trimmed.contains("(pid ") => {
+                    let process = process(trimmed)?;
+                    match meminfo.oom_adjustments.last_mut() {
+                        Some(category) => category.processes.push(process),
+                        None => return Err(invalid(PSS_BY_OOM_ADJUSTMENT, trimmed)),
+                    }
+                }
+                Some(PSS_BY_OOM_ADJUSTMENT) => {
+                    let (pss_kb, _, name) = entry(trimmed)?;
+                    meminfo.oom_adjustments.push(OomAdjustment {
+                        name: name.to_owned(),
+                        pss_kb,
+                        processes: Vec::new(),
+                    });
+                }
+                _ => meminfo.parse_total(trimmed)?,
+            }
+        }
+
+        Ok(meminfo)
+    }
+
+    /// The process called `name`.
+    pub fn process(&self, name: &str) -> Option<&ProcessPss> {
+        self.processes.iter().find(|process| process.name == name)
+    }
+
+    /// Parse a line of the totals at the end, ignoring anything else.
+    fn parse_total(&mut self, line: &str) -> Result<(), ParseError> {
+        let Some((key, value)) = line.split_once(": ") else {
+            return Ok(());
+        };
+        let first = || sizes(value).next().ok_or_else(|| invalid(key, value));
+        match key {
+            "Total RAM" => self.total_ram_kb = first()?,
+            "Free RAM" => self.free_ram_kb = first()?,
+            "Used RAM" => self.used_ram_kb = first()?,
+            "Lost RAM" => self.lost_ram_kb = first()?,
+            "ZRAM" => {
+                // 100,000K physical used for 400,000K in swap (2,097,148K total swap)
+                let mut sizes = sizes(value);
+                let (Some(physical_kb), Some(swap_used_kb), Some(swap_total_kb)) =
+                    (sizes.next(), sizes.next(), sizes.next())
+                else {
+                    return Err(invalid(key, value));
+                };
+                self.zram = Some(Zram {
+                    physical_kb,
+                    swap_used_kb,
+                    swap_total_kb,
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl DumpParse for MemInfo {
+    const SERVICE: &'static str = "meminfo";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// One row of the memory table of `dumpsys meminfo <package>`, such as `Native Heap` or `TOTAL`
+///
+/// Columns other than the PSS total are missing on rows and releases that don't print them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryCategory {
+    pub name: String,
+    pub pss_kb: u64,
+    pub private_dirty_kb: Option<u64>,
+    pub private_clean_kb: Option<u64>,
+    pub swap_pss_dirty_kb: Option<u64>,
+    pub rss_kb: Option<u64>,
+    pub heap_size_kb: Option<u64>,
+    pub heap_alloc_kb: Option<u64>,
+    pub heap_free_kb: Option<u64>,
+}
+
+/// The `App Summary` of `dumpsys meminfo <package>`, in PSS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AppSummary {
+    pub java_heap_kb: u64,
+    pub native_heap_kb: u64,
+    pub code_kb: u64,
+    pub stack_kb: u64,
+    pub graphics_kb: u64,
+    pub private_other_kb: u64,
+    pub system_kb: u64,
+    pub total_pss_kb: u64,
+    /// On releases that report RSS
+    pub total_rss_kb: Option<u64>,
+    pub total_swap_pss_kb: Option<u64>,
+}
+
+/// Output of `dumpsys meminfo <package>` for the first process it lists
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::meminfo::PackageMemInfo;
+///
+/// let meminfo = PackageMemInfo::parse(
+///     "** MEMINFO in pid 1234 [com.example.app] **
+///                    Pss  Private  Private  SwapPss      Rss     Heap     Heap     Heap
+///                  Total    Dirty    Clean    Dirty    Total     Size    Alloc     Free
+///                 ------   ------   ------   ------   ------   ------   ------   ------
+///   Native Heap    10468    10408        0        0    12000    20480    14462     6017
+///   Dalvik Heap     2977     2864        0        0     4000     8329     4165     4164
+///      .so mmap     3439      216     2176        0
+///         TOTAL    30000    24000     3900        0    40000    28809    18627    10181
+///
+///  App Summary
+///                        Pss(KB)                        Rss(KB)
+///                         ------                         ------
+///            Java Heap:     4364                          12345
+///          Native Heap:    10408                          12000
+///             Graphics:     6880                           6880
+///
+///            TOTAL PSS:    30000            TOTAL RSS:    40000      TOTAL SWAP PSS:        0",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(meminfo.package, "com.example.app");
+/// assert_eq!(meminfo.category("Native Heap").unwrap().heap_alloc_kb, Some(14462));
+/// assert_eq!(meminfo.category(".so mmap").unwrap().private_clean_kb, Some(2176));
+/// assert_eq!(meminfo.total().unwrap().private_dirty_kb, Some(24000));
+/// assert_eq!(meminfo.summary.java_heap_kb, 4364);
+/// assert_eq!(meminfo.summary.total_rss_kb, Some(40000));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageMemInfo {
+    pub pid: u32,
+    /// Process name, the package name for its main process
+    pub package: String,
+    /// Rows of the memory table, ending with `TOTAL`
+    pub categories: Vec<MemoryCategory>,
+    pub summary: AppSummary,
+}
+
+impl PackageMemInfo {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text
+            .lines()
+            .skip_while(|line| !line.trim_start().starts_with(PACKAGE_HEADER));
+        let header = lines
+            .next()
+            .ok_or_else(|| ParseError::Missing(PACKAGE_HEADER.trim().to_owned()))?;
+        // ** MEMINFO in pid 1234 [com.example.app] **
+        let (pid, package) = header
+            .trim()
+            .trim_start_matches(PACKAGE_HEADER)
+            .trim_end_matches(" **")
+            .split_once(" [")
+            .ok_or_else(|| invalid("pid", header))?;
+        let mut meminfo = Self {
+            pid: pid.parse().map_err(|_| invalid("pid", header))?,
+            package: package.trim_end_matches(']').to_owned(),
+            ..Self::default()
+        };
+
+        let mut columns: Vec<String> = Vec::new();
+        let mut header_words: Option<Vec<&str>> = None;
+        let mut in_summary = false;
+
+        for line in lines {
+            let trimmed = line.trim();
+            if trimmed.starts_with(PACKAGE_HEADER) {
+                break;
+            }
+            if trimmed == APP_SUMMARY {
+                in_summary = true;
+                continue;
+            }
+            if in_summary {
+                meminfo.summary.parse_line(trimmed)?;
+                if trimmed.starts_with(TOTAL) {
+                    break;
+                }
+                continue;
+            }
+
+            let words: Vec<&str> = trimmed.split_whitespace().collect();
+            let values = words
+                .iter()
+                .rev()
+                .take_while(|word| word.bytes().all(|b| b.is_ascii_digit()))
+                .count();
+            if words.is_empty() || words[0].starts_with('-') {
+                continue;
+            }
+            if values == 0 {
+                // The two header lines, whose words are joined into column names such as "Pss Total"
+                match header_words.take() {
+                    Some(first) if columns.is_empty() && first.len() == words.len() => {
+                        columns = first
+                            .iter()
+                            .zip(&words)
+                            .map(|(first, second)| format!("{first} {second}"))
+                            .collect();
+                    }
+                    _ => header_words = Some(words),
+                }
+                continue;
+            }
+            if values == words.len() {
+                continue;
+            }
+
+            let name = words[..words.len() - values].join(" ");
+            meminfo.categories.push(category(
+                name,
+                &columns,
+                &words[words.len() - values..],
+                line,
+            )?);
+        }
+
+        Ok(meminfo)
+    }
+
+    /// Dump and parse the memory of `package`, or of any process name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::meminfo::PackageMemInfo;
+    ///
+    /// # fn foo() {
+    /// let meminfo = PackageMemInfo::for_package("com.example.app").unwrap();
+    /// println!("{} KiB of Java heap", meminfo.summary.java_heap_kb);
+    /// # }
+    /// ```
+    pub fn for_package(package: &str) -> Result<Self, DumpError> {
+        typed::dump_service::<Self>([package])
+    }
+
+    /// The row called `name`, e.g. `Native Heap`.
+    pub fn category(&self, name: &str) -> Option<&MemoryCategory> {
+        self.categories
+            .iter()
+            .find(|category| category.name == name)
+    }
+
+    /// The `TOTAL` row.
+    pub fn total(&self) -> Option<&MemoryCategory> {
+        self.category(TOTAL)
+    }
+}
+
+impl DumpParse for PackageMemInfo {
+    const SERVICE: &'static str = MemInfo::SERVICE;
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+impl AppSummary {
+    /// Parse `Java Heap: 4364 12345` or `TOTAL PSS: 30000 TOTAL RSS: 40000 ...` into `self`.
+    fn parse_line(&mut self, line: &str) -> Result<(), ParseError> {
+        // "TOTAL PSS", "30000 TOTAL RSS", "40000 TOTAL SWAP PSS", "0"
+        let segments: Vec<&str> = line.split(':').collect();
+        for pair in segments.windows(2) {
+            let label = pair[0]
+                .split_whitespace()
+                .skip_while(|word| word.bytes().all(|b| b.is_ascii_digit()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let Some(value) = pair[1].split_whitespace().next() else {
+                continue;
+            };
+            let value = value.parse().map_err(|_| invalid(&label, value))?;
+            match label.as_str() {
+                "Java Heap" => self.java_heap_kb = value,
+                "Native Heap" => self.native_heap_kb = value,
+                "Code" => self.code_kb = value,
+                "Stack" => self.stack_kb = value,
+                "Graphics" => self.graphics_kb = value,
+                "Private Other" => self.private_other_kb = value,
+                "System" => self.system_kb = value,
+                "TOTAL" | "TOTAL PSS" => self.total_pss_kb = value,
+                "TOTAL RSS" => self.total_rss_kb = Some(value),
+                "TOTAL SWAP PSS" => self.total_swap_pss_kb = Some(value),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A row of the memory table, with `values` in the order of `columns`.
+fn category(
+    name: String,
+    columns: &[String],
+    values: &[&str],
+    line: &str,
+) -> Result<MemoryCategory, ParseError> {
+    let values = values
+        .iter()
+        .map(|value| value.parse::<u64>().map_err(|_| invalid(&name, line)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let column = |column: &str| {
+        columns
+            .iter()
+            .position(|name| name == column)
+            .and_then(|i| values.get(i).copied())
+    };
+
+    Ok(MemoryCategory {
+        pss_kb: column("Pss Total").unwrap_or(values[0]),
+        private_dirty_kb: column("Private Dirty"),
+        private_clean_kb: column("Private Clean"),
+        swap_pss_dirty_kb: column("SwapPss Dirty"),
+        rss_kb: column("Rss Total"),
+        heap_size_kb: column("Heap Size"),
+        heap_alloc_kb: column("Heap Alloc"),
+        heap_free_kb: column("Heap Free"),
+        name,
+    })
+}
+
+/// `150,000K: com.android.systemui (pid 2345 / activities)`
+fn process(line: &str) -> Result<ProcessPss, ParseError> {
+    let (pss_kb, swap_kb, rest) = entry(line)?;
+    let (name, pid) = rest
+        .split_once(" (pid ")
+        .ok_or_else(|| invalid("pid", line))?;
+    let digits = pid.bytes().take_while(u8::is_ascii_digit).count();
+
+    Ok(ProcessPss {
+        name: name.to_owned(),
+        pid: pid[..digits].parse().map_err(|_| invalid("pid", line))?,
+        pss_kb,
+        swap_kb,
+        activities: pid.contains("activities"),
+    })
+}
+
+/// Size, swap and label of `250,123K: system`, or of `335,642K: system (pid 1615)   (   39,431K in swap)`
+/// where swap is counted, with the label padded to 60 columns before the swap.
+fn entry(line: &str) -> Result<(u64, Option<u64>, &str), ParseError> {
+    let (size, label) = line.split_once(": ").ok_or_else(|| invalid("size", line))?;
+    let size = kilobytes(size.trim()).ok_or_else(|| invalid("size", line))?;
+    let Some((label, swap)) = label
+        .trim_end()
+        .strip_suffix(" in swap)")
+        .and_then(|label| label.rsplit_once('('))
+    else {
+        return Ok((size, None, label.trim()));
+    };
+    let swap = kilobytes(swap.trim()).ok_or_else(|| invalid("swap", line))?;
+    Ok((size, Some(swap), label.trim()))
+}
+
+/// Every `1,234K` size in `text`, in order.
+fn sizes(text: &str) -> impl Iterator<Item = u64> + '_ {
+    text.split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter_map(kilobytes)
+}
+
+/// `1,234K` as 1234.
+fn kilobytes(text: &str) -> Option<u64> {
+    let digits = text.strip_suffix('K')?;
+    if digits.is_empty() {
+        return None;
+    }
+    digits.replace(',', "").parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys meminfo` of a device counting swap, cut down to a few processes
+    const SWAP: &str = "Applications Memory Usage (in Kilobytes):
+Uptime: 1236295 Realtime: 1236295
+
+Total PSS by process:
+    335,642K: system (pid 1615)                                           (   39,431K in swap)
+    201,774K: com.android.systemui (pid 1871 / activities)                (   28,610K in swap)
+     52,330K: surfaceflinger (pid 704)                                    (    3,104K in swap)
+
+Total PSS by OOM adjustment:
+    102,633K: Native                                                      (   20,100K in swap)
+         52,330K: surfaceflinger (pid 704)                                (    3,104K in swap)
+    335,642K: System                                                      (   39,431K in swap)
+        335,642K: system (pid 1615)                                       (   39,431K in swap)
+    201,774K: Persistent                                                  (   28,610K in swap)
+        201,774K: com.android.systemui (pid 1871 / activities)            (   28,610K in swap)
+
+Total PSS by category:
+    210,142K: Dalvik                                                      (   31,967K in swap)
+     96,704K: Native                                                      (   24,606K in swap)
+
+Total RAM: 7,631,848K (status normal)
+ Free RAM: 4,291,567K (  396,123K cached pss +   834,108K cached kernel + 3,061,336K free)
+      ION:   102,244K (   92,244K mapped +         0K unmapped +    10,000K pools)
+ Used RAM: 2,873,473K (2,401,836K used pss +   471,637K kernel)
+ Lost RAM:   466,804K
+     ZRAM:    51,180K physical used for   163,840K in swap (2,097,148K total swap)
+   Tuning: 256 (large 512), oom   322,560K, restore limit   107,520K (high-end-gfx)
+";
+
+    #[test]
+    fn swap_is_read_after_the_label() {
+        let meminfo = MemInfo::parse(SWAP).unwrap();
+
+        assert_eq!(
+            meminfo.processes[1],
+            ProcessPss {
+                name: "com.android.systemui".to_owned(),
+                pid: 1871,
+                pss_kb: 201_774,
+                swap_kb: Some(28_610),
+                activities: true,
+            }
+        );
+        let swap: Vec<_> = meminfo
+            .processes
+            .iter()
+            .map(|process| process.swap_kb)
+            .collect();
+        assert_eq!(swap, [Some(39_431), Some(28_610), Some(3_104)]);
+
+        let native = &meminfo.oom_adjustments[0];
+        assert_eq!(native.name, "Native");
+        assert_eq!(native.pss_kb, 102_633);
+        assert_eq!(native.processes[0].name, "surfaceflinger");
+        assert_eq!(native.processes[0].swap_kb, Some(3_104));
+        assert_eq!(meminfo.oom_adjustments[2].name, "Persistent");
+    }
+
+    #[test]
+    fn totals_of_a_swap_device() {
+        let meminfo = MemInfo::parse(SWAP).unwrap();
+
+        assert_eq!(meminfo.total_ram_kb, 7_631_848);
+        assert_eq!(meminfo.free_ram_kb, 4_291_567);
+        assert_eq!(meminfo.used_ram_kb, 2_873_473);
+        assert_eq!(meminfo.lost_ram_kb, 466_804);
+        assert_eq!(
+            meminfo.zram,
+            Some(Zram {
+                physical_kb: 51_180,
+                swap_used_kb: 163_840,
+                swap_total_kb: 2_097_148,
+            })
+        );
+    }
+
+    #[test]
+    fn swap_is_none_without_it() {
+        let meminfo = MemInfo::parse(
+            "Total PSS by process:
+    250,123K: system (pid 1234)
+",
+        )
+        .unwrap();
+        assert_eq!(meminfo.processes[0].swap_kb, None);
+        assert_eq!(meminfo.processes[0].name, "system");
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = SWAP.replace('\n', "\r\n");
+        assert_eq!(
+            MemInfo::parse(&crlf).unwrap(),
+            MemInfo::parse(SWAP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_is_empty() {
+        assert_eq!(MemInfo::parse("").unwrap(), MemInfo::default());
+    }
+
+    #[test]
+    fn missing_sections_are_left_empty() {
+        let meminfo = MemInfo::parse("Total RAM: 7,631,848K (status normal)\n").unwrap();
+        assert_eq!(meminfo.total_ram_kb, 7_631_848);
+        assert!(meminfo.processes.is_empty());
+        assert_eq!(meminfo.zram, None);
+    }
+
+    #[test]
+    fn truncated_process_line_is_invalid() {
+        let truncated = "Total PSS by process:\n    335,642K: syst";
+        assert!(matches!(
+            MemInfo::parse(truncated),
+            Err(ParseError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn unreadable_swap_is_invalid() {
+        let text = "Total PSS by process:
+    335,642K: system (pid 1615)      (   39,4x1K in swap)
+";
+        assert!(matches!(
+            MemInfo::parse(text),
+            Err(ParseError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn oom_process_before_any_category_is_invalid() {
+        let text = "Total PSS by OOM adjustment:\n        335,642K: system (pid 1615)\n";
+        assert!(MemInfo::parse(text).is_err());
+    }
+
+    const PACKAGE: &str = "Applications Memory Usage (in Kilobytes):
+Uptime: 1248184 Realtime: 1248184
+
+** MEMINFO in pid 1871 [com.android.systemui] **
+                   Pss  Private  Private  SwapPss      Rss     Heap     Heap     Heap
+                 Total    Dirty    Clean    Dirty    Total     Size    Alloc     Free
+                ------   ------   ------   ------   ------   ------   ------   ------
+  Native Heap    29906    29860        0     6293    31432    52616    46391     2123
+  Dalvik Heap    25231    25156        0    11774    26656    31484    15742    15742
+ Dalvik Other     4097     3744        0      488     5348
+        Stack     2124     2124        0      376     2132
+       Ashmem      140        4        0        0     1028
+    Other dev       52        0       48        0      416
+     .so mmap    18812     1088    11264      306    56860
+  Unknown           22       20        0      194      452
+        TOTAL   123412    73760    30712    19431   194148    84100    62133    17865
+
+ App Summary
+                       Pss(KB)                        Rss(KB)
+                        ------                         ------
+           Java Heap:    31104                          37084
+         Native Heap:    29860                          31432
+                Code:    21868                          81148
+               Stack:     2124                           2132
+            Graphics:    13632                          13632
+       Private Other:     5884
+              System:    18944
+             Unknown:                                    6168
+
+           TOTAL PSS:   123412            TOTAL RSS:   171596      TOTAL SWAP PSS:    19431
+";
+
+    #[test]
+    fn package_table_and_summary() {
+        let meminfo = PackageMemInfo::parse(PACKAGE).unwrap();
+
+        assert_eq!(meminfo.pid, 1871);
+        assert_eq!(meminfo.package, "com.android.systemui");
+        let stack = meminfo.category("Stack").unwrap();
+        assert_eq!(stack.swap_pss_dirty_kb, Some(376));
+        assert_eq!(stack.heap_size_kb, None);
+        assert_eq!(
+            meminfo.category("Other dev").unwrap().private_clean_kb,
+            Some(48)
+        );
+        assert_eq!(meminfo.total().unwrap().pss_kb, 123_412);
+        assert_eq!(meminfo.summary.system_kb, 18_944);
+        assert_eq!(meminfo.summary.total_swap_pss_kb, Some(19_431));
+    }
+
+    #[test]
+    fn package_crlf_parses_the_same() {
+        let crlf = PACKAGE.replace('\n', "\r\n");
+        assert_eq!(
+            PackageMemInfo::parse(&crlf).unwrap(),
+            PackageMemInfo::parse(PACKAGE).unwrap()
+        );
+    }
+
+    #[test]
+    fn package_without_header_is_missing() {
+        assert!(matches!(
+            PackageMemInfo::parse(""),
+            Err(ParseError::Missing(_))
+        ));
+        assert!(matches!(
+            PackageMemInfo::parse("No process found for: com.example.missing\n"),
+            Err(ParseError::Missing(_))
+        ));
+    }
+
+    #[test]
+    fn package_truncated_after_the_table_has_no_summary() {
+        let truncated = &PACKAGE[..PACKAGE.find(" App Summary").unwrap()];
+        let meminfo = PackageMemInfo::parse(truncated).unwrap();
+        assert_eq!(meminfo.total().unwrap().rss_kb, Some(194_148));
+        assert_eq!(meminfo.summary, AppSummary::default());
+    }
+
+    #[test]
+    fn package_invalid_summary_value() {
+        let text = "** MEMINFO in pid 1 [a] **\n App Summary\n           Java Heap:    31x04\n";
+        assert!(matches!(
+            PackageMemInfo::parse(text),
+            Err(ParseError::Invalid { .. })
+        ));
+    }
+}
@@ -0,0 +1,389 @@
+//! Per-process PSS trends of `dumpsys meminfo`, for finding leaks in soak tests
+//!
+//! [`MemTrend`] is fed `meminfo` dumps and keeps, for every process, the run of samples in which its
+//! PSS never dropped below an earlier sample by more than a tolerance. Once a run is long enough and
+//! has grown enough, fast enough, it's reported as a leak; a drop starts a new run. A process is
+//! forgotten once it's missing from a dump, and started afresh when its pid is reused.
+//!
+//! [`MemTrendMonitor`] dumps `meminfo` with a [`Sampler`] and feeds a [`MemTrend`] on its thread.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::{meminfo::MemInfo, memtrend::MemTrend};
+//!
+//! let meminfo = |app_kb: u64, system_kb: u64| {
+//!     MemInfo::parse(&format!(
+//!         "Total PSS by process:
+//!     {system_kb}K: system (pid 1000)
+//!     {app_kb}K: com.example.app (pid 1234 / activities)"
+//!     ))
+//!     .unwrap()
+//! };
+//! let mut trend = MemTrend::new()
+//!     .min_samples(4)
+//!     .min_growth_kb(8 * 1024)
+//!     .min_slope_kb_per_min(1024.0);
+//!
+//! // The app grows by 4 MiB a minute, the system server goes up and down.
+//! let mut alerts = Vec::new();
+//! for (minute, system_kb) in [250_000, 260_000, 240_000, 255_000].into_iter().enumerate() {
+//!     let at = Duration::from_secs(60 * minute as u64);
+//!     alerts.extend(trend.push(at, &meminfo(150_000 + 4096 * minute as u64, system_kb)));
+//! }
+//!
+//! assert_eq!(alerts.len(), 1);
+//! let leak = &alerts[0];
+//! assert_eq!(leak.name, "com.example.app");
+//! assert_eq!(leak.samples, 4);
+//! assert_eq!(leak.growth_kb, 3 * 4096);
+//! assert_eq!(leak.duration, Duration::from_secs(180));
+//! assert!((leak.slope_kb_per_min - 4096.0).abs() < 0.01);
+//!
+//! // Alerts aren't repeated while the run goes on.
+//! assert!(trend.push(Duration::from_secs(240), &meminfo(170_000, 262_000)).is_empty());
+//! assert_eq!(trend.trend(1000).unwrap().samples, 3);
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    error::DumpError,
+    meminfo::{MemInfo, ProcessPss},
+    typed, DumpParse, Sampler, Snapshot,
+};
+
+/// PSS growth of a process over its current run of samples
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trend {
+    pub pid: u32,
+    pub name: String,
+    /// Samples in the run
+    pub samples: usize,
+    /// Time from the first sample of the run to the last
+    pub duration: Duration,
+    pub first_pss_kb: u64,
+    pub pss_kb: u64,
+    /// Growth from the first sample of the run to the last, negative within the tolerance
+    pub growth_kb: i64,
+    /// Least squares fit of the PSS of the run over time
+    pub slope_kb_per_min: f64,
+}
+
+/// Tracks per-process PSS over `meminfo` dumps and reports leaks, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct MemTrend {
+    limits: Limits,
+    tolerance_kb: u64,
+    window: usize,
+    series: BTreeMap<u32, Series>,
+}
+
+impl MemTrend {
+    /// Report processes growing by 10 MiB over 5 samples, with no drop between samples.
+    pub fn new() -> Self {
+        Self {
+            limits: Limits {
+                min_samples: 5,
+                min_growth_kb: 10 * 1024,
+                min_slope_kb_per_min: 0.0,
+            },
+            tolerance_kb: 0,
+            window: 360,
+            series: BTreeMap::new(),
+        }
+    }
+
+    /// Samples a run needs before it's reported, at least 2.
+    pub fn min_samples(mut self, samples: usize) -> Self {
+        self.limits.min_samples = samples.max(2);
+        self
+    }
+
+    /// Growth over the run a leak needs.
+    pub fn min_growth_kb(mut self, growth_kb: u64) -> Self {
+        self.limits.min_growth_kb = growth_kb;
+        self
+    }
+
+    /// Fitted growth rate a leak needs, which filters out a single jump followed by a plateau.
+    pub fn min_slope_kb_per_min(mut self, slope_kb_per_min: f64) -> Self {
+        self.limits.min_slope_kb_per_min = slope_kb_per_min;
+        self
+    }
+
+    /// How far the PSS may drop below the highest sample of the run without ending it, 0 by default.
+    ///
+    /// Garbage collection makes the PSS of Java processes noisy, so a strictly growing run is rare
+    /// even for a leaking app.
+    pub fn tolerance_kb(mut self, tolerance_kb: u64) -> Self {
+        self.tolerance_kb = tolerance_kb;
+        self
+    }
+
+    /// Samples kept per process, 360 by default; the oldest ones of a longer run are dropped.
+    pub fn window(mut self, samples: usize) -> Self {
+        self.window = samples.max(2);
+        self
+    }
+
+    /// Add the processes of a dump taken `at`, since any fixed point such as the start of the test,
+    /// and return the trends that just became leaks.
+    ///
+    /// A leak is reported once per run.
+    pub fn push(&mut self, at: Duration, meminfo: &MemInfo) -> Vec<Trend> {
+        let alive: BTreeSet<u32> = meminfo
+            .processes
+            .iter()
+            .map(|process| process.pid)
+            .collect();
+        self.series.retain(|pid, _| alive.contains(pid));
+
+        let mut leaks = Vec::new();
+        for process in &meminfo.processes {
+            let series = self
+                .series
+                .entry(process.pid)
+                .or_insert_with(|| Series::new(&process.name));
+            if series.name != process.name {
+                *series = Series::new(&process.name);
+            }
+            series.push(at, process, self.tolerance_kb, self.window);
+
+            if series.leaking {
+                continue;
+            }
+            let Some(trend) = series.trend(process.pid) else {
+                continue;
+            };
+            if self.limits.is_leak(&trend) {
+                series.leaking = true;
+                leaks.push(trend);
+            }
+        }
+        leaks
+    }
+
+    /// The trend of `pid`, `None` if it's missing from the last dump.
+    pub fn trend(&self, pid: u32) -> Option<Trend> {
+        self.series.get(&pid)?.trend(pid)
+    }
+
+    /// The trends of every process of the last dump, by pid.
+    pub fn trends(&self) -> Vec<Trend> {
+        self.series
+            .iter()
+            .filter_map(|(&pid, series)| series.trend(pid))
+            .collect()
+    }
+
+    /// The processes whose current run was reported as a leak.
+    pub fn leaking(&self) -> Vec<Trend> {
+        self.series
+            .iter()
+            .filter(|(_, series)| series.leaking)
+            .filter_map(|(&pid, series)| series.trend(pid))
+            .collect()
+    }
+}
+
+impl Default for MemTrend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a [`MemTrendMonitor`] reports
+#[derive(Debug)]
+pub enum MemTrendEvent {
+    /// A process became a leak, sent once per run
+    Leak(Trend),
+    /// Dumping or parsing `meminfo` failed, the monitor keeps trying every interval
+    Error(DumpError),
+}
+
+/// Dumps `meminfo` at an interval on a background thread and reports leaks, see the
+/// [module docs](self)
+///
+/// `meminfo` takes seconds to dump on a busy device, so intervals under 10 seconds gain little.
+/// Stopping, or dropping the monitor, aborts a dump in flight and joins the thread.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::memtrend::{MemTrend, MemTrendEvent, MemTrendMonitor};
+///
+/// # fn foo() {
+/// let trend = MemTrend::new().min_samples(10).tolerance_kb(2048);
+/// let (monitor, events) = MemTrendMonitor::channel(trend, Duration::from_secs(30)).unwrap();
+/// for event in events.iter().take(5) {
+///     if let MemTrendEvent::Leak(leak) = event {
+///         println!("{} grew {} KiB in {:?}", leak.name, leak.growth_kb, leak.duration);
+///     }
+/// }
+/// monitor.stop();
+/// # }
+/// ```
+pub struct MemTrendMonitor {
+    sampler: Sampler,
+}
+
+impl MemTrendMonitor {
+    /// Dump every `interval` into `trend`, passing events to `callback` until it breaks or the monitor
+    /// stops.
+    pub fn new<F>(
+        mut trend: MemTrend,
+        interval: Duration,
+        mut callback: F,
+    ) -> Result<Self, DumpError>
+    where
+        F: FnMut(MemTrendEvent) -> ControlFlow<()> + Send + 'static,
+    {
+        let dumpsys = typed::connect(MemInfo::SERVICE, &[])?;
+        let start = SystemTime::now();
+        let sampler = Sampler::new(dumpsys, MemInfo::ARGS, interval, move |sample| {
+            let events = match sample.and_then(|snapshot| parse(&snapshot)) {
+                Ok((captured_at, meminfo)) => {
+                    let at = captured_at.duration_since(start).unwrap_or_default();
+                    trend
+                        .push(at, &meminfo)
+                        .into_iter()
+                        .map(MemTrendEvent::Leak)
+                        .collect()
+                }
+                Err(err) => vec![MemTrendEvent::Error(err)],
+            };
+            for event in events {
+                if callback(event).is_break() {
+                    return ControlFlow::Break(());
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        Ok(Self { sampler })
+    }
+
+    /// Dump every `interval` into `trend`, sending events to the returned receiver until it's dropped.
+    pub fn channel(
+        trend: MemTrend,
+        interval: Duration,
+    ) -> Result<(Self, Receiver<MemTrendEvent>), DumpError> {
+        let (tx, rx) = mpsc::channel();
+        let monitor = Self::new(trend, interval, move |event| {
+            tx.send(event)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+        })?;
+        Ok((monitor, rx))
+    }
+
+    /// Stop dumping, aborting a dump in flight, and wait for the thread to exit.
+    pub fn stop(self) {
+        self.sampler.stop();
+    }
+}
+
+/// What a run needs to be a leak
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    min_samples: usize,
+    min_growth_kb: u64,
+    min_slope_kb_per_min: f64,
+}
+
+impl Limits {
+    fn is_leak(&self, trend: &Trend) -> bool {
+        trend.samples >= self.min_samples
+            && trend.growth_kb >= self.min_growth_kb as i64
+            && trend.slope_kb_per_min >= self.min_slope_kb_per_min
+    }
+}
+
+/// The current run of samples of a process
+#[derive(Debug, Clone)]
+struct Series {
+    name: String,
+    /// Time and PSS, oldest first
+    samples: VecDeque<(Duration, u64)>,
+    /// Whether the run was reported
+    leaking: bool,
+}
+
+impl Series {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            samples: VecDeque::new(),
+            leaking: false,
+        }
+    }
+
+    /// Add a sample, starting a new run if it dropped more than `tolerance_kb` below the run.
+    fn push(&mut self, at: Duration, process: &ProcessPss, tolerance_kb: u64, window: usize) {
+        let peak = self.samples.iter().map(|&(_, pss_kb)| pss_kb).max();
+        if peak.is_some_and(|peak| process.pss_kb + tolerance_kb < peak) {
+            self.samples.clear();
+            self.leaking = false;
+        }
+        self.samples.push_back((at, process.pss_kb));
+        if self.samples.len() > window {
+            self.samples.pop_front();
+        }
+    }
+
+    fn trend(&self, pid: u32) -> Option<Trend> {
+        let &(start, first_pss_kb) = self.samples.front()?;
+        let &(end, pss_kb) = self.samples.back()?;
+        Some(Trend {
+            pid,
+            name: self.name.clone(),
+            samples: self.samples.len(),
+            duration: end.saturating_sub(start),
+            first_pss_kb,
+            pss_kb,
+            growth_kb: pss_kb as i64 - first_pss_kb as i64,
+            slope_kb_per_min: slope(&self.samples),
+        })
+    }
+}
+
+/// Least squares slope of PSS over time in minutes, 0 without a spread of times.
+fn slope(samples: &VecDeque<(Duration, u64)>) -> f64 {
+    let Some(&(start, _)) = samples.front() else {
+        return 0.0;
+    };
+    let points = samples
+        .iter()
+        .map(|&(at, pss_kb)| (at.saturating_sub(start).as_secs_f64() / 60.0, pss_kb as f64));
+    let n = samples.len() as f64;
+    let (sum_t, sum_pss) = points
+        .clone()
+        .fold((0.0, 0.0), |(t, pss), (at, kb)| (t + at, pss + kb));
+    let (mean_t, mean_pss) = (sum_t / n, sum_pss / n);
+    let (covariance, variance) = points.fold((0.0, 0.0), |(covariance, variance), (t, pss)| {
+        (
+            covariance + (t - mean_t) * (pss - mean_pss),
+            variance + (t - mean_t) * (t - mean_t),
+        )
+    });
+    if variance == 0.0 {
+        return 0.0;
+    }
+    covariance / variance
+}
+
+/// The time `snapshot` was taken and its `meminfo`.
+fn parse(snapshot: &Snapshot) -> Result<(SystemTime, MemInfo), DumpError> {
+    let meminfo = MemInfo::parse(&snapshot.output)
+        .map_err(|err| DumpError::from(err).with_context(&snapshot.service, &snapshot.args))?;
+    Ok((snapshot.captured_at, meminfo))
+}
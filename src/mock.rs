@@ -0,0 +1,35 @@
+//! Canned services for testing off-device, on every target but Android
+//!
+//! There is no libbinder off Android, so `dumpsys-rs` is built against `dumpsys-rs-mock` instead. Its
+//! in-process servicemanager knows only the services added here, which answer dumps with canned
+//! output. Code using [`Dumpsys`](crate::Dumpsys) and [`service_manager`](crate::service_manager)
+//! then builds and runs its tests on Linux and macOS CI machines, unchanged.
+//!
+//! Services can fail dumps with a binder status, take their time to exercise timeouts, or be killed to
+//! exercise death notifications and [reconnecting](crate::DumpsysBuilder::reconnect). The registry
+//! is global to the process, so tests running in parallel should use service names of their own.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::{
+//!     mock::{self, MockService},
+//!     service_manager, Dumpsys,
+//! };
+//!
+//! mock::add_service(
+//!     "SurfaceFlinger",
+//!     MockService::new("Display 0 HWC layers:\n").with_args(&["--latency"], "16666666\n"),
+//! );
+//!
+//! let surfaceflinger = Dumpsys::new("SurfaceFlinger").unwrap();
+//! assert_eq!(surfaceflinger.dump(&["--latency"]).unwrap(), "16666666\n");
+//! assert!(service_manager::list_services()
+//!     .unwrap()
+//!     .contains(&"SurfaceFlinger".to_owned()));
+//!
+//! mock::kill_service("SurfaceFlinger");
+//! assert!(surfaceflinger.dump(&["--latency"]).is_err());
+//! ```
+
+pub use binder::mock::*;
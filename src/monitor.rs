@@ -0,0 +1,333 @@
+//! Per-process CPU, memory and importance, joined from `cpuinfo`, `meminfo` and `activity processes`
+//!
+//! [`ProcessTable::join`] merges the three dumps by pid; [`Monitor`] dumps them at an interval on a
+//! background thread, like a `top` that returns typed rows.
+//!
+//! CPU usage is the one `cpuinfo` measured over its own last window, which the system refreshes
+//! every few seconds, rather than over the monitor's interval.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::{activity::Processes, cpuinfo::CpuInfo, meminfo::MemInfo, monitor::ProcessTable};
+//!
+//! let cpuinfo = CpuInfo::parse(
+//!     "Load: 12.5 / 11.8 / 10.2
+//! CPU usage from 34567ms to 4567ms ago (2023-01-01 00:00:00.000 to 2023-01-01 00:00:30.000):
+//!   40% 1234/com.example.app: 30% user + 10% kernel / faults: 10 minor
+//!   25% 1000/system_server: 15% user + 10% kernel
+//! 23% TOTAL: 12% user + 9% kernel + 0.8% iowait + 0.6% irq + 0.4% softirq",
+//! )
+//! .unwrap();
+//! let meminfo = MemInfo::parse(
+//!     "Total PSS by process:
+//!     250,123K: system (pid 1000)
+//!     150,000K: com.example.app (pid 1234 / activities)
+//!      20,000K: com.example.cached (pid 5678)
+//!
+//! Total RAM: 7,823,456K (status normal)
+//!  Free RAM: 3,456,789K (  123,456K cached pss + 2,000,000K cached kernel +   333,333K free)",
+//! )
+//! .unwrap();
+//! let processes = Processes::parse(
+//!     "  Process LRU list (sorted by oom_adj, 3 total, non-act at 1, non-svc at 1):
+//!     Proc # 2: fg     T/A/TOP  LCM  t: 0 1234:com.example.app/u0a123 (top-activity)
+//!     PERS # 1: sys    F/ /PER  LCM  t: 0 1000:system/1000 (fixed)
+//!     Proc # 0: cch+75 B/ /CEM  ---  t: 0 5678:com.example.cached/u0a99 (cch-empty)",
+//! )
+//! .unwrap();
+//!
+//! let table = ProcessTable::join(&cpuinfo, &meminfo, &processes);
+//! assert_eq!(table.processes.len(), 3);
+//! assert_eq!(table.total_ram_kb, 7_823_456);
+//!
+//! let app = &table.processes[0];
+//! assert_eq!(app.pid, 1234);
+//! assert_eq!(app.name, "com.example.app");
+//! assert_eq!(app.cpu_percent, Some(40.0));
+//! assert_eq!(app.pss_kb, Some(150_000));
+//! assert_eq!(app.adj.as_deref(), Some("fg"));
+//! assert_eq!(app.proc_state.as_deref(), Some("TOP"));
+//! assert_eq!(app.uid, Some(10123));
+//!
+//! let cached = table.process(5678).unwrap();
+//! assert_eq!(cached.cpu_percent, None);
+//! assert_eq!(table.top_memory(1)[0].name, "system");
+//! ```
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    activity::Processes,
+    builder::Config,
+    cancel::CancelToken,
+    cpuinfo::{CpuInfo, CpuTotal, LoadAverage},
+    error::DumpError,
+    meminfo::MemInfo,
+    owned_args,
+    sampler::next_tick,
+    typed, DumpParse, Dumpsys,
+};
+
+/// One process, as far as each dump knows it
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessRecord {
+    pub pid: u32,
+    pub name: String,
+    pub uid: Option<u32>,
+    /// Percent of one core, `None` for processes idle over the `cpuinfo` window
+    pub cpu_percent: Option<f64>,
+    /// `None` for kernel threads and processes that exited between the dumps
+    pub pss_kb: Option<u64>,
+    /// Oom adjustment label such as `fg` or `cch+75`, for app processes
+    pub adj: Option<String>,
+    /// Process state such as `TOP` or `CEM`, for app processes
+    pub proc_state: Option<String>,
+}
+
+/// The processes of the device at one point in time
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessTable {
+    /// Busiest first, then largest
+    pub processes: Vec<ProcessRecord>,
+    pub cpu: Option<CpuTotal>,
+    pub load: Option<LoadAverage>,
+    pub total_ram_kb: u64,
+    pub free_ram_kb: u64,
+}
+
+impl ProcessTable {
+    /// Join the three dumps by pid, keeping processes that any of them lists.
+    pub fn join(cpuinfo: &CpuInfo, meminfo: &MemInfo, processes: &Processes) -> Self {
+        let mut records = BTreeMap::<u32, ProcessRecord>::new();
+        // `meminfo` and `activity` name processes alike, `cpuinfo` truncates some names.
+        for process in &meminfo.processes {
+            record(&mut records, process.pid, &process.name).pss_kb = Some(process.pss_kb);
+        }
+        for process in &processes.lru {
+            let record = record(&mut records, process.pid, &process.name);
+            record.uid = process.uid;
+            record.adj = Some(process.adj.clone());
+            record.proc_state = Some(process.proc_state.clone());
+        }
+        for process in &cpuinfo.processes {
+            record(&mut records, process.pid, &process.name).cpu_percent = Some(process.total);
+        }
+
+        let mut processes = records.into_values().collect::<Vec<_>>();
+        processes.sort_by(|a, b| {
+            let cpu = |record: &ProcessRecord| record.cpu_percent.unwrap_or_default();
+            cpu(b)
+                .total_cmp(&cpu(a))
+                .then_with(|| b.pss_kb.cmp(&a.pss_kb))
+        });
+        Self {
+            processes,
+            cpu: cpuinfo.total,
+            load: cpuinfo.load,
+            total_ram_kb: meminfo.total_ram_kb,
+            free_ram_kb: meminfo.free_ram_kb,
+        }
+    }
+
+    pub fn process(&self, pid: u32) -> Option<&ProcessRecord> {
+        self.processes.iter().find(|process| process.pid == pid)
+    }
+
+    /// The processes called `name`, as several processes may share a name.
+    pub fn named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a ProcessRecord> + 'a {
+        self.processes
+            .iter()
+            .filter(move |process| process.name == name)
+    }
+
+    /// The `n` processes with the largest PSS, largest first.
+    pub fn top_memory(&self, n: usize) -> Vec<&ProcessRecord> {
+        let mut processes = self.processes.iter().collect::<Vec<_>>();
+        processes.sort_by_key(|process| Reverse(process.pss_kb));
+        processes.truncate(n);
+        processes
+    }
+}
+
+/// Dump `cpuinfo`, `meminfo` and `activity processes` once and join them.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for process in dumpsys_rs::monitor::snapshot().unwrap().processes.iter().take(10) {
+///     println!("{:>6} {:>5.1}% {:>8?}K {}", process.pid, process.cpu_percent.unwrap_or_default(), process.pss_kb, process.name);
+/// }
+/// # }
+/// ```
+pub fn snapshot() -> Result<ProcessTable, DumpError> {
+    Sources::connect()?.dump()
+}
+
+/// Refreshes a [`ProcessTable`] at a fixed interval on a background thread
+///
+/// Refreshes are scheduled like [`Sampler`](crate::Sampler) samples: against the start time, skipping
+/// ticks missed while the dumps overran. Stopping, or dropping the monitor, aborts the dumps in
+/// flight and joins the thread.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::monitor::Monitor;
+///
+/// # fn foo() {
+/// let (monitor, tables) = Monitor::channel(Duration::from_secs(5)).unwrap();
+/// for table in tables.iter().take(12) {
+///     let table = table.unwrap();
+///     let busiest = &table.processes[0];
+///     println!("{} at {:?}%", busiest.name, busiest.cpu_percent);
+/// }
+/// monitor.stop();
+/// # }
+/// ```
+pub struct Monitor {
+    stop: Option<Sender<()>>,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Monitor {
+    /// Refresh every `interval`, passing each table to `callback` until it breaks or the monitor
+    /// stops.
+    pub fn new<F>(interval: Duration, mut callback: F) -> Result<Self, DumpError>
+    where
+        F: FnMut(Result<ProcessTable, DumpError>) -> ControlFlow<()> + Send + 'static,
+    {
+        let cancel = CancelToken::new();
+        let sources = Sources::connect()?.cancelled_by(&cancel);
+        let (stop, stopped) = mpsc::channel();
+
+        let token = cancel.clone();
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            let mut ticks = 0u32;
+
+            loop {
+                let table = sources.dump();
+                if token.is_cancelled() || callback(table).is_break() {
+                    return;
+                }
+
+                let elapsed = start.elapsed();
+                ticks = next_tick(ticks, interval, elapsed);
+                let wait = interval
+                    .checked_mul(ticks)
+                    .unwrap_or(Duration::MAX)
+                    .saturating_sub(elapsed);
+                match stopped.recv_timeout(wait) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+            }
+        });
+
+        Ok(Self {
+            stop: Some(stop),
+            cancel,
+            thread: Some(thread),
+        })
+    }
+
+    /// Refresh every `interval`, sending the tables to the returned receiver until it's dropped.
+    pub fn channel(
+        interval: Duration,
+    ) -> Result<(Self, Receiver<Result<ProcessTable, DumpError>>), DumpError> {
+        let (tx, rx) = mpsc::channel();
+        let monitor = Self::new(interval, move |table| {
+            tx.send(table)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+        })?;
+        Ok((monitor, rx))
+    }
+
+    /// Stop refreshing, aborting the dumps in flight, and wait for the thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.cancel.cancel();
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// The services a [`ProcessTable`] is joined from
+struct Sources {
+    cpuinfo: Dumpsys,
+    meminfo: Dumpsys,
+    activity: Dumpsys,
+}
+
+impl Sources {
+    fn connect() -> Result<Self, DumpError> {
+        Ok(Self {
+            cpuinfo: typed::connect(CpuInfo::SERVICE, &[])?,
+            meminfo: typed::connect(MemInfo::SERVICE, &[])?,
+            activity: typed::connect(Processes::SERVICE, &owned_args(Processes::ARGS))?,
+        })
+    }
+
+    /// Abort the dumps once `cancel` is cancelled.
+    fn cancelled_by(self, cancel: &CancelToken) -> Self {
+        let cancelled = |dumpsys: Dumpsys| {
+            dumpsys.with_config(Config {
+                cancel: Some(cancel.clone()),
+                ..(*dumpsys.config).clone()
+            })
+        };
+        Self {
+            cpuinfo: cancelled(self.cpuinfo),
+            meminfo: cancelled(self.meminfo),
+            activity: cancelled(self.activity),
+        }
+    }
+
+    fn dump(&self) -> Result<ProcessTable, DumpError> {
+        let cpuinfo = typed::dump_parsed::<CpuInfo>(&self.cpuinfo, CpuInfo::ARGS)?;
+        let meminfo = typed::dump_parsed::<MemInfo>(&self.meminfo, MemInfo::ARGS)?;
+        let processes = typed::dump_parsed::<Processes>(&self.activity, Processes::ARGS)?;
+        Ok(ProcessTable::join(&cpuinfo, &meminfo, &processes))
+    }
+}
+
+/// The record of `pid`, named `name` unless an earlier dump named it.
+fn record<'a>(
+    records: &'a mut BTreeMap<u32, ProcessRecord>,
+    pid: u32,
+    name: &str,
+) -> &'a mut ProcessRecord {
+    let record = records.entry(pid).or_insert_with(|| ProcessRecord {
+        pid,
+        ..ProcessRecord::default()
+    });
+    if record.name.is_empty() {
+        record.name = name.to_owned();
+    }
+    record
+}
@@ -0,0 +1,503 @@
+//! Typed output of `dumpsys netstats detail`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::netstats::NetStats;
+//!
+//! let stats = NetStats::parse(
+//!     r#"Active interfaces:
+//!   iface=wlan0 ident=[{type=WIFI, ratType=COMBINED, networkId="HomeNet", metered=false, defaultNetwork=true}]
+//! Active UID interfaces:
+//!   iface=wlan0 ident=[{type=WIFI, ratType=COMBINED, networkId="HomeNet", metered=false, defaultNetwork=true}]
+//!
+//! Dev stats:
+//!   Pending bytes: 0
+//!   History since boot:
+//!   ident=[{type=WIFI, ratType=COMBINED, networkId="HomeNet", metered=false, defaultNetwork=true}] uid=-1 set=ALL tag=0x0
+//!     NetworkStatsHistory: bucketDuration=3600
+//!       st=1696000000 rb=90000 rp=90 tb=9000 tp=45 op=0
+//! UID stats:
+//!   Pending bytes: 1024
+//!   History since boot:
+//!   ident=[{type=WIFI, ratType=COMBINED, networkId="HomeNet", metered=false, defaultNetwork=true}] uid=10123 set=DEFAULT tag=0x0
+//!     NetworkStatsHistory: bucketDuration=7200
+//!       st=1696000000 rb=50000 rp=40 tb=5000 tp=20 op=0
+//!       st=1696007200 rb=25000 rp=20 tb=2500 tp=10 op=0
+//!   ident=[{type=MOBILE, ratType=COMBINED, subscriberId=310260..., metered=true, defaultNetwork=false}] uid=10123 set=FOREGROUND tag=0x0
+//!     NetworkStatsHistory: bucketDuration=7200
+//!       st=1696000000 rb=1000 rp=2 tb=100 tp=1 op=0
+//!   ident=[{type=WIFI, ratType=COMBINED, networkId="HomeNet", metered=false, defaultNetwork=true}] uid=1000 set=DEFAULT tag=0x0
+//!     NetworkStatsHistory: bucketDuration=7200
+//!       st=1696000000 rb=300 rp=3 tb=200 tp=2 op=0
+//! UID tag stats:
+//!   Pending bytes: 0
+//!   History since boot:
+//!   ident=[{type=WIFI, ratType=COMBINED, networkId="HomeNet", metered=false, defaultNetwork=true}] uid=10123 set=DEFAULT tag=0xffffff01
+//!     NetworkStatsHistory: bucketDuration=7200
+//!       st=1696000000 rb=4000 rp=4 tb=400 tp=2 op=0"#,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(stats.interfaces[0].iface, "wlan0");
+//! assert_eq!(stats.dev.len(), 1);
+//! assert_eq!(stats.uid.len(), 3);
+//! assert_eq!(stats.uid_tag[0].tag, 0xffff_ff01);
+//!
+//! let wifi = &stats.uid[0];
+//! assert_eq!(wifi.network_type.as_deref(), Some("WIFI"));
+//! assert_eq!(wifi.metered, Some(false));
+//! assert_eq!(wifi.bucket_duration, Some(Duration::from_secs(7200)));
+//! assert_eq!(wifi.buckets.len(), 2);
+//! assert_eq!(wifi.total().rx_bytes, 75000);
+//! assert_eq!(stats.interfaces_of(wifi), ["wlan0"]);
+//!
+//! let usage = stats.usage_of(10123);
+//! assert_eq!(usage.rx_bytes, 76000);
+//! assert_eq!(usage.tx_bytes, 7600);
+//! assert_eq!(stats.by_uid()[&1000].total_bytes(), 500);
+//! assert_eq!(stats.top_uids(1)[0].0, 10123);
+//! ```
+
+use std::{cmp::Reverse, collections::BTreeMap, time::Duration};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_int, KeyValues},
+    typed, DumpParse,
+};
+
+const IDENT: &str = "ident=[";
+const IDENT_END: &str = "] uid=";
+const HISTORY: &str = "NetworkStatsHistory: ";
+const BUCKET_START: &str = "st=";
+
+/// Byte and packet counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Traffic {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    /// Socket operations counted by the app, usually 0
+    pub operations: u64,
+}
+
+impl Traffic {
+    pub fn total_bytes(&self) -> u64 {
+        self.rx_bytes + self.tx_bytes
+    }
+
+    fn add(&mut self, other: &Traffic) {
+        self.rx_bytes += other.rx_bytes;
+        self.rx_packets += other.rx_packets;
+        self.tx_bytes += other.tx_bytes;
+        self.tx_packets += other.tx_packets;
+        self.operations += other.operations;
+    }
+}
+
+/// Traffic of one recorded bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bucket {
+    /// In seconds since the epoch
+    pub start: i64,
+    pub traffic: Traffic,
+}
+
+/// History of one network identity, uid, set and tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatsHistory {
+    /// `NetworkIdentitySet` as printed, without brackets
+    pub ident: String,
+    /// Type of the first identity, as in `WIFI` or `MOBILE`
+    pub network_type: Option<String>,
+    pub metered: Option<bool>,
+    /// `-1` for interface-wide stats
+    pub uid: i32,
+    /// As in `DEFAULT`, `FOREGROUND` or `ALL`
+    pub set: String,
+    /// Socket tag, `0` for untagged traffic
+    pub tag: u32,
+    pub bucket_duration: Option<Duration>,
+    pub buckets: Vec<Bucket>,
+}
+
+impl StatsHistory {
+    /// Traffic of all buckets.
+    pub fn total(&self) -> Traffic {
+        let mut total = Traffic::default();
+        for bucket in &self.buckets {
+            total.add(&bucket.traffic);
+        }
+        total
+    }
+}
+
+/// An interface and the network identity it's recorded under
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActiveInterface {
+    pub iface: String,
+    pub ident: String,
+}
+
+/// Output of `dumpsys netstats detail`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NetStats {
+    pub interfaces: Vec<ActiveInterface>,
+    /// Interface stats from the kernel
+    pub dev: Vec<StatsHistory>,
+    /// Interface stats from `xt_qtaguid` or eBPF
+    pub xt: Vec<StatsHistory>,
+    pub uid: Vec<StatsHistory>,
+    /// Tagged traffic, also counted in `uid`
+    pub uid_tag: Vec<StatsHistory>,
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    Interfaces,
+    Dev,
+    Xt,
+    Uid,
+    UidTag,
+    Other,
+}
+
+impl NetStats {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut stats = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            if !line.starts_with(' ') {
+                section = match line.trim_end() {
+                    "Active interfaces:" => Section::Interfaces,
+                    "Dev stats:" => Section::Dev,
+                    "Xt stats:" => Section::Xt,
+                    "UID stats:" => Section::Uid,
+                    "UID tag stats:" => Section::UidTag,
+                    _ => Section::Other,
+                };
+                continue;
+            }
+
+            let line = line.trim();
+            let histories = match section {
+                Section::Interfaces => {
+                    if let Some(interface) = active_interface(line) {
+                        stats.interfaces.push(interface);
+                    }
+                    continue;
+                }
+                Section::Dev => &mut stats.dev,
+                Section::Xt => &mut stats.xt,
+                Section::Uid => &mut stats.uid,
+                Section::UidTag => &mut stats.uid_tag,
+                Section::Other => continue,
+            };
+            if line.starts_with(IDENT) {
+                histories.push(history(line)?);
+            } else if let Some(history) = histories.last_mut() {
+                if let Some(fields) = line.strip_prefix(HISTORY) {
+                    history.bucket_duration = KeyValues::parse(fields)
+                        .get_parsed("bucketDuration")
+                        .ok()
+                        .map(Duration::from_secs);
+                } else if line.starts_with(BUCKET_START) {
+                    history.buckets.push(bucket(line)?);
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Untagged traffic of `uid` over all networks and sets.
+    pub fn usage_of(&self, uid: i32) -> Traffic {
+        let mut usage = Traffic::default();
+        for history in self.uid.iter().filter(|history| history.uid == uid) {
+            usage.add(&history.total());
+        }
+        usage
+    }
+
+    /// Untagged traffic of every uid.
+    pub fn by_uid(&self) -> BTreeMap<i32, Traffic> {
+        let mut usage = BTreeMap::<i32, Traffic>::new();
+        for history in &self.uid {
+            usage.entry(history.uid).or_default().add(&history.total());
+        }
+        usage
+    }
+
+    /// The `n` uids that sent and received the most bytes, heaviest first.
+    pub fn top_uids(&self, n: usize) -> Vec<(i32, Traffic)> {
+        let mut usage = self.by_uid().into_iter().collect::<Vec<_>>();
+        usage.sort_by_key(|(_, traffic)| Reverse(traffic.total_bytes()));
+        usage.truncate(n);
+        usage
+    }
+
+    /// The active interfaces whose traffic is recorded under the identity of `history`.
+    pub fn interfaces_of(&self, history: &StatsHistory) -> Vec<&str> {
+        self.interfaces
+            .iter()
+            .filter(|interface| interface.ident == history.ident)
+            .map(|interface| interface.iface.as_str())
+            .collect()
+    }
+}
+
+impl DumpParse for NetStats {
+    const SERVICE: &'static str = "netstats";
+    const ARGS: &'static [&'static str] = &["detail"];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `netstats detail` and return the untagged traffic of every uid.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for (uid, traffic) in dumpsys_rs::netstats::usage_by_uid().unwrap() {
+///     println!("{uid}: {} bytes", traffic.total_bytes());
+/// }
+/// # }
+/// ```
+pub fn usage_by_uid() -> Result<BTreeMap<i32, Traffic>, DumpError> {
+    typed::dump_service::<NetStats>(NetStats::ARGS.iter().copied()).map(|stats| stats.by_uid())
+}
+
+/// `iface=wlan0 ident=[{type=WIFI, ...}]`
+fn active_interface(line: &str) -> Option<ActiveInterface> {
+    let iface = line.strip_prefix("iface=")?;
+    let (iface, ident) = iface.split_once(' ')?;
+    let ident = ident.strip_prefix(IDENT)?.strip_suffix(']')?;
+    Some(ActiveInterface {
+        iface: iface.to_owned(),
+        ident: ident.to_owned(),
+    })
+}
+
+/// `ident=[{type=WIFI, networkId="HomeNet", metered=false}] uid=10123 set=DEFAULT tag=0x0`
+fn history(line: &str) -> Result<StatsHistory, ParseError> {
+    let end = line
+        .rfind(IDENT_END)
+        .ok_or_else(|| invalid("ident", line))?;
+    let ident = &line[IDENT.len()..end];
+    let key = KeyValues::parse(&line[end + 2..]);
+    let identity = KeyValues::parse(ident);
+    let tag = key.require("tag")?;
+    Ok(StatsHistory {
+        ident: ident.to_owned(),
+        network_type: identity.get("type").map(str::to_owned),
+        metered: identity.get_bool("metered").ok(),
+        uid: key.get_parsed("uid")?,
+        set: key.require("set")?.to_owned(),
+        tag: parse_int(tag)
+            .and_then(|tag| u32::try_from(tag).ok())
+            .ok_or_else(|| invalid("tag", tag))?,
+        bucket_duration: None,
+        buckets: Vec::new(),
+    })
+}
+
+/// `st=1696000000 rb=50000 rp=40 tb=5000 tp=20 op=0`
+fn bucket(line: &str) -> Result<Bucket, ParseError> {
+    let fields = KeyValues::parse(line);
+    // Counters the device didn't track are left out.
+    let counter = |key| {
+        if fields.contains(key) {
+            fields.get_parsed(key)
+        } else {
+            Ok(0)
+        }
+    };
+    Ok(Bucket {
+        start: fields.get_parsed("st")?,
+        traffic: Traffic {
+            rx_bytes: counter("rb")?,
+            rx_packets: counter("rp")?,
+            tx_bytes: counter("tb")?,
+            tx_packets: counter("tp")?,
+            operations: counter("op")?,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIFI: &str = r#"{type=WIFI, ratType=COMBINED, networkId="Office 5G", metered=false, defaultNetwork=true, oemManaged=OEM_NONE}"#;
+    const MOBILE: &str = "{type=MOBILE, ratType=COMBINED, subscriberId=310260..., metered=true, defaultNetwork=false, oemManaged=OEM_NONE}";
+
+    /// `dumpsys netstats detail` of Android 12, cut down to a few uids and buckets
+    fn dump() -> String {
+        format!(
+            "Active interfaces:
+  iface=wlan0 ident=[{WIFI}]
+  iface=rmnet_data2 ident=[{MOBILE}]
+Active UID interfaces:
+  iface=wlan0 ident=[{WIFI}]
+
+Dev stats:
+  Pending bytes: 0
+  History since boot:
+  ident=[{WIFI}] uid=-1 set=ALL tag=0x0
+    NetworkStatsHistory: bucketDuration=3600
+      st=1696003200 rb=8421376 rp=6502 tb=912384 tp=4211 op=0
+      st=1696006800 rb=1048576 rp=820 tb=131072 tp=512 op=0
+Xt stats:
+  Pending bytes: 0
+  History since boot:
+  ident=[{MOBILE}] uid=-1 set=ALL tag=0x0
+    NetworkStatsHistory: bucketDuration=3600
+      st=1696003200 rb=524288 rp=400 tb=65536 tp=210 op=0
+
+UID stats:
+  Pending bytes: 2048
+  Complete history:
+  ident=[{WIFI}] uid=10216 set=DEFAULT tag=0x0
+    NetworkStatsHistory: bucketDuration=7200
+      st=1696003200 rb=6291456 rp=4800 tb=524288 tp=2600 op=12
+  ident=[{WIFI}] uid=10216 set=FOREGROUND tag=0x0
+    NetworkStatsHistory: bucketDuration=7200
+      st=1696003200 rb=1048576 rp=900 tb=131072 tp=640 op=0
+  ident=[{MOBILE}] uid=10216 set=DEFAULT tag=0x0
+    NetworkStatsHistory: bucketDuration=7200
+      st=1696003200 rb=262144 rp=200 tb=32768 tp=100 op=0
+  ident=[{WIFI}] uid=1000 set=DEFAULT tag=0x0
+    NetworkStatsHistory: bucketDuration=7200
+      st=1696003200 rb=81920 rp=90 tb=40960 tp=60 op=0
+  ident=[{WIFI}] uid=-5 set=DEFAULT tag=0x0
+    NetworkStatsHistory: bucketDuration=7200
+      st=1696003200 rb=4096 rp=4 tb=4096 tp=4 op=0
+UID tag stats:
+  Pending bytes: 0
+  Complete history:
+  ident=[{WIFI}] uid=10216 set=DEFAULT tag=0xffffff42
+    NetworkStatsHistory: bucketDuration=7200
+      st=1696003200 rb=2097152 rp=1500 tb=262144 tp=800 op=0
+"
+        )
+    }
+
+    #[test]
+    fn parses_the_dump() {
+        let stats = NetStats::parse(&dump()).unwrap();
+        // Only the active interfaces, not those of the uid stats
+        assert_eq!(stats.interfaces.len(), 2);
+        assert_eq!(stats.interfaces[1].iface, "rmnet_data2");
+        assert_eq!(stats.interfaces[1].ident, MOBILE);
+
+        let dev = &stats.dev[0];
+        assert_eq!(dev.uid, -1);
+        assert_eq!(dev.set, "ALL");
+        assert_eq!(dev.bucket_duration, Some(Duration::from_secs(3600)));
+        assert_eq!(dev.buckets[1].start, 1_696_006_800);
+        assert_eq!(dev.total().rx_bytes, 9_469_952);
+        assert_eq!(stats.xt[0].network_type.as_deref(), Some("MOBILE"));
+        assert_eq!(stats.xt[0].metered, Some(true));
+
+        assert_eq!(stats.uid.len(), 5);
+        assert_eq!(
+            stats.uid[0].buckets[0].traffic,
+            Traffic {
+                rx_bytes: 6_291_456,
+                rx_packets: 4800,
+                tx_bytes: 524_288,
+                tx_packets: 2600,
+                operations: 12,
+            }
+        );
+        assert_eq!(stats.uid_tag[0].tag, 0xffff_ff42);
+        assert_eq!(stats.interfaces_of(&stats.uid[0]), ["wlan0"]);
+        assert_eq!(stats.interfaces_of(&stats.uid[2]), ["rmnet_data2"]);
+    }
+
+    #[test]
+    fn usage_adds_up_networks_and_sets_but_not_tags() {
+        let stats = NetStats::parse(&dump()).unwrap();
+        let usage = stats.usage_of(10216);
+        assert_eq!(usage.rx_bytes, 6_291_456 + 1_048_576 + 262_144);
+        assert_eq!(usage.tx_bytes, 524_288 + 131_072 + 32_768);
+        assert_eq!(stats.usage_of(12345), Traffic::default());
+
+        let by_uid = stats.by_uid();
+        assert_eq!(
+            by_uid.keys().copied().collect::<Vec<_>>(),
+            [-5, 1000, 10216]
+        );
+        assert_eq!(by_uid[&1000].total_bytes(), 122_880);
+        let top: Vec<_> = stats.top_uids(2).into_iter().map(|(uid, _)| uid).collect();
+        assert_eq!(top, [10216, 1000]);
+    }
+
+    #[test]
+    fn counters_older_releases_leave_out_are_zero() {
+        let text = "UID stats:
+  ident=[[type=MOBILE, subType=COMBINED, subscriberId=310260...]] uid=10042 set=DEFAULT tag=0x0
+    NetworkStatsHistory: bucketDuration=7200
+      st=1496003200 rb=1024 rp=2 tb=512 tp=1
+";
+        let stats = NetStats::parse(text).unwrap();
+        let history = &stats.uid[0];
+        assert_eq!(history.metered, None);
+        assert_eq!(history.buckets[0].traffic.operations, 0);
+        assert_eq!(history.total().total_bytes(), 1536);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let dump = dump();
+        assert_eq!(
+            NetStats::parse(&dump.replace('\n', "\r\n")).unwrap(),
+            NetStats::parse(&dump).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        let stats = NetStats::parse("").unwrap();
+        assert_eq!(stats, NetStats::default());
+        assert!(stats.top_uids(3).is_empty());
+    }
+
+    #[test]
+    fn missing_and_invalid_keys() {
+        let line = format!("  ident=[{WIFI}] uid=10216 tag=0x0");
+        assert_eq!(
+            NetStats::parse(&format!("UID stats:\n{line}")).unwrap_err(),
+            ParseError::Missing("set".to_owned())
+        );
+        let line = format!("  ident=[{WIFI}] uid=10216 set=DEFAULT tag=0x1ffffffff");
+        assert_eq!(
+            NetStats::parse(&format!("UID stats:\n{line}")).unwrap_err(),
+            invalid("tag", "0x1ffffffff")
+        );
+    }
+
+    #[test]
+    fn truncated_lines_are_invalid() {
+        let line = "ident=[{type=WIFI, ratType=COMB";
+        assert_eq!(
+            NetStats::parse(&format!("Dev stats:\n  {line}")).unwrap_err(),
+            invalid("ident", line)
+        );
+        let text = format!("Dev stats:\n  ident=[{WIFI}] uid=-1 set=ALL tag=0x0\n      st=");
+        assert_eq!(NetStats::parse(&text).unwrap_err(), invalid("st", ""));
+    }
+}
@@ -0,0 +1,567 @@
+//! Typed output of `dumpsys notification --noredact`
+//!
+//! Without `--noredact`, titles and texts are printed as their length only and read as `None`.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::notification::{Importance, NotificationState, ZenMode};
+//!
+//! let state = NotificationState::parse(
+//!     "Current Notification Manager state:
+//!   Notification List:
+//!     NotificationRecord(0x0abc1234: pkg=com.example.app user=UserHandle{0} id=7 tag=sync importance=2 key=0|com.example.app|7|sync|10123: Notification(channel=updates shortcut=null contentView=null vibrate=null sound=null defaults=0x0 flags=0x62 color=0x00000000 vis=PRIVATE))
+//!       uid=10123 userId=0
+//!       opPkg=com.example.app
+//!       flags=0x62
+//!       key=0|com.example.app|7|sync|10123
+//!       notification=
+//!           when=1704110400000
+//!           extras={
+//!             android.title=String (Syncing)
+//!             android.text=String (3 files left)
+//!           }
+//!       publicNotification=
+//!           when=0
+//!       mImportance=LOW
+//!       mChannel=NotificationChannel{mId='updates', mName=Updates, mImportance=2}
+//!
+//!   Enqueued Notification List:
+//!
+//!     mZenMode=ZEN_MODE_IMPORTANT_INTERRUPTIONS
+//!     mConsolidatedPolicy=NotificationManager.Policy[priorityCategories=PRIORITY_CATEGORY_ALARMS,PRIORITY_CATEGORY_MEDIA,priorityCallSenders=PRIORITY_SENDERS_STARRED,priorityMessageSenders=PRIORITY_SENDERS_ANY,priorityConvSenders=CONVERSATION_SENDERS_IMPORTANT,suppressedVisualEffects=SUPPRESSED_EFFECT_SCREEN_OFF,areChannelsBypassingDnd=false]",
+//! )
+//! .unwrap();
+//!
+//! let notification = &state.notifications[0];
+//! assert_eq!(notification.package, "com.example.app");
+//! assert_eq!(notification.id, 7);
+//! assert_eq!(notification.tag.as_deref(), Some("sync"));
+//! assert_eq!(notification.uid, Some(10123));
+//! assert_eq!(notification.channel.as_deref(), Some("updates"));
+//! assert_eq!(notification.importance, Importance::Low);
+//! assert_eq!(notification.when, Some(1704110400000));
+//! assert!(notification.is_ongoing());
+//! assert!(notification.is_foreground_service());
+//! assert_eq!(notification.title.as_deref(), Some("Syncing"));
+//! assert_eq!(notification.text.as_deref(), Some("3 files left"));
+//! assert_eq!(state.for_package("com.example.app").count(), 1);
+//!
+//! assert_eq!(state.dnd.mode, ZenMode::ImportantInterruptions);
+//! assert_eq!(state.dnd.priority_categories, ["PRIORITY_CATEGORY_ALARMS", "PRIORITY_CATEGORY_MEDIA"]);
+//! assert_eq!(state.dnd.call_senders.as_deref(), Some("PRIORITY_SENDERS_STARRED"));
+//! assert_eq!(state.dnd.suppressed_effects, ["SUPPRESSED_EFFECT_SCREEN_OFF"]);
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{first_word, invalid, KeyValues},
+    typed, DumpParse,
+};
+
+const NOTIFICATION_LIST: &str = "Notification List:";
+const RECORD: &str = "NotificationRecord(";
+const CHANNEL: &str = "Notification(channel=";
+const CHANNEL_ID: &str = "mId='";
+const NO_REDACT: &str = "--noredact";
+
+/// `Notification.FLAG_ONGOING_EVENT`
+const FLAG_ONGOING_EVENT: u32 = 0x2;
+/// `Notification.FLAG_FOREGROUND_SERVICE`
+const FLAG_FOREGROUND_SERVICE: u32 = 0x40;
+
+/// `NotificationManager.IMPORTANCE_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Importance {
+    #[default]
+    Unspecified,
+    /// Blocked
+    None,
+    Min,
+    Low,
+    Default,
+    High,
+    Max,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for Importance {
+    fn from(name: &str) -> Self {
+        match name {
+            "UNSPECIFIED" => Self::Unspecified,
+            "NONE" => Self::None,
+            "MIN" => Self::Min,
+            "LOW" => Self::Low,
+            "DEFAULT" => Self::Default,
+            "HIGH" => Self::High,
+            "MAX" => Self::Max,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+impl From<i64> for Importance {
+    fn from(importance: i64) -> Self {
+        match importance {
+            -1000 => Self::Unspecified,
+            0 => Self::None,
+            1 => Self::Min,
+            2 => Self::Low,
+            3 => Self::Default,
+            4 => Self::High,
+            5 => Self::Max,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// A posted notification
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PostedNotification {
+    /// As in `0|com.example.app|7|sync|10123`
+    pub key: Option<String>,
+    pub package: String,
+    pub id: i64,
+    pub tag: Option<String>,
+    pub uid: Option<u32>,
+    pub user: Option<u32>,
+    /// Id of the notification channel, on Android 8 and later
+    pub channel: Option<String>,
+    pub importance: Importance,
+    /// `Notification.when`, in milliseconds since the epoch
+    pub when: Option<i64>,
+    /// `Notification.FLAG_*`
+    pub flags: u32,
+    pub title: Option<String>,
+    pub text: Option<String>,
+}
+
+impl PostedNotification {
+    /// Whether the user can't dismiss the notification.
+    pub fn is_ongoing(&self) -> bool {
+        self.flags & FLAG_ONGOING_EVENT != 0
+    }
+
+    pub fn is_foreground_service(&self) -> bool {
+        self.flags & FLAG_FOREGROUND_SERVICE != 0
+    }
+}
+
+/// `Settings.Global.ZEN_MODE_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZenMode {
+    #[default]
+    Off,
+    /// Priority only
+    ImportantInterruptions,
+    AlarmsOnly,
+    /// Total silence
+    NoInterruptions,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for ZenMode {
+    fn from(name: &str) -> Self {
+        match name.strip_prefix("ZEN_MODE_").unwrap_or(name) {
+            "OFF" => Self::Off,
+            "IMPORTANT_INTERRUPTIONS" => Self::ImportantInterruptions,
+            "ALARMS" => Self::AlarmsOnly,
+            "NO_INTERRUPTIONS" => Self::NoInterruptions,
+            _ => Self::Other(name.to_owned()),
+        }
+    }
+}
+
+/// Do Not Disturb mode and what it lets through
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DndPolicy {
+    pub mode: ZenMode,
+    /// Categories allowed in priority mode, as in `PRIORITY_CATEGORY_ALARMS`
+    pub priority_categories: Vec<String>,
+    /// Whose calls are allowed, as in `PRIORITY_SENDERS_STARRED`
+    pub call_senders: Option<String>,
+    pub message_senders: Option<String>,
+    /// Effects of notifications suppressed while DND is on, as in `SUPPRESSED_EFFECT_SCREEN_OFF`
+    pub suppressed_effects: Vec<String>,
+}
+
+/// Output of `dumpsys notification --noredact`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NotificationState {
+    pub notifications: Vec<PostedNotification>,
+    pub dnd: DndPolicy,
+}
+
+impl NotificationState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut state = Self::default();
+        // Indent of the `Notification List:` header while in the list
+        let mut list = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            if trimmed == NOTIFICATION_LIST {
+                list = Some(indent);
+                continue;
+            }
+            if list.is_some_and(|list| indent <= list) {
+                list = None;
+            }
+            let Some((key, value)) = trimmed.split_once('=') else {
+                continue;
+            };
+            match key {
+                "mZenMode" => state.dnd.mode = value.into(),
+                "mConsolidatedPolicy" => state.dnd.policy(value),
+                _ if list.is_none() => {}
+                _ if trimmed.starts_with(RECORD) => {
+                    state.notifications.push(record(trimmed)?);
+                }
+                _ => {
+                    if let Some(notification) = state.notifications.last_mut() {
+                        notification.detail(key, value);
+                    }
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Notifications posted by `package`.
+    pub fn for_package<'a>(
+        &'a self,
+        package: &'a str,
+    ) -> impl Iterator<Item = &'a PostedNotification> + 'a {
+        self.notifications
+            .iter()
+            .filter(move |notification| notification.package == package)
+    }
+}
+
+impl DumpParse for NotificationState {
+    const SERVICE: &'static str = "notification";
+    const ARGS: &'static [&'static str] = &[NO_REDACT];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `notification` and return the notifications currently posted.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for notification in dumpsys_rs::notification::posted().unwrap() {
+///     println!("{}: {:?}", notification.package, notification.title);
+/// }
+/// # }
+/// ```
+pub fn posted() -> Result<Vec<PostedNotification>, DumpError> {
+    typed::dump_service::<NotificationState>([NO_REDACT]).map(|state| state.notifications)
+}
+
+impl PostedNotification {
+    /// A `key=value` line under the record, the first of which wins over those of the public version.
+    fn detail(&mut self, key: &str, value: &str) {
+        match key {
+            "uid" => {
+                // uid=10123 userId=0
+                self.uid = first_word(value).parse().ok();
+                self.user = KeyValues::parse(value).get_parsed("userId").ok();
+            }
+            "key" => self.key = Some(value.to_owned()),
+            "flags" => {
+                if let Some(flags) = value
+                    .strip_prefix("0x")
+                    .and_then(|flags| u32::from_str_radix(flags, 16).ok())
+                {
+                    self.flags = flags;
+                }
+            }
+            "when" if self.when.is_none() => self.when = value.parse().ok(),
+            "mImportance" => self.importance = value.into(),
+            "mChannel" => {
+                if let Some(id) = value
+                    .split(CHANNEL_ID)
+                    .nth(1)
+                    .and_then(|id| id.split('\'').next())
+                {
+                    self.channel = Some(id.to_owned());
+                }
+            }
+            "android.title" if self.title.is_none() => self.title = extra(value),
+            "android.text" if self.text.is_none() => self.text = extra(value),
+            _ => {}
+        }
+    }
+}
+
+impl DndPolicy {
+    /// `NotificationManager.Policy[priorityCategories=PRIORITY_CATEGORY_ALARMS,...]`
+    fn policy(&mut self, value: &str) {
+        let fields = KeyValues::parse(value);
+        let list = |key| {
+            fields
+                .get(key)
+                .into_iter()
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        self.priority_categories = list("priorityCategories");
+        self.suppressed_effects = list("suppressedVisualEffects");
+        self.call_senders = fields.get("priorityCallSenders").map(str::to_owned);
+        self.message_senders = fields.get("priorityMessageSenders").map(str::to_owned);
+    }
+}
+
+/// `NotificationRecord(0x0abc1234: pkg=com.example.app user=UserHandle{0} id=7 tag=sync importance=2 key=...: Notification(channel=updates ...))`
+fn record(line: &str) -> Result<PostedNotification, ParseError> {
+    let fields = KeyValues::parse(line);
+    let channel = line
+        .split(CHANNEL)
+        .nth(1)
+        .and_then(|channel| channel.split_whitespace().next())
+        .filter(|channel| *channel != "null");
+    Ok(PostedNotification {
+        package: fields.require("pkg")?.to_owned(),
+        id: fields.get_int("id")?,
+        tag: fields
+            .get("tag")
+            .filter(|tag| *tag != "null")
+            .map(str::to_owned),
+        channel: channel.map(str::to_owned),
+        importance: fields
+            .get_int("importance")
+            .map_err(|_| invalid("importance", line))?
+            .into(),
+        ..PostedNotification::default()
+    })
+}
+
+/// `String (Syncing)`, or `String [length=7]` when redacted.
+fn extra(value: &str) -> Option<String> {
+    let (_, text) = value.split_once(" (")?;
+    Some(text.strip_suffix(')').unwrap_or(text).to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys notification --noredact` of Android 14, cut down to two notifications
+    const DUMP: &str = "Current Notification Manager state:
+  Notification List:
+    NotificationRecord(0x0a1b2c3d: pkg=com.example.chat user=UserHandle{0} id=1001 tag=null importance=4 key=0|com.example.chat|1001|null|10231: Notification(channel=messages shortcut=conv_42 contentView=null vibrate=null sound=null defaults=0x0 flags=0x10 color=0xff1e88e5 category=msg groupKey=conversations vis=PRIVATE))
+      uid=10231 userId=0
+      opPkg=com.example.chat
+      icon=Icon(typ=RESOURCE pkg=com.example.chat id=0x7f08012a)
+      flags=0x10
+      pri=0
+      key=0|com.example.chat|1001|null|10231
+      seen=true
+      notification=
+          fullscreenIntent=null
+          contentIntent=PendingIntent{5c2e1a9: PendingIntentRecord{8d3f0b4 com.example.chat startActivity}}
+          number=0
+          when=1710234000000
+          tickerText=null
+          extras={
+            android.title=String (Alice)
+            android.text=String (See you at 10)
+            android.subText=null
+            android.showWhen=Boolean (true)
+          }
+      publicNotification=
+          when=1710234000000
+          extras={
+            android.title=String (New message)
+          }
+      mSystemImportance=4
+      mImportance=HIGH
+      mImportanceExplanation=app
+      mChannel=NotificationChannel{mId='messages', mName=Messages, mDescription=, mImportance=4, mBypassDnd=false, mLockscreenVisibility=-1000}
+    NotificationRecord(0x7e6d5c4b: pkg=com.example.app user=UserHandle{0} id=7 tag=sync importance=2 key=0|com.example.app|7|sync|10123: Notification(channel=updates shortcut=null contentView=null vibrate=null sound=null defaults=0x0 flags=0x62 color=0x00000000 vis=PRIVATE))
+      uid=10123 userId=0
+      flags=0x62
+      key=0|com.example.app|7|sync|10123
+      notification=
+          when=1710230400000
+          extras={
+            android.title=String (Syncing)
+            android.text=String (3 files left)
+          }
+      mImportance=LOW
+      mChannel=NotificationChannel{mId='updates', mName=Updates, mImportance=2}
+
+  Enqueued Notification List:
+
+  Zen Mode:
+    mZenMode=ZEN_MODE_IMPORTANT_INTERRUPTIONS
+    mSuppressedEffects=0
+    mConsolidatedPolicy=NotificationManager.Policy[priorityCategories=PRIORITY_CATEGORY_REMINDERS,PRIORITY_CATEGORY_EVENTS,PRIORITY_CATEGORY_MESSAGES,PRIORITY_CATEGORY_ALARMS,priorityCallSenders=PRIORITY_SENDERS_STARRED,priorityMessageSenders=PRIORITY_SENDERS_ANY,priorityConvSenders=CONVERSATION_SENDERS_IMPORTANT,suppressedVisualEffects=SUPPRESSED_EFFECT_SCREEN_OFF,SUPPRESSED_EFFECT_LIGHTS,SUPPRESSED_EFFECT_PEEK,areChannelsBypassingDnd=false]
+";
+
+    #[test]
+    fn parses_the_notifications() {
+        let state = NotificationState::parse(DUMP).unwrap();
+        assert_eq!(state.notifications.len(), 2);
+        assert_eq!(
+            state.notifications[0],
+            PostedNotification {
+                key: Some("0|com.example.chat|1001|null|10231".to_owned()),
+                package: "com.example.chat".to_owned(),
+                id: 1001,
+                tag: None,
+                uid: Some(10231),
+                user: Some(0),
+                channel: Some("messages".to_owned()),
+                importance: Importance::High,
+                when: Some(1_710_234_000_000),
+                flags: 0x10,
+                // Not the title of the public version
+                title: Some("Alice".to_owned()),
+                text: Some("See you at 10".to_owned()),
+            }
+        );
+        assert!(!state.notifications[0].is_ongoing());
+
+        let sync = state.for_package("com.example.app").next().unwrap();
+        assert_eq!(sync.tag.as_deref(), Some("sync"));
+        assert_eq!(sync.importance, Importance::Low);
+        assert!(sync.is_ongoing() && sync.is_foreground_service());
+    }
+
+    #[test]
+    fn parses_the_dnd_policy() {
+        let state = NotificationState::parse(DUMP).unwrap();
+        assert_eq!(
+            state.dnd,
+            DndPolicy {
+                mode: ZenMode::ImportantInterruptions,
+                priority_categories: [
+                    "PRIORITY_CATEGORY_REMINDERS",
+                    "PRIORITY_CATEGORY_EVENTS",
+                    "PRIORITY_CATEGORY_MESSAGES",
+                    "PRIORITY_CATEGORY_ALARMS",
+                ]
+                .map(str::to_owned)
+                .to_vec(),
+                call_senders: Some("PRIORITY_SENDERS_STARRED".to_owned()),
+                message_senders: Some("PRIORITY_SENDERS_ANY".to_owned()),
+                suppressed_effects: [
+                    "SUPPRESSED_EFFECT_SCREEN_OFF",
+                    "SUPPRESSED_EFFECT_LIGHTS",
+                    "SUPPRESSED_EFFECT_PEEK",
+                ]
+                .map(str::to_owned)
+                .to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn redacted_texts_are_none() {
+        let text = "  Notification List:
+    NotificationRecord(0x0a1b2c3d: pkg=com.example.chat user=UserHandle{0} id=1001 tag=null importance=3 key=0|com.example.chat|1001|null|10231: Notification(channel=null shortcut=null))
+      notification=
+          extras={
+            android.title=String [length=5]
+            android.text=String [length=13]
+          }
+";
+        let state = NotificationState::parse(text).unwrap();
+        let notification = &state.notifications[0];
+        assert_eq!(notification.title, None);
+        assert_eq!(notification.text, None);
+        // Without an mImportance line the importance of the record stays
+        assert_eq!(notification.channel, None);
+        assert_eq!(notification.importance, Importance::Default);
+        assert_eq!(notification.uid, None);
+    }
+
+    #[test]
+    fn records_outside_the_list_are_skipped() {
+        let text = "  Enqueued Notification List:
+    NotificationRecord(0x0a1b2c3d: pkg=com.example.chat user=UserHandle{0} id=1001 tag=null importance=3 key=0|com.example.chat|1001|null|10231: Notification(channel=messages))
+";
+        assert!(NotificationState::parse(text)
+            .unwrap()
+            .notifications
+            .is_empty());
+    }
+
+    #[test]
+    fn unknown_values_are_kept() {
+        let text = "  Notification List:
+    NotificationRecord(0x0a1b2c3d: pkg=com.example.app user=UserHandle{0} id=1 tag=null importance=9 key=0|com.example.app|1|null|10123: Notification(channel=misc))
+
+    mZenMode=ZEN_MODE_BEDTIME
+";
+        let state = NotificationState::parse(text).unwrap();
+        assert_eq!(
+            state.notifications[0].importance,
+            Importance::Other("9".to_owned())
+        );
+        assert_eq!(
+            state.dnd.mode,
+            ZenMode::Other("ZEN_MODE_BEDTIME".to_owned())
+        );
+        assert!(state.dnd.priority_categories.is_empty());
+        assert_eq!(
+            Importance::from("URGENT"),
+            Importance::Other("URGENT".to_owned())
+        );
+    }
+
+    #[test]
+    fn invalid_records() {
+        let list = "  Notification List:\n";
+        let parse = |record: &str| NotificationState::parse(&format!("{list}    {record}\n"));
+        assert_eq!(
+            parse("NotificationRecord(0x0a1b2c3d: user=UserHandle{0} id=1 importance=3)")
+                .unwrap_err(),
+            ParseError::Missing("pkg".to_owned())
+        );
+        assert_eq!(
+            parse("NotificationRecord(0x0a1b2c3d: pkg=com.example.app id=one importance=3)")
+                .unwrap_err(),
+            invalid("id", "one")
+        );
+        let record = "NotificationRecord(0x0a1b2c3d: pkg=com.example.app id=1 importance=HIGH)";
+        assert_eq!(parse(record).unwrap_err(), invalid("importance", record));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            NotificationState::parse(&crlf).unwrap(),
+            NotificationState::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(
+            NotificationState::parse("").unwrap(),
+            NotificationState::default()
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{DumpContext, DumpError},
+    instrument,
+};
+
+/// Hooks into every dump attempt of a [`Dumpsys`](crate::Dumpsys), set with
+/// [`DumpsysBuilder::observer`](crate::DumpsysBuilder::observer)
+///
+/// A dump that is retried reports every attempt, each ending in [`on_complete`](DumpObserver::on_complete)
+/// or [`on_error`](DumpObserver::on_error), except for a [`DumpReader`](crate::DumpReader) dropped
+/// before the end. The hooks run on the thread reading the dump, so
+/// [`on_start`](DumpObserver::on_start) can block to rate limit dumps.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     sync::{
+///         atomic::{AtomicU64, Ordering},
+///         Arc,
+///     },
+///     time::Duration,
+/// };
+///
+/// use dumpsys_rs::{error::DumpContext, DumpObserver, Dumpsys};
+///
+/// #[derive(Default)]
+/// struct Bytes(AtomicU64);
+///
+/// impl DumpObserver for Bytes {
+///     fn on_chunk(&self, _context: &DumpContext, chunk: &[u8]) {
+///         self.0.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+///     }
+///
+///     fn on_complete(&self, context: &DumpContext, _bytes: Option<u64>, elapsed: Duration) {
+///         println!("{context} took {elapsed:?}");
+///     }
+/// }
+///
+/// # fn foo() -> Option<()> {
+/// let bytes = Arc::new(Bytes::default());
+/// let surfaceflinger = Dumpsys::builder("SurfaceFlinger")
+///     .observer(bytes.clone())
+///     .build()?;
+/// surfaceflinger.dump(&["--latency"]).unwrap();
+/// println!("{} bytes", bytes.0.load(Ordering::Relaxed));
+/// # Some(())
+/// # }
+/// ```
+pub trait DumpObserver: Send + Sync {
+    /// An attempt starts dumping.
+    fn on_start(&self, context: &DumpContext) {
+        let _ = context;
+    }
+
+    /// `chunk` of output was read. Not called for [`Dumpsys::dump_to_fd`](crate::Dumpsys::dump_to_fd),
+    /// [`Dumpsys::dump_to_file`](crate::Dumpsys::dump_to_file) and
+    /// [`Dumpsys::dump_to_memfd`](crate::Dumpsys::dump_to_memfd), whose output doesn't pass through this
+    /// process.
+    fn on_chunk(&self, context: &DumpContext, chunk: &[u8]) {
+        let _ = (context, chunk);
+    }
+
+    /// The attempt succeeded after `elapsed`, with `bytes` of output if they were read.
+    fn on_complete(&self, context: &DumpContext, bytes: Option<u64>, elapsed: Duration) {
+        let _ = (context, bytes, elapsed);
+    }
+
+    /// The attempt failed with `err` after `elapsed`.
+    fn on_error(&self, context: &DumpContext, err: &DumpError, elapsed: Duration) {
+        let _ = (context, err, elapsed);
+    }
+}
+
+/// The observer of a [`Config`](crate::builder::Config)
+#[derive(Clone)]
+pub(crate) struct Observer(pub(crate) Arc<dyn DumpObserver>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// The observer of an attempt with its context, for the reader to report chunks to
+#[derive(Clone)]
+pub(crate) struct Observed {
+    observer: Arc<dyn DumpObserver>,
+    context: Arc<DumpContext>,
+}
+
+impl Observed {
+    pub(crate) fn chunk(&self, chunk: &[u8]) {
+        self.observer.on_chunk(&self.context, chunk);
+    }
+}
+
+/// One attempt of a dump, reported to the observer and to `tracing`
+pub(crate) struct Attempt {
+    observer: Option<Arc<dyn DumpObserver>>,
+    context: Arc<DumpContext>,
+    start: Instant,
+}
+
+impl Attempt {
+    pub(crate) fn start(observer: Option<&Observer>, service: &str, args: &[String]) -> Self {
+        let attempt = Self {
+            observer: observer.map(|observer| observer.0.clone()),
+            context: Arc::new(DumpContext {
+                service: service.to_owned(),
+                args: args.to_vec(),
+            }),
+            start: instrument::started(),
+        };
+        if let Some(observer) = &attempt.observer {
+            observer.on_start(&attempt.context);
+        }
+        attempt
+    }
+
+    /// Where the reader of the attempt reports chunks, if anywhere.
+    pub(crate) fn observed(&self) -> Option<Observed> {
+        self.observer.as_ref().map(|observer| Observed {
+            observer: observer.clone(),
+            context: self.context.clone(),
+        })
+    }
+
+    /// The attempt ended with `result`, after reading `bytes` from the service if known. Returns the
+    /// result with the context of the dump attached to a failure.
+    pub(crate) fn finish<T>(
+        &self,
+        bytes: Option<u64>,
+        result: Result<T, DumpError>,
+    ) -> Result<T, DumpError> {
+        let output = result.map_err(|err| self.fail(bytes, err))?;
+        instrument::finished(self.start, bytes, None);
+        if let Some(observer) = &self.observer {
+            observer.on_complete(&self.context, bytes, self.start.elapsed());
+        }
+        Ok(output)
+    }
+
+    /// Like [`Attempt::finish`], for an attempt that failed with `err`.
+    pub(crate) fn fail(&self, bytes: Option<u64>, err: DumpError) -> DumpError {
+        let err = err.with_context(&self.context.service, &self.context.args);
+        instrument::finished(self.start, bytes, Some(&err));
+        if let Some(observer) = &self.observer {
+            observer.on_error(&self.context, &err, self.start.elapsed());
+        }
+        err
+    }
+}
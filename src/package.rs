@@ -0,0 +1,734 @@
+//! Typed output of `dumpsys package <package>`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::package::{ComponentKind, EnabledState, PackageInfo};
+//!
+//! let info = PackageInfo::parse(
+//!     r#"Activity Resolver Table:
+//!   Non-Data Actions:
+//!       android.intent.action.MAIN:
+//!         a1b2c3 com.example.app/.MainActivity filter d4e5f6
+//!           Action: "android.intent.action.MAIN"
+//!
+//! Service Resolver Table:
+//!   Non-Data Actions:
+//!       com.example.app.SYNC:
+//!         b2c3d4 com.example.app/.SyncService filter e5f6a7
+//!
+//! Packages:
+//!   Package [com.example.app] (f00ba4):
+//!     userId=10123
+//!     codePath=/data/app/~~abc==/com.example.app-xyz==
+//!     versionCode=7 minSdk=24 targetSdk=34
+//!     versionName=1.0
+//!     flags=[ HAS_CODE ALLOW_CLEAR_USER_DATA ALLOW_BACKUP ]
+//!     timeStamp=2024-01-01 12:00:00
+//!     lastUpdateTime=2024-01-02 13:00:00
+//!     installerPackageName=com.android.vending
+//!     requested permissions:
+//!       android.permission.INTERNET
+//!       android.permission.CAMERA
+//!     install permissions:
+//!       android.permission.INTERNET: granted=true
+//!     User 0: ceDataInode=12345 installed=true hidden=false suspended=false stopped=false notLaunched=false enabled=0 instant=false
+//!       firstInstallTime=2024-01-01 12:00:05
+//!       runtime permissions:
+//!         android.permission.CAMERA: granted=false, flags=[ USER_SENSITIVE_WHEN_GRANTED|USER_SENSITIVE_WHEN_DENIED ]
+//!         android.permission.POST_NOTIFICATIONS: granted=true, flags=[ USER_SET ]
+//!       disabledComponents:
+//!         com.example.app.LegacyActivity"#,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(info.package, "com.example.app");
+//! assert_eq!(info.uid, Some(10123));
+//! assert_eq!(info.version_code, Some(7));
+//! assert_eq!(info.version_name.as_deref(), Some("1.0"));
+//! assert_eq!((info.min_sdk, info.target_sdk), (Some(24), Some(34)));
+//! assert_eq!(info.last_update_time.as_deref(), Some("2024-01-02 13:00:00"));
+//! assert_eq!(info.requested_permissions.len(), 2);
+//! assert!(info.is_granted("android.permission.INTERNET", 0));
+//! assert!(!info.is_granted("android.permission.CAMERA", 0));
+//! assert_eq!(info.granted_permissions(0).count(), 2);
+//!
+//! let user = info.user(0).unwrap();
+//! assert_eq!(user.enabled, EnabledState::Default);
+//! assert_eq!(user.first_install_time.as_deref(), Some("2024-01-01 12:00:05"));
+//! assert_eq!(user.runtime_permissions[0].flags, ["USER_SENSITIVE_WHEN_GRANTED", "USER_SENSITIVE_WHEN_DENIED"]);
+//! assert_eq!(user.disabled_components, ["com.example.app.LegacyActivity"]);
+//! assert!(info.is_enabled(0));
+//!
+//! assert_eq!(info.components.len(), 2);
+//! assert_eq!(info.components[1].kind, ComponentKind::Service);
+//! assert_eq!(info.components[1].name, "com.example.app/.SyncService");
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, KeyValues},
+    typed, DumpParse,
+};
+
+const PACKAGES: &str = "Packages:";
+const PACKAGE: &str = "Package [";
+const USER: &str = "User ";
+const PROVIDERS: &str = "Registered ContentProviders:";
+const PROVIDER: &str = "Provider{";
+
+/// Kind of an app component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ComponentKind {
+    Activity,
+    Receiver,
+    Service,
+    Provider,
+}
+
+impl ComponentKind {
+    /// Kind of the components listed under `header`, such as `Activity Resolver Table:`.
+    fn from_header(header: &str) -> Option<Self> {
+        match header {
+            "Activity Resolver Table:" => Some(Self::Activity),
+            "Receiver Resolver Table:" => Some(Self::Receiver),
+            "Service Resolver Table:" => Some(Self::Service),
+            "Provider Resolver Table:" | PROVIDERS => Some(Self::Provider),
+            _ => None,
+        }
+    }
+}
+
+/// A component declared in the manifest
+///
+/// Only components with intent filters, and content providers, show up in the dump.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Component {
+    pub kind: ComponentKind,
+    /// As in `com.example.app/.MainActivity`
+    pub name: String,
+}
+
+/// `PackageManager.COMPONENT_ENABLED_STATE_*` of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EnabledState {
+    /// As declared in the manifest
+    #[default]
+    Default,
+    Enabled,
+    Disabled,
+    /// Disabled by the user
+    DisabledUser,
+    DisabledUntilUsed,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for EnabledState {
+    fn from(state: i64) -> Self {
+        match state {
+            0 => Self::Default,
+            1 => Self::Enabled,
+            2 => Self::Disabled,
+            3 => Self::DisabledUser,
+            4 => Self::DisabledUntilUsed,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// A permission with its grant state
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Permission {
+    pub name: String,
+    pub granted: bool,
+    /// Flags such as `USER_SET` or `POLICY_FIXED`
+    pub flags: Vec<String>,
+}
+
+/// State of the package for one user
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserState {
+    pub user: u32,
+    pub installed: bool,
+    pub enabled: EnabledState,
+    pub hidden: bool,
+    pub suspended: bool,
+    /// Force stopped, so it gets no broadcasts until launched again
+    pub stopped: bool,
+    /// On Android 13 and later, the package-wide value before
+    pub first_install_time: Option<String>,
+    pub runtime_permissions: Vec<Permission>,
+    /// Components disabled at runtime, by class name
+    pub disabled_components: Vec<String>,
+    /// Components enabled at runtime, by class name
+    pub enabled_components: Vec<String>,
+}
+
+/// Output of `dumpsys package <package>`
+///
+/// Times are as printed, in the local time of the device.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageInfo {
+    pub package: String,
+    pub uid: Option<u32>,
+    pub version_code: Option<u64>,
+    pub version_name: Option<String>,
+    pub min_sdk: Option<u32>,
+    pub target_sdk: Option<u32>,
+    pub code_path: Option<String>,
+    /// Flags such as `SYSTEM` or `DEBUGGABLE`
+    pub flags: Vec<String>,
+    pub installer: Option<String>,
+    /// Before Android 13, see [`UserState::first_install_time`]
+    pub first_install_time: Option<String>,
+    pub last_update_time: Option<String>,
+    /// Permissions requested in the manifest
+    pub requested_permissions: Vec<String>,
+    /// Normal and signature permissions, granted at install for all users
+    pub install_permissions: Vec<Permission>,
+    pub users: Vec<UserState>,
+    pub components: Vec<Component>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum List {
+    None,
+    Requested,
+    Install,
+    Runtime,
+    Disabled,
+    Enabled,
+}
+
+impl PackageInfo {
+    /// Parse the first package of the `Packages:` section, and the components of all packages.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut info = Self::default();
+        let mut table = None;
+        let mut in_packages = false;
+        let mut in_package = false;
+        let mut list = List::None;
+        let mut list_indent = 0;
+        let mut user_indent = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+
+            if indent == 0 {
+                table = ComponentKind::from_header(trimmed);
+                in_packages = trimmed == PACKAGES;
+                in_package = false;
+                continue;
+            }
+            if let Some(kind) = table {
+                if let Some(name) = component(trimmed) {
+                    let component = Component { kind, name };
+                    if !info.components.contains(&component) {
+                        info.components.push(component);
+                    }
+                }
+                continue;
+            }
+            if !in_packages {
+                continue;
+            }
+
+            if let Some(package) = trimmed.strip_prefix(PACKAGE) {
+                // Only the first package, `dumpsys package` without arguments prints all of them.
+                if !info.package.is_empty() {
+                    break;
+                }
+                let (package, _) = package
+                    .split_once(']')
+                    .ok_or_else(|| invalid("Package", trimmed))?;
+                info.package = package.to_owned();
+                in_package = true;
+                continue;
+            }
+            if !in_package {
+                continue;
+            }
+
+            if list != List::None && indent > list_indent {
+                info.list_item(list, trimmed)?;
+                continue;
+            }
+            list = match trimmed {
+                "requested permissions:" => List::Requested,
+                "install permissions:" => List::Install,
+                "runtime permissions:" => List::Runtime,
+                "disabledComponents:" => List::Disabled,
+                "enabledComponents:" => List::Enabled,
+                _ => List::None,
+            };
+            if list != List::None {
+                list_indent = indent;
+                continue;
+            }
+
+            if let Some(user) = trimmed.strip_prefix(USER) {
+                info.users.push(user_state(user, trimmed)?);
+                user_indent = Some(indent);
+                continue;
+            }
+            match (user_indent, info.users.last_mut()) {
+                (Some(user_indent), Some(user)) if indent > user_indent => {
+                    let fields = KeyValues::parse(trimmed);
+                    if let Some(time) = fields.get("firstInstallTime") {
+                        user.first_install_time = Some(time.to_owned());
+                    }
+                }
+                _ => {
+                    user_indent = None;
+                    info.fields(trimmed)?;
+                }
+            }
+        }
+
+        if info.package.is_empty() {
+            return Err(ParseError::Missing(
+                PACKAGE.trim_end_matches(" [").to_owned(),
+            ));
+        }
+        Ok(info)
+    }
+
+    /// Dump and parse `package`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::package::PackageInfo;
+    ///
+    /// # fn foo() {
+    /// let info = PackageInfo::for_package("com.example.app").unwrap();
+    /// println!("{:?} targets SDK {:?}", info.version_name, info.target_sdk);
+    /// # }
+    /// ```
+    pub fn for_package(package: &str) -> Result<Self, DumpError> {
+        typed::dump_service::<Self>([package])
+    }
+
+    pub fn user(&self, user: u32) -> Option<&UserState> {
+        self.users.iter().find(|state| state.user == user)
+    }
+
+    /// Names of the install and runtime permissions granted to `user`.
+    pub fn granted_permissions(&self, user: u32) -> impl Iterator<Item = &str> + '_ {
+        let runtime = self
+            .user(user)
+            .map_or(&[][..], |state| &state.runtime_permissions);
+        self.install_permissions
+            .iter()
+            .chain(runtime)
+            .filter(|permission| permission.granted)
+            .map(|permission| permission.name.as_str())
+    }
+
+    pub fn is_granted(&self, permission: &str, user: u32) -> bool {
+        self.granted_permissions(user)
+            .any(|name| name == permission)
+    }
+
+    /// Whether the package is installed and not disabled for `user`.
+    pub fn is_enabled(&self, user: u32) -> bool {
+        self.user(user).is_some_and(|state| {
+            state.installed
+                && matches!(state.enabled, EnabledState::Default | EnabledState::Enabled)
+        })
+    }
+
+    /// `key=value` fields of the package, such as `versionCode=7 minSdk=24 targetSdk=34`.
+    fn fields(&mut self, line: &str) -> Result<(), ParseError> {
+        let fields = KeyValues::parse(line);
+        for (key, value) in fields.iter() {
+            let number = || value.parse().map_err(|_| invalid(key, value));
+            match key {
+                "userId" => self.uid = Some(number()?),
+                "versionCode" => self.version_code = Some(fields.get_parsed(key)?),
+                "versionName" => self.version_name = Some(value.to_owned()),
+                "minSdk" => self.min_sdk = Some(number()?),
+                "targetSdk" => self.target_sdk = Some(number()?),
+                "codePath" => self.code_path = Some(value.to_owned()),
+                "flags" | "pkgFlags" if self.flags.is_empty() => self.flags = flags(value),
+                "installerPackageName" => self.installer = Some(value.to_owned()),
+                "firstInstallTime" => self.first_install_time = Some(value.to_owned()),
+                "lastUpdateTime" => self.last_update_time = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn list_item(&mut self, list: List, line: &str) -> Result<(), ParseError> {
+        match list {
+            List::None => {}
+            List::Requested => {
+                // Android 13 and later append restrictions, as in `android.permission.X: restricted=true`.
+                let name = line.split(':').next().unwrap_or(line);
+                self.requested_permissions.push(name.to_owned());
+            }
+            List::Install => self.install_permissions.push(permission(line)?),
+            List::Runtime | List::Disabled | List::Enabled => {
+                let Some(user) = self.users.last_mut() else {
+                    return Err(invalid("User", line));
+                };
+                match list {
+                    List::Runtime => user.runtime_permissions.push(permission(line)?),
+                    List::Disabled => user.disabled_components.push(line.to_owned()),
+                    _ => user.enabled_components.push(line.to_owned()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DumpParse for PackageInfo {
+    const SERVICE: &'static str = "package";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// `a1b2c3 com.example.app/.MainActivity filter d4e5f6` or `Provider{7f com.example.app/.Provider}`
+fn component(line: &str) -> Option<String> {
+    if let Some(provider) = line.strip_prefix(PROVIDER) {
+        let name = provider.trim_end_matches('}').split_whitespace().nth(1)?;
+        return Some(name.to_owned());
+    }
+    let mut words = line.split_whitespace();
+    let (_, name, filter) = (words.next()?, words.next()?, words.next()?);
+    (filter == "filter" && name.contains('/')).then(|| name.to_owned())
+}
+
+/// `0: ceDataInode=12345 installed=true hidden=false stopped=false enabled=0`
+fn user_state(user: &str, line: &str) -> Result<UserState, ParseError> {
+    let (id, fields) = user.split_once(':').ok_or_else(|| invalid("User", line))?;
+    let fields = KeyValues::parse(fields);
+    Ok(UserState {
+        user: id.parse().map_err(|_| invalid("User", line))?,
+        installed: fields.get_bool("installed").unwrap_or(true),
+        enabled: fields
+            .get_int("enabled")
+            .map_or(EnabledState::Default, EnabledState::from),
+        hidden: fields.get_bool("hidden").unwrap_or(false),
+        suspended: fields.get_bool("suspended").unwrap_or(false),
+        stopped: fields.get_bool("stopped").unwrap_or(false),
+        ..UserState::default()
+    })
+}
+
+/// `android.permission.CAMERA: granted=false, flags=[ USER_SET|USER_FIXED ]`
+fn permission(line: &str) -> Result<Permission, ParseError> {
+    let (name, state) = line
+        .split_once(": ")
+        .ok_or_else(|| invalid("permission", line))?;
+    let fields = KeyValues::parse(state);
+    Ok(Permission {
+        name: name.to_owned(),
+        granted: fields.get_bool("granted")?,
+        flags: fields.get("flags").map(flags).unwrap_or_default(),
+    })
+}
+
+/// `[ HAS_CODE ALLOW_BACKUP ]` or `[ USER_SET|USER_FIXED ]`
+fn flags(value: &str) -> Vec<String> {
+    value
+        .trim_matches(['[', ']'])
+        .split(|c: char| c.is_whitespace() || c == '|')
+        .filter(|flag| !flag.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys package com.google.android.youtube` of Android 14, cut down to the sections parsed
+    const DUMP: &str = r#"Activity Resolver Table:
+  Full MIME Types:
+      video/*:
+        6d1f2a3 com.google.android.youtube/com.google.android.apps.youtube.app.application.Shell$UrlActivity filter 8a9b0c1
+          Action: "android.intent.action.VIEW"
+          Category: "android.intent.category.DEFAULT"
+  Non-Data Actions:
+      android.intent.action.MAIN:
+        4e5f6a7 com.google.android.youtube/.app.honeycomb.Shell$HomeActivity filter 1b2c3d4
+          Action: "android.intent.action.MAIN"
+      android.intent.action.SEND:
+        6d1f2a3 com.google.android.youtube/com.google.android.apps.youtube.app.application.Shell$UrlActivity filter 0f1e2d3
+
+Receiver Resolver Table:
+  Non-Data Actions:
+      android.intent.action.BOOT_COMPLETED:
+        7a8b9c0 com.google.android.youtube/com.google.android.libraries.youtube.player.BootReceiver filter 2c3d4e5
+
+Registered ContentProviders:
+  com.google.android.youtube/com.google.android.apps.youtube.app.provider.SuggestionProvider:
+    Provider{9e0f1a2 com.google.android.youtube/com.google.android.apps.youtube.app.provider.SuggestionProvider}
+
+Key Set Manager:
+  [com.google.android.youtube]
+      Signing KeySets: 51
+
+Packages:
+  Package [com.google.android.youtube] (3c4d5e6):
+    userId=10178
+    pkg=Package{7f8e9d0 com.google.android.youtube}
+    codePath=/data/app/~~Xw3F2e1d==/com.google.android.youtube-Gq8sR4tz==
+    primaryCpuAbi=arm64-v8a
+    secondaryCpuAbi=null
+    versionCode=1545405888 minSdk=26 targetSdk=34
+    minExtensionVersions=[]
+    versionName=19.09.37
+    splits=[base, config.arm64_v8a]
+    apkSigningVersion=2
+    flags=[ HAS_CODE ALLOW_CLEAR_USER_DATA ALLOW_BACKUP LARGE_HEAP ]
+    privateFlags=[ PRIVATE_FLAG_ACTIVITIES_RESIZE_MODE_RESIZEABLE PRIVATE_FLAG_REQUEST_LEGACY_EXTERNAL_STORAGE ]
+    dataDir=/data/user/0/com.google.android.youtube
+    timeStamp=2024-03-02 04:11:23
+    lastUpdateTime=2024-03-02 04:11:45
+    installerPackageName=com.android.vending
+    installerPackageUid=10143
+    declared permissions:
+      com.google.android.youtube.permission.C2D_MESSAGE: prot=signature, INSTALLED
+    requested permissions:
+      android.permission.INTERNET
+      android.permission.POST_NOTIFICATIONS
+      android.permission.READ_EXTERNAL_STORAGE: restricted=true
+    install permissions:
+      android.permission.INTERNET: granted=true
+      android.permission.WAKE_LOCK: granted=true
+    User 0: ceDataInode=98765 installed=true hidden=false suspended=false distractionFlags=0 stopped=false notLaunched=false enabled=0 instant=false virtual=false quarantined=false
+      installReason=4
+      firstInstallTime=2023-11-20 10:01:02
+      uninstallReason=0
+      gids=[3003]
+      runtime permissions:
+        android.permission.POST_NOTIFICATIONS: granted=true, flags=[ USER_SET|USER_SENSITIVE_WHEN_GRANTED|USER_SENSITIVE_WHEN_DENIED ]
+        android.permission.READ_EXTERNAL_STORAGE: granted=false, flags=[ RESTRICTION_INSTALLER_EXEMPT ]
+      enabledComponents:
+        com.google.android.apps.youtube.app.application.Shell$UrlActivity
+    User 10: ceDataInode=0 installed=false hidden=false suspended=false distractionFlags=0 stopped=true notLaunched=true enabled=3 instant=false virtual=false quarantined=false
+      firstInstallTime=2024-01-05 08:00:00
+      runtime permissions:
+
+Hidden system packages:
+  Package [com.google.android.youtube] (1a2b3c4):
+    userId=10178
+    codePath=/product/app/YouTube
+    versionCode=1540258752 minSdk=26 targetSdk=33
+"#;
+
+    #[test]
+    fn parses_the_dump() {
+        let info = PackageInfo::parse(DUMP).unwrap();
+        assert_eq!(info.package, "com.google.android.youtube");
+        assert_eq!(info.uid, Some(10178));
+        assert_eq!(info.version_code, Some(1_545_405_888));
+        assert_eq!(info.version_name.as_deref(), Some("19.09.37"));
+        assert_eq!((info.min_sdk, info.target_sdk), (Some(26), Some(34)));
+        assert_eq!(
+            info.code_path.as_deref(),
+            Some("/data/app/~~Xw3F2e1d==/com.google.android.youtube-Gq8sR4tz==")
+        );
+        assert_eq!(
+            info.flags,
+            [
+                "HAS_CODE",
+                "ALLOW_CLEAR_USER_DATA",
+                "ALLOW_BACKUP",
+                "LARGE_HEAP"
+            ]
+        );
+        assert_eq!(info.installer.as_deref(), Some("com.android.vending"));
+        assert_eq!(info.first_install_time, None);
+        assert_eq!(
+            info.last_update_time.as_deref(),
+            Some("2024-03-02 04:11:45")
+        );
+        assert_eq!(
+            info.requested_permissions,
+            [
+                "android.permission.INTERNET",
+                "android.permission.POST_NOTIFICATIONS",
+                "android.permission.READ_EXTERNAL_STORAGE",
+            ]
+        );
+        assert_eq!(info.install_permissions.len(), 2);
+    }
+
+    #[test]
+    fn parses_the_users() {
+        let info = PackageInfo::parse(DUMP).unwrap();
+        assert_eq!(info.users.len(), 2);
+
+        let owner = info.user(0).unwrap();
+        assert_eq!(
+            owner.first_install_time.as_deref(),
+            Some("2023-11-20 10:01:02")
+        );
+        assert_eq!(
+            owner.runtime_permissions[0],
+            Permission {
+                name: "android.permission.POST_NOTIFICATIONS".to_owned(),
+                granted: true,
+                flags: vec![
+                    "USER_SET".to_owned(),
+                    "USER_SENSITIVE_WHEN_GRANTED".to_owned(),
+                    "USER_SENSITIVE_WHEN_DENIED".to_owned(),
+                ],
+            }
+        );
+        assert_eq!(
+            owner.enabled_components,
+            ["com.google.android.apps.youtube.app.application.Shell$UrlActivity"]
+        );
+        assert!(owner.disabled_components.is_empty());
+        assert!(info.is_enabled(0));
+        assert!(info.is_granted("android.permission.POST_NOTIFICATIONS", 0));
+        assert!(!info.is_granted("android.permission.READ_EXTERNAL_STORAGE", 0));
+        assert_eq!(info.granted_permissions(0).count(), 3);
+
+        let work = info.user(10).unwrap();
+        assert!(!work.installed);
+        assert!(work.stopped);
+        assert_eq!(work.enabled, EnabledState::DisabledUser);
+        assert!(work.runtime_permissions.is_empty());
+        assert!(!info.is_enabled(10));
+        assert!(!info.is_enabled(11));
+        assert_eq!(info.granted_permissions(10).count(), 2);
+    }
+
+    #[test]
+    fn parses_the_components() {
+        let info = PackageInfo::parse(DUMP).unwrap();
+        let components: Vec<_> = info
+            .components
+            .iter()
+            .map(|component| (component.kind, component.name.as_str()))
+            .collect();
+        assert_eq!(
+            components,
+            [
+                (
+                    ComponentKind::Activity,
+                    "com.google.android.youtube/com.google.android.apps.youtube.app.application.Shell$UrlActivity"
+                ),
+                (ComponentKind::Activity, "com.google.android.youtube/.app.honeycomb.Shell$HomeActivity"),
+                (
+                    ComponentKind::Receiver,
+                    "com.google.android.youtube/com.google.android.libraries.youtube.player.BootReceiver"
+                ),
+                (
+                    ComponentKind::Provider,
+                    "com.google.android.youtube/com.google.android.apps.youtube.app.provider.SuggestionProvider"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_first_package_is_parsed() {
+        let text = "Packages:
+  Package [com.android.chrome] (1a2b3c4):
+    userId=10216
+    versionCode=612109833 minSdk=29 targetSdk=34
+  Package [com.android.settings] (5d6e7f8):
+    userId=1000
+    versionCode=34 minSdk=34 targetSdk=34
+";
+        let info = PackageInfo::parse(text).unwrap();
+        assert_eq!(info.package, "com.android.chrome");
+        assert_eq!(info.uid, Some(10216));
+        assert_eq!(info.target_sdk, Some(34));
+    }
+
+    #[test]
+    fn install_time_before_android_13() {
+        let text = "Packages:
+  Package [com.example.app] (f00ba4):
+    userId=10123
+    pkgFlags=[ HAS_CODE ALLOW_BACKUP ]
+    firstInstallTime=2021-06-01 09:30:00
+    User 0: ceDataInode=4321 installed=true hidden=false suspended=false stopped=false notLaunched=false enabled=4
+";
+        let info = PackageInfo::parse(text).unwrap();
+        assert_eq!(
+            info.first_install_time.as_deref(),
+            Some("2021-06-01 09:30:00")
+        );
+        assert_eq!(info.flags, ["HAS_CODE", "ALLOW_BACKUP"]);
+        assert_eq!(
+            info.user(0).unwrap().enabled,
+            EnabledState::DisabledUntilUsed
+        );
+        assert!(!info.is_enabled(0));
+    }
+
+    #[test]
+    fn unknown_enabled_state_is_kept() {
+        let text = DUMP.replace("notLaunched=false enabled=0", "notLaunched=false enabled=7");
+        let info = PackageInfo::parse(&text).unwrap();
+        assert_eq!(info.user(0).unwrap().enabled, EnabledState::Other(7));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            PackageInfo::parse(&DUMP.replace('\n', "\r\n")).unwrap(),
+            PackageInfo::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_package_fails() {
+        let missing = ParseError::Missing("Package".to_owned());
+        assert_eq!(PackageInfo::parse("").unwrap_err(), missing);
+        // `dumpsys package` of a package that isn't installed still lists the resolver tables
+        let text = DUMP.split("Packages:").next().unwrap();
+        assert_eq!(PackageInfo::parse(text).unwrap_err(), missing);
+    }
+
+    #[test]
+    fn truncated_package_is_invalid() {
+        assert_eq!(
+            PackageInfo::parse("Packages:\n  Package [com.google.andr").unwrap_err(),
+            invalid("Package", "Package [com.google.andr")
+        );
+        let text = "Packages:
+  Package [com.example.app] (f00ba4):
+    install permissions:
+      android.permission.INTERNET: granted=tr";
+        assert_eq!(
+            PackageInfo::parse(text).unwrap_err(),
+            invalid("granted", "tr")
+        );
+    }
+
+    #[test]
+    fn runtime_permissions_outside_a_user_are_invalid() {
+        let text = "Packages:
+  Package [com.example.app] (f00ba4):
+    runtime permissions:
+      android.permission.CAMERA: granted=true";
+        assert_eq!(
+            PackageInfo::parse(text).unwrap_err(),
+            invalid("User", "android.permission.CAMERA: granted=true")
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Parsing dump output without a device
+//!
+//! Every parser in this crate works on plain text, whether it came from a live [`Dumpsys`](crate::Dumpsys)
+//! or from a `bugreport.txt` or `dumpsys` output pulled off a device earlier. [`read_lossy`] loads such
+//! files, which often contain invalid UTF-8 from truncated or binary sections, and [`sections`] splits
+//! them into the output of each service.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::{parse, ThreadUsage};
+//!
+//! // Pulled off a device with `cat /dev/binderfs/binder_logs/proc/1234`, cut short mid-line.
+//! let pulled: &[u8] = b"proc 1234
+//! context binder
+//!   thread 1234: l 12 need_return 0 tr 0
+//!   thread 1240: l 11 need_return 0 tr 0
+//!   thread 1241: l 01 need_return 0 tr 0
+//!   thread 12\xe2\x80";
+//! let logs = parse::read_lossy(pulled).unwrap();
+//! let usage = ThreadUsage::parse(&logs);
+//! assert_eq!((usage.in_use, usage.total), (1, 3));
+//! ```
+
+pub mod kv;
+mod registry;
+mod sections;
+pub mod table;
+mod value;
+
+use std::io::{self, Read};
+
+use crate::error::{DumpError, ParseError};
+
+pub use registry::{Parsed, ParserRegistry};
+pub use sections::{section_map, sections, DumpSection};
+pub use value::FromDumpValue;
+
+/// Uids reserved for each user, see `UserHandle.PER_USER_RANGE`
+const PER_USER_RANGE: u32 = 100_000;
+const FIRST_APPLICATION_UID: u32 = 10_000;
+const FIRST_ISOLATED_UID: u32 = 99_000;
+
+/// Read all of `reader` as text, replacing invalid UTF-8 with `U+FFFD`.
+pub fn read_lossy(mut reader: impl Read) -> io::Result<String> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    Ok(match String::from_utf8(buf) {
+        Ok(text) => text,
+        Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+    })
+}
+
+/// Parse a uid as formatted by `UserHandle.formatUid`, e.g. `1000`, `u0a123` for 10123 or `u10i5` for an
+/// isolated process of user 10.
+///
+/// ```
+/// use dumpsys_rs::parse::parse_uid;
+///
+/// assert_eq!(parse_uid("1000"), Some(1000));
+/// assert_eq!(parse_uid("u0a123"), Some(10123));
+/// assert_eq!(parse_uid("u10a5"), Some(1_010_005));
+/// assert_eq!(parse_uid("u0i5"), Some(99005));
+/// assert_eq!(parse_uid("u0s1000"), Some(1000));
+/// ```
+pub fn parse_uid(text: &str) -> Option<u32> {
+    if let Ok(uid) = text.parse() {
+        return Some(uid);
+    }
+
+    let rest = text.strip_prefix('u')?;
+    let kind = rest.find(|c: char| !c.is_ascii_digit())?;
+    let user: u32 = rest[..kind].parse().ok()?;
+    let id: u32 = rest[kind + 1..].parse().ok()?;
+    let app_id = match rest.as_bytes()[kind] {
+        b'a' => FIRST_APPLICATION_UID + id,
+        b'i' => FIRST_ISOLATED_UID + id,
+        b's' => id,
+        _ => return None,
+    };
+    user.checked_mul(PER_USER_RANGE)?.checked_add(app_id)
+}
+
+/// Typed form of the output of one service, see [`TypedDumpsys`](crate::TypedDumpsys)
+///
+/// With the `derive` feature, `#[derive(DumpParse)]` implements it for structs whose fields are found
+/// by `key=value` key or by regex:
+///
+#[cfg_attr(feature = "derive", doc = "```")]
+#[cfg_attr(not(feature = "derive"), doc = "```ignore")]
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::DumpParse;
+///
+/// #[derive(DumpParse)]
+/// #[dump(service = "power", args = ["-a"])]
+/// struct Power {
+///     #[dump(key = "mWakefulness")]
+///     wakefulness: String,
+///     #[dump(key = "mScreenOffTimeoutSetting")]
+///     screen_off_timeout: Duration,
+///     #[dump(key = "mBatteryLevel")]
+///     battery_level: Option<u32>,
+///     #[dump(regex = r"Display Power: state=(\w+)")]
+///     display_state: String,
+/// }
+///
+/// let power = Power::parse(
+///     "  mWakefulness=Awake
+///   mScreenOffTimeoutSetting=30000
+/// Display Power: state=ON",
+/// )
+/// .unwrap();
+/// assert_eq!(power.wakefulness, "Awake");
+/// assert_eq!(power.screen_off_timeout, Duration::from_secs(30));
+/// assert_eq!(power.battery_level, None);
+/// assert_eq!(power.display_state, "ON");
+/// ```
+///
+/// Fields without an attribute use their name as key, `Option` fields may be missing from the output
+/// and all field types implement [`FromDumpValue`].
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{error::ParseError, parse::kv::KeyValues, DumpParse};
+///
+/// struct Power {
+///     wakefulness: String,
+/// }
+///
+/// impl DumpParse for Power {
+///     const SERVICE: &'static str = "power";
+///
+///     fn parse(text: &str) -> Result<Self, ParseError> {
+///         let fields = KeyValues::parse(text);
+///         Ok(Self {
+///             wakefulness: fields.require("mWakefulness")?.to_owned(),
+///         })
+///     }
+/// }
+/// ```
+pub trait DumpParse: Sized {
+    /// Service whose output this parses
+    const SERVICE: &'static str;
+    /// Arguments producing the output [`DumpParse::parse`] expects
+    const ARGS: &'static [&'static str] = &[];
+
+    fn parse(text: &str) -> Result<Self, ParseError>;
+
+    /// Like [`DumpParse::parse`], reading the output from `reader`, e.g. a file saved earlier.
+    fn from_reader(reader: impl Read) -> Result<Self, DumpError> {
+        let text = read_lossy(reader)?;
+        Ok(Self::parse(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uid_of_an_unknown_kind_is_none() {
+        assert_eq!(parse_uid("u0x5"), None);
+    }
+
+    #[test]
+    fn truncated_uid_is_none() {
+        assert_eq!(parse_uid(""), None);
+        assert_eq!(parse_uid("u"), None);
+        assert_eq!(parse_uid("u0"), None);
+        assert_eq!(parse_uid("u0a"), None);
+    }
+
+    #[test]
+    fn uid_past_the_last_user_is_none() {
+        assert_eq!(parse_uid("u99999a1"), None);
+    }
+
+    #[test]
+    fn read_lossy_replaces_invalid_utf8() {
+        assert_eq!(read_lossy(&b"ok\xff\r\n"[..]).unwrap(), "ok\u{fffd}\r\n");
+        assert_eq!(read_lossy(&b""[..]).unwrap(), "");
+    }
+}
@@ -0,0 +1,252 @@
+//! `key=value` fields as in `mScreenOn=true mWakefulness=Awake`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::parse::kv::KeyValues;
+//!
+//! let fields = KeyValues::parse(
+//!     "  mWakefulness=Awake mScreenOn=true\n  mLastSleepTime=+1m30s250ms (12s ago) mUserId=0",
+//! );
+//! assert_eq!(fields.get("mWakefulness"), Some("Awake"));
+//! assert_eq!(fields.get_bool("mScreenOn"), Ok(true));
+//! assert_eq!(fields.get_int("mUserId"), Ok(0));
+//! assert_eq!(
+//!     fields.get_duration("mLastSleepTime"),
+//!     Ok(Duration::from_millis(90_250)),
+//! );
+//! ```
+
+use std::{str::FromStr, time::Duration};
+
+use crate::error::ParseError;
+
+/// The `key=value` fields of some dump text, in order of appearance
+///
+/// A value runs until the next `key=` on the same line, so it may contain spaces, as in
+/// `mLastWakeTime=12345 (3s ago)`. Getters that convert the value look at its first word only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyValues<'a> {
+    fields: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> KeyValues<'a> {
+    /// Collect every `key=value` field of `text`.
+    pub fn parse(text: &'a str) -> Self {
+        let mut fields = Vec::new();
+        for line in text.lines() {
+            parse_line(line, &mut fields);
+        }
+        Self { fields }
+    }
+
+    /// Value of the first `key` field.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.get_all(key).next()
+    }
+
+    /// Values of every `key` field, e.g. one per listed wake lock.
+    pub fn get_all<'s>(&'s self, key: &'s str) -> impl Iterator<Item = &'a str> + 's {
+        self.fields
+            .iter()
+            .filter(move |(name, _)| *name == key)
+            .map(|(_, value)| *value)
+    }
+
+    /// Whether there is a `key` field.
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Value of `key`, failing if it's missing.
+    pub fn require(&self, key: &str) -> Result<&'a str, ParseError> {
+        self.get(key)
+            .ok_or_else(|| ParseError::Missing(key.to_owned()))
+    }
+
+    /// `true` or `false` value of `key`.
+    pub fn get_bool(&self, key: &str) -> Result<bool, ParseError> {
+        self.get_parsed(key)
+    }
+
+    /// Integer value of `key`, decimal or `0x` prefixed hex.
+    pub fn get_int(&self, key: &str) -> Result<i64, ParseError> {
+        let value = self.require(key)?;
+        parse_int(first_word(value)).ok_or_else(|| invalid(key, value))
+    }
+
+    /// Floating point value of `key`.
+    pub fn get_float(&self, key: &str) -> Result<f64, ParseError> {
+        self.get_parsed(key)
+    }
+
+    /// Duration value of `key`, either in the `+1h2m3s45ms` form of Android's `TimeUtils.formatDuration`
+    /// or a bare number of milliseconds.
+    pub fn get_duration(&self, key: &str) -> Result<Duration, ParseError> {
+        let value = self.require(key)?;
+        parse_duration(first_word(value)).ok_or_else(|| invalid(key, value))
+    }
+
+    /// Value of `key` converted with [`FromStr`].
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Result<T, ParseError> {
+        let value = self.require(key)?;
+        first_word(value).parse().map_err(|_| invalid(key, value))
+    }
+
+    /// Every field as `(key, value)`, in order of appearance.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.fields.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+/// Parse a duration like `+1d2h3m4s5ms`, `-` signs excluded, or a bare number of milliseconds.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.strip_prefix('+').unwrap_or(text);
+    if let Ok(millis) = text.parse() {
+        return Some(Duration::from_millis(millis));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return None;
+        }
+        let n: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit] {
+            "d" => Duration::from_secs(n.saturating_mul(24 * 60 * 60)),
+            "h" => Duration::from_secs(n.saturating_mul(60 * 60)),
+            "m" => Duration::from_secs(n.saturating_mul(60)),
+            "s" => Duration::from_secs(n),
+            "ms" => Duration::from_millis(n),
+            "us" => Duration::from_micros(n),
+            "ns" => Duration::from_nanos(n),
+            _ => return None,
+        };
+        total += part;
+        rest = &rest[unit..];
+    }
+    Some(total)
+}
+
+fn parse_line<'a>(line: &'a str, fields: &mut Vec<(&'a str, &'a str)>) {
+    let mut keys = Vec::new();
+    let bytes = line.as_bytes();
+    for (eq, _) in line.match_indices('=') {
+        let start = line[..eq]
+            .rfind(|c: char| !is_key_char(c))
+            .map_or(0, |i| i + 1);
+        let key = &line[start..eq];
+        let boundary = start == 0 || matches!(bytes[start - 1], b' ' | b'\t' | b',' | b'{' | b'[');
+        if boundary && key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            keys.push((start, eq));
+        }
+    }
+
+    for (i, &(start, eq)) in keys.iter().enumerate() {
+        let end = keys.get(i + 1).map_or(line.len(), |&(next, _)| next);
+        let value = line[eq + 1..end]
+            .trim_end()
+            .trim_end_matches([',', '}', ']'])
+            .trim();
+        fields.push((&line[start..eq], value));
+    }
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+pub(crate) fn first_word(value: &str) -> &str {
+    value.split_whitespace().next().unwrap_or("")
+}
+
+/// Decimal or `0x` prefixed hex integer.
+pub(crate) fn parse_int(text: &str) -> Option<i64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+pub(crate) fn invalid(key: &str, value: &str) -> ParseError {
+    ParseError::Invalid {
+        key: key.to_owned(),
+        value: value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_key_fails() {
+        let fields = KeyValues::parse("mWakefulness=Awake");
+        assert_eq!(
+            fields.get_int("mUserId"),
+            Err(ParseError::Missing("mUserId".to_owned()))
+        );
+    }
+
+    #[test]
+    fn value_of_the_wrong_form_is_invalid() {
+        let fields = KeyValues::parse("mScreenOn=maybe");
+        assert_eq!(
+            fields.get_bool("mScreenOn"),
+            Err(ParseError::Invalid {
+                key: "mScreenOn".to_owned(),
+                value: "maybe".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let text = "mWakefulness=Awake\nmUserId=0 mScreenOn=true\n";
+        let crlf = text.replace('\n', "\r\n");
+        assert_eq!(KeyValues::parse(&crlf), KeyValues::parse(text));
+    }
+
+    #[test]
+    fn empty_input_has_no_fields() {
+        assert!(KeyValues::parse("").is_empty());
+        assert!(KeyValues::parse("\n\n").is_empty());
+    }
+
+    #[test]
+    fn truncated_field_has_an_empty_value() {
+        let fields = KeyValues::parse("mWakefulness=Awake mUserId=");
+        assert_eq!(fields.get("mUserId"), Some(""));
+        assert!(fields.get_int("mUserId").is_err());
+    }
+
+    #[test]
+    fn durations_of_every_form() {
+        assert_eq!(parse_duration("1500"), Some(Duration::from_millis(1500)));
+        assert_eq!(
+            parse_duration("+1h2m3s4ms"),
+            Some(Duration::from_millis(3_723_004))
+        );
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration("ms"), None);
+    }
+}
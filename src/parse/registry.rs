@@ -0,0 +1,248 @@
+use std::{any::Any, collections::HashMap, fmt};
+
+use crate::{
+    activity::Activities,
+    alarm::AlarmManager,
+    appops::AppOps,
+    audio::AudioState,
+    battery::BatteryStatus,
+    batterystats::BatteryStats,
+    connectivity::Connectivity,
+    cpuinfo::CpuInfo,
+    deviceidle::DeviceIdle,
+    diskstats::DiskStats,
+    display::DisplayManager,
+    dropbox::DropBox,
+    error::ParseError,
+    gfxinfo::Gfxinfo,
+    gpu::{GpuState, GraphicBuffers},
+    input::InputState,
+    jobscheduler::JobScheduler,
+    location::LocationState,
+    media_session::MediaSessions,
+    meminfo::MemInfo,
+    netstats::NetStats,
+    notification::NotificationState,
+    package::PackageInfo,
+    power::PowerState,
+    procstats::ProcStats,
+    sensorservice::SensorService,
+    telephony::TelephonyRegistry,
+    thermalservice::ThermalService,
+    usagestats::UsageStats,
+    user::Users,
+    wifi::WifiState,
+    window::WindowManager,
+    DumpParse,
+};
+
+type Parser = Box<dyn Fn(&str) -> Result<Parsed, ParseError> + Send + Sync>;
+
+/// Parsers keyed by service name, to turn the output of many services into typed data at once
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{
+///     error::ParseError,
+///     parse::{kv::KeyValues, ParserRegistry},
+///     service_manager,
+/// };
+///
+/// struct Power {
+///     wakefulness: String,
+/// }
+///
+/// fn parse_power(text: &str) -> Result<Power, ParseError> {
+///     let fields = KeyValues::parse(text);
+///     Ok(Power {
+///         wakefulness: fields.require("mWakefulness")?.to_owned(),
+///     })
+/// }
+///
+/// let mut registry = ParserRegistry::new();
+/// registry.register("power", parse_power);
+///
+/// let outputs = service_manager::dump_all(Duration::from_secs(10), Vec::<&str>::new())
+///     .unwrap()
+///     .filter_map(|(service, result)| Some((service, result.ok()?)));
+/// for (service, parsed) in registry.parse_all(outputs) {
+///     if let Some(power) = parsed.ok().and_then(|parsed| parsed.downcast::<Power>().ok()) {
+///         println!("{service}: {}", power.wakefulness);
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct ParserRegistry {
+    parsers: HashMap<String, Parser>,
+}
+
+impl fmt::Debug for ParserRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.parsers.keys()).finish()
+    }
+}
+
+impl ParserRegistry {
+    /// A registry without any parsers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with a parser for every service the crate has a [`DumpParse`] type for.
+    ///
+    /// A registry holds one parser per service, so where several types parse the same service the one
+    /// for the most complete dump wins:
+    ///
+    /// - `meminfo`: [`MemInfo`], [`PackageMemInfo`](crate::meminfo::PackageMemInfo) is for the dump of a
+    ///   single package
+    /// - `SurfaceFlinger`: [`GraphicBuffers`], [`Latency`](crate::surfaceflinger::Latency) and
+    ///   [`TimeStats`](crate::surfaceflinger::TimeStats) are for dumps with their arguments
+    /// - `batterystats`: [`BatteryStats`], [`Checkin`](crate::checkin::Checkin) is for `--checkin` dumps
+    /// - `activity`: [`Activities`], [`Processes`](crate::activity::Processes) is for the
+    ///   `processes` section
+    ///
+    /// Register one of the others afterwards to replace the default.
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register_parse::<Activities>()
+            .register_parse::<AlarmManager>()
+            .register_parse::<AppOps>()
+            .register_parse::<AudioState>()
+            .register_parse::<BatteryStats>()
+            .register_parse::<BatteryStatus>()
+            .register_parse::<Connectivity>()
+            .register_parse::<CpuInfo>()
+            .register_parse::<DeviceIdle>()
+            .register_parse::<DiskStats>()
+            .register_parse::<DisplayManager>()
+            .register_parse::<DropBox>()
+            .register_parse::<Gfxinfo>()
+            .register_parse::<GpuState>()
+            .register_parse::<GraphicBuffers>()
+            .register_parse::<InputState>()
+            .register_parse::<JobScheduler>()
+            .register_parse::<LocationState>()
+            .register_parse::<MediaSessions>()
+            .register_parse::<MemInfo>()
+            .register_parse::<NetStats>()
+            .register_parse::<NotificationState>()
+            .register_parse::<PackageInfo>()
+            .register_parse::<PowerState>()
+            .register_parse::<ProcStats>()
+            .register_parse::<SensorService>()
+            .register_parse::<TelephonyRegistry>()
+            .register_parse::<ThermalService>()
+            .register_parse::<UsageStats>()
+            .register_parse::<Users>()
+            .register_parse::<WifiState>()
+            .register_parse::<WindowManager>();
+        registry
+    }
+
+    /// Parse the output of `service` with `parser`, replacing the parser registered before.
+    pub fn register<T, F>(&mut self, service: impl AsRef<str>, parser: F) -> &mut Self
+    where
+        T: Any + Send + Sync,
+        F: Fn(&str) -> Result<T, ParseError> + Send + Sync + 'static,
+    {
+        self.parsers.insert(
+            service.as_ref().to_owned(),
+            Box::new(move |text| parser(text).map(|value| Parsed(Box::new(value)))),
+        );
+        self
+    }
+
+    /// Parse the output of [`T::SERVICE`](DumpParse::SERVICE) with [`T::parse`](DumpParse::parse).
+    pub fn register_parse<T>(&mut self) -> &mut Self
+    where
+        T: DumpParse + Any + Send + Sync,
+    {
+        self.register(T::SERVICE, T::parse)
+    }
+
+    /// Whether there is a parser for `service`.
+    pub fn contains(&self, service: &str) -> bool {
+        self.parsers.contains_key(service)
+    }
+
+    /// Parse `text` as the output of `service`, `None` without a parser for it.
+    pub fn parse(&self, service: &str, text: &str) -> Option<Result<Parsed, ParseError>> {
+        self.parsers.get(service).map(|parser| parser(text))
+    }
+
+    /// Parse the output of every `(service, text)` pair with a parser, skipping the other services.
+    ///
+    /// Takes e.g. the successful dumps of [`dump_all`](crate::service_manager::dump_all) or a
+    /// [`section_map`](crate::parse::section_map) of a bugreport.
+    pub fn parse_all(
+        &self,
+        outputs: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>)>,
+    ) -> HashMap<String, Result<Parsed, ParseError>> {
+        outputs
+            .into_iter()
+            .filter_map(|(service, text)| {
+                let service = service.as_ref();
+                Some((service.to_owned(), self.parse(service, text.as_ref())?))
+            })
+            .collect()
+    }
+}
+
+/// Output of a parser from a [`ParserRegistry`], to be downcast to the type the parser returns
+pub struct Parsed(Box<dyn Any + Send + Sync>);
+
+impl fmt::Debug for Parsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Parsed").finish_non_exhaustive()
+    }
+}
+
+impl Parsed {
+    /// Whether the value is a `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.is::<T>()
+    }
+
+    /// The value, if it's a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    /// The value, or `self` back if it isn't a `T`.
+    pub fn downcast<T: Any>(self) -> Result<T, Self> {
+        self.0.downcast().map(|value| *value).map_err(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meminfo::PackageMemInfo;
+
+    const MEMINFO: &str = "Total PSS by process:
+    412,345K: system (pid 1234)
+
+Total RAM: 7,823,456K (status normal)
+";
+
+    #[test]
+    fn builtin_parses_meminfo_as_the_system_summary() {
+        let registry = ParserRegistry::with_builtin();
+        let parsed = registry.parse("meminfo", MEMINFO).unwrap().unwrap();
+        assert!(parsed.is::<MemInfo>());
+    }
+
+    #[test]
+    fn register_replaces_a_builtin_parser() {
+        let mut registry = ParserRegistry::with_builtin();
+        registry.register_parse::<PackageMemInfo>();
+        // A system summary has no package header
+        assert!(registry.parse("meminfo", MEMINFO).unwrap().is_err());
+        assert!(registry.contains("SurfaceFlinger"));
+        assert!(!registry.contains("nonexistent"));
+    }
+}
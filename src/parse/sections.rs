@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use crate::DumpPriority;
+
+const HEADER: &str = "DUMP OF SERVICE ";
+
+/// Output of one service in the concatenated output of a bare `dumpsys` or a bugreport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DumpSection<'a> {
+    pub service: &'a str,
+    /// Priority named in the header, e.g. `CRITICAL` in `DUMP OF SERVICE CRITICAL SurfaceFlinger:`
+    pub priority: Option<DumpPriority>,
+    /// Output between the header and the footer, without either
+    pub text: &'a str,
+}
+
+/// Split `text` at its `DUMP OF SERVICE <name>:` headers, in order of appearance.
+///
+/// Text before the first header, the separator lines and the `--------- 0.01s was the duration of
+/// dumpsys ...` footers aren't part of any section.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::parse;
+///
+/// let text = "\
+/// -------------------------------------------------------------------------------
+/// DUMP OF SERVICE power:
+/// mWakefulness=Awake
+/// --------- 0.004s was the duration of dumpsys power, ending at: 2024-01-01 00:00:00
+/// ";
+/// let sections = parse::sections(text);
+/// assert_eq!(sections[0].service, "power");
+/// assert_eq!(sections[0].text, "mWakefulness=Awake\n");
+/// ```
+pub fn sections(text: &str) -> Vec<DumpSection<'_>> {
+    let mut sections = Vec::new();
+    let mut open: Option<(&str, Option<DumpPriority>, usize)> = None;
+    let mut end = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+
+        if let Some(name) = content
+            .strip_prefix(HEADER)
+            .and_then(|rest| rest.strip_suffix(':'))
+        {
+            if let Some((service, priority, from)) = open.take() {
+                sections.push(DumpSection {
+                    service,
+                    priority,
+                    text: &text[from..end.unwrap_or(start)],
+                });
+            }
+            let (service, priority) = split_priority(name);
+            open = Some((service, priority, offset));
+            end = None;
+            continue;
+        }
+
+        if open.is_none() {
+            continue;
+        }
+        if is_footer(content) {
+            if let Some((service, priority, from)) = open.take() {
+                sections.push(DumpSection {
+                    service,
+                    priority,
+                    text: &text[from..end.unwrap_or(start)],
+                });
+            }
+        } else if is_separator(content) {
+            // Belongs to the next header, unless more output follows.
+            end.get_or_insert(start);
+        } else if !content.is_empty() {
+            end = None;
+        }
+    }
+
+    if let Some((service, priority, from)) = open {
+        sections.push(DumpSection {
+            service,
+            priority,
+            text: &text[from..end.unwrap_or(text.len())],
+        });
+    }
+    sections
+}
+
+/// Output of every service in `text`, joining the sections of a service dumped at several priorities.
+///
+/// Holds the same as collecting the successful dumps of
+/// [`service_manager::dump_all`](crate::service_manager::dump_all), but for saved output, e.g. a bugreport.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::parse;
+///
+/// let bugreport = "\
+/// -------------------------------------------------------------------------------
+/// DUMP OF SERVICE CRITICAL meminfo:
+/// Total RAM: 7,612,312K
+/// --------- 0.012s was the duration of dumpsys meminfo, ending at: 2024-01-01 00:00:00
+/// -------------------------------------------------------------------------------
+/// DUMP OF SERVICE meminfo:
+/// Lost RAM: 412,008K
+/// --------- 0.020s was the duration of dumpsys meminfo, ending at: 2024-01-01 00:00:01
+/// ";
+/// let services = parse::section_map(bugreport);
+/// assert_eq!(services["meminfo"], "Total RAM: 7,612,312K\nLost RAM: 412,008K\n");
+/// ```
+pub fn section_map(text: &str) -> HashMap<String, String> {
+    let mut map: HashMap<String, String> = HashMap::new();
+    for section in sections(text) {
+        map.entry(section.service.to_owned())
+            .or_default()
+            .push_str(section.text);
+    }
+    map
+}
+
+/// Split `CRITICAL SurfaceFlinger` into the service and the priority dumpsys put in front of it.
+fn split_priority(name: &str) -> (&str, Option<DumpPriority>) {
+    let Some((priority, service)) = name.split_once(' ') else {
+        return (name, None);
+    };
+    let priority = match priority {
+        "CRITICAL" => DumpPriority::Critical,
+        "HIGH" => DumpPriority::High,
+        "NORMAL" => DumpPriority::Normal,
+        _ => return (name, None),
+    };
+    (service, Some(priority))
+}
+
+/// e.g. `--------- 0.004s was the duration of dumpsys power, ending at: 2024-01-01 00:00:00`
+fn is_footer(line: &str) -> bool {
+    line.starts_with("--------- ") && line.contains(" was the duration of dumpsys ")
+}
+
+fn is_separator(line: &str) -> bool {
+    line.len() >= 10 && line.bytes().all(|byte| byte == b'-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUGREPORT: &str = "\
+== dumpstate: 2024-01-01 00:00:00
+-------------------------------------------------------------------------------
+DUMP OF SERVICE CRITICAL SurfaceFlinger:
+Display 0 HWC layers:
+--------- 0.010s was the duration of dumpsys SurfaceFlinger, ending at: 2024-01-01 00:00:00
+-------------------------------------------------------------------------------
+DUMP OF SERVICE power:
+mWakefulness=Awake
+
+-------------------------------------------------------------------------------
+DUMP OF SERVICE HIGH power:
+mScreenOn=true
+";
+
+    #[test]
+    fn splits_at_headers() {
+        let sections = sections(BUGREPORT);
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].service, "SurfaceFlinger");
+        assert_eq!(sections[0].priority, Some(DumpPriority::Critical));
+        assert_eq!(sections[0].text, "Display 0 HWC layers:\n");
+        assert_eq!(sections[1].service, "power");
+        assert_eq!(sections[1].priority, None);
+        assert_eq!(sections[1].text, "mWakefulness=Awake\n\n");
+        assert_eq!(sections[2].priority, Some(DumpPriority::High));
+    }
+
+    #[test]
+    fn joins_sections_of_a_service() {
+        let services = section_map(BUGREPORT);
+        assert_eq!(services["power"], "mWakefulness=Awake\n\nmScreenOn=true\n");
+    }
+
+    #[test]
+    fn crlf_line_endings_keep_the_sections() {
+        let crlf = BUGREPORT.replace('\n', "\r\n");
+        let sections = sections(&crlf);
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].service, "SurfaceFlinger");
+        assert_eq!(sections[0].text, "Display 0 HWC layers:\r\n");
+    }
+
+    #[test]
+    fn empty_input_has_no_sections() {
+        assert!(sections("").is_empty());
+        assert!(sections("no headers here\n").is_empty());
+    }
+
+    #[test]
+    fn truncated_section_runs_to_the_end() {
+        let sections = sections("DUMP OF SERVICE meminfo:\nTotal RAM: 7,732,844K");
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].text, "Total RAM: 7,732,844K");
+    }
+
+    #[test]
+    fn unknown_priority_is_part_of_the_name() {
+        let sections = sections("DUMP OF SERVICE URGENT power:\n");
+        assert_eq!(sections[0].service, "URGENT power");
+        assert_eq!(sections[0].priority, None);
+        assert_eq!(sections[0].text, "");
+    }
+}
@@ -0,0 +1,338 @@
+//! Whitespace aligned tables as in `cpuinfo`, `netstats` or the process lists of `meminfo`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::parse::table::Table;
+//!
+//! let table = Table::parse(
+//!     "  PID   USER      RSS  NAME
+//!   1234  u0_a12  81234  com.android.systemui
+//!    567  system   2048  surfaceflinger",
+//! )
+//! .unwrap();
+//! let rows: Vec<_> = table.rows().collect();
+//! assert_eq!(rows[0].get("NAME"), Some("com.android.systemui"));
+//! assert_eq!(rows[1].get_int("RSS"), Ok(2048));
+//! ```
+
+use std::{ops::Range, str::FromStr};
+
+use crate::{
+    error::ParseError,
+    parse::kv::{invalid, parse_int},
+};
+
+/// A table with columns taken from its header row
+///
+/// Cells are matched to columns by position: a row with one word per column is taken as is, otherwise
+/// each word goes to the column its text overlaps the most, or the nearest one, and words of the same
+/// column are joined. The last column also takes everything after it, e.g. command lines with spaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table<'a> {
+    columns: Vec<&'a str>,
+    rows: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Table<'a> {
+    /// Parse `text`, taking its first non-blank line as the header and the lines up to the next blank
+    /// one as rows. `None` without a header.
+    pub fn parse(text: &'a str) -> Option<Self> {
+        Self::parse_with_gap(text, 1)
+    }
+
+    /// Like [`Table::parse`], splitting header names only at runs of at least `gap` spaces, for headers
+    /// like `Rx bytes  Tx bytes`.
+    pub fn parse_with_gap(text: &'a str, gap: usize) -> Option<Self> {
+        let mut lines = text.lines().skip_while(|line| line.trim().is_empty());
+        let header = lines.next()?;
+        let spans = header_spans(header, gap.max(1));
+        let columns = spans.iter().map(|span| &header[span.clone()]).collect();
+
+        let rows = lines
+            .take_while(|line| !line.trim().is_empty())
+            .map(|line| split_row(line, &spans))
+            .collect();
+        Some(Self { columns, rows })
+    }
+
+    /// Column names from the header, left to right.
+    pub fn columns(&self) -> &[&'a str] {
+        &self.columns
+    }
+
+    /// Position of the column named `name`.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| *column == name)
+    }
+
+    /// Rows below the header, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_, 'a>> {
+        self.rows.iter().map(|cells| Row { table: self, cells })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// One row of a [`Table`], with a cell per column, empty where the row has nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Row<'t, 'a> {
+    table: &'t Table<'a>,
+    cells: &'t [&'a str],
+}
+
+impl<'a> Row<'_, 'a> {
+    /// Cells left to right.
+    pub fn cells(&self) -> &[&'a str] {
+        self.cells
+    }
+
+    /// Cell of the column named `column`, `None` if there is no such column or the cell is empty.
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        let cell = self.cells[self.table.column(column)?];
+        (!cell.is_empty()).then_some(cell)
+    }
+
+    /// Cell of `column`, failing if it's missing.
+    pub fn require(&self, column: &str) -> Result<&'a str, ParseError> {
+        self.get(column)
+            .ok_or_else(|| ParseError::Missing(column.to_owned()))
+    }
+
+    /// Integer cell of `column`, decimal or `0x` prefixed hex.
+    pub fn get_int(&self, column: &str) -> Result<i64, ParseError> {
+        let cell = self.require(column)?;
+        parse_int(cell).ok_or_else(|| invalid(column, cell))
+    }
+
+    /// Floating point cell of `column`, ignoring a trailing `%`.
+    pub fn get_float(&self, column: &str) -> Result<f64, ParseError> {
+        let cell = self.require(column)?;
+        cell.trim_end_matches('%')
+            .parse()
+            .map_err(|_| invalid(column, cell))
+    }
+
+    /// Cell of `column` converted with [`FromStr`].
+    pub fn get_parsed<T: FromStr>(&self, column: &str) -> Result<T, ParseError> {
+        let cell = self.require(column)?;
+        cell.parse().map_err(|_| invalid(column, cell))
+    }
+}
+
+/// Byte ranges of the header names, which are separated by at least `gap` spaces.
+fn header_spans(header: &str, gap: usize) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    for word in words(header) {
+        match spans.last_mut() {
+            Some(last) if word.start - last.end < gap => last.end = word.end,
+            _ => spans.push(word),
+        }
+    }
+    spans
+}
+
+fn split_row<'a>(line: &'a str, columns: &[Range<usize>]) -> Vec<&'a str> {
+    let words: Vec<_> = words(line).collect();
+    if words.len() == columns.len() {
+        return words.into_iter().map(|word| &line[word]).collect();
+    }
+
+    let mut cells: Vec<Option<Range<usize>>> = vec![None; columns.len()];
+    for word in words {
+        let column = column_of(&word, columns);
+        let cell = &mut cells[column];
+        *cell = Some(match cell.take() {
+            Some(cell) => cell.start..word.end,
+            None => word,
+        });
+    }
+    cells
+        .into_iter()
+        .map(|cell| cell.map_or("", |cell| &line[cell]))
+        .collect()
+}
+
+/// The column `word` belongs to, by overlap with the header names or else by distance.
+fn column_of(word: &Range<usize>, columns: &[Range<usize>]) -> usize {
+    let last = columns.len() - 1;
+    if word.start >= columns[last].start {
+        return last;
+    }
+
+    let overlap = |column: &Range<usize>| {
+        word.end
+            .min(column.end)
+            .saturating_sub(word.start.max(column.start))
+    };
+    let distance = |column: &Range<usize>| {
+        column
+            .start
+            .saturating_sub(word.end)
+            .max(word.start.saturating_sub(column.end))
+    };
+    (0..columns.len())
+        .max_by_key(|&i| {
+            let column = &columns[i];
+            (overlap(column), std::cmp::Reverse(distance(column)))
+        })
+        .unwrap_or(0)
+}
+
+/// Byte ranges of the whitespace separated words of `line`.
+fn words(line: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut offset = 0;
+    line.split_whitespace().map(move |word| {
+        let start = offset + line[offset..].find(word).unwrap_or(0);
+        offset = start + word.len();
+        start..offset
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells<'a>(table: &'a Table<'a>) -> Vec<Vec<&'a str>> {
+        table.rows().map(|row| row.cells().to_vec()).collect()
+    }
+
+    #[test]
+    fn right_aligned_numbers_wider_than_their_header() {
+        let table = Table::parse(
+            "  PID       RSS  NAME
+    1   1234567  init
+12345       512  com.example.app
+  678  98765432",
+        )
+        .unwrap();
+        assert_eq!(
+            cells(&table),
+            [
+                vec!["1", "1234567", "init"],
+                vec!["12345", "512", "com.example.app"],
+                vec!["678", "98765432", ""],
+            ]
+        );
+        let rows: Vec<_> = table.rows().collect();
+        assert_eq!(rows[0].get_int("RSS"), Ok(1_234_567));
+        assert_eq!(rows[2].get("NAME"), None);
+        assert_eq!(
+            rows[2].require("NAME"),
+            Err(ParseError::Missing("NAME".to_owned()))
+        );
+    }
+
+    #[test]
+    fn empty_middle_cells() {
+        let table = Table::parse(
+            "  PID  USER      RSS  NAME
+ 1234  u0_a12    812  com.android.systemui
+    2             0  kthreadd
+  567  system",
+        )
+        .unwrap();
+        assert_eq!(
+            cells(&table),
+            [
+                vec!["1234", "u0_a12", "812", "com.android.systemui"],
+                vec!["2", "", "0", "kthreadd"],
+                vec!["567", "system", "", ""],
+            ]
+        );
+        assert_eq!(table.rows().nth(1).unwrap().get("USER"), None);
+    }
+
+    #[test]
+    fn words_between_columns_go_to_the_nearest() {
+        let table = Table::parse("A          B\n  x      y\n     z").unwrap();
+        assert_eq!(cells(&table), [vec!["x", "y"], vec!["z", ""]]);
+    }
+
+    #[test]
+    fn words_of_one_column_are_joined() {
+        let table = Table::parse("STATE              PID\nnot running   42").unwrap();
+        assert_eq!(cells(&table), [vec!["not running", "42"]]);
+    }
+
+    #[test]
+    fn last_column_takes_the_rest_of_the_line() {
+        let table = Table::parse(
+            "  PID  CMD
+  612  /system/bin/app_process64 -Xzygote /system/bin --zygote --start-system-server",
+        )
+        .unwrap();
+        let row = table.rows().next().unwrap();
+        assert_eq!(row.get_int("PID"), Ok(612));
+        assert_eq!(
+            row.get("CMD"),
+            Some("/system/bin/app_process64 -Xzygote /system/bin --zygote --start-system-server")
+        );
+    }
+
+    #[test]
+    fn multi_word_headers_with_a_gap() {
+        let text = "Iface   Rx bytes  Rx pkts  Tx bytes  Tx pkts
+wlan0   123456789   98765  23456789    54321
+rmnet0          0       0         0        0";
+        let table = Table::parse_with_gap(text, 2).unwrap();
+        assert_eq!(
+            table.columns(),
+            ["Iface", "Rx bytes", "Rx pkts", "Tx bytes", "Tx pkts"]
+        );
+        let row = table.rows().next().unwrap();
+        assert_eq!(row.get_int("Rx bytes"), Ok(123_456_789));
+        assert_eq!(row.get_parsed::<u32>("Tx pkts"), Ok(54_321));
+        assert_eq!(table.len(), 2);
+
+        assert_eq!(Table::parse(text).unwrap().columns().len(), 9);
+    }
+
+    #[test]
+    fn tab_separated_rows() {
+        let table =
+            Table::parse("uid\tpackage\tcpu\n10042\tcom.example.app\t12.5%\n1000\tandroid\t0.3%")
+                .unwrap();
+        assert_eq!(table.columns(), ["uid", "package", "cpu"]);
+        let rows: Vec<_> = table.rows().collect();
+        assert_eq!(rows[0].get("package"), Some("com.example.app"));
+        assert_eq!(rows[1].get_float("cpu"), Ok(0.3));
+    }
+
+    #[test]
+    fn crlf_lines() {
+        let table = Table::parse("PID  NAME\r\n  1  init\r\n").unwrap();
+        assert_eq!(table.columns(), ["PID", "NAME"]);
+        assert_eq!(cells(&table), [vec!["1", "init"]]);
+    }
+
+    #[test]
+    fn rows_end_at_a_blank_line() {
+        let table = Table::parse("\n\n  ID  FLAGS\n   1  0x1f\n\n   2  0x0\n").unwrap();
+        assert_eq!(table.len(), 1);
+        let row = table.rows().next().unwrap();
+        assert_eq!(row.get_int("FLAGS"), Ok(0x1f));
+        assert_eq!(row.get("MISSING"), None);
+    }
+
+    #[test]
+    fn invalid_cells() {
+        let table = Table::parse("PID  CPU\nabc  n/a").unwrap();
+        let row = table.rows().next().unwrap();
+        assert_eq!(row.get_int("PID"), Err(invalid("PID", "abc")));
+        assert_eq!(row.get_float("CPU"), Err(invalid("CPU", "n/a")));
+    }
+
+    #[test]
+    fn without_a_header() {
+        assert_eq!(Table::parse(""), None);
+        assert_eq!(Table::parse("  \n\t\n"), None);
+        assert!(Table::parse("PID  NAME").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use crate::parse::kv::{first_word, parse_duration, parse_int};
+
+/// Conversion of a field value found in dump text, used by `#[derive(DumpParse)]`
+///
+/// Numbers, booleans and durations take the first word of the value, so `12345 (3s ago)` converts to
+/// `12345`; strings take the whole value.
+pub trait FromDumpValue: Sized {
+    /// `None` if `value` doesn't have the expected form.
+    fn from_dump_value(value: &str) -> Option<Self>;
+}
+
+impl FromDumpValue for String {
+    fn from_dump_value(value: &str) -> Option<Self> {
+        Some(value.trim().to_owned())
+    }
+}
+
+impl FromDumpValue for bool {
+    fn from_dump_value(value: &str) -> Option<Self> {
+        first_word(value).parse().ok()
+    }
+}
+
+impl FromDumpValue for Duration {
+    /// `+1h2m3s45ms` as formatted by Android's `TimeUtils.formatDuration`, or a number of milliseconds.
+    fn from_dump_value(value: &str) -> Option<Self> {
+        parse_duration(first_word(value))
+    }
+}
+
+macro_rules! from_dump_value_int {
+    ($($int:ty),*) => {
+        $(
+            impl FromDumpValue for $int {
+                /// Decimal or `0x` prefixed hex, `,` digit separators ignored.
+                fn from_dump_value(value: &str) -> Option<Self> {
+                    let word = first_word(value).replace(',', "");
+                    parse_int(&word)?.try_into().ok()
+                }
+            }
+        )*
+    };
+}
+
+from_dump_value_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! from_dump_value_float {
+    ($($float:ty),*) => {
+        $(
+            impl FromDumpValue for $float {
+                /// Ignores a trailing `%`.
+                fn from_dump_value(value: &str) -> Option<Self> {
+                    first_word(value).trim_end_matches('%').parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+from_dump_value_float!(f32, f64);
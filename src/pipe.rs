@@ -0,0 +1,349 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    time::{Duration, Instant},
+};
+
+use libc::c_int;
+use os_pipe::{PipeReader, PipeWriter};
+
+use crate::{cancel::CancelToken, error::DumpError, observer::Observed};
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+const SPLICE_CHUNK: usize = 1024 * 1024;
+const PIPE_MAX_SIZE: &str = "/proc/sys/fs/pipe-max-size";
+/// Multiple of every page size a limited memfd is sized to
+#[cfg(any(target_os = "android", target_os = "linux"))]
+const MEMFD_GRANULE: u64 = 64 * 1024;
+/// How long a read waits between checking for cancellation
+pub(crate) const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Create the pipe a service dumps into, optionally resizing its kernel buffer.
+pub(crate) fn pipe(capacity: Option<usize>) -> io::Result<(PipeReader, PipeWriter)> {
+    let (read, write) = os_pipe::pipe()?;
+    if let Some(capacity) = capacity {
+        set_capacity(write.as_raw_fd(), capacity)?;
+    }
+    Ok((read, write))
+}
+
+/// Resize the pipe buffer, settling for `pipe-max-size` when unprivileged and asking for more.
+fn set_capacity(fd: RawFd, capacity: usize) -> io::Result<()> {
+    match fcntl_setpipe_sz(fd, capacity) {
+        Err(err) if err.raw_os_error() == Some(libc::EPERM) => match max_capacity() {
+            Some(max) if max < capacity => fcntl_setpipe_sz(fd, max),
+            _ => Err(err),
+        },
+        result => result,
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn fcntl_setpipe_sz(fd: RawFd, capacity: usize) -> io::Result<()> {
+    let capacity = c_int::try_from(capacity).map_err(|_| io::ErrorKind::InvalidInput)?;
+    // SAFETY: `fd` is an open pipe descriptor owned by the caller.
+    if unsafe { libc::fcntl(fd, libc::F_SETPIPE_SZ, capacity) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Pipe buffers can't be resized elsewhere, e.g. on macOS, where they grow on their own.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn fcntl_setpipe_sz(_fd: RawFd, _capacity: usize) -> io::Result<()> {
+    Ok(())
+}
+
+/// Largest pipe buffer an unprivileged process may request.
+fn max_capacity() -> Option<usize> {
+    fs::read_to_string(PIPE_MAX_SIZE).ok()?.trim().parse().ok()
+}
+
+/// Create an anonymous in-memory file a service can dump into without ever blocking on a reader.
+///
+/// With a `limit`, the file is sized and sealed so writes fail once it holds more than `limit` bytes,
+/// enough to tell output of exactly `limit` bytes from truncated output. The kernel fills it a page at
+/// a time and fails a write at the first page that doesn't fit, so the size is rounded up to whole
+/// pages.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub(crate) fn memfd(limit: Option<u64>) -> io::Result<File> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: the name is a valid C string and the returned descriptor is owned by nobody else.
+    let fd = unsafe {
+        libc::memfd_create(
+            c"dumpsys".as_ptr(),
+            libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just created and is owned by us.
+    let file = unsafe { File::from_raw_fd(fd) };
+    if let Some(limit) = limit {
+        file.set_len(limit.saturating_add(1).next_multiple_of(MEMFD_GRANULE))?;
+        // SAFETY: `file` is open and the seal takes no pointers.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, libc::F_SEAL_GROW) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(file)
+}
+
+/// A temporary file removed right away, where there is no memfd, so nothing caps its size.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+pub(crate) fn memfd(_limit: Option<u64>) -> io::Result<File> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let name = format!(
+        "dumpsys-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    );
+    let path = std::env::temp_dir().join(name);
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+
+/// The read end of a dump pipe
+///
+/// Fails with [`io::ErrorKind::TimedOut`] once the deadline passes, fails once the cancel token fires
+/// and reports end of file once the output limit is reached, after which [`Reader::truncated`] tells
+/// whether more output was left.
+pub(crate) struct Reader {
+    pipe: File,
+    deadline: Option<Instant>,
+    limit: Option<u64>,
+    total: u64,
+    /// Reads of the pipe so far
+    reads: u64,
+    truncated: bool,
+    cancel: Option<CancelToken>,
+    observed: Option<Observed>,
+    #[cfg(feature = "io-uring")]
+    ring: Option<crate::uring::Ring>,
+}
+
+impl Reader {
+    pub(crate) fn new(pipe: impl Into<OwnedFd>, deadline: Option<Instant>) -> Self {
+        Self {
+            pipe: File::from(pipe.into()),
+            deadline,
+            limit: None,
+            total: 0,
+            reads: 0,
+            truncated: false,
+            cancel: None,
+            observed: None,
+            #[cfg(feature = "io-uring")]
+            ring: None,
+        }
+    }
+
+    /// Stop reading after `limit` bytes.
+    pub(crate) fn with_limit(mut self, limit: Option<u64>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Abort reads once `cancel` fires.
+    pub(crate) fn with_cancel(mut self, cancel: Option<CancelToken>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Report the chunks read to the observer of the dump.
+    pub(crate) fn with_observer(mut self, observed: Option<Observed>) -> Self {
+        self.observed = observed;
+        self
+    }
+
+    /// Read through io_uring instead of `read(2)`, if the kernel allows it.
+    #[cfg(feature = "io-uring")]
+    pub(crate) fn with_io_uring(mut self) -> Self {
+        self.ring = crate::uring::Ring::new();
+        self
+    }
+
+    /// When reads start failing with [`io::ErrorKind::TimedOut`], if ever.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Bytes handed out so far.
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.total
+    }
+
+    /// Reads of the pipe so far, limit probes and splices included.
+    pub(crate) fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// Whether the service had more output than the limit allowed.
+    pub(crate) fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Move everything left in the pipe into `out` with `splice(2)`, without copying through userspace.
+    ///
+    /// Falls back to a plain copy when `out` doesn't support splicing.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub(crate) fn splice_to(&mut self, out: &mut File) -> io::Result<u64> {
+        let mut total = 0;
+
+        loop {
+            let len = self.allowance(SPLICE_CHUNK)?;
+            if len == 0 {
+                return Ok(total);
+            }
+            self.wait_readable()?;
+            self.reads += 1;
+
+            // SAFETY: both descriptors stay open for the call and null offsets use the file positions.
+            let n = unsafe {
+                libc::splice(
+                    self.pipe.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    out.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    len,
+                    libc::SPLICE_F_MOVE | libc::SPLICE_F_MORE,
+                )
+            };
+
+            match n {
+                0 => return Ok(total),
+                n if n > 0 => {
+                    total += n as u64;
+                    self.total += n as u64;
+                }
+                _ => {
+                    let err = io::Error::last_os_error();
+                    match err.raw_os_error() {
+                        Some(libc::EINTR) => {}
+                        Some(libc::EINVAL) if total == 0 => return io::copy(self, out),
+                        _ => return Err(err),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy everything left in the pipe into `out`, where there is no `splice(2)`.
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    pub(crate) fn splice_to(&mut self, out: &mut File) -> io::Result<u64> {
+        io::copy(self, out)
+    }
+
+    /// How much of `want` may still be read, 0 once the limit is reached.
+    fn allowance(&mut self, want: usize) -> io::Result<usize> {
+        let Some(limit) = self.limit else {
+            return Ok(want);
+        };
+
+        let left = limit.saturating_sub(self.total);
+        if left == 0 && want > 0 && !self.truncated {
+            // Output of exactly `limit` bytes isn't truncated, so look for one more.
+            let mut probe = [0u8; 1];
+            self.truncated = self.read_pipe(&mut probe)? > 0;
+        }
+        Ok(want.min(usize::try_from(left).unwrap_or(usize::MAX)))
+    }
+
+    fn read_pipe(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+        #[cfg(feature = "io-uring")]
+        if let Some(ring) = self.ring.as_mut() {
+            if self.cancel.is_some() {
+                wait_readable(self.pipe.as_raw_fd(), self.deadline, self.cancel.as_ref())?;
+            }
+            return ring.read(self.pipe.as_raw_fd(), buf, self.deadline);
+        }
+
+        self.wait_readable()?;
+        self.pipe.read(buf)
+    }
+
+    fn wait_readable(&self) -> io::Result<()> {
+        if self.deadline.is_none() && self.cancel.is_none() {
+            return Ok(());
+        }
+        wait_readable(self.pipe.as_raw_fd(), self.deadline, self.cancel.as_ref())
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.allowance(buf.len())?;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let n = self.read_pipe(&mut buf[..len])?;
+        self.total += n as u64;
+        if let (Some(observed), true) = (&self.observed, n > 0) {
+            observed.chunk(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// Error of a read aborted through its [`CancelToken`]
+pub(crate) fn cancelled() -> io::Error {
+    io::Error::other(DumpError::cancelled())
+}
+
+fn wait_readable(
+    fd: RawFd,
+    deadline: Option<Instant>,
+    cancel: Option<&CancelToken>,
+) -> io::Result<()> {
+    loop {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return Err(cancelled());
+        }
+
+        let mut wait = None;
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            wait = Some(remaining);
+        }
+        if cancel.is_some() {
+            wait = Some(wait.map_or(CANCEL_POLL_INTERVAL, |wait| wait.min(CANCEL_POLL_INTERVAL)));
+        }
+
+        let timeout = wait.map_or(-1, |wait| {
+            c_int::try_from(wait.as_millis())
+                .unwrap_or(c_int::MAX)
+                .max(1)
+        });
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pollfd` is a single valid entry that lives across the call.
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => {}
+            _ => return Ok(()),
+        }
+    }
+}
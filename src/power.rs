@@ -0,0 +1,406 @@
+//! Typed output of `dumpsys power`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::power::{DisplayState, PowerState, Wakefulness};
+//!
+//! let power = PowerState::parse(
+//!     "POWER MANAGER (dumpsys power)
+//!
+//! Power Manager State:
+//!   mDirty=0x0
+//!   mWakefulness=Awake
+//!   mWakefulnessChanging=false
+//!   mIsPowered=false
+//!
+//! Display Power: state=ON
+//!
+//! Wake Locks: size=2
+//!   PARTIAL_WAKE_LOCK              'AudioMix' ACQ=-1m2s345ms (uid=1041 ws=WorkSource{10123})
+//!   SCREEN_BRIGHT_WAKE_LOCK        'WindowManager' ON_AFTER_RELEASE ACQ=-12s5ms (uid=1000 pid=1234)
+//!
+//! Suspend Blockers: size=4
+//!   PowerManagerService.WakeLocks: ref count=1
+//!
+//! Battery saving stats:
+//!   Battery Saver is currently: OFF",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(power.wakefulness, Wakefulness::Awake);
+//! assert_eq!(power.display_state, Some(DisplayState::On));
+//! assert!(power.is_screen_on());
+//! assert_eq!(power.battery_saver, Some(false));
+//! assert_eq!(power.wakelocks.len(), 2);
+//! assert_eq!(power.wakelocks[0].tag, "AudioMix");
+//! assert_eq!(power.wakelocks[0].held, Some(Duration::from_millis(62_345)));
+//! assert_eq!(power.wakelocks[0].work_source.as_deref(), Some("WorkSource{10123}"));
+//! assert_eq!(power.wakelocks[1].flags, ["ON_AFTER_RELEASE"]);
+//! assert_eq!(power.wakelocks[1].pid, Some(1234));
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_duration, KeyValues},
+    typed, DumpParse,
+};
+
+const DISPLAY_POWER: &str = "Display Power: state=";
+const WAKE_LOCKS: &str = "Wake Locks: size=";
+const BATTERY_SAVER: &str = "Battery Saver is currently: ";
+
+/// `PowerManagerInternal.WAKEFULNESS_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Wakefulness {
+    Asleep,
+    Awake,
+    Dreaming,
+    Dozing,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for Wakefulness {
+    fn from(wakefulness: &str) -> Self {
+        match wakefulness {
+            "Asleep" => Self::Asleep,
+            "Awake" => Self::Awake,
+            "Dreaming" => Self::Dreaming,
+            "Dozing" => Self::Dozing,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// `Display.STATE_*` of the default display
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DisplayState {
+    Unknown,
+    Off,
+    On,
+    Doze,
+    DozeSuspend,
+    Vr,
+    OnSuspend,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for DisplayState {
+    fn from(state: &str) -> Self {
+        match state {
+            "UNKNOWN" => Self::Unknown,
+            "OFF" => Self::Off,
+            "ON" => Self::On,
+            "DOZE" => Self::Doze,
+            "DOZE_SUSPEND" => Self::DozeSuspend,
+            "VR" => Self::Vr,
+            "ON_SUSPEND" => Self::OnSuspend,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A wake lock held at the time of the dump
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WakeLock {
+    /// Level such as `PARTIAL_WAKE_LOCK`
+    pub level: String,
+    pub tag: String,
+    /// Flags such as `ACQUIRE_CAUSES_WAKEUP` or `DISABLED`
+    pub flags: Vec<String>,
+    /// Time since it was acquired
+    pub held: Option<Duration>,
+    pub uid: Option<u32>,
+    pub pid: Option<u32>,
+    /// Apps the wake lock is held on behalf of, as printed
+    pub work_source: Option<String>,
+}
+
+/// Output of `dumpsys power`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerState {
+    pub wakefulness: Wakefulness,
+    pub display_state: Option<DisplayState>,
+    /// Whether the device is plugged in
+    pub is_powered: Option<bool>,
+    /// Whether battery saver is on, on releases that print it
+    pub battery_saver: Option<bool>,
+    pub wakelocks: Vec<WakeLock>,
+}
+
+impl PowerState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let fields = KeyValues::parse(text);
+        let mut display_state = None;
+        let mut battery_saver = fields
+            .get_bool("mFullEnabled")
+            .or_else(|_| fields.get_bool("mLowPowerModeEnabled"))
+            .ok();
+        let mut wakelocks = Vec::new();
+        let mut in_wakelocks = false;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if in_wakelocks {
+                if trimmed.is_empty() || !line.starts_with(char::is_whitespace) {
+                    in_wakelocks = false;
+                } else {
+                    wakelocks.push(wakelock(trimmed)?);
+                    continue;
+                }
+            }
+
+            if let Some(state) = trimmed.strip_prefix(DISPLAY_POWER) {
+                display_state = Some(state.into());
+            } else if trimmed.starts_with(WAKE_LOCKS) {
+                in_wakelocks = true;
+            } else if let Some(state) = trimmed.strip_prefix(BATTERY_SAVER) {
+                battery_saver.get_or_insert(state == "ON");
+            }
+        }
+
+        Ok(Self {
+            wakefulness: fields.require("mWakefulness")?.into(),
+            display_state,
+            is_powered: fields.get_bool("mIsPowered").ok(),
+            battery_saver,
+            wakelocks,
+        })
+    }
+
+    /// Whether the default display is on, going by the wakefulness on releases without a display state.
+    pub fn is_screen_on(&self) -> bool {
+        match &self.display_state {
+            Some(state) => matches!(state, DisplayState::On | DisplayState::Vr),
+            None => self.wakefulness == Wakefulness::Awake,
+        }
+    }
+
+    /// Whether the device is awake and accepting input, as `PowerManager.isInteractive`.
+    pub fn is_interactive(&self) -> bool {
+        self.wakefulness == Wakefulness::Awake
+    }
+
+    /// Wake locks held by `uid`.
+    pub fn wakelocks_of(&self, uid: u32) -> impl Iterator<Item = &WakeLock> + '_ {
+        self.wakelocks
+            .iter()
+            .filter(move |wakelock| wakelock.uid == Some(uid))
+    }
+}
+
+impl DumpParse for PowerState {
+    const SERVICE: &'static str = "power";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump and parse the state of the power manager.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::power;
+///
+/// # fn foo() {
+/// let power = power::state().unwrap();
+/// println!("screen on: {}, {} wake locks", power.is_screen_on(), power.wakelocks.len());
+/// # }
+/// ```
+pub fn state() -> Result<PowerState, DumpError> {
+    typed::dump_service::<PowerState>(Vec::<&str>::new())
+}
+
+/// `PARTIAL_WAKE_LOCK 'AudioMix' ON_AFTER_RELEASE ACQ=-1m2s345ms (uid=1041 pid=12 ws=WorkSource{10123})`
+fn wakelock(line: &str) -> Result<WakeLock, ParseError> {
+    let (level, rest) = line
+        .split_once(' ')
+        .ok_or_else(|| invalid("wake lock", line))?;
+    let rest = rest
+        .trim_start()
+        .strip_prefix('\'')
+        .ok_or_else(|| invalid("wake lock", line))?;
+    let close = rest
+        .find("' ")
+        .or_else(|| rest.rfind('\''))
+        .ok_or_else(|| invalid("wake lock", line))?;
+    let (tag, rest) = (&rest[..close], &rest[close + 1..]);
+    let (flags, owner) = rest.split_once('(').unwrap_or((rest, ""));
+
+    let mut wakelock = WakeLock {
+        level: level.to_owned(),
+        tag: tag.to_owned(),
+        ..WakeLock::default()
+    };
+    for word in flags.split_whitespace() {
+        match word.strip_prefix("ACQ=") {
+            Some(acquired) => wakelock.held = parse_duration(acquired.trim_start_matches('-')),
+            None => wakelock.flags.push(word.to_owned()),
+        }
+    }
+
+    let owner = owner.trim_end();
+    let owner = owner.strip_suffix(')').unwrap_or(owner);
+    let fields = KeyValues::parse(owner);
+    wakelock.uid = fields.get_parsed("uid").ok();
+    wakelock.pid = fields.get_parsed("pid").ok();
+    // Keep the braces KeyValues trims off.
+    wakelock.work_source = owner
+        .find("ws=")
+        .map(|start| owner[start + 3..].trim().to_owned());
+    Ok(wakelock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys power` of Android 14, cut down to a few lines of each section
+    const DUMP: &str = "POWER MANAGER (dumpsys power)
+
+Power Manager State:
+  Settings power_manager_constants:
+    no_cached_wake_locks=true
+  mDirty=0x0
+  mWakefulness=Awake
+  mWakefulnessChanging=false
+  mIsPowered=true
+  mPlugType=2
+  mBatteryLevel=87
+  mStayOn=false
+  mProximityPositive=false
+
+Display Power: state=ON
+
+Wake Locks: size=4
+  PARTIAL_WAKE_LOCK              'AudioMix' ACQ=-4s312ms (uid=1041 ws=WorkSource{10231})
+  PARTIAL_WAKE_LOCK              '*job*/com.example.app/.SyncJob' ACQ=-1m2s5ms (uid=10231 pid=24588 ws=WorkSource{10231 com.example.app})
+  SCREEN_BRIGHT_WAKE_LOCK        'WindowManager' ON_AFTER_RELEASE ACQ=-12m3s (uid=1000 pid=1520)
+  PARTIAL_WAKE_LOCK              'GnssLocationProvider' DISABLED ACQ=-3m (uid=1000 pid=1520)
+
+Suspend Blockers: size=4
+  PowerManagerService.WakeLocks: ref count=1
+  PowerManagerService.Display: ref count=1
+
+Battery saving stats:
+  Battery Saver is currently: OFF
+";
+
+    #[test]
+    fn parses_the_dump() {
+        let power = PowerState::parse(DUMP).unwrap();
+        assert_eq!(power.wakefulness, Wakefulness::Awake);
+        assert_eq!(power.display_state, Some(DisplayState::On));
+        assert_eq!(power.is_powered, Some(true));
+        assert_eq!(power.battery_saver, Some(false));
+        assert!(power.is_screen_on());
+        assert!(power.is_interactive());
+    }
+
+    #[test]
+    fn parses_the_wake_locks() {
+        let power = PowerState::parse(DUMP).unwrap();
+        assert_eq!(power.wakelocks.len(), 4);
+        assert_eq!(
+            power.wakelocks[1],
+            WakeLock {
+                level: "PARTIAL_WAKE_LOCK".to_owned(),
+                tag: "*job*/com.example.app/.SyncJob".to_owned(),
+                flags: Vec::new(),
+                held: Some(Duration::from_millis(62_005)),
+                uid: Some(10231),
+                pid: Some(24588),
+                work_source: Some("WorkSource{10231 com.example.app}".to_owned()),
+            }
+        );
+        assert_eq!(power.wakelocks[0].pid, None);
+        assert_eq!(power.wakelocks[2].flags, ["ON_AFTER_RELEASE"]);
+        assert_eq!(power.wakelocks[2].work_source, None);
+        let system: Vec<_> = power
+            .wakelocks_of(1000)
+            .map(|wakelock| &wakelock.tag)
+            .collect();
+        assert_eq!(system, ["WindowManager", "GnssLocationProvider"]);
+        assert_eq!(power.wakelocks[3].flags, ["DISABLED"]);
+        assert_eq!(power.wakelocks[3].held, Some(Duration::from_secs(180)));
+    }
+
+    #[test]
+    fn battery_saver_of_older_releases() {
+        let text = "mWakefulness=Asleep\n  mLowPowerModeEnabled=true\n";
+        assert_eq!(PowerState::parse(text).unwrap().battery_saver, Some(true));
+        let text = "mWakefulness=Asleep\n  Battery Saver is currently: ON\n";
+        assert_eq!(PowerState::parse(text).unwrap().battery_saver, Some(true));
+    }
+
+    #[test]
+    fn missing_display_state_goes_by_wakefulness() {
+        let power = PowerState::parse("  mWakefulness=Dozing\n").unwrap();
+        assert_eq!(power.display_state, None);
+        assert_eq!(power.is_powered, None);
+        assert_eq!(power.battery_saver, None);
+        assert!(!power.is_screen_on());
+        assert!(PowerState::parse("  mWakefulness=Awake\n")
+            .unwrap()
+            .is_screen_on());
+    }
+
+    #[test]
+    fn unknown_states_are_kept() {
+        let text = "  mWakefulness=Hibernating\nDisplay Power: state=ON_WARP\n";
+        let power = PowerState::parse(text).unwrap();
+        assert_eq!(
+            power.wakefulness,
+            Wakefulness::Other("Hibernating".to_owned())
+        );
+        assert_eq!(
+            power.display_state,
+            Some(DisplayState::Other("ON_WARP".to_owned()))
+        );
+        assert!(!power.is_screen_on());
+    }
+
+    #[test]
+    fn truncated_wake_locks_are_invalid() {
+        for line in [
+            "PARTIAL_WAKE_LOCK",
+            "PARTIAL_WAKE_LOCK              AudioMix ACQ=-4s312ms",
+            "PARTIAL_WAKE_LOCK              'AudioM",
+        ] {
+            let text = format!("  mWakefulness=Awake\nWake Locks: size=1\n  {line}\n");
+            assert_eq!(
+                PowerState::parse(&text).unwrap_err(),
+                invalid("wake lock", line)
+            );
+        }
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            PowerState::parse(&crlf).unwrap(),
+            PowerState::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_fails() {
+        assert_eq!(
+            PowerState::parse("").unwrap_err(),
+            ParseError::Missing("mWakefulness".to_owned())
+        );
+    }
+}
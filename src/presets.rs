@@ -0,0 +1,160 @@
+//! Dumps that are asked for all the time, each paired with its service, arguments and parser
+//!
+//! A [`Preset`] names a dump like "the frame timings of this layer" instead of spelling out
+//! `dumpsys SurfaceFlinger --latency <layer>` and picking the parser by hand. [`Preset::dump`]
+//! looks the service up, dumps it with the right arguments and parses the output into the matching
+//! [`PresetOutput`] variant.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::presets::{Preset, PresetOutput};
+//!
+//! let preset = Preset::SurfaceFlingerLatency("com.example.game/.MainActivity#0".to_owned());
+//! assert_eq!(preset.service(), "SurfaceFlinger");
+//! assert_eq!(preset.args(), ["--latency", "com.example.game/.MainActivity#0"]);
+//!
+//! let Ok(PresetOutput::Latency(latency)) = preset.parse("16666666\n") else {
+//!     panic!("not latency");
+//! };
+//! assert_eq!(latency.refresh_period.as_nanos(), 16666666);
+//!
+//! # fn foo() {
+//! match Preset::MeminfoPackage("com.example.app".to_owned()).dump().unwrap() {
+//!     PresetOutput::PackageMemInfo(meminfo) => println!("{:?}", meminfo.total()),
+//!     output => println!("{output:?}"),
+//! }
+//! # }
+//! ```
+
+use crate::{
+    activity::Activities,
+    battery::BatteryStatus,
+    checkin::Checkin,
+    cpuinfo::CpuInfo,
+    error::{DumpError, ParseError},
+    gfxinfo::Gfxinfo,
+    meminfo::{MemInfo, PackageMemInfo},
+    owned_args,
+    package::PackageInfo,
+    surfaceflinger::{Latency, TimeStats},
+    thermalservice::ThermalService,
+    typed, DumpParse, Dumpsys,
+};
+
+const FRAMESTATS_ARG: &str = "framestats";
+const LATENCY_ARG: &str = "--latency";
+
+/// A dump of one service with fixed arguments, parsed by a fixed parser
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Preset {
+    /// `SurfaceFlinger --latency <layer>`, frame timings of a layer
+    SurfaceFlingerLatency(String),
+    /// `SurfaceFlinger --timestats -dump`, frame statistics while collecting them is enabled
+    SurfaceFlingerTimeStats,
+    /// `gfxinfo <package> framestats`, rendering stats of the frames of a package
+    GfxinfoFramestats(String),
+    /// `meminfo`, memory use of every process
+    Meminfo,
+    /// `meminfo <package>`, the memory breakdown of a package
+    MeminfoPackage(String),
+    /// `package <package>`, versions, permissions and state of a package
+    PackageInfo(String),
+    /// `activity activities`, the activity stacks
+    Activities,
+    /// `batterystats --checkin`, battery usage since the last charge in the checkin format
+    BatteryStatsCheckin,
+    /// `battery`, level, temperature and charging state
+    Battery,
+    /// `cpuinfo`, CPU load per process
+    CpuInfo,
+    /// `thermalservice`, temperatures and throttling
+    Thermal,
+}
+
+/// Output of a [`Preset`], by the parser the preset pairs with
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum PresetOutput {
+    Latency(Latency),
+    TimeStats(TimeStats),
+    Gfxinfo(Gfxinfo),
+    MemInfo(MemInfo),
+    PackageMemInfo(PackageMemInfo),
+    PackageInfo(PackageInfo),
+    Activities(Activities),
+    Checkin(Checkin),
+    Battery(BatteryStatus),
+    CpuInfo(CpuInfo),
+    Thermal(ThermalService),
+}
+
+impl Preset {
+    /// Service the preset dumps.
+    pub fn service(&self) -> &'static str {
+        match self {
+            Self::SurfaceFlingerLatency(_) => Latency::SERVICE,
+            Self::SurfaceFlingerTimeStats => TimeStats::SERVICE,
+            Self::GfxinfoFramestats(_) => Gfxinfo::SERVICE,
+            Self::Meminfo => MemInfo::SERVICE,
+            Self::MeminfoPackage(_) => PackageMemInfo::SERVICE,
+            Self::PackageInfo(_) => PackageInfo::SERVICE,
+            Self::Activities => Activities::SERVICE,
+            Self::BatteryStatsCheckin => Checkin::SERVICE,
+            Self::Battery => BatteryStatus::SERVICE,
+            Self::CpuInfo => CpuInfo::SERVICE,
+            Self::Thermal => ThermalService::SERVICE,
+        }
+    }
+
+    /// Arguments of the dump.
+    pub fn args(&self) -> Vec<&str> {
+        match self {
+            Self::SurfaceFlingerLatency(layer) => vec![LATENCY_ARG, layer],
+            Self::GfxinfoFramestats(package) => vec![package, FRAMESTATS_ARG],
+            Self::MeminfoPackage(package) | Self::PackageInfo(package) => vec![package],
+            Self::SurfaceFlingerTimeStats => TimeStats::ARGS.to_vec(),
+            Self::Meminfo => MemInfo::ARGS.to_vec(),
+            Self::Activities => Activities::ARGS.to_vec(),
+            Self::BatteryStatsCheckin => Checkin::ARGS.to_vec(),
+            Self::Battery => BatteryStatus::ARGS.to_vec(),
+            Self::CpuInfo => CpuInfo::ARGS.to_vec(),
+            Self::Thermal => ThermalService::ARGS.to_vec(),
+        }
+    }
+
+    /// Look the service up and dump it.
+    pub fn dump(&self) -> Result<PresetOutput, DumpError> {
+        self.dump_with(&typed::connect(self.service(), &owned_args(self.args()))?)
+    }
+
+    /// Dump through `dumpsys`, e.g. one configured through [`Dumpsys::builder`], which must be a
+    /// handle of [`Preset::service`].
+    pub fn dump_with(&self, dumpsys: &Dumpsys) -> Result<PresetOutput, DumpError> {
+        let args = self.args();
+        let output = dumpsys.dump(&args)?;
+        self.parse(&output).map_err(|err| {
+            DumpError::from(err).with_context(&dumpsys.service_name, &owned_args(args))
+        })
+    }
+
+    /// Parse `text`, e.g. a dump saved earlier, with the parser of the preset.
+    pub fn parse(&self, text: &str) -> Result<PresetOutput, ParseError> {
+        Ok(match self {
+            Self::SurfaceFlingerLatency(_) => PresetOutput::Latency(Latency::parse(text)?),
+            Self::SurfaceFlingerTimeStats => PresetOutput::TimeStats(TimeStats::parse(text)?),
+            Self::GfxinfoFramestats(_) => PresetOutput::Gfxinfo(Gfxinfo::parse(text)?),
+            Self::Meminfo => PresetOutput::MemInfo(MemInfo::parse(text)?),
+            Self::MeminfoPackage(_) => PresetOutput::PackageMemInfo(PackageMemInfo::parse(text)?),
+            Self::PackageInfo(_) => PresetOutput::PackageInfo(PackageInfo::parse(text)?),
+            Self::Activities => PresetOutput::Activities(Activities::parse(text)?),
+            Self::BatteryStatsCheckin => PresetOutput::Checkin(Checkin::parse(text)?),
+            Self::Battery => PresetOutput::Battery(BatteryStatus::parse(text)?),
+            Self::CpuInfo => PresetOutput::CpuInfo(CpuInfo::parse(text)?),
+            Self::Thermal => PresetOutput::Thermal(ThermalService::parse(text)?),
+        })
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{aidl, error::DumpError, Dumpsys};
+
+/// Dump priority a service registered with, the tiers `dumpsys --priority` and bugreports collect by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DumpPriority {
+    Critical,
+    High,
+    Normal,
+    /// Services that didn't register with a priority
+    Default,
+}
+
+impl DumpPriority {
+    /// The matching `IServiceManager::DUMP_FLAG_PRIORITY_*` flag.
+    pub(crate) const fn flag(self) -> i32 {
+        match self {
+            Self::Critical => aidl::DUMP_FLAG_PRIORITY_CRITICAL,
+            Self::High => aidl::DUMP_FLAG_PRIORITY_HIGH,
+            Self::Normal => aidl::DUMP_FLAG_PRIORITY_NORMAL,
+            Self::Default => aidl::DUMP_FLAG_PRIORITY_DEFAULT,
+        }
+    }
+
+    /// Arguments dumpsys puts in front when dumping a service at this priority.
+    pub(crate) const fn args(self) -> &'static [&'static str] {
+        match self {
+            Self::Critical => &["--dump-priority", "CRITICAL"],
+            Self::High => &["--dump-priority", "HIGH"],
+            Self::Normal => &["--dump-priority", "NORMAL", "-a"],
+            Self::Default => &["-a"],
+        }
+    }
+}
+
+impl Dumpsys {
+    /// Like [`Dumpsys::dump`], asking only for the sections of `priority` the way `dumpsys --priority` does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::{DumpPriority, Dumpsys};
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let result = Dumpsys::new("activity")?
+    ///     .dump_with_priority(DumpPriority::Normal, &["activities"])
+    ///     .unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_with_priority(
+        &self,
+        priority: DumpPriority,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, DumpError> {
+        let args: Vec<String> = priority
+            .args()
+            .iter()
+            .map(|arg| (*arg).to_owned())
+            .chain(args.into_iter().map(|arg| arg.as_ref().to_owned()))
+            .collect();
+        self.dump(args)
+    }
+}
@@ -0,0 +1,391 @@
+//! Typed output of `dumpsys procstats --hours <n>`
+//!
+//! procstats samples the memory of every process for hours, so its averages show long-term regressions
+//! that a single `meminfo` misses. Sizes are converted to KiB.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::procstats::ProcStats;
+//!
+//! let procstats = ProcStats::parse(
+//!     "AGGREGATED OVER LAST 3 HOURS:
+//!   * com.android.systemui / u0a42 / v30:
+//!            TOTAL: 100% (150MB-160MB-170MB/120MB-130MB-140MB/200MB-210MB-220MB over 10)
+//!       Persistent: 100% (150MB-160MB-170MB/120MB-130MB-140MB/200MB-210MB-220MB over 10)
+//!   * com.example.app / u0a123 / v7:
+//!            TOTAL: 2.5% (40MB-45MB-1.5GB/30MB-35MB-40MB over 4)
+//!           Imp Fg: 2.0% (40MB-45MB-1.5GB/30MB-35MB-40MB over 3)
+//!          Service: 0.50%
+//! Run time Stats:
+//!   SOff/Norm: +1h2m3s",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(procstats.hours, Some(3));
+//! let app = procstats.process("com.example.app").unwrap();
+//! assert_eq!(app.version, Some(7));
+//! let total = app.total.as_ref().unwrap();
+//! assert_eq!(total.time_percent, 2.5);
+//! assert_eq!(total.pss.unwrap().max_kb, 1_572_864);
+//! assert_eq!(total.rss, None);
+//! assert_eq!(app.states[1].state, "Service");
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::invalid,
+    typed, DumpParse,
+};
+
+const AGGREGATED: &str = "AGGREGATED OVER LAST ";
+const PROCESS_PREFIX: &str = "* ";
+const TOTAL: &str = "TOTAL";
+
+/// Minimum, average and maximum of the samples of one kind of memory, in KiB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryRange {
+    pub min_kb: u64,
+    pub avg_kb: u64,
+    pub max_kb: u64,
+}
+
+/// Time a process spent in one state, such as `Top` or `Service`, and its memory meanwhile
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateStats {
+    pub state: String,
+    /// Percent of the aggregated time spent in the state
+    pub time_percent: f64,
+    pub pss: Option<MemoryRange>,
+    pub uss: Option<MemoryRange>,
+    /// On releases that sample RSS
+    pub rss: Option<MemoryRange>,
+    /// Memory samples taken
+    pub samples: u64,
+}
+
+/// Stats of one process
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessStats {
+    pub name: String,
+    /// Uid as printed, such as `1000` or `u0a42`
+    pub uid: String,
+    pub version: Option<u64>,
+    /// Over all states
+    pub total: Option<StateStats>,
+    pub states: Vec<StateStats>,
+}
+
+impl ProcessStats {
+    /// Average PSS over all states.
+    pub fn avg_pss_kb(&self) -> Option<u64> {
+        Some(self.total.as_ref()?.pss?.avg_kb)
+    }
+}
+
+/// Output of `dumpsys procstats --hours <n>`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcStats {
+    /// Hours the stats were aggregated over
+    pub hours: Option<u32>,
+    pub processes: Vec<ProcessStats>,
+}
+
+impl ProcStats {
+    /// Parse the process stats of the first section, ignoring run time and summary sections.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut procstats = Self::default();
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                if let Some(hours) = trimmed.strip_prefix(AGGREGATED) {
+                    procstats.hours = hours.split_whitespace().next().and_then(|n| n.parse().ok());
+                }
+                if procstats.processes.is_empty() {
+                    continue;
+                }
+                break;
+            }
+
+            if let Some(header) = trimmed.strip_prefix(PROCESS_PREFIX) {
+                procstats.processes.push(process(header)?);
+                continue;
+            }
+            let Some(process) = procstats.processes.last_mut() else {
+                continue;
+            };
+            let Some(state) = state(trimmed)? else {
+                continue;
+            };
+            if state.state == TOTAL {
+                process.total = Some(state);
+            } else {
+                process.states.push(state);
+            }
+        }
+
+        Ok(procstats)
+    }
+
+    /// Dump and parse the stats of the last `hours`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::procstats::ProcStats;
+    ///
+    /// # fn foo() {
+    /// let procstats = ProcStats::for_hours(24).unwrap();
+    /// for process in &procstats.processes {
+    ///     println!("{}: {:?} KiB", process.name, process.avg_pss_kb());
+    /// }
+    /// # }
+    /// ```
+    pub fn for_hours(hours: u32) -> Result<Self, DumpError> {
+        typed::dump_service::<Self>(["--hours".to_owned(), hours.to_string()])
+    }
+
+    /// The process called `name`.
+    pub fn process(&self, name: &str) -> Option<&ProcessStats> {
+        self.processes.iter().find(|process| process.name == name)
+    }
+}
+
+impl DumpParse for ProcStats {
+    const SERVICE: &'static str = "procstats";
+    const ARGS: &'static [&'static str] = &["--hours", "3"];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// `com.android.systemui / u0a42 / v30:`
+fn process(header: &str) -> Result<ProcessStats, ParseError> {
+    let mut parts = header.trim_end_matches(':').split(" / ");
+    let (Some(name), Some(uid)) = (parts.next(), parts.next()) else {
+        return Err(invalid("process", header));
+    };
+    Ok(ProcessStats {
+        name: name.to_owned(),
+        uid: uid.to_owned(),
+        version: parts
+            .next()
+            .and_then(|version| version.strip_prefix('v')?.parse().ok()),
+        ..ProcessStats::default()
+    })
+}
+
+/// `Imp Fg: 2.0% (40MB-45MB-50MB/30MB-35MB-40MB over 3)`, `None` for lines that aren't states.
+fn state(line: &str) -> Result<Option<StateStats>, ParseError> {
+    let Some((state, rest)) = line.split_once(": ") else {
+        return Ok(None);
+    };
+    let (percent, memory) = rest.split_once(' ').unwrap_or((rest, ""));
+    let Some(percent) = percent.strip_suffix('%') else {
+        return Ok(None);
+    };
+    let mut stats = StateStats {
+        state: state.to_owned(),
+        time_percent: percent.parse().map_err(|_| invalid(state, rest))?,
+        ..StateStats::default()
+    };
+
+    let memory = memory.trim().trim_start_matches('(').trim_end_matches(')');
+    if let Some((ranges, samples)) = memory.split_once(" over ") {
+        stats.samples = samples.trim().parse().map_err(|_| invalid(state, rest))?;
+        let mut ranges = ranges
+            .split('/')
+            .map(|range| memory_range(range).ok_or_else(|| invalid(state, rest)));
+        stats.pss = ranges.next().transpose()?;
+        stats.uss = ranges.next().transpose()?;
+        stats.rss = ranges.next().transpose()?;
+    }
+    Ok(Some(stats))
+}
+
+/// `40MB-45MB-50MB`
+fn memory_range(range: &str) -> Option<MemoryRange> {
+    let mut sizes = range.split('-').map(size_kb);
+    Some(MemoryRange {
+        min_kb: sizes.next()??,
+        avg_kb: sizes.next()??,
+        max_kb: sizes.next()??,
+    })
+}
+
+/// `900KB`, `150MB` or `1.5GB` in KiB.
+fn size_kb(size: &str) -> Option<u64> {
+    let digits = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let scale = match &size[digits.len()..] {
+        "B" => 1.0 / 1024.0,
+        "K" | "KB" => 1.0,
+        "M" | "MB" => 1024.0,
+        "G" | "GB" => 1024.0 * 1024.0,
+        "T" | "TB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    let value: f64 = digits.parse().ok()?;
+    Some((value * scale).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys procstats --hours 3` of Android 14, cut down to a few processes
+    const DUMP: &str = "AGGREGATED OVER LAST 3 HOURS:
+  * system / 1000 / v34:
+           TOTAL: 100% (219MB-231MB-245MB/196MB-207MB-219MB/287MB-301MB-318MB over 18)
+      Persistent: 100% (219MB-231MB-245MB/196MB-207MB-219MB/287MB-301MB-318MB over 18)
+  * com.android.systemui / u0a180 / v34:
+           TOTAL: 100% (176MB-189MB-204MB/150MB-161MB-172MB/262MB-275MB-290MB over 18)
+      Persistent: 100% (176MB-189MB-204MB/150MB-161MB-172MB/262MB-275MB-290MB over 18)
+  * com.google.android.gms.persistent / u0a148 / v240913035:
+           TOTAL: 100% (98MB-104MB-117MB/71MB-76MB-88MB/168MB-175MB-191MB over 18)
+              Top: 0.12%
+           Imp Fg: 99% (98MB-104MB-117MB/71MB-76MB-88MB/168MB-175MB-191MB over 18)
+        (Cached): 0.87%
+Run time Stats:
+  SOff/Norm: +2h35m15s341ms
+  SOn /Norm: +24m44s659ms
+      TOTAL: +3h0m0s0ms
+
+Summary:
+  * system / 1000 / v34:
+           TOTAL: 100% (219MB-231MB-245MB/196MB-207MB-219MB/287MB-301MB-318MB over 18)
+";
+
+    #[test]
+    fn parses_the_dump() {
+        let procstats = ProcStats::parse(DUMP).unwrap();
+        assert_eq!(procstats.hours, Some(3));
+        assert_eq!(procstats.processes.len(), 3);
+        let system = procstats.process("system").unwrap();
+        assert_eq!(system.uid, "1000");
+        assert_eq!(system.version, Some(34));
+        assert_eq!(
+            system.total,
+            Some(StateStats {
+                state: TOTAL.to_owned(),
+                time_percent: 100.0,
+                pss: Some(MemoryRange {
+                    min_kb: 224_256,
+                    avg_kb: 236_544,
+                    max_kb: 250_880,
+                }),
+                uss: Some(MemoryRange {
+                    min_kb: 200_704,
+                    avg_kb: 211_968,
+                    max_kb: 224_256,
+                }),
+                rss: Some(MemoryRange {
+                    min_kb: 293_888,
+                    avg_kb: 308_224,
+                    max_kb: 325_632,
+                }),
+                samples: 18,
+            })
+        );
+        assert_eq!(system.states.len(), 1);
+        assert_eq!(system.states[0].state, "Persistent");
+        assert_eq!(
+            procstats
+                .process("com.android.systemui")
+                .unwrap()
+                .avg_pss_kb(),
+            Some(193_536)
+        );
+    }
+
+    #[test]
+    fn states_without_memory() {
+        let procstats = ProcStats::parse(DUMP).unwrap();
+        let gms = procstats
+            .process("com.google.android.gms.persistent")
+            .unwrap();
+        assert_eq!(gms.version, Some(240_913_035));
+        let states: Vec<_> = gms
+            .states
+            .iter()
+            .map(|state| state.state.as_str())
+            .collect();
+        assert_eq!(states, ["Top", "Imp Fg", "(Cached)"]);
+        assert_eq!(gms.states[0].time_percent, 0.12);
+        assert_eq!(gms.states[0].pss, None);
+        assert_eq!(gms.states[0].samples, 0);
+        assert_eq!(gms.states[1].samples, 18);
+    }
+
+    #[test]
+    fn stops_at_the_first_section() {
+        // The summary repeats the processes, and the run time stats have a TOTAL of their own
+        let procstats = ProcStats::parse(DUMP).unwrap();
+        assert_eq!(procstats.process("system").unwrap().states.len(), 1);
+        assert_eq!(procstats.processes.len(), 3);
+    }
+
+    #[test]
+    fn missing_header_version_and_rss() {
+        let text = "  * com.example.app / u0a123:
+           TOTAL: 2.5% (40MB-45MB-50MB/30MB-35MB-40MB over 4)
+";
+        let procstats = ProcStats::parse(text).unwrap();
+        assert_eq!(procstats.hours, None);
+        let app = &procstats.processes[0];
+        assert_eq!(app.version, None);
+        let total = app.total.as_ref().unwrap();
+        assert_eq!(total.uss.unwrap().avg_kb, 35 * 1024);
+        assert_eq!(total.rss, None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(
+            ProcStats::parse("  * com.example.app:").unwrap_err(),
+            invalid("process", "com.example.app:")
+        );
+        let process = "  * com.example.app / u0a123 / v7:\n";
+        let rest = "2.5% (40MB-45MB/30MB-35MB-40MB over 4)";
+        assert_eq!(
+            ProcStats::parse(&format!("{process}    TOTAL: {rest}")).unwrap_err(),
+            invalid(TOTAL, rest)
+        );
+        let rest = "2.5% (40MB-45MB-50XB over 4)";
+        assert_eq!(
+            ProcStats::parse(&format!("{process}    TOTAL: {rest}")).unwrap_err(),
+            invalid(TOTAL, rest)
+        );
+        let rest = "2.5% (40MB-45MB-50MB over many)";
+        assert_eq!(
+            ProcStats::parse(&format!("{process}    TOTAL: {rest}")).unwrap_err(),
+            invalid(TOTAL, rest)
+        );
+        assert_eq!(
+            ProcStats::parse(&format!("{process}    Top: most%")).unwrap_err(),
+            invalid("Top", "most%")
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            ProcStats::parse(&crlf).unwrap(),
+            ProcStats::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(ProcStats::parse("").unwrap(), ProcStats::default());
+    }
+}
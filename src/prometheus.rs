@@ -0,0 +1,348 @@
+//! Metrics in the Prometheus text exposition format, optionally served on `/metrics`
+//!
+//! [`Metrics`] holds gauges, [`PrometheusSink`] updates them from sampled snapshots and [`serve`]
+//! answers scrapes over HTTP.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::{
+//!     parse::kv::KeyValues,
+//!     prometheus::{self, Metrics, PrometheusSink},
+//!     Dumpsys, Sampler,
+//! };
+//!
+//! # fn foo() -> Option<()> {
+//! let metrics = Metrics::new();
+//! let sink = PrometheusSink::new(metrics.clone()).gauge(
+//!     "dumpsys_power_screen_brightness",
+//!     "Screen brightness setting",
+//!     |output| KeyValues::parse(output).get_float("mScreenBrightnessSetting").ok(),
+//! );
+//! let sampler = Sampler::with_sink(Dumpsys::new("power")?, Vec::<&str>::new(), Duration::from_secs(15), sink);
+//! let server = prometheus::serve("0.0.0.0:9464", metrics).unwrap();
+//! # Some(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{DumpSink, Snapshot};
+
+pub(crate) const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// How long a scrape may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST: usize = 8 * 1024;
+
+type Labels = Vec<(String, String)>;
+type Extract = Box<dyn Fn(&str) -> Option<f64> + Send>;
+
+/// Gauges shared between producers and the exporter; clones share the same values
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    families: Arc<Mutex<BTreeMap<String, Family>>>,
+}
+
+#[derive(Debug, Default)]
+struct Family {
+    help: Option<String>,
+    samples: BTreeMap<Labels, f64>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the gauge `name` with `labels` to `value`; invalid characters in `name` become `_`.
+    pub fn set(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| (sanitize(key), (*value).to_owned()))
+            .collect();
+        self.families
+            .lock()
+            .unwrap()
+            .entry(sanitize(name))
+            .or_default()
+            .samples
+            .insert(labels, value);
+    }
+
+    /// Document the gauge `name` with a `# HELP` line.
+    pub fn describe(&self, name: &str, help: &str) {
+        self.families
+            .lock()
+            .unwrap()
+            .entry(sanitize(name))
+            .or_default()
+            .help = Some(help.to_owned());
+    }
+
+    /// Drop every sample of the gauge `name`.
+    pub fn remove(&self, name: &str) {
+        self.families.lock().unwrap().remove(&sanitize(name));
+    }
+
+    /// All gauges in the text exposition format.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, family) in self.families.lock().unwrap().iter() {
+            if let Some(help) = &family.help {
+                let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+                let _ = writeln!(text, "# HELP {name} {help}");
+            }
+            let _ = writeln!(text, "# TYPE {name} gauge");
+            for (labels, value) in &family.samples {
+                text.push_str(name);
+                if !labels.is_empty() {
+                    text.push('{');
+                    for (i, (key, value)) in labels.iter().enumerate() {
+                        if i > 0 {
+                            text.push(',');
+                        }
+                        let _ = write!(text, "{key}=\"{}\"", escape(value));
+                    }
+                    text.push('}');
+                }
+                let _ = writeln!(text, " {}", Value(*value));
+            }
+        }
+        text
+    }
+}
+
+/// Updates [`Metrics`] from each snapshot, labelled with the service
+///
+/// Besides the gauges added with [`PrometheusSink::gauge`], records `dumpsys_duration_seconds` and
+/// `dumpsys_last_capture_timestamp_seconds` per service.
+pub struct PrometheusSink {
+    metrics: Metrics,
+    gauges: Vec<(String, Extract)>,
+}
+
+impl fmt::Debug for PrometheusSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gauges: Vec<_> = self.gauges.iter().map(|(name, _)| name).collect();
+        f.debug_struct("PrometheusSink")
+            .field("metrics", &self.metrics)
+            .field("gauges", &gauges)
+            .finish()
+    }
+}
+
+impl PrometheusSink {
+    pub fn new(metrics: Metrics) -> Self {
+        metrics.describe("dumpsys_duration_seconds", "How long the last dump took");
+        metrics.describe(
+            "dumpsys_last_capture_timestamp_seconds",
+            "When the last dump started",
+        );
+        Self {
+            metrics,
+            gauges: Vec::new(),
+        }
+    }
+
+    /// Set the gauge `name` to what `extract` finds in the output of each snapshot, leaving it as is
+    /// when `extract` finds nothing.
+    pub fn gauge<F>(mut self, name: &str, help: &str, extract: F) -> Self
+    where
+        F: Fn(&str) -> Option<f64> + Send + 'static,
+    {
+        self.metrics.describe(name, help);
+        self.gauges.push((name.to_owned(), Box::new(extract)));
+        self
+    }
+}
+
+impl DumpSink for PrometheusSink {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let labels = [("service", snapshot.service.as_str())];
+        for (name, extract) in &self.gauges {
+            if let Some(value) = extract(&snapshot.output) {
+                self.metrics.set(name, &labels, value);
+            }
+        }
+
+        let captured_at = snapshot
+            .captured_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.metrics.set(
+            "dumpsys_duration_seconds",
+            &labels,
+            snapshot.duration.as_secs_f64(),
+        );
+        self.metrics.set(
+            "dumpsys_last_capture_timestamp_seconds",
+            &labels,
+            captured_at.as_secs_f64(),
+        );
+        Ok(())
+    }
+}
+
+/// Serves `/metrics` over HTTP on a background thread, returned by [`serve`]
+///
+/// Dropping it stops the server.
+#[derive(Debug)]
+pub struct MetricsServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Address the server listens on, e.g. to find the port picked for `127.0.0.1:0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the blocking accept.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer `GET /metrics` on `addr` with the current `metrics`, one scrape at a time.
+pub fn serve(addr: impl ToSocketAddrs, metrics: Metrics) -> io::Result<MetricsServer> {
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let stopped = stop.clone();
+    let thread = thread::spawn(move || {
+        for stream in listener.incoming() {
+            if stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Ok(stream) = stream {
+                let _ = respond(stream, &metrics);
+            }
+        }
+    });
+
+    Ok(MetricsServer {
+        addr,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    let request = read_request(&mut stream)?;
+    let (status, body) = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/metrics") => ("200 OK", metrics.render()),
+        ("GET", _) => ("404 Not Found", String::from("try /metrics\n")),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    write_response(&mut stream, status, CONTENT_TYPE, &body)
+}
+
+/// The request line of an HTTP request
+pub(crate) struct Request {
+    pub(crate) method: String,
+    /// Without the query
+    pub(crate) path: String,
+    /// After the `?`, still percent-encoded
+    pub(crate) query: String,
+}
+
+/// Read the head of a request from `stream`, giving up after [`REQUEST_TIMEOUT`].
+pub(crate) fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let line = request
+        .split(|&byte| byte == b'\r')
+        .next()
+        .unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split(' ');
+    let method = parts.next().unwrap_or_default().to_owned();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Request {
+        method,
+        path: path.to_owned(),
+        query: query.to_owned(),
+    })
+}
+
+pub(crate) fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// A metric or label name with characters Prometheus doesn't allow replaced by `_`.
+fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':' => c,
+            _ => '_',
+        })
+        .collect();
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A sample value, spelling out the special floats the way Prometheus expects.
+struct Value(f64);
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            value if value.is_nan() => f.write_str("NaN"),
+            f64::INFINITY => f.write_str("+Inf"),
+            f64::NEG_INFINITY => f.write_str("-Inf"),
+            value => write!(f, "{value}"),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(feature = "prost")]
+use std::io;
+
+use crate::{error::DumpError, Dumpsys};
+
+const PROTO_ARG: &str = "--proto";
+
+impl Dumpsys {
+    /// Dump as a serialized protobuf, like `dumpsys <service> --proto`.
+    ///
+    /// Only services listed by [`list_proto_services`](crate::service_manager::list_proto_services)
+    /// support this, others typically write their text dump instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let proto = Dumpsys::new("activity")?.dump_proto(&["activities"]).unwrap();
+    /// println!("{} bytes", proto.len());
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_proto(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Vec<u8>, DumpError> {
+        self.dump_to_vec(proto_args(args))
+    }
+
+    /// Like [`Dumpsys::dump_proto`], decoding the output as `M`.
+    ///
+    /// `M` is generated with `prost-build` from the AOSP protos of the service, e.g.
+    /// `frameworks/base/core/proto/android/server/windowmanagerservice.proto` for `window`. Output that
+    /// doesn't decode fails with [`io::ErrorKind::InvalidData`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// // Generated by prost-build from windowmanagerservice.proto
+    /// use crate::proto::WindowManagerServiceDumpProto;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let window: WindowManagerServiceDumpProto = Dumpsys::new("window")?
+    ///     .dump_proto_as(Vec::<&str>::new())
+    ///     .unwrap();
+    /// # Some(())
+    /// # }
+    /// ```
+    #[cfg(feature = "prost")]
+    pub fn dump_proto_as<M>(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<M, DumpError>
+    where
+        M: prost::Message + Default,
+    {
+        let args = proto_args(args);
+        let proto = self.dump_to_vec(&args)?;
+        M::decode(proto.as_slice()).map_err(|err| {
+            DumpError::from(io::Error::new(io::ErrorKind::InvalidData, err))
+                .with_context(&self.service_name, &args)
+        })
+    }
+}
+
+/// `args` with `--proto` in front, which services expect before their own arguments.
+fn proto_args(args: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<String> {
+    [PROTO_ARG.to_owned()]
+        .into_iter()
+        .chain(args.into_iter().map(|arg| arg.as_ref().to_owned()))
+        .collect()
+}
@@ -0,0 +1,100 @@
+use std::{
+    io::{self, Read},
+    time::Instant,
+};
+
+use crate::{error::DumpError, execution::Transaction, observer::Attempt, pipe, stats::Tally};
+
+/// Output of an in-flight dump, returned by [`Dumpsys::dump_reader`](crate::Dumpsys::dump_reader)
+///
+/// Reading to the end waits for the dump transaction and fails if it didn't succeed.
+/// Dropping the reader early closes the pipe so the service stops writing, and waits for the
+/// transaction to return, up to the dump timeout if there is one, before reporting the attempt.
+pub struct DumpReader {
+    read: Option<pipe::Reader>,
+    handle: Option<Transaction>,
+    /// Taken once the attempt has been reported as over
+    attempt: Option<Attempt>,
+    tally: Tally,
+    deadline: Option<Instant>,
+}
+
+impl DumpReader {
+    pub(crate) fn new(
+        read: pipe::Reader,
+        handle: Option<Transaction>,
+        attempt: Attempt,
+        tally: Tally,
+    ) -> Self {
+        Self {
+            deadline: read.deadline(),
+            read: Some(read),
+            handle,
+            attempt: Some(attempt),
+            tally,
+        }
+    }
+
+    /// Stop reading and return the final status of the dump transaction.
+    pub fn finish(mut self) -> Result<(), DumpError> {
+        self.status()
+    }
+
+    fn status(&mut self) -> Result<(), DumpError> {
+        self.status_until(None)
+    }
+
+    /// Close the pipe and wait for the transaction until `deadline`, reporting the attempt as over.
+    fn status_until(&mut self, deadline: Option<Instant>) -> Result<(), DumpError> {
+        let bytes_read = self.close();
+        let result = match self.handle.take().map(|handle| handle.wait_until(deadline)) {
+            Some(Some(result)) => result.map_err(|err| err.after_output(bytes_read.unwrap_or(0))),
+            Some(None) => Err(DumpError::timeout()),
+            None => Ok(()),
+        };
+        match self.attempt.take() {
+            Some(attempt) => {
+                self.tally.record();
+                attempt.finish(bytes_read, result)
+            }
+            None => result,
+        }
+    }
+
+    /// Close the pipe, counting what was read from it, and return how many bytes that was.
+    fn close(&mut self) -> Option<u64> {
+        let read = self.read.take()?;
+        self.tally.read(read.bytes_read(), read.reads());
+        Some(read.bytes_read())
+    }
+}
+
+impl Read for DumpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(read) = self.read.as_mut() else {
+            return Ok(0);
+        };
+
+        let n = read.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            if read.truncated() {
+                let bytes_read = read.bytes_read();
+                self.close();
+                let mut err = DumpError::truncated(bytes_read);
+                if let Some(attempt) = self.attempt.take() {
+                    self.tally.record();
+                    err = attempt.fail(Some(bytes_read), err);
+                }
+                return Err(io::Error::other(err));
+            }
+            self.status().map_err(io::Error::other)?;
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for DumpReader {
+    fn drop(&mut self) {
+        let _ = self.status_until(self.deadline);
+    }
+}
@@ -0,0 +1,482 @@
+//! Recording dump sessions and replaying them through the normal API
+//!
+//! [`Backend::Record`] saves every dump of a [`Dumpsys`](crate::Dumpsys) using it to a directory, one
+//! file per dump, and [`Backend::Replay`] serves such a directory back instead of asking the service:
+//! integration tests get the same output every run, and a session captured on a device in the field
+//! can be reproduced on another one, or off-device with [`Replay::add_mock_services`].
+//!
+//! A recording is named `<capture time in ms>-<pid>-<sequence>.rec` and holds, with the encoding
+//! of [`remote`](crate::remote):
+//!
+//! ```text
+//! u8       version, 1
+//! string   service
+//! u32      argument count, then each argument as a string
+//! u64      capture time in milliseconds since the epoch
+//! u64      duration of the dump in microseconds
+//! i32      binder status of the dump, 0 when it succeeded
+//! u32      output length, then the output as written by the service
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::{
+//!     recording::{Recorder, Replay},
+//!     Backend, Dumpsys,
+//! };
+//!
+//! # fn foo() -> Option<()> {
+//! let recorder = Recorder::new("/data/local/tmp/session").ok()?;
+//! let surfaceflinger = Dumpsys::builder("SurfaceFlinger")
+//!     .backend(Backend::Record(recorder))
+//!     .build()?;
+//! let recorded = surfaceflinger.dump(&["--latency"]).unwrap();
+//!
+//! let replay = Replay::open("/data/local/tmp/session").ok()?;
+//! let surfaceflinger = Dumpsys::builder("SurfaceFlinger")
+//!     .backend(Backend::Replay(replay))
+//!     .build()?;
+//! assert_eq!(surfaceflinger.dump(&["--latency"]).unwrap(), recorded);
+//! # Some(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Seek, Write},
+    os::fd::{AsFd, BorrowedFd},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use binder::{SpIBinder, StatusCode};
+
+use crate::{
+    backend::Backend,
+    error::DumpError,
+    pipe,
+    remote::{invalid, put_str, put_u32, Payload},
+};
+
+const VERSION: u8 = 1;
+const EXTENSION: &str = "rec";
+
+/// Statuses a recording can hold, any other one is replayed as `UNKNOWN_ERROR`
+const STATUSES: [StatusCode; 18] = [
+    StatusCode::NO_MEMORY,
+    StatusCode::INVALID_OPERATION,
+    StatusCode::BAD_VALUE,
+    StatusCode::BAD_TYPE,
+    StatusCode::NAME_NOT_FOUND,
+    StatusCode::PERMISSION_DENIED,
+    StatusCode::NO_INIT,
+    StatusCode::ALREADY_EXISTS,
+    StatusCode::DEAD_OBJECT,
+    StatusCode::FAILED_TRANSACTION,
+    StatusCode::BAD_INDEX,
+    StatusCode::NOT_ENOUGH_DATA,
+    StatusCode::WOULD_BLOCK,
+    StatusCode::TIMED_OUT,
+    StatusCode::UNKNOWN_TRANSACTION,
+    StatusCode::FDS_NOT_ALLOWED,
+    StatusCode::UNEXPECTED_NULL,
+    StatusCode::UNKNOWN_ERROR,
+];
+
+/// One recorded dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub service: String,
+    pub args: Vec<String>,
+    /// Output as written by the service, which isn't UTF-8 for `--proto` dumps
+    pub output: Vec<u8>,
+    /// Wall clock time the dump started
+    pub captured_at: SystemTime,
+    /// How long the dump took
+    pub duration: Duration,
+    /// The binder status the dump failed with, `None` if it succeeded
+    pub status: Option<StatusCode>,
+}
+
+impl Recording {
+    /// Read the recording in the file at `path`.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    /// The file contents of the recording, see the [module docs](self).
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        let mut payload = Vec::with_capacity(self.output.len() + 64);
+        payload.push(VERSION);
+        put_str(&mut payload, &self.service)?;
+        put_u32(&mut payload, self.args.len())?;
+        for arg in &self.args {
+            put_str(&mut payload, arg)?;
+        }
+        let captured_at = self
+            .captured_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        payload.extend_from_slice(&(captured_at.as_millis() as u64).to_be_bytes());
+        payload.extend_from_slice(&(self.duration.as_micros() as u64).to_be_bytes());
+        let status = self.status.map_or(0, |status| status as i32);
+        payload.extend_from_slice(&status.to_be_bytes());
+        put_u32(&mut payload, self.output.len())?;
+        payload.extend_from_slice(&self.output);
+        Ok(payload)
+    }
+
+    /// The recording in file contents.
+    pub fn decode(payload: &[u8]) -> io::Result<Self> {
+        let mut payload = Payload(payload);
+        let version = payload.take(1)?[0];
+        if version != VERSION {
+            return Err(invalid(format!("unknown recording version {version}")));
+        }
+        let service = payload.string()?;
+        let args = (0..payload.u32()?)
+            .map(|_| payload.string())
+            .collect::<io::Result<_>>()?;
+        let captured_at = UNIX_EPOCH + Duration::from_millis(payload.u64()?);
+        let duration = Duration::from_micros(payload.u64()?);
+        let status = match payload.u32()? as i32 {
+            0 => None,
+            code => Some(
+                STATUSES
+                    .into_iter()
+                    .find(|status| *status as i32 == code)
+                    .unwrap_or(StatusCode::UNKNOWN_ERROR),
+            ),
+        };
+        let len = payload.u32()? as usize;
+        let output = payload.take(len)?.to_vec();
+        if !payload.0.is_empty() {
+            return Err(invalid(format!(
+                "{} bytes after the recording",
+                payload.0.len()
+            )));
+        }
+        Ok(Self {
+            service,
+            args,
+            output,
+            captured_at,
+            duration,
+            status,
+        })
+    }
+
+    /// What the dump returned.
+    fn result(&self) -> Result<(), StatusCode> {
+        self.status.map_or(Ok(()), Err)
+    }
+}
+
+/// Saves every dump to a directory, see [`Backend::Record`]
+///
+/// The output reaches the reader once the dump is complete, rather than while the service writes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recorder {
+    dir: PathBuf,
+    backend: Box<Backend>,
+}
+
+impl Recorder {
+    /// Record into `dir`, created if missing, dumping through [`Backend::Binder`].
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            backend: Box::default(),
+        })
+    }
+
+    /// Dump through `backend` instead, e.g. [`Backend::fallback`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Box::new(backend);
+        self
+    }
+
+    /// The directory recordings are saved to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Dump through the backend, save the recording, then hand the output to `fd`.
+    pub(crate) fn dump(
+        &self,
+        service: &mut SpIBinder,
+        service_name: &str,
+        args: &[String],
+        timeout: Option<Duration>,
+        fd: BorrowedFd<'_>,
+    ) -> Result<(), DumpError> {
+        let mut file = pipe::memfd(None)?;
+        let captured_at = SystemTime::now();
+        let start = Instant::now();
+        let result = self
+            .backend
+            .dump(service, service_name, args, timeout, file.as_fd());
+        let duration = start.elapsed();
+
+        let mut output = Vec::new();
+        file.rewind()?;
+        file.read_to_end(&mut output)?;
+        let recording = Recording {
+            service: service_name.to_owned(),
+            args: args.to_vec(),
+            output,
+            captured_at,
+            duration,
+            status: result
+                .as_ref()
+                .err()
+                .map(|err| err.status().unwrap_or(StatusCode::UNKNOWN_ERROR)),
+        };
+        self.save(&recording)?;
+
+        write_output(fd, &recording.output)?;
+        result
+    }
+
+    /// Write `recording` under a temporary name first, so a replay never reads half of it.
+    fn save(&self, recording: &Recording) -> io::Result<()> {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let captured_at = recording
+            .captured_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let name = format!(
+            "{captured_at}-{}-{}",
+            std::process::id(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+        let temp = self.dir.join(format!(".{name}.tmp"));
+        fs::write(&temp, recording.encode()?)?;
+        fs::rename(&temp, self.dir.join(name).with_extension(EXTENSION))
+    }
+}
+
+/// The sequence number in the name of the recording at `path`, 0 if it was renamed.
+fn sequence(path: &Path) -> u64 {
+    path.file_stem()
+        .and_then(|stem| stem.to_str()?.rsplit('-').next()?.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Serves the dumps of a recorded session, see [`Backend::Replay`]
+///
+/// The n-th dump of a service with some arguments gets the n-th recording of them in capture order,
+/// and the last one again after that. A dump nothing was recorded for fails with
+/// [`io::ErrorKind::NotFound`]. Clones share their position in the session.
+#[derive(Clone)]
+pub struct Replay {
+    session: Arc<Session>,
+    timing: bool,
+}
+
+struct Session {
+    dir: PathBuf,
+    recordings: HashMap<(String, Vec<String>), Vec<Recording>>,
+    /// Index of the next recording to serve per dump
+    next: Mutex<HashMap<(String, Vec<String>), usize>>,
+}
+
+impl Replay {
+    /// Load every recording in `dir`.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        let mut sequenced: HashMap<_, Vec<(u64, Recording)>> = HashMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(EXTENSION.as_ref()) {
+                continue;
+            }
+            let recording = Recording::read(&path)
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+            sequenced
+                .entry((recording.service.clone(), recording.args.clone()))
+                .or_default()
+                .push((sequence(&path), recording));
+        }
+        // Capture times only have millisecond resolution, the sequence orders dumps within one
+        let recordings = sequenced
+            .into_iter()
+            .map(|(key, mut recordings)| {
+                recordings.sort_by_key(|(sequence, recording)| (recording.captured_at, *sequence));
+                let recordings = recordings.into_iter().map(|(_, recording)| recording);
+                (key, recordings.collect())
+            })
+            .collect();
+
+        Ok(Self {
+            session: Arc::new(Session {
+                dir,
+                recordings,
+                next: Mutex::default(),
+            }),
+            timing: false,
+        })
+    }
+
+    /// Take as long as the recorded dump did before serving it, e.g. to reproduce timeouts. Off by
+    /// default.
+    pub fn timing(mut self, enable: bool) -> Self {
+        self.timing = enable;
+        self
+    }
+
+    /// Names of the recorded services, sorted.
+    pub fn services(&self) -> Vec<String> {
+        let mut services: Vec<String> = self
+            .session
+            .recordings
+            .keys()
+            .map(|(service, _)| service.clone())
+            .collect();
+        services.sort_unstable();
+        services.dedup();
+        services
+    }
+
+    /// Register every recorded service with the in-process servicemanager of [`mock`](crate::mock),
+    /// answering dumps from the session, so code using the real services replays off-device unchanged.
+    ///
+    /// Dumps nothing was recorded for fail with `BAD_VALUE`.
+    #[cfg(not(target_os = "android"))]
+    pub fn add_mock_services(&self) {
+        use crate::mock::{self, MockService};
+
+        for service in self.services() {
+            let replay = self.clone();
+            let name = service.clone();
+            mock::add_service(
+                service,
+                MockService::from_fn(move |args| {
+                    let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+                    let recording = replay.next(&name, &args).ok_or(StatusCode::BAD_VALUE)?;
+                    recording.result().map(|()| recording.output.clone())
+                }),
+            );
+        }
+    }
+
+    /// Serve the next recording of the dump into `fd`.
+    pub(crate) fn dump(
+        &self,
+        service_name: &str,
+        args: &[String],
+        fd: BorrowedFd<'_>,
+    ) -> Result<(), DumpError> {
+        let recording = self.next(service_name, args).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("nothing recorded in {}", self.session.dir.display()),
+            )
+        })?;
+        write_output(fd, &recording.output)?;
+        Ok(recording.result()?)
+    }
+
+    fn next(&self, service_name: &str, args: &[String]) -> Option<Recording> {
+        let key = (service_name.to_owned(), args.to_vec());
+        let recordings = self.session.recordings.get(&key)?;
+        let index = {
+            let mut next = self.session.next.lock().unwrap();
+            let index = next.entry(key).or_default();
+            *index += 1;
+            (*index - 1).min(recordings.len() - 1)
+        };
+        let recording = recordings[index].clone();
+        if self.timing {
+            thread::sleep(recording.duration);
+        }
+        Some(recording)
+    }
+}
+
+impl fmt::Debug for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replay")
+            .field("dir", &self.session.dir)
+            .field("timing", &self.timing)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Replays are equal when they share their session.
+impl PartialEq for Replay {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.session, &other.session) && self.timing == other.timing
+    }
+}
+
+impl Eq for Replay {}
+
+/// Write `output` to the reader of the dump like a service would, ignoring a reader that went away.
+fn write_output(fd: BorrowedFd<'_>, output: &[u8]) -> io::Result<()> {
+    let _ = File::from(fd.try_clone_to_owned()?).write_all(output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(output: &str, captured_at: SystemTime) -> Recording {
+        Recording {
+            service: "SurfaceFlinger".to_owned(),
+            args: vec!["--latency".to_owned()],
+            output: output.as_bytes().to_vec(),
+            captured_at,
+            duration: Duration::ZERO,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn replays_dumps_of_the_same_millisecond_in_sequence_order() {
+        let dir = std::env::temp_dir().join(format!("dumpsys-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let captured_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        for (sequence, output) in [(9, "ninth"), (10, "tenth"), (2, "second")] {
+            let name = format!("1700000000000-42-{sequence}.{EXTENSION}");
+            let encoded = recording(output, captured_at).encode().unwrap();
+            fs::write(dir.join(name), encoded).unwrap();
+        }
+        let earlier = recording("first", captured_at - Duration::from_millis(1));
+        fs::write(
+            dir.join(format!("1699999999999-42-11.{EXTENSION}")),
+            earlier.encode().unwrap(),
+        )
+        .unwrap();
+
+        let replay = Replay::open(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let replay = replay.unwrap();
+        let args = ["--latency".to_owned()];
+        let outputs: Vec<_> = (0..5)
+            .map(|_| replay.next("SurfaceFlinger", &args).unwrap().output)
+            .collect();
+        assert_eq!(
+            outputs,
+            ["first", "second", "ninth", "tenth", "tenth"].map(|output| output.as_bytes())
+        );
+    }
+
+    #[test]
+    fn sequence_of_a_renamed_recording_is_zero() {
+        assert_eq!(sequence(Path::new("/tmp/1700000000000-42-10.rec")), 10);
+        assert_eq!(sequence(Path::new("/tmp/boot.rec")), 0);
+    }
+}
@@ -0,0 +1,504 @@
+//! Streaming snapshots to a host collector over a socket
+//!
+//! [`RemoteSink`] sends every snapshot as one frame: a big-endian `u32` length followed by that many
+//! bytes of payload. The payload is, with every integer big-endian and every string a `u32` length
+//! followed by UTF-8:
+//!
+//! ```text
+//! u8       version, 1
+//! string   service
+//! u32      argument count, then each argument as a string
+//! u64      capture time in milliseconds since the epoch
+//! u64      duration of the dump in microseconds
+//! string   output
+//! ```
+//!
+//! The sink connects to a collector over TCP, or a Unix socket, reconnecting after a failed write. It
+//! can also listen for the collector, so `adb forward tcp:9000 tcp:9000` on the host reaches it;
+//! connecting out to `127.0.0.1` works with `adb reverse` instead. Snapshots captured while
+//! disconnected are kept up to a limit and sent once the collector is back, oldest first.
+//!
+//! On the host, [`RemoteReader`] reads the snapshots back from a stream.
+//!
+//! # Example
+//!
+//! ```
+//! use std::{io::Cursor, time::{Duration, UNIX_EPOCH}};
+//!
+//! use dumpsys_rs::{remote::{self, RemoteReader}, Snapshot};
+//!
+//! let snapshot = Snapshot {
+//!     service: "battery".to_owned(),
+//!     args: vec!["--checkin".to_owned()],
+//!     output: "level: 85\n".to_owned(),
+//!     captured_at: UNIX_EPOCH + Duration::from_millis(1_700_000_000_000),
+//!     duration: Duration::from_micros(2500),
+//! };
+//!
+//! let mut stream = Vec::new();
+//! remote::write_snapshot(&mut stream, &snapshot).unwrap();
+//! remote::write_snapshot(&mut stream, &snapshot).unwrap();
+//!
+//! let snapshots: Vec<Snapshot> = RemoteReader::new(Cursor::new(stream))
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(snapshots, [snapshot.clone(), snapshot]);
+//! ```
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use crate::{DumpSink, Snapshot};
+
+const VERSION: u8 = 1;
+
+/// Largest frame [`read_frame`] accepts, so a corrupt length can't allocate the memory of the host
+pub const MAX_FRAME_LEN: u32 = 256 * 1024 * 1024;
+
+/// Write `payload` as one frame.
+pub fn write_frame(out: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    out.write_all(&frame(payload)?)
+}
+
+/// Read the payload of the next frame, `None` at the end of the stream between frames.
+pub fn read_frame(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len[..1]) {
+        Ok(()) => input.read_exact(&mut len[1..])?,
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(invalid(format!(
+            "frame of {len} bytes, at most {MAX_FRAME_LEN} allowed"
+        )));
+    }
+    let mut payload = vec![0; len as usize];
+    input.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+/// Write `snapshot` as one frame.
+pub fn write_snapshot(out: &mut impl Write, snapshot: &Snapshot) -> io::Result<()> {
+    write_frame(out, &encode(snapshot)?)
+}
+
+/// Read the next snapshot, `None` at the end of the stream.
+pub fn read_snapshot(input: &mut impl Read) -> io::Result<Option<Snapshot>> {
+    read_frame(input)?
+        .map(|payload| decode(&payload))
+        .transpose()
+}
+
+/// The payload of the frame of `snapshot`, see the [module docs](self).
+pub fn encode(snapshot: &Snapshot) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(snapshot.output.len() + 64);
+    payload.push(VERSION);
+    put_str(&mut payload, &snapshot.service)?;
+    put_u32(&mut payload, snapshot.args.len())?;
+    for arg in &snapshot.args {
+        put_str(&mut payload, arg)?;
+    }
+    let captured_at = snapshot
+        .captured_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    payload.extend_from_slice(&(captured_at.as_millis() as u64).to_be_bytes());
+    payload.extend_from_slice(&(snapshot.duration.as_micros() as u64).to_be_bytes());
+    put_str(&mut payload, &snapshot.output)?;
+    Ok(payload)
+}
+
+/// The snapshot in a frame payload.
+pub fn decode(payload: &[u8]) -> io::Result<Snapshot> {
+    let mut payload = Payload(payload);
+    let version = payload.take(1)?[0];
+    if version != VERSION {
+        return Err(invalid(format!("unknown frame version {version}")));
+    }
+    let service = payload.string()?;
+    let args = (0..payload.u32()?)
+        .map(|_| payload.string())
+        .collect::<io::Result<_>>()?;
+    let captured_at = UNIX_EPOCH + Duration::from_millis(payload.u64()?);
+    let duration = Duration::from_micros(payload.u64()?);
+    let output = payload.string()?;
+    if !payload.0.is_empty() {
+        return Err(invalid(format!(
+            "{} bytes after the snapshot",
+            payload.0.len()
+        )));
+    }
+    Ok(Snapshot {
+        service,
+        args,
+        output,
+        captured_at,
+        duration,
+    })
+}
+
+/// Snapshots read from a stream written by a [`RemoteSink`], ending with the stream
+///
+/// # Example
+///
+/// ```
+/// use std::net::TcpListener;
+///
+/// use dumpsys_rs::remote::RemoteReader;
+///
+/// # fn foo() {
+/// // With `adb reverse tcp:9000 tcp:9000`, for a device sending to `RemoteSink::tcp("127.0.0.1:9000")`.
+/// let listener = TcpListener::bind("127.0.0.1:9000").unwrap();
+/// for stream in listener.incoming() {
+///     for snapshot in RemoteReader::new(stream.unwrap()) {
+///         let Ok(snapshot) = snapshot else { break };
+///         println!("{} at {:?}: {} bytes", snapshot.service, snapshot.captured_at, snapshot.len());
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RemoteReader<R> {
+    input: R,
+}
+
+impl<R: Read> RemoteReader<R> {
+    pub fn new(input: R) -> Self {
+        Self { input }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+impl<R: Read> Iterator for RemoteReader<R> {
+    type Item = io::Result<Snapshot>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_snapshot(&mut self.input).transpose()
+    }
+}
+
+/// Sends snapshots to a collector, see the [module docs](self)
+///
+/// Writes never fail because the collector is gone: the snapshot is buffered and the sink tries to
+/// connect again on the next write, at most once per [`RemoteSink::retry_delay`]. Once more than
+/// [`RemoteSink::max_buffered`] bytes wait, the oldest snapshots are dropped and counted in
+/// [`RemoteSink::dropped`]. [`DumpSink::flush`] fails if snapshots are still waiting.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{remote::RemoteSink, Dumpsys, Sampler};
+///
+/// # fn foo() -> Option<()> {
+/// // Reached from the host with `adb forward tcp:9000 tcp:9000`.
+/// let sink = RemoteSink::listen("127.0.0.1:9000")
+///     .unwrap()
+///     .max_buffered(64 * 1024 * 1024)
+///     .retry_delay(Duration::from_secs(5));
+/// let sampler = Sampler::with_sink(Dumpsys::new("meminfo")?, ["-a"], Duration::from_secs(30), sink);
+/// # Some(())
+/// # }
+/// ```
+pub struct RemoteSink {
+    endpoint: Endpoint,
+    connection: Option<Connection>,
+    /// Frames not sent yet, oldest first
+    pending: VecDeque<Vec<u8>>,
+    pending_bytes: usize,
+    max_buffered: usize,
+    retry_delay: Duration,
+    write_timeout: Duration,
+    last_attempt: Option<Instant>,
+    dropped: u64,
+}
+
+enum Endpoint {
+    Tcp(Vec<SocketAddr>),
+    Listen(TcpListener),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl fmt::Debug for RemoteSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endpoint = match &self.endpoint {
+            Endpoint::Tcp(addrs) => format!("tcp {addrs:?}"),
+            Endpoint::Listen(listener) => format!("listening on {:?}", listener.local_addr()),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => format!("unix {}", path.display()),
+        };
+        f.debug_struct("RemoteSink")
+            .field("endpoint", &endpoint)
+            .field("connected", &self.is_connected())
+            .field("buffered", &self.pending.len())
+            .field("dropped", &self.dropped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RemoteSink {
+    /// Connect to a collector listening on `addr`, resolved once.
+    pub fn tcp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(invalid("no address to connect to".to_owned()));
+        }
+        Ok(Self::with_endpoint(Endpoint::Tcp(addrs)))
+    }
+
+    /// Listen on `addr` and send to the collector connected last, accepting another once it's gone.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self::with_endpoint(Endpoint::Listen(listener)))
+    }
+
+    /// Connect to a collector listening on the Unix socket at `path`.
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Self::with_endpoint(Endpoint::Unix(path.into()))
+    }
+
+    fn with_endpoint(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            connection: None,
+            pending: VecDeque::new(),
+            pending_bytes: 0,
+            max_buffered: 16 * 1024 * 1024,
+            retry_delay: Duration::from_secs(1),
+            write_timeout: Duration::from_secs(10),
+            last_attempt: None,
+            dropped: 0,
+        }
+    }
+
+    /// Keep at most `bytes` of snapshots while disconnected, 16 MiB by default. The latest snapshot
+    /// is always kept.
+    pub fn max_buffered(mut self, bytes: usize) -> Self {
+        self.max_buffered = bytes;
+        self
+    }
+
+    /// Wait at least `delay` between attempts to connect, 1 second by default.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Consider the collector gone when a write blocks for `timeout`, 10 seconds by default. Also
+    /// bounds connecting over TCP.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Snapshots waiting to be sent.
+    pub fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Snapshots dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Send the waiting frames, connecting first if needed and due. Frames stay buffered on failure.
+    fn send_pending(&mut self) {
+        while !self.pending.is_empty() {
+            if self.connection.is_none() && !self.reconnect() {
+                return;
+            }
+            let (Some(connection), Some(frame)) = (self.connection.as_mut(), self.pending.front())
+            else {
+                return;
+            };
+            if connection.write_all(frame).is_err() {
+                // Sent again whole on the next connection, the collector drops a cut frame.
+                self.connection = None;
+                continue;
+            }
+            let frame = self.pending.pop_front().expect("front frame");
+            self.pending_bytes -= frame.len();
+        }
+    }
+
+    /// Try to connect, unless the last attempt was too recent.
+    fn reconnect(&mut self) -> bool {
+        let due = self
+            .last_attempt
+            .is_none_or(|attempt| attempt.elapsed() >= self.retry_delay);
+        if !due {
+            return false;
+        }
+        self.last_attempt = Some(Instant::now());
+        self.connection = self.connect().ok();
+        self.connection.is_some()
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        let connection = match &self.endpoint {
+            Endpoint::Tcp(addrs) => {
+                let mut last = None;
+                let stream = addrs.iter().find_map(|addr| {
+                    TcpStream::connect_timeout(addr, self.write_timeout)
+                        .map_err(|err| last = Some(err))
+                        .ok()
+                });
+                Connection::Tcp(stream.ok_or_else(|| last.expect("at least one address"))?)
+            }
+            Endpoint::Listen(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Connection::Tcp(stream)
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Connection::Unix(UnixStream::connect(path)?),
+        };
+        match &connection {
+            Connection::Tcp(stream) => {
+                stream.set_nodelay(true)?;
+                stream.set_write_timeout(Some(self.write_timeout))?;
+            }
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_write_timeout(Some(self.write_timeout))?,
+        }
+        Ok(connection)
+    }
+}
+
+impl DumpSink for RemoteSink {
+    /// Queue `snapshot` and send everything waiting if connected, or once connecting succeeds.
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let frame = frame(&encode(snapshot)?)?;
+        self.pending_bytes += frame.len();
+        self.pending.push_back(frame);
+        while self.pending_bytes > self.max_buffered && self.pending.len() > 1 {
+            let oldest = self.pending.pop_front().expect("more than one frame");
+            self.pending_bytes -= oldest.len();
+            self.dropped += 1;
+        }
+        self.send_pending();
+        Ok(())
+    }
+
+    /// Send the waiting snapshots, failing with [`ErrorKind::NotConnected`] if some remain.
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_pending();
+        if let Some(connection) = self.connection.as_mut() {
+            if connection.flush().is_err() {
+                self.connection = None;
+            }
+        }
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            ErrorKind::NotConnected,
+            format!("{} snapshots not sent", self.pending.len()),
+        ))
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// `payload` behind its length.
+fn frame(payload: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| invalid(format!("frame of {} bytes is too large", payload.len())))?;
+    let mut frame = Vec::with_capacity(payload.len() + 4);
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+pub(crate) fn put_u32(payload: &mut Vec<u8>, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| invalid(format!("{value} is too large")))?;
+    payload.extend_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+
+pub(crate) fn put_str(payload: &mut Vec<u8>, text: &str) -> io::Result<()> {
+    put_u32(payload, text.len())?;
+    payload.extend_from_slice(text.as_bytes());
+    Ok(())
+}
+
+/// The rest of a payload being decoded
+pub(crate) struct Payload<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Payload<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.0.len() {
+            return Err(invalid("truncated snapshot".to_owned()));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    pub(crate) fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(
+            self.take(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    pub(crate) fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|err| invalid(err.to_string()))
+    }
+}
+
+pub(crate) fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
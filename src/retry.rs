@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use binder::StatusCode;
+
+use crate::error::DumpError;
+
+/// How often a failed dump transaction is attempted again
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use binder::StatusCode;
+/// use dumpsys_rs::RetryPolicy;
+///
+/// // 50ms, 100ms, 200ms, 400ms between the five attempts, only while the service restarts
+/// let policy = RetryPolicy::exponential(5, Duration::from_millis(50), Duration::from_secs(1))
+///     .retry_on([StatusCode::DEAD_OBJECT, StatusCode::FAILED_TRANSACTION]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) backoff: Duration,
+    pub(crate) multiplier: u32,
+    pub(crate) max_backoff: Duration,
+    pub(crate) retry_on: Option<Vec<StatusCode>>,
+}
+
+impl RetryPolicy {
+    /// Try every dump exactly once
+    pub const fn none() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+
+    /// Try a dump up to `max_attempts` times, sleeping `backoff` between attempts
+    pub const fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            multiplier: 1,
+            max_backoff: Duration::MAX,
+            retry_on: None,
+        }
+    }
+
+    /// Try a dump up to `max_attempts` times, doubling the sleep from `backoff` up to `max_backoff`
+    pub const fn exponential(max_attempts: u32, backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            multiplier: 2,
+            max_backoff,
+            retry_on: None,
+        }
+    }
+
+    /// Only retry transactions failing with one of `codes`, instead of any status.
+    pub fn retry_on(mut self, codes: impl IntoIterator<Item = StatusCode>) -> Self {
+        self.retry_on = Some(codes.into_iter().collect());
+        self
+    }
+
+    /// Whether a dump that failed `attempts` times with `err` should run again.
+    pub(crate) fn should_retry(&self, err: &DumpError, attempts: u32) -> bool {
+        let Some(status) = err.status() else {
+            return false;
+        };
+        attempts < self.max_attempts
+            && self
+                .retry_on
+                .as_ref()
+                .is_none_or(|codes| codes.contains(&status))
+    }
+
+    /// Sleep after the `attempts`th failure.
+    pub(crate) fn delay(&self, attempts: u32) -> Duration {
+        self.multiplier
+            .checked_pow(attempts.saturating_sub(1))
+            .and_then(|factor| self.backoff.checked_mul(factor))
+            .unwrap_or(Duration::MAX)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builder::Config, cancel::CancelToken, error::DumpError, owned_args, DumpSink, Dumpsys, Snapshot,
+};
+
+/// Dumps a service at a fixed interval on a background thread
+///
+/// Samples are scheduled against the start time rather than the end of the previous dump, so slow dumps
+/// don't make the schedule drift; ticks missed while a dump overran are skipped. Stopping, or dropping
+/// the sampler, aborts a dump in flight and joins the thread.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{Dumpsys, Sampler};
+///
+/// # fn foo() -> Option<()> {
+/// let dumpsys = Dumpsys::new("SurfaceFlinger")?;
+/// let (sampler, samples) = Sampler::channel(dumpsys, &["--latency"], Duration::from_secs(1));
+/// for sample in samples.iter().take(10) {
+///     println!("{}", sample.unwrap().output);
+/// }
+/// sampler.stop();
+/// # Some(())
+/// # }
+/// ```
+pub struct Sampler {
+    stop: Option<Sender<()>>,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Dump every `interval`, passing each result to `callback` until it breaks or the sampler stops.
+    pub fn new<F>(
+        dumpsys: Dumpsys,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        interval: Duration,
+        mut callback: F,
+    ) -> Self
+    where
+        F: FnMut(Result<Snapshot, DumpError>) -> ControlFlow<()> + Send + 'static,
+    {
+        let args = owned_args(args);
+        let cancel = CancelToken::new();
+        let (stop, stopped) = mpsc::channel();
+
+        let token = cancel.clone();
+        let thread = thread::spawn(move || {
+            let dumpsys = dumpsys.with_config(Config {
+                cancel: Some(token.clone()),
+                ..(*dumpsys.config).clone()
+            });
+            let start = Instant::now();
+            let mut ticks = 0u32;
+
+            loop {
+                let sample = dumpsys.dump_snapshot(&args);
+                if token.is_cancelled() || callback(sample).is_break() {
+                    return;
+                }
+
+                let elapsed = start.elapsed();
+                ticks = next_tick(ticks, interval, elapsed);
+                let wait = interval
+                    .checked_mul(ticks)
+                    .unwrap_or(Duration::MAX)
+                    .saturating_sub(elapsed);
+                match stopped.recv_timeout(wait) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    /// Dump every `interval`, sending the results to the returned receiver until it's dropped.
+    pub fn channel(
+        dumpsys: Dumpsys,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        interval: Duration,
+    ) -> (Self, Receiver<Result<Snapshot, DumpError>>) {
+        let (tx, rx) = mpsc::channel();
+        let sampler = Self::new(dumpsys, args, interval, move |sample| {
+            tx.send(sample)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+        });
+        (sampler, rx)
+    }
+
+    /// Dump every `interval`, writing each snapshot to `sink` and flushing it once sampling ends.
+    ///
+    /// Failed dumps are skipped; sampling stops early if the sink fails.
+    pub fn with_sink<S>(
+        dumpsys: Dumpsys,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        interval: Duration,
+        sink: S,
+    ) -> Self
+    where
+        S: DumpSink + Send + 'static,
+    {
+        let mut sink = FlushOnDrop(sink);
+        Self::new(dumpsys, args, interval, move |sample| match sample {
+            Ok(snapshot) => sink
+                .0
+                .write_snapshot(&snapshot)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue),
+            Err(_) => ControlFlow::Continue(()),
+        })
+    }
+
+    /// Stop sampling, aborting a dump in flight, and wait for the thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.cancel.cancel();
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Flushes the sink once the sampler thread drops its callback.
+struct FlushOnDrop<S: DumpSink>(S);
+
+impl<S: DumpSink> Drop for FlushOnDrop<S> {
+    fn drop(&mut self) {
+        let _ = self.0.flush();
+    }
+}
+
+/// The first tick after `ticks` that is still ahead of `elapsed`, skipping any missed ones.
+pub(crate) fn next_tick(ticks: u32, interval: Duration, elapsed: Duration) -> u32 {
+    if interval.is_zero() {
+        return ticks + 1;
+    }
+    let due = elapsed.as_nanos() / interval.as_nanos() + 1;
+    u32::try_from(due).unwrap_or(u32::MAX).max(ticks + 1)
+}
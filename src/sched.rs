@@ -0,0 +1,161 @@
+use std::{io, thread};
+
+/// Scheduling policy of a thread, see `sched(7)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedPolicy {
+    /// `SCHED_OTHER` at a nice value, from -20 (favoured) to 19
+    Normal { nice: i32 },
+    /// `SCHED_BATCH` at a nice value, for work that can wait to be woken up
+    Batch { nice: i32 },
+    /// `SCHED_IDLE`, running only when nothing else wants the CPU
+    Idle,
+    /// `SCHED_FIFO` at a realtime priority, from 1 to 99
+    Fifo { priority: i32 },
+    /// `SCHED_RR` at a realtime priority, from 1 to 99
+    RoundRobin { priority: i32 },
+}
+
+/// Name and scheduling of the threads dumps run on, set with
+/// [`DumpsysBuilder::threads`](crate::DumpsysBuilder::threads) or [`WorkerPool::with_options`](crate::WorkerPool::with_options)
+///
+/// They apply to the threads this crate spawns, never to the calling thread: the transaction thread
+/// of [`Execution::Spawn`](crate::Execution::Spawn), blocking and async dumps alike, the thread reading
+/// the pipe for `Dumpsys::dump_stream`, and the workers of a pool. The other blocking dumps read the
+/// pipe on the calling thread, which keeps its name and scheduling, as setting them back after a
+/// lowered priority would need `CAP_SYS_NICE`; call them from a thread scheduled as wanted, or stream
+/// the dump, to place the reading too. Threads of tokio's blocking pool run other work too and keep
+/// their own. A thread that can't be scheduled as asked, e.g. with a realtime policy and no
+/// `CAP_SYS_NICE`, fails its dump. Elsewhere than on Android and Linux, only the name applies.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{Dumpsys, SchedPolicy, ThreadOptions};
+///
+/// # fn foo() -> Option<()> {
+/// let surfaceflinger = Dumpsys::builder("SurfaceFlinger")
+///     .threads(
+///         ThreadOptions::new()
+///             .name("sf-dump")
+///             .policy(SchedPolicy::Normal { nice: 10 })
+///             .cpus(0..4),
+///     )
+///     .build()?;
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadOptions {
+    name: Option<String>,
+    policy: Option<SchedPolicy>,
+    cpus: Option<Vec<usize>>,
+}
+
+impl ThreadOptions {
+    /// Unnamed threads scheduled like the threads that spawn them.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the threads, as `ps -T`, systrace and Perfetto show them. Linux keeps the first 15 bytes.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Schedule the threads with `policy`.
+    pub fn policy(mut self, policy: SchedPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Only run the threads on `cpus`, e.g. the little cores.
+    pub fn cpus(mut self, cpus: impl IntoIterator<Item = usize>) -> Self {
+        self.cpus = Some(cpus.into_iter().collect());
+        self
+    }
+
+    /// The name of the threads, if any.
+    pub(crate) fn thread_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// A builder for a thread with the name, whose closure has to [`apply`](Self::apply) the rest.
+    pub(crate) fn builder(&self) -> thread::Builder {
+        match &self.name {
+            Some(name) => thread::Builder::new().name(name.clone()),
+            None => thread::Builder::new(),
+        }
+    }
+
+    /// Schedule the calling thread as asked.
+    pub(crate) fn apply(&self) -> io::Result<()> {
+        if let Some(policy) = self.policy {
+            set_policy(policy)?;
+        }
+        if let Some(cpus) = &self.cpus {
+            set_affinity(cpus)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn set_policy(policy: SchedPolicy) -> io::Result<()> {
+    let (policy, priority, nice) = match policy {
+        SchedPolicy::Normal { nice } => (libc::SCHED_OTHER, 0, Some(nice)),
+        SchedPolicy::Batch { nice } => (libc::SCHED_BATCH, 0, Some(nice)),
+        SchedPolicy::Idle => (libc::SCHED_IDLE, 0, None),
+        SchedPolicy::Fifo { priority } => (libc::SCHED_FIFO, priority, None),
+        SchedPolicy::RoundRobin { priority } => (libc::SCHED_RR, priority, None),
+    };
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    // SAFETY: `param` lives across the call, and pid 0 is the calling thread.
+    if unsafe { libc::sched_setscheduler(0, policy, &param) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if let Some(nice) = nice {
+        // Linux keeps nice values per thread, so renicing the thread leaves the rest of the process be.
+        // SAFETY: plain syscalls on the calling thread.
+        let result =
+            unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, nice) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn set_affinity(cpus: &[usize]) -> io::Result<()> {
+    use std::mem;
+
+    // SAFETY: `cpu_set_t` is a plain bit set, empty when zeroed.
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    for &cpu in cpus {
+        if cpu >= mem::size_of::<libc::cpu_set_t>() * 8 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        // SAFETY: `cpu` is within the set, checked above.
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    // SAFETY: `set` lives across the call, and pid 0 is the calling thread.
+    if unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Scheduling can't be set elsewhere, e.g. on macOS, where threads only take a QoS class.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn set_policy(_policy: SchedPolicy) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn set_affinity(_cpus: &[usize]) -> io::Result<()> {
+    Ok(())
+}
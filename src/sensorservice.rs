@@ -0,0 +1,582 @@
+//! Typed output of `dumpsys sensorservice`
+//!
+//! Connections don't print the rates they requested, so those are taken from the latest activation
+//! in the registration history.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::sensorservice::{ReportingMode, SensorService};
+//!
+//! let service = SensorService::parse(
+//!     "Captured at: 12:00:00.000
+//! Sensor Device:
+//! Total 2 h/w sensors, 2 running 0 disabled clients:
+//! 0x0000000b) active-count = 1; sampling_period(ms) = {5.0}, selected = 5.00 ms; batching_period(ms) = {0.0}, selected = 0.00 ms
+//! Sensor List:
+//! 0x0000000b) BMI160 Accelerometer      | Bosch           | ver: 1 | type: android.sensor.accelerometer(1) | perm: n/a | flags: 0x00000000
+//! \tcontinuous | minRate=5.00Hz | maxRate=200.00Hz | FIFO (max,reserved) = (10000, 3000) events | non-wakeUp |
+//! 0x00000014) Significant Motion        | Google          | ver: 1 | type: android.sensor.significant_motion(17) | perm: n/a | flags: 0x00000005
+//! \tone-shot | FIFO (max,reserved) = (0, 0) events | wakeUp |
+//! Fusion States:
+//! 9-axis fusion disabled (0 clients), gyro-rate= 200.00Hz
+//! 1 active connections
+//! Connection Number: 0
+//! \tOperating Mode: NORMAL
+//! \t com.example.app.MotionService | WakeLockRefCount 0 | uid 10123 | cache size 0 | max cache size 0
+//! \t BMI160 Accelerometer 0x0000000b | status: active | pending flush events 0
+//! 0 direct connections
+//! Previous Registrations:
+//! 12:00:00 + 0x0000000b pid= 4567 uid=10123 package=com.example.app.MotionService samplingPeriod=5000us batchingPeriod=0us
+//! 11:59:00 - 0x0000000b pid= 4567 uid=10123 package=com.example.app.MotionService
+//! 11:58:00 + 0x0000000b pid= 4567 uid=10123 package=com.example.app.MotionService samplingPeriod=20000us batchingPeriod=0us",
+//! )
+//! .unwrap();
+//!
+//! let accelerometer = service.sensor(0xb).unwrap();
+//! assert_eq!(accelerometer.name, "BMI160 Accelerometer");
+//! assert_eq!(accelerometer.vendor, "Bosch");
+//! assert_eq!(accelerometer.type_name, "android.sensor.accelerometer");
+//! assert_eq!(accelerometer.sensor_type, 1);
+//! assert_eq!(accelerometer.reporting_mode, Some(ReportingMode::Continuous));
+//! assert_eq!(accelerometer.max_rate_hz, Some(200.0));
+//! assert!(!accelerometer.wakeup);
+//! assert!(service.sensor(0x14).unwrap().wakeup);
+//!
+//! let connection = &service.connections[0];
+//! assert_eq!(connection.package, "com.example.app.MotionService");
+//! assert_eq!(connection.uid, Some(10123));
+//! let sensor = &connection.sensors[0];
+//! assert_eq!(sensor.handle, 0xb);
+//! assert_eq!(sensor.sampling_period, Some(Duration::from_micros(5000)));
+//! assert_eq!(sensor.rate_hz(), Some(200.0));
+//!
+//! assert_eq!(service.registrations.len(), 3);
+//! assert_eq!(service.connections_using(0xb).count(), 1);
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, KeyValues},
+    typed, DumpParse,
+};
+
+const SENSOR_LIST: &str = "Sensor List:";
+const CONNECTION: &str = "Connection Number:";
+const REGISTRATIONS: &str = "Previous Registrations:";
+const UID: &str = "| uid ";
+const STATUS: &str = " | status: ";
+
+/// `Sensor.REPORTING_MODE_*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReportingMode {
+    Continuous,
+    OnChange,
+    OneShot,
+    SpecialTrigger,
+    /// A value this crate doesn't know
+    Other(String),
+}
+
+impl From<&str> for ReportingMode {
+    fn from(name: &str) -> Self {
+        match name {
+            "continuous" => Self::Continuous,
+            "on-change" => Self::OnChange,
+            "one-shot" => Self::OneShot,
+            "special-trigger" => Self::SpecialTrigger,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A sensor of the device
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sensor {
+    pub handle: u32,
+    pub name: String,
+    pub vendor: String,
+    pub version: Option<i64>,
+    /// As in `android.sensor.accelerometer`
+    pub type_name: String,
+    /// `Sensor.TYPE_*`
+    pub sensor_type: i64,
+    pub reporting_mode: Option<ReportingMode>,
+    /// Rates of continuous sensors
+    pub min_rate_hz: Option<f64>,
+    pub max_rate_hz: Option<f64>,
+    /// Whether the sensor wakes the device up to deliver events
+    pub wakeup: bool,
+}
+
+/// A sensor enabled by a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionSensor {
+    pub handle: u32,
+    pub name: String,
+    /// Whether events are delivered, `false` for sensors paused for a flush or an idle uid
+    pub active: bool,
+    /// Requested in the latest activation
+    pub sampling_period: Option<Duration>,
+    pub batching_period: Option<Duration>,
+}
+
+impl ConnectionSensor {
+    /// Requested rate, from the sampling period.
+    pub fn rate_hz(&self) -> Option<f64> {
+        let period = self.sampling_period?.as_secs_f64();
+        (period > 0.0).then(|| 1.0 / period)
+    }
+}
+
+/// A client receiving sensor events
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensorConnection {
+    /// Package, or package and class, of the client
+    pub package: String,
+    pub uid: Option<u32>,
+    pub sensors: Vec<ConnectionSensor>,
+}
+
+/// An activation or deactivation of a sensor
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Registration {
+    /// As printed, in the local time of the device
+    pub time: String,
+    pub activated: bool,
+    pub handle: u32,
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    pub package: String,
+    pub sampling_period: Option<Duration>,
+    pub batching_period: Option<Duration>,
+}
+
+/// Output of `dumpsys sensorservice`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensorService {
+    pub sensors: Vec<Sensor>,
+    pub connections: Vec<SensorConnection>,
+    /// Newest first
+    pub registrations: Vec<Registration>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    Sensors,
+    Connections,
+    Registrations,
+}
+
+impl SensorService {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut service = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if trimmed == SENSOR_LIST {
+                section = Section::Sensors;
+                continue;
+            }
+            if trimmed == REGISTRATIONS {
+                section = Section::Registrations;
+                continue;
+            }
+            if trimmed.starts_with(CONNECTION) {
+                section = Section::Connections;
+                service.connections.push(SensorConnection {
+                    package: String::new(),
+                    uid: None,
+                    sensors: Vec::new(),
+                });
+                continue;
+            }
+            let indented = line.starts_with(char::is_whitespace);
+
+            match section {
+                Section::Other => {}
+                Section::Sensors if indented => {
+                    if let Some(sensor) = service.sensors.last_mut() {
+                        sensor.modes(trimmed);
+                    }
+                }
+                Section::Sensors => match sensor(trimmed) {
+                    Some(sensor) => service.sensors.push(sensor?),
+                    None => section = Section::Other,
+                },
+                Section::Connections if indented => {
+                    let Some(connection) = service.connections.last_mut() else {
+                        continue;
+                    };
+                    if let Some((package, rest)) = trimmed.split_once(UID) {
+                        connection.package =
+                            package.split(" | ").next().unwrap_or(package).to_owned();
+                        connection.uid = rest
+                            .split_whitespace()
+                            .next()
+                            .and_then(|uid| uid.parse().ok());
+                    } else if let Some(sensor) = connection_sensor(trimmed) {
+                        connection.sensors.push(sensor);
+                    }
+                }
+                Section::Connections => section = Section::Other,
+                Section::Registrations => match registration(trimmed) {
+                    Some(registration) => service.registrations.push(registration?),
+                    None => section = Section::Other,
+                },
+            }
+        }
+
+        // The history is newest first, so the first registration of a client and sensor is its
+        // current one.
+        let SensorService {
+            connections,
+            registrations,
+            ..
+        } = &mut service;
+        for connection in connections {
+            for sensor in &mut connection.sensors {
+                if let Some(registration) = registrations.iter().find(|registration| {
+                    registration.handle == sensor.handle
+                        && registration.package == connection.package
+                }) {
+                    sensor.sampling_period = registration.sampling_period;
+                    sensor.batching_period = registration.batching_period;
+                }
+            }
+        }
+
+        Ok(service)
+    }
+
+    pub fn sensor(&self, handle: u32) -> Option<&Sensor> {
+        self.sensors.iter().find(|sensor| sensor.handle == handle)
+    }
+
+    /// The first sensor of `Sensor.TYPE_*` `sensor_type`, usually the default one.
+    pub fn sensor_of_type(&self, sensor_type: i64) -> Option<&Sensor> {
+        self.sensors
+            .iter()
+            .find(|sensor| sensor.sensor_type == sensor_type)
+    }
+
+    /// Connections with the sensor `handle` enabled.
+    pub fn connections_using(&self, handle: u32) -> impl Iterator<Item = &SensorConnection> + '_ {
+        self.connections.iter().filter(move |connection| {
+            connection
+                .sensors
+                .iter()
+                .any(|sensor| sensor.handle == handle)
+        })
+    }
+}
+
+impl DumpParse for SensorService {
+    const SERVICE: &'static str = "sensorservice";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `sensorservice` and return the clients receiving sensor events.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for connection in dumpsys_rs::sensorservice::active_connections().unwrap() {
+///     for sensor in &connection.sensors {
+///         println!("{} uses {} at {:?} Hz", connection.package, sensor.name, sensor.rate_hz());
+///     }
+/// }
+/// # }
+/// ```
+pub fn active_connections() -> Result<Vec<SensorConnection>, DumpError> {
+    typed::dump_service::<SensorService>(Vec::<&str>::new()).map(|service| service.connections)
+}
+
+impl Sensor {
+    /// `continuous | minRate=5.00Hz | maxRate=200.00Hz | FIFO (max,reserved) = (10000, 3000) events | non-wakeUp |`
+    fn modes(&mut self, line: &str) {
+        let rate = |value: &str| value.trim().strip_suffix("Hz")?.parse().ok();
+        for part in line.split('|').map(str::trim) {
+            if let Some(min) = part.strip_prefix("minRate=") {
+                self.min_rate_hz = rate(min);
+            } else if let Some(max) = part.strip_prefix("maxRate=") {
+                self.max_rate_hz = rate(max);
+            } else if part == "wakeUp" {
+                self.wakeup = true;
+            } else if self.reporting_mode.is_none()
+                && !part.is_empty()
+                && !part.contains(['=', ' '])
+            {
+                self.reporting_mode = Some(part.into());
+            }
+        }
+    }
+}
+
+/// `0x0000000b) BMI160 Accelerometer | Bosch | ver: 1 | type: android.sensor.accelerometer(1) | ...`
+fn sensor(line: &str) -> Option<Result<Sensor, ParseError>> {
+    let (handle, rest) = line.split_once(") ")?;
+    let handle = u32::from_str_radix(handle.strip_prefix("0x")?, 16).ok()?;
+    let mut parts = rest.split('|').map(str::trim);
+    let name = parts.next()?;
+    let vendor = parts.next()?;
+    let mut version = None;
+    let mut kind = None;
+    for part in parts {
+        if let Some(ver) = part.strip_prefix("ver: ") {
+            version = ver.parse().ok();
+        } else if let Some(kind_name) = part.strip_prefix("type: ") {
+            kind = Some(kind_name.trim());
+        }
+    }
+    // `android.sensor.accelerometer(1)`
+    let kind = kind.and_then(|kind| {
+        let (name, code) = kind.strip_suffix(')')?.rsplit_once('(')?;
+        Some((name.trim(), code.parse().ok()?))
+    });
+    Some(
+        kind.map(|(type_name, sensor_type)| Sensor {
+            handle,
+            name: name.to_owned(),
+            vendor: vendor.to_owned(),
+            version,
+            type_name: type_name.to_owned(),
+            sensor_type,
+            reporting_mode: None,
+            min_rate_hz: None,
+            max_rate_hz: None,
+            wakeup: false,
+        })
+        .ok_or_else(|| invalid("type", line)),
+    )
+}
+
+/// `BMI160 Accelerometer 0x0000000b | status: active | pending flush events 0`
+fn connection_sensor(line: &str) -> Option<ConnectionSensor> {
+    let (sensor, rest) = line.split_once(STATUS)?;
+    let (name, handle) = sensor.rsplit_once(" 0x")?;
+    Some(ConnectionSensor {
+        handle: u32::from_str_radix(handle, 16).ok()?,
+        name: name.to_owned(),
+        active: rest.starts_with("active"),
+        sampling_period: None,
+        batching_period: None,
+    })
+}
+
+/// `12:00:00 + 0x0000000b pid= 4567 uid=10123 package=com.example.app samplingPeriod=5000us batchingPeriod=0us`
+fn registration(line: &str) -> Option<Result<Registration, ParseError>> {
+    let mut words = line.split_whitespace();
+    let time = words.next()?;
+    let activated = match words.next()? {
+        "+" => true,
+        "-" => false,
+        _ => return None,
+    };
+    let handle = u32::from_str_radix(words.next()?.strip_prefix("0x")?, 16).ok()?;
+    let fields = KeyValues::parse(line);
+    let period = |key| fields.get_duration(key).ok();
+    Some(fields.require("package").map(|package| Registration {
+        time: time.to_owned(),
+        activated,
+        handle,
+        pid: fields.get_parsed("pid").ok(),
+        uid: fields.get_parsed("uid").ok(),
+        package: package.to_owned(),
+        sampling_period: period("samplingPeriod"),
+        batching_period: period("batchingPeriod"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys sensorservice` of Android 14, cut down to a few sensors and one client
+    const DUMP: &str = "Captured at: 09:12:44.123
+Sensor Device:
+Total 3 h/w sensors, 3 running 0 disabled clients:
+0x0000000b) active-count = 1; sampling_period(ms) = {5.0}, selected = 5.00 ms; batching_period(ms) = {0.0}, selected = 0.00 ms
+Sensor List:
+0x0000000b) BMI160 Accelerometer      | Bosch           | ver: 1 | type: android.sensor.accelerometer(1) | perm: n/a | flags: 0x00000000
+\tcontinuous | minRate=5.00Hz | maxRate=200.00Hz | FIFO (max,reserved) = (10000, 3000) events | non-wakeUp |
+0x00000012) TMD4903 Light             | ams AG          | ver: 1 | type: android.sensor.light(5) | perm: n/a | flags: 0x00000002
+\ton-change | maxRate=10.00Hz | FIFO (max,reserved) = (0, 0) events | non-wakeUp |
+0x00000014) Significant Motion        | Google          | ver: 1 | type: android.sensor.significant_motion(17) | perm: n/a | flags: 0x00000005
+\tone-shot | FIFO (max,reserved) = (0, 0) events | wakeUp |
+Fusion States:
+9-axis fusion disabled (0 clients), gyro-rate= 200.00Hz, q=< 0, 0, 0, 0 > (0), b=< 0, 0, 0 >
+Recent Sensor events:
+TMD4903 Light: last 2 events
+\t 1 (ts=872112.345, wall=09:12:40.000) 120.00,
+Active sensors:
+BMI160 Accelerometer (handle=0x0000000b, connections=1)
+Socket Buffer size = 39 events
+WakeLock Status: not held
+Mode : NORMAL
+Sensor Privacy: disabled
+2 active connections
+Connection Number: 0
+\tOperating Mode: NORMAL
+\t com.example.app.MotionService | WakeLockRefCount 0 | uid 10123 | cache size 0 | max cache size 0
+\t BMI160 Accelerometer 0x0000000b | status: active | pending flush events 0
+\t events recvd: 5230 | sent 5230 | cache 0 | dropped 0 | total_acks_needed 0 | total_acks_recvd 0
+Connection Number: 1
+\tOperating Mode: NORMAL
+\t com.android.systemui.doze.DozeSensors | WakeLockRefCount 0 | uid 10056 | cache size 0 | max cache size 0
+\t Significant Motion 0x00000014 | status: inactive | pending flush events 1
+0 direct connections
+Previous Registrations:
+09:12:00 + 0x0000000b pid= 4567 uid=10123 package=com.example.app.MotionService samplingPeriod=5000us batchingPeriod=0us
+09:11:00 - 0x0000000b pid= 4567 uid=10123 package=com.example.app.MotionService
+09:10:00 + 0x0000000b pid= 4567 uid=10123 package=com.example.app.MotionService samplingPeriod=20000us batchingPeriod=100000us
+09:00:00 + 0x00000014 pid= 2345 uid=10056 package=com.android.systemui.doze.DozeSensors samplingPeriod=0us batchingPeriod=0us
+";
+
+    #[test]
+    fn parses_the_sensors() {
+        let service = SensorService::parse(DUMP).unwrap();
+        assert_eq!(service.sensors.len(), 3);
+        assert_eq!(
+            service.sensors[0],
+            Sensor {
+                handle: 0xb,
+                name: "BMI160 Accelerometer".to_owned(),
+                vendor: "Bosch".to_owned(),
+                version: Some(1),
+                type_name: "android.sensor.accelerometer".to_owned(),
+                sensor_type: 1,
+                reporting_mode: Some(ReportingMode::Continuous),
+                min_rate_hz: Some(5.0),
+                max_rate_hz: Some(200.0),
+                wakeup: false,
+            }
+        );
+        let light = service.sensor_of_type(5).unwrap();
+        assert_eq!(light.reporting_mode, Some(ReportingMode::OnChange));
+        assert_eq!(light.min_rate_hz, None);
+        assert_eq!(light.max_rate_hz, Some(10.0));
+        let motion = service.sensor(0x14).unwrap();
+        assert_eq!(motion.reporting_mode, Some(ReportingMode::OneShot));
+        assert!(motion.wakeup);
+        assert_eq!(service.sensor(0x1), None);
+    }
+
+    #[test]
+    fn parses_the_connections() {
+        let service = SensorService::parse(DUMP).unwrap();
+        assert_eq!(service.connections.len(), 2);
+        assert_eq!(
+            service.connections[0],
+            SensorConnection {
+                package: "com.example.app.MotionService".to_owned(),
+                uid: Some(10123),
+                sensors: vec![ConnectionSensor {
+                    handle: 0xb,
+                    name: "BMI160 Accelerometer".to_owned(),
+                    active: true,
+                    sampling_period: Some(Duration::from_millis(5)),
+                    batching_period: Some(Duration::ZERO),
+                }],
+            }
+        );
+        let doze = &service.connections[1].sensors[0];
+        assert!(!doze.active);
+        // One-shot sensors have no rate
+        assert_eq!(doze.rate_hz(), None);
+        assert_eq!(service.connections_using(0x14).count(), 1);
+        assert_eq!(service.connections_using(0x12).count(), 0);
+    }
+
+    #[test]
+    fn parses_the_registrations() {
+        let service = SensorService::parse(DUMP).unwrap();
+        assert_eq!(service.registrations.len(), 4);
+        assert_eq!(
+            service.registrations[2],
+            Registration {
+                time: "09:10:00".to_owned(),
+                activated: true,
+                handle: 0xb,
+                pid: Some(4567),
+                uid: Some(10123),
+                package: "com.example.app.MotionService".to_owned(),
+                sampling_period: Some(Duration::from_millis(20)),
+                batching_period: Some(Duration::from_millis(100)),
+            }
+        );
+        let deactivated = &service.registrations[1];
+        assert!(!deactivated.activated);
+        assert_eq!(deactivated.sampling_period, None);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            SensorService::parse(&DUMP.replace('\n', "\r\n")),
+            SensorService::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(SensorService::parse("").unwrap(), SensorService::default());
+    }
+
+    #[test]
+    fn unknown_and_missing_fields() {
+        let text = "Sensor List:
+0x00000030) Hinge Angle | Vendor | type: android.sensor.hinge_angle(36) | perm: n/a
+\tsome-future-mode | non-wakeUp |
+Connection Number: 0
+\t BMI160 Accelerometer 0x0000000b | status: active | pending flush events 0
+";
+        let service = SensorService::parse(text).unwrap();
+        let hinge = &service.sensors[0];
+        assert_eq!(hinge.version, None);
+        assert_eq!(
+            hinge.reporting_mode,
+            Some(ReportingMode::Other("some-future-mode".to_owned()))
+        );
+        let connection = &service.connections[0];
+        assert_eq!(connection.package, "");
+        assert_eq!(connection.uid, None);
+        // Without a registration there is no rate
+        assert_eq!(connection.sensors[0].sampling_period, None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let line = "0x0000000b) BMI160 Accelerometer | Bosch | ver: 1 | type: android.sensor.accel";
+        assert_eq!(
+            SensorService::parse(&format!("{SENSOR_LIST}\n{line}\n")).unwrap_err(),
+            invalid("type", line)
+        );
+        assert_eq!(
+            SensorService::parse(&format!(
+                "{REGISTRATIONS}\n09:12:00 + 0x0000000b pid= 4567 uid=10123\n"
+            ))
+            .unwrap_err(),
+            ParseError::Missing("package".to_owned())
+        );
+    }
+}
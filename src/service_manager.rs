@@ -0,0 +1,369 @@
+//! Queries to servicemanager itself instead of the services it hands out
+
+use std::{io, time::Duration, vec};
+
+use binder::{check_service, BinderFeatures, Interface, ProcessState, SpIBinder, Strong};
+
+use crate::{
+    aidl::{self, BnServiceCallback, IServiceCallback, IServiceManager},
+    compat::Sdk,
+    error::{DumpContext, DumpError},
+    DumpPriority, Dumpsys,
+};
+
+/// Name servicemanager registers itself under
+const SERVICE_MANAGER: &str = "manager";
+/// Arguments dumpsys passes to every service when dumping all of them
+const DUMP_ALL_ARGS: [&str; 1] = ["-a"];
+
+/// A callback registered with [`on_registration`], unregistered again on drop
+pub struct Registration {
+    manager: Strong<dyn IServiceManager>,
+    callback: Strong<dyn IServiceCallback>,
+    name: String,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _ = self
+            .manager
+            .unregister_for_notifications(&self.name, &self.callback);
+    }
+}
+
+struct Callback<F>(F);
+
+impl<F> Interface for Callback<F> where F: Fn(Dumpsys) + Send + Sync + 'static {}
+
+impl<F> IServiceCallback for Callback<F>
+where
+    F: Fn(Dumpsys) + Send + Sync + 'static,
+{
+    fn on_registration(&self, name: String, binder: SpIBinder) -> binder::Result<()> {
+        (self.0)(Dumpsys::from_parts(name, binder, Default::default()));
+        Ok(())
+    }
+}
+
+/// Call `callback` whenever an instance of `name` registers with servicemanager, instead of polling for it.
+///
+/// servicemanager calls back right away if the service is already running, and again every time it
+/// registers anew, e.g. after a restart. Callbacks arrive on binder threads, which are started for the
+/// process if needed.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::mpsc;
+///
+/// use dumpsys_rs::service_manager;
+///
+/// # fn foo() -> Option<()> {
+/// let (tx, rx) = mpsc::sync_channel(1);
+/// let registration = service_manager::on_registration("SurfaceFlinger", move |dumpsys| {
+///     let _ = tx.try_send(dumpsys);
+/// })
+/// .ok()?;
+/// let surfaceflinger = rx.recv().ok()?;
+/// drop(registration);
+/// # Some(())
+/// # }
+/// ```
+pub fn on_registration<S, F>(name: S, callback: F) -> Result<Registration, DumpError>
+where
+    S: AsRef<str>,
+    F: Fn(Dumpsys) + Send + Sync + 'static,
+{
+    let name = name.as_ref();
+    let manager = manager()?;
+    let callback = BnServiceCallback::new_binder(Callback(callback), BinderFeatures::default());
+
+    ProcessState::start_thread_pool();
+    manager
+        .register_for_notifications(name, &callback)
+        .map_err(|status| DumpError::from(status).with_context(name, &[]))?;
+
+    Ok(Registration {
+        manager,
+        callback,
+        name: name.to_owned(),
+    })
+}
+
+/// Names of all registered services in sorted order, like `dumpsys -l`.
+pub fn list_services() -> Result<Vec<String>, DumpError> {
+    list(aidl::DUMP_FLAG_PRIORITY_ALL)
+}
+
+/// Names of the services registered with `priority` in sorted order, like `dumpsys -l --priority CRITICAL`.
+pub fn list_services_by_priority(priority: DumpPriority) -> Result<Vec<String>, DumpError> {
+    list(priority.flag())
+}
+
+/// Names of the services that can dump as protobuf in sorted order, like `dumpsys --proto -l`.
+///
+/// These accept `--proto` and write a serialized proto instead of text.
+pub fn list_proto_services() -> Result<Vec<String>, DumpError> {
+    let proto = list(aidl::DUMP_FLAG_PROTO)?;
+    let mut services = list(aidl::DUMP_FLAG_PRIORITY_ALL)?;
+    services.retain(|service| proto.binary_search(service).is_ok());
+    Ok(services)
+}
+
+/// Registered services with names close to `name`, closest first, for "did you mean" hints after
+/// [`Dumpsys::new`] found no service called `name`. See [`closest`].
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{service_manager, Dumpsys};
+///
+/// let name = "surfaceflinger";
+/// if Dumpsys::try_new(name).is_none() {
+///     match service_manager::suggestions(name).unwrap().first() {
+///         Some(service) => eprintln!("no service {name}, did you mean {service}?"),
+///         None => eprintln!("no service {name}"),
+///     }
+/// }
+/// ```
+pub fn suggestions(name: &str) -> Result<Vec<String>, DumpError> {
+    Ok(closest(name, list_services()?))
+}
+
+/// The names out of `services` close to `name`, closest first.
+///
+/// Names match ignoring case if `name` starts them, e.g. `media` for `media.audio_flinger`, or they're
+/// a few edits from it: one per three characters of `name`, at least one.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::service_manager::closest;
+///
+/// let services = ["SurfaceFlinger", "media.audio_flinger", "media.camera", "meminfo", "power"];
+/// assert_eq!(closest("surfaceflinger", services), ["SurfaceFlinger"]);
+/// assert_eq!(closest("memifno", services), ["meminfo"]);
+/// assert_eq!(closest("media", services), ["media.camera", "media.audio_flinger"]);
+/// assert!(closest("battery", services).is_empty());
+/// ```
+pub fn closest<S: AsRef<str>>(name: &str, services: impl IntoIterator<Item = S>) -> Vec<S> {
+    let name = name.to_lowercase();
+    let max_edits = (name.chars().count() / 3).max(1);
+
+    let mut matches: Vec<_> = services
+        .into_iter()
+        .filter_map(|service| {
+            let lower = service.as_ref().to_lowercase();
+            let prefix = !name.is_empty() && lower.starts_with(&name);
+            let edits = edit_distance(&name, &lower);
+            (prefix || edits <= max_edits).then_some(((!prefix, edits), service))
+        })
+        .collect();
+    matches.sort_by(|(a, a_name), (b, b_name)| {
+        a.cmp(b).then_with(|| a_name.as_ref().cmp(b_name.as_ref()))
+    });
+    matches.into_iter().map(|(_, service)| service).collect()
+}
+
+/// Levenshtein distance between `a` and `b`, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances from a prefix of `a` to every prefix of `b`, one row per character of `a`.
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(a != b);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Dumps of every registered service, returned by [`dump_all`]
+///
+/// Each service is looked up and dumped only once the iterator reaches it.
+pub struct DumpAll {
+    services: vec::IntoIter<String>,
+    timeout: Duration,
+}
+
+impl Iterator for DumpAll {
+    type Item = (String, Result<String, DumpError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let service = self.services.next()?;
+        let result = match Dumpsys::try_new(&service) {
+            Some(dumpsys) => dumpsys.dump_with_timeout(DUMP_ALL_ARGS, self.timeout),
+            None => Err(DumpError::ServiceNotFound {
+                context: DumpContext {
+                    service: service.clone(),
+                    args: DUMP_ALL_ARGS.map(str::to_owned).to_vec(),
+                },
+            }),
+        };
+        Some((service, result))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.services.size_hint()
+    }
+}
+
+/// Dump every registered service except those in `skip`, like running a bare `dumpsys --skip ...`.
+///
+/// Services are dumped in sorted order with `-a`, each given up to `timeout`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::service_manager;
+///
+/// for (service, result) in service_manager::dump_all(Duration::from_secs(10), ["meminfo"]).unwrap() {
+///     match result {
+///         Ok(output) => println!("{service}: {} bytes", output.len()),
+///         Err(err) => eprintln!("{err}"),
+///     }
+/// }
+/// ```
+pub fn dump_all<S>(
+    timeout: Duration,
+    skip: impl IntoIterator<Item = S>,
+) -> Result<DumpAll, DumpError>
+where
+    S: AsRef<str>,
+{
+    let skip: Vec<S> = skip.into_iter().collect();
+    let mut services = list_services()?;
+    services.retain(|service| !skip.iter().any(|skip| skip.as_ref() == service));
+
+    Ok(DumpAll {
+        services: services.into_iter(),
+        timeout,
+    })
+}
+
+impl Dumpsys {
+    /// Whether `name`, e.g. `android.hardware.thermal.IThermal/default`, is declared in the VINTF manifest.
+    ///
+    /// Declared AIDL HAL services may start lazily, so this is true even when they aren't registered yet.
+    pub fn is_declared(name: &str) -> Result<bool, DumpError> {
+        binder::is_declared(name).map_err(|status| DumpError::from(status).with_context(name, &[]))
+    }
+
+    /// Instance names declared in the VINTF manifest for `interface`, e.g. `default` for
+    /// `android.hardware.thermal.IThermal`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// const THERMAL: &str = "android.hardware.thermal.IThermal";
+    /// for instance in Dumpsys::declared_instances(THERMAL).unwrap() {
+    ///     let hal = Dumpsys::new(format!("{THERMAL}/{instance}"));
+    /// }
+    /// ```
+    pub fn declared_instances(interface: &str) -> Result<Vec<String>, DumpError> {
+        binder::get_declared_instances(interface)
+            .map_err(|status| DumpError::from(status).with_context(interface, &[]))
+    }
+
+    /// Id of the process hosting the service, like `dumpsys --pid`, e.g. to correlate dumps with `/proc` stats.
+    ///
+    /// Needs Android 12 to 15, where the transaction asking servicemanager is known. Off-device,
+    /// servicemanager is taken to answer as on Android 15.
+    pub fn pid(&self) -> Result<u32, DumpError> {
+        let sdk = Sdk::current().unwrap_or(Sdk::VANILLA_ICE_CREAM);
+        let Some(code) = aidl::get_service_debug_info_code(sdk) else {
+            let err = io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "pid needs {} to {}, not {sdk}",
+                    Sdk::S,
+                    Sdk::VANILLA_ICE_CREAM
+                ),
+            );
+            return Err(self.error(err));
+        };
+        let infos = manager()?
+            .get_service_debug_info(code)
+            .map_err(|status| self.error(status))?;
+        infos
+            .into_iter()
+            .find(|info| info.name == *self.service_name)
+            .and_then(|info| u32::try_from(info.debug_pid).ok())
+            .ok_or_else(|| self.error(binder::StatusCode::NAME_NOT_FOUND))
+    }
+
+    /// Resolve every registered service whose name matches the glob `pattern`, e.g. `media.*`.
+    ///
+    /// `*` matches any run of characters and `?` any single one. Services that go away between listing
+    /// and lookup are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// for media in Dumpsys::find_matching("media.*").unwrap() {
+    ///     println!("{}", media.dump(&["-a"]).unwrap());
+    /// }
+    /// ```
+    pub fn find_matching(pattern: &str) -> Result<Vec<Self>, DumpError> {
+        Ok(list_services()?
+            .into_iter()
+            .filter(|service| glob_match(pattern.as_bytes(), service.as_bytes()))
+            .filter_map(Self::try_new)
+            .collect())
+    }
+}
+
+/// Match `name` against a pattern of literal bytes, `*` and `?`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` if the rest fails to match.
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+fn list(dump_priority: i32) -> Result<Vec<String>, DumpError> {
+    let mut services = manager()?
+        .list_services(dump_priority)
+        .map_err(|status| DumpError::from(status).with_context(SERVICE_MANAGER, &[]))?;
+    services.sort_unstable();
+    Ok(services)
+}
+
+fn manager() -> Result<Strong<dyn IServiceManager>, DumpError> {
+    let fail = |status| DumpError::from(status).with_context(SERVICE_MANAGER, &[]);
+    check_service(SERVICE_MANAGER)
+        .ok_or(binder::StatusCode::NAME_NOT_FOUND)
+        .and_then(SpIBinder::into_interface)
+        .map_err(fail)
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::Dumpsys;
+
+/// Names of commonly dumped system services, so typos are caught at compile time
+///
+/// Works anywhere a service name is taken, e.g. `Dumpsys::new(ServiceName::MemInfo)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServiceName {
+    /// `activity`
+    Activity,
+    /// `alarm`
+    Alarm,
+    /// `appops`
+    AppOps,
+    /// `audio`
+    Audio,
+    /// `battery`
+    Battery,
+    /// `batterystats`
+    BatteryStats,
+    /// `connectivity`
+    Connectivity,
+    /// `cpuinfo`
+    CpuInfo,
+    /// `diskstats`
+    DiskStats,
+    /// `display`
+    Display,
+    /// `dropbox`
+    DropBox,
+    /// `gfxinfo`
+    GfxInfo,
+    /// `input`
+    Input,
+    /// `jobscheduler`
+    JobScheduler,
+    /// `location`
+    Location,
+    /// `media.audio_flinger`
+    MediaAudioFlinger,
+    /// `media.camera`
+    MediaCamera,
+    /// `meminfo`
+    MemInfo,
+    /// `netstats`
+    NetStats,
+    /// `notification`
+    Notification,
+    /// `package`
+    Package,
+    /// `power`
+    Power,
+    /// `procstats`
+    ProcStats,
+    /// `sensorservice`
+    SensorService,
+    /// `SurfaceFlinger`
+    SurfaceFlinger,
+    /// `telephony.registry`
+    TelephonyRegistry,
+    /// `thermalservice`
+    ThermalService,
+    /// `usagestats`
+    UsageStats,
+    /// `wifi`
+    Wifi,
+    /// `window`
+    Window,
+}
+
+impl ServiceName {
+    /// The name the service is registered under.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Activity => "activity",
+            Self::Alarm => "alarm",
+            Self::AppOps => "appops",
+            Self::Audio => "audio",
+            Self::Battery => "battery",
+            Self::BatteryStats => "batterystats",
+            Self::Connectivity => "connectivity",
+            Self::CpuInfo => "cpuinfo",
+            Self::DiskStats => "diskstats",
+            Self::Display => "display",
+            Self::DropBox => "dropbox",
+            Self::GfxInfo => "gfxinfo",
+            Self::Input => "input",
+            Self::JobScheduler => "jobscheduler",
+            Self::Location => "location",
+            Self::MediaAudioFlinger => "media.audio_flinger",
+            Self::MediaCamera => "media.camera",
+            Self::MemInfo => "meminfo",
+            Self::NetStats => "netstats",
+            Self::Notification => "notification",
+            Self::Package => "package",
+            Self::Power => "power",
+            Self::ProcStats => "procstats",
+            Self::SensorService => "sensorservice",
+            Self::SurfaceFlinger => "SurfaceFlinger",
+            Self::TelephonyRegistry => "telephony.registry",
+            Self::ThermalService => "thermalservice",
+            Self::UsageStats => "usagestats",
+            Self::Wifi => "wifi",
+            Self::Window => "window",
+        }
+    }
+}
+
+impl AsRef<str> for ServiceName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for ServiceName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Dumpsys {
+    /// Like [`Dumpsys::new`] for a well-known service.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::{Dumpsys, ServiceName};
+    ///
+    /// let surfaceflinger = Dumpsys::new_known(ServiceName::SurfaceFlinger);
+    /// ```
+    pub fn new_known(service: ServiceName) -> Option<Self> {
+        Self::new(service)
+    }
+}
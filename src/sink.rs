@@ -0,0 +1,323 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use crate::Snapshot;
+
+/// Destination for captured snapshots, e.g. files, memory or a socket
+///
+/// Used by [`Sampler::with_sink`](crate::Sampler::with_sink) and
+/// [`DumpBatch::run_into`](crate::DumpBatch::run_into), so output can go anywhere without changes to
+/// the crate.
+///
+/// # Example
+///
+/// ```
+/// use std::{io, net::TcpStream};
+///
+/// use dumpsys_rs::{DumpSink, Snapshot};
+///
+/// struct Socket(TcpStream);
+///
+/// impl DumpSink for Socket {
+///     fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+///         use io::Write;
+///         writeln!(self.0, "{}\n{}", snapshot.service, snapshot.output)
+///     }
+/// }
+/// ```
+pub trait DumpSink {
+    /// Store one snapshot.
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()>;
+
+    /// Push out anything buffered, called when a producer is done.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Start a new segment of output, e.g. a new file. Does nothing by default.
+    fn rotate(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: DumpSink + ?Sized> DumpSink for &mut S {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        (**self).write_snapshot(snapshot)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        (**self).rotate()
+    }
+}
+
+impl<S: DumpSink + ?Sized> DumpSink for Box<S> {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        (**self).write_snapshot(snapshot)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        (**self).rotate()
+    }
+}
+
+/// Keeps snapshots in memory; clones share the same storage, so one can be handed to a [`Sampler`](crate::Sampler)
+/// and another read from
+///
+/// Rotating drops the snapshots stored so far.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{Dumpsys, MemorySink, Sampler};
+///
+/// # fn foo() -> Option<()> {
+/// let sink = MemorySink::new();
+/// let sampler = Sampler::with_sink(
+///     Dumpsys::new("SurfaceFlinger")?,
+///     &["--latency"],
+///     Duration::from_secs(1),
+///     sink.clone(),
+/// );
+/// std::thread::sleep(Duration::from_secs(10));
+/// sampler.stop();
+/// println!("{} samples", sink.take().len());
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    snapshots: Arc<Mutex<Vec<Snapshot>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies of the snapshots stored so far, oldest first.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.snapshots.lock().unwrap().clone()
+    }
+
+    /// Remove and return the snapshots stored so far, oldest first.
+    pub fn take(&self) -> Vec<Snapshot> {
+        mem::take(&mut *self.snapshots.lock().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl DumpSink for MemorySink {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        self.snapshots.lock().unwrap().push(snapshot.clone());
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.snapshots.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// Writes snapshots to size and time rotated log files, deleting the oldest beyond a retention limit
+///
+/// Files are named `dump-<name>-0001.log`, `dump-<name>-0002.log`, ... in `dir`, continuing after the
+/// highest number already there. Each snapshot starts with a header line naming the capture time in
+/// milliseconds since the epoch, the dumped service and its arguments.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{DumpSink, Dumpsys, RotatingFileSink};
+///
+/// # fn foo() -> Option<()> {
+/// let mut sink = RotatingFileSink::new("/data/local/tmp/dumps", "surfaceflinger")
+///     .max_file_size(4 * 1024 * 1024)
+///     .max_age(Duration::from_secs(3600))
+///     .max_files(8);
+/// let snapshot = Dumpsys::new("SurfaceFlinger")?.dump_snapshot(&["--latency"]).unwrap();
+/// sink.write_snapshot(&snapshot).unwrap();
+/// # Some(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RotatingFileSink {
+    dir: PathBuf,
+    name: String,
+    max_file_size: Option<u64>,
+    max_age: Option<Duration>,
+    max_files: Option<usize>,
+    current: Option<Current>,
+}
+
+#[derive(Debug)]
+struct Current {
+    file: BufWriter<File>,
+    index: u32,
+    size: u64,
+    opened: Instant,
+}
+
+impl RotatingFileSink {
+    /// Log to `dump-<name>-*.log` files in `dir`, created on the first write. Files never rotate by default.
+    pub fn new(dir: impl AsRef<Path>, name: impl AsRef<str>) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+            name: name.as_ref().to_lowercase(),
+            max_file_size: None,
+            max_age: None,
+            max_files: None,
+            current: None,
+        }
+    }
+
+    /// Start a new file before one would grow past `bytes`.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Start a new file once the current one has been written to for `age`.
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    /// Keep at most `files` log files, deleting the oldest on rotation, at least one.
+    pub fn max_files(mut self, files: usize) -> Self {
+        self.max_files = Some(files.max(1));
+        self
+    }
+
+    fn path(&self, index: u32) -> PathBuf {
+        self.dir.join(format!("dump-{}-{index:04}.log", self.name))
+    }
+
+    /// Numbers of the log files in the directory, in ascending order.
+    fn existing(&self) -> io::Result<Vec<u32>> {
+        let prefix = format!("dump-{}-", self.name);
+        let mut indices: Vec<u32> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                name.strip_prefix(&prefix)?
+                    .strip_suffix(".log")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        indices.sort_unstable();
+        Ok(indices)
+    }
+}
+
+impl DumpSink for RotatingFileSink {
+    /// Append `snapshot` to the current file, rotating first if it's full or too old.
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let header = header(snapshot);
+        let len = (header.len() + snapshot.output.len() + 1) as u64;
+        let due = self.current.as_ref().is_some_and(|current| {
+            let full = self
+                .max_file_size
+                .is_some_and(|max| current.size > 0 && current.size + len > max);
+            let old = self
+                .max_age
+                .is_some_and(|max| current.opened.elapsed() >= max);
+            full || old
+        });
+        if due || self.current.is_none() {
+            self.rotate()?;
+        }
+
+        let current = self.current.as_mut().expect("rotate opened a file");
+        current.file.write_all(header.as_bytes())?;
+        current.file.write_all(snapshot.output.as_bytes())?;
+        current.file.write_all(b"\n")?;
+        current.size += len;
+        Ok(())
+    }
+
+    /// Write buffered output to the current file.
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some(current) => current.file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Close the current file and start the next one, applying the retention limit.
+    fn rotate(&mut self) -> io::Result<()> {
+        let index = match self.current.take() {
+            Some(mut current) => {
+                current.file.flush()?;
+                current.index + 1
+            }
+            None => {
+                fs::create_dir_all(&self.dir)?;
+                self.existing()?.last().map_or(1, |index| index + 1)
+            }
+        };
+
+        let file = File::create(self.path(index))?;
+        self.current = Some(Current {
+            file: BufWriter::new(file),
+            index,
+            size: 0,
+            opened: Instant::now(),
+        });
+
+        if let Some(max_files) = self.max_files {
+            let existing = self.existing()?;
+            let excess = existing.len().saturating_sub(max_files);
+            for index in &existing[..excess] {
+                fs::remove_file(self.path(*index))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RotatingFileSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Line introducing a snapshot in log output, with the capture time in milliseconds since the epoch.
+pub(crate) fn header(snapshot: &Snapshot) -> String {
+    let captured_at = snapshot
+        .captured_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut header = format!("=== {captured_at} dumpsys {}", snapshot.service);
+    for arg in &snapshot.args {
+        header.push(' ');
+        header.push_str(arg);
+    }
+    header.push_str(&format!(" ({:?}) ===\n", snapshot.duration));
+    header
+}
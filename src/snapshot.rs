@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{error::DumpError, owned_args, Dumpsys};
+
+/// Dump output together with when and how it was captured, returned by [`Dumpsys::dump_snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub service: String,
+    pub args: Vec<String>,
+    pub output: String,
+    /// Wall clock time the dump started
+    pub captured_at: SystemTime,
+    /// How long the dump took
+    pub duration: Duration,
+}
+
+impl Snapshot {
+    /// Size of the output in bytes.
+    pub fn len(&self) -> usize {
+        self.output.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.output.is_empty()
+    }
+}
+
+impl Dumpsys {
+    /// Like [`Dumpsys::dump`], recording capture time and duration for time series and logs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let snapshot = Dumpsys::new("meminfo")?.dump_snapshot(&["-a"]).unwrap();
+    /// println!("{} bytes in {:?}", snapshot.len(), snapshot.duration);
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_snapshot(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Snapshot, DumpError> {
+        let args = owned_args(args);
+        let captured_at = SystemTime::now();
+        let start = Instant::now();
+        let output = self.dump(&args)?;
+
+        Ok(Snapshot {
+            service: self.service_name.to_string(),
+            args,
+            output,
+            captured_at,
+            duration: start.elapsed(),
+        })
+    }
+}
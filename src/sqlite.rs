@@ -0,0 +1,246 @@
+//! Durable history of snapshots and metrics in a local SQLite database
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::{Duration, SystemTime};
+//!
+//! use dumpsys_rs::{parse::kv::KeyValues, sqlite::SqliteStore, Dumpsys, Sampler};
+//!
+//! # fn foo() -> Option<()> {
+//! let store = SqliteStore::open("/data/local/tmp/dumps.db")
+//!     .unwrap()
+//!     .metric("brightness", |output| {
+//!         KeyValues::parse(output).get_float("mScreenBrightnessSetting").ok()
+//!     });
+//! let sampler = Sampler::with_sink(Dumpsys::new("power")?, Vec::<&str>::new(), Duration::from_secs(60), store);
+//!
+//! // After a restart
+//! let store = SqliteStore::open("/data/local/tmp/dumps.db").unwrap();
+//! let day_ago = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+//! for (time, brightness) in store.metric_values("power", "brightness", day_ago..SystemTime::now()).unwrap() {
+//!     println!("{time:?}: {brightness}");
+//! }
+//! # Some(())
+//! # }
+//! ```
+
+use std::{
+    fmt, io,
+    ops::Range,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, Row};
+
+use crate::{DumpSink, Snapshot};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY,
+        service TEXT NOT NULL,
+        args TEXT NOT NULL,
+        captured_at_ms INTEGER NOT NULL,
+        duration_us INTEGER NOT NULL,
+        output TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS snapshots_by_time ON snapshots (service, captured_at_ms);
+    CREATE TABLE IF NOT EXISTS metrics (
+        snapshot_id INTEGER NOT NULL REFERENCES snapshots (id),
+        name TEXT NOT NULL,
+        value REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS metrics_by_snapshot ON metrics (snapshot_id, name);
+";
+const SNAPSHOT_COLUMNS: &str = "service, args, captured_at_ms, duration_us, output";
+/// Separates the arguments in the `args` column
+const ARG_SEPARATOR: char = '\u{1f}';
+
+type Extract = Box<dyn Fn(&str) -> Option<f64> + Send>;
+
+/// Snapshots and the metrics extracted from them, stored in SQLite
+///
+/// As a [`DumpSink`] it stores every snapshot along with the metrics added with [`SqliteStore::metric`].
+pub struct SqliteStore {
+    conn: Connection,
+    metrics: Vec<(String, Extract)>,
+}
+
+impl fmt::Debug for SqliteStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metrics: Vec<_> = self.metrics.iter().map(|(name, _)| name).collect();
+        f.debug_struct("SqliteStore")
+            .field("metrics", &metrics)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SqliteStore {
+    /// Open or create the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// A database that lives only as long as the store.
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn,
+            metrics: Vec::new(),
+        })
+    }
+
+    /// Record the metric `name`, found by `extract` in the output of each snapshot written as a sink.
+    pub fn metric<F>(mut self, name: impl AsRef<str>, extract: F) -> Self
+    where
+        F: Fn(&str) -> Option<f64> + Send + 'static,
+    {
+        self.metrics
+            .push((name.as_ref().to_owned(), Box::new(extract)));
+        self
+    }
+
+    /// Store `snapshot` with `metrics` of it in one transaction, returning its row id.
+    pub fn insert(
+        &mut self,
+        snapshot: &Snapshot,
+        metrics: &[(&str, f64)],
+    ) -> rusqlite::Result<i64> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            &format!("INSERT INTO snapshots ({SNAPSHOT_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5)"),
+            params![
+                snapshot.service,
+                snapshot.args.join(&ARG_SEPARATOR.to_string()),
+                millis(snapshot.captured_at),
+                i64::try_from(snapshot.duration.as_micros()).unwrap_or(i64::MAX),
+                snapshot.output,
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for (name, value) in metrics {
+            tx.execute(
+                "INSERT INTO metrics (snapshot_id, name, value) VALUES (?1, ?2, ?3)",
+                params![id, name, value],
+            )?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Snapshots of `service` captured within `range`, oldest first.
+    pub fn snapshots(
+        &self,
+        service: &str,
+        range: Range<SystemTime>,
+    ) -> rusqlite::Result<Vec<Snapshot>> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {SNAPSHOT_COLUMNS} FROM snapshots
+             WHERE service = ?1 AND captured_at_ms >= ?2 AND captured_at_ms < ?3
+             ORDER BY captured_at_ms"
+        ))?;
+        let rows = statement.query_map(
+            params![service, millis(range.start), millis(range.end)],
+            snapshot,
+        )?;
+        rows.collect()
+    }
+
+    /// The last snapshot of `service`.
+    pub fn latest(&self, service: &str) -> rusqlite::Result<Option<Snapshot>> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {SNAPSHOT_COLUMNS} FROM snapshots WHERE service = ?1
+             ORDER BY captured_at_ms DESC LIMIT 1"
+        ))?;
+        let mut rows = statement.query_map(params![service], snapshot)?;
+        rows.next().transpose()
+    }
+
+    /// Values of the metric `name` of `service` captured within `range`, oldest first.
+    pub fn metric_values(
+        &self,
+        service: &str,
+        name: &str,
+        range: Range<SystemTime>,
+    ) -> rusqlite::Result<Vec<(SystemTime, f64)>> {
+        let mut statement = self.conn.prepare(
+            "SELECT snapshots.captured_at_ms, metrics.value FROM metrics
+             JOIN snapshots ON snapshots.id = metrics.snapshot_id
+             WHERE snapshots.service = ?1 AND metrics.name = ?2
+                 AND snapshots.captured_at_ms >= ?3 AND snapshots.captured_at_ms < ?4
+             ORDER BY snapshots.captured_at_ms",
+        )?;
+        let rows = statement.query_map(
+            params![service, name, millis(range.start), millis(range.end)],
+            |row| Ok((time(row.get(0)?), row.get(1)?)),
+        )?;
+        rows.collect()
+    }
+
+    /// Delete everything captured before `cutoff`, returning how many snapshots were removed.
+    pub fn prune(&mut self, cutoff: SystemTime) -> rusqlite::Result<usize> {
+        let cutoff = millis(cutoff);
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM metrics WHERE snapshot_id IN
+                 (SELECT id FROM snapshots WHERE captured_at_ms < ?1)",
+            params![cutoff],
+        )?;
+        let removed = tx.execute(
+            "DELETE FROM snapshots WHERE captured_at_ms < ?1",
+            params![cutoff],
+        )?;
+        tx.commit()?;
+        Ok(removed)
+    }
+}
+
+impl DumpSink for SqliteStore {
+    fn write_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let values: Vec<_> = self
+            .metrics
+            .iter()
+            .filter_map(|(name, extract)| Some((name.clone(), extract(&snapshot.output)?)))
+            .collect();
+        let metrics: Vec<_> = values
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        self.insert(snapshot, &metrics)
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+}
+
+fn snapshot(row: &Row<'_>) -> rusqlite::Result<Snapshot> {
+    let args: String = row.get(1)?;
+    let duration: i64 = row.get(3)?;
+    Ok(Snapshot {
+        service: row.get(0)?,
+        args: if args.is_empty() {
+            Vec::new()
+        } else {
+            args.split(ARG_SEPARATOR).map(str::to_owned).collect()
+        },
+        captured_at: time(row.get(2)?),
+        duration: Duration::from_micros(duration.try_into().unwrap_or_default()),
+        output: row.get(4)?,
+    })
+}
+
+fn millis(time: SystemTime) -> i64 {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    i64::try_from(millis).unwrap_or(i64::MAX)
+}
+
+fn time(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.try_into().unwrap_or_default())
+}
@@ -0,0 +1,306 @@
+//! ANRs and crashes found in `dumpsys activity lastanr` and `dumpsys dropbox`
+//!
+//! [`from_dropbox`] reads both the entry summaries of `dumpsys dropbox` and the full entries of
+//! `dumpsys dropbox --print`; only the latter carry reasons. [`StabilityDetector`] polls both services
+//! and returns the events it hasn't returned before.
+//!
+//! Timestamps are kept as printed, in the local time of the device.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::stability::{self, EventKind};
+//!
+//! let events = stability::from_dropbox(
+//!     "Drop box contents: 2 entries
+//! Max entries: 1000
+//!
+//! ========================================
+//! 2024-01-01 12:00:00 data_app_crash (text, 1234 bytes)
+//! Process: com.example.app
+//! PID: 1234
+//! Flags: 0x38c83e44
+//! Package: com.example.app v7 (1.0)
+//!
+//! java.lang.NullPointerException: Attempt to invoke virtual method on a null object reference
+//! \tat com.example.app.MainActivity.onCreate(MainActivity.java:12)
+//!
+//! ========================================
+//! 2024-01-01 12:05:00 data_app_anr (compressed text, 5678 bytes)
+//! Process: com.example.app
+//! PID: 1234
+//! Subject: Input dispatching timed out (Waiting to send non-key event)
+//!
+//! ----- pid 1234 at 2024-01-01 12:05:00 -----",
+//! );
+//!
+//! assert_eq!(events.len(), 2);
+//! assert_eq!(events[0].kind, EventKind::Crash);
+//! assert_eq!(events[0].package.as_deref(), Some("com.example.app"));
+//! assert_eq!(events[0].pid, Some(1234));
+//! assert_eq!(events[0].reason.as_deref(), Some("java.lang.NullPointerException: Attempt to invoke virtual method on a null object reference"));
+//! assert_eq!(events[1].kind, EventKind::Anr);
+//! assert_eq!(events[1].timestamp.as_deref(), Some("2024-01-01 12:05:00"));
+//! assert_eq!(events[1].reason.as_deref(), Some("Input dispatching timed out (Waiting to send non-key event)"));
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{
+    dropbox::{self, Entry},
+    error::DumpError,
+    typed, Dumpsys,
+};
+
+const ACTIVITY: &str = "activity";
+const DROPBOX: &str = "dropbox";
+const LAST_ANR: &str = "lastanr";
+const PRINT: &str = "--print";
+
+/// What went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventKind {
+    Anr,
+    /// Uncaught Java exception
+    Crash,
+    /// Crash of native code, including tombstones
+    NativeCrash,
+}
+
+impl EventKind {
+    /// Kind of the dropbox entries with `tag`, `None` for tags unrelated to stability.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        if tag.ends_with("_anr") {
+            Some(Self::Anr)
+        } else if tag.ends_with("_native_crash") || tag.starts_with("SYSTEM_TOMBSTONE") {
+            Some(Self::NativeCrash)
+        } else if tag.ends_with("_crash") {
+            Some(Self::Crash)
+        } else {
+            None
+        }
+    }
+}
+
+/// An ANR or crash
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StabilityEvent {
+    pub kind: EventKind,
+    /// Dropbox tag such as `data_app_crash`, or `lastanr` for the ANR of `dumpsys activity`
+    pub source: String,
+    /// Process name, the package for most apps
+    pub package: Option<String>,
+    pub pid: Option<u32>,
+    /// As printed by the device
+    pub timestamp: Option<String>,
+    /// Exception, ANR subject or signal
+    pub reason: Option<String>,
+}
+
+impl StabilityEvent {
+    fn new(kind: EventKind, source: &str) -> Self {
+        Self {
+            kind,
+            source: source.to_owned(),
+            package: None,
+            pid: None,
+            timestamp: None,
+            reason: None,
+        }
+    }
+
+    /// The ANR or crash a dropbox entry records, `None` for entries with other tags.
+    ///
+    /// Only entries read with `--print` carry their text, and so a package and reason.
+    pub fn from_entry(entry: &Entry) -> Option<Self> {
+        let kind = EventKind::from_tag(&entry.tag)?;
+        let mut reader = EntryReader {
+            event: StabilityEvent {
+                timestamp: Some(entry.timestamp.clone()),
+                ..StabilityEvent::new(kind, &entry.tag)
+            },
+            in_body: false,
+        };
+        for line in entry.text.iter().flat_map(|text| text.lines()) {
+            reader.line(line.trim());
+        }
+        Some(reader.event)
+    }
+
+    /// Whether the event happened in a system app or the system server.
+    pub fn is_system(&self) -> bool {
+        self.source.starts_with("system_") || self.source.starts_with("SYSTEM_")
+    }
+}
+
+/// ANRs and crashes in the output of `dumpsys dropbox`, with or without `--print`, in order.
+///
+/// Entries with other tags, such as `data_app_strictmode`, are skipped.
+pub fn from_dropbox(text: &str) -> Vec<StabilityEvent> {
+    dropbox::entries(text)
+        .iter()
+        .filter_map(StabilityEvent::from_entry)
+        .collect()
+}
+
+/// The last ANR in the output of `dumpsys activity lastanr`, or of a full `dumpsys activity`.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::stability;
+///
+/// let anr = stability::from_activity(
+///     "ACTIVITY MANAGER LAST ANR (dumpsys activity lastanr)
+///   ANR time: Jan 1, 2024 12:05:00 PM
+///   Application at fault: ProcessRecord{f00 1234:com.example.app/u0a123}
+///   Annotation: Input dispatching timed out
+///
+///   -------------------------------------------------------------------------------",
+/// )
+/// .unwrap();
+/// assert_eq!(anr.package.as_deref(), Some("com.example.app"));
+/// assert_eq!(anr.pid, Some(1234));
+/// assert_eq!(anr.reason.as_deref(), Some("Input dispatching timed out"));
+///
+/// assert_eq!(stability::from_activity("  <no ANR has occurred since boot>"), None);
+/// ```
+pub fn from_activity(text: &str) -> Option<StabilityEvent> {
+    let mut anr = None;
+
+    for line in text.lines().map(str::trim) {
+        if let Some(time) = line.strip_prefix("ANR time: ") {
+            anr.get_or_insert_with(|| StabilityEvent::new(EventKind::Anr, LAST_ANR))
+                .timestamp = Some(time.to_owned());
+            continue;
+        }
+        let Some(anr) = &mut anr else {
+            continue;
+        };
+        if let Some(process) = line.strip_prefix("ANR in ") {
+            anr.package = process.split_whitespace().next().map(str::to_owned);
+        } else if let Some(app) = line.strip_prefix("Application at fault: ") {
+            // ProcessRecord{f00 1234:com.example.app/u0a123}
+            let process = app
+                .split_whitespace()
+                .last()
+                .unwrap_or(app)
+                .trim_end_matches('}');
+            if let Some((pid, name)) = process.split_once(':') {
+                anr.pid = pid.parse().ok();
+                anr.package = Some(name.split('/').next().unwrap_or(name).to_owned());
+            }
+        } else if let Some(pid) = line.strip_prefix("PID: ") {
+            anr.pid = pid.parse().ok();
+        } else if let Some(reason) = line
+            .strip_prefix("Reason: ")
+            .or_else(|| line.strip_prefix("Annotation: "))
+        {
+            anr.reason.get_or_insert_with(|| reason.to_owned());
+        } else if line.starts_with("-----") {
+            // The activity dump taken at the time of the ANR follows.
+            break;
+        }
+    }
+    anr
+}
+
+/// Polls `activity` and `dropbox` for ANRs and crashes
+///
+/// # Example
+///
+/// ```
+/// use std::{thread, time::Duration};
+///
+/// use dumpsys_rs::stability::StabilityDetector;
+///
+/// # fn foo() {
+/// let mut detector = StabilityDetector::new().unwrap();
+/// // Skip what happened before the test.
+/// detector.poll().unwrap();
+/// loop {
+///     thread::sleep(Duration::from_secs(10));
+///     for event in detector.poll().unwrap() {
+///         println!("{:?} in {:?}: {:?}", event.kind, event.package, event.reason);
+///     }
+/// }
+/// # }
+/// ```
+pub struct StabilityDetector {
+    activity: Dumpsys,
+    dropbox: Dumpsys,
+    /// Timestamp of the newest dropbox entry, limiting the next dump to newer ones
+    since: Option<String>,
+    seen: HashSet<StabilityEvent>,
+}
+
+impl StabilityDetector {
+    pub fn new() -> Result<Self, DumpError> {
+        Ok(Self {
+            activity: typed::connect(ACTIVITY, &[LAST_ANR.to_owned()])?,
+            dropbox: typed::connect(DROPBOX, &[PRINT.to_owned()])?,
+            since: None,
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Events recorded since the last poll, or all recorded ones on the first.
+    pub fn poll(&mut self) -> Result<Vec<StabilityEvent>, DumpError> {
+        let mut args = vec![PRINT.to_owned()];
+        if let Some(since) = &self.since {
+            // `dumpsys dropbox --print 2024-01-01 12:00:00` prints that second and later.
+            args.extend(since.split_whitespace().map(str::to_owned));
+        }
+        let mut events = from_dropbox(&self.dropbox.dump(&args)?);
+        if let Some(newest) = events
+            .iter()
+            .rev()
+            .find_map(|event| event.timestamp.clone())
+        {
+            self.since = Some(newest);
+        }
+        events.extend(from_activity(&self.activity.dump([LAST_ANR])?));
+
+        events.retain(|event| self.seen.insert(event.clone()));
+        Ok(events)
+    }
+}
+
+/// The text of a dropbox entry being read
+struct EntryReader {
+    event: StabilityEvent,
+    /// Past the `key: value` headers
+    in_body: bool,
+}
+
+impl EntryReader {
+    fn line(&mut self, line: &str) {
+        let event = &mut self.event;
+        if line.is_empty() {
+            self.in_body = true;
+            return;
+        }
+        if line.starts_with("=====") {
+            return;
+        }
+
+        if let Some(process) = line.strip_prefix("Process: ") {
+            event.package.get_or_insert_with(|| process.to_owned());
+        } else if let Some(pid) = line.strip_prefix("PID: ") {
+            event.pid = event.pid.or(pid.parse().ok());
+        } else if let Some(subject) = line.strip_prefix("Subject: ") {
+            event.reason = Some(subject.to_owned());
+        } else if line.starts_with("signal ") && event.kind == EventKind::NativeCrash {
+            event.reason = Some(line.to_owned());
+        } else if let Some(name) = line.split(">>> ").nth(1) {
+            // pid: 1234, tid: 1234, name: main  >>> com.example.app <<<
+            event
+                .package
+                .get_or_insert_with(|| name.trim_end_matches(" <<<").to_owned());
+        } else if self.in_body && event.reason.is_none() && event.kind == EventKind::Crash {
+            event.reason = Some(line.to_owned());
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::Dumpsys;
+
+/// What a dump cost, see [`Dumpsys::last_stats`]
+///
+/// Counts cover every attempt of the dump, so a dump that was retried reports the output of the
+/// failed attempts as well.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DumpStats {
+    /// From the start of the first attempt to the end of the last, retry delays included
+    pub duration: Duration,
+    /// Output read from the service, 0 for [`Dumpsys::dump_to_fd`] where the service writes to the
+    /// descriptor directly
+    pub bytes: u64,
+    /// Reads of the dump pipe, `splice(2)` and io_uring reads included
+    pub read_syscalls: u64,
+    /// Attempts after the first, including the one after reconnecting to a dead service
+    pub retries: u32,
+}
+
+/// Where a handle keeps the stats of its last dump, shared with the handles derived from it
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LastStats(Arc<Mutex<Option<DumpStats>>>);
+
+/// The stats of a dump in progress, recorded in the [`LastStats`] of its handle once it ends
+pub(crate) struct Tally {
+    last: LastStats,
+    start: Instant,
+    stats: DumpStats,
+}
+
+impl Tally {
+    /// Output of `bytes` was read in `reads` reads.
+    pub(crate) fn read(&mut self, bytes: u64, reads: u64) {
+        self.stats.bytes += bytes;
+        self.stats.read_syscalls += reads;
+    }
+
+    /// The dump is attempted again.
+    pub(crate) fn retried(&mut self) {
+        self.stats.retries += 1;
+    }
+
+    /// The dump ended, whether it succeeded or not.
+    pub(crate) fn record(&self) {
+        let stats = DumpStats {
+            duration: self.start.elapsed(),
+            ..self.stats
+        };
+        *self.last.0.lock().unwrap() = Some(stats);
+    }
+}
+
+impl Dumpsys {
+    /// What the last dump through this handle cost, failed ones included, `None` before the first
+    /// ended.
+    ///
+    /// Clones of the handle and handles derived from it, e.g. by [`Dumpsys::dump_with_timeout`], record
+    /// here as well.
+    /// With several dumps running at once, the stats are those of the one that ended last, and a
+    /// [`DumpReader`](crate::DumpReader) dropped before the end records none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let meminfo = Dumpsys::new("meminfo")?;
+    /// meminfo.dump(&["-a"]).unwrap();
+    /// let stats = meminfo.last_stats()?;
+    /// if stats.duration > Duration::from_secs(1) {
+    ///     println!("meminfo took {:?} for {} bytes", stats.duration, stats.bytes);
+    /// }
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn last_stats(&self) -> Option<DumpStats> {
+        *self.last_stats.0.lock().unwrap()
+    }
+
+    /// Start counting the cost of a dump.
+    pub(crate) fn tally(&self) -> Tally {
+        Tally {
+            last: self.last_stats.clone(),
+            start: Instant::now(),
+            stats: DumpStats::default(),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
+
+use crate::{error::DumpError, Dumpsys, CHUNK_SIZE};
+
+/// Output queued ahead of a slow consumer before the reader stops pulling from the pipe, unless
+/// [`DumpsysBuilder::stream_high_water_mark`](crate::DumpsysBuilder::stream_high_water_mark) says otherwise
+const STREAM_HIGH_WATER_MARK: usize = 4 * CHUNK_SIZE;
+
+/// Output chunks of an in-flight dump, returned by [`Dumpsys::dump_stream`]
+///
+/// The final item is an error if the dump transaction failed.
+/// Dropping the stream closes the pipe so the service stops writing.
+pub struct DumpStream {
+    queue: Arc<Queue>,
+}
+
+impl DumpStream {
+    /// Bytes read from the pipe that the stream hasn't yielded yet.
+    pub fn buffered(&self) -> usize {
+        self.queue.state.lock().unwrap().bytes
+    }
+
+    /// Whether reading is paused until the consumer catches up.
+    pub fn is_paused(&self) -> bool {
+        self.queue.state.lock().unwrap().paused
+    }
+}
+
+impl Stream for DumpStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let queue = &self.queue;
+        let mut state = queue.state.lock().unwrap();
+        match state.chunks.pop_front() {
+            Some(chunk) => {
+                state.bytes -= chunk.as_ref().map_or(0, Bytes::len);
+                if state.paused && state.bytes <= queue.high_water_mark / 2 {
+                    state.paused = false;
+                    queue.resume.notify_one();
+                }
+                Poll::Ready(Some(chunk))
+            }
+            None if state.finished => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for DumpStream {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.closed = true;
+        state.chunks.clear();
+        self.queue.resume.notify_one();
+    }
+}
+
+/// Chunks on their way from the reading thread to the stream, up to the high-water mark of them
+struct Queue {
+    state: Mutex<QueueState>,
+    resume: Condvar,
+    high_water_mark: usize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    chunks: VecDeque<io::Result<Bytes>>,
+    /// Bytes in `chunks`
+    bytes: usize,
+    /// The reader waits for the consumer to take the queue down to half the high-water mark
+    paused: bool,
+    /// The reader is done, nothing follows the chunks queued
+    finished: bool,
+    /// The stream was dropped
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Queue {
+    /// Queue `chunk`, first waiting while the queue is at the high-water mark. `false` once the stream
+    /// is gone.
+    fn push(&self, chunk: io::Result<Bytes>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.chunks.is_empty() && state.bytes >= self.high_water_mark {
+            state.paused = true;
+            state = self
+                .resume
+                .wait_while(state, |state| state.paused && !state.closed)
+                .unwrap();
+        }
+        if state.closed {
+            return false;
+        }
+        state.bytes += chunk.as_ref().map_or(0, Bytes::len);
+        state.chunks.push_back(chunk);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+}
+
+/// Ends the stream once the reading thread is done, however it got there.
+struct Finish(Arc<Queue>);
+
+impl Drop for Finish {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Dumpsys {
+    /// Stream the dump as [`Bytes`] chunks; reading pauses while the consumer falls behind.
+    ///
+    /// Output the consumer hasn't taken yet is queued up to a high-water mark, see
+    /// [`DumpsysBuilder::stream_high_water_mark`](crate::DumpsysBuilder::stream_high_water_mark).
+    /// Past it, the pipe fills up and blocks the service, so a slow consumer throttles the dump
+    /// instead of the output piling up in memory.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dumpsys_rs::Dumpsys;
+    /// use futures::StreamExt;
+    ///
+    /// # async fn foo() -> Option<()> {
+    /// let mut stream = Dumpsys::new("meminfo")?.dump_stream(&["-a"]).unwrap();
+    /// while let Some(chunk) = stream.next().await {
+    ///     println!("{} bytes", chunk.unwrap().len());
+    /// }
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn dump_stream(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<DumpStream, DumpError> {
+        let mut reader = self.dump_reader(args)?;
+        let queue = Arc::new(Queue {
+            state: Mutex::default(),
+            resume: Condvar::new(),
+            high_water_mark: self
+                .config
+                .stream_high_water_mark
+                .unwrap_or(STREAM_HIGH_WATER_MARK),
+        });
+
+        let threads = self.config.threads.clone();
+        let finish = Finish(queue.clone());
+        threads.builder().spawn(move || {
+            let queue = &finish.0;
+            if let Err(err) = threads.apply() {
+                queue.push(Err(err));
+                return;
+            }
+            loop {
+                let mut buf = BytesMut::zeroed(CHUNK_SIZE);
+                let chunk = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        buf.truncate(n);
+                        Ok(buf.freeze())
+                    }
+                    Err(err) => Err(err),
+                };
+
+                let failed = chunk.is_err();
+                if !queue.push(chunk) || failed {
+                    break;
+                }
+            }
+        })?;
+
+        Ok(DumpStream { queue })
+    }
+}
@@ -0,0 +1,678 @@
+//! Typed output of `dumpsys SurfaceFlinger`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::surfaceflinger::Latency;
+//!
+//! let latency = Latency::parse(
+//!     "16666666
+//! 0\t0\t0
+//! 1000000000\t1000500000\t999000000
+//! 1016666666\t1017166666\t1015666666
+//! 1033333332\t9223372036854775807\t1032333332",
+//! )
+//! .unwrap();
+//! assert_eq!(latency.refresh_period, Duration::from_nanos(16_666_666));
+//! assert_eq!(latency.frames.len(), 3);
+//! assert!(latency.frames[2].is_pending());
+//! assert_eq!(latency.presented().count(), 2);
+//! ```
+//!
+//! [`TimeStats`] also parses the histograms of `--timestats -dump`, which SurfaceFlinger only collects
+//! after [`set_timestats`] enabled them.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{first_word, invalid, parse_int},
+    typed, DumpParse, Dumpsys,
+};
+
+/// Timestamp of a fence SurfaceFlinger hasn't seen signal yet, `INT64_MAX`
+pub const PENDING: u64 = i64::MAX as u64;
+
+const LATENCY_ARG: &str = "--latency";
+const LATENCY_CLEAR_ARG: &str = "--latency-clear";
+const LIST_ARG: &str = "--list";
+const TIMESTATS_ARG: &str = "--timestats";
+const HISTOGRAM_SUFFIX: &str = " histogram is as below:";
+const LAYER_NAME: &str = "layerName";
+/// Half the time 60 Hz takes to fill the 128 frame history, so no frame is overwritten before it's read
+const DEFAULT_TRACK_INTERVAL: Duration = Duration::from_secs(1);
+const REFRESH_PERIOD: &str = "refresh period";
+
+/// Timestamps of one frame of a layer, in nanoseconds of `CLOCK_MONOTONIC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameTiming {
+    /// When the app wanted the frame on screen
+    pub desired_present: u64,
+    /// When the frame was actually presented, [`PENDING`] until then
+    pub actual_present: u64,
+    /// When the GPU finished rendering the frame, [`PENDING`] until then
+    pub frame_ready: u64,
+}
+
+impl FrameTiming {
+    /// Whether the frame is still waiting for its present or ready fence.
+    pub fn is_pending(&self) -> bool {
+        self.actual_present == PENDING || self.frame_ready == PENDING
+    }
+}
+
+/// Output of `dumpsys SurfaceFlinger --latency <layer>`
+///
+/// SurfaceFlinger keeps the timings of the last 128 frames of the layer in a ring buffer, oldest first.
+/// Unused slots, printed as `0 0 0`, are skipped.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Latency {
+    /// Vsync period of the display
+    pub refresh_period: Duration,
+    pub frames: Vec<FrameTiming>,
+}
+
+impl Latency {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let period = lines
+            .next()
+            .ok_or_else(|| ParseError::Missing(REFRESH_PERIOD.to_owned()))?;
+        let refresh_period = period
+            .parse()
+            .map(Duration::from_nanos)
+            .map_err(|_| invalid(REFRESH_PERIOD, period))?;
+
+        let mut frames = Vec::new();
+        for line in lines {
+            let mut timestamps = line.split_whitespace().map(str::parse::<u64>);
+            let (Some(Ok(desired_present)), Some(Ok(actual_present)), Some(Ok(frame_ready))) =
+                (timestamps.next(), timestamps.next(), timestamps.next())
+            else {
+                return Err(invalid("frame", line));
+            };
+            if desired_present == 0 && actual_present == 0 && frame_ready == 0 {
+                continue;
+            }
+            frames.push(FrameTiming {
+                desired_present,
+                actual_present,
+                frame_ready,
+            });
+        }
+
+        Ok(Self {
+            refresh_period,
+            frames,
+        })
+    }
+
+    /// Refresh rate of the display in Hz, 0 if the period is unknown.
+    pub fn refresh_rate(&self) -> f64 {
+        if self.refresh_period.is_zero() {
+            return 0.0;
+        }
+        1.0 / self.refresh_period.as_secs_f64()
+    }
+
+    /// Frames that made it to the screen.
+    pub fn presented(&self) -> impl Iterator<Item = &FrameTiming> {
+        self.frames.iter().filter(|frame| !frame.is_pending())
+    }
+
+    /// Average frame rate over the presented frames, `None` with fewer than two.
+    pub fn fps(&self) -> Option<f64> {
+        let mut presented = self.presented().map(|frame| frame.actual_present);
+        let first = presented.next()?;
+        let (count, last) = presented.fold((0, first), |(count, _), present| (count + 1, present));
+        let span = Duration::from_nanos(last.checked_sub(first)?).as_secs_f64();
+        (count > 0 && span > 0.0).then(|| f64::from(count) / span)
+    }
+}
+
+impl DumpParse for Latency {
+    const SERVICE: &'static str = "SurfaceFlinger";
+    /// Without a layer, older releases report the frames of the whole display.
+    const ARGS: &'static [&'static str] = &[LATENCY_ARG];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Frame timings of `layer`, as listed by `dumpsys SurfaceFlinger --list`.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{surfaceflinger, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// let dumpsys = Dumpsys::new("SurfaceFlinger")?;
+/// let latency = surfaceflinger::latency(&dumpsys, "SurfaceView[com.example.game/.MainActivity]#0").unwrap();
+/// println!("{:.1} fps at {:.0} Hz", latency.fps().unwrap_or(0.0), latency.refresh_rate());
+/// # Some(())
+/// # }
+/// ```
+pub fn latency(dumpsys: &Dumpsys, layer: &str) -> Result<Latency, DumpError> {
+    typed::dump_parsed(dumpsys, [LATENCY_ARG, layer])
+}
+
+/// Names of every layer, from `dumpsys SurfaceFlinger --list`.
+pub fn layers(dumpsys: &Dumpsys) -> Result<Vec<String>, DumpError> {
+    let output = dumpsys.dump([LIST_ARG])?;
+    Ok(output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+/// The layer among `layers` that the frames of activity `component`, e.g. `com.example.game/.MainActivity`,
+/// are queued to, for [`latency`].
+///
+/// Layer names changed with nearly every release: `com.example.game/com.example.game.MainActivity` up
+/// to Android 10, with a `#<id>` suffix from Android 11, and from Android 12 with a `(BLAST)` child layer
+/// holding the buffers, which is preferred. After that, its SurfaceView, `SurfaceView - <activity>` up
+/// to Android 11 and `SurfaceView[<activity>]` from Android 12, is preferred over the window, since
+/// games and video draw there.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::surfaceflinger;
+///
+/// let layers = [
+///     "com.example.game/com.example.game.MainActivity#42".to_owned(),
+///     "com.example.game/com.example.game.MainActivity(BLAST)#43".to_owned(),
+///     "SurfaceView[com.example.game/com.example.game.MainActivity]#44".to_owned(),
+///     "SurfaceView[com.example.game/com.example.game.MainActivity](BLAST)#45".to_owned(),
+/// ];
+/// assert_eq!(
+///     surfaceflinger::activity_layer(&layers, "com.example.game/.MainActivity"),
+///     Some("SurfaceView[com.example.game/com.example.game.MainActivity](BLAST)#45")
+/// );
+/// ```
+pub fn activity_layer<'a>(layers: &'a [String], component: &str) -> Option<&'a str> {
+    let component = match component.split_once("/.") {
+        Some((package, class)) => format!("{package}/{package}.{class}"),
+        None => component.to_owned(),
+    };
+    layers
+        .iter()
+        .filter_map(|layer| Some((layer_rank(layer, &component)?, layer.as_str())))
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, layer)| layer)
+}
+
+/// How well `layer` shows the frames of `component`, lowest first, `None` for a layer of something else.
+fn layer_rank(layer: &str, component: &str) -> Option<(bool, bool)> {
+    let name = match layer.rsplit_once('#') {
+        Some((name, id)) if id.bytes().all(|byte| byte.is_ascii_digit()) => name,
+        _ => layer,
+    };
+    let (name, blast) = match name.strip_suffix("(BLAST)") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let surface_view = name
+        .strip_prefix("SurfaceView[")
+        .and_then(|name| name.strip_suffix(']'))
+        .or_else(|| name.strip_prefix("SurfaceView - "));
+    if surface_view.unwrap_or(name) != component {
+        return None;
+    }
+    // Where there are BLAST layers, the others only hold them and never get a buffer.
+    Some((!blast, surface_view.is_none()))
+}
+
+/// Follows the frames of one layer across repeated `--latency` dumps
+///
+/// The history is cleared with `--latency-clear` on the first poll, so only frames presented from then
+/// on are reported, each exactly once and in order. A frame whose fence hasn't signaled yet is held back
+/// until a later dump has its timestamps, unless a newer frame already made it to the screen. Frames are
+/// lost if the layer presents all 128 slots of its history between two polls.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{surfaceflinger::FrameLatencyTracker, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// let dumpsys = Dumpsys::new("SurfaceFlinger")?;
+/// let tracker = FrameLatencyTracker::new(dumpsys, "SurfaceView[com.example.game/.MainActivity]#0")
+///     .interval(Duration::from_millis(500));
+/// for frame in tracker.take(600) {
+///     let frame = frame.unwrap();
+///     println!("{}", frame.actual_present - frame.desired_present);
+/// }
+/// # Some(())
+/// # }
+/// ```
+pub struct FrameLatencyTracker {
+    dumpsys: Dumpsys,
+    layer: String,
+    interval: Duration,
+    cleared: bool,
+    /// `desired_present` of the last frame returned
+    last: u64,
+    ready: VecDeque<FrameTiming>,
+}
+
+impl FrameLatencyTracker {
+    /// Track `layer` of `dumpsys`, a handle to `SurfaceFlinger`.
+    pub fn new(dumpsys: Dumpsys, layer: impl Into<String>) -> Self {
+        Self {
+            dumpsys,
+            layer: layer.into(),
+            interval: DEFAULT_TRACK_INTERVAL,
+            cleared: false,
+            last: 0,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// How long iterating waits between dumps, 1 second by default.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Clear the frame history of the layer, dropping frames that weren't polled yet.
+    pub fn clear(&mut self) -> Result<(), DumpError> {
+        self.dumpsys
+            .dump([LATENCY_CLEAR_ARG, self.layer.as_str()])?;
+        self.cleared = true;
+        self.last = 0;
+        self.ready.clear();
+        Ok(())
+    }
+
+    /// Dump once and return the frames presented since the previous poll, without waiting.
+    pub fn poll(&mut self) -> Result<Vec<FrameTiming>, DumpError> {
+        self.poll_latency().map(|(_, frames)| frames)
+    }
+
+    /// Like [`FrameLatencyTracker::poll`], also returning the refresh period of the display.
+    pub(crate) fn poll_latency(&mut self) -> Result<(Duration, Vec<FrameTiming>), DumpError> {
+        if !self.cleared {
+            self.clear()?;
+        }
+
+        let latency = latency(&self.dumpsys, &self.layer)?;
+        let frames: Vec<_> = latency
+            .frames
+            .into_iter()
+            .filter(|frame| frame.desired_present > self.last)
+            .collect();
+        // Pending frames followed by a presented one were dropped and never get timestamps.
+        let held = frames
+            .iter()
+            .rposition(|frame| !frame.is_pending())
+            .map_or(0, |last_presented| last_presented + 1);
+
+        let presented: Vec<_> = frames[..held]
+            .iter()
+            .filter(|frame| !frame.is_pending())
+            .copied()
+            .collect();
+        if let Some(frame) = presented.last() {
+            self.last = frame.desired_present;
+        }
+        Ok((latency.refresh_period, presented))
+    }
+}
+
+impl Iterator for FrameLatencyTracker {
+    type Item = Result<FrameTiming, DumpError>;
+
+    /// Next presented frame, dumping every interval until there is one.
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut first = !self.cleared;
+        while self.ready.is_empty() {
+            if !first {
+                thread::sleep(self.interval);
+            }
+            first = false;
+            match self.poll() {
+                Ok(frames) => self.ready.extend(frames),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        self.ready.pop_front().map(Ok)
+    }
+}
+
+/// Frame times in milliseconds and how many frames took that long, from `--timestats -dump`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    /// Frame count by bucket in milliseconds
+    pub buckets: BTreeMap<u32, u64>,
+}
+
+impl Histogram {
+    /// Frames counted in all buckets.
+    pub fn total(&self) -> u64 {
+        self.buckets.values().sum()
+    }
+
+    /// Smallest bucket holding at least `percentile` percent of the frames, `None` if empty.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let wanted = (total as f64 * percentile / 100.0).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.buckets.iter().find_map(|(&ms, &count)| {
+            seen += count;
+            (seen >= wanted).then(|| Duration::from_millis(u64::from(ms)))
+        })
+    }
+
+    /// Average of the buckets weighted by their count, `None` if empty.
+    pub fn mean(&self) -> Option<Duration> {
+        let total = self.total();
+        let sum: u64 = self
+            .buckets
+            .iter()
+            .map(|(&ms, &count)| u64::from(ms) * count)
+            .sum();
+        (total > 0).then(|| Duration::from_secs_f64(sum as f64 / total as f64 / 1000.0))
+    }
+
+    /// Parse a line of `<ms>ms=<count>` buckets into `self`, false if it isn't one.
+    fn parse_line(&mut self, line: &str) -> bool {
+        let mut buckets: Vec<(u32, u64)> = Vec::new();
+        for bucket in line.split_whitespace() {
+            let parsed = bucket
+                .split_once("ms=")
+                .and_then(|(ms, count)| Some((ms.parse().ok()?, count.parse().ok()?)));
+            match parsed {
+                Some(bucket) => buckets.push(bucket),
+                None => return false,
+            }
+        }
+        self.buckets.extend(buckets);
+        !line.trim().is_empty()
+    }
+}
+
+/// Stats of one layer in `--timestats -dump`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerTimeStats {
+    pub name: String,
+    pub package: Option<String>,
+    pub uid: Option<u32>,
+    pub total_frames: u64,
+    pub dropped_frames: u64,
+    pub average_fps: Option<f64>,
+    /// Histograms by name, such as `present2present` or `post2present`
+    pub histograms: BTreeMap<String, Histogram>,
+    /// Every `key = value` field of the layer
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Output of `dumpsys SurfaceFlinger --timestats -dump`
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::surfaceflinger::TimeStats;
+///
+/// let stats = TimeStats::parse(
+///     "statsStart = 1540000000
+/// statsEnd = 1540000100
+/// totalFrames = 5000
+/// missedFrames = 12
+/// clientCompositionFrames = 100
+/// displayOnTime = 100000 ms
+/// presentToPresent histogram is as below:
+/// 16ms=4900 33ms=90 50ms=10
+/// layerName = SurfaceView[com.example.game/.MainActivity]#0
+/// packageName = com.example.game
+/// totalFrames = 3000
+/// droppedFrames = 2
+/// averageFPS = 59.8
+/// present2present histogram is as below:
+/// 16ms=2990 33ms=10",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(stats.missed_frames, 12);
+/// assert_eq!(stats.display_on_time, Duration::from_secs(100));
+/// let present = &stats.histograms["presentToPresent"];
+/// assert_eq!(present.percentile(99.0), Some(Duration::from_millis(33)));
+/// assert_eq!(stats.layers[0].package.as_deref(), Some("com.example.game"));
+/// assert_eq!(stats.layers[0].average_fps, Some(59.8));
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeStats {
+    /// Unix time in seconds collection started at
+    pub stats_start: u64,
+    pub stats_end: u64,
+    pub total_frames: u64,
+    /// Frames that missed their vsync
+    pub missed_frames: u64,
+    /// Frames composed by the GPU instead of the hardware composer
+    pub client_composition_frames: u64,
+    pub display_on_time: Duration,
+    /// Global histograms by name, such as `presentToPresent` or `frameDuration`
+    pub histograms: BTreeMap<String, Histogram>,
+    /// Every global `key = value` field
+    pub fields: BTreeMap<String, String>,
+    pub layers: Vec<LayerTimeStats>,
+}
+
+impl TimeStats {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut stats = Self::default();
+        // Histogram whose bucket lines are being read
+        let mut histogram: Option<String> = None;
+
+        for line in text.lines().map(str::trim) {
+            let (fields, histograms) = match stats.layers.last_mut() {
+                Some(layer) => (&mut layer.fields, &mut layer.histograms),
+                None => (&mut stats.fields, &mut stats.histograms),
+            };
+
+            if let Some(name) = line.strip_suffix(HISTOGRAM_SUFFIX) {
+                histograms.entry(name.to_owned()).or_default();
+                histogram = Some(name.to_owned());
+                continue;
+            }
+            if let Some(name) = &histogram {
+                if histograms.entry(name.clone()).or_default().parse_line(line) {
+                    continue;
+                }
+                histogram = None;
+            }
+
+            let Some((key, value)) = line.split_once(" = ") else {
+                continue;
+            };
+            if key == LAYER_NAME {
+                stats.layers.push(LayerTimeStats {
+                    name: value.to_owned(),
+                    ..LayerTimeStats::default()
+                });
+                continue;
+            }
+            fields.insert(key.to_owned(), value.to_owned());
+        }
+
+        stats.stats_start = field(&stats.fields, "statsStart")?;
+        stats.stats_end = field(&stats.fields, "statsEnd")?;
+        stats.total_frames = field(&stats.fields, "totalFrames")?;
+        stats.missed_frames = field(&stats.fields, "missedFrames")?;
+        stats.client_composition_frames = field(&stats.fields, "clientCompositionFrames")?;
+        stats.display_on_time = Duration::from_millis(field(&stats.fields, "displayOnTime")?);
+
+        for layer in &mut stats.layers {
+            layer.package = layer.fields.get("packageName").cloned();
+            layer.uid = match layer.fields.get("uid") {
+                Some(uid) => Some(uid.parse().map_err(|_| invalid("uid", uid))?),
+                None => None,
+            };
+            layer.total_frames = field(&layer.fields, "totalFrames")?;
+            layer.dropped_frames = field(&layer.fields, "droppedFrames")?;
+            layer.average_fps = match layer.fields.get("averageFPS") {
+                Some(fps) => Some(fps.parse().map_err(|_| invalid("averageFPS", fps))?),
+                None => None,
+            };
+        }
+
+        Ok(stats)
+    }
+
+    /// The stats of the layer called `name`.
+    pub fn layer(&self, name: &str) -> Option<&LayerTimeStats> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+}
+
+impl DumpParse for TimeStats {
+    const SERVICE: &'static str = "SurfaceFlinger";
+    const ARGS: &'static [&'static str] = &[TIMESTATS_ARG, "-dump"];
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Start or stop collecting time stats, which SurfaceFlinger doesn't do by default.
+pub fn set_timestats(dumpsys: &Dumpsys, enable: bool) -> Result<(), DumpError> {
+    let flag = if enable { "-enable" } else { "-disable" };
+    dumpsys.dump([TIMESTATS_ARG, flag]).map(drop)
+}
+
+/// Reset the collected time stats, e.g. before a benchmark.
+pub fn clear_timestats(dumpsys: &Dumpsys) -> Result<(), DumpError> {
+    dumpsys.dump([TIMESTATS_ARG, "-clear"]).map(drop)
+}
+
+/// Time stats collected since they were enabled or cleared.
+///
+/// # Example
+///
+/// ```
+/// use std::{thread, time::Duration};
+///
+/// use dumpsys_rs::{surfaceflinger, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// let dumpsys = Dumpsys::new("SurfaceFlinger")?;
+/// surfaceflinger::set_timestats(&dumpsys, true).unwrap();
+/// surfaceflinger::clear_timestats(&dumpsys).unwrap();
+/// thread::sleep(Duration::from_secs(10));
+///
+/// let stats = surfaceflinger::timestats(&dumpsys).unwrap();
+/// println!("{}/{} frames missed", stats.missed_frames, stats.total_frames);
+/// # Some(())
+/// # }
+/// ```
+pub fn timestats(dumpsys: &Dumpsys) -> Result<TimeStats, DumpError> {
+    typed::dump_parsed(dumpsys, TimeStats::ARGS)
+}
+
+/// Number at the start of `key`, 0 if it is missing, e.g. `100000` of `displayOnTime = 100000 ms`.
+fn field(fields: &BTreeMap<String, String>, key: &str) -> Result<u64, ParseError> {
+    let Some(value) = fields.get(key) else {
+        return Ok(0);
+    };
+    parse_int(first_word(value))
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| invalid(key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATENCY: &str = "16666666
+0 0 0
+1000 2000 1500
+2000 3000 2500
+3000 9223372036854775807 3500
+";
+
+    #[test]
+    fn latency_skips_unused_slots() {
+        let latency = Latency::parse(LATENCY).unwrap();
+        assert_eq!(latency.refresh_period, Duration::from_nanos(16_666_666));
+        assert_eq!(latency.frames.len(), 3);
+        assert!(latency.frames[2].is_pending());
+        assert_eq!(latency.presented().count(), 2);
+    }
+
+    #[test]
+    fn latency_with_crlf_parses_the_same() {
+        let crlf = LATENCY.replace('\n', "\r\n");
+        assert_eq!(
+            Latency::parse(&crlf).unwrap(),
+            Latency::parse(LATENCY).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_latency_misses_the_refresh_period() {
+        assert_eq!(
+            Latency::parse("").unwrap_err(),
+            ParseError::Missing(REFRESH_PERIOD.to_owned())
+        );
+        assert_eq!(Latency::parse("16666666\n").unwrap().fps(), None);
+    }
+
+    #[test]
+    fn truncated_frame_is_invalid() {
+        assert_eq!(
+            Latency::parse("16666666\n1000 2000 1500\n2000 30").unwrap_err(),
+            ParseError::Invalid {
+                key: "frame".to_owned(),
+                value: "2000 30".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn timestats_without_fields_are_zero() {
+        assert_eq!(TimeStats::parse("").unwrap(), TimeStats::default());
+    }
+
+    #[test]
+    fn timestats_with_crlf_parse_the_same() {
+        let text = "totalFrames = 50\nmissedFrames = 2\nlayerName = Game#0\ntotalFrames = 30\n";
+        let crlf = text.replace('\n', "\r\n");
+        let stats = TimeStats::parse(&crlf).unwrap();
+        assert_eq!(stats, TimeStats::parse(text).unwrap());
+        assert_eq!(stats.missed_frames, 2);
+        assert_eq!(stats.layer("Game#0").unwrap().total_frames, 30);
+    }
+
+    #[test]
+    fn timestats_field_of_the_wrong_form_is_invalid() {
+        assert_eq!(
+            TimeStats::parse("totalFrames = many").unwrap_err(),
+            ParseError::Invalid {
+                key: "totalFrames".to_owned(),
+                value: "many".to_owned(),
+            }
+        );
+    }
+}
@@ -0,0 +1,555 @@
+//! Typed output of `dumpsys telephony.registry`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::telephony::{CallState, DataState, RegState, TelephonyRegistry};
+//!
+//! let registry = TelephonyRegistry::parse(
+//!     "last known state:
+//!   Phone Id=0
+//!     mCallState=2
+//!     mRingingCallState=0
+//!     mServiceState={mVoiceRegState=0(IN_SERVICE), mDataRegState=0(IN_SERVICE), mOperatorAlphaLong=Example Mobile, mOperatorAlphaShort=ExMo, mIsManualNetworkSelection=false, getRilVoiceRadioTechnology=14(LTE), getRilDataRadioTechnology=14(LTE), mIsEmergencyOnly=false}
+//!     mSignalStrength=SignalStrength:{mCdma=CellSignalStrengthCdma: cdmaDbm=2147483647 cdmaEcio=2147483647 level=0, mGsm=CellSignalStrengthGsm: rssi=2147483647 ber=2147483647 mTa=2147483647 mLevel=0, mLte=CellSignalStrengthLte: rssi=-51 rsrp=-95 rsrq=-9 rssnr=2147483647 cqi=2147483647 ta=2147483647 level=3, mNr=CellSignalStrengthNr:{ csiRsrp = 2147483647 ssRsrp = -101 ssRsrq = -11 ssSinr = 12 level = 2 }, primary=CellSignalStrengthLte}
+//!     mMessageWaiting=false
+//!     mDataActivity=0
+//!     mDataConnectionState=2
+//!   Phone Id=1
+//!     mCallState=0
+//!     mServiceState={mVoiceRegState=1(OUT_OF_SERVICE), mDataRegState=1(OUT_OF_SERVICE), mOperatorAlphaLong=null, mOperatorAlphaShort=null}
+//!     mDataConnectionState=0
+//!   mCarrierNetworkChangeState=false
+//!   mActiveDataSubId=1
+//!   mDefaultPhoneId=0",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(registry.phones.len(), 2);
+//! assert_eq!(registry.default_phone_id, Some(0));
+//!
+//! let phone = registry.default_phone().unwrap();
+//! assert_eq!(phone.call_state, Some(CallState::Offhook));
+//! assert_eq!(phone.data_state, Some(DataState::Connected));
+//!
+//! let service = phone.service.as_ref().unwrap();
+//! assert!(service.is_in_service());
+//! assert_eq!(service.operator.as_deref(), Some("Example Mobile"));
+//! assert_eq!(service.data_radio_technology.as_deref(), Some("LTE"));
+//!
+//! let signal = phone.signal.as_ref().unwrap();
+//! assert_eq!(signal.cells.len(), 2);
+//! let lte = signal.primary_cell().unwrap();
+//! assert_eq!(lte.technology, "Lte");
+//! assert_eq!(lte.level, Some(3));
+//! assert_eq!(lte.get("rsrp"), Some(-95));
+//! assert_eq!(lte.get("rssnr"), None);
+//! assert_eq!(signal.cell("Nr").unwrap().get("ssSinr"), Some(12));
+//!
+//! let idle = registry.phone(1).unwrap();
+//! assert_eq!(idle.service.as_ref().unwrap().voice, Some(RegState::OutOfService));
+//! assert_eq!(idle.service.as_ref().unwrap().operator, None);
+//! assert_eq!(idle.signal, None);
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_int, KeyValues},
+    typed, DumpParse,
+};
+
+const PHONE_ID: &str = "Phone Id=";
+const SIGNAL_STRENGTH: &str = "SignalStrength:{";
+const CELL: &str = "=CellSignalStrength";
+const PRIMARY: &str = "primary=CellSignalStrength";
+/// `CellInfo.UNAVAILABLE`
+const UNAVAILABLE: i32 = i32::MAX;
+
+/// `TelephonyManager.CALL_STATE_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CallState {
+    Idle,
+    Ringing,
+    /// Dialing, active or on hold
+    Offhook,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for CallState {
+    fn from(state: i64) -> Self {
+        match state {
+            0 => Self::Idle,
+            1 => Self::Ringing,
+            2 => Self::Offhook,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// `TelephonyManager.DATA_*` connection states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataState {
+    Unknown,
+    Disconnected,
+    Connecting,
+    Connected,
+    Suspended,
+    Disconnecting,
+    HandoverInProgress,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for DataState {
+    fn from(state: i64) -> Self {
+        match state {
+            -1 => Self::Unknown,
+            0 => Self::Disconnected,
+            1 => Self::Connecting,
+            2 => Self::Connected,
+            3 => Self::Suspended,
+            4 => Self::Disconnecting,
+            5 => Self::HandoverInProgress,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// `ServiceState.STATE_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RegState {
+    InService,
+    OutOfService,
+    EmergencyOnly,
+    PowerOff,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for RegState {
+    fn from(state: i64) -> Self {
+        match state {
+            0 => Self::InService,
+            1 => Self::OutOfService,
+            2 => Self::EmergencyOnly,
+            3 => Self::PowerOff,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Registration of a slot with the network
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceState {
+    pub voice: Option<RegState>,
+    pub data: Option<RegState>,
+    /// Long operator name
+    pub operator: Option<String>,
+    pub operator_short: Option<String>,
+    /// As in `LTE` or `NR`
+    pub voice_radio_technology: Option<String>,
+    pub data_radio_technology: Option<String>,
+    pub manual_selection: Option<bool>,
+}
+
+impl ServiceState {
+    /// Whether voice or data is registered.
+    pub fn is_in_service(&self) -> bool {
+        self.voice == Some(RegState::InService) || self.data == Some(RegState::InService)
+    }
+}
+
+/// Signal of one radio technology
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CellSignal {
+    /// As in `Lte`, `Nr` or `Gsm`
+    pub technology: String,
+    /// From 0, none, to 4, great
+    pub level: Option<u32>,
+    /// Measurements the modem reported, as in `rsrp` or `ssSinr`, in dBm or dB
+    pub measurements: Vec<(String, i32)>,
+}
+
+impl CellSignal {
+    pub fn get(&self, measurement: &str) -> Option<i32> {
+        self.measurements
+            .iter()
+            .find(|(name, _)| name == measurement)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Signal strength of a slot, on Android 10 and later
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignalStrength {
+    /// Technologies with at least one measurement, as the others are placeholders
+    pub cells: Vec<CellSignal>,
+    /// Technology the level shown to the user comes from
+    pub primary: Option<String>,
+}
+
+impl SignalStrength {
+    pub fn cell(&self, technology: &str) -> Option<&CellSignal> {
+        self.cells.iter().find(|cell| cell.technology == technology)
+    }
+
+    /// The primary cell, or the first one measured.
+    pub fn primary_cell(&self) -> Option<&CellSignal> {
+        self.primary
+            .as_deref()
+            .and_then(|technology| self.cell(technology))
+            .or_else(|| self.cells.first())
+    }
+
+    /// Level of the primary cell.
+    pub fn level(&self) -> Option<u32> {
+        self.primary_cell()?.level
+    }
+}
+
+/// Last known state of one SIM slot
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhoneState {
+    pub phone_id: u32,
+    pub call_state: Option<CallState>,
+    pub service: Option<ServiceState>,
+    pub signal: Option<SignalStrength>,
+    pub data_state: Option<DataState>,
+}
+
+/// Output of `dumpsys telephony.registry`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TelephonyRegistry {
+    pub phones: Vec<PhoneState>,
+    pub default_phone_id: Option<u32>,
+    pub active_data_sub_id: Option<i32>,
+}
+
+impl TelephonyRegistry {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut registry = Self::default();
+        // Indent of the `Phone Id=` line of the current slot, as fields that follow at the same
+        // indent are global.
+        let mut phone_indent = None;
+
+        for line in text.lines() {
+            let indent = line.len() - line.trim_start().len();
+            let line = line.trim();
+            if let Some(id) = line.strip_prefix(PHONE_ID) {
+                registry.phones.push(PhoneState {
+                    phone_id: id.parse().map_err(|_| invalid("Phone Id", id))?,
+                    ..PhoneState::default()
+                });
+                phone_indent = Some(indent);
+                continue;
+            }
+            if phone_indent.is_some_and(|phone_indent| indent <= phone_indent) {
+                phone_indent = None;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key {
+                "mDefaultPhoneId" => registry.default_phone_id = value.parse().ok(),
+                "mActiveDataSubId" => registry.active_data_sub_id = value.parse().ok(),
+                _ => {}
+            }
+            let phone = match phone_indent {
+                Some(_) => registry.phones.last_mut(),
+                // Android 7 and earlier print a single slot without an id.
+                None if registry.phones.is_empty() && key == "mCallState" => {
+                    registry.phones.push(PhoneState::default());
+                    phone_indent = Some(indent.saturating_sub(1));
+                    registry.phones.last_mut()
+                }
+                None => None,
+            };
+            let Some(phone) = phone else {
+                continue;
+            };
+            match key {
+                "mCallState" => phone.call_state = parse_int(value).map(CallState::from),
+                "mServiceState" => phone.service = Some(service_state(value)),
+                "mSignalStrength" => phone.signal = signal_strength(value),
+                "mDataConnectionState" => {
+                    phone.data_state = parse_int(value).map(DataState::from);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn phone(&self, phone_id: u32) -> Option<&PhoneState> {
+        self.phones.iter().find(|phone| phone.phone_id == phone_id)
+    }
+
+    /// The slot used for calls and data by default.
+    pub fn default_phone(&self) -> Option<&PhoneState> {
+        match self.default_phone_id {
+            Some(phone_id) => self.phone(phone_id),
+            None => self.phones.first(),
+        }
+    }
+}
+
+impl DumpParse for TelephonyRegistry {
+    const SERVICE: &'static str = "telephony.registry";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `telephony.registry` and return the state of every slot.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for phone in dumpsys_rs::telephony::phones().unwrap() {
+///     let level = phone.signal.and_then(|signal| signal.level());
+///     println!("slot {}: {:?} at level {level:?}", phone.phone_id, phone.call_state);
+/// }
+/// # }
+/// ```
+pub fn phones() -> Result<Vec<PhoneState>, DumpError> {
+    typed::dump_service::<TelephonyRegistry>(Vec::<&str>::new()).map(|registry| registry.phones)
+}
+
+/// `{mVoiceRegState=0(IN_SERVICE), ..., mOperatorAlphaLong=Example Mobile, ...,
+/// getRilDataRadioTechnology=14(LTE), ...}`
+fn service_state(text: &str) -> ServiceState {
+    let fields = KeyValues::parse(text);
+    let get = |key| fields.get(key).filter(|value| *value != "null");
+    // `0(IN_SERVICE)` and `14(LTE)`
+    let code = |key| get(key).and_then(|value| parse_int(value.split('(').next()?));
+    let name = |key| {
+        get(key)
+            .and_then(|value| value.split_once('('))
+            .map(|(_, name)| name.trim_end_matches(')').to_owned())
+    };
+    ServiceState {
+        voice: code("mVoiceRegState").map(RegState::from),
+        data: code("mDataRegState").map(RegState::from),
+        operator: get("mOperatorAlphaLong").map(str::to_owned),
+        operator_short: get("mOperatorAlphaShort").map(str::to_owned),
+        voice_radio_technology: name("getRilVoiceRadioTechnology"),
+        data_radio_technology: name("getRilDataRadioTechnology"),
+        // `false(automatic)` on Android 11 and later
+        manual_selection: get("isManualNetworkSelection")
+            .or_else(|| get("mIsManualNetworkSelection"))
+            .and_then(|value| value.split('(').next()?.parse().ok()),
+    }
+}
+
+/// `SignalStrength:{mLte=CellSignalStrengthLte: rssi=-51 rsrp=-95 level=3, mNr=CellSignalStrengthNr:{
+/// ssRsrp = -101 level = 2 }, primary=CellSignalStrengthLte}`
+fn signal_strength(text: &str) -> Option<SignalStrength> {
+    let body = text.strip_prefix(SIGNAL_STRENGTH)?;
+    let (cells, primary) = body.rsplit_once(PRIMARY).unwrap_or((body, ""));
+    let mut signal = SignalStrength {
+        cells: Vec::new(),
+        primary: Some(primary.trim_end_matches('}').to_owned()).filter(|name| !name.is_empty()),
+    };
+
+    for cell in cells.split(CELL).skip(1) {
+        let name_end = cell
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(cell.len());
+        // NR spaces its `=`.
+        let fields = cell[name_end..].replace(" = ", "=");
+        let mut level = None;
+        let mut measurements = Vec::new();
+        // Cells are joined by a bare `,`, as in `mLevel=0,mWcdma=`.
+        for (key, value) in fields
+            .split([' ', ','])
+            .filter_map(|word| word.split_once('='))
+        {
+            let Ok(value) = value.trim_end_matches('}').parse::<i32>() else {
+                continue;
+            };
+            match key {
+                "level" | "mLevel" => level = u32::try_from(value).ok(),
+                "parametersUseForLevel" | "oplevel" => {}
+                _ if value != UNAVAILABLE => measurements.push((key.to_owned(), value)),
+                _ => {}
+            }
+        }
+        if !measurements.is_empty() {
+            signal.cells.push(CellSignal {
+                technology: cell[..name_end].to_owned(),
+                level,
+                measurements,
+            });
+        }
+    }
+
+    Some(signal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys telephony.registry` of Android 14, cut down to two slots
+    const DUMP: &str = "last known state:
+  Phone Id=0
+    mCallState=0
+    mRingingCallState=0
+    mForegroundCallState=0
+    mCallIncomingNumber=
+    mServiceState={mVoiceRegState=0(IN_SERVICE), mDataRegState=0(IN_SERVICE), mChannelNumber=1300, duplexMode()=1, mCellBandwidths=[20000], mOperatorAlphaLong=Example Mobile, mOperatorAlphaShort=ExMo, isManualNetworkSelection=false(automatic), getRilVoiceRadioTechnology=14(LTE), getRilDataRadioTechnology=14(LTE), mCssIndicator=supported, mNetworkId=-1, mSystemId=-1, mIsEmergencyOnly=false, isUsingCarrierAggregation=false, mNrFrequencyRange=0, mIsIwlanPreferred=false}
+    mVoiceActivationState= 0
+    mDataActivationState= 0
+    mUserMobileDataState= true
+    mSignalStrength=SignalStrength:{mCdma=CellSignalStrengthCdma: cdmaDbm=2147483647 cdmaEcio=2147483647 evdoDbm=2147483647 evdoEcio=2147483647 evdoSnr=2147483647 level=0,mGsm=CellSignalStrengthGsm: rssi=2147483647 ber=2147483647 mTa=2147483647 mLevel=0,mWcdma=CellSignalStrengthWcdma: ss=2147483647 ber=2147483647 rscp=2147483647 ecno=2147483647 level=0,mTdscdma=CellSignalStrengthTdscdma: rssi=2147483647 ber=2147483647 rscp=2147483647 level=0,mLte=CellSignalStrengthLte: rssi=-51 rsrp=-95 rsrq=-9 rssnr=2147483647 cqiTableIndex=2147483647 cqi=2147483647 ta=2147483647 level=3 parametersUseForLevel=1,mNr=CellSignalStrengthNr:{ csiRsrp = 2147483647 csiRsrq = 2147483647 csiCqiTableIndex = 2147483647 csiCqiReport = [] ssRsrp = -101 ssRsrq = -11 ssSinr = 12 level = 2 parametersUseForLevel = 0 timingAdvance = 2147483647 },primary=CellSignalStrengthLte}
+    mMessageWaiting=false
+    mCallForwarding=false
+    mDataActivity=3
+    mDataConnectionState=2
+  Phone Id=1
+    mCallState=1
+    mServiceState={mVoiceRegState=1(OUT_OF_SERVICE), mDataRegState=1(OUT_OF_SERVICE), mOperatorAlphaLong=null, mOperatorAlphaShort=null, isManualNetworkSelection=true(manual), getRilVoiceRadioTechnology=0(Unknown), getRilDataRadioTechnology=0(Unknown), mIsEmergencyOnly=false}
+    mSignalStrength=SignalStrength:{mCdma=CellSignalStrengthCdma: cdmaDbm=2147483647 cdmaEcio=2147483647 evdoDbm=2147483647 evdoEcio=2147483647 evdoSnr=2147483647 level=0,mGsm=CellSignalStrengthGsm: rssi=-89 ber=99 mTa=2147483647 mLevel=2,mWcdma=CellSignalStrengthWcdma: ss=2147483647 ber=2147483647 rscp=2147483647 ecno=2147483647 level=0,primary=CellSignalStrengthGsm}
+    mDataConnectionState=0
+  mCarrierNetworkChangeState=false
+  mPhoneCapability=null
+  mActiveDataSubId=2
+  mDefaultPhoneId=0
+  mRadioPowerState=1
+";
+
+    #[test]
+    fn parses_the_slots() {
+        let registry = TelephonyRegistry::parse(DUMP).unwrap();
+        assert_eq!(registry.phones.len(), 2);
+        assert_eq!(registry.default_phone_id, Some(0));
+        assert_eq!(registry.active_data_sub_id, Some(2));
+        let phone = registry.default_phone().unwrap();
+        assert_eq!(phone.call_state, Some(CallState::Idle));
+        assert_eq!(phone.data_state, Some(DataState::Connected));
+        assert_eq!(
+            phone.service.as_ref().unwrap(),
+            &ServiceState {
+                voice: Some(RegState::InService),
+                data: Some(RegState::InService),
+                operator: Some("Example Mobile".to_owned()),
+                operator_short: Some("ExMo".to_owned()),
+                voice_radio_technology: Some("LTE".to_owned()),
+                data_radio_technology: Some("LTE".to_owned()),
+                manual_selection: Some(false),
+            }
+        );
+        let other = registry.phone(1).unwrap();
+        assert_eq!(other.call_state, Some(CallState::Ringing));
+        assert_eq!(other.data_state, Some(DataState::Disconnected));
+        let service = other.service.as_ref().unwrap();
+        assert!(!service.is_in_service());
+        assert_eq!(service.operator, None);
+        assert_eq!(service.manual_selection, Some(true));
+    }
+
+    #[test]
+    fn parses_the_signal_strength() {
+        let registry = TelephonyRegistry::parse(DUMP).unwrap();
+        let signal = registry.phone(0).unwrap().signal.as_ref().unwrap();
+        // Technologies without measurements are left out
+        assert_eq!(signal.cells.len(), 2);
+        assert_eq!(
+            signal.primary_cell().unwrap(),
+            &CellSignal {
+                technology: "Lte".to_owned(),
+                level: Some(3),
+                measurements: vec![
+                    ("rssi".to_owned(), -51),
+                    ("rsrp".to_owned(), -95),
+                    ("rsrq".to_owned(), -9),
+                ],
+            }
+        );
+        let nr = signal.cell("Nr").unwrap();
+        assert_eq!(nr.level, Some(2));
+        assert_eq!(nr.get("ssRsrp"), Some(-101));
+        assert_eq!(nr.get("csiRsrp"), None);
+
+        // The level of a cell followed by another one
+        let gsm = registry.phone(1).unwrap().signal.as_ref().unwrap();
+        assert_eq!(gsm.primary.as_deref(), Some("Gsm"));
+        assert_eq!(gsm.level(), Some(2));
+        assert_eq!(gsm.cells[0].get("ber"), Some(99));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            TelephonyRegistry::parse(&DUMP.replace('\n', "\r\n")),
+            TelephonyRegistry::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(
+            TelephonyRegistry::parse("").unwrap(),
+            TelephonyRegistry::default()
+        );
+    }
+
+    #[test]
+    fn state_of_older_releases() {
+        // Android 7 prints a single slot without an id, and the signal strength as a list
+        let text = "last known state:
+  mCallState=2
+  mCallIncomingNumber=
+  mServiceState=0 0 voice home data home Example Mobile ExMo 00101 Example Mobile ExMo 00101  LTE LTE CSS supported -1 -1 RoamInd=-1 DefRoamInd=-1 EmergOnly=false IsDataRoamingFromRegistration=false
+  mSignalStrength=SignalStrength: 99 0 -120 -160 -120 -1 -1 28 -95 -9 300 2147483647 2147483647 gsm|lte
+  mMessageWaiting=false
+  mDataConnectionState=2
+";
+        let registry = TelephonyRegistry::parse(text).unwrap();
+        assert_eq!(registry.phones.len(), 1);
+        let phone = registry.default_phone().unwrap();
+        assert_eq!(phone.call_state, Some(CallState::Offhook));
+        assert_eq!(phone.data_state, Some(DataState::Connected));
+        assert_eq!(phone.service.as_ref().unwrap().voice, None);
+        assert_eq!(phone.signal, None);
+    }
+
+    #[test]
+    fn unknown_values_are_kept() {
+        let text = "  Phone Id=0
+    mCallState=7
+    mServiceState={mVoiceRegState=9(FUTURE), mDataRegState=3(POWER_OFF)}
+    mDataConnectionState=6
+";
+        let registry = TelephonyRegistry::parse(text).unwrap();
+        let phone = &registry.phones[0];
+        assert_eq!(phone.call_state, Some(CallState::Other(7)));
+        assert_eq!(phone.data_state, Some(DataState::Other(6)));
+        let service = phone.service.as_ref().unwrap();
+        assert_eq!(service.voice, Some(RegState::Other(9)));
+        assert_eq!(service.data, Some(RegState::PowerOff));
+        assert_eq!(service.voice_radio_technology, None);
+        assert_eq!(registry.default_phone_id, None);
+    }
+
+    #[test]
+    fn invalid_slots() {
+        assert_eq!(
+            TelephonyRegistry::parse("  Phone Id=first").unwrap_err(),
+            invalid("Phone Id", "first")
+        );
+    }
+}
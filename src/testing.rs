@@ -0,0 +1,245 @@
+//! Temporary services with scripted dumps, for end-to-end tests of code that dumps
+//!
+//! [`FakeService::start`] registers a service under a name of its own with servicemanager, answering
+//! dumps as its [`Script`] says, and takes it down again on drop. Dumps of it go through servicemanager,
+//! the binder driver, the pipe and the reader like the dumps of any other service, so tests exercise
+//! the same paths as production code, timeouts, failures and large outputs included.
+//!
+//! On a device, adding a service takes a process SELinux lets do so, e.g. root with SELinux
+//! permissive. servicemanager keeps listing the service until the process exits, but it fails every dump
+//! with `DEAD_OBJECT` once taken down. Off-device, it's registered with [`mock`](crate::mock) instead
+//! and unregistered on drop.
+//!
+//! # Example
+//!
+//! ```
+//! use binder::{ProcessState, StatusCode};
+//! use dumpsys_rs::{
+//!     error::DumpError,
+//!     testing::{FakeService, Script},
+//! };
+//!
+//! ProcessState::start_thread_pool();
+//! let fake = FakeService::start(
+//!     Script::new("Display 0 HWC layers:\n")
+//!         .reply(&["--latency"], "16666666\n")
+//!         .then(StatusCode::DEAD_OBJECT),
+//! )
+//! .unwrap();
+//! let dumpsys = fake.dumpsys().unwrap();
+//!
+//! assert!(matches!(dumpsys.dump(&["-a"]), Err(DumpError::DeadObject { .. })));
+//! assert_eq!(dumpsys.dump(&["--latency"]).unwrap(), "16666666\n");
+//! assert_eq!(dumpsys.dump(&["-a"]).unwrap(), "Display 0 HWC layers:\n");
+//! assert_eq!(fake.calls().len(), 3);
+//! ```
+
+use std::{
+    collections::VecDeque,
+    ffi::CStr,
+    io::Write,
+    process,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use binder::StatusCode;
+
+use crate::{handler, Dumpsys};
+
+/// How a fake service answers one dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// Write the output, then succeed
+    Output(Vec<u8>),
+    /// Write the output, then fail with the status, like a service giving up halfway
+    Fail { output: Vec<u8>, status: StatusCode },
+}
+
+impl Reply {
+    /// Write the reply to the reader, ignoring one that went away like services do.
+    fn write(&self, writer: &mut dyn Write) -> binder::Result<()> {
+        let (output, result) = match self {
+            Self::Output(output) => (output, Ok(())),
+            Self::Fail { output, status } => (output, Err(*status)),
+        };
+        let _ = writer.write_all(output);
+        result
+    }
+}
+
+impl From<&str> for Reply {
+    fn from(output: &str) -> Self {
+        Self::Output(output.into())
+    }
+}
+
+impl From<String> for Reply {
+    fn from(output: String) -> Self {
+        Self::Output(output.into())
+    }
+}
+
+impl From<Vec<u8>> for Reply {
+    fn from(output: Vec<u8>) -> Self {
+        Self::Output(output)
+    }
+}
+
+/// Failing without output
+impl From<StatusCode> for Reply {
+    fn from(status: StatusCode) -> Self {
+        Self::Fail {
+            output: Vec::new(),
+            status,
+        }
+    }
+}
+
+/// What a fake service replies to its dumps
+///
+/// Replies queued with [`Script::then`] come first, one per dump in order. Once they ran out, dumps
+/// get the reply for their exact arguments, or the default reply.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    default: Option<Reply>,
+    replies: Vec<(Vec<String>, Reply)>,
+    queue: VecDeque<Reply>,
+    delay: Duration,
+}
+
+impl Script {
+    /// Reply `reply` to every dump.
+    pub fn new(reply: impl Into<Reply>) -> Self {
+        Self {
+            default: Some(reply.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Reply `reply` instead when the arguments are exactly `args`.
+    pub fn reply<S: AsRef<str>>(mut self, args: &[S], reply: impl Into<Reply>) -> Self {
+        let args = args.iter().map(|arg| arg.as_ref().to_owned()).collect();
+        self.replies.push((args, reply.into()));
+        self
+    }
+
+    /// Reply `reply` to the next dump not answered by an earlier `then`, whatever its arguments.
+    pub fn then(mut self, reply: impl Into<Reply>) -> Self {
+        self.queue.push_back(reply.into());
+        self
+    }
+
+    /// Wait for `delay` before every reply, to exercise timeouts and cancellation.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn next(&mut self, args: &[String]) -> Reply {
+        if let Some(reply) = self.queue.pop_front() {
+            return reply;
+        }
+        self.replies
+            .iter()
+            .find(|(expected, _)| expected == args)
+            .map(|(_, reply)| reply)
+            .or(self.default.as_ref())
+            .cloned()
+            .unwrap_or(Reply::Output(Vec::new()))
+    }
+}
+
+/// A service registered for the duration of a test, taken down on drop
+pub struct FakeService {
+    name: String,
+    state: Arc<State>,
+}
+
+struct State {
+    script: Mutex<Script>,
+    /// Arguments of every dump so far
+    calls: Mutex<Vec<Vec<String>>>,
+    stopped: AtomicBool,
+}
+
+impl FakeService {
+    /// Register a service answering dumps with `script`, under a name no other service has.
+    ///
+    /// Dumps arrive on binder threads, so the process needs
+    /// [`ProcessState::start_thread_pool`](binder::ProcessState::start_thread_pool).
+    pub fn start(script: Script) -> binder::Result<Self> {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let name = format!(
+            "dumpsys_rs.fake.{}.{}",
+            process::id(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+        let state = Arc::new(State {
+            script: Mutex::new(script),
+            calls: Mutex::default(),
+            stopped: AtomicBool::new(false),
+        });
+
+        let serving = state.clone();
+        handler::add_dump_service(&name, move |writer, args| serving.dump(writer, args))?;
+        Ok(Self { name, state })
+    }
+
+    /// The name the service is registered under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A handle to dump the service with.
+    pub fn dumpsys(&self) -> Option<Dumpsys> {
+        Dumpsys::new(&self.name)
+    }
+
+    /// Queue `reply` for a later dump, like [`Script::then`].
+    pub fn then(&self, reply: impl Into<Reply>) {
+        self.state
+            .script
+            .lock()
+            .unwrap()
+            .queue
+            .push_back(reply.into());
+    }
+
+    /// Arguments of every dump so far, in the order they arrived.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.state.calls.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FakeService {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        #[cfg(not(target_os = "android"))]
+        crate::mock::remove_service(&self.name);
+    }
+}
+
+impl State {
+    fn dump(&self, writer: &mut dyn Write, args: &[&CStr]) -> binder::Result<()> {
+        if self.stopped.load(Ordering::SeqCst) {
+            return Err(StatusCode::DEAD_OBJECT);
+        }
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let (reply, delay) = {
+            let mut script = self.script.lock().unwrap();
+            (script.next(&args), script.delay)
+        };
+        self.calls.lock().unwrap().push(args);
+
+        thread::sleep(delay);
+        reply.write(writer)
+    }
+}
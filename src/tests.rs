@@ -0,0 +1,325 @@
+use std::{
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use binder::StatusCode;
+
+use crate::{
+    error::DumpError,
+    handler,
+    mock::{self, MockService},
+    CancelToken, DumpBatch, Dumpsys, DumpsysPool, Execution, RetryPolicy, WorkerPool,
+};
+
+const NO_ARGS: [&str; 0] = [];
+
+/// Register `service` with the mock servicemanager, under a name of its own as tests run in parallel.
+fn service(name: &str, service: MockService) -> Dumpsys {
+    mock::add_service(name, service);
+    Dumpsys::new(name).unwrap()
+}
+
+/// A service writing `output` and then failing with `status` or hanging for `delay`.
+fn writing(name: &str, output: &'static [u8], status: Option<StatusCode>, delay: Duration) {
+    handler::add_dump_service(name, move |writer, _| {
+        let _ = writer.write_all(output);
+        let _ = writer.flush();
+        thread::sleep(delay);
+        status.map_or(Ok(()), Err)
+    })
+    .unwrap();
+}
+
+#[test]
+fn dump_returns_output_for_args() {
+    let dumpsys = service(
+        "tests.output",
+        MockService::new("default\n").with_args(&["--latency"], "16666666\n"),
+    );
+
+    assert_eq!(dumpsys.dump(["--latency"]).unwrap(), "16666666\n");
+    assert_eq!(dumpsys.dump(["-a"]).unwrap(), "default\n");
+}
+
+#[test]
+fn output_of_exactly_the_limit_isnt_truncated() {
+    mock::add_service("tests.limit.exact", MockService::new("0123456789"));
+    let dumpsys = Dumpsys::builder("tests.limit.exact")
+        .max_output_bytes(10)
+        .build()
+        .unwrap();
+
+    assert_eq!(dumpsys.dump(NO_ARGS).unwrap(), "0123456789");
+}
+
+#[test]
+fn output_past_the_limit_is_truncated() {
+    mock::add_service("tests.limit.over", MockService::new("0123456789"));
+    for execution in [Execution::Spawn, Execution::CurrentThread] {
+        let dumpsys = Dumpsys::builder("tests.limit.over")
+            .max_output_bytes(4)
+            .execution(execution)
+            .build()
+            .unwrap();
+
+        let err = dumpsys.dump(["-a"]).unwrap_err();
+        assert!(
+            matches!(err, DumpError::Truncated { bytes_read: 4, .. }),
+            "{err:?}"
+        );
+        assert_eq!(err.partial(), b"0123");
+        assert_eq!(err.context().args, ["-a"]);
+    }
+}
+
+#[test]
+fn slow_dump_times_out() {
+    let name = "tests.timeout";
+    writing(name, b"head\n", None, Duration::from_millis(500));
+    let dumpsys = Dumpsys::builder(name)
+        .dump_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    let err = dumpsys.dump(NO_ARGS).unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert!(matches!(err, DumpError::Timeout { .. }), "{err:?}");
+    assert_eq!(err.partial(), b"head\n");
+}
+
+#[test]
+fn cancelled_dump_stops_reading() {
+    let dumpsys = service(
+        "tests.cancel",
+        MockService::new("late\n").delay(Duration::from_millis(500)),
+    );
+    let token = CancelToken::new();
+    let cancel = token.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        cancel.cancel();
+    });
+
+    let start = Instant::now();
+    let err = dumpsys.dump_cancellable(["-a"], &token).unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert!(matches!(err, DumpError::Cancelled { .. }), "{err:?}");
+}
+
+#[test]
+fn cancelled_token_skips_the_transaction() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let counted = calls.clone();
+    let dumpsys = service(
+        "tests.cancel.before",
+        MockService::from_fn(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok(b"output".to_vec())
+        }),
+    );
+    let token = CancelToken::new();
+    token.cancel();
+
+    let err = dumpsys.dump_cancellable(["-a"], &token).unwrap_err();
+    assert!(matches!(err, DumpError::Cancelled { .. }), "{err:?}");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+/// A service failing its first `failures` dumps with `FAILED_TRANSACTION`.
+fn flaky(failures: u32) -> MockService {
+    let calls = AtomicU32::new(0);
+    MockService::from_fn(
+        move |_| match calls.fetch_add(1, Ordering::SeqCst) < failures {
+            true => Err(StatusCode::FAILED_TRANSACTION),
+            false => Ok(b"recovered\n".to_vec()),
+        },
+    )
+}
+
+#[test]
+fn failed_dump_is_retried() {
+    mock::add_service("tests.retry", flaky(2));
+    let dumpsys = Dumpsys::builder("tests.retry")
+        .retry(RetryPolicy::new(3, Duration::ZERO))
+        .build()
+        .unwrap();
+
+    assert_eq!(dumpsys.dump(NO_ARGS).unwrap(), "recovered\n");
+    assert_eq!(dumpsys.last_stats().unwrap().retries, 2);
+}
+
+#[test]
+fn retries_give_up_after_max_attempts() {
+    mock::add_service("tests.retry.exhausted", flaky(2));
+    let dumpsys = Dumpsys::builder("tests.retry.exhausted")
+        .retry(RetryPolicy::new(2, Duration::ZERO))
+        .build()
+        .unwrap();
+
+    let err = dumpsys.dump(NO_ARGS).unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::FAILED_TRANSACTION));
+    assert_eq!(dumpsys.last_stats().unwrap().retries, 1);
+}
+
+#[test]
+fn failure_after_output_is_incomplete() {
+    let name = "tests.incomplete";
+    writing(name, b"abc", Some(StatusCode::DEAD_OBJECT), Duration::ZERO);
+    let dumpsys = Dumpsys::new(name).unwrap();
+
+    let err = dumpsys.dump(NO_ARGS).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            DumpError::Incomplete {
+                status: StatusCode::DEAD_OBJECT,
+                bytes_read: 3,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert_eq!(err.partial(), b"abc");
+
+    let mut buf = String::new();
+    assert!(dumpsys.dump_into(NO_ARGS, &mut buf).is_err());
+    assert_eq!(buf, "abc");
+}
+
+#[test]
+fn failure_without_output_keeps_its_status() {
+    let dumpsys = service(
+        "tests.denied",
+        MockService::failing(StatusCode::PERMISSION_DENIED),
+    );
+
+    let err = dumpsys.dump(NO_ARGS).unwrap_err();
+    assert!(matches!(err, DumpError::PermissionDenied { .. }), "{err:?}");
+    assert!(err.partial().is_empty());
+}
+
+#[test]
+fn output_that_isnt_utf8_fails_with_the_raw_bytes() {
+    let dumpsys = service("tests.utf8", MockService::new(vec![b'a', 0xff, b'b']));
+
+    let err = dumpsys.dump(NO_ARGS).unwrap_err();
+    let DumpError::Io { source, .. } = &err else {
+        panic!("{err:?}");
+    };
+    assert_eq!(source.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.partial(), [b'a', 0xff, b'b']);
+    assert_eq!(dumpsys.dump_lossy(NO_ARGS).unwrap(), "a\u{fffd}b");
+}
+
+#[test]
+fn pid_is_the_one_servicemanager_reports() {
+    let dumpsys = service("tests.pid", MockService::new("").pid(4321));
+
+    assert_eq!(dumpsys.pid().unwrap(), 4321);
+}
+
+#[test]
+fn pool_worker_stuck_on_a_hung_service_is_replaced() {
+    let pool = WorkerPool::new(1);
+    mock::add_service(
+        "tests.pool.hung",
+        MockService::new("late\n").delay(Duration::from_millis(1500)),
+    );
+    mock::add_service("tests.pool.ok", MockService::new("ok\n"));
+    let hung = Dumpsys::builder("tests.pool.hung")
+        .execution(Execution::Pool(pool.clone()))
+        .dump_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+    let ok = Dumpsys::builder("tests.pool.ok")
+        .execution(Execution::Pool(pool))
+        .build()
+        .unwrap();
+
+    assert!(hung.dump(NO_ARGS).is_err());
+    thread::sleep(Duration::from_millis(1200));
+    assert_eq!(
+        ok.dump_with_timeout(NO_ARGS, Duration::from_millis(200))
+            .unwrap(),
+        "ok\n"
+    );
+}
+
+#[test]
+fn dropped_reader_waits_for_the_transaction() {
+    let name = "tests.reader.drop";
+    writing(name, b"abc", None, Duration::from_millis(200));
+    let dumpsys = Dumpsys::new(name).unwrap();
+    let mut reader = dumpsys.dump_reader(NO_ARGS).unwrap();
+    reader.read_exact(&mut [0; 3]).unwrap();
+
+    let start = Instant::now();
+    drop(reader);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(dumpsys.last_stats().unwrap().bytes, 3);
+}
+
+#[test]
+fn dropped_reader_waits_until_the_dump_timeout() {
+    let name = "tests.reader.drop.timeout";
+    writing(name, b"abc", None, Duration::from_millis(500));
+    let dumpsys = Dumpsys::builder(name)
+        .dump_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let mut reader = dumpsys.dump_reader(NO_ARGS).unwrap();
+    reader.read_exact(&mut [0; 3]).unwrap();
+
+    let start = Instant::now();
+    drop(reader);
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert_eq!(dumpsys.last_stats().unwrap().bytes, 3);
+}
+
+#[test]
+fn batch_keeps_every_entry_in_order() {
+    mock::add_service(
+        "tests.batch",
+        MockService::new("default\n").with_args(&["--latency"], "16666666\n"),
+    );
+    mock::add_service(
+        "tests.batch.denied",
+        MockService::failing(StatusCode::PERMISSION_DENIED),
+    );
+
+    let results = DumpBatch::new()
+        .add("tests.batch", ["--latency"])
+        .add("tests.batch.denied", NO_ARGS)
+        .add("tests.batch", NO_ARGS)
+        .run();
+    let services: Vec<_> = results
+        .iter()
+        .map(|(service, _)| service.as_str())
+        .collect();
+    assert_eq!(
+        services,
+        ["tests.batch", "tests.batch.denied", "tests.batch"]
+    );
+    assert_eq!(results[0].1.as_ref().unwrap(), "16666666\n");
+    assert!(results[1].1.is_err());
+    assert_eq!(results[2].1.as_ref().unwrap(), "default\n");
+}
+
+#[test]
+fn pool_hands_out_clones_of_one_handle() {
+    mock::add_service("tests.dumpsys_pool", MockService::new("ok\n"));
+    let pool = DumpsysPool::new();
+
+    let first = pool.get("tests.dumpsys_pool").unwrap();
+    assert_eq!(first.dump(NO_ARGS).unwrap(), "ok\n");
+    let second = pool.get("tests.dumpsys_pool").unwrap();
+    assert_eq!(second.last_stats(), first.last_stats());
+    assert!(pool.get("tests.dumpsys_pool.missing").is_none());
+}
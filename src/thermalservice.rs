@@ -0,0 +1,590 @@
+//! Typed output of `dumpsys thermalservice`, and a watcher for throttling changes
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::thermalservice::{TemperatureType, ThermalService, ThrottlingStatus};
+//!
+//! let thermal = ThermalService::parse(
+//!     "IsStatusOverride: false
+//! ThermalEventListeners:
+//! \tcallbacks: 1
+//! Thermal Status: 2
+//! Cached temperatures:
+//! \tTemperature{mValue=38.5, mType=3, mName=skin, mStatus=2}
+//! HAL Ready: true
+//! HAL connection:
+//! \tThermalHAL 2.0 connected: yes
+//! Current temperatures from HAL:
+//! \tTemperature{mValue=38.5, mType=3, mName=skin, mStatus=2}
+//! \tTemperature{mValue=61.2, mType=0, mName=cpu0, mStatus=1}
+//! Current cooling devices from HAL:
+//! \tCoolingDevice{mValue=3, mType=2, mName=cpu0}",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(thermal.status, ThrottlingStatus::Moderate);
+//! assert_eq!(thermal.hal_ready, Some(true));
+//! assert_eq!(thermal.temperatures.len(), 2);
+//! let cpu = thermal.sensor("cpu0").unwrap();
+//! assert_eq!(cpu.kind, TemperatureType::Cpu);
+//! assert_eq!(cpu.status, ThrottlingStatus::Light);
+//! assert_eq!(thermal.hottest().unwrap().value, 61.2);
+//! assert_eq!(thermal.cooling_devices[0].value, 3);
+//! ```
+
+use std::{
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    builder::Config,
+    cancel::CancelToken,
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, parse_int},
+    typed, DumpParse, Dumpsys,
+};
+
+const STATUS_OVERRIDE: &str = "IsStatusOverride: ";
+const THERMAL_STATUS: &str = "Thermal Status: ";
+const HAL_READY: &str = "HAL Ready: ";
+const CACHED: &str = "Cached temperatures:";
+const HAL_TEMPERATURES: &str = "Current temperatures from HAL:";
+const HAL_COOLING_DEVICES: &str = "Current cooling devices from HAL:";
+const TEMPERATURE: &str = "Temperature{";
+const COOLING_DEVICE: &str = "CoolingDevice{";
+
+/// `PowerManager.THERMAL_STATUS_*`, ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThrottlingStatus {
+    #[default]
+    None,
+    Light,
+    Moderate,
+    Severe,
+    Critical,
+    Emergency,
+    Shutdown,
+}
+
+impl ThrottlingStatus {
+    /// The status with the code printed by the service, `None` for codes this crate doesn't know.
+    pub fn from_code(code: i64) -> Option<Self> {
+        Some(match code {
+            0 => Self::None,
+            1 => Self::Light,
+            2 => Self::Moderate,
+            3 => Self::Severe,
+            4 => Self::Critical,
+            5 => Self::Emergency,
+            6 => Self::Shutdown,
+            _ => return None,
+        })
+    }
+}
+
+/// `Temperature.TYPE_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemperatureType {
+    Unknown,
+    Cpu,
+    Gpu,
+    Battery,
+    Skin,
+    UsbPort,
+    PowerAmplifier,
+    BclVoltage,
+    BclCurrent,
+    BclPercentage,
+    Npu,
+    Tpu,
+    Display,
+    Modem,
+    Soc,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl From<i64> for TemperatureType {
+    fn from(kind: i64) -> Self {
+        match kind {
+            -1 => Self::Unknown,
+            0 => Self::Cpu,
+            1 => Self::Gpu,
+            2 => Self::Battery,
+            3 => Self::Skin,
+            4 => Self::UsbPort,
+            5 => Self::PowerAmplifier,
+            6 => Self::BclVoltage,
+            7 => Self::BclCurrent,
+            8 => Self::BclPercentage,
+            9 => Self::Npu,
+            10 => Self::Tpu,
+            11 => Self::Display,
+            12 => Self::Modem,
+            13 => Self::Soc,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Reading of one temperature sensor
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Temperature {
+    pub name: String,
+    pub kind: TemperatureType,
+    /// Degrees Celsius
+    pub value: f64,
+    /// Throttling the HAL reports for this sensor
+    pub status: ThrottlingStatus,
+}
+
+/// State of a cooling device such as a CPU frequency limit or a fan
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoolingDevice {
+    pub name: String,
+    /// `CoolingDevice.TYPE_*`
+    pub kind: i64,
+    /// Throttling step, 0 when not throttling
+    pub value: i64,
+}
+
+/// Output of `dumpsys thermalservice`
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThermalService {
+    /// Status of the whole device, as returned by `PowerManager.getCurrentThermalStatus`
+    pub status: ThrottlingStatus,
+    /// Whether the status was forced with `cmd thermalservice override-status`
+    pub status_override: bool,
+    pub hal_ready: Option<bool>,
+    /// Temperatures the service last received from the HAL
+    pub cached: Vec<Temperature>,
+    /// Temperatures read from the HAL while dumping
+    pub temperatures: Vec<Temperature>,
+    pub cooling_devices: Vec<CoolingDevice>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Other,
+    Cached,
+    Temperatures,
+    CoolingDevices,
+}
+
+impl ThermalService {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut thermal = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if !line.starts_with(char::is_whitespace) {
+                section = match trimmed {
+                    CACHED => Section::Cached,
+                    HAL_TEMPERATURES => Section::Temperatures,
+                    HAL_COOLING_DEVICES => Section::CoolingDevices,
+                    _ => Section::Other,
+                };
+            }
+
+            if let Some(status) = trimmed.strip_prefix(THERMAL_STATUS) {
+                thermal.status = status_code(THERMAL_STATUS.trim(), status)?;
+            } else if let Some(value) = trimmed.strip_prefix(STATUS_OVERRIDE) {
+                thermal.status_override = value == "true";
+            } else if let Some(value) = trimmed.strip_prefix(HAL_READY) {
+                thermal.hal_ready = Some(value == "true");
+            } else if let Some(fields) = trimmed.strip_prefix(TEMPERATURE) {
+                let temperature = temperature(fields)?;
+                match section {
+                    Section::Cached => thermal.cached.push(temperature),
+                    Section::Temperatures => thermal.temperatures.push(temperature),
+                    _ => {}
+                }
+            } else if let Some(fields) = trimmed.strip_prefix(COOLING_DEVICE) {
+                if section == Section::CoolingDevices {
+                    thermal.cooling_devices.push(cooling_device(fields)?);
+                }
+            }
+        }
+
+        Ok(thermal)
+    }
+
+    /// Temperatures from the HAL, or the cached ones if the HAL couldn't be read.
+    pub fn sensors(&self) -> &[Temperature] {
+        if self.temperatures.is_empty() {
+            &self.cached
+        } else {
+            &self.temperatures
+        }
+    }
+
+    /// The sensor called `name`.
+    pub fn sensor(&self, name: &str) -> Option<&Temperature> {
+        self.sensors().iter().find(|sensor| sensor.name == name)
+    }
+
+    /// The sensor with the highest temperature.
+    pub fn hottest(&self) -> Option<&Temperature> {
+        self.sensors()
+            .iter()
+            .max_by(|a, b| a.value.total_cmp(&b.value))
+    }
+}
+
+impl DumpParse for ThermalService {
+    const SERVICE: &'static str = "thermalservice";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `thermalservice` and return the throttling status of the device.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::thermalservice::{self, ThrottlingStatus};
+///
+/// # fn foo() {
+/// if thermalservice::throttling_status().unwrap() >= ThrottlingStatus::Severe {
+///     println!("throttling, results will be skewed");
+/// }
+/// # }
+/// ```
+pub fn throttling_status() -> Result<ThrottlingStatus, DumpError> {
+    typed::dump_service::<ThermalService>(Vec::<&str>::new()).map(|thermal| thermal.status)
+}
+
+/// What a [`ThrottlingWatcher`] reports
+#[derive(Debug)]
+pub enum ThrottlingEvent {
+    /// The throttling status changed, also sent for the first successful dump with `previous` unset
+    Changed {
+        previous: Option<ThrottlingStatus>,
+        status: ThrottlingStatus,
+        thermal: ThermalService,
+    },
+    /// Dumping or parsing failed, the watcher keeps trying every interval
+    Error(DumpError),
+}
+
+/// Polls `thermalservice` on a background thread and reports changes of the throttling status
+///
+/// Stopping, or dropping the watcher, aborts a dump in flight and joins the thread.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpsys_rs::{thermalservice::{ThrottlingEvent, ThrottlingWatcher}, Dumpsys};
+///
+/// # fn foo() -> Option<()> {
+/// let dumpsys = Dumpsys::new("thermalservice")?;
+/// let (watcher, events) = ThrottlingWatcher::channel(dumpsys, Duration::from_secs(5));
+/// for event in events.iter().take(10) {
+///     match event {
+///         ThrottlingEvent::Changed { previous, status, .. } => println!("{previous:?} -> {status:?}"),
+///         ThrottlingEvent::Error(err) => println!("{err}"),
+///     }
+/// }
+/// watcher.stop();
+/// # Some(())
+/// # }
+/// ```
+pub struct ThrottlingWatcher {
+    stop: Option<Sender<()>>,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThrottlingWatcher {
+    /// Dump `dumpsys`, a handle to `thermalservice`, every `interval`, passing events to `callback`
+    /// until it breaks or the watcher stops.
+    pub fn new<F>(dumpsys: Dumpsys, interval: Duration, mut callback: F) -> Self
+    where
+        F: FnMut(ThrottlingEvent) -> ControlFlow<()> + Send + 'static,
+    {
+        let cancel = CancelToken::new();
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let token = cancel.clone();
+        let thread = thread::spawn(move || {
+            let dumpsys = dumpsys.with_config(Config {
+                cancel: Some(token.clone()),
+                ..(*dumpsys.config).clone()
+            });
+            let mut previous = None;
+
+            loop {
+                let event =
+                    match typed::dump_parsed::<ThermalService>(&dumpsys, ThermalService::ARGS) {
+                        Ok(thermal) if previous != Some(thermal.status) => {
+                            let status = thermal.status;
+                            Some(ThrottlingEvent::Changed {
+                                previous: previous.replace(status),
+                                status,
+                                thermal,
+                            })
+                        }
+                        Ok(_) => None,
+                        Err(err) => Some(ThrottlingEvent::Error(err)),
+                    };
+                if let Some(event) = event {
+                    if token.is_cancelled() || callback(event).is_break() {
+                        return;
+                    }
+                }
+
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    /// Dump every `interval`, sending events to the returned receiver until it's dropped.
+    pub fn channel(dumpsys: Dumpsys, interval: Duration) -> (Self, Receiver<ThrottlingEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let watcher = Self::new(dumpsys, interval, move |event| {
+            tx.send(event)
+                .map_or(ControlFlow::Break(()), ControlFlow::Continue)
+        });
+        (watcher, rx)
+    }
+
+    /// Stop watching and wait for the thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.cancel.cancel();
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ThrottlingWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn status_code(key: &str, value: &str) -> Result<ThrottlingStatus, ParseError> {
+    parse_int(value)
+        .and_then(ThrottlingStatus::from_code)
+        .ok_or_else(|| invalid(key, value))
+}
+
+/// `mValue=38.5, mType=3, mName=skin, mStatus=2}`
+fn temperature(fields: &str) -> Result<Temperature, ParseError> {
+    let fields = Fields::parse(fields);
+    let value = fields.get("mValue")?;
+    Ok(Temperature {
+        name: fields.get("mName")?.to_owned(),
+        kind: fields.int("mType")?.into(),
+        value: value.parse().map_err(|_| invalid("mValue", value))?,
+        status: status_code("mStatus", fields.get("mStatus")?)?,
+    })
+}
+
+/// `mValue=3, mType=2, mName=cpu0}`
+fn cooling_device(fields: &str) -> Result<CoolingDevice, ParseError> {
+    let fields = Fields::parse(fields);
+    Ok(CoolingDevice {
+        name: fields.get("mName")?.to_owned(),
+        kind: fields.int("mType")?,
+        value: fields.int("mValue")?,
+    })
+}
+
+/// The comma separated fields of Java's `toString` of a HAL struct
+struct Fields<'a>(Vec<(&'a str, &'a str)>);
+
+impl<'a> Fields<'a> {
+    fn parse(fields: &'a str) -> Self {
+        Self(
+            fields
+                .trim_end_matches('}')
+                .split(", ")
+                .filter_map(|field| field.split_once('='))
+                .collect(),
+        )
+    }
+
+    fn get(&self, key: &str) -> Result<&'a str, ParseError> {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| ParseError::Missing(key.to_owned()))
+    }
+
+    fn int(&self, key: &str) -> Result<i64, ParseError> {
+        let value = self.get(key)?;
+        parse_int(value).ok_or_else(|| invalid(key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys thermalservice` of Android 13 with the AIDL HAL
+    const DUMP: &str = "IsStatusOverride: false
+ThermalEventListeners:
+\tcallbacks: 2
+\tkilled: false
+\tbroadcasts count: -1
+ThermalStatusListeners:
+\tcallbacks: 5
+\tkilled: false
+\tbroadcasts count: -1
+Thermal Status: 1
+Cached temperatures:
+\tTemperature{mValue=39.686, mType=3, mName=VIRTUAL-SKIN, mStatus=1}
+\tTemperature{mValue=30.1, mType=2, mName=battery, mStatus=0}
+HAL Ready: true
+HAL connection:
+\tThermalHAL AIDL 1  connected: yes
+Current temperatures from HAL:
+\tTemperature{mValue=30.1, mType=2, mName=battery, mStatus=0}
+\tTemperature{mValue=46.52, mType=0, mName=BIG, mStatus=0}
+\tTemperature{mValue=35.011, mType=0, mName=LITTLE, mStatus=0}
+\tTemperature{mValue=33.9, mType=1, mName=G3D, mStatus=0}
+\tTemperature{mValue=39.686, mType=3, mName=VIRTUAL-SKIN, mStatus=1}
+\tTemperature{mValue=29.5, mType=10, mName=TPU, mStatus=0}
+Current cooling devices from HAL:
+\tCoolingDevice{mValue=0, mType=2, mName=fan}
+\tCoolingDevice{mValue=0, mType=3, mName=battery}
+\tCoolingDevice{mValue=1, mType=0, mName=thermal-cpufreq-0}
+Temperature static thresholds from HAL:
+\tTemperatureThreshold{mType=3, mName=VIRTUAL-SKIN, mHotThrottlingThresholds=[NaN, 39.0, 43.0, 45.0, 47.0, 50.0, 55.0], mColdThrottlingThresholds=[NaN, NaN, NaN, NaN, NaN, NaN, NaN]}
+";
+
+    #[test]
+    fn parses_the_dump() {
+        let thermal = ThermalService::parse(DUMP).unwrap();
+        assert_eq!(thermal.status, ThrottlingStatus::Light);
+        assert!(!thermal.status_override);
+        assert_eq!(thermal.hal_ready, Some(true));
+        assert_eq!(thermal.cached.len(), 2);
+        assert_eq!(thermal.temperatures.len(), 6);
+        assert_eq!(
+            thermal.sensor("VIRTUAL-SKIN"),
+            Some(&Temperature {
+                name: "VIRTUAL-SKIN".to_owned(),
+                kind: TemperatureType::Skin,
+                value: 39.686,
+                status: ThrottlingStatus::Light,
+            })
+        );
+        assert_eq!(thermal.sensor("TPU").unwrap().kind, TemperatureType::Tpu);
+        assert_eq!(thermal.hottest().unwrap().name, "BIG");
+        assert_eq!(
+            thermal.cooling_devices[2],
+            CoolingDevice {
+                name: "thermal-cpufreq-0".to_owned(),
+                kind: 0,
+                value: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn cached_temperatures_without_the_hal() {
+        let text = "IsStatusOverride: true
+Thermal Status: 3
+Cached temperatures:
+\tTemperature{mValue=44.2, mType=3, mName=skin, mStatus=3}
+HAL Ready: false
+HAL connection:
+\tNo HAL
+Current temperatures from HAL:
+Current cooling devices from HAL:
+";
+        let thermal = ThermalService::parse(text).unwrap();
+        assert!(thermal.status_override);
+        assert_eq!(thermal.status, ThrottlingStatus::Severe);
+        assert_eq!(thermal.hal_ready, Some(false));
+        assert!(thermal.temperatures.is_empty());
+        assert_eq!(thermal.sensors().len(), 1);
+        assert_eq!(thermal.hottest().unwrap().value, 44.2);
+        assert!(thermal.cooling_devices.is_empty());
+    }
+
+    #[test]
+    fn unknown_temperature_types_are_kept() {
+        let text = "Current temperatures from HAL:
+\tTemperature{mValue=41.0, mType=42, mName=camera, mStatus=0}
+\tTemperature{mValue=25.0, mType=-1, mName=ambient, mStatus=0}";
+        let thermal = ThermalService::parse(text).unwrap();
+        assert_eq!(thermal.temperatures[0].kind, TemperatureType::Other(42));
+        assert_eq!(thermal.temperatures[1].kind, TemperatureType::Unknown);
+    }
+
+    #[test]
+    fn unknown_status_is_invalid() {
+        assert_eq!(
+            ThermalService::parse("Thermal Status: 9").unwrap_err(),
+            invalid("Thermal Status:", "9")
+        );
+        let text =
+            "Cached temperatures:\n\tTemperature{mValue=44.2, mType=3, mName=skin, mStatus=7}";
+        assert_eq!(
+            ThermalService::parse(text).unwrap_err(),
+            invalid("mStatus", "7")
+        );
+    }
+
+    #[test]
+    fn truncated_temperature_is_missing_fields() {
+        let text = "Current temperatures from HAL:\n\tTemperature{mValue=46.52, mType=0, mNa";
+        assert_eq!(
+            ThermalService::parse(text).unwrap_err(),
+            ParseError::Missing("mName".to_owned())
+        );
+        let text =
+            "Current cooling devices from HAL:\n\tCoolingDevice{mValue=x, mType=2, mName=fan}";
+        assert_eq!(
+            ThermalService::parse(text).unwrap_err(),
+            invalid("mValue", "x")
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            ThermalService::parse(&DUMP.replace('\n', "\r\n")).unwrap(),
+            ThermalService::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        let thermal = ThermalService::parse("").unwrap();
+        assert_eq!(thermal, ThermalService::default());
+        assert_eq!(thermal.hottest(), None);
+    }
+}
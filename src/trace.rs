@@ -0,0 +1,612 @@
+//! Frame timings and jank from dumps as a trace, to view them next to real traces in
+//! [ui.perfetto.dev](https://ui.perfetto.dev)
+//!
+//! A [`FrameTrace`] collects the frames of SurfaceFlinger layers and gfxinfo packages, and the events of
+//! a [`JankMonitor`](crate::jank::JankMonitor), on tracks of their own:
+//!
+//! - `<layer>` holds a `Frame` slice per presented frame, from its desired to its actual present, and
+//!   `<layer> frame time` the time since the previous present. Frames shown for more than 1.5 refresh
+//!   periods get a `Janky frame` on `<layer> jank`.
+//! - `<package>` holds a `Frame` slice per frame, from its intended vsync until it was completed,
+//!   with the time of each stage as arguments, and `<package> frame time` its total.
+//! - `Jank` holds the jank and recovery of a monitor, and `fps` and `dropped frames` its stats.
+//!
+//! Frames that overlap, e.g. because the app queued several, go to `<name> (2)` and so on, as slices
+//! of one track can't overlap.
+//!
+//! [`FrameTrace::write_perfetto`] writes a Perfetto protobuf trace. Its timestamps stay in
+//! `CLOCK_MONOTONIC`, along with a snapshot of the clocks taken when the [`FrameTrace`] was created, so
+//! Perfetto lines them up with the boot time of real traces of the same device. Protobuf traces
+//! concatenate, so `cat system.perfetto-trace frames.perfetto-trace > merged.perfetto-trace` shows
+//! both on one timeline. [`FrameTrace::write_json`] writes the legacy JSON format of systrace and
+//! `chrome://tracing` instead, which can't be lined up with other traces.
+//!
+//! # Example
+//!
+//! ```
+//! use std::{fs::File, time::Duration};
+//!
+//! use dumpsys_rs::{
+//!     surfaceflinger::{FrameTiming, Latency},
+//!     trace::FrameTrace,
+//! };
+//!
+//! let latency = Latency {
+//!     refresh_period: Duration::from_nanos(16_666_667),
+//!     frames: [16, 33, 66, 83]
+//!         .map(|ms| FrameTiming {
+//!             desired_present: ms * 1_000_000,
+//!             actual_present: ms * 1_000_000 + 2_000_000,
+//!             frame_ready: ms * 1_000_000 - 4_000_000,
+//!         })
+//!         .into(),
+//! };
+//!
+//! let mut trace = FrameTrace::new();
+//! trace.add_latency("com.example.game/.MainActivity#0", &latency);
+//! let mut json = Vec::new();
+//! trace.write_json(&mut json).unwrap();
+//! assert!(String::from_utf8(json).unwrap().contains(r#""name":"Janky frame""#));
+//!
+//! # fn foo(trace: FrameTrace) {
+//! let file = File::create("/data/local/tmp/frames.perfetto-trace").unwrap();
+//! trace.write_perfetto(file).unwrap();
+//! # }
+//! ```
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    collector::quote,
+    gfxinfo::Gfxinfo,
+    jank::{JankEvent, JankStats, JANK_FACTOR},
+    surfaceflinger::Latency,
+};
+
+/// Uuid of the track grouping the others, "dumpsys" in ASCII, so they don't collide with the tracks of
+/// a trace they are merged with
+const ROOT_TRACK: u64 = 0x6475_6d70_7379_7300;
+const ROOT_NAME: &str = "dumpsys-rs";
+const SEQUENCE_ID: u64 = 1;
+/// Process id of the tracks of a JSON trace
+const PID: usize = 1;
+
+const FRAME: &str = "Frame";
+const JANKY_FRAME: &str = "Janky frame";
+const JANK_TRACK: &str = "Jank";
+
+// Field numbers of the Perfetto trace protos.
+const TRACE_PACKET: u32 = 1;
+const PACKET_TIMESTAMP: u32 = 8;
+const PACKET_SEQUENCE_ID: u32 = 10;
+const PACKET_TRACK_EVENT: u32 = 11;
+const PACKET_SEQUENCE_FLAGS: u32 = 13;
+const PACKET_CLOCK_SNAPSHOT: u32 = 6;
+const PACKET_CLOCK_ID: u32 = 58;
+const PACKET_TRACK_DESCRIPTOR: u32 = 60;
+const CLOCK_SNAPSHOT_CLOCKS: u32 = 1;
+const CLOCK_ID: u32 = 1;
+const CLOCK_TIMESTAMP: u32 = 2;
+const TRACK_UUID: u32 = 1;
+const TRACK_NAME: u32 = 2;
+const TRACK_PARENT_UUID: u32 = 5;
+const TRACK_COUNTER: u32 = 8;
+const COUNTER_UNIT: u32 = 3;
+const EVENT_DEBUG_ANNOTATIONS: u32 = 4;
+const EVENT_TYPE: u32 = 9;
+const EVENT_TRACK_UUID: u32 = 11;
+const EVENT_NAME: u32 = 23;
+const EVENT_COUNTER_VALUE: u32 = 44;
+const ANNOTATION_UINT: u32 = 3;
+const ANNOTATION_DOUBLE: u32 = 5;
+const ANNOTATION_STRING: u32 = 6;
+const ANNOTATION_NAME: u32 = 10;
+
+// Values of the Perfetto trace protos.
+const SEQ_INCREMENTAL_STATE_CLEARED: u64 = 1;
+const BUILTIN_CLOCK_MONOTONIC: u64 = 3;
+const BUILTIN_CLOCK_BOOTTIME: u64 = 6;
+const TYPE_SLICE_BEGIN: u64 = 1;
+const TYPE_SLICE_END: u64 = 2;
+const TYPE_INSTANT: u64 = 3;
+const TYPE_COUNTER: u64 = 4;
+const UNIT_TIME_NS: u64 = 1;
+const UNIT_COUNT: u64 = 2;
+
+/// Frames and jank events on named tracks, written as a Perfetto or JSON trace, see the
+/// [module docs](self)
+///
+/// Timestamps are nanoseconds of `CLOCK_MONOTONIC`, like those of the dumps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameTrace {
+    tracks: Vec<Track>,
+    events: Vec<Event>,
+    /// `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME` read at the same time
+    clocks: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Track {
+    name: String,
+    kind: TrackKind,
+    /// End of the last slice on the track
+    end: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrackKind {
+    Slices,
+    /// A counter whose values are in a Perfetto `CounterDescriptor.Unit`, if any
+    Counter(Option<u64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    track: usize,
+    timestamp: u64,
+    kind: EventKind,
+    args: Vec<(&'static str, Arg)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum EventKind {
+    Slice { name: &'static str, duration: u64 },
+    Instant { name: &'static str },
+    Counter(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Uint(u64),
+    Double(f64),
+    Str(String),
+}
+
+impl Default for FrameTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameTrace {
+    /// An empty trace, taking the snapshot of the clocks that lines it up with other traces.
+    pub fn new() -> Self {
+        Self {
+            tracks: Vec::new(),
+            events: Vec::new(),
+            clocks: clocks(),
+        }
+    }
+
+    /// Add the presented frames of `layer`, e.g. from [`surfaceflinger::latency`](crate::surfaceflinger::latency).
+    pub fn add_latency(&mut self, layer: &str, latency: &Latency) {
+        let (frame_time, jank) = (format!("{layer} frame time"), format!("{layer} jank"));
+        let janky = latency.refresh_period.as_nanos() as f64 * JANK_FACTOR;
+        let mut previous = None;
+
+        for frame in latency.presented() {
+            let start = frame.desired_present.min(frame.actual_present);
+            let track = self.slice_track(layer, start, frame.actual_present);
+            self.push(
+                track,
+                start,
+                EventKind::Slice {
+                    name: FRAME,
+                    duration: frame.actual_present - start,
+                },
+                vec![
+                    ("desired_present", Arg::Uint(frame.desired_present)),
+                    ("frame_ready", Arg::Uint(frame.frame_ready)),
+                    ("actual_present", Arg::Uint(frame.actual_present)),
+                ],
+            );
+
+            if let Some(previous) = previous {
+                let interval = frame.actual_present.saturating_sub(previous);
+                let counter =
+                    self.track(frame_time.clone(), TrackKind::Counter(Some(UNIT_TIME_NS)));
+                self.push(
+                    counter,
+                    frame.actual_present,
+                    EventKind::Counter(interval as f64),
+                    Vec::new(),
+                );
+                if janky > 0.0 && interval as f64 > janky {
+                    let jank = self.track(jank.clone(), TrackKind::Slices);
+                    self.push(
+                        jank,
+                        frame.actual_present,
+                        EventKind::Instant { name: JANKY_FRAME },
+                        vec![("frame_time", Arg::Uint(interval))],
+                    );
+                }
+            }
+            previous = Some(frame.actual_present);
+        }
+    }
+
+    /// Add the frames of `package`, e.g. from [`Gfxinfo::for_package`], invalid ones included.
+    pub fn add_gfxinfo(&mut self, package: &str, gfxinfo: &Gfxinfo) {
+        let frame_time = format!("{package} frame time");
+
+        for frame in &gfxinfo.all_frames {
+            let (start, total) = (frame.intended_vsync, frame.total.as_nanos() as u64);
+            let end = start.saturating_add(total);
+            let track = self.slice_track(package, start, end);
+            let mut args = vec![
+                ("flags", Arg::Uint(frame.flags)),
+                ("input", nanos(frame.input)),
+                ("animation", nanos(frame.animation)),
+                ("traversal", nanos(frame.traversal)),
+                ("draw", nanos(frame.draw)),
+                ("sync", nanos(frame.sync)),
+                ("command_issue", nanos(frame.command_issue)),
+            ];
+            if let Some(gpu) = frame.gpu {
+                args.push(("gpu", nanos(gpu)));
+            }
+            self.push(
+                track,
+                start,
+                EventKind::Slice {
+                    name: FRAME,
+                    duration: total,
+                },
+                args,
+            );
+            let counter = self.track(frame_time.clone(), TrackKind::Counter(Some(UNIT_TIME_NS)));
+            self.push(counter, end, EventKind::Counter(total as f64), Vec::new());
+        }
+    }
+
+    /// Add an event of a [`JankMonitor`](crate::jank::JankMonitor) at `timestamp`, e.g. the
+    /// `actual_present` of the last frame of the interval. Errors are skipped.
+    pub fn add_jank(&mut self, timestamp: u64, event: &JankEvent) {
+        let (stats, instant) = match event {
+            JankEvent::Stats(stats) => (stats, None),
+            JankEvent::Jank { stats, threshold } => {
+                (stats, Some(("Jank", format!("{threshold:?}"))))
+            }
+            JankEvent::Recovered(stats) => (stats, Some(("Recovered", String::new()))),
+            JankEvent::Error(_) => return,
+        };
+        self.jank_stats(timestamp, stats);
+
+        if let Some((name, threshold)) = instant {
+            let track = self.track(JANK_TRACK.to_owned(), TrackKind::Slices);
+            let mut args = vec![
+                ("fps", Arg::Double(stats.fps)),
+                ("janky_frames", Arg::Uint(stats.janky_frames as u64)),
+                ("p99", nanos(stats.p99)),
+            ];
+            if !threshold.is_empty() {
+                args.push(("threshold", Arg::Str(threshold)));
+            }
+            self.push(track, timestamp, EventKind::Instant { name }, args);
+        }
+    }
+
+    /// Whether nothing was added yet.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Write the trace in the protobuf format of Perfetto, e.g. to a `.perfetto-trace` file.
+    pub fn write_perfetto(&self, mut out: impl Write) -> io::Result<()> {
+        let mut trace = Proto::default();
+        let clock = self.clocks.map(|(monotonic, boottime)| {
+            trace.message(TRACE_PACKET, |packet| {
+                packet.message(PACKET_CLOCK_SNAPSHOT, |snapshot| {
+                    for (id, timestamp) in [
+                        (BUILTIN_CLOCK_MONOTONIC, monotonic),
+                        (BUILTIN_CLOCK_BOOTTIME, boottime),
+                    ] {
+                        snapshot.message(CLOCK_SNAPSHOT_CLOCKS, |clock| {
+                            clock.varint(CLOCK_ID, id);
+                            clock.varint(CLOCK_TIMESTAMP, timestamp);
+                        });
+                    }
+                });
+            });
+            BUILTIN_CLOCK_MONOTONIC
+        });
+
+        trace.message(TRACE_PACKET, |packet| {
+            packet.varint(PACKET_SEQUENCE_ID, SEQUENCE_ID);
+            packet.varint(PACKET_SEQUENCE_FLAGS, SEQ_INCREMENTAL_STATE_CLEARED);
+            packet.message(PACKET_TRACK_DESCRIPTOR, |track| {
+                track.varint(TRACK_UUID, ROOT_TRACK);
+                track.string(TRACK_NAME, ROOT_NAME);
+            });
+        });
+        for (i, track) in self.tracks.iter().enumerate() {
+            trace.message(TRACE_PACKET, |packet| {
+                packet.varint(PACKET_SEQUENCE_ID, SEQUENCE_ID);
+                packet.message(PACKET_TRACK_DESCRIPTOR, |descriptor| {
+                    descriptor.varint(TRACK_UUID, track_uuid(i));
+                    descriptor.varint(TRACK_PARENT_UUID, ROOT_TRACK);
+                    descriptor.string(TRACK_NAME, &track.name);
+                    if let TrackKind::Counter(unit) = track.kind {
+                        descriptor.message(TRACK_COUNTER, |counter| {
+                            if let Some(unit) = unit {
+                                counter.varint(COUNTER_UNIT, unit);
+                            }
+                        });
+                    }
+                });
+            });
+        }
+
+        for event in &self.events {
+            let packet = |trace: &mut Proto, timestamp: u64, write: &dyn Fn(&mut Proto)| {
+                trace.message(TRACE_PACKET, |packet| {
+                    packet.varint(PACKET_TIMESTAMP, timestamp);
+                    if let Some(clock) = clock {
+                        packet.varint(PACKET_CLOCK_ID, clock);
+                    }
+                    packet.varint(PACKET_SEQUENCE_ID, SEQUENCE_ID);
+                    packet.message(PACKET_TRACK_EVENT, |track_event| {
+                        track_event.varint(EVENT_TRACK_UUID, track_uuid(event.track));
+                        write(track_event);
+                    });
+                });
+            };
+            let named = |track_event: &mut Proto, kind: u64, name: &str| {
+                track_event.varint(EVENT_TYPE, kind);
+                track_event.string(EVENT_NAME, name);
+                for (name, arg) in &event.args {
+                    track_event.message(EVENT_DEBUG_ANNOTATIONS, |annotation| {
+                        annotation.string(ANNOTATION_NAME, name);
+                        match arg {
+                            Arg::Uint(value) => annotation.varint(ANNOTATION_UINT, *value),
+                            Arg::Double(value) => annotation.double(ANNOTATION_DOUBLE, *value),
+                            Arg::Str(value) => annotation.string(ANNOTATION_STRING, value),
+                        }
+                    });
+                }
+            };
+
+            match event.kind {
+                EventKind::Slice { name, duration } => {
+                    packet(&mut trace, event.timestamp, &|track_event| {
+                        named(track_event, TYPE_SLICE_BEGIN, name)
+                    });
+                    packet(&mut trace, event.timestamp + duration, &|track_event| {
+                        track_event.varint(EVENT_TYPE, TYPE_SLICE_END)
+                    });
+                }
+                EventKind::Instant { name } => {
+                    packet(&mut trace, event.timestamp, &|track_event| {
+                        named(track_event, TYPE_INSTANT, name)
+                    });
+                }
+                EventKind::Counter(value) => {
+                    packet(&mut trace, event.timestamp, &|track_event| {
+                        track_event.varint(EVENT_TYPE, TYPE_COUNTER);
+                        track_event.double(EVENT_COUNTER_VALUE, value);
+                    });
+                }
+            }
+        }
+
+        out.write_all(&trace.0)?;
+        out.flush()
+    }
+
+    /// Write the trace in the JSON format of systrace and `chrome://tracing`, with one thread per
+    /// track.
+    pub fn write_json(&self, mut out: impl Write) -> io::Result<()> {
+        let mut json = String::from("{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n");
+        let _ = write!(
+            json,
+            "{{\"ph\":\"M\",\"pid\":{PID},\"name\":\"process_name\",\"args\":{{\"name\":{}}}}}",
+            quote(ROOT_NAME)
+        );
+        // Counters are named after their track instead.
+        let threads = self.tracks.iter().enumerate();
+        for (i, track) in threads.filter(|(_, track)| track.kind == TrackKind::Slices) {
+            let _ = write!(
+                json,
+                ",\n{{\"ph\":\"M\",\"pid\":{PID},\"tid\":{},\"name\":\"thread_name\",\"args\":{{\"name\":{}}}}}",
+                i + 1,
+                quote(&track.name)
+            );
+        }
+
+        for event in &self.events {
+            let (tid, ts) = (event.track + 1, micros(event.timestamp));
+            let _ = match event.kind {
+                EventKind::Slice { name, duration } => write!(
+                    json,
+                    ",\n{{\"ph\":\"X\",\"pid\":{PID},\"tid\":{tid},\"ts\":{ts},\"dur\":{},\"name\":{},\"args\":{}}}",
+                    micros(duration),
+                    quote(name),
+                    json_args(&event.args)
+                ),
+                EventKind::Instant { name } => write!(
+                    json,
+                    ",\n{{\"ph\":\"i\",\"s\":\"t\",\"pid\":{PID},\"tid\":{tid},\"ts\":{ts},\"name\":{},\"args\":{}}}",
+                    quote(name),
+                    json_args(&event.args)
+                ),
+                EventKind::Counter(value) => write!(
+                    json,
+                    ",\n{{\"ph\":\"C\",\"pid\":{PID},\"ts\":{ts},\"name\":{},\"args\":{{\"value\":{}}}}}",
+                    quote(&self.tracks[event.track].name),
+                    json_number(value)
+                ),
+            };
+        }
+        json.push_str("\n]}\n");
+
+        out.write_all(json.as_bytes())?;
+        out.flush()
+    }
+
+    fn jank_stats(&mut self, timestamp: u64, stats: &JankStats) {
+        let fps = self.track("fps".to_owned(), TrackKind::Counter(None));
+        let dropped = self.track(
+            "dropped frames".to_owned(),
+            TrackKind::Counter(Some(UNIT_COUNT)),
+        );
+        self.push(fps, timestamp, EventKind::Counter(stats.fps), Vec::new());
+        self.push(
+            dropped,
+            timestamp,
+            EventKind::Counter(stats.dropped_frames as f64),
+            Vec::new(),
+        );
+    }
+
+    /// Index of the track `name`, added if there is none.
+    fn track(&mut self, name: String, kind: TrackKind) -> usize {
+        if let Some(i) = self.tracks.iter().position(|track| track.name == name) {
+            return i;
+        }
+        self.tracks.push(Track { name, kind, end: 0 });
+        self.tracks.len() - 1
+    }
+
+    /// The first of the tracks `name`, `name (2)` and so on that a slice from `start` to `end` doesn't
+    /// overlap the slices of.
+    fn slice_track(&mut self, name: &str, start: u64, end: u64) -> usize {
+        for lane in 1.. {
+            let name = match lane {
+                1 => name.to_owned(),
+                lane => format!("{name} ({lane})"),
+            };
+            let track = self.track(name, TrackKind::Slices);
+            if self.tracks[track].end <= start {
+                self.tracks[track].end = end;
+                return track;
+            }
+        }
+        unreachable!("a lane is free past the last one")
+    }
+
+    fn push(
+        &mut self,
+        track: usize,
+        timestamp: u64,
+        kind: EventKind,
+        args: Vec<(&'static str, Arg)>,
+    ) {
+        self.events.push(Event {
+            track,
+            timestamp,
+            kind,
+            args,
+        });
+    }
+}
+
+fn nanos(duration: Duration) -> Arg {
+    Arg::Uint(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX))
+}
+
+fn track_uuid(track: usize) -> u64 {
+    ROOT_TRACK + 1 + track as u64
+}
+
+/// `nanos` in microseconds, the unit of JSON timestamps.
+fn micros(nanos: u64) -> String {
+    format!("{}.{:03}", nanos / 1000, nanos % 1000)
+}
+
+/// `value` as a JSON number, which can't be infinite or NaN.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "0".to_owned()
+    }
+}
+
+fn json_args(args: &[(&str, Arg)]) -> String {
+    let mut json = String::from("{");
+    for (i, (name, arg)) in args.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str(&quote(name));
+        json.push(':');
+        match arg {
+            Arg::Uint(value) => json.push_str(&value.to_string()),
+            Arg::Double(value) => json.push_str(&json_number(*value)),
+            Arg::Str(value) => json.push_str(&quote(value)),
+        }
+    }
+    json.push('}');
+    json
+}
+
+/// `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME` now, in nanoseconds.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn clocks() -> Option<(u64, u64)> {
+    let read = |clock| {
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `now` is valid for writes.
+        if unsafe { libc::clock_gettime(clock, &mut now) } < 0 {
+            return None;
+        }
+        Some(u64::try_from(now.tv_sec).ok()? * 1_000_000_000 + u64::try_from(now.tv_nsec).ok()?)
+    };
+    Some((read(libc::CLOCK_MONOTONIC)?, read(libc::CLOCK_BOOTTIME)?))
+}
+
+/// Timestamps are left in the clock of the trace, where there is no `CLOCK_BOOTTIME`.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn clocks() -> Option<(u64, u64)> {
+    None
+}
+
+/// A protobuf message in the making, with just the wire types a trace needs
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+impl Proto {
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.raw_varint(u64::from(field) << 3 | u64::from(wire_type));
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u32, value: u64) {
+        self.key(field, 0);
+        self.raw_varint(value);
+    }
+
+    fn double(&mut self, field: u32, value: f64) {
+        self.key(field, 1);
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.key(field, 2);
+        self.raw_varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn message(&mut self, field: u32, write: impl FnOnce(&mut Self)) {
+        let mut message = Self::default();
+        write(&mut message);
+        self.bytes(field, &message.0);
+    }
+}
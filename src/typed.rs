@@ -0,0 +1,103 @@
+use std::marker::PhantomData;
+
+use crate::{
+    error::{DumpContext, DumpError},
+    owned_args, DumpParse, Dumpsys,
+};
+
+/// A [`Dumpsys`] of the service parsed by `T`, returning dumps as `T`
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{error::ParseError, parse::kv::KeyValues, DumpParse, TypedDumpsys};
+///
+/// struct Power {
+///     wakefulness: String,
+/// }
+///
+/// impl DumpParse for Power {
+///     const SERVICE: &'static str = "power";
+///
+///     fn parse(text: &str) -> Result<Self, ParseError> {
+///         Ok(Self {
+///             wakefulness: KeyValues::parse(text).require("mWakefulness")?.to_owned(),
+///         })
+///     }
+/// }
+///
+/// # fn foo() -> Option<()> {
+/// let power = TypedDumpsys::<Power>::new()?.dump_parsed().unwrap();
+/// println!("{}", power.wakefulness);
+/// # Some(())
+/// # }
+/// ```
+pub struct TypedDumpsys<T> {
+    dumpsys: Dumpsys,
+    parse: PhantomData<fn() -> T>,
+}
+
+impl<T: DumpParse> TypedDumpsys<T> {
+    /// Connect to [`T::SERVICE`](DumpParse::SERVICE) like [`Dumpsys::new`].
+    pub fn new() -> Option<Self> {
+        Dumpsys::new(T::SERVICE).map(Self::from_dumpsys)
+    }
+
+    /// Parse the dumps of `dumpsys`, e.g. one configured through [`Dumpsys::builder`].
+    pub fn from_dumpsys(dumpsys: Dumpsys) -> Self {
+        Self {
+            dumpsys,
+            parse: PhantomData,
+        }
+    }
+
+    /// Dump with [`T::ARGS`](DumpParse::ARGS) and parse the output.
+    pub fn dump_parsed(&self) -> Result<T, DumpError> {
+        self.dump_parsed_with(T::ARGS)
+    }
+
+    /// Dump with `args` instead of [`T::ARGS`](DumpParse::ARGS), which must produce output `T` can parse.
+    pub fn dump_parsed_with(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<T, DumpError> {
+        dump_parsed(&self.dumpsys, args)
+    }
+
+    /// The untyped handle, for raw dumps.
+    pub fn dumpsys(&self) -> &Dumpsys {
+        &self.dumpsys
+    }
+
+    pub fn into_inner(self) -> Dumpsys {
+        self.dumpsys
+    }
+}
+
+/// Dump `dumpsys` with `args` and parse the output as `T`, with the dump as context of parse errors.
+pub(crate) fn dump_parsed<T: DumpParse>(
+    dumpsys: &Dumpsys,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<T, DumpError> {
+    let args = owned_args(args);
+    let output = dumpsys.dump(&args)?;
+    T::parse(&output).map_err(|err| DumpError::from(err).with_context(&dumpsys.service_name, &args))
+}
+
+/// Look up [`T::SERVICE`](DumpParse::SERVICE) and parse its dump with `args` as `T`.
+pub(crate) fn dump_service<T: DumpParse>(
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<T, DumpError> {
+    let args = owned_args(args);
+    dump_parsed(&connect(T::SERVICE, &args)?, args)
+}
+
+/// Look up `service`, failing with [`DumpError::ServiceNotFound`] for a dump with `args`.
+pub(crate) fn connect(service: &str, args: &[String]) -> Result<Dumpsys, DumpError> {
+    Dumpsys::new(service).ok_or_else(|| DumpError::ServiceNotFound {
+        context: DumpContext {
+            service: service.to_owned(),
+            args: args.to_vec(),
+        },
+    })
+}
@@ -0,0 +1,138 @@
+use std::{
+    io, mem,
+    os::fd::RawFd,
+    time::{Duration, Instant},
+};
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+const READ: u64 = 0;
+const TIMEOUT: u64 = 1;
+const CANCEL: u64 = 2;
+
+/// Size of the buffer the kernel reads into, the default capacity of a pipe
+const BUF_LEN: usize = 64 * 1024;
+
+/// An io_uring instance reading one dump pipe
+///
+/// The kernel reads into a buffer the ring owns rather than into the caller's, so a read left in
+/// flight by a failed wait never writes to memory that was handed back.
+pub(crate) struct Ring {
+    ring: IoUring,
+    buf: Box<[u8]>,
+    /// A read was submitted and its completion not reaped yet, so the kernel may still write to `buf`
+    in_flight: bool,
+}
+
+impl Ring {
+    /// `None` if the kernel doesn't support io_uring or it's blocked, e.g. by seccomp.
+    pub(crate) fn new() -> Option<Self> {
+        IoUring::new(4).ok().map(|ring| Self {
+            ring,
+            buf: vec![0; BUF_LEN].into_boxed_slice(),
+            in_flight: false,
+        })
+    }
+
+    /// Read from `fd`, failing with [`io::ErrorKind::TimedOut`] after `deadline` through a linked timeout.
+    pub(crate) fn read(
+        &mut self,
+        fd: RawFd,
+        buf: &mut [u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        if self.in_flight {
+            return Err(io::Error::other(
+                "io_uring read of an earlier call still in flight",
+            ));
+        }
+
+        let len = buf.len().min(self.buf.len());
+        // Pipes have no offset, -1 reads at the current position.
+        let read = opcode::Read::new(types::Fd(fd), self.buf.as_mut_ptr(), len as u32)
+            .offset(u64::MAX)
+            .build()
+            .user_data(READ);
+
+        let timeout = deadline.map(|deadline| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            types::Timespec::from(remaining.max(Duration::from_millis(1)))
+        });
+
+        // SAFETY: `self.buf` isn't used or freed while the read is in flight, and `timeout` outlives
+        // the submission, which is waited on before returning.
+        unsafe {
+            let mut submission = self.ring.submission();
+            match &timeout {
+                Some(timeout) => submission.push_multiple(&[
+                    read.flags(squeue::Flags::IO_LINK),
+                    opcode::LinkTimeout::new(timeout).build().user_data(TIMEOUT),
+                ]),
+                None => submission.push(&read),
+            }
+            .map_err(io::Error::other)?;
+        }
+        self.in_flight = true;
+
+        let result = self.wait();
+        if self.in_flight {
+            // Waiting failed with the read still submitted.
+            self.cancel();
+        }
+
+        let n = result??;
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        Ok(n)
+    }
+
+    /// Wait for the read in flight to complete and return its result.
+    fn wait(&mut self) -> io::Result<io::Result<usize>> {
+        loop {
+            match self.ring.submit_and_wait(1) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => result?,
+            };
+            if let Some(result) = self.reap() {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Take the completions, returning the result of the read once it completed.
+    ///
+    /// Completions of linked timeouts and cancellations arriving later are dropped by the next call.
+    fn reap(&mut self) -> Option<io::Result<usize>> {
+        let mut result = None;
+        for entry in self.ring.completion() {
+            if entry.user_data() == READ {
+                result = Some(match entry.result() {
+                    n if n >= 0 => Ok(n as usize),
+                    n if -n == libc::ECANCELED => Err(io::ErrorKind::TimedOut.into()),
+                    n => Err(io::Error::from_raw_os_error(-n)),
+                });
+            }
+        }
+        self.in_flight &= result.is_none();
+        result
+    }
+
+    /// Cancel the read in flight and wait for it to complete.
+    ///
+    /// If the ring keeps failing the read stays in flight: further reads fail, and `buf` is leaked on
+    /// drop rather than freed under the kernel.
+    fn cancel(&mut self) {
+        let cancel = opcode::AsyncCancel::new(READ).build().user_data(CANCEL);
+        // SAFETY: the cancellation refers to no memory.
+        if unsafe { self.ring.submission().push(&cancel) }.is_ok() {
+            let _ = self.wait();
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        if self.in_flight {
+            mem::forget(mem::take(&mut self.buf));
+        }
+    }
+}
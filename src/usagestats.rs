@@ -0,0 +1,492 @@
+//! Typed output of `dumpsys usagestats`
+//!
+//! Only the in-memory stats of the first user are read. Timestamps are kept as printed, in the local
+//! time of the device.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::usagestats::{Interval, StandbyBucket, UsageStats};
+//!
+//! let stats = UsageStats::parse(
+//!     r#"user=0
+//! Last 24 hour events (timeRange="2024-01-01 12:00:00 - 2024-01-02 12:00:00")
+//!     time="2024-01-02 11:59:00" type=ACTIVITY_RESUMED package=com.example.app class=com.example.app.MainActivity
+//! In-memory daily stats
+//! timeRange="2024-01-02 00:00:00 - 2024-01-02 12:00:00"
+//!   packages
+//!     package=com.example.app totalTimeUsed="01:02:03" lastTimeUsed="2024-01-02 11:59:00" totalTimeVisible="01:05:00" lastTimeVisible="2024-01-02 11:59:30" appLaunchCount=3
+//!     package=com.android.launcher totalTimeUsed="05:10" lastTimeUsed="2024-01-02 11:58:00" appLaunchCount=0
+//!   ChooserCounts
+//! In-memory weekly stats
+//! timeRange="2023-12-31 00:00:00 - 2024-01-02 12:00:00"
+//!   packages
+//!     package=com.example.app totalTimeUsed="10:00:00" lastTimeUsed="2024-01-02 11:59:00" appLaunchCount=20
+//!
+//! App Standby States:
+//!  Apps:
+//!   package=com.example.app u=0 bucket=10 reason=u-mu lastUsedElapsed=+1m2s idle=n"#,
+//! )
+//! .unwrap();
+//!
+//! let daily = stats.package_usage(Interval::Daily, "com.example.app").unwrap();
+//! assert_eq!(daily.foreground_time, Duration::from_secs(3723));
+//! assert_eq!(daily.visible_time, Some(Duration::from_secs(3900)));
+//! assert_eq!(daily.last_time_used.as_deref(), Some("2024-01-02 11:59:00"));
+//! assert_eq!(daily.launch_count, Some(3));
+//! assert_eq!(stats.bucket(Interval::Daily).unwrap().packages[1].foreground_time, Duration::from_secs(310));
+//! assert_eq!(stats.package_usage(Interval::Weekly, "com.example.app").unwrap().launch_count, Some(20));
+//! assert_eq!(stats.standby_bucket("com.example.app", 0), Some(StandbyBucket::Active));
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, KeyValues},
+    typed, DumpParse,
+};
+
+const IN_MEMORY: &str = "In-memory ";
+const TIME_RANGE: &str = "timeRange=";
+const PACKAGES: &str = "packages";
+
+/// Length of the buckets usage is aggregated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interval {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Interval {
+    /// `daily` in `In-memory daily stats`.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            "monthly" => Some(Self::Monthly),
+            "yearly" => Some(Self::Yearly),
+            _ => None,
+        }
+    }
+}
+
+/// Usage of one package in a bucket
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageUsage {
+    pub package: String,
+    /// Time an activity of the package was in the foreground
+    pub foreground_time: Duration,
+    pub last_time_used: Option<String>,
+    /// Time an activity of the package was visible, on Android 10 and later
+    pub visible_time: Option<Duration>,
+    pub last_time_visible: Option<String>,
+    pub launch_count: Option<u32>,
+}
+
+/// Usage aggregated over one interval
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsageBucket {
+    pub interval: Interval,
+    /// As printed, as in `2024-01-02 00:00:00 - 2024-01-02 12:00:00`
+    pub time_range: Option<String>,
+    pub packages: Vec<PackageUsage>,
+}
+
+/// `UsageStatsManager.STANDBY_BUCKET_*`, which limits how often an app may run jobs and alarms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StandbyBucket {
+    Exempted,
+    Active,
+    WorkingSet,
+    Frequent,
+    Rare,
+    Restricted,
+    Never,
+    /// A value this crate doesn't know
+    Other(i64),
+}
+
+impl StandbyBucket {
+    /// The bucket named as in `ACTIVE` or `WORKING_SET`, as other services print it.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "EXEMPTED" => Self::Exempted,
+            "ACTIVE" => Self::Active,
+            "WORKING_SET" => Self::WorkingSet,
+            "FREQUENT" => Self::Frequent,
+            "RARE" => Self::Rare,
+            "RESTRICTED" => Self::Restricted,
+            "NEVER" => Self::Never,
+            _ => return None,
+        })
+    }
+}
+
+impl From<i64> for StandbyBucket {
+    fn from(bucket: i64) -> Self {
+        match bucket {
+            5 => Self::Exempted,
+            10 => Self::Active,
+            20 => Self::WorkingSet,
+            30 => Self::Frequent,
+            40 => Self::Rare,
+            45 => Self::Restricted,
+            50 => Self::Never,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Standby bucket of a package for one user
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AppStandby {
+    pub package: String,
+    pub user: u32,
+    pub bucket: StandbyBucket,
+    /// Why the app is in the bucket, such as `u-mu` for user interaction
+    pub reason: Option<String>,
+}
+
+/// Output of `dumpsys usagestats`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsageStats {
+    pub buckets: Vec<UsageBucket>,
+    pub standby: Vec<AppStandby>,
+}
+
+impl UsageStats {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut stats = Self::default();
+        let mut bucket: Option<UsageBucket> = None;
+        let mut in_packages = false;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            let fields = KeyValues::parse(trimmed);
+
+            if let Some(interval) = trimmed
+                .strip_prefix(IN_MEMORY)
+                .and_then(|rest| rest.split_whitespace().next())
+            {
+                stats.buckets.extend(bucket.take());
+                in_packages = false;
+                // Later users repeat the intervals.
+                let interval = Interval::from_name(interval);
+                if interval.is_some_and(|interval| stats.bucket(interval).is_none()) {
+                    bucket = interval.map(|interval| UsageBucket {
+                        interval,
+                        time_range: None,
+                        packages: Vec::new(),
+                    });
+                }
+                continue;
+            }
+
+            if fields.contains("bucket") && trimmed.starts_with("package=") {
+                stats.standby.push(standby(&fields, trimmed)?);
+                continue;
+            }
+            let Some(bucket) = &mut bucket else {
+                continue;
+            };
+            if trimmed.starts_with(TIME_RANGE) && bucket.time_range.is_none() {
+                bucket.time_range = fields
+                    .get("timeRange")
+                    .map(|range| unquote(range).to_owned());
+            } else if !line.starts_with("    ") {
+                in_packages = trimmed == PACKAGES;
+            } else if in_packages && trimmed.starts_with("package=") {
+                bucket.packages.push(package_usage(&fields, trimmed)?);
+            }
+        }
+        stats.buckets.extend(bucket);
+
+        Ok(stats)
+    }
+
+    pub fn bucket(&self, interval: Interval) -> Option<&UsageBucket> {
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.interval == interval)
+    }
+
+    /// Usage of `package` in the current bucket of `interval`.
+    pub fn package_usage(&self, interval: Interval, package: &str) -> Option<&PackageUsage> {
+        self.bucket(interval)?
+            .packages
+            .iter()
+            .find(|usage| usage.package == package)
+    }
+
+    pub fn standby_bucket(&self, package: &str, user: u32) -> Option<StandbyBucket> {
+        self.standby
+            .iter()
+            .find(|standby| standby.package == package && standby.user == user)
+            .map(|standby| standby.bucket)
+    }
+}
+
+impl DumpParse for UsageStats {
+    const SERVICE: &'static str = "usagestats";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `usagestats` and return the standby buckets of all packages.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// for app in dumpsys_rs::usagestats::standby_buckets().unwrap() {
+///     println!("{} u{}: {:?}", app.package, app.user, app.bucket);
+/// }
+/// # }
+/// ```
+pub fn standby_buckets() -> Result<Vec<AppStandby>, DumpError> {
+    typed::dump_service::<UsageStats>(Vec::<&str>::new()).map(|stats| stats.standby)
+}
+
+/// `package=com.example.app totalTimeUsed="01:02:03" lastTimeUsed="2024-01-02 11:59:00" appLaunchCount=3`
+fn package_usage(fields: &KeyValues<'_>, line: &str) -> Result<PackageUsage, ParseError> {
+    // Android 9 and earlier print `totalTime` and `lastTime`.
+    let time = |keys: &[&str]| keys.iter().find_map(|key| fields.get(key)).map(unquote);
+    let duration = |keys: &[&str]| {
+        time(keys)
+            .map(|value| elapsed_time(value).ok_or_else(|| invalid(keys[0], value)))
+            .transpose()
+    };
+
+    Ok(PackageUsage {
+        package: fields.require("package")?.to_owned(),
+        foreground_time: duration(&["totalTimeUsed", "totalTime"])?
+            .ok_or_else(|| invalid("totalTimeUsed", line))?,
+        last_time_used: time(&["lastTimeUsed", "lastTime"]).map(str::to_owned),
+        visible_time: duration(&["totalTimeVisible"])?,
+        last_time_visible: time(&["lastTimeVisible"]).map(str::to_owned),
+        launch_count: fields.get_parsed("appLaunchCount").ok(),
+    })
+}
+
+/// `package=com.example.app u=0 bucket=10 reason=u-mu`
+fn standby(fields: &KeyValues<'_>, line: &str) -> Result<AppStandby, ParseError> {
+    Ok(AppStandby {
+        package: fields.require("package")?.to_owned(),
+        user: fields.get_parsed("u").map_err(|_| invalid("u", line))?,
+        bucket: fields.get_int("bucket")?.into(),
+        reason: fields.get("reason").map(str::to_owned),
+    })
+}
+
+/// `01:02:03` or `05:10`, as printed by `DateUtils.formatElapsedTime`.
+fn elapsed_time(text: &str) -> Option<Duration> {
+    let mut secs = 0;
+    for part in text.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim_matches('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys usagestats` of Android 14, cut down to a few packages of two users
+    const DUMP: &str = r#"user=0
+Last 24 hour events (timeRange="2024-03-11 09:12:44 - 2024-03-12 09:12:44")
+    time="2024-03-12 09:10:02" type=ACTIVITY_RESUMED package=com.android.chrome class=org.chromium.chrome.browser.ChromeTabbedActivity instanceId=161586911 taskRootPackage=com.android.chrome taskRootClass=org.chromium.chrome.browser.ChromeTabbedActivity
+    time="2024-03-12 09:11:40" type=STANDBY_BUCKET_CHANGED package=com.example.app standbyBucket=40 reason=t
+In-memory daily stats
+timeRange="2024-03-12 00:00:00 - 2024-03-12 09:12:44"
+  packages
+    package=com.android.chrome totalTimeUsed="01:12:45" lastTimeUsed="2024-03-12 09:10:02" totalTimeVisible="01:15:03" lastTimeVisible="2024-03-12 09:10:05" lastTimeComponentUsed="2024-03-12 09:10:02" totalTimeFS="00:00" lastTimeFS="1970-01-01 00:00:00" appLaunchCount=7
+    package=com.google.android.apps.nexuslauncher totalTimeUsed="12:03" lastTimeUsed="2024-03-12 09:09:58" totalTimeVisible="14:51" lastTimeVisible="2024-03-12 09:10:01" lastTimeComponentUsed="1970-01-01 00:00:00" totalTimeFS="00:00" lastTimeFS="1970-01-01 00:00:00" appLaunchCount=0
+  ChooserCounts
+    com.android.chrome
+      text/plain:android.intent.action.SEND=2
+  configurations
+    config=en-rUS-ldltr-sw411dp-w411dp-h842dp-normal-long-notround-lowdr-nowidecg-port-notnight-420dpi-finger-keysexposed-nokeys-navhidden-nonav totalTime="01:27:36" lastTime="2024-03-12 09:10:02" count=12
+  event aggregations
+    screen-interactive count=5 time="01:20:00"
+In-memory weekly stats
+timeRange="2024-03-10 00:00:00 - 2024-03-12 09:12:44"
+  packages
+    package=com.android.chrome totalTimeUsed="05:31:10" lastTimeUsed="2024-03-12 09:10:02" totalTimeVisible="05:48:12" lastTimeVisible="2024-03-12 09:10:05" appLaunchCount=41
+In-memory monthly stats
+timeRange="2024-02-25 00:00:00 - 2024-03-12 09:12:44"
+  packages
+In-memory yearly stats
+timeRange="2023-12-31 00:00:00 - 2024-03-12 09:12:44"
+  packages
+    package=com.android.chrome totalTimeUsed="103:12:00" lastTimeUsed="2024-03-12 09:10:02" appLaunchCount=912
+user=10
+In-memory daily stats
+timeRange="2024-03-12 00:00:00 - 2024-03-12 09:12:44"
+  packages
+    package=com.android.chrome totalTimeUsed="00:12" lastTimeUsed="2024-03-12 08:00:00" appLaunchCount=1
+
+App Standby States:
+ User 0
+  package=com.android.chrome u=0 bucket=10 reason=u-mu lastUsedElapsed=+5m23s lastUsedScreenOn=+2m lastPredictedTime=-- lastJob=-- lastInformedBucket=10 lastRestrictAttempt=-- lastRestrictReason=0000 idle=n totalElapsed=+10d2h totalScreenOn=+3d1h
+  package=com.example.app u=0 bucket=40 reason=t lastUsedElapsed=+3d4h lastUsedScreenOn=+1d lastPredictedTime=-- lastJob=-- lastInformedBucket=40 idle=y totalElapsed=+10d2h totalScreenOn=+3d1h
+  package=com.android.shell u=0 bucket=5 reason=s lastUsedElapsed=-- idle=n
+ User 10
+  package=com.android.chrome u=10 bucket=45 reason=d-u lastUsedElapsed=+9d idle=y
+"#;
+
+    #[test]
+    fn parses_the_buckets_of_the_first_user() {
+        let stats = UsageStats::parse(DUMP).unwrap();
+        let intervals: Vec<_> = stats.buckets.iter().map(|bucket| bucket.interval).collect();
+        assert_eq!(
+            intervals,
+            [
+                Interval::Daily,
+                Interval::Weekly,
+                Interval::Monthly,
+                Interval::Yearly
+            ]
+        );
+        let daily = stats.bucket(Interval::Daily).unwrap();
+        assert_eq!(
+            daily.time_range.as_deref(),
+            Some("2024-03-12 00:00:00 - 2024-03-12 09:12:44")
+        );
+        // Neither the chooser counts nor the configurations are packages
+        assert_eq!(daily.packages.len(), 2);
+        assert!(stats.bucket(Interval::Monthly).unwrap().packages.is_empty());
+        assert_eq!(
+            stats.package_usage(Interval::Daily, "com.android.chrome"),
+            Some(&PackageUsage {
+                package: "com.android.chrome".to_owned(),
+                foreground_time: Duration::from_secs(4365),
+                last_time_used: Some("2024-03-12 09:10:02".to_owned()),
+                visible_time: Some(Duration::from_secs(4503)),
+                last_time_visible: Some("2024-03-12 09:10:05".to_owned()),
+                launch_count: Some(7),
+            })
+        );
+        let launcher = &daily.packages[1];
+        assert_eq!(launcher.foreground_time, Duration::from_secs(723));
+        assert_eq!(launcher.launch_count, Some(0));
+        let yearly = stats
+            .package_usage(Interval::Yearly, "com.android.chrome")
+            .unwrap();
+        assert_eq!(yearly.foreground_time, Duration::from_secs(371_520));
+        assert_eq!(yearly.visible_time, None);
+    }
+
+    #[test]
+    fn parses_the_standby_buckets() {
+        let stats = UsageStats::parse(DUMP).unwrap();
+        // The bucket change event isn't a standby state
+        assert_eq!(stats.standby.len(), 4);
+        assert_eq!(
+            stats.standby[1],
+            AppStandby {
+                package: "com.example.app".to_owned(),
+                user: 0,
+                bucket: StandbyBucket::Rare,
+                reason: Some("t".to_owned()),
+            }
+        );
+        assert_eq!(
+            stats.standby_bucket("com.android.chrome", 0),
+            Some(StandbyBucket::Active)
+        );
+        assert_eq!(
+            stats.standby_bucket("com.android.chrome", 10),
+            Some(StandbyBucket::Restricted)
+        );
+        assert_eq!(
+            stats.standby_bucket("com.android.shell", 0),
+            Some(StandbyBucket::Exempted)
+        );
+        assert_eq!(stats.standby_bucket("com.android.shell", 10), None);
+    }
+
+    #[test]
+    fn unknown_buckets_are_kept() {
+        let stats = UsageStats::parse("  package=com.example.app u=0 bucket=35 reason=p").unwrap();
+        assert_eq!(stats.standby[0].bucket, StandbyBucket::Other(35));
+        assert_eq!(
+            StandbyBucket::from_name("WORKING_SET"),
+            Some(StandbyBucket::WorkingSet)
+        );
+        assert_eq!(StandbyBucket::from_name("SOMETIMES"), None);
+    }
+
+    #[test]
+    fn keys_of_older_releases() {
+        // Android 9 prints `totalTime` and `lastTime`, and no visible time
+        let text = r#"In-memory daily stats
+timeRange="2024-03-12 00:00:00 - 2024-03-12 09:12:44"
+  packages
+    package=com.android.chrome totalTime="01:12:45" lastTime="2024-03-12 09:10:02" appLaunchCount=7
+"#;
+        let stats = UsageStats::parse(text).unwrap();
+        let chrome = stats
+            .package_usage(Interval::Daily, "com.android.chrome")
+            .unwrap();
+        assert_eq!(chrome.foreground_time, Duration::from_secs(4365));
+        assert_eq!(
+            chrome.last_time_used.as_deref(),
+            Some("2024-03-12 09:10:02")
+        );
+        assert_eq!(chrome.visible_time, None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let packages = "In-memory daily stats\n  packages\n";
+        let text = format!(r#"{packages}    package=com.android.chrome totalTimeUsed="ages""#);
+        assert_eq!(
+            UsageStats::parse(&text).unwrap_err(),
+            invalid("totalTimeUsed", "ages")
+        );
+        let line = "package=com.android.chrome appLaunchCount=7";
+        let text = format!("{packages}    {line}");
+        assert_eq!(
+            UsageStats::parse(&text).unwrap_err(),
+            invalid("totalTimeUsed", line)
+        );
+
+        let line = "package=com.example.app bucket=10 reason=u-mu";
+        assert_eq!(UsageStats::parse(line).unwrap_err(), invalid("u", line));
+        assert_eq!(
+            UsageStats::parse("package=com.example.app u=0 bucket=active").unwrap_err(),
+            invalid("bucket", "active")
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        let crlf = DUMP.replace('\n', "\r\n");
+        assert_eq!(
+            UsageStats::parse(&crlf).unwrap(),
+            UsageStats::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(UsageStats::parse("").unwrap(), UsageStats::default());
+    }
+}
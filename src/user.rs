@@ -0,0 +1,392 @@
+//! Typed output of `dumpsys user`, and dumps of services for one user at a time
+//!
+//! Services such as `usagestats`, `jobscheduler` and `notification` keep state per user. Their parsers
+//! read the dump of one user, so on a device with a work profile or a guest, dump them with
+//! [`dump_parsed_for_user`] or [`for_running_users`], which pass `--user <id>` ahead of the arguments.
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::user::Users;
+//!
+//! let users = Users::parse(
+//!     "Current user: 0
+//! Users:
+//!   UserInfo{0:Owner:c13} serialNo=0 isPrimary=true parentId=-1
+//!     Type: android.os.usertype.full.SYSTEM
+//!     Flags: 3091 (ADMIN|FULL|INITIALIZED|MAIN|PRIMARY|SYSTEM)
+//!     State: RUNNING_UNLOCKED
+//!     Created: <unknown>
+//!   UserInfo{10:Work profile:1030} serialNo=10 isPrimary=false parentId=0
+//!     Type: android.os.usertype.profile.MANAGED
+//!     Flags: 4144 (INITIALIZED|MANAGED_PROFILE|PROFILE)
+//!     State: RUNNING_UNLOCKED
+//!   UserInfo{11:Guest:414} serialNo=11 isPrimary=false parentId=-1
+//!     Type: android.os.usertype.full.GUEST
+//!     Flags: 1044 (FULL|GUEST|INITIALIZED)
+//!     State: -1
+//!
+//!   Device managed: false",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(users.current, Some(0));
+//! assert_eq!(users.users.len(), 3);
+//! assert_eq!(users.user(10).unwrap().name, "Work profile");
+//! assert_eq!(users.user(10).unwrap().parent, Some(0));
+//! assert!(users.user(10).unwrap().is_profile());
+//! assert!(users.user(11).unwrap().is_guest());
+//! assert_eq!(users.running().map(|user| user.id).collect::<Vec<_>>(), [0, 10]);
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::{invalid, KeyValues},
+    typed, DumpParse, Dumpsys,
+};
+
+const CURRENT_USER: &str = "Current user:";
+const USER_INFO: &str = "UserInfo{";
+const USER_ARG: &str = "--user";
+/// `State:` of a user that isn't started
+const NOT_RUNNING: &str = "-1";
+
+/// `UserInfo.FLAG_GUEST`
+const FLAG_GUEST: u32 = 0x4;
+/// `UserInfo.FLAG_MANAGED_PROFILE`
+const FLAG_MANAGED_PROFILE: u32 = 0x20;
+/// `UserInfo.FLAG_PROFILE`, since Android 11
+const FLAG_PROFILE: u32 = 0x1000;
+
+/// A user or profile of the device
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserInfo {
+    pub id: u32,
+    pub name: String,
+    /// `UserInfo.FLAG_*` bits
+    pub flags: u32,
+    pub serial: Option<u32>,
+    /// The user a profile belongs to
+    pub parent: Option<u32>,
+    /// Such as `android.os.usertype.full.SECONDARY`, since Android 11
+    pub user_type: Option<String>,
+    /// Such as `RUNNING_UNLOCKED`, `None` while the user isn't started
+    pub state: Option<String>,
+}
+
+impl UserInfo {
+    pub fn is_running(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Whether the user is a profile of another one, e.g. a work profile.
+    pub fn is_profile(&self) -> bool {
+        self.flags & (FLAG_PROFILE | FLAG_MANAGED_PROFILE) != 0 || self.parent.is_some()
+    }
+
+    pub fn is_guest(&self) -> bool {
+        self.flags & FLAG_GUEST != 0
+    }
+}
+
+/// Output of `dumpsys user`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Users {
+    /// The user in the foreground, since Android 12
+    pub current: Option<u32>,
+    pub users: Vec<UserInfo>,
+}
+
+impl Users {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut users = Self::default();
+        // Indentation of the `UserInfo{` line of the user whose details follow.
+        let mut details = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+
+            if let Some(current) = trimmed.strip_prefix(CURRENT_USER) {
+                let current = current.trim();
+                users.current = Some(
+                    current
+                        .parse()
+                        .map_err(|_| invalid(CURRENT_USER, current))?,
+                );
+            } else if trimmed.starts_with(USER_INFO) {
+                users.users.push(user_info(trimmed)?);
+                details = Some(indent);
+            } else if let (Some(user), Some(user_indent)) = (users.users.last_mut(), details) {
+                if indent <= user_indent {
+                    details = None;
+                    continue;
+                }
+                match trimmed.split_once(": ") {
+                    Some(("Type", user_type)) => user.user_type = Some(user_type.to_owned()),
+                    Some(("State", NOT_RUNNING)) => user.state = None,
+                    Some(("State", state)) => user.state = Some(state.to_owned()),
+                    _ => {}
+                }
+            }
+        }
+
+        if users.users.is_empty() {
+            return Err(ParseError::Missing(USER_INFO.to_owned()));
+        }
+        Ok(users)
+    }
+
+    pub fn user(&self, id: u32) -> Option<&UserInfo> {
+        self.users.iter().find(|user| user.id == id)
+    }
+
+    /// Users that are started, the current one and e.g. its profiles.
+    pub fn running(&self) -> impl Iterator<Item = &UserInfo> {
+        self.users.iter().filter(|user| user.is_running())
+    }
+}
+
+impl DumpParse for Users {
+    const SERVICE: &'static str = "user";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump and parse the users of the device.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// let users = dumpsys_rs::user::users().unwrap();
+/// println!("user {:?} of {}", users.current, users.users.len());
+/// # }
+/// ```
+pub fn users() -> Result<Users, DumpError> {
+    typed::dump_service::<Users>(Vec::<&str>::new())
+}
+
+/// `args` for a dump of `user` only: `--user <id>` followed by `args`.
+pub fn user_args(user: u32, args: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<String> {
+    [USER_ARG.to_owned(), user.to_string()]
+        .into_iter()
+        .chain(args.into_iter().map(|arg| arg.as_ref().to_owned()))
+        .collect()
+}
+
+impl Dumpsys {
+    /// Like [`Dumpsys::dump`], for `user` only, see [`user_args`].
+    pub fn dump_for_user(
+        &self,
+        user: u32,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<String, DumpError> {
+        self.dump(user_args(user, args))
+    }
+}
+
+/// Look up [`T::SERVICE`](DumpParse::SERVICE) and parse its dump for `user` as `T`.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{jobscheduler::JobScheduler, user};
+///
+/// # fn foo() {
+/// let work = user::dump_parsed_for_user::<JobScheduler>(10).unwrap();
+/// println!("{} jobs in the work profile", work.jobs.len());
+/// # }
+/// ```
+pub fn dump_parsed_for_user<T: DumpParse>(user: u32) -> Result<T, DumpError> {
+    typed::dump_service::<T>(user_args(user, T::ARGS))
+}
+
+/// Parse the dump of [`T::SERVICE`](DumpParse::SERVICE) for every running user, by user id.
+///
+/// # Example
+///
+/// ```
+/// use dumpsys_rs::{notification::NotificationState, user};
+///
+/// # fn foo() {
+/// for (user, notifications) in user::for_running_users::<NotificationState>().unwrap() {
+///     println!("user {user}: {} notifications", notifications.notifications.len());
+/// }
+/// # }
+/// ```
+pub fn for_running_users<T: DumpParse>() -> Result<Vec<(u32, T)>, DumpError> {
+    let users = users()?;
+    let dumpsys = typed::connect(T::SERVICE, &[])?;
+    users
+        .running()
+        .map(|user| {
+            let parsed = typed::dump_parsed(&dumpsys, user_args(user.id, T::ARGS))?;
+            Ok((user.id, parsed))
+        })
+        .collect()
+}
+
+/// `UserInfo{10:Work profile:1030} serialNo=10 isPrimary=false parentId=0`
+fn user_info(line: &str) -> Result<UserInfo, ParseError> {
+    let (info, rest) = line[USER_INFO.len()..]
+        .split_once('}')
+        .ok_or_else(|| invalid(USER_INFO, line))?;
+    // The name may hold colons itself.
+    let (id, info) = info
+        .split_once(':')
+        .ok_or_else(|| invalid(USER_INFO, line))?;
+    let (name, flags) = info
+        .rsplit_once(':')
+        .ok_or_else(|| invalid(USER_INFO, line))?;
+
+    let fields = KeyValues::parse(rest);
+    Ok(UserInfo {
+        id: id.parse().map_err(|_| invalid(USER_INFO, line))?,
+        name: name.to_owned(),
+        flags: u32::from_str_radix(flags, 16).map_err(|_| invalid(USER_INFO, line))?,
+        serial: fields.get_parsed("serialNo").ok(),
+        parent: fields.get_parsed("parentId").ok(),
+        user_type: None,
+        state: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys user` of Android 14, with a work profile and a guest that isn't started
+    const DUMP: &str = "Current user: 0
+Users:
+  UserInfo{0:Owner:c13} serialNo=0 isPrimary=true parentId=-1
+    Type: android.os.usertype.full.SYSTEM
+    Flags: 3091 (ADMIN|FULL|INITIALIZED|MAIN|PRIMARY|SYSTEM)
+    State: RUNNING_UNLOCKED
+    Created: <unknown>
+    Last logged in: +3d1h2m3s ago
+    Start time: +3d1h2m ago
+    Unlock time: +3d1h1m ago
+    Has profile owner: false
+    Restrictions:
+      none
+  UserInfo{10:Work: Example Corp:1030} serialNo=10 isPrimary=false parentId=0
+    Type: android.os.usertype.profile.MANAGED
+    Flags: 4144 (INITIALIZED|MANAGED_PROFILE|PROFILE)
+    State: RUNNING_LOCKED
+    Created: +20d ago
+    Has profile owner: true
+  UserInfo{11:Guest:414} serialNo=11 isPrimary=false parentId=-1
+    Type: android.os.usertype.full.GUEST
+    Flags: 1044 (FULL|GUEST|INITIALIZED)
+    State: -1
+    Created: +1h ago
+
+  Device managed: false
+  Started users state: {0=RUNNING_UNLOCKED, 10=RUNNING_LOCKED}
+
+  Max users: 4 (limit reached: false)
+  Supports switchable users: true
+";
+
+    #[test]
+    fn parses_the_users() {
+        let users = Users::parse(DUMP).unwrap();
+        assert_eq!(users.current, Some(0));
+        assert_eq!(users.users.len(), 3);
+        // Colons in the name stay in it
+        assert_eq!(
+            users.user(10).unwrap(),
+            &UserInfo {
+                id: 10,
+                name: "Work: Example Corp".to_owned(),
+                flags: 0x1030,
+                serial: Some(10),
+                parent: Some(0),
+                user_type: Some("android.os.usertype.profile.MANAGED".to_owned()),
+                state: Some("RUNNING_LOCKED".to_owned()),
+            }
+        );
+        let owner = users.user(0).unwrap();
+        assert_eq!(owner.parent, None);
+        assert!(!owner.is_profile());
+        assert!(!owner.is_guest());
+        let guest = users.user(11).unwrap();
+        assert!(guest.is_guest());
+        assert!(!guest.is_running());
+        assert_eq!(
+            users.running().map(|user| user.id).collect::<Vec<_>>(),
+            [0, 10]
+        );
+        assert_eq!(users.user(12), None);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            Users::parse(&DUMP.replace('\n', "\r\n")),
+            Users::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_fails() {
+        assert_eq!(
+            Users::parse("").unwrap_err(),
+            ParseError::Missing(USER_INFO.to_owned())
+        );
+    }
+
+    #[test]
+    fn users_of_older_releases() {
+        // Android 10 prints neither the current user nor the user types
+        let text = "Users:
+  UserInfo{0:Owner:13} serialNo=0
+    State: RUNNING_UNLOCKED
+    Created: <unknown>
+  UserInfo{10:Work profile:30} serialNo=10
+    State: RUNNING_UNLOCKED
+";
+        let users = Users::parse(text).unwrap();
+        assert_eq!(users.current, None);
+        let work = users.user(10).unwrap();
+        assert_eq!(work.user_type, None);
+        assert_eq!(work.parent, None);
+        // Without a parent id or the profile flag, the managed profile flag tells a work profile
+        assert!(work.is_profile());
+        assert!(!users.user(0).unwrap().is_profile());
+        assert_eq!(users.running().count(), 2);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(
+            Users::parse("Current user: none").unwrap_err(),
+            invalid(CURRENT_USER, "none")
+        );
+        // Cut short in the middle of the name
+        let line = "UserInfo{10:Work prof";
+        assert_eq!(
+            Users::parse(&format!("Users:\n  {line}")).unwrap_err(),
+            invalid(USER_INFO, line)
+        );
+        let line = "UserInfo{10:Work profile:PROFILE} serialNo=10";
+        assert_eq!(
+            Users::parse(&format!("Users:\n  {line}")).unwrap_err(),
+            invalid(USER_INFO, line)
+        );
+    }
+
+    #[test]
+    fn user_args_come_first() {
+        assert_eq!(user_args(10, ["-a"]), ["--user", "10", "-a"]);
+        assert_eq!(user_args(0, Vec::<&str>::new()), ["--user", "0"]);
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{error::DumpError, owned_args, Dumpsys};
+
+type Select<'a> = Box<dyn for<'o> Fn(&'o str) -> &'o str + Send + 'a>;
+
+/// Dumps repeated at an interval, yielding only those that changed, returned by [`Dumpsys::watch`]
+///
+/// The first dump is always yielded, and so is every error. Only a hash of the previous output is kept,
+/// not the output itself.
+pub struct Watch<'a> {
+    dumpsys: &'a Dumpsys,
+    args: Vec<String>,
+    interval: Duration,
+    select: Select<'a>,
+    last: Option<u64>,
+    next: Option<Instant>,
+}
+
+impl<'a> Watch<'a> {
+    /// Only compare the part of the output returned by `select`, e.g. a single section.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let battery = Dumpsys::new("battery")?;
+    /// let changes = battery
+    ///     .watch([] as [&str; 0], Duration::from_secs(5))
+    ///     .select(|output| output.lines().find(|line| line.contains("level:")).unwrap_or(""));
+    /// for output in changes {
+    ///     println!("{}", output.unwrap());
+    /// }
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn select<F>(mut self, select: F) -> Self
+    where
+        F: for<'o> Fn(&'o str) -> &'o str + Send + 'a,
+    {
+        self.select = Box::new(select);
+        self
+    }
+}
+
+impl Iterator for Watch<'_> {
+    type Item = Result<String, DumpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.next {
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+            self.next = Some(Instant::now() + self.interval);
+
+            let output = match self.dumpsys.dump(&self.args) {
+                Ok(output) => output,
+                Err(err) => return Some(Err(err)),
+            };
+
+            let mut hasher = DefaultHasher::new();
+            (self.select)(&output).hash(&mut hasher);
+            let hash = hasher.finish();
+            if self.last.replace(hash) != Some(hash) {
+                return Some(Ok(output));
+            }
+        }
+    }
+}
+
+impl Dumpsys {
+    /// Dump every `interval` and yield the output whenever it changed, like `watch -d dumpsys ...`.
+    pub fn watch(
+        &self,
+        args: impl IntoIterator<Item = impl AsRef<str>>,
+        interval: Duration,
+    ) -> Watch<'_> {
+        Watch {
+            dumpsys: self,
+            args: owned_args(args),
+            interval,
+            select: Box::new(|output| output),
+            last: None,
+            next: None,
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    mem,
+    os::fd::OwnedFd,
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Once, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{instrument, Dumpsys};
+
+/// How long past its deadline a transaction gets to notice the reader is gone and return before it
+/// counts as hung
+const GRACE: Duration = Duration::from_secs(1);
+
+// States of a watched transaction.
+const RUNNING: u8 = 0;
+const RETURNED: u8 = 1;
+const HUNG: u8 = 2;
+
+type OnHung = Box<dyn FnOnce() + Send>;
+
+static WATCHDOG: Watchdog = Watchdog {
+    entries: Mutex::new(Vec::new()),
+    changed: Condvar::new(),
+};
+static STARTED: Once = Once::new();
+
+/// Transactions of a handle and its clones that outlived their deadline and haven't returned yet
+#[derive(Debug, Default)]
+pub(crate) struct Health {
+    hung: AtomicUsize,
+}
+
+/// Held by the thread running a dump transaction until the transaction returns
+pub(crate) struct Watch(Arc<Watched>);
+
+struct Watched {
+    state: AtomicU8,
+    health: Arc<Health>,
+    on_hung: Mutex<Option<OnHung>>,
+}
+
+impl Watch {
+    /// Run `f` on the watchdog thread once the transaction is given up on.
+    pub(crate) fn on_hung(&self, f: impl FnOnce() + Send + 'static) {
+        *self.0.on_hung.lock().unwrap() = Some(Box::new(f));
+    }
+
+    /// Mark the transaction as returned.
+    pub(crate) fn finish(&self) {
+        if self.0.state.swap(RETURNED, Ordering::AcqRel) == HUNG {
+            self.0.health.hung.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Transactions in flight with a deadline, checked by a thread started on first use
+struct Watchdog {
+    entries: Mutex<Vec<Entry>>,
+    changed: Condvar,
+}
+
+struct Entry {
+    service: Arc<str>,
+    deadline: Instant,
+    watched: Weak<Watched>,
+    write: Weak<OwnedFd>,
+}
+
+/// Watch a transaction writing to `write` for `service`, due back by `deadline`.
+///
+/// Past the deadline and a grace period, the transaction is given up on: `write` is pointed at
+/// `/dev/null` so this process lets go of the pipe even though the transaction still holds the
+/// descriptor, and `health` counts the transaction as hung until it returns. The pipe itself lives on
+/// until the service closes the copy of the write end binder handed it.
+pub(crate) fn watch(
+    health: &Arc<Health>,
+    service: &Arc<str>,
+    deadline: Option<Instant>,
+    write: &Arc<OwnedFd>,
+) -> Watch {
+    let watched = Arc::new(Watched {
+        state: AtomicU8::new(RUNNING),
+        health: health.clone(),
+        on_hung: Mutex::new(None),
+    });
+    if let Some(deadline) = deadline {
+        STARTED.call_once(|| {
+            // Without the thread, dumps past their deadline are only abandoned by their reader.
+            let _ = thread::Builder::new()
+                .name("dumpsys-watchdog".to_owned())
+                .spawn(run);
+        });
+        WATCHDOG.entries.lock().unwrap().push(Entry {
+            service: service.clone(),
+            deadline: deadline + GRACE,
+            watched: Arc::downgrade(&watched),
+            write: Arc::downgrade(write),
+        });
+        WATCHDOG.changed.notify_one();
+    }
+    Watch(watched)
+}
+
+fn run() {
+    let mut entries = WATCHDOG.entries.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let (expired, pending): (Vec<_>, Vec<_>) = mem::take(&mut *entries)
+            .into_iter()
+            .filter(|entry| entry.watched.strong_count() > 0)
+            .partition(|entry| entry.deadline <= now);
+        *entries = pending;
+
+        if !expired.is_empty() {
+            drop(entries);
+            expired.into_iter().for_each(Entry::expire);
+            entries = WATCHDOG.entries.lock().unwrap();
+            continue;
+        }
+        entries = match entries.iter().map(|entry| entry.deadline).min() {
+            Some(next) => {
+                WATCHDOG
+                    .changed
+                    .wait_timeout(entries, next - now)
+                    .unwrap()
+                    .0
+            }
+            None => WATCHDOG.changed.wait(entries).unwrap(),
+        };
+    }
+}
+
+impl Entry {
+    fn expire(self) {
+        let Some(watched) = self.watched.upgrade() else {
+            return;
+        };
+        // Counted first so the count never drops below the hung transactions while one returns.
+        watched.health.hung.fetch_add(1, Ordering::AcqRel);
+        if watched
+            .state
+            .compare_exchange(RUNNING, HUNG, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            watched.health.hung.fetch_sub(1, Ordering::AcqRel);
+            return;
+        }
+
+        instrument::hung(&self.service);
+        if let Some(write) = self.write.upgrade() {
+            let _ = release(&write);
+        }
+        let on_hung = watched.on_hung.lock().unwrap().take();
+        if let Some(on_hung) = on_hung {
+            on_hung();
+        }
+    }
+}
+
+/// Point `write` at `/dev/null`, dropping this process's reference to the pipe without freeing a
+/// descriptor number the transaction still writes to.
+///
+/// Only the descriptor of this process changes: copies of the write end elsewhere, like the one a
+/// remote service received through binder or one inherited by a child process, stay open on the
+/// pipe until they're closed there. An in-process service writes to `/dev/null` from then on.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn release(write: &OwnedFd) -> std::io::Result<()> {
+    use std::{fs::File, os::fd::AsRawFd};
+
+    let null = File::options().write(true).open("/dev/null")?;
+    // SAFETY: both descriptors are open, and `write` stays owned by its `OwnedFd`, open on /dev/null.
+    if unsafe { libc::dup3(null.as_raw_fd(), write.as_raw_fd(), libc::O_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The pipe stays open until the transaction returns, where there is no `dup3`.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn release(_write: &OwnedFd) -> std::io::Result<()> {
+    Ok(())
+}
+
+impl Dumpsys {
+    /// Whether a dump of this handle, or of one of its clones, outlived its timeout and the service
+    /// hasn't returned from it yet.
+    ///
+    /// A transaction blocked in the service can't be interrupted, so its thread lives on until the
+    /// service answers or dies. Past the dump timeout and a second of grace, the transaction is given
+    /// up on instead: this process's descriptor of the pipe is pointed at `/dev/null`, though the
+    /// service keeps its own copy open until it returns, a [`WorkerPool`](crate::WorkerPool) worker
+    /// stuck on it is replaced, and the handle turns suspect. Dumps of a suspect handle fail with
+    /// [`DumpError::Timeout`](crate::error::DumpError::Timeout) right away rather than piling up more
+    /// threads behind the hung service. The mark clears once every hung transaction has returned.
+    ///
+    /// Only dumps with a [`dump_timeout`](crate::DumpsysBuilder::dump_timeout) are watched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use dumpsys_rs::Dumpsys;
+    ///
+    /// # fn foo() -> Option<()> {
+    /// let window = Dumpsys::builder("window")
+    ///     .dump_timeout(Duration::from_secs(5))
+    ///     .build()?;
+    /// // Polled every few seconds, skipping the service while it hangs.
+    /// if !window.is_suspect() {
+    ///     println!("{:?}", window.dump(&["windows"]).map(|output| output.len()));
+    /// }
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn is_suspect(&self) -> bool {
+        self.health.hung.load(Ordering::Acquire) > 0
+    }
+}
@@ -0,0 +1,412 @@
+//! Typed output of `dumpsys wifi`
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use dumpsys_rs::wifi::WifiState;
+//!
+//! let wifi = WifiState::parse(
+//!     r#"Wi-Fi is enabled
+//! Verbose logging is off
+//! ClientModeImpl:
+//!  total records=3
+//! curState=L3ConnectedState
+//! mWifiInfo SSID: "HomeNet", BSSID: 00:11:22:33:44:55, MAC: 02:00:00:00:00:00, IP: /192.168.1.23, Security type: 2, Supplicant state: COMPLETED, Wi-Fi standard: 11ax, RSSI: -55, Link speed: 866Mbps, Tx Link speed: 866Mbps, Rx Link speed: 780Mbps, Frequency: 5180MHz, Net ID: 3, Metered hint: false, score: 60
+//! Latest scan results:
+//!     BSSID              Frequency      RSSI           Age(sec)     SSID                                 Flags
+//!   00:11:22:33:44:55       5180    -55(0:-56/1:-57)    2.345    HomeNet                          [WPA2-PSK-CCMP][RSN-PSK-CCMP][ESS]
+//!   66:77:88:99:aa:bb       2437        -71             10.000   Cafe Guest                       [ESS]
+//!   cc:dd:ee:ff:00:11       5745        -80             3.000                                     [WPA2-PSK-CCMP][ESS]
+//!
+//! Locks held:"#,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(wifi.enabled, Some(true));
+//! assert_eq!(wifi.state_machine.as_deref(), Some("L3ConnectedState"));
+//!
+//! let connection = wifi.connection.as_ref().unwrap();
+//! assert!(connection.is_connected());
+//! assert_eq!(connection.ssid.as_deref(), Some("HomeNet"));
+//! assert_eq!(connection.bssid.as_deref(), Some("00:11:22:33:44:55"));
+//! assert_eq!(connection.ip.as_deref(), Some("192.168.1.23"));
+//! assert_eq!(connection.rssi, Some(-55));
+//! assert_eq!(connection.link_speed_mbps, Some(866));
+//! assert_eq!(connection.rx_link_speed_mbps, Some(780));
+//! assert_eq!(connection.frequency_mhz, Some(5180));
+//! assert_eq!(connection.network_id, Some(3));
+//!
+//! assert_eq!(wifi.scan_results.len(), 3);
+//! assert_eq!(wifi.scan_results[0].age, Some(Duration::from_millis(2345)));
+//! assert_eq!(wifi.scan_results[1].ssid.as_deref(), Some("Cafe Guest"));
+//! assert_eq!(wifi.scan_results[1].rssi, -71);
+//! assert_eq!(wifi.scan_results[2].ssid, None);
+//! assert_eq!(wifi.scan_results[0].flags, ["WPA2-PSK-CCMP", "RSN-PSK-CCMP", "ESS"]);
+//! assert_eq!(wifi.strongest("Cafe Guest").unwrap().frequency_mhz, 2437);
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::invalid,
+    typed, DumpParse,
+};
+
+const ENABLED: &str = "Wi-Fi is ";
+const WIFI_INFO: &str = "mWifiInfo ";
+const CURRENT_STATE: &str = "curState=";
+const SCAN_RESULTS: &str = "Latest scan results:";
+const UNKNOWN_SSID: &str = "<unknown ssid>";
+const COMPLETED: &str = "COMPLETED";
+
+/// The network the device is connected to, or trying to connect to
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionInfo {
+    /// Without quotes, `None` when not connected or hidden by location permissions
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub ip: Option<String>,
+    /// `SupplicantState`, as in `COMPLETED` or `SCANNING`
+    pub supplicant_state: Option<String>,
+    /// As in `11ax`, on Android 11 and later
+    pub standard: Option<String>,
+    /// Signal strength in dBm
+    pub rssi: Option<i32>,
+    pub link_speed_mbps: Option<u32>,
+    pub tx_link_speed_mbps: Option<u32>,
+    pub rx_link_speed_mbps: Option<u32>,
+    pub frequency_mhz: Option<u32>,
+    pub network_id: Option<i32>,
+}
+
+impl ConnectionInfo {
+    /// Whether association and authentication completed.
+    pub fn is_connected(&self) -> bool {
+        self.supplicant_state.as_deref() == Some(COMPLETED)
+    }
+}
+
+/// An access point found by the latest scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanResult {
+    pub bssid: String,
+    pub frequency_mhz: u32,
+    /// Signal strength in dBm, the strongest of all chains
+    pub rssi: i32,
+    /// Time since the access point was seen
+    pub age: Option<Duration>,
+    /// `None` for hidden networks
+    pub ssid: Option<String>,
+    /// Capabilities, as in `WPA2-PSK-CCMP` or `ESS`
+    pub flags: Vec<String>,
+}
+
+/// Output of `dumpsys wifi`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WifiState {
+    pub enabled: Option<bool>,
+    /// Current state of the client mode state machine, as in `L3ConnectedState`
+    pub state_machine: Option<String>,
+    /// Of the primary client interface
+    pub connection: Option<ConnectionInfo>,
+    pub scan_results: Vec<ScanResult>,
+}
+
+impl WifiState {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut wifi = Self::default();
+        let mut in_client_mode = false;
+        let mut in_scan_results = false;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                in_scan_results = false;
+                continue;
+            }
+
+            if let Some(state) = line.strip_prefix(ENABLED) {
+                wifi.enabled.get_or_insert(state == "enabled");
+            } else if let Some(info) = trimmed.strip_prefix(WIFI_INFO) {
+                wifi.connection.get_or_insert_with(|| wifi_info(info));
+            } else if trimmed == SCAN_RESULTS {
+                in_scan_results = true;
+            } else if in_scan_results {
+                if let Some(result) = scan_result(trimmed)? {
+                    wifi.scan_results.push(result);
+                } else if !trimmed.starts_with("BSSID") {
+                    in_scan_results = false;
+                }
+            } else if let Some(state) = trimmed.strip_prefix(CURRENT_STATE) {
+                if in_client_mode {
+                    wifi.state_machine.get_or_insert_with(|| state.to_owned());
+                }
+            } else if let Some(name) = trimmed.strip_suffix(':') {
+                // The state machine was named `WifiStateMachine` before Android 10.
+                in_client_mode =
+                    name.starts_with("ClientModeImpl") || name.starts_with("WifiStateMachine");
+            }
+        }
+
+        Ok(wifi)
+    }
+
+    /// The access point of `ssid` with the strongest signal.
+    pub fn strongest(&self, ssid: &str) -> Option<&ScanResult> {
+        self.scan_results
+            .iter()
+            .filter(|result| result.ssid.as_deref() == Some(ssid))
+            .max_by_key(|result| result.rssi)
+    }
+}
+
+impl DumpParse for WifiState {
+    const SERVICE: &'static str = "wifi";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `wifi` and return the current connection.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// if let Some(info) = dumpsys_rs::wifi::connection_info().unwrap() {
+///     println!("{:?} at {:?} dBm", info.ssid, info.rssi);
+/// }
+/// # }
+/// ```
+pub fn connection_info() -> Result<Option<ConnectionInfo>, DumpError> {
+    typed::dump_service::<WifiState>(Vec::<&str>::new()).map(|wifi| wifi.connection)
+}
+
+/// `SSID: "HomeNet", BSSID: 00:11:22:33:44:55, ..., RSSI: -55, Link speed: 866Mbps, Frequency: 5180MHz, ...`
+fn wifi_info(text: &str) -> ConnectionInfo {
+    let mut info = ConnectionInfo::default();
+    let owned = |value: &str| Some(value.to_owned()).filter(|value| !value.is_empty());
+    let number = |value: &str, unit: &str| value.strip_suffix(unit).unwrap_or(value).parse().ok();
+
+    for field in text.split(", ") {
+        let Some((key, value)) = field.split_once(": ") else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "SSID" => {
+                info.ssid = owned(value.trim_matches('"')).filter(|ssid| ssid != UNKNOWN_SSID);
+            }
+            "BSSID" => info.bssid = owned(value).filter(|bssid| bssid != "<none>"),
+            "IP" => info.ip = owned(value.trim_start_matches('/')).filter(|ip| ip != "null"),
+            "Supplicant state" => info.supplicant_state = owned(value),
+            "Wi-Fi standard" => info.standard = owned(value),
+            "RSSI" => info.rssi = value.parse().ok(),
+            "Link speed" => info.link_speed_mbps = number(value, "Mbps"),
+            "Tx Link speed" => info.tx_link_speed_mbps = number(value, "Mbps"),
+            "Rx Link speed" => info.rx_link_speed_mbps = number(value, "Mbps"),
+            "Frequency" => info.frequency_mhz = number(value, "MHz"),
+            "Net ID" => info.network_id = value.parse().ok(),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// `00:11:22:33:44:55  5180  -55(0:-56/1:-57)  2.345  HomeNet  [WPA2-PSK-CCMP][ESS]`
+fn scan_result(line: &str) -> Result<Option<ScanResult>, ParseError> {
+    let mut words = line.split_whitespace();
+    let (Some(bssid), Some(frequency), Some(rssi), Some(age)) =
+        (words.next(), words.next(), words.next(), words.next())
+    else {
+        return Ok(None);
+    };
+    if bssid.matches(':').count() != 5 {
+        return Ok(None);
+    }
+
+    let rest = words.collect::<Vec<_>>().join(" ");
+    let (ssid, flags) = rest.split_at(rest.find('[').unwrap_or(rest.len()));
+    let rssi = rssi.split('(').next().unwrap_or(rssi);
+    Ok(Some(ScanResult {
+        bssid: bssid.to_owned(),
+        frequency_mhz: frequency
+            .parse()
+            .map_err(|_| invalid("Frequency", frequency))?,
+        rssi: rssi.parse().map_err(|_| invalid("RSSI", rssi))?,
+        age: age
+            .parse::<f64>()
+            .ok()
+            .and_then(|age| Duration::try_from_secs_f64(age).ok()),
+        ssid: Some(ssid.trim().to_owned()).filter(|ssid| !ssid.is_empty()),
+        flags: flags
+            .split(['[', ']'])
+            .filter(|flag| !flag.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys wifi` of Android 14, cut down to the client mode and the latest scan
+    const DUMP: &str = r#"Wi-Fi is enabled
+Verbose logging is off
+Stay-awake conditions: 0
+mInIdleMode false
+mScanPending false
+WifiController:
+ total records=12
+curState=EnabledState
+
+Dump of ClientModeImpl id=6283
+ClientModeImpl:
+ total records=50
+curState=L3ConnectedState
+mWifiInfo SSID: "HomeNet", BSSID: 00:11:22:33:44:55, MAC: 02:00:00:00:00:00, IP: /192.168.1.23, Security type: 2, Supplicant state: COMPLETED, Wi-Fi standard: 11ax, RSSI: -55, Link speed: 866Mbps, Tx Link speed: 866Mbps, Max Supported Tx Link speed: 1201Mbps, Rx Link speed: 780Mbps, Max Supported Rx Link speed: 1201Mbps, Frequency: 5180MHz, Net ID: 3, Metered hint: false, score: 60, isUsable: true, CarrierMerged: false, SubscriptionId: -1, IsPrimary: 1, Trusted: true, Restricted: false, Ephemeral: false
+mDhcpResultsParcelable baseConfiguration IP address 192.168.1.23/24 Gateway 192.168.1.1
+
+Dump of ClientModeImpl id=6284
+ClientModeImpl:
+curState=DisconnectedState
+mWifiInfo SSID: <unknown ssid>, BSSID: <none>, MAC: 02:00:00:00:00:00, IP: null, Security type: -1, Supplicant state: DISCONNECTED, RSSI: -127, Link speed: -1Mbps, Frequency: -1MHz, Net ID: -1
+
+WifiScanningService - Log Begin ----
+Latest scan results:
+    BSSID              Frequency      RSSI           Age(sec)     SSID                                 Flags
+  00:11:22:33:44:55       5180    -55(0:-56/1:-57)    2.345    HomeNet                          [WPA2-PSK-CCMP][RSN-PSK-CCMP][ESS]
+  00:11:22:33:44:56       2437    -67(0:-67/1:-70)    2.345    HomeNet                          [WPA2-PSK-CCMP][RSN-PSK-CCMP][ESS]
+  66:77:88:99:aa:bb       2437        -71             10.000   Cafe Guest                       [ESS]
+  cc:dd:ee:ff:00:11       5745        -80             3.000                                     [WPA2-PSK-CCMP][ESS]
+
+Locks held:
+"#;
+
+    #[test]
+    fn parses_the_connection() {
+        let wifi = WifiState::parse(DUMP).unwrap();
+        assert_eq!(wifi.enabled, Some(true));
+        // The state of the primary client mode, not of WifiController
+        assert_eq!(wifi.state_machine.as_deref(), Some("L3ConnectedState"));
+        assert_eq!(
+            wifi.connection.unwrap(),
+            ConnectionInfo {
+                ssid: Some("HomeNet".to_owned()),
+                bssid: Some("00:11:22:33:44:55".to_owned()),
+                ip: Some("192.168.1.23".to_owned()),
+                supplicant_state: Some("COMPLETED".to_owned()),
+                standard: Some("11ax".to_owned()),
+                rssi: Some(-55),
+                link_speed_mbps: Some(866),
+                tx_link_speed_mbps: Some(866),
+                rx_link_speed_mbps: Some(780),
+                frequency_mhz: Some(5180),
+                network_id: Some(3),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_the_scan_results() {
+        let wifi = WifiState::parse(DUMP).unwrap();
+        assert_eq!(wifi.scan_results.len(), 4);
+        assert_eq!(
+            wifi.scan_results[1],
+            ScanResult {
+                bssid: "00:11:22:33:44:56".to_owned(),
+                frequency_mhz: 2437,
+                rssi: -67,
+                age: Some(Duration::from_millis(2345)),
+                ssid: Some("HomeNet".to_owned()),
+                flags: vec![
+                    "WPA2-PSK-CCMP".to_owned(),
+                    "RSN-PSK-CCMP".to_owned(),
+                    "ESS".to_owned(),
+                ],
+            }
+        );
+        assert_eq!(wifi.strongest("HomeNet").unwrap().frequency_mhz, 5180);
+        assert_eq!(wifi.strongest("Neighbour"), None);
+        assert_eq!(wifi.scan_results[3].ssid, None);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            WifiState::parse(&DUMP.replace('\n', "\r\n")),
+            WifiState::parse(DUMP)
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        assert_eq!(WifiState::parse("").unwrap(), WifiState::default());
+    }
+
+    #[test]
+    fn disconnected() {
+        let text = "Wi-Fi is disabled
+ClientModeImpl:
+curState=DisconnectedState
+mWifiInfo SSID: <unknown ssid>, BSSID: <none>, MAC: 02:00:00:00:00:00, IP: null, Security type: -1, Supplicant state: DISCONNECTED, RSSI: -127, Link speed: -1Mbps, Frequency: -1MHz, Net ID: -1
+";
+        let wifi = WifiState::parse(text).unwrap();
+        assert_eq!(wifi.enabled, Some(false));
+        let connection = wifi.connection.unwrap();
+        assert!(!connection.is_connected());
+        assert_eq!(connection.ssid, None);
+        assert_eq!(connection.bssid, None);
+        assert_eq!(connection.ip, None);
+        assert_eq!(connection.link_speed_mbps, None);
+        assert_eq!(connection.frequency_mhz, None);
+        assert_eq!(connection.network_id, Some(-1));
+    }
+
+    #[test]
+    fn state_of_older_releases() {
+        // Android 9 has no Wi-Fi standard, no Tx and Rx speeds and a single RSSI per result
+        let text = r#"Wi-Fi is enabled
+WifiStateMachine:
+ total records=100
+curState=ConnectedState
+mWifiInfo SSID: HomeNet, BSSID: 00:11:22:33:44:55, MAC: 02:00:00:00:00:00, Supplicant state: COMPLETED, RSSI: -60, Link speed: 433Mbps, Frequency: 5180MHz, Net ID: 0, Metered hint: false, score: 60
+Latest scan results:
+    BSSID              Frequency  RSSI    Age      SSID                                 Flags
+  00:11:22:33:44:55       5180    -60    1.500    HomeNet                          [WPA2-PSK-CCMP][ESS]
+  66:77:88:99:aa:bb       2412
+"#;
+        let wifi = WifiState::parse(text).unwrap();
+        assert_eq!(wifi.state_machine.as_deref(), Some("ConnectedState"));
+        let connection = wifi.connection.unwrap();
+        assert_eq!(connection.ssid.as_deref(), Some("HomeNet"));
+        assert_eq!(connection.standard, None);
+        assert_eq!(connection.ip, None);
+        assert_eq!(connection.tx_link_speed_mbps, None);
+        assert_eq!(connection.link_speed_mbps, Some(433));
+        // The truncated result ends the scan results
+        assert_eq!(wifi.scan_results.len(), 1);
+        assert_eq!(wifi.scan_results[0].rssi, -60);
+    }
+
+    #[test]
+    fn invalid_scan_results() {
+        let scan = |line: &str| WifiState::parse(&format!("{SCAN_RESULTS}\n  {line}\n"));
+        assert_eq!(
+            scan("00:11:22:33:44:55  5.18GHz  -55  2.345  HomeNet  [ESS]").unwrap_err(),
+            invalid("Frequency", "5.18GHz")
+        );
+        assert_eq!(
+            scan("00:11:22:33:44:55  5180  strong  2.345  HomeNet  [ESS]").unwrap_err(),
+            invalid("RSSI", "strong")
+        );
+    }
+}
@@ -0,0 +1,352 @@
+//! Typed output of `dumpsys window`
+//!
+//! # Example
+//!
+//! ```
+//! use dumpsys_rs::window::WindowManager;
+//!
+//! let window = WindowManager::parse(
+//!     "WINDOW MANAGER POLICY STATE (dumpsys window policy)
+//!     KeyguardServiceDelegate
+//!       showing=false
+//!       occluded=false
+//!
+//! WINDOW MANAGER WINDOWS (dumpsys window windows)
+//!   Window #0 Window{9f3b2a1 u0 InputMethod}:
+//!     mDisplayId=0 rootTaskId=1 mSession=Session{a1 1234:u0a10100} mClient=android.os.BinderProxy@ab
+//!     mHasSurface=true isReadyForDisplay()=true mWindowRemovalAllowed=false
+//!     mViewVisibility=0x0 mHaveFrame=true mObscured=false
+//!   Window #1 Window{c0ffee1 u0 com.example.app/com.example.app.MainActivity}:
+//!     mViewVisibility=0x0 mHaveFrame=true mObscured=false
+//!
+//!   mCurrentFocus=Window{c0ffee1 u0 com.example.app/com.example.app.MainActivity}
+//!   mFocusedApp=ActivityRecord{d4e5f6 u0 com.example.app/.MainActivity t123}",
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(window.focused_package(), Some("com.example.app"));
+//! let app = window.focused_app.as_ref().unwrap();
+//! assert_eq!(app.class_name(), "com.example.app.MainActivity");
+//! assert_eq!(app.task_id, Some(123));
+//! assert_eq!(window.current_focus.as_ref().unwrap().user, Some(0));
+//! assert_eq!(window.keyguard_showing, Some(false));
+//! assert_eq!(window.ime_visible, Some(true));
+//! ```
+
+use crate::{
+    error::{DumpError, ParseError},
+    parse::kv::KeyValues,
+    typed, DumpParse,
+};
+
+const CURRENT_FOCUS: &str = "mCurrentFocus=";
+const FOCUSED_APP: &str = "mFocusedApp=";
+const ACTIVITY_RECORD: &str = "ActivityRecord{";
+const WINDOW: &str = "Window{";
+const KEYGUARD_DELEGATE: &str = "KeyguardServiceDelegate";
+const INPUT_METHOD: &str = "InputMethod}:";
+/// `View.VISIBLE`
+const VISIBLE: &str = "0x0";
+
+/// A window as printed by `WindowState.toString`, as in `Window{c0ffee1 u0 com.example/.Main}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Window {
+    pub user: Option<u32>,
+    /// Component of an activity window, or a title such as `StatusBar`
+    pub title: String,
+}
+
+impl Window {
+    /// Package of an activity window.
+    pub fn package(&self) -> Option<&str> {
+        self.title.split_once('/').map(|(package, _)| package)
+    }
+}
+
+/// An activity as printed by `ActivityRecord.toString`, as in `ActivityRecord{d4e5f6 u0 com.example/.Main t12}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActivityRecord {
+    pub user: Option<u32>,
+    pub package: String,
+    /// Class of the activity, relative to the package if it starts with a `.`
+    pub activity: String,
+    pub task_id: Option<u32>,
+}
+
+impl ActivityRecord {
+    /// Parse the fields between the braces of `ActivityRecord{...}`.
+    pub(crate) fn parse(record: &str) -> Option<Self> {
+        let mut words = record.split_whitespace().skip(1).peekable();
+        let user = words
+            .next_if(|word| user_id(word).is_some())
+            .and_then(user_id);
+        let (package, activity) = words.next()?.split_once('/')?;
+        let task_id = words.find_map(|word| word.strip_prefix('t')?.parse().ok());
+        Some(Self {
+            user,
+            package: package.to_owned(),
+            activity: activity.to_owned(),
+            task_id,
+        })
+    }
+
+    /// Fully qualified class of the activity.
+    pub fn class_name(&self) -> String {
+        match self.activity.strip_prefix('.') {
+            Some(relative) => format!("{}.{relative}", self.package),
+            None => self.activity.clone(),
+        }
+    }
+}
+
+/// Output of `dumpsys window`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowManager {
+    /// Window receiving key events on the focused display
+    pub current_focus: Option<Window>,
+    /// Activity focused on the focused display, the app below the notification shade or a dialog
+    pub focused_app: Option<ActivityRecord>,
+    pub keyguard_showing: Option<bool>,
+    /// Whether the input method window is shown, on releases that dump its visibility
+    pub ime_visible: Option<bool>,
+}
+
+impl WindowManager {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut window = Self::default();
+        let mut in_keyguard = false;
+        let mut in_input_method = false;
+        let mut ime_surface = None;
+        let mut ime_visibility = None;
+
+        for line in text.lines().map(str::trim) {
+            if let Some(focus) = line.strip_prefix(CURRENT_FOCUS) {
+                if window.current_focus.is_none() {
+                    window.current_focus = window_ref(focus);
+                }
+                continue;
+            }
+            if let Some(app) = line.strip_prefix(FOCUSED_APP) {
+                if window.focused_app.is_none() {
+                    window.focused_app = app
+                        .find(ACTIVITY_RECORD)
+                        .map(|start| &app[start + ACTIVITY_RECORD.len()..])
+                        .and_then(|record| ActivityRecord::parse(record.split('}').next()?));
+                }
+                continue;
+            }
+
+            if line == KEYGUARD_DELEGATE {
+                in_keyguard = true;
+                continue;
+            }
+            if in_keyguard {
+                if let Some(showing) = line.strip_prefix("showing=") {
+                    window.keyguard_showing = Some(showing == "true");
+                    in_keyguard = false;
+                }
+                continue;
+            }
+
+            if line.starts_with("Window #") {
+                in_input_method = line.ends_with(INPUT_METHOD);
+                continue;
+            }
+            if in_input_method {
+                let fields = KeyValues::parse(line);
+                if let Ok(surface) = fields.get_bool("mHasSurface") {
+                    ime_surface = Some(surface);
+                }
+                if let Some(visibility) = fields.get("mViewVisibility") {
+                    ime_visibility = Some(visibility == VISIBLE);
+                }
+            }
+        }
+
+        let fields = KeyValues::parse(text);
+        // Releases before Android 9 print the keyguard state on the policy directly.
+        if let Ok(showing) = fields.get_bool("mShowingLockscreen") {
+            window.keyguard_showing.get_or_insert(showing);
+        }
+        // Android 11 and later track the IME insets, before only the window itself tells.
+        window.ime_visible = match (fields.get_bool("mImeShowing"), ime_surface, ime_visibility) {
+            (Ok(showing), _, _) => Some(showing),
+            (_, None, None) => None,
+            (_, surface, visible) => Some(surface.unwrap_or(true) && visible.unwrap_or(true)),
+        };
+        Ok(window)
+    }
+
+    /// Package of the app in the foreground: that of the focused window, or of the focused activity
+    /// if a system window such as the notification shade has focus.
+    pub fn focused_package(&self) -> Option<&str> {
+        self.current_focus
+            .as_ref()
+            .and_then(Window::package)
+            .or_else(|| self.focused_app.as_ref().map(|app| app.package.as_str()))
+    }
+}
+
+impl DumpParse for WindowManager {
+    const SERVICE: &'static str = "window";
+
+    fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text)
+    }
+}
+
+/// Dump `window` and return the package of the app in the foreground.
+///
+/// # Example
+///
+/// ```
+/// # fn foo() {
+/// if let Some(package) = dumpsys_rs::window::focused_package().unwrap() {
+///     println!("foreground: {package}");
+/// }
+/// # }
+/// ```
+pub fn focused_package() -> Result<Option<String>, DumpError> {
+    let window = typed::dump_service::<WindowManager>(Vec::<&str>::new())?;
+    Ok(window.focused_package().map(str::to_owned))
+}
+
+/// `Window{c0ffee1 u0 com.example/.Main}`, `None` for `null`.
+fn window_ref(text: &str) -> Option<Window> {
+    let inner = text.strip_prefix(WINDOW)?;
+    let inner = inner.strip_suffix('}').unwrap_or(inner);
+    let mut words = inner.split_whitespace().skip(1).peekable();
+    let user = words
+        .next_if(|word| user_id(word).is_some())
+        .and_then(user_id);
+    Some(Window {
+        user,
+        title: words.collect::<Vec<_>>().join(" "),
+    })
+}
+
+/// `u10` as 10.
+fn user_id(word: &str) -> Option<u32> {
+    word.strip_prefix('u')?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dumpsys window` of Android 13 on the lock screen, cut down to a few windows
+    const DUMP: &str = "WINDOW MANAGER POLICY STATE (dumpsys window policy)
+    mSafeMode=false mSystemReady=true mSystemBooted=true
+    mCameraLensCoverState=LENS_COVER_ABSENT
+    KeyguardServiceDelegate
+      showing=true
+      inputRestricted=true
+      occluded=false
+      secure=true
+      dreaming=false
+
+WINDOW MANAGER WINDOWS (dumpsys window windows)
+  Window #0 Window{8c1e2f4 u0 NotificationShade}:
+    mDisplayId=0 rootTaskId=1 mSession=Session{f3a4b5c 2145:u0a10212} mClient=android.os.BinderProxy@4d5e6f7
+    mHasSurface=true isReadyForDisplay()=true mWindowRemovalAllowed=false
+    mViewVisibility=0x0 mHaveFrame=true mObscured=false
+  Window #1 Window{2a3b4c5 u0 InputMethod}:
+    mDisplayId=0 rootTaskId=1 mSession=Session{a1b2c3d 3321:u0a10150} mClient=android.os.BinderProxy@5e6f7a8
+    mHasSurface=false isReadyForDisplay()=false mWindowRemovalAllowed=false
+    mViewVisibility=0x8 mHaveFrame=true mObscured=false
+  Window #2 Window{d1e2f3a u0 com.google.android.apps.nexuslauncher/com.google.android.apps.nexuslauncher.NexusLauncherActivity}:
+    mDisplayId=0 rootTaskId=1 mSession=Session{b2c3d4e 4412:u0a10160} mClient=android.os.BinderProxy@6f7a8b9
+    mHasSurface=true isReadyForDisplay()=false mWindowRemovalAllowed=false
+    mViewVisibility=0x0 mHaveFrame=true mObscured=true
+
+  mGlobalConfiguration={1.0 310mcc260mnc [en_US] ldltr sw411dp w411dp h842dp 420dpi nrml long port}
+  mCurrentFocus=Window{8c1e2f4 u0 NotificationShade}
+  mFocusedApp=ActivityRecord{c11e5b5 u0 com.google.android.apps.nexuslauncher/.NexusLauncherActivity t75}
+";
+
+    #[test]
+    fn parses_the_dump() {
+        let window = WindowManager::parse(DUMP).unwrap();
+        assert_eq!(
+            window.current_focus,
+            Some(Window {
+                user: Some(0),
+                title: "NotificationShade".to_owned(),
+            })
+        );
+        assert_eq!(
+            window.focused_app,
+            Some(ActivityRecord {
+                user: Some(0),
+                package: "com.google.android.apps.nexuslauncher".to_owned(),
+                activity: ".NexusLauncherActivity".to_owned(),
+                task_id: Some(75),
+            })
+        );
+        assert_eq!(window.keyguard_showing, Some(true));
+        assert_eq!(window.ime_visible, Some(false));
+        // The shade has focus, so the foreground app is the focused activity
+        assert_eq!(
+            window.focused_package(),
+            Some("com.google.android.apps.nexuslauncher")
+        );
+    }
+
+    #[test]
+    fn ime_insets_win_over_the_window() {
+        let text = format!(
+            "{DUMP}    InsetsSourceProvider mSource=InsetsSource: {{mType=ime}} mImeShowing=true\n"
+        );
+        assert_eq!(WindowManager::parse(&text).unwrap().ime_visible, Some(true));
+    }
+
+    #[test]
+    fn lockscreen_before_android_9() {
+        let text = "    mShowingLockscreen=true mShowingDream=false mDreamingLockscreen=true
+  mCurrentFocus=Window{42e5a1c8 u0 StatusBar}
+  mFocusedApp=AppWindowToken{42f0c3b0 token=Token{42e0b748 ActivityRecord{42e0b5d0 u0 com.android.launcher/com.android.launcher2.Launcher t1}}}";
+        let window = WindowManager::parse(text).unwrap();
+        assert_eq!(window.keyguard_showing, Some(true));
+        assert_eq!(window.ime_visible, None);
+        assert_eq!(window.focused_app.unwrap().task_id, Some(1));
+        assert_eq!(window.current_focus.unwrap().package(), None);
+    }
+
+    #[test]
+    fn first_display_focus_wins() {
+        let text =
+            "  mCurrentFocus=Window{c0ffee1 u10 com.example.app/com.example.app.MainActivity}
+  mCurrentFocus=Window{d00d1e2 u0 com.example.cast/.Presentation}";
+        let window = WindowManager::parse(text).unwrap();
+        let focus = window.current_focus.unwrap();
+        assert_eq!(focus.user, Some(10));
+        assert_eq!(focus.package(), Some("com.example.app"));
+    }
+
+    #[test]
+    fn null_and_truncated_focus() {
+        let text = "  mCurrentFocus=null\n  mFocusedApp=ActivityRecord{c11e5b5 u0";
+        assert_eq!(
+            WindowManager::parse(text).unwrap(),
+            WindowManager::default()
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_the_same() {
+        assert_eq!(
+            WindowManager::parse(&DUMP.replace('\n', "\r\n")).unwrap(),
+            WindowManager::parse(DUMP).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_input_has_nothing() {
+        let window = WindowManager::parse("").unwrap();
+        assert_eq!(window, WindowManager::default());
+        assert_eq!(window.focused_package(), None);
+    }
+}
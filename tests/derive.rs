@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use dumpsys_rs::{error::ParseError, DumpParse};
+
+/// `dumpsys power` of Android 14, cut down to a few fields
+const POWER: &str = "POWER MANAGER (dumpsys power)
+
+Power Manager State:
+  mDirty=0x0
+  mWakefulness=Awake
+  mWakefulnessChanging=false
+  mIsPowered=true
+  mPlugType=2
+  mBatteryLevel=87
+  mScreenOffTimeoutSetting=30000
+  mStayOnWhilePluggedInSetting=0
+
+Display Power: state=ON
+";
+
+#[derive(Debug, DumpParse)]
+#[dump(service = "power", args = ["-a"])]
+struct Power {
+    #[dump(key = "mWakefulness")]
+    wakefulness: String,
+    #[dump(key = "mScreenOffTimeoutSetting")]
+    screen_off_timeout: Duration,
+    #[dump(key = "mBatteryLevel")]
+    battery_level: Option<u32>,
+    #[dump(key = "mLastUserActivityTime")]
+    last_user_activity: Option<u64>,
+    #[dump(regex = r"Display Power: state=(\w+)")]
+    display_state: String,
+    #[dump(regex = r"mIsPowered=(\w+)")]
+    powered: Option<bool>,
+}
+
+#[test]
+fn service_and_args_come_from_the_attribute() {
+    assert_eq!(Power::SERVICE, "power");
+    assert_eq!(Power::ARGS, ["-a"]);
+}
+
+#[test]
+fn fields_by_key_and_regex() {
+    let power = Power::parse(POWER).unwrap();
+    assert_eq!(power.wakefulness, "Awake");
+    assert_eq!(power.screen_off_timeout, Duration::from_secs(30));
+    assert_eq!(power.battery_level, Some(87));
+    assert_eq!(power.last_user_activity, None);
+    assert_eq!(power.display_state, "ON");
+    assert_eq!(power.powered, Some(true));
+}
+
+#[test]
+fn crlf_output() {
+    let power = Power::parse(&POWER.replace('\n', "\r\n")).unwrap();
+    assert_eq!(power.wakefulness, "Awake");
+    assert_eq!(power.display_state, "ON");
+}
+
+#[test]
+fn missing_required_fields() {
+    let err = Power::parse(&POWER.replace("mWakefulness=", "mWakefulnessRaw=")).unwrap_err();
+    assert_eq!(err, ParseError::Missing("mWakefulness".to_owned()));
+
+    let err = Power::parse(&POWER.replace("Display Power:", "Display:")).unwrap_err();
+    assert_eq!(err, ParseError::Missing("display_state".to_owned()));
+
+    let err = Power::parse("").unwrap_err();
+    assert!(matches!(err, ParseError::Missing(_)), "{err:?}");
+}
+
+#[test]
+fn invalid_values() {
+    let err =
+        Power::parse(&POWER.replace("mBatteryLevel=87", "mBatteryLevel=unknown")).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::Invalid {
+            key: "mBatteryLevel".to_owned(),
+            value: "unknown".to_owned(),
+        }
+    );
+}
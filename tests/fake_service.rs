@@ -0,0 +1,162 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use binder::{ProcessState, StatusCode};
+use dumpsys_rs::{
+    error::DumpError,
+    testing::{FakeService, Reply, Script},
+    CancelToken, Dumpsys, Execution, RetryPolicy, WorkerPool,
+};
+
+fn start(script: Script) -> FakeService {
+    ProcessState::start_thread_pool();
+    FakeService::start(script).unwrap()
+}
+
+#[test]
+fn replies_follow_the_script() {
+    let fake = start(
+        Script::new("Display 0 HWC layers:\n")
+            .reply(&["--latency"], "16666666\n")
+            .then(StatusCode::DEAD_OBJECT),
+    );
+    let dumpsys = fake.dumpsys().unwrap();
+
+    let err = dumpsys.dump(["-a"]).unwrap_err();
+    assert!(matches!(err, DumpError::DeadObject { .. }), "{err:?}");
+    assert_eq!(dumpsys.dump(["--latency"]).unwrap(), "16666666\n");
+    assert_eq!(dumpsys.dump(["-a"]).unwrap(), "Display 0 HWC layers:\n");
+    assert_eq!(fake.calls(), [["-a"], ["--latency"], ["-a"]]);
+}
+
+#[test]
+fn large_output_goes_through_the_pipe() {
+    let output = "0123456789abcdef".repeat(64 * 1024);
+    let fake = start(Script::new(output.as_str()));
+    let dumpsys = fake.dumpsys().unwrap();
+
+    assert_eq!(dumpsys.dump(["-a"]).unwrap(), output);
+    assert_eq!(dumpsys.last_stats().unwrap().bytes, output.len() as u64);
+}
+
+#[test]
+fn service_failing_halfway_reports_its_output() {
+    let fake = start(Script::new(Reply::Fail {
+        output: b"ACTIVITY MANAGER ACTIVITIES\n".to_vec(),
+        status: StatusCode::FAILED_TRANSACTION,
+    }));
+    let dumpsys = fake.dumpsys().unwrap();
+
+    let err = dumpsys.dump(["activities"]).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            DumpError::Incomplete {
+                status: StatusCode::FAILED_TRANSACTION,
+                bytes_read: 28,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert_eq!(err.partial(), b"ACTIVITY MANAGER ACTIVITIES\n");
+    assert_eq!(err.context().service, fake.name());
+}
+
+#[test]
+fn failed_dump_is_retried() {
+    let fake = start(
+        Script::new("ok\n")
+            .then(StatusCode::FAILED_TRANSACTION)
+            .then(StatusCode::FAILED_TRANSACTION),
+    );
+    let dumpsys = Dumpsys::builder(fake.name())
+        .retry(RetryPolicy::new(3, Duration::from_millis(1)))
+        .build()
+        .unwrap();
+
+    assert_eq!(dumpsys.dump(["-a"]).unwrap(), "ok\n");
+    assert_eq!(fake.calls().len(), 3);
+    assert_eq!(dumpsys.last_stats().unwrap().retries, 2);
+}
+
+#[test]
+fn slow_service_times_out() {
+    let fake = start(Script::new("late\n").delay(Duration::from_millis(500)));
+    let dumpsys = fake.dumpsys().unwrap();
+
+    let start = Instant::now();
+    let err = dumpsys
+        .dump_with_timeout(["-a"], Duration::from_millis(50))
+        .unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert!(matches!(err, DumpError::Timeout { .. }), "{err:?}");
+}
+
+#[test]
+fn hung_service_turns_the_handle_suspect() {
+    let fake = start(Script::new("late\n").delay(Duration::from_millis(1500)));
+    let dumpsys = Dumpsys::builder(fake.name())
+        .dump_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+    assert!(dumpsys.dump(["-a"]).is_err());
+    thread::sleep(Duration::from_millis(1200));
+    assert!(dumpsys.is_suspect());
+    let err = dumpsys.dump(["-a"]).unwrap_err();
+    assert!(matches!(err, DumpError::Timeout { .. }), "{err:?}");
+    assert_eq!(fake.calls().len(), 1);
+
+    thread::sleep(Duration::from_millis(600));
+    assert!(!dumpsys.is_suspect());
+}
+
+#[test]
+fn cancelled_dump_returns_early() {
+    let fake = start(Script::new("late\n").delay(Duration::from_millis(500)));
+    let dumpsys = fake.dumpsys().unwrap();
+    let token = CancelToken::new();
+    let cancel = token.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        cancel.cancel();
+    });
+
+    let start = Instant::now();
+    let err = dumpsys.dump_cancellable(["-a"], &token).unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert!(matches!(err, DumpError::Cancelled { .. }), "{err:?}");
+}
+
+#[test]
+fn every_execution_dumps_the_same() {
+    let fake = start(Script::new("Battery level: 80\n"));
+    let pool = WorkerPool::new(2);
+
+    for execution in [
+        Execution::Spawn,
+        Execution::CurrentThread,
+        Execution::Pool(pool),
+    ] {
+        let dumpsys = Dumpsys::builder(fake.name())
+            .execution(execution)
+            .max_output_bytes(1024)
+            .build()
+            .unwrap();
+        assert_eq!(dumpsys.dump(["-a"]).unwrap(), "Battery level: 80\n");
+    }
+}
+
+#[test]
+fn stopped_service_fails_with_dead_object() {
+    let fake = start(Script::new("ok\n"));
+    let dumpsys = fake.dumpsys().unwrap();
+    assert_eq!(dumpsys.dump(["-a"]).unwrap(), "ok\n");
+
+    drop(fake);
+    let err = dumpsys.dump(["-a"]).unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::DEAD_OBJECT));
+}